@@ -0,0 +1,41 @@
+//! Marching the rays of a frame with the uniforms converted once against
+//! converting them for every ray
+
+mod common;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use domain::object::objects::Light;
+use glam::Vec3;
+
+use common::{cloud, rays};
+
+fn bench_uniforms(c: &mut Criterion) {
+    let cloud = cloud();
+    let lights = [
+        Light::directional(Vec3::new(1.0, 1.0, 0.0)),
+        Light::point(Vec3::new(0.0, 3.0, 0.0)),
+    ];
+    let origin = Vec3::new(0.0, 0.0, 4.0);
+    let rays = rays();
+
+    let mut group = c.benchmark_group("cloud_march");
+    group.bench_function("per_ray", |b| {
+        b.iter(|| {
+            for &dir in &rays {
+                black_box(cloud.march(origin, dir, &lights));
+            }
+        })
+    });
+    group.bench_function("shared_uniforms", |b| {
+        b.iter(|| {
+            let uniforms = cloud.uniforms(&lights);
+            for &dir in &rays {
+                black_box(cloud.march_with(&uniforms, origin, dir));
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_uniforms);
+criterion_main!(benches);
@@ -0,0 +1,72 @@
+//! Scene shared by the benchmarks
+
+// Every benchmark uses a part of it
+#![allow(dead_code)]
+
+use domain::object::objects::cloud::CloudBuilder;
+use domain::object::objects::texture3d::{PerlinBuilder, WorleyBuilder};
+use domain::object::objects::Cloud;
+use egui::Color32;
+use glam::{Quat, Vec3, Vec4};
+
+/// Shape noise of the benchmarked cloud at the resolution
+pub fn worley(resolution: usize) -> WorleyBuilder {
+    WorleyBuilder::new()
+        .with_seed(0)
+        .with_num_points_a(4)
+        .with_num_points_b(8)
+        .with_num_points_c(12)
+        .with_tile(1.0)
+        .with_resolution(resolution)
+        .with_color_mask(Vec4::ONE)
+        .with_persistence(0.84)
+        .with_invert_noise(true)
+}
+
+/// Rotated cloud in the box from -1 to 1
+pub fn cloud() -> Cloud {
+    let noise = worley(32);
+    CloudBuilder::default()
+        .with_bounding_box((Vec3::splat(-1.0), Vec3::splat(1.0)))
+        .with_rotation(Quat::from_rotation_y(0.3))
+        .with_noise(noise)
+        .with_detail_noise(noise)
+        .with_weather_noise(
+            PerlinBuilder::new()
+                .with_num_points_a(1)
+                .with_num_points_b(1)
+                .with_num_points_c(5)
+                .with_tile(1.0)
+                .with_resolution(16)
+                .with_color_mask(Vec4::ONE)
+                .with_persistence(0.3)
+                .with_invert_noise(true),
+        )
+        .with_cloud_scale(100.0)
+        .with_density_multiplier(5.0)
+        .with_shape_noise_weights(Vec4::new(3.0, 6.0, 5.0, 1.0))
+        .with_detail_weights(Vec4::new(4.0, 1.5, 1.5, 3.0))
+        .with_edge_distance(0.5)
+        .with_height_map_factor(2.0)
+        .with_light_color(Color32::WHITE)
+        .with_light_absorption_through_cloud(0.6)
+        .with_light_absorption_toward_sun(0.6)
+        .with_phase_params(Vec4::new(0.0, 0.48, 0.37, 0.34))
+        .with_num_steps(32)
+        .with_num_steps_light(8)
+        .build()
+}
+
+/// Directions of a small fan of view rays towards the cloud
+pub fn rays() -> Vec<Vec3> {
+    (0..16 * 16)
+        .map(|i| {
+            Vec3::new(
+                (i % 16) as f32 / 8.0 - 1.0,
+                (i / 16) as f32 / 8.0 - 1.0,
+                -4.0,
+            )
+            .normalize()
+        })
+        .collect()
+}
@@ -0,0 +1,86 @@
+//! Core of the cloud raymarch: density samples, light marches, whole view
+//! rays and the noise the cloud samples
+
+mod common;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use domain::object::objects::texture3d::INoiseBuilder;
+use domain::object::objects::Light;
+use glam::Vec3;
+
+use common::{cloud, rays, worley};
+
+/// Points spread over the volume of the cloud
+fn points() -> Vec<Vec3> {
+    (0..8 * 8 * 8)
+        .map(|i| Vec3::new((i % 8) as f32, (i / 8 % 8) as f32, (i / 64) as f32) / 4.0 - 0.875)
+        .collect()
+}
+
+fn bench_sample_density(c: &mut Criterion) {
+    let cloud = cloud();
+    let points = points();
+    c.bench_function("sample_density", |b| {
+        b.iter(|| {
+            for &p in &points {
+                black_box(cloud.sample_density(p));
+            }
+        })
+    });
+}
+
+fn bench_light_march(c: &mut Criterion) {
+    let cloud = cloud();
+    let points = points();
+    let dir_to_light = Vec3::new(1.0, 1.0, 0.0).normalize();
+    c.bench_function("light_march", |b| {
+        b.iter(|| {
+            for &p in &points {
+                black_box(cloud.light_march(p, dir_to_light));
+            }
+        })
+    });
+}
+
+fn bench_march(c: &mut Criterion) {
+    let lights = [Light::directional(Vec3::new(1.0, 1.0, 0.0))];
+    let origin = Vec3::new(0.0, 0.0, 4.0);
+    let rays = rays();
+
+    let mut group = c.benchmark_group("march");
+    for steps in [16, 64, 256] {
+        let mut cloud = cloud();
+        cloud.num_steps = steps;
+        let uniforms = cloud.uniforms(&lights);
+        group.bench_with_input(BenchmarkId::from_parameter(steps), &steps, |b, _| {
+            b.iter(|| {
+                for &dir in &rays {
+                    black_box(cloud.march_with(&uniforms, origin, dir));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_worley(c: &mut Criterion) {
+    let mut group = c.benchmark_group("worley");
+    group.sample_size(10);
+    for resolution in [16, 32, 64, 128] {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(resolution),
+            &resolution,
+            |b, &resolution| b.iter(|| black_box(worley(resolution).build())),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_sample_density,
+    bench_light_march,
+    bench_march,
+    bench_worley
+);
+criterion_main!(benches);
@@ -0,0 +1,398 @@
+//! Animated clips encoded from rendered frames
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+
+use crate::canvas::render_target::RenderTarget;
+
+/// Sampling factor of the GIF palette quantizer, 1 is the slowest and best
+const GIF_SPEED: i32 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClipFormat {
+    Gif,
+    /// AV1 video in an MP4 container
+    #[cfg(feature = "mp4")]
+    Mp4,
+}
+
+/// Encoder taking the frames one by one as they are rendered
+pub enum ClipWriter {
+    Gif(GifEncoder<BufWriter<File>>, Delay, [usize; 2]),
+    #[cfg(feature = "mp4")]
+    Mp4(Box<mp4::Mp4Writer<BufWriter<File>>>),
+}
+
+impl ClipWriter {
+    pub fn create(
+        format: ClipFormat,
+        path: &Path,
+        fps: f32,
+        size: [usize; 2],
+    ) -> Result<Self, String> {
+        let file = File::create(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let file = BufWriter::new(file);
+        match format {
+            ClipFormat::Gif => {
+                let mut encoder = GifEncoder::new_with_speed(file, GIF_SPEED);
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(|err| err.to_string())?;
+                let delay = Delay::from_numer_denom_ms(1000, fps.round().max(1.0) as u32);
+                Ok(Self::Gif(encoder, delay, size))
+            }
+            #[cfg(feature = "mp4")]
+            ClipFormat::Mp4 => Ok(Self::Mp4(Box::new(mp4::Mp4Writer::new(file, fps, size)?))),
+        }
+    }
+
+    pub fn push(&mut self, target: &RenderTarget) -> Result<(), String> {
+        match self {
+            Self::Gif(encoder, delay, size) => {
+                if target.size() != *size {
+                    return Err("frame size mismatch".to_owned());
+                }
+                let [width, height] = size.map(|x| x as u32);
+                let image = RgbaImage::from_raw(width, height, target.to_rgba8())
+                    .ok_or("frame size mismatch")?;
+                encoder
+                    .encode_frame(Frame::from_parts(image, 0, 0, *delay))
+                    .map_err(|err| err.to_string())
+            }
+            #[cfg(feature = "mp4")]
+            Self::Mp4(writer) => writer.push(target),
+        }
+    }
+
+    /// Writes what is left, the clip is incomplete without it
+    pub fn finish(self) -> Result<(), String> {
+        match self {
+            Self::Gif(encoder, ..) => {
+                // The trailer is written when the encoder is dropped
+                drop(encoder);
+                Ok(())
+            }
+            #[cfg(feature = "mp4")]
+            Self::Mp4(writer) => writer.finish().map(|_| ()),
+        }
+    }
+}
+
+#[cfg(feature = "mp4")]
+pub mod mp4 {
+    //! AV1 encoded with rav1e, muxed into a single-track MP4 by hand
+
+    use std::io::Write;
+
+    use rav1e::color::{
+        ColorDescription, ColorPrimaries, MatrixCoefficients, TransferCharacteristics,
+    };
+    use rav1e::data::{FrameType, Rational};
+    use rav1e::prelude::{Config, Context, EncoderConfig, EncoderStatus};
+
+    use crate::canvas::render_target::RenderTarget;
+
+    /// Fastest preset of rav1e, the clips are for sharing
+    const SPEED: u8 = 10;
+    /// Ticks of the track clock in one frame
+    const FRAME_TICKS: u32 = 1000;
+    const TEMPORAL_DELIMITER: [u8; 2] = [0x12, 0x00];
+
+    pub struct Mp4Writer<W: Write> {
+        out: W,
+        context: Context<u8>,
+        size: [usize; 2],
+        timescale: u32,
+        data: Vec<u8>,
+        sample_sizes: Vec<u32>,
+        /// Numbers of the key frames, from 1
+        sync_samples: Vec<u32>,
+    }
+
+    impl<W: Write> Mp4Writer<W> {
+        pub fn new(out: W, fps: f32, size: [usize; 2]) -> Result<Self, String> {
+            let timescale = (fps * FRAME_TICKS as f32).round().max(1.0) as u32;
+            let mut encoder = EncoderConfig::with_speed_preset(SPEED);
+            encoder.width = size[0];
+            encoder.height = size[1];
+            encoder.time_base = Rational::new(FRAME_TICKS as u64, timescale as u64);
+            // Packets come out in the order of the frames, one per frame
+            encoder.low_latency = true;
+            encoder.color_description = Some(ColorDescription {
+                color_primaries: ColorPrimaries::BT709,
+                transfer_characteristics: TransferCharacteristics::BT709,
+                matrix_coefficients: MatrixCoefficients::BT709,
+            });
+            let context = Config::new()
+                .with_encoder_config(encoder)
+                .new_context()
+                .map_err(|err| err.to_string())?;
+            Ok(Self {
+                out,
+                context,
+                size,
+                timescale,
+                data: Vec::new(),
+                sample_sizes: Vec::new(),
+                sync_samples: Vec::new(),
+            })
+        }
+
+        pub fn push(&mut self, target: &RenderTarget) -> Result<(), String> {
+            if target.size() != self.size {
+                return Err("frame size mismatch".to_owned());
+            }
+            let [y, u, v] = to_yuv420(target);
+            let [width, _] = self.size;
+            let mut frame = self.context.new_frame();
+            frame.planes[0].copy_from_raw_u8(&y, width, 1);
+            frame.planes[1].copy_from_raw_u8(&u, width.div_ceil(2), 1);
+            frame.planes[2].copy_from_raw_u8(&v, width.div_ceil(2), 1);
+            self.context
+                .send_frame(frame)
+                .map_err(|err| err.to_string())?;
+            self.drain()
+        }
+
+        /// Encodes the frames still queued and writes the file
+        pub fn finish(mut self) -> Result<W, String> {
+            self.context.flush();
+            self.drain()?;
+            let ftyp = ftyp();
+            // The samples follow the header of the mdat after the moov
+            let offset = ftyp.len() + self.moov(0).len() + 8;
+            let moov = self.moov(offset as u32);
+            let mdat_len = (self.data.len() + 8) as u32;
+            let mut out = self.out;
+            [
+                &ftyp,
+                &moov,
+                &mdat_len.to_be_bytes()[..],
+                b"mdat",
+                &self.data,
+            ]
+            .iter()
+            .try_for_each(|x| out.write_all(x))
+            .and_then(|_| out.flush())
+            .map_err(|err| err.to_string())?;
+            Ok(out)
+        }
+
+        fn drain(&mut self) -> Result<(), String> {
+            loop {
+                match self.context.receive_packet() {
+                    Ok(packet) => {
+                        let data = packet
+                            .data
+                            .strip_prefix(&TEMPORAL_DELIMITER)
+                            .unwrap_or(&packet.data);
+                        self.data.extend_from_slice(data);
+                        self.sample_sizes.push(data.len() as u32);
+                        if packet.frame_type == FrameType::KEY {
+                            self.sync_samples.push(self.sample_sizes.len() as u32);
+                        }
+                    }
+                    Err(EncoderStatus::Encoded) => {}
+                    Err(EncoderStatus::NeedMoreData | EncoderStatus::LimitReached) => {
+                        return Ok(());
+                    }
+                    Err(err) => return Err(err.to_string()),
+                }
+            }
+        }
+
+        fn moov(&self, offset: u32) -> Vec<u8> {
+            let count = self.sample_sizes.len() as u32;
+            let duration = count * FRAME_TICKS;
+            let [width, height] = self.size.map(|x| x as u32);
+
+            let mut mvhd = Vec::new();
+            put(&mut mvhd, &[0, 0, self.timescale, duration, 0x0001_0000]);
+            mvhd.extend_from_slice(&[0x01, 0x00, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            put(&mut mvhd, &MATRIX);
+            put(&mut mvhd, &[0, 0, 0, 0, 0, 0, 2]);
+
+            let mut tkhd = Vec::new();
+            put(&mut tkhd, &[0, 0, 1, 0, duration, 0, 0, 0, 0]);
+            put(&mut tkhd, &MATRIX);
+            put(&mut tkhd, &[width << 16, height << 16]);
+
+            let mut mdhd = Vec::new();
+            put(&mut mdhd, &[0, 0, self.timescale, duration, 0x55c4_0000]);
+
+            let mut hdlr = Vec::new();
+            put(&mut hdlr, &[0, u32::from_be_bytes(*b"vide"), 0, 0, 0]);
+            hdlr.extend_from_slice(b"VideoHandler\0");
+
+            let dref = full(
+                b"dref",
+                0,
+                &[&1u32.to_be_bytes()[..], &full(b"url ", 1, &[])].concat(),
+            );
+
+            let mut av01 = vec![0; 6];
+            av01.extend_from_slice(&[0, 1]);
+            av01.extend_from_slice(&[0; 16]);
+            av01.extend_from_slice(&(width as u16).to_be_bytes());
+            av01.extend_from_slice(&(height as u16).to_be_bytes());
+            put(&mut av01, &[0x0048_0000, 0x0048_0000, 0]);
+            av01.extend_from_slice(&1u16.to_be_bytes());
+            av01.extend_from_slice(&[0; 32]);
+            av01.extend_from_slice(&[0x00, 0x18, 0xff, 0xff]);
+            av01.extend(boxed(b"av1C", &self.context.container_sequence_header()));
+            let stsd = [&1u32.to_be_bytes()[..], &boxed(b"av01", &av01)].concat();
+
+            let mut stss = vec![self.sync_samples.len() as u32];
+            stss.extend(&self.sync_samples);
+            let mut stsz = vec![0, count];
+            stsz.extend(&self.sample_sizes);
+
+            let stbl = [
+                full(b"stsd", 0, &stsd),
+                full(b"stts", 0, &words(&[1, count, FRAME_TICKS])),
+                full(b"stss", 0, &words(&stss)),
+                full(b"stsc", 0, &words(&[1, 1, count, 1])),
+                full(b"stsz", 0, &words(&stsz)),
+                full(b"stco", 0, &words(&[1, offset])),
+            ]
+            .concat();
+            let minf = [
+                full(b"vmhd", 1, &[0; 8]),
+                boxed(b"dinf", &dref),
+                boxed(b"stbl", &stbl),
+            ]
+            .concat();
+            let mdia = [
+                full(b"mdhd", 0, &mdhd),
+                full(b"hdlr", 0, &hdlr),
+                boxed(b"minf", &minf),
+            ]
+            .concat();
+            let trak = [full(b"tkhd", 3, &tkhd), boxed(b"mdia", &mdia)].concat();
+            let moov = [full(b"mvhd", 0, &mvhd), boxed(b"trak", &trak)].concat();
+            boxed(b"moov", &moov)
+        }
+    }
+
+    /// Unity transform of the movie and the track headers
+    const MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+    fn ftyp() -> Vec<u8> {
+        boxed(b"ftyp", b"isom\0\0\x02\0isomiso2av01mp41")
+    }
+
+    fn boxed(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut result = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        result.extend_from_slice(kind);
+        result.extend_from_slice(body);
+        result
+    }
+
+    fn full(kind: &[u8; 4], flags: u32, body: &[u8]) -> Vec<u8> {
+        boxed(kind, &[&flags.to_be_bytes()[..], body].concat())
+    }
+
+    fn put(out: &mut Vec<u8>, values: &[u32]) {
+        out.extend(values.iter().flat_map(|x| x.to_be_bytes()));
+    }
+
+    fn words(values: &[u32]) -> Vec<u8> {
+        let mut result = Vec::new();
+        put(&mut result, values);
+        result
+    }
+
+    /// Limited range BT.709 planes, the chroma averaged over 2x2 blocks
+    fn to_yuv420(target: &RenderTarget) -> [Vec<u8>; 3] {
+        let [width, height] = target.size();
+        let pixels = &target.image().pixels;
+        let luma = |[r, g, b]: [f32; 3]| 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let rgb = |x: usize, y: usize| {
+            let pixel = pixels[y.min(height - 1) * width + x.min(width - 1)];
+            [pixel.r(), pixel.g(), pixel.b()].map(|x| x as f32 / 255.0)
+        };
+
+        let mut y_plane = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                y_plane.push((16.0 + 219.0 * luma(rgb(x, y))).round() as u8);
+            }
+        }
+        let [chroma_width, chroma_height] = [width.div_ceil(2), height.div_ceil(2)];
+        let mut u_plane = Vec::with_capacity(chroma_width * chroma_height);
+        let mut v_plane = Vec::with_capacity(chroma_width * chroma_height);
+        for y in 0..chroma_height {
+            for x in 0..chroma_width {
+                let mut sum = [0.0; 3];
+                for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                    let color = rgb(2 * x + dx, 2 * y + dy);
+                    sum = [0, 1, 2].map(|i| sum[i] + color[i] / 4.0);
+                }
+                let l = luma(sum);
+                u_plane.push((128.0 + 224.0 * (sum[2] - l) / 1.8556).round() as u8);
+                v_plane.push((128.0 + 224.0 * (sum[0] - l) / 1.5748).round() as u8);
+            }
+        }
+        [y_plane, u_plane, v_plane]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::Color32;
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+
+    fn frames([width, height]: [usize; 2]) -> Vec<RenderTarget> {
+        [Color32::RED, Color32::GREEN, Color32::BLUE]
+            .map(|color| {
+                let mut target = RenderTarget::new(width, height);
+                target.fill(color);
+                target
+            })
+            .into()
+    }
+
+    #[test]
+    fn test_gif() {
+        let path = std::env::temp_dir().join("clip_test.gif");
+        let mut writer = ClipWriter::create(ClipFormat::Gif, &path, 10.0, [16, 12]).unwrap();
+        for frame in frames([16, 12]) {
+            writer.push(&frame).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let file = std::io::BufReader::new(File::open(&path).unwrap());
+        let decoded = GifDecoder::new(file).unwrap().into_frames();
+        let decoded = decoded.collect_frames().unwrap();
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[0].delay().numer_denom_ms(), (100, 1));
+        assert_eq!(decoded[2].buffer().get_pixel(3, 4).0, [0, 0, 255, 255]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(feature = "mp4")]
+    #[test]
+    fn test_mp4() {
+        let mut writer = mp4::Mp4Writer::new(Vec::new(), 10.0, [33, 17]).unwrap();
+        for frame in frames([33, 17]) {
+            writer.push(&frame).unwrap();
+        }
+        let file = writer.finish().unwrap();
+
+        assert_eq!(&file[4..8], b"ftyp");
+        let find = |kind: &[u8]| file.windows(4).position(|x| x == kind).unwrap();
+        let stsz = find(b"stsz");
+        let count = u32::from_be_bytes(file[stsz + 12..stsz + 16].try_into().unwrap());
+        assert_eq!(count, 3);
+        let stco = find(b"stco");
+        let offset = u32::from_be_bytes(file[stco + 12..stco + 16].try_into().unwrap());
+        assert_eq!(offset as usize, find(b"mdat") + 4);
+        assert!(find(b"av1C") < stsz);
+    }
+}
@@ -0,0 +1,212 @@
+//! Animation rendered frame by frame away from the UI
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use crate::canvas::clip::{ClipFormat, ClipWriter};
+use crate::canvas::render_target::RenderTarget;
+use crate::facade::command::animate;
+use crate::managers::animation_manager::AnimationManager;
+use crate::managers::draw_manager::render_offscreen;
+use crate::managers::job_manager::JobContext;
+use crate::object::camera::Camera;
+use crate::object::objects::Cloud;
+use crate::scene::scene::Scene;
+use crate::visitor::update_visitor::UpdateVisitor;
+use crate::visitor::{VisitableMut, VisitorMut};
+
+/// Time range rendered into frames
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SequenceSettings {
+    /// Times of the first and the last frame on the animation timeline
+    pub start: f32,
+    pub end: f32,
+    pub fps: f32,
+    pub size: [usize; 2],
+    /// Multiplier of the march steps of the clouds, 1 keeps them as they are
+    pub quality: f32,
+}
+
+impl Default for SequenceSettings {
+    fn default() -> Self {
+        Self {
+            start: 0.0,
+            end: 1.0,
+            fps: 24.0,
+            size: [640, 360],
+            quality: 1.0,
+        }
+    }
+}
+
+impl SequenceSettings {
+    /// Frames from the start to the end, both included
+    pub fn frame_count(&self) -> usize {
+        if self.fps <= 0.0 || self.end < self.start {
+            return 0;
+        }
+        ((self.end - self.start) * self.fps).floor() as usize + 1
+    }
+}
+
+/// Frames a [`FrameSequence`] rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rendered {
+    /// Every frame of the sequence
+    Finished(usize),
+    /// The frames done before the job was cancelled
+    Cancelled(usize),
+}
+
+impl Rendered {
+    pub fn frames(self) -> usize {
+        match self {
+            Rendered::Finished(frames) | Rendered::Cancelled(frames) => frames,
+        }
+    }
+}
+
+/// Copy of the scene stepped at a fixed frame rate. The animation tracks
+/// are sampled at the time of every frame, the wind and the sun cycle move
+/// on from the scene as it was by the frame time.
+pub struct FrameSequence {
+    scene: Scene,
+    camera: Camera,
+    animation: AnimationManager,
+    hidden_layers: BTreeSet<&'static str>,
+    settings: SequenceSettings,
+}
+
+impl FrameSequence {
+    pub fn new(
+        mut scene: Scene,
+        camera: Camera,
+        animation: AnimationManager,
+        hidden_layers: BTreeSet<&'static str>,
+        settings: SequenceSettings,
+    ) -> Self {
+        scene.accept_mut(&mut StepsVisitor(settings.quality));
+        Self {
+            scene,
+            camera,
+            animation,
+            hidden_layers,
+            settings,
+        }
+    }
+
+    /// Renders the frames in order and hands them to `write`. Stops early
+    /// once the job is cancelled.
+    pub fn render(
+        mut self,
+        context: &JobContext,
+        mut write: impl FnMut(usize, RenderTarget) -> Result<(), String>,
+    ) -> Result<Rendered, String> {
+        let count = self.settings.frame_count();
+        let dt = 1.0 / self.settings.fps;
+        for frame in 0..count {
+            if context.is_cancelled() {
+                return Ok(Rendered::Cancelled(frame));
+            }
+            if frame > 0 {
+                self.scene.accept_mut(&mut UpdateVisitor::new(dt));
+            }
+            self.animation.seek(self.settings.start + frame as f32 * dt);
+            let values = self.animation.values();
+            animate(&mut self.camera, &mut self.scene, &values);
+
+            let target = render_offscreen(
+                &self.scene,
+                &self.camera,
+                self.settings.size,
+                self.hidden_layers.clone(),
+            );
+            write(frame, target)?;
+            context.set_progress((frame + 1) as f32 / count as f32);
+        }
+        Ok(Rendered::Finished(count))
+    }
+
+    /// Renders the frames into numbered PNG files in the directory
+    pub fn write_png(self, context: &JobContext, dir: &Path) -> Result<Rendered, String> {
+        std::fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+        self.render(context, |frame, target| {
+            let path = dir.join(format!("frame_{frame:05}.png"));
+            target
+                .save_png(&path)
+                .map_err(|err| format!("{}: {err}", path.display()))
+        })
+    }
+
+    /// Renders the frames into an animated clip in the file, a cancelled
+    /// one ends at the last frame done
+    pub fn write_clip(
+        self,
+        context: &JobContext,
+        format: ClipFormat,
+        path: &Path,
+    ) -> Result<Rendered, String> {
+        let settings = self.settings;
+        let mut writer = ClipWriter::create(format, path, settings.fps, settings.size)?;
+        let frames = self.render(context, |_, target| writer.push(&target))?;
+        writer.finish()?;
+        Ok(frames)
+    }
+}
+
+/// Scales the march steps of every cloud by the quality
+struct StepsVisitor(f32);
+
+impl VisitorMut for StepsVisitor {
+    fn visit_cloud_mut(&mut self, cloud: &mut Cloud) {
+        let steps = |x: usize| ((x as f32 * self.0).ceil() as usize).max(1);
+        cloud.num_steps = steps(cloud.num_steps);
+        cloud.num_steps_light = steps(cloud.num_steps_light);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::animation_manager::{Property, Track};
+    use crate::object::objects::{Background, Sun};
+
+    #[test]
+    fn test_frame_sequence() {
+        let settings = SequenceSettings {
+            start: 0.0,
+            end: 1.0,
+            fps: 4.0,
+            size: [8, 6],
+            quality: 0.5,
+        };
+        assert_eq!(settings.frame_count(), 5);
+
+        let mut scene = Scene::default();
+        scene.add_object("background", Background::default());
+        scene.add_object("sun", Sun::new(10.0, 170.0, 0.0));
+        let mut animation = AnimationManager::default();
+        let track = Track::new("sun", Property::SunIntensity)
+            .with_key(0.0, 0.0)
+            .with_key(1.0, 2.0);
+        animation.add_track("sun", track);
+        let sequence = FrameSequence::new(
+            scene,
+            Camera::default(),
+            animation,
+            BTreeSet::new(),
+            settings,
+        );
+
+        let context = JobContext::default();
+        let mut frames = Vec::new();
+        let done = sequence.render(&context, |frame, target| {
+            assert_eq!(target.size(), [8, 6]);
+            frames.push(frame);
+            Ok(())
+        });
+        assert_eq!(done, Ok(Rendered::Finished(5)));
+        assert_eq!(frames, [0, 1, 2, 3, 4]);
+        assert_eq!(context.progress(), 1.0);
+    }
+}
@@ -0,0 +1,140 @@
+//! Device shared by the compute shaders
+
+use std::sync::{mpsc, Mutex, OnceLock};
+
+use log::{info, warn};
+use wgpu::util::DeviceExt;
+
+/// Device and queue of the first adapter, see [`Gpu::shared`]
+#[derive(Debug)]
+pub struct Gpu {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    /// The error scopes belong to the device, so the submissions of several
+    /// threads take turns
+    lock: Mutex<()>,
+}
+
+impl Gpu {
+    /// Device of the first adapter, `None` when there is no adapter
+    pub fn new() -> Option<Self> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor::default());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                label: Some("compute"),
+                required_features: wgpu::Features::empty(),
+                required_limits:
+                    wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+                memory_hints: wgpu::MemoryHints::Performance,
+            },
+            None,
+        ))
+        .ok()?;
+        info!("Compute shaders run on {}", adapter.get_info().name);
+        Some(Self {
+            device,
+            queue,
+            lock: Mutex::new(()),
+        })
+    }
+
+    /// Device shared by the whole program, created on the first call
+    pub fn shared() -> Option<&'static Self> {
+        static SHARED: OnceLock<Option<Gpu>> = OnceLock::new();
+        SHARED
+            .get_or_init(|| {
+                let gpu = Self::new();
+                if gpu.is_none() {
+                    warn!("No GPU adapter, everything runs on the CPU");
+                }
+                gpu
+            })
+            .as_ref()
+    }
+
+    pub fn buffer(&self, label: &str, contents: &[u8], usage: wgpu::BufferUsages) -> wgpu::Buffer {
+        self.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some(label),
+                contents,
+                usage,
+            })
+    }
+
+    /// Compute pipeline of the entry point of the shader
+    pub fn pipeline(
+        &self,
+        module: &wgpu::ShaderModule,
+        layout: Option<&wgpu::PipelineLayout>,
+        entry_point: &str,
+    ) -> wgpu::ComputePipeline {
+        self.device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout,
+                module,
+                entry_point,
+                compilation_options: Default::default(),
+                cache: None,
+            })
+    }
+
+    /// Records the commands and submits them, `None` when the device
+    /// rejects the resources or commands made meanwhile
+    pub fn submit<T>(&self, record: impl FnOnce(&mut wgpu::CommandEncoder) -> T) -> Option<T> {
+        let _lock = self.lock.lock().unwrap();
+        self.device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor::default());
+        let output = record(&mut encoder);
+        self.queue.submit([encoder.finish()]);
+
+        let validation = pollster::block_on(self.device.pop_error_scope());
+        let out_of_memory = pollster::block_on(self.device.pop_error_scope());
+        if let Some(error) = validation.or(out_of_memory) {
+            warn!("Compute shader failed: {error}");
+            return None;
+        }
+        Some(output)
+    }
+
+    /// Buffer of the given size to copy results into and read them back
+    pub fn readback_buffer(&self, size: u64) -> wgpu::Buffer {
+        self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Waits for the submitted work and reads the buffer as 32-bit words
+    pub fn read_words(&self, buffer: &wgpu::Buffer) -> Option<Vec<u32>> {
+        let slice = buffer.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver.recv().ok()?.ok()?;
+        let words = slice
+            .get_mapped_range()
+            .chunks_exact(4)
+            .map(|x| u32::from_le_bytes([x[0], x[1], x[2], x[3]]))
+            .collect();
+        buffer.unmap();
+        Some(words)
+    }
+}
+
+/// Little endian bytes of the words, for the uniforms and storage buffers
+pub fn words_to_bytes(words: &[u32]) -> Vec<u8> {
+    words.iter().flat_map(|x| x.to_le_bytes()).collect()
+}
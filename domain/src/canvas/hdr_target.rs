@@ -0,0 +1,116 @@
+use std::path::Path;
+
+use glam::Vec3;
+
+/// Linear light of the clouds per pixel, kept unclamped for offline tone
+/// mapping
+#[derive(Clone, Debug, PartialEq)]
+pub struct HdrTarget {
+    size: [usize; 2],
+    radiance: Vec<Vec3>,
+    transmittance: Vec<f32>,
+}
+
+impl HdrTarget {
+    /// Buffer with nothing in front of the background
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            size: [width, height],
+            radiance: vec![Vec3::ZERO; width * height],
+            transmittance: vec![1.0; width * height],
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> [usize; 2] {
+        self.size
+    }
+
+    /// Light reaching the eye, row by row
+    pub fn radiance(&self) -> &[Vec3] {
+        &self.radiance
+    }
+
+    /// Share of the background seen through the clouds, row by row
+    pub fn transmittance(&self) -> &[f32] {
+        &self.transmittance
+    }
+
+    /// Puts a layer behind what is already in the buffer, the layer gives the
+    /// light and the transmittance of every pixel
+    pub fn add_behind(&mut self, layer: impl Fn(usize) -> (Vec3, f32) + Sync) {
+        use rayon::prelude::*;
+
+        self.radiance
+            .par_iter_mut()
+            .zip(&mut self.transmittance)
+            .enumerate()
+            .for_each(|(idx, (radiance, transmittance))| {
+                if *transmittance <= 0.0 {
+                    return;
+                }
+                let (light, through) = layer(idx);
+                *radiance += *transmittance * light;
+                *transmittance *= through;
+            });
+    }
+
+    /// Writes the buffer as an OpenEXR file with the radiance in the R, G and
+    /// B channels and the transmittance in T
+    pub fn save_exr(&self, path: impl AsRef<Path>) -> exr::error::UnitResult {
+        use exr::prelude::*;
+
+        let [width, _] = self.size;
+        let pixels = SpecificChannels::build()
+            .with_channel("R")
+            .with_channel("G")
+            .with_channel("B")
+            .with_channel("T")
+            .with_pixel_fn(|position: Vec2<usize>| {
+                let idx = position.y() * width + position.x();
+                let [r, g, b] = self.radiance[idx].to_array();
+                (r, g, b, self.transmittance[idx])
+            });
+        Image::from_channels((self.size[0], self.size[1]), pixels)
+            .write()
+            .to_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_exr() {
+        use exr::prelude::*;
+
+        let mut target = HdrTarget::new(4, 3);
+        target.add_behind(|idx| (Vec3::splat(idx as f32), 0.5));
+        target.add_behind(|_| (Vec3::new(8.0, 0.0, 0.0), 0.0));
+        assert_eq!(target.radiance()[2], Vec3::new(6.0, 2.0, 2.0));
+        assert_eq!(target.transmittance()[2], 0.0);
+
+        let path = std::env::temp_dir().join("hdr_target_test.exr");
+        target.save_exr(&path).unwrap();
+        let image = read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .specific_channels()
+            .required("R")
+            .required("T")
+            .collect_pixels(
+                |resolution, _| vec![(0.0, 0.0); resolution.width() * resolution.height()],
+                |pixels: &mut Vec<(f32, f32)>, position, (r, t): (f32, f32)| {
+                    pixels[position.y() * 4 + position.x()] = (r, t);
+                },
+            )
+            .first_valid_layer()
+            .all_attributes()
+            .from_file(&path)
+            .unwrap();
+        let pixels = image.layer_data.channel_data.pixels;
+        assert_eq!(pixels[11], (15.0, 0.0));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,4 +1,11 @@
+pub mod clip;
+pub mod frame_sequence;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+pub mod hdr_target;
 pub mod painter;
+pub mod render_target;
+pub mod render_thread;
 
 use egui::Stroke;
 use glam::Vec3;
@@ -0,0 +1,66 @@
+//! Line batching for `Painter3D`
+
+use egui::{Mesh, Stroke};
+use glam::Vec3;
+
+use super::painter::{feathered_segment, solid_segment};
+use super::{LineStyle, Painter3D};
+use crate::math::Transform;
+
+/// Accumulates line segments into one mesh, so egui tessellates a single
+/// shape instead of one per segment.
+///
+/// The mesh is submitted on [`LineBatch::submit`] or when the batch is dropped.
+pub struct LineBatch<'a> {
+    painter: &'a Painter3D,
+    mesh: Mesh,
+}
+
+impl<'a> LineBatch<'a> {
+    pub fn new(painter: &'a Painter3D) -> Self {
+        Self {
+            painter,
+            mesh: Mesh::default(),
+        }
+    }
+
+    pub fn line(&mut self, a: Vec3, b: Vec3, stroke: Stroke, mvp: Transform) {
+        self.styled_line(a, b, stroke, LineStyle::default(), mvp);
+    }
+
+    /// Adds a line following the given style, see [`Painter3D::styled_line`]
+    pub fn styled_line(
+        &mut self,
+        a: Vec3,
+        b: Vec3,
+        stroke: Stroke,
+        style: LineStyle,
+        mvp: Transform,
+    ) {
+        let mesh = &mut self.mesh;
+        self.painter
+            .for_each_styled_segment(a, b, stroke, style, mvp, |segment, stroke| {
+                if style.anti_alias {
+                    mesh.append(feathered_segment(segment, stroke));
+                } else {
+                    mesh.append(solid_segment(segment, stroke));
+                }
+            });
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mesh.is_empty()
+    }
+
+    pub fn submit(self) {
+        drop(self)
+    }
+}
+
+impl Drop for LineBatch<'_> {
+    fn drop(&mut self) {
+        if !self.mesh.is_empty() {
+            self.painter.egui().add(std::mem::take(&mut self.mesh));
+        }
+    }
+}
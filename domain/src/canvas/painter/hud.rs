@@ -0,0 +1,95 @@
+//! Screen-space overlay on top of the 3D canvas
+
+use egui::{Align2, Color32, FontId, LayerId, Order, Pos2, Rect, Rounding, Stroke, Vec2};
+
+/// 2D overlay for HUD elements such as frame time or parameter readouts.
+///
+/// Shapes go to a separate egui layer, so they end up above all 3D content
+/// no matter when they are added. Positions are in points relative to the
+/// top left corner of the canvas and are not affected by the camera.
+#[derive(Clone)]
+pub struct Hud {
+    painter: egui::Painter,
+    rect: Rect,
+}
+
+impl Hud {
+    pub fn new(painter: &egui::Painter, rect: Rect) -> Self {
+        let layer_id = LayerId::new(Order::Middle, painter.layer_id().id.with("hud"));
+        Self {
+            painter: painter.clone().with_layer_id(layer_id),
+            rect,
+        }
+    }
+
+    /// Canvas rect in screen coordinates
+    pub fn rect(&self) -> Rect {
+        self.rect
+    }
+
+    fn to_screen(&self, pos: Pos2) -> Pos2 {
+        self.rect.min + pos.to_vec2()
+    }
+
+    pub fn text(
+        &self,
+        pos: Pos2,
+        anchor: Align2,
+        text: impl ToString,
+        font_id: FontId,
+        text_color: Color32,
+    ) -> Rect {
+        self.painter
+            .text(self.to_screen(pos), anchor, text, font_id, text_color)
+    }
+
+    /// Draws lines of text stacked under each other, starting at `pos`
+    pub fn readout<'a>(
+        &self,
+        pos: Pos2,
+        lines: impl IntoIterator<Item = &'a str>,
+        font_id: FontId,
+        text_color: Color32,
+    ) {
+        let mut pos = pos;
+        for line in lines {
+            let rect = self.text(pos, Align2::LEFT_TOP, line, font_id.clone(), text_color);
+            pos.y += rect.height();
+        }
+    }
+
+    pub fn rect_filled(&self, rect: Rect, rounding: impl Into<Rounding>, fill_color: Color32) {
+        let rect = rect.translate(self.rect.min.to_vec2());
+        self.painter.rect_filled(rect, rounding, fill_color);
+    }
+
+    pub fn rect_stroke(&self, rect: Rect, rounding: impl Into<Rounding>, stroke: Stroke) {
+        let rect = rect.translate(self.rect.min.to_vec2());
+        self.painter.rect_stroke(rect, rounding, stroke);
+    }
+
+    pub fn line(&self, a: Pos2, b: Pos2, stroke: Stroke) {
+        self.painter
+            .line_segment([self.to_screen(a), self.to_screen(b)], stroke);
+    }
+
+    pub fn circle_filled(&self, center: Pos2, radius: f32, fill_color: Color32) {
+        self.painter
+            .circle_filled(self.to_screen(center), radius, fill_color);
+    }
+
+    pub fn circle_stroke(&self, center: Pos2, radius: f32, stroke: Stroke) {
+        self.painter
+            .circle_stroke(self.to_screen(center), radius, stroke);
+    }
+
+    /// Draws a cross in the middle of the canvas
+    pub fn crosshair(&self, size: f32, stroke: Stroke) {
+        let center = self.rect.center();
+        let (dx, dy) = (Vec2::new(0.5 * size, 0.0), Vec2::new(0.0, 0.5 * size));
+        self.painter
+            .line_segment([center - dx, center + dx], stroke);
+        self.painter
+            .line_segment([center - dy, center + dy], stroke);
+    }
+}
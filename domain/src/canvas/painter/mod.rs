@@ -1,4 +1,9 @@
+pub mod batch;
+pub mod hud;
 #[allow(clippy::module_inception)]
 pub mod painter;
+mod texture_cache;
 
-pub use painter::Painter3D;
+pub use batch::LineBatch;
+pub use hud::Hud;
+pub use painter::{LineStyle, LineThickness, Occlusion, Painter3D};
@@ -5,15 +5,93 @@ use egui::{Color32, Shape, Stroke, TextureId};
 use std::ops::Deref;
 // glam's types are part of our interface
 // TODO: use mint? But then we'd have to convert every time ...
+use super::batch::LineBatch;
+use super::hud::Hud;
+use super::texture_cache::TextureCache;
 use crate::math::transform::Transform;
+use crate::math::Curve;
+use crate::object::objects::BoundingBox;
 pub use glam;
 pub use glam::Vec3;
 
+/// How the width of a 3D line stroke is interpreted
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LineThickness {
+    /// Stroke width is given in screen points and does not depend on depth
+    #[default]
+    Screen,
+    /// Stroke width is given in screen points at the given view distance
+    /// and shrinks proportionally farther away
+    World(f32),
+}
+
+impl LineThickness {
+    /// Returns the stroke to use for a point at the given clip-space `w`
+    pub fn scale(&self, stroke: Stroke, w: f32) -> Stroke {
+        match *self {
+            LineThickness::Screen => stroke,
+            LineThickness::World(reference) => {
+                Stroke::new(stroke.width * reference / w.max(f32::EPSILON), stroke.color)
+            }
+        }
+    }
+}
+
+/// Per-stroke rasterization options of 3D lines
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LineStyle {
+    pub thickness: LineThickness,
+    /// Draw the line as a mesh with a one point wide feathered edge
+    pub anti_alias: bool,
+}
+
+impl LineStyle {
+    pub fn with_thickness(mut self, thickness: LineThickness) -> Self {
+        self.thickness = thickness;
+        self
+    }
+
+    pub fn with_anti_alias(mut self, anti_alias: bool) -> Self {
+        self.anti_alias = anti_alias;
+        self
+    }
+}
+
+/// Occlusion query used by depth-aware drawing.
+///
+/// A point is hidden when the ray from the eye to it enters one of the
+/// occluder volumes before reaching the point.
+#[derive(Clone, Copy, Debug)]
+pub struct Occlusion<'a> {
+    pub eye: Vec3,
+    pub occluders: &'a [BoundingBox],
+}
+
+impl<'a> Occlusion<'a> {
+    pub fn new(eye: Vec3, occluders: &'a [BoundingBox]) -> Self {
+        Self { eye, occluders }
+    }
+
+    pub fn hides(&self, pt: Vec3) -> bool {
+        let to_point = pt - self.eye;
+        let distance = to_point.length();
+        if distance <= f32::EPSILON {
+            return false;
+        }
+        let dir = to_point / distance;
+        self.occluders.iter().any(|bb| {
+            let (dst_to_box, dst_inside_box) = bb.dst(self.eye, dir).into();
+            dst_inside_box > 0.0 && dst_to_box < distance
+        })
+    }
+}
+
 #[derive(Clone)]
 pub struct Painter3D {
     painter_2d: egui::Painter,
     resp_rect: egui::Rect,
     to_screen: egui::emath::RectTransform,
+    hud: Hud,
     pub color: Color32,
 }
 
@@ -26,6 +104,7 @@ impl Deref for Painter3D {
 
 impl Painter3D {
     pub fn new(painter_2d: egui::Painter, resp_rect: egui::Rect, color: Color32) -> Self {
+        let hud = Hud::new(&painter_2d, resp_rect);
         Self {
             painter_2d,
             resp_rect,
@@ -33,6 +112,7 @@ impl Painter3D {
                 egui::Rect::from_min_size(egui::Pos2::ZERO, resp_rect.size()),
                 resp_rect.translate(egui::Pos2::new(-15.0, -15.0).to_vec2()),
             ),
+            hud,
             color,
         }
     }
@@ -54,6 +134,27 @@ impl Painter3D {
             .map(|pos| self.painter_2d.text(pos, anchor, text, font_id, text_color))
     }
 
+    /// Same as [`Self::text`], but nothing is drawn when the anchor is outside
+    /// the viewport or hidden behind one of the occluders
+    #[allow(clippy::too_many_arguments)]
+    pub fn text_occluded(
+        &self,
+        pos: Vec3,
+        anchor: egui::Align2,
+        text: impl ToString,
+        font_id: egui::FontId,
+        text_color: Color32,
+        occlusion: Occlusion,
+        mvp: Transform,
+    ) -> Option<egui::Rect> {
+        if occlusion.hides(pos) {
+            return None;
+        }
+        self.transform(pos, mvp)
+            .filter(|pos| self.resp_rect.contains(*pos))
+            .map(|pos| self.painter_2d.text(pos, anchor, text, font_id, text_color))
+    }
+
     /// Transform a point in world coordinates to egui coordinates
     pub fn transform(&self, pt: Vec3, mvp: Transform) -> Option<egui::Pos2> {
         let (sc, z) = mvp.world_to_egui(pt);
@@ -61,6 +162,32 @@ impl Painter3D {
         (0.0..=1.0).contains(&z).then(|| sc.to_pos2())
     }
 
+    /// Starts collecting lines that are submitted to egui as a single mesh
+    pub fn batch(&self) -> LineBatch<'_> {
+        LineBatch::new(self)
+    }
+
+    /// Screen-space overlay drawn above the 3D content
+    pub fn hud(&self) -> &Hud {
+        &self.hud
+    }
+
+    /// Uploads an image into the named texture, reusing the texture kept
+    /// from previous frames instead of allocating a new one
+    pub fn load_texture(
+        &self,
+        name: &str,
+        image: impl Into<egui::ImageData>,
+        options: egui::TextureOptions,
+    ) -> TextureId {
+        TextureCache::load(self.ctx(), name, image, options)
+    }
+
+    /// Releases the named texture loaded with [`Self::load_texture`]
+    pub fn forget_texture(&self, name: &str) {
+        TextureCache::forget(self.ctx(), name)
+    }
+
     /// Get egui's 2D painter
     pub fn egui(&self) -> &egui::Painter {
         &self.painter_2d
@@ -87,6 +214,185 @@ impl Painter3D {
         self.painter_2d.line_segment([a, b], stroke);
     }
 
+    /// Draws a line following the given style.
+    ///
+    /// With world thickness the segment is split so that both ends of a long
+    /// line get a width matching their own depth.
+    pub fn styled_line(&self, a: Vec3, b: Vec3, stroke: Stroke, style: LineStyle, mvp: Transform) {
+        self.for_each_styled_segment(a, b, stroke, style, mvp, |segment, stroke| {
+            if style.anti_alias {
+                self.painter_2d.add(feathered_segment(segment, stroke));
+            } else {
+                self.painter_2d.line_segment(segment, stroke);
+            }
+        });
+    }
+
+    /// Projects a line drawn with the given style into screen segments along
+    /// with the stroke each of them should get
+    pub(crate) fn for_each_styled_segment(
+        &self,
+        a: Vec3,
+        b: Vec3,
+        stroke: Stroke,
+        style: LineStyle,
+        mvp: Transform,
+        mut f: impl FnMut([egui::Pos2; 2], Stroke),
+    ) {
+        if style.thickness == LineThickness::Screen {
+            if let Some(segment) = self.project_segment(a, b, mvp) {
+                f(segment, stroke);
+            }
+            return;
+        }
+
+        let (wa, wb) = (mvp.clip_w(a), mvp.clip_w(b));
+        if wa <= 0.0 || wb <= 0.0 {
+            return;
+        }
+        let segments = ((wa / wb).max(wb / wa).ceil() as usize * 2).clamp(1, 16);
+        for i in 0..segments {
+            let t0 = i as f32 / segments as f32;
+            let t1 = (i + 1) as f32 / segments as f32;
+            let (p0, p1) = (a.lerp(b, t0), a.lerp(b, t1));
+            let w = mvp.clip_w(0.5 * (p0 + p1));
+            if let Some(segment) = self.project_segment(p0, p1, mvp) {
+                f(segment, style.thickness.scale(stroke, w));
+            }
+        }
+    }
+
+    /// Returns the screen positions of both ends if they are both visible
+    pub(crate) fn project_segment(
+        &self,
+        a: Vec3,
+        b: Vec3,
+        mvp: Transform,
+    ) -> Option<[egui::Pos2; 2]> {
+        let a = self.transform(a, mvp)?;
+        let b = self.transform(b, mvp)?;
+        Some([
+            self.to_screen.transform_pos(a),
+            self.to_screen.transform_pos(b),
+        ])
+    }
+
+    /// Draws connected line segments through the given points.
+    ///
+    /// Points outside the clip volume break the line into separate pieces.
+    pub fn polyline(&self, points: &[Vec3], stroke: Stroke, mvp: Transform) {
+        let mut run = Vec::with_capacity(points.len());
+        for &pt in points {
+            match self.transform(pt, mvp) {
+                Some(pos) => run.push(self.to_screen.transform_pos(pos)),
+                None => {
+                    if run.len() > 1 {
+                        self.painter_2d
+                            .add(Shape::line(std::mem::take(&mut run), stroke));
+                    }
+                    run.clear();
+                }
+            }
+        }
+        if run.len() > 1 {
+            self.painter_2d.add(Shape::line(run, stroke));
+        }
+    }
+
+    /// Draws a curve as a polyline subdivided until every piece deviates from
+    /// the projected curve by less than `tolerance` screen points
+    pub fn curve(&self, curve: &Curve, tolerance: f32, stroke: Stroke, mvp: Transform) {
+        let pieces = curve.pieces();
+        if pieces == 0 {
+            return;
+        }
+
+        let mut points = vec![curve.eval(0.0)];
+        for i in 0..pieces {
+            let (t0, t1) = (i as f32 / pieces as f32, (i + 1) as f32 / pieces as f32);
+            self.subdivide(curve, t0, t1, tolerance.max(0.01), 0, mvp, &mut points);
+        }
+        self.polyline(&points, stroke, mvp);
+    }
+
+    /// Pushes the points of `curve` in `(t0, t1]`, splitting the interval
+    /// while its projected midpoint is too far from the chord
+    #[allow(clippy::too_many_arguments)]
+    fn subdivide(
+        &self,
+        curve: &Curve,
+        t0: f32,
+        t1: f32,
+        tolerance: f32,
+        depth: usize,
+        mvp: Transform,
+        points: &mut Vec<Vec3>,
+    ) {
+        // A cubic piece may cross its chord at the middle, so always split twice
+        const MIN_DEPTH: usize = 2;
+        const MAX_DEPTH: usize = 10;
+
+        let tm = 0.5 * (t0 + t1);
+        let (a, m, b) = (curve.eval(t0), curve.eval(tm), curve.eval(t1));
+        let flat = match (
+            self.transform(a, mvp),
+            self.transform(m, mvp),
+            self.transform(b, mvp),
+        ) {
+            (Some(a), Some(m), Some(b)) => {
+                let chord = b - a;
+                let length = chord.length();
+                let deviation = if length <= f32::EPSILON {
+                    m.distance(a)
+                } else {
+                    (chord.x * (m - a).y - chord.y * (m - a).x).abs() / length
+                };
+                deviation <= tolerance
+            }
+            // Nothing to refine against when a part of the piece is clipped
+            _ => true,
+        };
+
+        if depth >= MAX_DEPTH || (depth >= MIN_DEPTH && flat) {
+            points.push(b);
+        } else {
+            self.subdivide(curve, t0, tm, tolerance, depth + 1, mvp, points);
+            self.subdivide(curve, tm, t1, tolerance, depth + 1, mvp, points);
+        }
+    }
+
+    /// Draws an arrow from `origin` to `origin + vector`.
+    ///
+    /// The head is built in screen space after projection, so it keeps its
+    /// shape regardless of the viewing angle.
+    pub fn arrow(&self, origin: Vec3, vector: Vec3, stroke: Stroke, mvp: Transform) {
+        let Some(a) = self.transform(origin, mvp) else {
+            return;
+        };
+        let Some(b) = self.transform(origin + vector, mvp) else {
+            return;
+        };
+        let (a, b) = (
+            self.to_screen.transform_pos(a),
+            self.to_screen.transform_pos(b),
+        );
+
+        let shaft = b - a;
+        let length = shaft.length();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let dir = shaft / length;
+        let head_length = (0.25 * length).min(12.0);
+        let rot = egui::emath::Rot2::from_angle(std::f32::consts::TAU / 12.0);
+
+        self.painter_2d.line_segment([a, b], stroke);
+        self.painter_2d
+            .line_segment([b, b - head_length * (rot * dir)], stroke);
+        self.painter_2d
+            .line_segment([b, b - head_length * (rot.inverse() * dir)], stroke);
+    }
+
     pub fn dashed_line(
         &self,
         a: Vec3,
@@ -110,6 +416,44 @@ impl Painter3D {
             .add(Shape::dashed_line(&[a, b], stroke, dash_length, gap_length));
     }
 
+    /// Draws a dashed line following the given style
+    #[allow(clippy::too_many_arguments)]
+    pub fn styled_dashed_line(
+        &self,
+        a: Vec3,
+        b: Vec3,
+        dash_length: f32,
+        gap_length: f32,
+        stroke: Stroke,
+        style: LineStyle,
+        mvp: Transform,
+    ) {
+        let stroke = style.thickness.scale(stroke, mvp.clip_w(0.5 * (a + b)));
+        if !style.anti_alias {
+            return self.dashed_line(a, b, dash_length, gap_length, stroke, mvp);
+        }
+
+        let Some(a) = self.transform(a, mvp) else {
+            return;
+        };
+        let Some(b) = self.transform(b, mvp) else {
+            return;
+        };
+        let (a, b) = (
+            self.to_screen.transform_pos(a),
+            self.to_screen.transform_pos(b),
+        );
+        let length = a.distance(b);
+        let period = (dash_length + gap_length).max(f32::EPSILON);
+        let mut start = 0.0;
+        while start < length {
+            let end = (start + dash_length).min(length);
+            let segment = [a.lerp(b, start / length), a.lerp(b, end / length)];
+            self.painter_2d.add(feathered_segment(segment, stroke));
+            start += period;
+        }
+    }
+
     pub fn bound_rect(
         &self,
         a: impl Into<Vec3>,
@@ -220,6 +564,63 @@ impl Painter3D {
         self.painter_2d.add(mesh);
     }
 
+    /// Draws a textured quad placed in world space.
+    ///
+    /// Corners are expected in order: left top, right top, right bottom and
+    /// left bottom, matching the same corners of the `uv` rect.
+    pub fn textured_quad(
+        &self,
+        corners: [Vec3; 4],
+        texture_id: TextureId,
+        uv: egui::Rect,
+        tint: Color32,
+        mvp: Transform,
+    ) {
+        let uvs = [
+            uv.left_top(),
+            uv.right_top(),
+            uv.right_bottom(),
+            uv.left_bottom(),
+        ];
+
+        let mut mesh = egui::Mesh::with_texture(texture_id);
+        for (corner, uv) in corners.into_iter().zip(uvs) {
+            let Some(pos) = self.transform(corner, mvp) else {
+                return;
+            };
+            mesh.vertices.push(Vertex {
+                pos,
+                uv,
+                color: tint,
+            });
+        }
+
+        mesh.add_triangle(0, 1, 2);
+        mesh.add_triangle(0, 2, 3);
+        self.painter_2d.add(mesh);
+    }
+
+    /// Draws a batch of square points as a single mesh.
+    ///
+    /// Points are sorted far to near so that closer ones stay on top.
+    pub fn points(&self, points: &[Vec3], size: f32, color: Color32, mvp: Transform) {
+        let mut projected: Vec<_> = points
+            .iter()
+            .map(|&pt| mvp.world_to_egui(pt))
+            .filter(|(_, z)| (0.0..=1.0).contains(z))
+            .collect();
+        projected.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+        let mut mesh = egui::Mesh::default();
+        mesh.reserve_vertices(projected.len() * 4);
+        mesh.reserve_triangles(projected.len() * 2);
+        for (pos, _) in projected {
+            let rect = egui::Rect::from_center_size(pos.to_pos2(), egui::Vec2::splat(size));
+            mesh.add_colored_rect(rect, color);
+        }
+        self.painter_2d.add(mesh);
+    }
+
     pub fn triangle(&self, a: Vec3, b: Vec3, c: Vec3, color32: Color32, mvp: Transform) {
         let Some(a) = self.transform(a, mvp) else {
             return;
@@ -245,6 +646,99 @@ impl Painter3D {
         self.painter_2d.add(mesh);
     }
 
+    /// Draws filled triangles as a single mesh. There is no depth buffer, so
+    /// the triangles are sorted back to front; triangles with a corner
+    /// outside of the view are skipped.
+    pub fn mesh(&self, triangles: &[([Vec3; 3], Color32)], mvp: Transform) {
+        let mut projected = triangles
+            .iter()
+            .filter_map(|&(corners, color)| {
+                let [a, b, c] = corners.map(|x| self.transform(x, mvp));
+                let depth = corners.iter().map(|&x| mvp.clip_w(x)).sum::<f32>();
+                Some(([a?, b?, c?], depth, color))
+            })
+            .collect::<Vec<_>>();
+        projected.sort_by(|x, y| y.1.total_cmp(&x.1));
+
+        let mut mesh = egui::Mesh::with_texture(TextureId::default());
+        for ([a, b, c], _, color) in projected {
+            let idx = mesh.vertices.len() as u32;
+            mesh.colored_vertex(a, color);
+            mesh.colored_vertex(b, color);
+            mesh.colored_vertex(c, color);
+            mesh.add_triangle(idx, idx + 1, idx + 2);
+        }
+        if !mesh.is_empty() {
+            self.painter_2d.add(mesh);
+        }
+    }
+
+    /// Draws a sphere as three orthogonal great circles around `center`
+    pub fn sphere_wire(
+        &self,
+        center: Vec3,
+        radius: f32,
+        segments: usize,
+        stroke: Stroke,
+        mvp: Transform,
+    ) {
+        self.ellipsoid_wire(center, Vec3::splat(radius), segments, stroke, mvp);
+    }
+
+    /// Draws an axis-aligned ellipsoid as its three principal ellipses
+    pub fn ellipsoid_wire(
+        &self,
+        center: Vec3,
+        radii: Vec3,
+        segments: usize,
+        stroke: Stroke,
+        mvp: Transform,
+    ) {
+        let segments = segments.max(3);
+        let axes = [(Vec3::X, Vec3::Y), (Vec3::Y, Vec3::Z), (Vec3::Z, Vec3::X)];
+        for (u, v) in axes {
+            let point = |i: usize| {
+                let t = i as f32 / segments as f32 * std::f32::consts::TAU;
+                center + (u * t.cos() + v * t.sin()) * radii
+            };
+            for i in 0..segments {
+                self.line(point(i), point(i + 1), stroke, mvp);
+            }
+        }
+    }
+
+    /// Draws a circle lying in the plane through `center` with the given normal.
+    ///
+    /// The circle is approximated with a polygon and is skipped entirely when
+    /// any of its points is outside the clip volume.
+    pub fn circle_3d(
+        &self,
+        center: Vec3,
+        normal: Vec3,
+        radius: f32,
+        fill_color: impl Into<Color32>,
+        stroke: impl Into<Stroke>,
+        mvp: Transform,
+    ) {
+        const SEGMENTS: usize = 48;
+
+        let Some(normal) = normal.try_normalize() else {
+            return;
+        };
+        let (u, v) = normal.any_orthonormal_pair();
+        let mut points = Vec::with_capacity(SEGMENTS);
+        for i in 0..SEGMENTS {
+            let t = i as f32 / SEGMENTS as f32 * std::f32::consts::TAU;
+            let Some(pt) = self.transform(center + (u * t.cos() + v * t.sin()) * radius, mvp)
+            else {
+                return;
+            };
+            points.push(self.to_screen.transform_pos(pt));
+        }
+        self.painter_2d
+            .add(Shape::convex_polygon(points, fill_color, stroke.into()));
+    }
+
     pub fn circle_filled(
         &self,
         center: Vec3,
@@ -269,3 +763,86 @@ impl Painter3D {
     //     self.painter_2d.circle_stroke(center, radius, stroke);
     // }
 }
+
+/// Builds a hard edged line segment mesh
+pub(crate) fn solid_segment([a, b]: [egui::Pos2; 2], stroke: Stroke) -> egui::Mesh {
+    let mut mesh = egui::Mesh::default();
+    let length = a.distance(b);
+    if length <= f32::EPSILON || stroke.width <= 0.0 {
+        return mesh;
+    }
+
+    let normal = ((b - a) / length).rot90() * 0.5 * stroke.width;
+    mesh.colored_vertex(a + normal, stroke.color);
+    mesh.colored_vertex(a - normal, stroke.color);
+    mesh.colored_vertex(b + normal, stroke.color);
+    mesh.colored_vertex(b - normal, stroke.color);
+    mesh.add_triangle(0, 1, 2);
+    mesh.add_triangle(1, 2, 3);
+    mesh
+}
+
+/// Builds a line segment mesh whose sides fade out over one point, independent
+/// of egui's global feathering setting
+pub(crate) fn feathered_segment([a, b]: [egui::Pos2; 2], stroke: Stroke) -> egui::Mesh {
+    const FEATHER: f32 = 1.0;
+
+    let mut mesh = egui::Mesh::default();
+    let length = a.distance(b);
+    if length <= f32::EPSILON || stroke.width <= 0.0 {
+        return mesh;
+    }
+
+    // Lines thinner than the feather are drawn at feather width with reduced opacity
+    let (color, inner) = if stroke.width < FEATHER {
+        (stroke.color.gamma_multiply(stroke.width / FEATHER), 0.0)
+    } else {
+        (stroke.color, 0.5 * (stroke.width - FEATHER))
+    };
+    let outer = inner + FEATHER;
+    let dir = (b - a) / length;
+    let normal = dir.rot90();
+    let (a_out, b_out) = (a - dir * 0.5 * FEATHER, b + dir * 0.5 * FEATHER);
+
+    mesh.colored_vertex(a + normal * inner, color);
+    mesh.colored_vertex(a - normal * inner, color);
+    mesh.colored_vertex(b + normal * inner, color);
+    mesh.colored_vertex(b - normal * inner, color);
+    mesh.colored_vertex(a_out + normal * outer, Color32::TRANSPARENT);
+    mesh.colored_vertex(a_out - normal * outer, Color32::TRANSPARENT);
+    mesh.colored_vertex(b_out + normal * outer, Color32::TRANSPARENT);
+    mesh.colored_vertex(b_out - normal * outer, Color32::TRANSPARENT);
+
+    // core
+    mesh.add_triangle(0, 1, 2);
+    mesh.add_triangle(1, 2, 3);
+    // sides
+    mesh.add_triangle(4, 0, 6);
+    mesh.add_triangle(0, 6, 2);
+    mesh.add_triangle(5, 1, 7);
+    mesh.add_triangle(1, 7, 3);
+    // caps
+    mesh.add_triangle(4, 5, 0);
+    mesh.add_triangle(5, 0, 1);
+    mesh.add_triangle(6, 7, 2);
+    mesh.add_triangle(7, 2, 3);
+    mesh
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_occlusion_hides() {
+        let occluders = [BoundingBox::from_two_pos(
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        )];
+        let occlusion = Occlusion::new(Vec3::new(5.0, 0.0, 0.0), &occluders);
+
+        assert!(occlusion.hides(Vec3::new(-5.0, 0.0, 0.0)));
+        assert!(!occlusion.hides(Vec3::new(3.0, 0.0, 0.0)));
+        assert!(!occlusion.hides(Vec3::new(-5.0, 3.0, 0.0)));
+    }
+}
@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+
+use egui::{ImageData, TextureHandle, TextureId, TextureOptions};
+
+/// Texture handles kept alive between frames, keyed by name.
+///
+/// `Painter3D` is recreated every frame, so the cache lives in egui's
+/// temporary memory and outlives it.
+#[derive(Clone, Default)]
+pub struct TextureCache {
+    handles: HashMap<String, TextureHandle>,
+}
+
+impl TextureCache {
+    fn id() -> egui::Id {
+        egui::Id::new("painter3d_texture_cache")
+    }
+
+    /// Uploads the image into the texture with the given name, reusing the
+    /// existing handle if there is one
+    pub fn load(
+        ctx: &egui::Context,
+        name: &str,
+        image: impl Into<ImageData>,
+        options: TextureOptions,
+    ) -> TextureId {
+        // The texture manager can't be touched while the memory is locked,
+        // so the handle is cloned out first
+        let cached = ctx.data_mut(|data| {
+            data.get_temp_mut_or_default::<TextureCache>(Self::id())
+                .handles
+                .get(name)
+                .cloned()
+        });
+        if let Some(mut handle) = cached {
+            handle.set(image, options);
+            return handle.id();
+        }
+
+        let handle = ctx.load_texture(name, image, options);
+        let id = handle.id();
+        ctx.data_mut(|data| {
+            data.get_temp_mut_or_default::<TextureCache>(Self::id())
+                .handles
+                .insert(name.to_owned(), handle)
+        });
+        id
+    }
+
+    /// Frees the texture with the given name once nothing else refers to it
+    pub fn forget(ctx: &egui::Context, name: &str) {
+        let handle = ctx.data_mut(|data| {
+            data.get_temp_mut_or_default::<TextureCache>(Self::id())
+                .handles
+                .remove(name)
+        });
+        drop(handle);
+    }
+}
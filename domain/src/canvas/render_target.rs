@@ -0,0 +1,194 @@
+use std::path::Path;
+
+use egui::{Color32, ColorImage, Pos2};
+
+use crate::visitor::raster::inside_triangle;
+
+/// Owned RGBA pixel buffer that a scene can be rendered into without egui
+#[derive(Clone, Debug, PartialEq)]
+pub struct RenderTarget {
+    image: ColorImage,
+}
+
+impl RenderTarget {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            image: ColorImage::new([width, height], Color32::TRANSPARENT),
+        }
+    }
+
+    #[inline]
+    pub fn width(&self) -> usize {
+        self.image.size[0]
+    }
+
+    #[inline]
+    pub fn height(&self) -> usize {
+        self.image.size[1]
+    }
+
+    #[inline]
+    pub fn size(&self) -> [usize; 2] {
+        self.image.size
+    }
+
+    pub fn image(&self) -> &ColorImage {
+        &self.image
+    }
+
+    pub fn into_image(self) -> ColorImage {
+        self.image
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [Color32] {
+        &mut self.image.pixels
+    }
+
+    /// Returns the buffer as tightly packed unmultiplied RGBA bytes
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.image
+            .pixels
+            .iter()
+            .flat_map(|x| x.to_srgba_unmultiplied())
+            .collect()
+    }
+
+    /// Writes the buffer as an RGBA PNG file
+    pub fn save_png(&self, path: impl AsRef<Path>) -> image::ImageResult<()> {
+        let [w, h] = self.size().map(|x| x as u32);
+        image::save_buffer_with_format(
+            path,
+            &self.to_rgba8(),
+            w,
+            h,
+            image::ColorType::Rgba8,
+            image::ImageFormat::Png,
+        )
+    }
+
+    /// Puts the buffer into the clipboard as an image, see
+    /// [`DrawManager::copy_to_clipboard`]
+    ///
+    /// [`DrawManager::copy_to_clipboard`]: crate::managers::draw_manager::DrawManager::copy_to_clipboard
+    pub fn copy_to_clipboard(
+        &self,
+        clipboard: &mut arboard::Clipboard,
+    ) -> Result<(), arboard::Error> {
+        let [width, height] = self.size();
+        let image = arboard::ImageData {
+            width,
+            height,
+            bytes: self.to_rgba8().into(),
+        };
+        clipboard.set_image(image)
+    }
+
+    pub fn fill(&mut self, color: Color32) {
+        self.image.pixels.fill(color);
+    }
+
+    /// Composites `color` over the pixel at `(x, y)`, ignoring out of bounds writes
+    pub fn blend(&mut self, x: usize, y: usize, color: Color32) {
+        if x >= self.width() || y >= self.height() {
+            return;
+        }
+        let dst = &mut self.image[(x, y)];
+        *dst = over(color, *dst);
+    }
+
+    /// Composites an image over the buffer with its top left corner at `pos`
+    pub fn draw_image(&mut self, pos: [usize; 2], image: &ColorImage) {
+        let [w, h] = image.size;
+        for y in 0..h {
+            for x in 0..w {
+                self.blend(pos[0] + x, pos[1] + y, image[(x, y)]);
+            }
+        }
+    }
+
+    /// Draws a one pixel wide line between two pixel positions
+    pub fn line(&mut self, a: Pos2, b: Pos2, color: Color32) {
+        let steps = (b - a).abs().max_elem().ceil().max(1.0) as usize;
+        let (width, height) = (self.width() as f32, self.height() as f32);
+        for i in 0..=steps {
+            let p = a.lerp(b, i as f32 / steps as f32);
+            if (0.0..width).contains(&p.x) && (0.0..height).contains(&p.y) {
+                self.blend(p.x as usize, p.y as usize, color);
+            }
+        }
+    }
+
+    pub fn circle_filled(&mut self, center: Pos2, radius: f32, color: Color32) {
+        let min_x = (center.x - radius).floor().max(0.0) as usize;
+        let min_y = (center.y - radius).floor().max(0.0) as usize;
+        let max_x = (center.x + radius).ceil().max(0.0) as usize;
+        let max_y = (center.y + radius).ceil().max(0.0) as usize;
+        for y in min_y..=max_y.min(self.height().saturating_sub(1)) {
+            for x in min_x..=max_x.min(self.width().saturating_sub(1)) {
+                let p = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                if p.distance_sq(center) <= radius * radius {
+                    self.blend(x, y, color);
+                }
+            }
+        }
+    }
+
+    /// Fills the triangle, pixels are covered when their center is inside it
+    pub fn triangle(&mut self, [a, b, c]: [Pos2; 3], color: Color32) {
+        let min = a.min(b).min(c);
+        let max = a.max(b).max(c);
+        let min_x = min.x.floor().max(0.0) as usize;
+        let min_y = min.y.floor().max(0.0) as usize;
+        let max_x = (max.x.ceil().max(0.0) as usize).min(self.width());
+        let max_y = (max.y.ceil().max(0.0) as usize).min(self.height());
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let p = Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                if inside_triangle(p, a, b, c) {
+                    self.blend(x, y, color);
+                }
+            }
+        }
+    }
+}
+
+/// Premultiplied alpha "over" operator
+#[inline]
+pub fn over(src: Color32, dst: Color32) -> Color32 {
+    let inv_alpha = 255 - src.a() as u16;
+    let mix = |s: u8, d: u8| (s as u16 + (d as u16 * inv_alpha + 127) / 255).min(255) as u8;
+    Color32::from_rgba_premultiplied(
+        mix(src.r(), dst.r()),
+        mix(src.g(), dst.g()),
+        mix(src.b(), dst.b()),
+        mix(src.a(), dst.a()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_over() {
+        let mut target = RenderTarget::new(2, 1);
+        target.fill(Color32::BLUE);
+        target.blend(0, 0, Color32::TRANSPARENT);
+        target.blend(1, 0, Color32::RED);
+        assert_eq!(target.image()[(0, 0)], Color32::BLUE);
+        assert_eq!(target.image()[(1, 0)], Color32::RED);
+        assert_eq!(target.to_rgba8().len(), 8);
+    }
+
+    #[test]
+    fn test_save_png() {
+        let mut target = RenderTarget::new(3, 2);
+        target.fill(Color32::RED);
+        let path = std::env::temp_dir().join(format!("target-{}.png", std::process::id()));
+        target.save_png(&path).unwrap();
+        let saved = image::open(&path).unwrap().into_rgba8();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.dimensions(), (3, 2));
+        assert_eq!(saved.get_pixel(2, 1).0, [255, 0, 0, 255]);
+    }
+}
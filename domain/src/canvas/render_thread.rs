@@ -0,0 +1,210 @@
+//! Cloud raymarching on a thread of its own
+
+use std::collections::BTreeMap;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use egui::{Color32, ColorImage, Rect, TextureHandle, TextureId};
+
+use crate::managers::cache_manager::CacheKey;
+use crate::visitor::raster::CloudMarch;
+
+struct Request {
+    id: &'static str,
+    key: CacheKey,
+    size: [usize; 2],
+    march: CloudMarch,
+}
+
+/// Finished image of a cloud with the screen rect it was marched for
+struct Frame {
+    key: CacheKey,
+    image: ColorImage,
+    rect: Rect,
+}
+
+/// Image of a cloud on screen
+struct Presented {
+    key: CacheKey,
+    texture: TextureHandle,
+    rect: Rect,
+}
+
+type Slot = Mutex<BTreeMap<&'static str, Frame>>;
+
+/// Marches the clouds on a dedicated thread, so a slow march never holds up
+/// the frame. A cloud has up to three images at a time: the one being
+/// marched, the latest finished one waiting in the slot and the one on
+/// screen, which the waiting one replaces as soon as it is there.
+pub struct RenderThread {
+    requests: Option<Sender<Request>>,
+    /// Latest finished frame of each cloud
+    slot: Arc<Slot>,
+    /// Key of the last request of each cloud
+    submitted: Mutex<BTreeMap<&'static str, CacheKey>>,
+    presented: Mutex<BTreeMap<&'static str, Presented>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl RenderThread {
+    pub fn spawn() -> Self {
+        let (requests, receiver) = mpsc::channel();
+        let slot = Arc::new(Mutex::new(BTreeMap::new()));
+        let shared = slot.clone();
+        let worker = std::thread::Builder::new()
+            .name("render".into())
+            .spawn(move || run(receiver, &shared))
+            .expect("failed to start the render thread");
+        Self {
+            requests: Some(requests),
+            slot,
+            submitted: Mutex::new(BTreeMap::new()),
+            presented: Mutex::new(BTreeMap::new()),
+            worker: Some(worker),
+        }
+    }
+
+    /// Queues the march of the cloud into an image of the size, unless the
+    /// last request of the cloud had the same key
+    pub fn submit(
+        &self,
+        id: &'static str,
+        key: CacheKey,
+        size: [usize; 2],
+        march: impl FnOnce() -> CloudMarch,
+    ) {
+        let mut submitted = self.submitted.lock().unwrap();
+        if submitted.get(id) == Some(&key) {
+            return;
+        }
+        submitted.insert(id, key);
+        if let Some(requests) = &self.requests {
+            let march = march();
+            // The thread only stops when the sender is dropped
+            let _ = requests.send(Request {
+                id,
+                key,
+                size,
+                march,
+            });
+        }
+    }
+
+    /// Uploads the latest finished image of the cloud, if there is a new
+    /// one, and returns the texture and the screen rect of the image on
+    /// screen. `None` until the first image is done.
+    pub fn present(&self, ctx: &egui::Context, id: &'static str) -> Option<(TextureId, Rect)> {
+        let frame = self.slot.lock().unwrap().remove(id);
+        let mut presented = self.presented.lock().unwrap();
+        if let Some(Frame { key, image, rect }) = frame {
+            match presented.get_mut(id) {
+                Some(shown) => {
+                    shown.texture.set(image, Default::default());
+                    shown.key = key;
+                    shown.rect = rect;
+                }
+                None => {
+                    let texture =
+                        ctx.load_texture(format!("cloud {id}"), image, Default::default());
+                    presented.insert(id, Presented { key, texture, rect });
+                }
+            }
+        }
+        presented.get(id).map(|x| (x.texture.id(), x.rect))
+    }
+
+    /// Whether the image of the cloud on screen is older than its last
+    /// request
+    pub fn is_pending(&self, id: &str) -> bool {
+        let submitted = self.submitted.lock().unwrap().get(id).copied();
+        let shown = self.presented.lock().unwrap().get(id).map(|x| x.key);
+        submitted.is_some() && submitted != shown
+    }
+
+    /// Drops the images of the cloud, e.g. once it is removed
+    pub fn forget(&self, id: &str) {
+        self.submitted.lock().unwrap().remove(id);
+        self.slot.lock().unwrap().remove(id);
+        self.presented.lock().unwrap().remove(id);
+    }
+}
+
+impl Drop for RenderThread {
+    fn drop(&mut self) {
+        self.requests.take();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Marches the requests until the sender is dropped. Only the newest
+/// request of each cloud is marched, the older ones are stale by then.
+fn run(receiver: Receiver<Request>, slot: &Slot) {
+    // Images of the frames replaced before they were shown
+    let mut spare = Vec::new();
+    while let Ok(request) = receiver.recv() {
+        let mut latest = BTreeMap::from([(request.id, request)]);
+        latest.extend(receiver.try_iter().map(|x| (x.id, x)));
+        for (id, request) in latest {
+            let mut image: ColorImage = spare.pop().unwrap_or_default();
+            let [w, h] = request.size;
+            image.size = request.size;
+            image.pixels.clear();
+            image.pixels.resize(w * h, Color32::TRANSPARENT);
+            request.march.run(&mut image);
+
+            let frame = Frame {
+                key: request.key,
+                image,
+                rect: request.march.rect,
+            };
+            let replaced = slot.lock().unwrap().insert(id, frame);
+            spare.extend(replaced.map(|x| x.image));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::camera::Camera;
+    use crate::object::objects::Cloud;
+
+    #[test]
+    fn test_render_thread() {
+        let thread = RenderThread::spawn();
+        let march = || CloudMarch {
+            cloud: Cloud::default(),
+            camera: Camera::default(),
+            viewport: [8, 8],
+            inverse: glam::Mat4::IDENTITY,
+            lights: Vec::new(),
+            fog: None,
+            rect: Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(8.0, 8.0)),
+            hull: None,
+            rays: None,
+            occupancy: None,
+            density: None,
+            gpu: false,
+            base: None,
+            profiler: None,
+        };
+        thread.submit("cloud", 1, [4, 4], march);
+        // The same key is not marched again
+        thread.submit("cloud", 1, [4, 4], || unreachable!());
+        assert!(thread.is_pending("cloud"));
+
+        let ctx = egui::Context::default();
+        let start = std::time::Instant::now();
+        while thread.present(&ctx, "cloud").is_none() {
+            assert!(
+                start.elapsed().as_secs() < 10,
+                "no frame from the render thread"
+            );
+            std::thread::yield_now();
+        }
+        assert!(!thread.is_pending("cloud"));
+    }
+}
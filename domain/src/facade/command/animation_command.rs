@@ -0,0 +1,123 @@
+use crate::facade::Command;
+use crate::managers::animation_manager::AnimationManager;
+use crate::managers::animation_manager::{Property, Track};
+use crate::managers::camera_manager::CameraManager;
+use crate::managers::event_manager::{EventManager, ParamChanged};
+use crate::managers::scene_manager::SceneManager;
+use crate::managers::ManagerSolution;
+use crate::object::camera::Camera;
+use crate::object::objects::Sun;
+use crate::object::Component;
+use crate::scene::scene::Scene;
+
+/// Timeline after the command
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct AnimationState {
+    pub time: f32,
+    pub duration: f32,
+    pub playing: bool,
+    pub tracks: Vec<String>,
+}
+
+pub enum AnimationCommand {
+    AddTrack(String, Track),
+    RemoveTrack(String),
+    /// Adds a key at the given time to the named track
+    InsertKey(String, f32, f32),
+    /// Plays the tracks, over and over when the flag is set. The time
+    /// manager advances them every tick.
+    Play(bool),
+    Stop,
+    /// Moves to the time and applies the values there
+    Seek(f32),
+    Clear,
+    Query,
+}
+
+impl Command for AnimationCommand {
+    type ReturnType = AnimationState;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        let am = manager.get_mut::<AnimationManager>();
+        match self {
+            AnimationCommand::AddTrack(name, track) => am.add_track(name, track),
+            AnimationCommand::RemoveTrack(name) => {
+                am.remove_track(&name);
+            }
+            AnimationCommand::InsertKey(name, time, value) => {
+                if let Some(track) = am.track_mut(&name) {
+                    track.insert_key(time, value);
+                }
+            }
+            AnimationCommand::Play(looped) => am.play(looped),
+            AnimationCommand::Stop => am.stop(),
+            AnimationCommand::Seek(time) => {
+                am.seek(time);
+                let values = am.values();
+                apply_animation(manager, &values);
+            }
+            AnimationCommand::Clear => am.clear(),
+            AnimationCommand::Query => {}
+        }
+        let am = manager.get::<AnimationManager>();
+        AnimationState {
+            time: am.time(),
+            duration: am.duration(),
+            playing: am.is_playing(),
+            tracks: am.tracks().map(|(name, _)| name.to_owned()).collect(),
+        }
+    }
+}
+
+/// Sets the animated parameters, angles are given in degrees
+pub(crate) fn apply_animation(
+    manager: &mut ManagerSolution,
+    values: &[(&'static str, Property, f32)],
+) {
+    let changed = manager.lend(|cm: &mut CameraManager, manager| {
+        let scene = manager.get_mut::<SceneManager>().get_mut_scene();
+        animate(cm.get_mut_camera(), scene, values)
+    });
+    for id in changed {
+        manager
+            .get_mut::<EventManager>()
+            .publish(ParamChanged { id });
+    }
+}
+
+/// Sets the animated parameters of the camera and the scene objects and
+/// returns the objects changed
+pub fn animate(
+    camera: &mut Camera,
+    scene: &mut Scene,
+    values: &[(&'static str, Property, f32)],
+) -> Vec<&'static str> {
+    let mut changed = Vec::new();
+    for &(target, property, value) in values {
+        let view = &mut camera.view;
+        match property {
+            Property::CameraYaw => view.yaw = value.to_radians(),
+            Property::CameraPitch => view.pitch = value.to_radians(),
+            Property::CameraDistance => view.distance = value,
+            _ => {}
+        }
+        match scene.get_mut_object(target) {
+            Some(Component::Cloud(cloud)) => match property {
+                Property::CloudDensityMultiplier => cloud.density_multiplier = value,
+                Property::CloudDensityOffset => cloud.density_offset = value,
+                Property::CloudDensityThreshold => cloud.density_threshold = value,
+                Property::CloudAbsorption => cloud.light_absorption_through_cloud = value,
+                _ => {}
+            },
+            Some(Component::Sun(sun)) => match property {
+                Property::SunElevation => sun.a = -value,
+                Property::SunAzimuth => sun.z = value,
+                Property::SunIntensity => sun.intensity = value,
+                Property::SunTemperature => sun.color = Sun::temperature_color(value),
+                _ => {}
+            },
+            _ => continue,
+        }
+        changed.push(target);
+    }
+    changed
+}
@@ -0,0 +1,40 @@
+use crate::facade::Command;
+use crate::managers::cache_manager::{CacheManager, CacheStats};
+use crate::managers::event_manager::EventManager;
+use crate::managers::ManagerSolution;
+
+/// Derived rendering data, see [`CacheManager`]
+pub enum CacheCommand {
+    Invalidate(&'static str),
+    Clear,
+    Query,
+}
+
+impl Command for CacheCommand {
+    type ReturnType = CacheStats;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        invalidate_caches(manager);
+        let cache = manager.get::<CacheManager>();
+        match self {
+            CacheCommand::Invalidate(id) => cache.invalidate(id),
+            CacheCommand::Clear => cache.clear(),
+            CacheCommand::Query => {}
+        }
+        cache.stats()
+    }
+}
+
+/// Drops the caches of the objects changed since the previous call, called
+/// before the caches are read
+pub(crate) fn invalidate_caches(manager: &mut ManagerSolution) {
+    let (changed, added) = manager.get::<CacheManager>().subscriptions();
+    let events = manager.get_mut::<EventManager>();
+    let mut ids: Vec<_> = events.read(changed).into_iter().map(|x| x.id).collect();
+    ids.extend(events.read(added).into_iter().map(|x| x.id));
+    ids.sort_unstable();
+    ids.dedup();
+    let cache = manager.get::<CacheManager>();
+    for id in ids {
+        cache.invalidate(id);
+    }
+}
@@ -1,6 +1,25 @@
 use crate::facade::Command;
+use crate::managers::camera_manager::CameraManager;
+use crate::managers::diagnostics_manager::{validate_camera, DiagnosticsManager};
+use crate::managers::scene_manager::SceneManager;
 use crate::managers::ManagerSolution;
 use crate::object::camera::Camera;
+use crate::object::Component;
+
+pub enum CameraCommandReturn {
+    Nothing,
+    Presets(Vec<String>),
+}
+
+impl CameraCommandReturn {
+    #[inline]
+    pub fn as_presets(&self) -> Option<&[String]> {
+        if let Self::Presets(presets) = self {
+            return Some(presets);
+        }
+        None
+    }
+}
 
 #[derive(Debug)]
 pub enum CameraCommand {
@@ -8,26 +27,90 @@ pub enum CameraCommand {
     Zoom(f32),
     Pivot(f32, f32),
     SetCamera(Camera),
+    /// Moves the camera to look from the given direction
+    LookFrom(glam::Vec3),
+    /// Frames the visible objects of the scene and fits the clip planes to
+    /// them
+    FrameAll,
+    /// Fits the clip planes to the visible objects of the scene
+    FitClipPlanes,
+    /// Snaps the view to the axis under the pointer if it hits a handle of
+    /// the orientation gizmo with the given name drawn in the canvas rect
+    ClickGizmo(&'static str, egui::Rect, egui::Pos2),
+    /// Saves the current camera under the name
+    SavePreset(String),
+    /// Makes the saved camera the current one
+    ApplyPreset(String),
+    RemovePreset(String),
+    /// Lists the names of the saved cameras
+    QueryPresets,
 }
 
 impl Command for CameraCommand {
-    type ReturnType = ();
-    fn exec(self, manager: &mut ManagerSolution) {
-        let cm = manager.get_mut_camera_manager();
-        let camera = cm.get_mut_camera();
+    type ReturnType = CameraCommandReturn;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        let cm = manager.get_mut::<CameraManager>();
         match self {
             CameraCommand::Pan(x, y) => {
-                camera.pan(x, y);
+                cm.get_mut_camera().pan(x, y);
             }
             CameraCommand::Pivot(x, y) => {
-                camera.pivot(x, y);
+                cm.get_mut_camera().pivot(x, y);
             }
             CameraCommand::Zoom(x) => {
-                camera.zoom(x);
+                cm.get_mut_camera().zoom(x);
             }
             CameraCommand::SetCamera(c) => {
                 cm.set_camera(c);
             }
+            CameraCommand::LookFrom(dir) => {
+                cm.get_mut_camera().look_from(dir);
+            }
+            CameraCommand::FrameAll | CameraCommand::FitClipPlanes => {
+                let Some(bounds) = manager.get::<SceneManager>().bounds() else {
+                    return CameraCommandReturn::Nothing;
+                };
+                let camera = manager.get_mut::<CameraManager>().get_mut_camera();
+                if matches!(self, CameraCommand::FrameAll) {
+                    camera.frame(&bounds);
+                }
+                camera.fit_clip_planes(&bounds);
+            }
+            CameraCommand::ClickGizmo(id, canvas, pointer) => {
+                let Some(Component::Gizmo(gizmo)) = manager.get::<SceneManager>().get_object(id)
+                else {
+                    return CameraCommandReturn::Nothing;
+                };
+                let camera = manager.get::<CameraManager>().get_camera();
+                if let Some(axis) = gizmo.hit(camera, canvas, pointer) {
+                    manager
+                        .get_mut::<CameraManager>()
+                        .get_mut_camera()
+                        .look_from(axis);
+                }
+            }
+            CameraCommand::SavePreset(name) => cm.save_preset(name),
+            CameraCommand::ApplyPreset(name) => {
+                let Some(&camera) = cm.preset(&name) else {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .warning(None, format!("no camera preset named {name}"));
+                    return CameraCommandReturn::Nothing;
+                };
+                cm.set_camera(camera);
+            }
+            CameraCommand::RemovePreset(name) => {
+                cm.remove_preset(&name);
+            }
+            CameraCommand::QueryPresets => {
+                return CameraCommandReturn::Presets(cm.presets());
+            }
+        }
+        let problems = validate_camera(manager.get::<CameraManager>().get_camera());
+        let dm = manager.get_mut::<DiagnosticsManager>();
+        for problem in problems {
+            dm.error(Some("camera"), problem);
         }
+        CameraCommandReturn::Nothing
     }
 }
@@ -0,0 +1,70 @@
+use crate::facade::Command;
+use crate::managers::diagnostics_manager::{validate, Diagnostic, DiagnosticsManager, Severity};
+use crate::managers::event_manager::EventManager;
+use crate::managers::scene_manager::SceneManager;
+use crate::managers::ManagerSolution;
+use crate::visitor::validate_visitor::ValidateVisitor;
+use crate::visitor::Visitor;
+
+/// Problems reported by the commands, see [`DiagnosticsManager`]
+pub enum DiagnosticsCommand {
+    /// Diagnostics of the severity or a higher one
+    Query(Severity),
+    Clear,
+    /// Checks every object of the scene, see [`validate_scene`]
+    ValidateScene,
+}
+
+impl Command for DiagnosticsCommand {
+    type ReturnType = Vec<Diagnostic>;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        let dm = manager.get_mut::<DiagnosticsManager>();
+        match self {
+            DiagnosticsCommand::Query(severity) => dm.diagnostics(severity),
+            DiagnosticsCommand::Clear => {
+                dm.clear();
+                Vec::new()
+            }
+            DiagnosticsCommand::ValidateScene => {
+                validate_scene(manager);
+                manager
+                    .get::<DiagnosticsManager>()
+                    .diagnostics(Severity::Warning)
+            }
+        }
+    }
+}
+
+/// Validates the objects changed since the previous call
+pub(crate) fn validate_changed(manager: &mut ManagerSolution) {
+    let changed = manager.get::<DiagnosticsManager>().subscription();
+    let mut ids: Vec<_> = manager
+        .get_mut::<EventManager>()
+        .read(changed)
+        .into_iter()
+        .map(|x| x.id)
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+    for id in ids {
+        let sm = manager.get::<SceneManager>();
+        let (Some(object), Some(transform)) = (sm.get_object(id), sm.local_transform(id)) else {
+            continue;
+        };
+        let problems = validate(object, &transform);
+        let dm = manager.get_mut::<DiagnosticsManager>();
+        for problem in problems {
+            dm.warning(Some(id), problem);
+        }
+    }
+}
+
+/// Reports the problems of all objects of the scene, e.g. after it is loaded
+pub(crate) fn validate_scene(manager: &mut ManagerSolution) {
+    let objects = &manager.get::<SceneManager>().get_scene().objects;
+    let problems = ValidateVisitor::default().visit_composite(objects);
+    let dm = manager.get_mut::<DiagnosticsManager>();
+    for (id, problem) in problems {
+        dm.warning(Some(id), problem);
+    }
+}
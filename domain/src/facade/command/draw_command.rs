@@ -1,32 +1,322 @@
-use crate::canvas::painter::Painter3D;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use glam::Vec3;
+
+use crate::canvas::painter::{LineThickness, Painter3D};
+use crate::canvas::render_target::RenderTarget;
+use crate::facade::command::cache_command::invalidate_caches;
+use crate::facade::command::diagnostics_command::validate_changed;
 use crate::facade::Command;
+use crate::managers::cache_manager::CacheManager;
+use crate::managers::camera_manager::CameraManager;
+use crate::managers::diagnostics_manager::DiagnosticsManager;
+use crate::managers::draw_manager::DrawManager;
+use crate::managers::event_manager::{EventManager, FrameRendered};
+use crate::managers::quality_manager::QualityManager;
+use crate::managers::render_manager::RenderManager;
+use crate::managers::render_manager::{PassState, RenderPass};
+use crate::managers::scene_manager::SceneManager;
+use crate::managers::selection_manager::SelectionManager;
 use crate::managers::ManagerSolution;
+use crate::object::camera::Camera;
+use crate::object::Component;
+
+pub enum DrawCommandReturn {
+    Nothing,
+    Image(RenderTarget),
+    Passes(Vec<PassState>),
+    Error(String),
+}
+
+impl DrawCommandReturn {
+    #[inline]
+    pub fn into_image(self) -> Option<RenderTarget> {
+        if let Self::Image(target) = self {
+            return Some(target);
+        }
+        None
+    }
+
+    #[inline]
+    pub fn as_passes(&self) -> Option<&[PassState]> {
+        if let Self::Passes(passes) = self {
+            return Some(passes);
+        }
+        None
+    }
+}
+
+/// Camera an auxiliary view of the scene is rendered from
+pub enum View {
+    /// Saved camera, e.g. for its thumbnail
+    Preset(String),
+    /// Straight down at the whole scene
+    TopDown,
+    /// From the sun at the whole scene, the way the shadows are cast
+    Sun,
+}
+
+/// Camera of the view, fitted to the visible objects of the scene
+fn view_camera(manager: &ManagerSolution, view: &View) -> Result<Camera, String> {
+    let sm = manager.get::<SceneManager>();
+    let bounds = || sm.bounds().ok_or_else(|| "the scene is empty".to_owned());
+    match view {
+        View::Preset(name) => manager
+            .get::<CameraManager>()
+            .preset(name)
+            .copied()
+            .ok_or_else(|| format!("no camera preset named {name}")),
+        View::TopDown => Ok(Camera::looking_from(Vec3::Y, &bounds()?)),
+        View::Sun => {
+            let objects = &sm.get_scene().objects;
+            let sun = objects.visible().find_map(|(name, x)| match x {
+                Component::Sun(sun) => {
+                    let model = objects.world_transform(name);
+                    Some(model.transform_point3(sun.get_pos()))
+                }
+                _ => None,
+            });
+            let sun = sun.ok_or_else(|| "the scene has no sun".to_owned())?;
+            let bounds = bounds()?;
+            Ok(Camera::looking_from(sun - bounds.center(), &bounds))
+        }
+    }
+}
+
+/// Current view rendered offscreen at the size of the canvas unless another
+/// one is given
+fn render_current(
+    manager: &ManagerSolution,
+    size: Option<[usize; 2]>,
+) -> Result<RenderTarget, String> {
+    let draw = manager.get::<DrawManager>();
+    let camera = manager.get::<CameraManager>().get_camera();
+    let scene = manager.get::<SceneManager>().get_scene();
+    let [width, height] = size
+        .or_else(|| draw.viewport_size())
+        .ok_or_else(|| "no canvas to take the size of".to_owned())?;
+    Ok(draw.render_offscreen(scene, camera, width, height))
+}
 
 pub enum DrawCommand {
     SetPainter(Painter3D),
     SetPainterColor(egui::Color32),
+    SetLineThickness(LineThickness),
+    SetLineAntiAlias(bool),
+    /// Shows or hides all objects of the render layer, e.g. the debug one
+    SetLayerVisible(&'static str, bool),
+    /// Objects farther from the camera are not drawn, no limit when `None`
+    SetMaxDrawDistance(Option<f32>),
+    /// Draws the objects small on screen at a lower quality, see
+    /// [`LodVisitor`]
+    ///
+    /// [`LodVisitor`]: crate::visitor::lod_visitor::LodVisitor
+    SetLodEnabled(bool),
+    /// Marches the clouds on a thread of their own instead of during the
+    /// frame, see [`RenderThread`]
+    ///
+    /// [`RenderThread`]: crate::canvas::render_thread::RenderThread
+    SetAsyncClouds(bool),
+    /// Marches the clouds in a compute shader instead of on the CPU, see
+    /// [`DrawManager::set_gpu_clouds`]
+    SetGpuClouds(bool),
+    /// Marches only the changed part of a cloud when the lights change, see
+    /// [`DrawManager::set_partial_redraw`]
+    SetPartialRedraw(bool),
+    /// Frame rate held while the camera moves by lowering the cloud quality,
+    /// see [`QualityManager`]. `None` keeps the full quality.
+    SetTargetFps(Option<f32>),
+    /// Marches the clouds through a coarse density field while the camera
+    /// moves, see [`DensityField`]
+    ///
+    /// [`DensityField`]: crate::object::objects::DensityField
+    SetCoarseDensity(bool),
+    SetPassEnabled(RenderPass, bool),
+    /// Reorders the render passes, see [`RenderManager::set_order`]
+    ///
+    /// [`RenderManager::set_order`]: crate::managers::render_manager::RenderManager::set_order
+    SetPassOrder(Vec<RenderPass>),
+    /// Lists the render passes with their times during the last frame
+    QueryPasses,
+    /// Draws the scene pass by pass
     Draw,
+    RenderOffscreen(usize, usize),
+    /// Renders the scene of the given size from the camera of the view
+    /// instead of the interactive one
+    RenderView(View, [usize; 2]),
+    /// Writes the grid, the object boxes and the sun direction seen by the
+    /// camera as an SVG figure of the given size
+    ExportSvg(PathBuf, [usize; 2]),
+    /// Renders the current view offscreen into a PNG file, at the size of the
+    /// canvas unless another one is given
+    Screenshot(PathBuf, Option<[usize; 2]>),
+    /// Renders the current view offscreen into the system clipboard, at the
+    /// size of the canvas unless another one is given
+    CopyToClipboard(Option<[usize; 2]>),
 }
 
 impl Command for DrawCommand {
-    type ReturnType = ();
-    fn exec(self, manager: &mut ManagerSolution) {
+    type ReturnType = DrawCommandReturn;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
         match self {
             Self::SetPainter(painter) => {
-                let dm = manager.get_mut_draw_manager();
+                let dm = manager.get_mut::<DrawManager>();
                 dm.set_canvas(painter)
             }
             Self::SetPainterColor(color) => {
-                let dm = manager.get_mut_draw_manager();
+                let dm = manager.get_mut::<DrawManager>();
                 dm.set_color(color);
             }
+            Self::SetLineThickness(thickness) => {
+                let dm = manager.get_mut::<DrawManager>();
+                dm.set_line_thickness(thickness);
+            }
+            Self::SetLineAntiAlias(anti_alias) => {
+                let dm = manager.get_mut::<DrawManager>();
+                dm.set_line_anti_alias(anti_alias);
+            }
+            Self::SetLayerVisible(layer, visible) => {
+                let dm = manager.get_mut::<DrawManager>();
+                dm.set_layer_visible(layer, visible);
+            }
+            Self::SetMaxDrawDistance(distance) => {
+                let dm = manager.get_mut::<DrawManager>();
+                dm.set_max_draw_distance(distance);
+            }
+            Self::SetLodEnabled(enabled) => {
+                let dm = manager.get_mut::<DrawManager>();
+                dm.set_lod_enabled(enabled);
+            }
+            Self::SetAsyncClouds(enabled) => {
+                let dm = manager.get_mut::<DrawManager>();
+                dm.set_async_clouds(enabled);
+            }
+            Self::SetGpuClouds(enabled) => {
+                let dm = manager.get_mut::<DrawManager>();
+                dm.set_gpu_clouds(enabled);
+            }
+            Self::SetPartialRedraw(enabled) => {
+                let dm = manager.get_mut::<DrawManager>();
+                dm.set_partial_redraw(enabled);
+            }
+            Self::SetTargetFps(fps) => {
+                let qm = manager.get_mut::<QualityManager>();
+                qm.set_target_fps(fps);
+            }
+            Self::SetCoarseDensity(enabled) => {
+                let qm = manager.get_mut::<QualityManager>();
+                qm.set_coarse_density(enabled);
+            }
+            Self::SetPassEnabled(pass, enabled) => {
+                let rm = manager.get_mut::<RenderManager>();
+                rm.set_enabled(pass, enabled);
+            }
+            Self::SetPassOrder(order) => {
+                let rm = manager.get_mut::<RenderManager>();
+                rm.set_order(&order);
+            }
+            Self::QueryPasses => {
+                let passes = manager.get::<RenderManager>().passes().to_vec();
+                return DrawCommandReturn::Passes(passes);
+            }
             Self::Draw => {
-                let draw = manager.get_draw_manager();
-                let camera = manager.get_camera_manager().get_camera();
-                let scene = manager.get_scene_manager().get_scene();
+                let start = Instant::now();
+                invalidate_caches(manager);
+                validate_changed(manager);
+                manager.get_mut::<SceneManager>().update_gizmos();
+                manager.lend(|rm: &mut RenderManager, manager| {
+                    let camera = manager.get::<CameraManager>().get_camera();
+                    let scene = manager.get::<SceneManager>().get_scene();
+                    let selection = manager.get::<SelectionManager>().selected();
+                    let draw_manager = manager.get::<DrawManager>();
+                    let cache = manager.get::<CacheManager>();
+                    let mut plan = draw_manager.plan(scene, camera, Some(cache));
+                    let qm = manager.get::<QualityManager>();
+                    plan.quality = qm.quality();
+                    plan.coarse_density = qm.coarse_density(camera);
+
+                    rm.render(|pass, pixels| {
+                        draw_manager.draw_pass(scene, camera, pass, selection, &plan, cache, pixels)
+                    });
+                });
+                let elapsed = start.elapsed();
+                let camera = *manager.get::<CameraManager>().get_camera();
+                manager.get_mut::<QualityManager>().record(elapsed, &camera);
+                manager
+                    .get_mut::<EventManager>()
+                    .publish(FrameRendered { elapsed });
+            }
+            Self::RenderOffscreen(width, height) => {
+                let draw = manager.get::<DrawManager>();
+                let camera = manager.get::<CameraManager>().get_camera();
+                let scene = manager.get::<SceneManager>().get_scene();
 
-                draw.draw_scene(scene, camera)
+                return DrawCommandReturn::Image(
+                    draw.render_offscreen(scene, camera, width, height),
+                );
+            }
+            Self::RenderView(view, [width, height]) => {
+                let camera = match view_camera(manager, &view) {
+                    Ok(camera) => camera,
+                    Err(err) => {
+                        manager
+                            .get_mut::<DiagnosticsManager>()
+                            .warning(None, format!("failed to render a view: {err}"));
+                        return DrawCommandReturn::Error(err);
+                    }
+                };
+                let draw = manager.get::<DrawManager>();
+                let scene = manager.get::<SceneManager>().get_scene();
+                return DrawCommandReturn::Image(
+                    draw.render_offscreen(scene, &camera, width, height),
+                );
+            }
+            Self::ExportSvg(path, size) => {
+                let draw = manager.get::<DrawManager>();
+                let camera = manager.get::<CameraManager>().get_camera();
+                let scene = manager.get::<SceneManager>().get_scene();
+                let svg = draw.export_svg(scene, camera, size);
+                if let Err(err) = fs::write(&path, svg) {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(None, format!("failed to save {}: {err}", path.display()));
+                    return DrawCommandReturn::Error(err.to_string());
+                }
+            }
+            Self::Screenshot(path, size) => {
+                let target = match render_current(manager, size) {
+                    Ok(target) => target,
+                    Err(err) => {
+                        manager
+                            .get_mut::<DiagnosticsManager>()
+                            .error(None, format!("failed to save a screenshot: {err}"));
+                        return DrawCommandReturn::Error(err);
+                    }
+                };
+                if let Err(err) = target.save_png(&path) {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(None, format!("failed to save {}: {err}", path.display()));
+                    return DrawCommandReturn::Error(err.to_string());
+                }
+            }
+            Self::CopyToClipboard(size) => {
+                let copied = render_current(manager, size).and_then(|target| {
+                    manager
+                        .get_mut::<DrawManager>()
+                        .copy_to_clipboard(&target)
+                        .map_err(|err| err.to_string())
+                });
+                if let Err(err) = copied {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(None, format!("failed to copy the render: {err}"));
+                    return DrawCommandReturn::Error(err);
+                }
             }
         }
+        DrawCommandReturn::Nothing
     }
 }
@@ -0,0 +1,52 @@
+use crate::facade::Command;
+use crate::managers::event_manager::{Event, EventManager, Subscription};
+use crate::managers::ManagerSolution;
+
+pub enum EventCommandReturn<E> {
+    Nothing,
+    Subscribed(Subscription<E>),
+    Events(Vec<E>),
+}
+
+impl<E> EventCommandReturn<E> {
+    #[inline]
+    pub fn as_subscription(&self) -> Option<Subscription<E>> {
+        if let Self::Subscribed(subscription) = self {
+            return Some(*subscription);
+        }
+        None
+    }
+
+    #[inline]
+    pub fn into_events(self) -> Vec<E> {
+        if let Self::Events(events) = self {
+            return events;
+        }
+        Vec::new()
+    }
+}
+
+/// Access to the channel of `E`, see [`EventManager`]
+pub enum EventCommand<E: Event> {
+    Subscribe,
+    Unsubscribe(Subscription<E>),
+    /// Events published since the previous read
+    Read(Subscription<E>),
+    Publish(E),
+}
+
+impl<E: Event> Command for EventCommand<E> {
+    type ReturnType = EventCommandReturn<E>;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        let events = manager.get_mut::<EventManager>();
+        match self {
+            EventCommand::Subscribe => return EventCommandReturn::Subscribed(events.subscribe()),
+            EventCommand::Unsubscribe(subscription) => events.unsubscribe(subscription),
+            EventCommand::Read(subscription) => {
+                return EventCommandReturn::Events(events.read(subscription))
+            }
+            EventCommand::Publish(event) => events.publish(event),
+        }
+        EventCommandReturn::Nothing
+    }
+}
@@ -0,0 +1,149 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::facade::command::scene_command::replace_cloud;
+use crate::facade::Command;
+use crate::managers::diagnostics_manager::DiagnosticsManager;
+use crate::managers::event_manager::{EventManager, ParamChanged};
+use crate::managers::history_manager::{diff, HistoryManager, ParamDiff, Version};
+use crate::managers::scene_manager::SceneManager;
+use crate::managers::ManagerSolution;
+use crate::object::objects::cloud::CloudBuilder;
+use crate::object::Component;
+
+pub enum HistoryCommandReturn {
+    Nothing,
+    Versions(Vec<Version>),
+    Diff(Vec<ParamDiff>),
+    Error(String),
+}
+
+impl HistoryCommandReturn {
+    #[inline]
+    pub fn as_versions(&self) -> Option<&[Version]> {
+        if let Self::Versions(versions) = self {
+            return Some(versions);
+        }
+        None
+    }
+
+    #[inline]
+    pub fn as_diff(&self) -> Option<&[ParamDiff]> {
+        if let Self::Diff(diff) = self {
+            return Some(diff);
+        }
+        None
+    }
+}
+
+/// Named versions of the cloud parameters, see [`HistoryManager`]
+pub enum HistoryCommand {
+    /// Stores the parameters of the cloud under the name
+    Commit(&'static str, String),
+    /// Sets the parameters of the version to the cloud
+    Restore(&'static str, usize),
+    /// Changes from the first version to the second one
+    Diff(usize, usize),
+    /// Changes from the version to the current parameters of the cloud
+    DiffCurrent(usize, &'static str),
+    Remove(usize),
+    Save(PathBuf),
+    Load(PathBuf),
+    Query,
+}
+
+fn cloud_params(manager: &ManagerSolution, id: &'static str) -> Result<CloudBuilder, String> {
+    match manager.get::<SceneManager>().get_object(id) {
+        Some(Component::Cloud(cloud)) => Ok(cloud.cloud_params),
+        _ => Err(format!("no cloud named {id}")),
+    }
+}
+
+impl Command for HistoryCommand {
+    type ReturnType = HistoryCommandReturn;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        match self {
+            HistoryCommand::Commit(id, name) => match cloud_params(manager, id) {
+                Ok(params) => {
+                    manager.get_mut::<HistoryManager>().commit(name, params);
+                }
+                Err(err) => {
+                    manager.get_mut::<DiagnosticsManager>().error(
+                        Some(id),
+                        format!("failed to store a version of {id}: {err}"),
+                    );
+                    return HistoryCommandReturn::Error(err);
+                }
+            },
+            HistoryCommand::Restore(id, number) => {
+                let Some(version) = manager.get::<HistoryManager>().get(number) else {
+                    let err = format!("no version {number}");
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(Some(id), format!("failed to restore {id}: {err}"));
+                    return HistoryCommandReturn::Error(err);
+                };
+                if let Err(err) = replace_cloud(manager, id, version.params) {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(Some(id), format!("failed to restore {id}: {err}"));
+                    return HistoryCommandReturn::Error(err);
+                }
+                manager
+                    .get_mut::<EventManager>()
+                    .publish(ParamChanged { id });
+            }
+            HistoryCommand::Diff(old, new) => {
+                return match manager.get::<HistoryManager>().diff(old, new) {
+                    Some(diff) => HistoryCommandReturn::Diff(diff),
+                    None => HistoryCommandReturn::Error(format!("no version {old} or {new}")),
+                };
+            }
+            HistoryCommand::DiffCurrent(number, id) => {
+                let Some(version) = manager.get::<HistoryManager>().get(number) else {
+                    return HistoryCommandReturn::Error(format!("no version {number}"));
+                };
+                let old = version.params;
+                return match cloud_params(manager, id) {
+                    Ok(new) => HistoryCommandReturn::Diff(diff(&old, &new)),
+                    Err(err) => HistoryCommandReturn::Error(err),
+                };
+            }
+            HistoryCommand::Remove(number) => {
+                manager.get_mut::<HistoryManager>().remove(number);
+            }
+            HistoryCommand::Save(path) => {
+                let saved = manager
+                    .get::<HistoryManager>()
+                    .to_ron()
+                    .map_err(|err| err.to_string())
+                    .and_then(|x| fs::write(&path, x).map_err(|err| err.to_string()));
+                if let Err(err) = saved {
+                    manager.get_mut::<DiagnosticsManager>().error(
+                        None,
+                        format!("failed to save versions to {}: {err}", path.display()),
+                    );
+                    return HistoryCommandReturn::Error(err);
+                }
+            }
+            HistoryCommand::Load(path) => {
+                let history = fs::read_to_string(&path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|x| HistoryManager::from_ron(&x).map_err(|err| err.to_string()));
+                match history {
+                    Ok(history) => manager.register(history),
+                    Err(err) => {
+                        manager.get_mut::<DiagnosticsManager>().error(
+                            None,
+                            format!("failed to load versions from {}: {err}", path.display()),
+                        );
+                        return HistoryCommandReturn::Error(err);
+                    }
+                }
+            }
+            HistoryCommand::Query => {}
+        }
+        let versions = manager.get::<HistoryManager>().versions().to_vec();
+        HistoryCommandReturn::Versions(versions)
+    }
+}
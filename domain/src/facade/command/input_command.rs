@@ -0,0 +1,104 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::facade::command::draw_command::DrawCommand;
+use crate::facade::Command;
+use crate::managers::camera_manager::CameraManager;
+use crate::managers::diagnostics_manager::DiagnosticsManager;
+use crate::managers::draw_manager::DrawManager;
+use crate::managers::input_manager::InputManager;
+use crate::managers::input_manager::{Action, InputConfig, InputEvent, InputFrame};
+use crate::managers::ManagerSolution;
+use crate::scene::scene_composite::DEBUG_LAYER;
+
+pub enum InputCommandReturn {
+    Nothing,
+    /// Actions triggered by the input, the camera, the debug layer and the
+    /// clipboard ones are already carried out
+    Events(Vec<InputEvent>),
+    Error(String),
+}
+
+impl InputCommandReturn {
+    #[inline]
+    pub fn as_events(&self) -> Option<&[InputEvent]> {
+        if let Self::Events(events) = self {
+            return Some(events);
+        }
+        None
+    }
+}
+
+pub enum InputCommand {
+    /// Maps the input of the frame to actions and performs them
+    Handle(InputFrame),
+    SetConfig(InputConfig),
+    /// Reads the bindings from a RON file
+    LoadConfig(PathBuf),
+    SaveConfig(PathBuf),
+}
+
+impl Command for InputCommand {
+    type ReturnType = InputCommandReturn;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        match self {
+            InputCommand::Handle(frame) => {
+                let events = manager.get::<InputManager>().events(&frame);
+                for event in &events {
+                    let (x, y) = (event.amount.x, event.amount.y);
+                    let camera = manager.get_mut::<CameraManager>().get_mut_camera();
+                    match event.action {
+                        Action::Orbit => camera.pivot(x, y),
+                        Action::Pan => camera.pan(x, y),
+                        Action::Zoom => camera.zoom(-y),
+                        Action::ToggleDebug => {
+                            let dm = manager.get_mut::<DrawManager>();
+                            let visible = dm.is_layer_visible(DEBUG_LAYER);
+                            dm.set_layer_visible(DEBUG_LAYER, !visible);
+                        }
+                        // The caller picks the file, see `DrawCommand::Screenshot`
+                        Action::Screenshot => {}
+                        Action::CopyToClipboard => {
+                            DrawCommand::CopyToClipboard(None).exec(manager);
+                        }
+                    }
+                }
+                return InputCommandReturn::Events(events);
+            }
+            InputCommand::SetConfig(config) => {
+                manager.get_mut::<InputManager>().set_config(config);
+            }
+            InputCommand::LoadConfig(path) => {
+                let config = fs::read_to_string(&path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|x| InputConfig::from_ron(&x).map_err(|err| err.to_string()));
+                match config {
+                    Ok(config) => manager.get_mut::<InputManager>().set_config(config),
+                    Err(err) => {
+                        manager.get_mut::<DiagnosticsManager>().error(
+                            None,
+                            format!("failed to load bindings from {}: {err}", path.display()),
+                        );
+                        return InputCommandReturn::Error(err);
+                    }
+                }
+            }
+            InputCommand::SaveConfig(path) => {
+                let saved = manager
+                    .get::<InputManager>()
+                    .config()
+                    .to_ron()
+                    .map_err(|err| err.to_string())
+                    .and_then(|x| fs::write(&path, x).map_err(|err| err.to_string()));
+                if let Err(err) = saved {
+                    manager.get_mut::<DiagnosticsManager>().error(
+                        None,
+                        format!("failed to save bindings to {}: {err}", path.display()),
+                    );
+                    return InputCommandReturn::Error(err);
+                }
+            }
+        }
+        InputCommandReturn::Nothing
+    }
+}
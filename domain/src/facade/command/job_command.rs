@@ -0,0 +1,396 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::canvas::clip::ClipFormat;
+use crate::canvas::frame_sequence::{FrameSequence, SequenceSettings};
+use crate::facade::command::scene_command::forget_removed;
+use crate::facade::Command;
+use crate::io::batch::{load_batch, BatchRender};
+use crate::io::obj::save_obj;
+use crate::io::scene::load_scene;
+use crate::io::stl::save_stl;
+use crate::io::volume::DensityVolume;
+use crate::managers::animation_manager::AnimationManager;
+use crate::managers::camera_manager::CameraManager;
+use crate::managers::diagnostics_manager::DiagnosticsManager;
+use crate::managers::draw_manager::DrawManager;
+use crate::managers::draw_manager::{export_gltf, render_hdr, render_offscreen};
+use crate::managers::event_manager::{EventManager, JobFinished, ParamChanged};
+use crate::managers::job_manager::JobManager;
+use crate::managers::job_manager::{JobId, JobInfo, JobOutput};
+use crate::managers::resource_manager::ResourceManager;
+use crate::managers::scene_manager::SceneManager;
+use crate::managers::ManagerSolution;
+use crate::object::camera::Camera;
+use crate::object::objects::texture3d::NoiseBuilder;
+use crate::object::objects::Cloud;
+use crate::object::Component;
+use crate::scene::scene::Scene;
+
+/// Where [`JobCommand::ExtractIsosurface`] puts the mesh
+#[derive(Debug, Clone, PartialEq)]
+pub enum IsosurfaceTarget {
+    /// Object of the scene with the given name, attached to the cloud
+    Scene(&'static str),
+    Obj(PathBuf),
+    Stl(PathBuf),
+}
+
+pub enum JobCommandReturn {
+    Nothing,
+    Started(JobId),
+    /// Jobs still running
+    Jobs(Vec<JobInfo>),
+}
+
+impl JobCommandReturn {
+    #[inline]
+    pub fn as_jobs(&self) -> Option<&[JobInfo]> {
+        if let Self::Jobs(jobs) = self {
+            return Some(jobs);
+        }
+        None
+    }
+}
+
+/// Work done away from the frame, see [`JobManager`]
+///
+/// [`JobManager`]: crate::managers::job_manager::JobManager
+pub enum JobCommand {
+    /// Generates the shape noise of the cloud and swaps it in when done
+    RegenerateNoise(&'static str, NoiseBuilder),
+    RegenerateDetailNoise(&'static str, NoiseBuilder),
+    /// Renders the current scene at the resolution into a PNG file
+    RenderToFile([usize; 2], PathBuf),
+    /// Writes the linear light and the transmittance of the clouds into an
+    /// OpenEXR file
+    RenderHdr([usize; 2], PathBuf),
+    /// Renders the images listed in the batch config one after another, see
+    /// [`crate::io::batch`]
+    RenderBatch(PathBuf),
+    /// Renders the animation into numbered PNG frames in the directory
+    RenderSequence(SequenceSettings, PathBuf),
+    /// Renders the animation into an animated clip
+    RenderClip(SequenceSettings, ClipFormat, PathBuf),
+    /// Writes the layout of the scene seen from the camera as glTF, with the
+    /// density volumes of the clouds next to it
+    ExportGltf([usize; 2], PathBuf),
+    /// Samples the density of the cloud into a grid of the given size and
+    /// writes it as raw voxels with a JSON description next to them
+    ExportVolume(&'static str, [usize; 3], PathBuf),
+    /// Meshes the surface where the density of the cloud sampled into a grid
+    /// of the given size crosses the threshold
+    ExtractIsosurface(&'static str, [usize; 3], f32, IsosurfaceTarget),
+    Cancel(JobId),
+    /// Applies the results of the finished jobs, call once per frame
+    Poll,
+    Query,
+}
+
+impl Command for JobCommand {
+    type ReturnType = JobCommandReturn;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        match self {
+            JobCommand::RegenerateNoise(id, builder) => {
+                let pool = manager.get::<ResourceManager>().pool();
+                let jm = manager.get_mut::<JobManager>();
+                let job = jm.spawn(format!("noise {id}"), move |_| {
+                    Ok(JobOutput::Noise(id, builder, pool.build(builder)))
+                });
+                return JobCommandReturn::Started(job);
+            }
+            JobCommand::RegenerateDetailNoise(id, builder) => {
+                let pool = manager.get::<ResourceManager>().pool();
+                let jm = manager.get_mut::<JobManager>();
+                let job = jm.spawn(format!("detail noise {id}"), move |_| {
+                    Ok(JobOutput::DetailNoise(id, builder, pool.build(builder)))
+                });
+                return JobCommandReturn::Started(job);
+            }
+            JobCommand::RenderToFile(size, path) => {
+                let scene = manager.get::<SceneManager>().get_scene().clone();
+                let camera = *manager.get::<CameraManager>().get_camera();
+                let hidden_layers = manager.get::<DrawManager>().hidden_layers().clone();
+                let name = format!("render {}", path.display());
+                let job = manager.get_mut::<JobManager>().spawn(name, move |_| {
+                    let target = render_offscreen(&scene, &camera, size, hidden_layers);
+                    target
+                        .save_png(&path)
+                        .map_err(|err| format!("{}: {err}", path.display()))?;
+                    Ok(JobOutput::Nothing)
+                });
+                return JobCommandReturn::Started(job);
+            }
+            JobCommand::RenderHdr(size, path) => {
+                let scene = manager.get::<SceneManager>().get_scene().clone();
+                let camera = *manager.get::<CameraManager>().get_camera();
+                let hidden_layers = manager.get::<DrawManager>().hidden_layers().clone();
+                let name = format!("hdr {}", path.display());
+                let job = manager.get_mut::<JobManager>().spawn(name, move |_| {
+                    let target = render_hdr(&scene, &camera, size, hidden_layers);
+                    target
+                        .save_exr(&path)
+                        .map_err(|err| format!("{}: {err}", path.display()))?;
+                    Ok(JobOutput::Nothing)
+                });
+                return JobCommandReturn::Started(job);
+            }
+            JobCommand::RenderBatch(path) => {
+                let config = match load_batch(&path) {
+                    Ok(config) => config,
+                    Err(err) => {
+                        manager
+                            .get_mut::<DiagnosticsManager>()
+                            .error(None, format!("failed to load {}: {err}", path.display()));
+                        return JobCommandReturn::Nothing;
+                    }
+                };
+                // Missing cameras are reported before anything is rendered
+                let cm = manager.get::<CameraManager>();
+                let cameras = config
+                    .renders
+                    .iter()
+                    .map(|render| match &render.camera {
+                        Some(name) => cm
+                            .preset(name)
+                            .copied()
+                            .ok_or_else(|| format!("no camera preset named {name}")),
+                        None => Ok(*cm.get_camera()),
+                    })
+                    .collect::<Result<Vec<Camera>, _>>();
+                let cameras = match cameras {
+                    Ok(cameras) => cameras,
+                    Err(err) => {
+                        manager
+                            .get_mut::<DiagnosticsManager>()
+                            .error(None, format!("failed to start {}: {err}", path.display()));
+                        return JobCommandReturn::Nothing;
+                    }
+                };
+                let current = Ok(manager.get::<SceneManager>().get_scene().clone());
+                let hidden_layers = manager.get::<DrawManager>().hidden_layers().clone();
+                let name = format!("batch {}", path.display());
+                let job = manager.get_mut::<JobManager>().spawn(name, move |ctx| {
+                    // Scene files are loaded once for all of their cameras
+                    let mut scenes = BTreeMap::new();
+                    let mut errors = Vec::new();
+                    let count = config.renders.len();
+                    for (i, (render, camera)) in config.renders.iter().zip(cameras).enumerate() {
+                        if ctx.is_cancelled() {
+                            break;
+                        }
+                        let scene = match &render.scene {
+                            Some(path) => &*scenes.entry(path).or_insert_with(|| {
+                                load_scene(path)
+                                    .map(|objects| Scene { objects })
+                                    .map_err(|err| format!("{}: {err}", path.display()))
+                            }),
+                            None => &current,
+                        };
+                        let result = scene.as_ref().map_err(Clone::clone).and_then(|scene| {
+                            render_batch_entry(scene, &camera, render, hidden_layers.clone())
+                        });
+                        if let Err(err) = result {
+                            errors.push(err);
+                        }
+                        ctx.set_progress((i + 1) as f32 / count as f32);
+                    }
+                    if !errors.is_empty() {
+                        return Err(errors.join("; "));
+                    }
+                    Ok(JobOutput::Nothing)
+                });
+                return JobCommandReturn::Started(job);
+            }
+            JobCommand::ExportGltf(size, path) => {
+                let scene = manager.get::<SceneManager>().get_scene();
+                let camera = manager.get::<CameraManager>().get_camera();
+                let hidden_layers = manager.get::<DrawManager>().hidden_layers().clone();
+                let document = export_gltf(scene, camera, size, hidden_layers);
+                let name = format!("gltf {}", path.display());
+                let job = manager.get_mut::<JobManager>().spawn(name, move |ctx| {
+                    document.save(&path, ctx)?;
+                    Ok(JobOutput::Nothing)
+                });
+                return JobCommandReturn::Started(job);
+            }
+            JobCommand::ExportVolume(id, size, path) => {
+                let Some(cloud) = cloud_copy(manager, id) else {
+                    return JobCommandReturn::Nothing;
+                };
+                let name = format!("volume {}", path.display());
+                let job = manager.get_mut::<JobManager>().spawn(name, move |ctx| {
+                    if let Some(volume) = DensityVolume::sample(&cloud, size, ctx) {
+                        volume
+                            .save_raw(&path)
+                            .map_err(|err| format!("{}: {err}", path.display()))?;
+                    }
+                    Ok(JobOutput::Nothing)
+                });
+                return JobCommandReturn::Started(job);
+            }
+            JobCommand::ExtractIsosurface(id, size, threshold, target) => {
+                let Some(cloud) = cloud_copy(manager, id) else {
+                    return JobCommandReturn::Nothing;
+                };
+                let name = format!("isosurface {id}");
+                let job = manager.get_mut::<JobManager>().spawn(name, move |ctx| {
+                    let Some(volume) = DensityVolume::sample(&cloud, size, ctx) else {
+                        return Ok(JobOutput::Nothing);
+                    };
+                    let mesh = volume.isosurface(threshold);
+                    let error = |path: &PathBuf, err| format!("{}: {err}", path.display());
+                    match target {
+                        IsosurfaceTarget::Scene(name) => {
+                            return Ok(JobOutput::Mesh(name, id, mesh))
+                        }
+                        IsosurfaceTarget::Obj(path) => {
+                            save_obj(&path, &mesh).map_err(|err| error(&path, err))?
+                        }
+                        IsosurfaceTarget::Stl(path) => {
+                            save_stl(&path, &mesh).map_err(|err| error(&path, err))?
+                        }
+                    }
+                    Ok(JobOutput::Nothing)
+                });
+                return JobCommandReturn::Started(job);
+            }
+            JobCommand::RenderSequence(settings, dir) => {
+                let sequence = frame_sequence(manager, settings);
+                let name = format!("sequence {}", dir.display());
+                let job = manager.get_mut::<JobManager>().spawn(name, move |ctx| {
+                    sequence.write_png(ctx, &dir)?;
+                    Ok(JobOutput::Nothing)
+                });
+                return JobCommandReturn::Started(job);
+            }
+            JobCommand::RenderClip(settings, format, path) => {
+                let sequence = frame_sequence(manager, settings);
+                let name = format!("clip {}", path.display());
+                let job = manager.get_mut::<JobManager>().spawn(name, move |ctx| {
+                    sequence.write_clip(ctx, format, &path)?;
+                    Ok(JobOutput::Nothing)
+                });
+                return JobCommandReturn::Started(job);
+            }
+            JobCommand::Cancel(job) => manager.get_mut::<JobManager>().cancel(job),
+            JobCommand::Poll => {
+                // Replaced volumes go to the pool once the frames drop them
+                manager.get_mut::<ResourceManager>().collect();
+                for (job, name, result) in manager.get_mut::<JobManager>().take_finished() {
+                    let error = match result {
+                        Ok(output) => {
+                            apply_output(manager, output);
+                            None
+                        }
+                        Err(err) => {
+                            manager
+                                .get_mut::<DiagnosticsManager>()
+                                .error(None, format!("job {name} failed: {err}"));
+                            Some(err)
+                        }
+                    };
+                    let event = JobFinished { job, name, error };
+                    manager.get_mut::<EventManager>().publish(event);
+                }
+                return JobCommandReturn::Jobs(manager.get::<JobManager>().jobs());
+            }
+            JobCommand::Query => return JobCommandReturn::Jobs(manager.get::<JobManager>().jobs()),
+        }
+        JobCommandReturn::Nothing
+    }
+}
+
+/// Copy of the scene and its animation rendered away from the frame
+fn frame_sequence(manager: &ManagerSolution, settings: SequenceSettings) -> FrameSequence {
+    let scene = manager.get::<SceneManager>().get_scene().clone();
+    let camera = *manager.get::<CameraManager>().get_camera();
+    let animation = manager.get::<AnimationManager>().clone();
+    let hidden_layers = manager.get::<DrawManager>().hidden_layers().clone();
+    FrameSequence::new(scene, camera, animation, hidden_layers, settings)
+}
+
+/// Renders the entry of a batch into its output, creating its directory
+fn render_batch_entry(
+    scene: &Scene,
+    camera: &Camera,
+    render: &BatchRender,
+    hidden_layers: BTreeSet<&'static str>,
+) -> Result<(), String> {
+    let output = &render.output;
+    let error = |err: std::io::Error| format!("{}: {err}", output.display());
+    if let Some(dir) = output.parent() {
+        fs::create_dir_all(dir).map_err(error)?;
+    }
+    if render.is_hdr() {
+        let target = render_hdr(scene, camera, render.size, hidden_layers);
+        target
+            .save_exr(output)
+            .map_err(|err| format!("{}: {err}", output.display()))
+    } else {
+        let target = render_offscreen(scene, camera, render.size, hidden_layers);
+        target
+            .save_png(output)
+            .map_err(|err| format!("{}: {err}", output.display()))
+    }
+}
+
+/// Copy of the cloud for a job, reports when there is no such cloud
+fn cloud_copy(manager: &mut ManagerSolution, id: &'static str) -> Option<Cloud> {
+    if let Some(Component::Cloud(cloud)) = manager.get::<SceneManager>().get_object(id) {
+        return Some(Cloud::clone(cloud));
+    }
+    manager
+        .get_mut::<DiagnosticsManager>()
+        .error(Some(id), format!("no cloud named {id}"));
+    None
+}
+
+fn apply_output(manager: &mut ManagerSolution, output: JobOutput) {
+    let changed = match output {
+        JobOutput::Noise(id, builder, noise) => {
+            let volume = manager
+                .get_mut::<ResourceManager>()
+                .insert_volume(builder, noise);
+            if let Some(Component::Cloud(cloud)) =
+                manager.get_mut::<SceneManager>().get_mut_object(id)
+            {
+                let old = cloud.set_noise(builder, volume);
+                manager.get_mut::<ResourceManager>().retire(old);
+            }
+            Some(id)
+        }
+        JobOutput::DetailNoise(id, builder, noise) => {
+            let volume = manager
+                .get_mut::<ResourceManager>()
+                .insert_volume(builder, noise);
+            if let Some(Component::Cloud(cloud)) =
+                manager.get_mut::<SceneManager>().get_mut_object(id)
+            {
+                let old = cloud.set_detail_noise(builder, volume);
+                manager.get_mut::<ResourceManager>().retire(old);
+            }
+            Some(id)
+        }
+        JobOutput::Mesh(name, parent, mesh) => {
+            let removed = manager.get_mut::<SceneManager>().remove_object(name);
+            forget_removed(manager, &removed);
+            let sm = manager.get_mut::<SceneManager>();
+            sm.add_object(name, mesh);
+            if let Err(err) = sm.set_parent(name, parent) {
+                manager
+                    .get_mut::<DiagnosticsManager>()
+                    .warning(Some(name), err);
+            }
+            Some(name)
+        }
+        JobOutput::Nothing => None,
+    };
+    if let Some(id) = changed {
+        manager
+            .get_mut::<EventManager>()
+            .publish(ParamChanged { id });
+    }
+    manager.get_mut::<ResourceManager>().collect();
+}
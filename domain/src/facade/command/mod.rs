@@ -1,11 +1,35 @@
+mod animation_command;
+mod cache_command;
 mod camera_command;
+mod diagnostics_command;
 mod draw_command;
+mod event_command;
+mod history_command;
+mod input_command;
+mod job_command;
+mod profiling_command;
 mod scene_command;
+mod script_command;
+mod selection_command;
+mod settings_command;
+mod time_command;
 
 use crate::managers::ManagerSolution;
-pub use camera_command::CameraCommand;
-pub use draw_command::DrawCommand;
+pub use animation_command::{animate, AnimationCommand, AnimationState};
+pub use cache_command::CacheCommand;
+pub use camera_command::{CameraCommand, CameraCommandReturn};
+pub use diagnostics_command::DiagnosticsCommand;
+pub use draw_command::{DrawCommand, DrawCommandReturn, View};
+pub use event_command::{EventCommand, EventCommandReturn};
+pub use history_command::{HistoryCommand, HistoryCommandReturn};
+pub use input_command::{InputCommand, InputCommandReturn};
+pub use job_command::{IsosurfaceTarget, JobCommand, JobCommandReturn};
+pub use profiling_command::{ProfilingCommand, ProfilingCommandReturn};
 pub use scene_command::SceneCommand;
+pub use script_command::{ScriptCommand, ScriptCommandReturn};
+pub use selection_command::{SelectionCommand, SelectionState};
+pub use settings_command::{SettingsCommand, SettingsCommandReturn};
+pub use time_command::TimeCommand;
 
 pub trait Command: Sized + Send + Sync {
     type ReturnType;
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+
+use crate::facade::Command;
+use crate::managers::camera_manager::CameraManager;
+use crate::managers::diagnostics_manager::DiagnosticsManager;
+use crate::managers::profiling_manager::ProfilingManager;
+use crate::managers::profiling_manager::{FrameProfile, Stage};
+use crate::managers::scene_manager::SceneManager;
+use crate::managers::ManagerSolution;
+use crate::visitor::stats_visitor::{SceneStats, StatsVisitor};
+use crate::visitor::Visitor;
+
+pub enum ProfilingCommandReturn {
+    Nothing,
+    /// Timings of the finished frame
+    Frame(FrameProfile),
+    /// Mean time of every stage over the history
+    Averages(FrameProfile),
+    /// Stage timings from the oldest frame
+    History(Vec<FrameProfile>),
+    SceneStats(SceneStats),
+    Error(String),
+}
+
+impl ProfilingCommandReturn {
+    #[inline]
+    pub fn as_profile(&self) -> Option<&FrameProfile> {
+        match self {
+            Self::Frame(profile) | Self::Averages(profile) => Some(profile),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    pub fn as_scene_stats(&self) -> Option<&SceneStats> {
+        if let Self::SceneStats(stats) = self {
+            return Some(stats);
+        }
+        None
+    }
+
+    #[inline]
+    pub fn as_history(&self) -> Option<&[FrameProfile]> {
+        if let Self::History(history) = self {
+            return Some(history);
+        }
+        None
+    }
+}
+
+pub enum ProfilingCommand {
+    SetEnabled(bool),
+    /// Closes the frame, call once per frame after drawing
+    EndFrame,
+    QueryAverages,
+    QueryHistory,
+    /// Primitives, raymarch cost and memory of the visible objects seen by
+    /// the camera on a canvas of the given size
+    QuerySceneStats([f32; 2]),
+    Clear,
+    /// Writes the history as CSV in milliseconds
+    SaveCsv(PathBuf),
+}
+
+impl Command for ProfilingCommand {
+    type ReturnType = ProfilingCommandReturn;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        let pm = manager.get_mut::<ProfilingManager>();
+        match self {
+            ProfilingCommand::SetEnabled(enabled) => pm.set_enabled(enabled),
+            ProfilingCommand::EndFrame => return ProfilingCommandReturn::Frame(pm.end_frame()),
+            ProfilingCommand::QueryAverages => {
+                let mut profile = FrameProfile::default();
+                for stage in Stage::ALL {
+                    profile.stages[stage as usize] = pm.average(stage);
+                }
+                return ProfilingCommandReturn::Averages(profile);
+            }
+            ProfilingCommand::QueryHistory => {
+                return ProfilingCommandReturn::History(pm.history().iter().copied().collect());
+            }
+            ProfilingCommand::QuerySceneStats([width, height]) => {
+                let camera = manager.get::<CameraManager>().get_camera();
+                let scene = manager.get::<SceneManager>().get_scene();
+                let objects =
+                    StatsVisitor::new(camera, width, height).visit_composite(&scene.objects);
+                return ProfilingCommandReturn::SceneStats(SceneStats { objects });
+            }
+            ProfilingCommand::Clear => pm.clear(),
+            ProfilingCommand::SaveCsv(path) => {
+                if let Err(err) = std::fs::write(&path, pm.to_csv()) {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(None, format!("failed to save {}: {err}", path.display()));
+                    return ProfilingCommandReturn::Error(err.to_string());
+                }
+            }
+        }
+        ProfilingCommandReturn::Nothing
+    }
+}
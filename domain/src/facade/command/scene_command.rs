@@ -1,16 +1,72 @@
+use std::path::PathBuf;
+
 use egui::Color32;
-use log::debug;
 
+use crate::facade::command::diagnostics_command::validate_scene;
 use crate::facade::Command;
+use crate::io::preset::{load_preset, save_preset};
+use crate::io::shader::{save_shader, ShaderLanguage};
+use crate::io::unity::load_unity;
+use crate::managers::cache_manager::CacheManager;
+use crate::managers::camera_manager::CameraManager;
+use crate::managers::diagnostics_manager::DiagnosticsManager;
+use crate::managers::draw_manager::DrawManager;
+use crate::managers::event_manager::{EventManager, ObjectAdded, ParamChanged};
+use crate::managers::resource_manager::ResourceManager;
+use crate::managers::scene_manager::SceneManager;
+use crate::managers::scene_manager::{ComponentSnapshot, ObjectInfo};
+use crate::managers::selection_manager::SelectionManager;
+use crate::managers::settings_manager::SettingsManager;
 use crate::managers::ManagerSolution;
+use crate::object::objects::cloud::CloudBuilder;
+use crate::object::objects::{
+    Background, BoundingBox, Cloud, Fog, GizmoDrag, GizmoMode, GridPlane, HeightMap, Light, Skybox,
+};
 use crate::object::Component;
+use crate::scene::pick::HitRecord;
+use crate::scene::ObjectTransform;
 use crate::object::objects::texture3d::{NoiseBuilder};
 
 pub enum SceneCommandReturn {
     Nothing,
     SunPos(glam::Vec3),
+    Scene(Vec<ObjectInfo>),
+    Hits(Vec<HitRecord>),
+    /// Whether a gizmo handle was grabbed
+    Grabbed(bool),
+    Snapshot(ComponentSnapshot),
+    Error(String),
 }
 impl SceneCommandReturn {
+    #[inline]
+    pub fn as_scene(&self) -> Option<&[ObjectInfo]> {
+        if let Self::Scene(objects) = self {
+            return Some(objects);
+        }
+        None
+    }
+
+    #[inline]
+    pub fn as_hits(&self) -> Option<&[HitRecord]> {
+        if let Self::Hits(hits) = self {
+            return Some(hits);
+        }
+        None
+    }
+
+    #[inline]
+    pub fn as_snapshot(&self) -> Option<&ComponentSnapshot> {
+        if let Self::Snapshot(snapshot) = self {
+            return Some(snapshot);
+        }
+        None
+    }
+
+    #[inline]
+    pub fn is_grabbed(&self) -> bool {
+        matches!(self, Self::Grabbed(true))
+    }
+
     #[inline]
     pub fn as_sun_pos(&self) -> Option<glam::Vec3> {
         if let Self::SunPos(vec) = *self {
@@ -21,17 +77,79 @@ impl SceneCommandReturn {
 }
 pub enum SceneCommand {
     AddObject(&'static str, Component),
+    /// Loads a Wavefront OBJ model as a mesh object
+    AddObjectFromFile(&'static str, PathBuf),
+    /// Adds a cloud sharing the noise with the clouds built from the same
+    /// noise builders
+    AddCloud(&'static str, Box<CloudBuilder>),
+    /// Adds an equirectangular panorama, the image is shared with the other
+    /// skyboxes loaded from the same file
+    AddSkyboxFromFile(&'static str, PathBuf),
     GetObject(Component),
-    RemoveObject(Component),
+    /// Copies the parameters of the object, see [`ComponentSnapshot`]
+    GetObjectSnapshot(&'static str),
+    /// Lists all objects of the scene
+    QueryScene,
+    /// Writes the scene to a `.ron` or `.json` file
+    SaveScene(PathBuf),
+    /// Casts a camera ray through the pointer inside the canvas rect and
+    /// returns the objects it hits, nearest first
+    Pick(egui::Rect, egui::Pos2),
+    /// Replaces the scene with the contents of a `.ron` or `.json` file
+    LoadScene(PathBuf),
+    /// Writes every parameter of the cloud with its noise builders to a
+    /// TOML preset
+    SavePreset(&'static str, PathBuf),
+    /// Sets all parameters of the preset to the cloud at once, the cloud is
+    /// left as it was when the file does not parse
+    LoadPreset(&'static str, PathBuf),
+    /// Writes the density and the lighting of the cloud with its current
+    /// parameters as a shader snippet
+    ExportShader(&'static str, ShaderLanguage, PathBuf),
+    /// Reads the cloud settings from a Unity YAML file of the Sebastian Lague
+    /// clouds project over the current parameters of the cloud
+    ImportUnity(&'static str, PathBuf),
+    /// Attaches the first object to the second one
+    SetParent(&'static str, &'static str),
+    DetachObject(&'static str),
+    /// Shows or hides the object together with its descendants
+    SetVisible(&'static str, bool),
+    /// Moves the object to the render layer, see [`DrawCommand::SetLayerVisible`]
+    ///
+    /// [`DrawCommand::SetLayerVisible`]: crate::facade::DrawCommand::SetLayerVisible
+    SetLayer(&'static str, &'static str),
+    SetTransform(&'static str, ObjectTransform),
+    TranslateObject(&'static str, glam::Vec3),
+    RotateObject(&'static str, glam::Quat),
+    ScaleObject(&'static str, glam::Vec3),
+    /// Removes the object with its descendants
+    RemoveObject(&'static str),
+    /// Removes every object of the given kind, see [`Component::kind`]
+    RemoveObjectsOfKind(&'static str),
+    ClearScene,
     SetNumSteps(&'static str, usize),
     SetNumStepsLight(&'static str, usize),
+    /// Steps of the view rays by their path through the cloud, see
+    /// [`CloudBuilder::auto_steps`]
+    SetAutoSteps(&'static str, bool),
+    /// View ray steps between the light marches, see
+    /// [`CloudBuilder::light_interval`]
+    SetLightInterval(&'static str, usize),
     SetCloudScale(&'static str, f32),
     SetDensityMultiplier(&'static str, f32),
     SetDensityThreshold(&'static str, f32),
     SetDensityOffset(&'static str, f32),
     SetOffset(&'static str, glam::Vec3),
+    /// Sets the drift of the cloud noise per second, see [`TimeCommand::Tick`]
+    ///
+    /// [`TimeCommand::Tick`]: crate::facade::TimeCommand::Tick
+    SetWind(&'static str, glam::Vec3),
     SetAlphaThreshold(&'static str, u8),
     MoveBoundingBox(&'static str, glam::Vec3),
+    /// Replaces the cloud volume with the box between two corners
+    SetCloudBounds(&'static str, glam::Vec3, glam::Vec3),
+    /// Shifts the cloud volume by the offset
+    MoveCloud(&'static str, glam::Vec3),
     ExtendBoundingBox(&'static str, glam::Vec3),
     SetNoise(&'static str, NoiseBuilder),
     SetDetailNoise(&'static str, NoiseBuilder),
@@ -46,7 +164,11 @@ pub enum SceneCommand {
     SetLightAbsorptionThroughCloud(&'static str, f32),
     SetDarknessThreshold(&'static str, f32),
     SetRayOffsetStrength(&'static str, f32),
+    /// Sets the light color of a cloud or a light
     SetLightColor(&'static str, Color32),
+    SetLight(&'static str, Light),
+    SetLightIntensity(&'static str, f32),
+    SetLightAbsorption(&'static str, f32),
     SetColA(&'static str, Color32),
     SetColB(&'static str, Color32),
     SetHeightMapFactor(&'static str, f32),
@@ -54,6 +176,13 @@ pub enum SceneCommand {
     SetEdgeDistance(&'static str, f32),
     SetSunDistance(&'static str, f32),
     SetSunAngle(&'static str, glam::Vec2),
+    /// Sets the color of the sunlight, see [`Sun::temperature_color`]
+    ///
+    /// [`Sun::temperature_color`]: crate::object::objects::Sun::temperature_color
+    SetSunColor(&'static str, Color32),
+    SetSunIntensity(&'static str, f32),
+    /// Sets the seconds of a full day cycle of the sun, zero stops it
+    SetDayLength(&'static str, f32),
     GetSunPos(&'static str),
     SetTerrainScale(&'static str, usize),
     SetTerrainNoise(&'static str, NoiseBuilder),
@@ -64,206 +193,669 @@ pub enum SceneCommand {
     SetTerrainNumShadowsSteps(&'static str, usize),
     SetTerrainDensityScale(&'static str, f32),
     SetTerrainDiffuseFactor(&'static str, f32),
+    SetTerrainSize(&'static str, glam::Vec2),
+    SetTerrainHeight(&'static str, f32),
+    SetTerrainHeightMap(&'static str, Option<HeightMap>),
+    /// Sets the number of base grid cells per grid scale
+    SetGridK(&'static str, i32),
+    SetGridScale(&'static str, f32),
+    /// Sets the distance between the base grid lines keeping `k`
+    SetGridSpacing(&'static str, f32),
+    SetGridPlane(&'static str, GridPlane),
+    /// Sets the major and minor line colors of the grid
+    SetGridColors(&'static str, Color32, Color32),
+    SetBackground(&'static str, Background),
+    SetFog(&'static str, Fog),
+    SetWaterLevel(&'static str, f32),
+    SetWaterColor(&'static str, Color32),
+    SetWaterReflectivity(&'static str, f32),
+    SetWaterWaveAmplitude(&'static str, f32),
+    SetWaterWaveScale(&'static str, f32),
+    SetWaterWaveSpeed(&'static str, f32),
+    AdvanceWater(&'static str, f32),
+    SetSkybox(&'static str, Skybox),
+    /// Attaches the transform gizmo to the object, or hides it
+    SetGizmoTarget(&'static str, Option<&'static str>),
+    SetGizmoMode(&'static str, GizmoMode),
+    /// Grabs the gizmo handle under the pointer inside the canvas rect
+    GrabGizmo(&'static str, egui::Rect, egui::Pos2),
+    /// Moves or turns the target of the gizmo by dragging the grabbed handle
+    /// from the first pointer position to the second one
+    DragGizmo(&'static str, egui::Rect, egui::Pos2, egui::Pos2),
+    ReleaseGizmo(&'static str),
 }
 impl Command for SceneCommand {
     type ReturnType = SceneCommandReturn;
-    #[allow(clippy::collapsible_match)]
     fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        let added = self.added_object();
+        let changed = self.changed_object();
+        let result = self.apply(manager);
+        if let SceneCommandReturn::Error(_) = result {
+            return result;
+        }
+        if let Some(id) = added {
+            let kind = manager
+                .get::<SceneManager>()
+                .get_object(id)
+                .map(Component::kind);
+            if let Some(kind) = kind {
+                manager
+                    .get_mut::<EventManager>()
+                    .publish(ObjectAdded { id, kind });
+            }
+        }
+        let changed = changed.filter(|id| manager.get::<SceneManager>().get_object(id).is_some());
+        if let Some(id) = changed {
+            manager
+                .get_mut::<EventManager>()
+                .publish(ParamChanged { id });
+        }
+        result
+    }
+}
+
+impl SceneCommand {
+    /// Object put into the scene by the command
+    fn added_object(&self) -> Option<&'static str> {
+        match self {
+            Self::AddObject(id, _)
+            | Self::AddObjectFromFile(id, _)
+            | Self::AddCloud(id, _)
+            | Self::AddSkyboxFromFile(id, _) => Some(id),
+            _ => None,
+        }
+    }
+
+    /// Object whose parameters or transform the command changes
+    fn changed_object(&self) -> Option<&'static str> {
+        match self {
+            Self::SetParent(id, ..)
+            | Self::DetachObject(id, ..)
+            | Self::SetVisible(id, ..)
+            | Self::SetLayer(id, ..)
+            | Self::SetTransform(id, ..)
+            | Self::TranslateObject(id, ..)
+            | Self::RotateObject(id, ..)
+            | Self::ScaleObject(id, ..)
+            | Self::SetNumSteps(id, ..)
+            | Self::SetNumStepsLight(id, ..)
+            | Self::SetAutoSteps(id, ..)
+            | Self::SetLightInterval(id, ..)
+            | Self::SetCloudScale(id, ..)
+            | Self::SetDensityMultiplier(id, ..)
+            | Self::SetDensityThreshold(id, ..)
+            | Self::SetDensityOffset(id, ..)
+            | Self::SetOffset(id, ..)
+            | Self::SetWind(id, ..)
+            | Self::SetAlphaThreshold(id, ..)
+            | Self::MoveBoundingBox(id, ..)
+            | Self::SetCloudBounds(id, ..)
+            | Self::MoveCloud(id, ..)
+            | Self::ExtendBoundingBox(id, ..)
+            | Self::SetNoise(id, ..)
+            | Self::LoadPreset(id, ..)
+            | Self::ImportUnity(id, ..)
+            | Self::SetDetailNoise(id, ..)
+            | Self::SetDetailNoiseScale(id, ..)
+            | Self::SetDetailNoiseWeight(id, ..)
+            | Self::SetDetailWeights(id, ..)
+            | Self::SetShapeNoiseWeights(id, ..)
+            | Self::SetPhaseParams(id, ..)
+            | Self::SetShapeOffset(id, ..)
+            | Self::SetDetailOffset(id, ..)
+            | Self::SetLightAbsorptionTowardSun(id, ..)
+            | Self::SetLightAbsorptionThroughCloud(id, ..)
+            | Self::SetDarknessThreshold(id, ..)
+            | Self::SetRayOffsetStrength(id, ..)
+            | Self::SetLightColor(id, ..)
+            | Self::SetLight(id, ..)
+            | Self::SetLightIntensity(id, ..)
+            | Self::SetLightAbsorption(id, ..)
+            | Self::SetColA(id, ..)
+            | Self::SetColB(id, ..)
+            | Self::SetHeightMapFactor(id, ..)
+            | Self::SetVolumeOffset(id, ..)
+            | Self::SetEdgeDistance(id, ..)
+            | Self::SetSunDistance(id, ..)
+            | Self::SetSunAngle(id, ..)
+            | Self::SetSunColor(id, ..)
+            | Self::SetSunIntensity(id, ..)
+            | Self::SetDayLength(id, ..)
+            | Self::SetTerrainScale(id, ..)
+            | Self::SetTerrainNoise(id, ..)
+            | Self::SetTerrainNoiseWeight(id, ..)
+            | Self::SetTerrainTopColor(id, ..)
+            | Self::SetTerrainBottomColor(id, ..)
+            | Self::SetTerrainShadowThreshold(id, ..)
+            | Self::SetTerrainNumShadowsSteps(id, ..)
+            | Self::SetTerrainDensityScale(id, ..)
+            | Self::SetTerrainDiffuseFactor(id, ..)
+            | Self::SetTerrainSize(id, ..)
+            | Self::SetTerrainHeight(id, ..)
+            | Self::SetTerrainHeightMap(id, ..)
+            | Self::SetGridK(id, ..)
+            | Self::SetGridScale(id, ..)
+            | Self::SetGridSpacing(id, ..)
+            | Self::SetGridPlane(id, ..)
+            | Self::SetGridColors(id, ..)
+            | Self::SetBackground(id, ..)
+            | Self::SetFog(id, ..)
+            | Self::SetWaterLevel(id, ..)
+            | Self::SetWaterColor(id, ..)
+            | Self::SetWaterReflectivity(id, ..)
+            | Self::SetWaterWaveAmplitude(id, ..)
+            | Self::SetWaterWaveScale(id, ..)
+            | Self::SetWaterWaveSpeed(id, ..)
+            | Self::AdvanceWater(id, ..)
+            | Self::SetSkybox(id, ..)
+            | Self::SetGizmoTarget(id, ..)
+            | Self::SetGizmoMode(id, ..) => Some(id),
+            // Adding and removing have events of their own, the gizmo drags
+            // change their target through the commands above
+            Self::AddObject(..)
+            | Self::AddObjectFromFile(..)
+            | Self::AddCloud(..)
+            | Self::AddSkyboxFromFile(..)
+            | Self::RemoveObject(..)
+            | Self::RemoveObjectsOfKind(..)
+            | Self::ClearScene
+            | Self::LoadScene(..)
+            | Self::SaveScene(..)
+            | Self::SavePreset(..)
+            | Self::ExportShader(..)
+            | Self::QueryScene
+            | Self::GetObject(..)
+            | Self::GetObjectSnapshot(..)
+            | Self::GetSunPos(..)
+            | Self::Pick(..)
+            | Self::GrabGizmo(..)
+            | Self::DragGizmo(..)
+            | Self::ReleaseGizmo(..) => None,
+        }
+    }
+
+    #[allow(clippy::collapsible_match)]
+    fn apply(self, manager: &mut ManagerSolution) -> SceneCommandReturn {
         match self {
             SceneCommand::AddObject(name, component) => {
-                let sm = manager.get_mut_scene_manager();
+                let sm = manager.get_mut::<SceneManager>();
                 sm.add_object(name, component);
             }
+            SceneCommand::AddObjectFromFile(name, path) => {
+                match manager.get_mut::<ResourceManager>().mesh(&path) {
+                    Ok(mesh) => {
+                        manager.get_mut::<SceneManager>().add_object(name, mesh);
+                    }
+                    Err(err) => {
+                        manager.get_mut::<DiagnosticsManager>().error(
+                            Some(name),
+                            format!("failed to load {}: {err}", path.display()),
+                        );
+                        return SceneCommandReturn::Error(err.to_string());
+                    }
+                }
+            }
+            SceneCommand::AddCloud(name, params) => {
+                let params = *params;
+                let resources = manager.get_mut::<ResourceManager>();
+                let cloud = Cloud::with_volumes(
+                    params,
+                    resources.volume(params.noise),
+                    resources.volume(params.detail_noise),
+                    resources.volume(params.weather_noise),
+                );
+                manager.get_mut::<SceneManager>().add_object(name, cloud);
+            }
+            SceneCommand::AddSkyboxFromFile(name, path) => {
+                match manager.get_mut::<ResourceManager>().texture(&path) {
+                    Ok(img) => {
+                        let skybox = Skybox::Panorama(img);
+                        manager.get_mut::<SceneManager>().add_object(name, skybox);
+                    }
+                    Err(err) => {
+                        manager.get_mut::<DiagnosticsManager>().error(
+                            Some(name),
+                            format!("failed to load {}: {err}", path.display()),
+                        );
+                        return SceneCommandReturn::Error(err.to_string());
+                    }
+                }
+            }
+            SceneCommand::SetParent(child, parent) => {
+                if let Err(err) = manager.get_mut::<SceneManager>().set_parent(child, parent) {
+                    manager.get_mut::<DiagnosticsManager>().error(
+                        Some(child),
+                        format!("failed to attach {child} to {parent}: {err}"),
+                    );
+                    return SceneCommandReturn::Error(err);
+                }
+            }
+            SceneCommand::DetachObject(id) => {
+                if let Err(err) = manager.get_mut::<SceneManager>().detach_object(id) {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(Some(id), format!("failed to detach {id}: {err}"));
+                    return SceneCommandReturn::Error(err);
+                }
+            }
+            SceneCommand::SetVisible(id, visible) => {
+                if let Err(err) = manager.get_mut::<SceneManager>().set_visible(id, visible) {
+                    manager.get_mut::<DiagnosticsManager>().error(
+                        Some(id),
+                        format!("failed to change visibility of {id}: {err}"),
+                    );
+                    return SceneCommandReturn::Error(err);
+                }
+            }
+            SceneCommand::SetLayer(id, layer) => {
+                if let Err(err) = manager.get_mut::<SceneManager>().set_layer(id, layer) {
+                    manager.get_mut::<DiagnosticsManager>().error(
+                        Some(id),
+                        format!("failed to move {id} to layer {layer}: {err}"),
+                    );
+                    return SceneCommandReturn::Error(err);
+                }
+            }
+            SceneCommand::SetTransform(id, transform) => {
+                if let Some(local) = manager.get_mut::<SceneManager>().local_transform_mut(id) {
+                    *local = transform;
+                }
+            }
+            SceneCommand::TranslateObject(id, offset) => {
+                if let Some(local) = manager.get_mut::<SceneManager>().local_transform_mut(id) {
+                    local.translate(offset);
+                }
+            }
+            SceneCommand::RotateObject(id, rotation) => {
+                if let Some(local) = manager.get_mut::<SceneManager>().local_transform_mut(id) {
+                    local.rotate(rotation);
+                }
+            }
+            SceneCommand::ScaleObject(id, factor) => {
+                if let Some(local) = manager.get_mut::<SceneManager>().local_transform_mut(id) {
+                    local.scale(factor);
+                }
+            }
+            SceneCommand::QueryScene => {
+                return SceneCommandReturn::Scene(manager.get::<SceneManager>().list_objects());
+            }
+            SceneCommand::Pick(canvas, pointer) => {
+                let camera = manager.get::<CameraManager>().get_camera();
+                let (origin, dir) = camera.ray(canvas, pointer);
+                return SceneCommandReturn::Hits(manager.get::<SceneManager>().pick(origin, dir));
+            }
+            SceneCommand::SaveScene(path) => {
+                let skipped = match manager.get::<SceneManager>().save_scene(&path) {
+                    Ok(skipped) => skipped,
+                    Err(err) => {
+                        manager
+                            .get_mut::<DiagnosticsManager>()
+                            .error(None, format!("failed to save {}: {err}", path.display()));
+                        return SceneCommandReturn::Error(err.to_string());
+                    }
+                };
+                let diagnostics = manager.get_mut::<DiagnosticsManager>();
+                for name in skipped {
+                    diagnostics.warning(
+                        Some(name),
+                        format!("{name} is a plugin object, left out of {}", path.display()),
+                    );
+                }
+                manager
+                    .get_mut::<SettingsManager>()
+                    .settings_mut()
+                    .last_scene = Some(path);
+            }
+            SceneCommand::LoadScene(path) => {
+                if let Err(err) = manager.get_mut::<SceneManager>().load_scene(&path) {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(None, format!("failed to load {}: {err}", path.display()));
+                    return SceneCommandReturn::Error(err.to_string());
+                }
+                manager.get_mut::<SelectionManager>().clear();
+                manager.get::<CacheManager>().clear();
+                manager
+                    .get_mut::<SettingsManager>()
+                    .settings_mut()
+                    .last_scene = Some(path);
+                validate_scene(manager);
+            }
+            SceneCommand::SavePreset(id, path) => {
+                let saved = match manager.get::<SceneManager>().get_object(id) {
+                    Some(Component::Cloud(cloud)) => {
+                        save_preset(&path, &cloud.cloud_params).map_err(|err| err.to_string())
+                    }
+                    _ => Err(format!("no cloud named {id}")),
+                };
+                if let Err(err) = saved {
+                    manager.get_mut::<DiagnosticsManager>().error(
+                        Some(id),
+                        format!("failed to save {}: {err}", path.display()),
+                    );
+                    return SceneCommandReturn::Error(err);
+                }
+            }
+            SceneCommand::LoadPreset(id, path) => {
+                let loaded = load_preset(&path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|params| replace_cloud(manager, id, params));
+                if let Err(err) = loaded {
+                    manager.get_mut::<DiagnosticsManager>().error(
+                        Some(id),
+                        format!("failed to load {}: {err}", path.display()),
+                    );
+                    return SceneCommandReturn::Error(err);
+                }
+            }
+            SceneCommand::ExportShader(id, language, path) => {
+                let saved = match manager.get::<SceneManager>().get_object(id) {
+                    Some(Component::Cloud(cloud)) => {
+                        save_shader(&path, cloud, language).map_err(|err| err.to_string())
+                    }
+                    _ => Err(format!("no cloud named {id}")),
+                };
+                if let Err(err) = saved {
+                    manager.get_mut::<DiagnosticsManager>().error(
+                        Some(id),
+                        format!("failed to export {}: {err}", path.display()),
+                    );
+                    return SceneCommandReturn::Error(err);
+                }
+            }
+            SceneCommand::ImportUnity(id, path) => {
+                let base = match manager.get::<SceneManager>().get_object(id) {
+                    Some(Component::Cloud(cloud)) => Ok(cloud.cloud_params),
+                    _ => Err(format!("no cloud named {id}")),
+                };
+                let imported = base.and_then(|base| {
+                    let import = load_unity(&path, base).map_err(|err| err.to_string())?;
+                    replace_cloud(manager, id, import.cloud)?;
+                    Ok(import.ignored)
+                });
+                match imported {
+                    Ok(ignored) if !ignored.is_empty() => {
+                        manager.get_mut::<DiagnosticsManager>().warning(
+                            Some(id),
+                            format!(
+                                "not imported from {}: {}",
+                                path.display(),
+                                ignored.join(", ")
+                            ),
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(err) => {
+                        manager.get_mut::<DiagnosticsManager>().error(
+                            Some(id),
+                            format!("failed to import {}: {err}", path.display()),
+                        );
+                        return SceneCommandReturn::Error(err);
+                    }
+                }
+            }
             SceneCommand::GetObject(_component) => {
-                debug!("get object");
+                let message = "GetObject is not supported, use GetObjectSnapshot";
+                manager
+                    .get_mut::<DiagnosticsManager>()
+                    .warning(None, message);
+            }
+            SceneCommand::GetObjectSnapshot(id) => {
+                if let Some(snapshot) = manager.get::<SceneManager>().get_object_snapshot(id) {
+                    return SceneCommandReturn::Snapshot(snapshot);
+                }
+            }
+            SceneCommand::RemoveObject(id) => {
+                let removed = manager.get_mut::<SceneManager>().remove_object(id);
+                if removed.is_empty() {
+                    let err = format!("no object named {id}");
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(Some(id), format!("failed to remove {id}: {err}"));
+                    return SceneCommandReturn::Error(err);
+                }
+                forget_removed(manager, &removed);
+            }
+            SceneCommand::RemoveObjectsOfKind(kind) => {
+                let removed = manager.get_mut::<SceneManager>().remove_kind(kind);
+                forget_removed(manager, &removed);
             }
-            SceneCommand::RemoveObject(_component) => {
-                debug!("remove object");
+            SceneCommand::ClearScene => {
+                let removed = manager.get_mut::<SceneManager>().clear_scene();
+                forget_removed(manager, &removed);
             }
             SceneCommand::SetNumSteps(id, num_steps) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.num_steps = num_steps;
                     }
                 }
             }
             SceneCommand::SetNumStepsLight(id, num_steps) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.num_steps_light = num_steps;
                     }
                 }
             }
+            SceneCommand::SetAutoSteps(id, auto_steps) => {
+                if let Some(Component::Cloud(cloud)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    cloud.auto_steps = auto_steps;
+                }
+            }
+            SceneCommand::SetLightInterval(id, light_interval) => {
+                if let Some(Component::Cloud(cloud)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    cloud.light_interval = light_interval;
+                }
+            }
             SceneCommand::SetCloudScale(id, cloud_scale) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.cloud_scale = cloud_scale
                     }
                 }
             }
             SceneCommand::SetRayOffsetStrength(id, ray_offset_strength) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.ray_offset_strength = ray_offset_strength
                     }
                 }
             }
             SceneCommand::SetDensityMultiplier(id, density_multiplier) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.density_multiplier = density_multiplier
                     }
                 }
             }
             SceneCommand::SetDensityThreshold(id, density_threshold) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.density_threshold = density_threshold
                     }
                 }
             }
             SceneCommand::SetDensityOffset(id, d) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.density_offset = d
                     }
                 }
             }
             SceneCommand::SetOffset(id, offset) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.offset = offset
                     }
                 }
             }
+            SceneCommand::SetWind(id, wind) => {
+                if let Some(Component::Cloud(cloud)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    cloud.wind = wind;
+                }
+            }
             SceneCommand::SetAlphaThreshold(id, threshold) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.alpha_threshold = threshold
                     }
                 }
             }
             SceneCommand::SetNoise(id, noise) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
-                    if let Component::Cloud(cloud) = i {
-                        cloud.regenerate_noise(noise)
-                    }
+                let volume = manager.get_mut::<ResourceManager>().volume(noise);
+                if let Some(Component::Cloud(cloud)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    let old = cloud.set_noise(noise, volume);
+                    manager.get_mut::<ResourceManager>().retire(old);
                 }
+                manager.get_mut::<ResourceManager>().collect();
             }
             SceneCommand::SetDetailNoise(id, noise) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
-                    if let Component::Cloud(cloud) = i {
-                        cloud.regenerate_detail_noise(noise)
-                    }
+                let volume = manager.get_mut::<ResourceManager>().volume(noise);
+                if let Some(Component::Cloud(cloud)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    let old = cloud.set_detail_noise(noise, volume);
+                    manager.get_mut::<ResourceManager>().retire(old);
                 }
+                manager.get_mut::<ResourceManager>().collect();
             }
             SceneCommand::SetDetailNoiseScale(id, detail_noise_scale) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.detail_noise_scale = detail_noise_scale
                     }
                 }
             }
             SceneCommand::SetDetailNoiseWeight(id, detail_noise_weight) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.detail_noise_weight = detail_noise_weight
                     }
                 }
             }
             SceneCommand::SetDetailWeights(id, detail_weights) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.detail_weights = detail_weights
                     }
                 }
             }
             SceneCommand::SetShapeNoiseWeights(id, shape_noise_weights) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.shape_noise_weights = shape_noise_weights
                     }
                 }
             }
             SceneCommand::SetPhaseParams(id, phase_params) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.phase_params = phase_params
                     }
                 }
             }
             SceneCommand::SetShapeOffset(id, shape_offset) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.shape_offset = shape_offset
                     }
                 }
             }
             SceneCommand::SetDetailOffset(id, detail_offset) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.detail_offset = detail_offset
                     }
                 }
             }
             SceneCommand::SetLightAbsorptionTowardSun(id, light_absorption_toward_sun) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.light_absorption_toward_sun = light_absorption_toward_sun
                     }
                 }
             }
             SceneCommand::SetLightAbsorptionThroughCloud(id, light_absorption_through_cloud) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.light_absorption_through_cloud = light_absorption_through_cloud
                     }
                 }
             }
             SceneCommand::SetDarknessThreshold(id, darkness_threshold) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.darkness_threshold = darkness_threshold
                     }
                 }
             }
             SceneCommand::SetLightColor(id, light_color) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
-                    if let Component::Cloud(cloud) = i {
-                        cloud.light_color = light_color
-                    }
+                match manager.get_mut::<SceneManager>().get_mut_object(id) {
+                    Some(Component::Cloud(cloud)) => cloud.light_color = light_color,
+                    Some(Component::Light(light)) => light.color = light_color,
+                    _ => {}
+                }
+            }
+            SceneCommand::SetLight(id, light) => {
+                if let Some(Component::Light(l)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    *l = light;
+                }
+            }
+            SceneCommand::SetLightIntensity(id, intensity) => {
+                if let Some(Component::Light(light)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    light.intensity = intensity;
+                }
+            }
+            SceneCommand::SetLightAbsorption(id, absorption) => {
+                if let Some(Component::Light(light)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    light.absorption = absorption;
                 }
             }
             SceneCommand::SetColA(id, col_a) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.col_a = col_a
                     }
                 }
             }
             SceneCommand::SetColB(id, col_b) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.col_b = col_b
                     }
                 }
             }
             SceneCommand::SetEdgeDistance(id, ed) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.edge_distance = ed
                     }
                 }
             }
             SceneCommand::SetVolumeOffset(id, vo) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.volume_offset = vo
                     }
                 }
             }
             SceneCommand::SetHeightMapFactor(id, hmf) => {
-                if let Some(i) = manager.get_mut_scene_manager().get_mut_object(id) {
+                if let Some(i) = manager.get_mut::<SceneManager>().get_mut_object(id) {
                     if let Component::Cloud(cloud) = i {
                         cloud.height_map_factor = hmf
                     }
@@ -271,21 +863,42 @@ impl Command for SceneCommand {
             }
             SceneCommand::SetSunDistance(id, d) => {
                 if let Some(Component::Sun(sun)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     sun.set_d(d);
                 }
             }
             SceneCommand::SetSunAngle(id, a) => {
                 if let Some(Component::Sun(sun)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     sun.prepend_angle(a);
                 }
             }
+            SceneCommand::SetSunColor(id, color) => {
+                if let Some(Component::Sun(sun)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    sun.color = color;
+                }
+            }
+            SceneCommand::SetSunIntensity(id, intensity) => {
+                if let Some(Component::Sun(sun)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    sun.intensity = intensity;
+                }
+            }
+            SceneCommand::SetDayLength(id, day_length) => {
+                if let Some(Component::Sun(sun)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    sun.day_length = day_length;
+                }
+            }
             SceneCommand::GetSunPos(id) => {
                 if let Some(Component::Sun(sun)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     let sun_pos = sun.get_pos();
                     return SceneCommandReturn::SunPos(sun_pos);
@@ -293,7 +906,7 @@ impl Command for SceneCommand {
             }
             SceneCommand::SetTerrainScale(id, s) => {
                 if let Some(Component::Terrain(terrain)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     terrain.scale = s;
                     terrain.generate_grid();
@@ -301,74 +914,355 @@ impl Command for SceneCommand {
             }
             SceneCommand::SetTerrainNoise(id, w) => {
                 if let Some(Component::Terrain(terrain)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     terrain.regenerate_noise(w);
                     terrain.generate_grid();
                 }
             }
+            SceneCommand::SetTerrainSize(id, size) => {
+                if let Some(Component::Terrain(terrain)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    terrain.set_size(size);
+                    terrain.generate_grid();
+                }
+            }
+            SceneCommand::SetTerrainHeight(id, height) => {
+                if let Some(Component::Terrain(terrain)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    terrain.set_height(height);
+                    terrain.generate_grid();
+                }
+            }
+            SceneCommand::SetTerrainHeightMap(id, height_map) => {
+                if let Some(Component::Terrain(terrain)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    terrain.set_height_map(height_map);
+                    terrain.generate_grid();
+                }
+            }
             SceneCommand::SetTerrainTopColor(id, c) => {
                 if let Some(Component::Terrain(terrain)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     terrain.top_color = c;
                 }
             }
             SceneCommand::SetTerrainBottomColor(id, c) => {
                 if let Some(Component::Terrain(terrain)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     terrain.bottom_color = c;
                 }
             }
             SceneCommand::SetTerrainDiffuseFactor(id, diffuse_factor) => {
                 if let Some(Component::Terrain(terrain)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     terrain.diffuse_factor = diffuse_factor;
                 }
             }
             SceneCommand::SetTerrainDensityScale(id, density_scale) => {
                 if let Some(Component::Terrain(terrain)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     terrain.density_scale = density_scale;
                 }
             }
             SceneCommand::SetTerrainNumShadowsSteps(id, num_shadows_steps) => {
                 if let Some(Component::Terrain(terrain)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     terrain.num_shadows_steps = num_shadows_steps;
                 }
             }
             SceneCommand::SetTerrainShadowThreshold(id, shadow_threshold) => {
                 if let Some(Component::Terrain(terrain)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     terrain.shadow_threshold = shadow_threshold;
                 }
             }
             SceneCommand::SetTerrainNoiseWeight(id, noise_weight) => {
                 if let Some(Component::Terrain(terrain)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     terrain.noise_weight = noise_weight;
                 }
             }
             SceneCommand::MoveBoundingBox(id, bb) => {
                 if let Some(Component::Cloud(cloud)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {
                     cloud.bounding_box.move_center(bb)
                 }
             }
+            SceneCommand::SetCloudBounds(id, min, max) => {
+                if let Some(Component::Cloud(cloud)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    cloud.bounding_box = BoundingBox::from_two_pos(min, max);
+                    invalidate_render(manager, id);
+                }
+            }
+            SceneCommand::MoveCloud(id, delta) => {
+                if let Some(Component::Cloud(cloud)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    let center = cloud.bounding_box.center();
+                    cloud.bounding_box.move_center(center + delta);
+                    invalidate_render(manager, id);
+                }
+            }
+            SceneCommand::SetGridK(id, k) => {
+                if let Some(Component::Grid(grid)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    grid.k = k.max(1);
+                }
+            }
+            // The grid can't pick its line density from a zero or non-finite
+            // spacing, such values are rejected
+            SceneCommand::SetGridScale(id, scale) | SceneCommand::SetGridSpacing(id, scale)
+                if !(scale > 0.0 && scale.is_finite()) =>
+            {
+                manager.get_mut::<DiagnosticsManager>().warning(
+                    Some(id),
+                    format!("grid spacing must be positive, got {scale}"),
+                );
+            }
+            SceneCommand::SetGridScale(id, scale) => {
+                if let Some(Component::Grid(grid)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    grid.scale = scale;
+                }
+            }
+            SceneCommand::SetGridSpacing(id, spacing) => {
+                if let Some(Component::Grid(grid)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    grid.set_spacing(spacing);
+                }
+            }
+            SceneCommand::SetGridPlane(id, plane) => {
+                if let Some(Component::Grid(grid)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    grid.plane = plane;
+                }
+            }
+            SceneCommand::SetGridColors(id, major, minor) => {
+                if let Some(Component::Grid(grid)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    grid.major_color = major;
+                    grid.minor_color = minor;
+                }
+            }
+            SceneCommand::SetBackground(id, background) => {
+                if let Some(Component::Background(bg)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    *bg = background;
+                }
+            }
+            SceneCommand::SetFog(id, fog) => {
+                if let Some(Component::Fog(x)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    *x = fog;
+                }
+            }
+            SceneCommand::SetWaterLevel(id, level) => {
+                if let Some(Component::Water(water)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    water.set_level(level);
+                }
+            }
+            SceneCommand::SetWaterColor(id, color) => {
+                if let Some(Component::Water(water)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    water.color = color;
+                }
+            }
+            SceneCommand::SetWaterReflectivity(id, reflectivity) => {
+                if let Some(Component::Water(water)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    water.reflectivity = reflectivity;
+                }
+            }
+            SceneCommand::SetWaterWaveAmplitude(id, amplitude) => {
+                if let Some(Component::Water(water)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    water.wave_amplitude = amplitude;
+                }
+            }
+            SceneCommand::SetWaterWaveScale(id, scale) => {
+                if let Some(Component::Water(water)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    water.wave_scale = scale;
+                }
+            }
+            SceneCommand::SetWaterWaveSpeed(id, speed) => {
+                if let Some(Component::Water(water)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    water.wave_speed = speed;
+                }
+            }
+            SceneCommand::AdvanceWater(id, dt) => {
+                if let Some(Component::Water(water)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    water.advance(dt);
+                }
+            }
+            SceneCommand::SetSkybox(id, skybox) => {
+                if let Some(Component::Skybox(sky)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    *sky = skybox;
+                }
+            }
+            SceneCommand::SetGizmoTarget(id, target) => {
+                if let Some(Component::TransformGizmo(gizmo)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    gizmo.target = target;
+                    gizmo.active = None;
+                }
+                manager.get_mut::<SceneManager>().update_gizmos();
+            }
+            SceneCommand::SetGizmoMode(id, mode) => {
+                if let Some(Component::TransformGizmo(gizmo)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    gizmo.mode = mode;
+                }
+            }
+            SceneCommand::GrabGizmo(id, canvas, pointer) => {
+                let camera = *manager.get::<CameraManager>().get_camera();
+                if let Some(Component::TransformGizmo(gizmo)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    if gizmo.target.is_some() {
+                        gizmo.active = gizmo.hit(&camera, canvas, pointer);
+                        return SceneCommandReturn::Grabbed(gizmo.active.is_some());
+                    }
+                }
+                return SceneCommandReturn::Grabbed(false);
+            }
+            SceneCommand::DragGizmo(id, canvas, from, to) => {
+                let sm = manager.get::<SceneManager>();
+                let Some(Component::TransformGizmo(gizmo)) = sm.get_object(id) else {
+                    return SceneCommandReturn::Nothing;
+                };
+                let (Some(target), Some(axis)) = (gizmo.target, gizmo.active) else {
+                    return SceneCommandReturn::Nothing;
+                };
+                let Some(local) = sm.local_transform(target) else {
+                    return SceneCommandReturn::Nothing;
+                };
+                let camera = manager.get::<CameraManager>().get_camera();
+                let drag = gizmo.drag(camera, canvas, axis, from, to);
+
+                // The commands work in the space of the parent of the target
+                let parent = sm.world_transform(target) * local.matrix().inverse();
+                let inverse = parent.inverse();
+                match drag {
+                    GizmoDrag::Translate(offset) => {
+                        let offset = inverse.transform_vector3(offset);
+                        SceneCommand::TranslateObject(target, offset).exec(manager);
+                    }
+                    GizmoDrag::Rotate(rotation) => {
+                        let (_, parent_rotation, _) = parent.to_scale_rotation_translation();
+                        let rotation = parent_rotation.inverse() * rotation * parent_rotation;
+                        // Turning around the pivot rather than the origin
+                        let pivot = inverse.transform_point3(gizmo.pivot);
+                        let t = local.translation;
+                        let offset = pivot + rotation * (t - pivot) - t;
+                        SceneCommand::RotateObject(target, rotation).exec(manager);
+                        SceneCommand::TranslateObject(target, offset).exec(manager);
+                    }
+                }
+                manager.get_mut::<SceneManager>().update_gizmos();
+            }
+            SceneCommand::ReleaseGizmo(id) => {
+                if let Some(Component::TransformGizmo(gizmo)) =
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
+                {
+                    gizmo.active = None;
+                }
+            }
             SceneCommand::ExtendBoundingBox(id, _) => {
                 if let Some(Component::Cloud(_)) =
-                    manager.get_mut_scene_manager().get_mut_object(id)
+                    manager.get_mut::<SceneManager>().get_mut_object(id)
                 {}
             }
         }
         SceneCommandReturn::Nothing
     }
 }
+
+/// Frees the painter textures only the removed objects were rendered into
+/// and drops the objects from the selection and the caches
+pub(crate) fn forget_removed(manager: &mut ManagerSolution, removed: &[(&'static str, Component)]) {
+    let selection = manager.get_mut::<SelectionManager>();
+    for (id, _) in removed {
+        selection.deselect(id);
+    }
+    let cache = manager.get::<CacheManager>();
+    let draw = manager.get::<DrawManager>();
+    for (id, _) in removed {
+        cache.invalidate(id);
+        draw.forget_object(id);
+    }
+    let unused = manager
+        .get::<SceneManager>()
+        .unused_textures(removed.iter().map(|(_, x)| x));
+    manager.get::<DrawManager>().release_textures(&unused);
+    manager.get_mut::<ResourceManager>().collect();
+}
+
+/// Rebuilds the cloud from the parameters, sharing the volumes of the same
+/// noise builders
+pub(crate) fn replace_cloud(
+    manager: &mut ManagerSolution,
+    id: &'static str,
+    params: CloudBuilder,
+) -> Result<(), String> {
+    let resources = manager.get_mut::<ResourceManager>();
+    let replaced = Cloud::with_volumes(
+        params,
+        resources.volume(params.noise),
+        resources.volume(params.detail_noise),
+        resources.volume(params.weather_noise),
+    );
+    match manager.get_mut::<SceneManager>().get_mut_object(id) {
+        Some(Component::Cloud(cloud)) => **cloud = replaced,
+        _ => return Err(format!("no cloud named {id}")),
+    }
+    manager.get_mut::<ResourceManager>().collect();
+    Ok(())
+}
+
+/// Drops the texture the object was last rendered into, so a stale image of
+/// the old placement is never shown
+fn invalidate_render(manager: &mut ManagerSolution, id: &'static str) {
+    let texture = manager
+        .get::<SceneManager>()
+        .get_object(id)
+        .and_then(Component::texture_name);
+    if let Some(texture) = texture {
+        manager.get::<DrawManager>().release_textures(&[texture]);
+    }
+}
@@ -0,0 +1,181 @@
+use std::path::PathBuf;
+
+use log::info;
+
+use crate::facade::command::scene_command::SceneCommandReturn;
+use crate::facade::{AnimationCommand, Command, JobCommand, SceneCommand, TimeCommand};
+use crate::io::script::Statement;
+use crate::managers::animation_manager::{Property, Track};
+use crate::managers::camera_manager::CameraManager;
+use crate::managers::diagnostics_manager::DiagnosticsManager;
+use crate::managers::scene_manager::SceneManager;
+use crate::managers::script_manager::{ScriptInfo, ScriptManager};
+use crate::managers::settings_manager::SettingsManager;
+use crate::managers::ManagerSolution;
+
+/// Animation track turning the camera for the `turntable` statement
+const TURNTABLE_TRACK: &str = "turntable";
+
+pub enum ScriptCommandReturn {
+    Nothing,
+    Scripts(Vec<ScriptInfo>),
+    Error(String),
+}
+
+impl ScriptCommandReturn {
+    #[inline]
+    pub fn as_scripts(&self) -> Option<&[ScriptInfo]> {
+        if let Self::Scripts(scripts) = self {
+            return Some(scripts);
+        }
+        None
+    }
+}
+
+/// Command scripts, see [`crate::io::script`]. Due scripts are run by
+/// [`TimeCommand::Tick`].
+pub enum ScriptCommand {
+    /// Loads every `.script` file of the directory
+    Discover(PathBuf),
+    Load(PathBuf),
+    /// Runs the loaded script now, whatever its schedule
+    Run(PathBuf),
+    Remove(PathBuf),
+    Query,
+}
+
+impl Command for ScriptCommand {
+    type ReturnType = ScriptCommandReturn;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        let sm = manager.get_mut::<ScriptManager>();
+        match self {
+            ScriptCommand::Discover(dir) => match sm.discover(&dir) {
+                Ok(errors) => {
+                    for err in errors {
+                        manager.get_mut::<DiagnosticsManager>().error(
+                            None,
+                            format!("failed to load a script from {}: {err}", dir.display()),
+                        );
+                    }
+                }
+                Err(err) => {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(None, format!("failed to read {}: {err}", dir.display()));
+                    return ScriptCommandReturn::Error(err.to_string());
+                }
+            },
+            ScriptCommand::Load(path) => {
+                if let Err(err) = sm.load(&path) {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(None, format!("failed to load {}: {err}", path.display()));
+                    return ScriptCommandReturn::Error(err.to_string());
+                }
+            }
+            ScriptCommand::Run(path) => {
+                if let Err(err) = run_script(manager, path) {
+                    return ScriptCommandReturn::Error(err);
+                }
+            }
+            ScriptCommand::Remove(path) => sm.remove(&path),
+            ScriptCommand::Query => {}
+        }
+        ScriptCommandReturn::Scripts(manager.get::<ScriptManager>().scripts())
+    }
+}
+
+/// Runs the scripts due after `dt` seconds of wall time
+pub(crate) fn run_due_scripts(manager: &mut ManagerSolution, dt: f32) {
+    for path in manager.get_mut::<ScriptManager>().advance(dt) {
+        let _ = run_script(manager, path);
+    }
+}
+
+/// Runs the statements one by one, stopping at the first failure
+fn run_script(manager: &mut ManagerSolution, path: PathBuf) -> Result<(), String> {
+    let statements = manager.get::<ScriptManager>().statements(&path);
+    let Some(statements) = statements.map(<[_]>::to_vec) else {
+        return Err(format!("no script {}", path.display()));
+    };
+    info!("running {}", path.display());
+    let result = statements
+        .into_iter()
+        .try_for_each(|x| run_statement(manager, x));
+    if let Err(err) = &result {
+        manager
+            .get_mut::<DiagnosticsManager>()
+            .error(None, format!("script {} failed: {err}", path.display()));
+    }
+    manager
+        .get_mut::<ScriptManager>()
+        .finish(&path, result.clone());
+    result
+}
+
+fn run_statement(manager: &mut ManagerSolution, statement: Statement) -> Result<(), String> {
+    let scene = |manager: &mut ManagerSolution, command: SceneCommand| match command.exec(manager) {
+        SceneCommandReturn::Error(err) => Err(err),
+        _ => Ok(()),
+    };
+    match statement {
+        Statement::LoadScene(path) => scene(manager, SceneCommand::LoadScene(path))?,
+        Statement::SaveScene(path) => scene(manager, SceneCommand::SaveScene(path))?,
+        Statement::Quality(quality) => {
+            let clouds: Vec<_> = manager
+                .get::<SceneManager>()
+                .list_objects()
+                .into_iter()
+                .filter(|x| x.kind == "cloud")
+                .map(|x| x.name)
+                .collect();
+            for id in clouds {
+                scene(manager, SceneCommand::SetNumSteps(id, quality.num_steps))?;
+                scene(
+                    manager,
+                    SceneCommand::SetNumStepsLight(id, quality.num_steps_light),
+                )?;
+                scene(
+                    manager,
+                    SceneCommand::SetLightInterval(id, quality.light_interval),
+                )?;
+            }
+            manager.get_mut::<SettingsManager>().settings_mut().quality = quality;
+        }
+        Statement::Turntable(Some(period)) => {
+            let yaw = manager
+                .get::<CameraManager>()
+                .get_camera()
+                .view
+                .yaw
+                .to_degrees();
+            let track = Track::new("camera", Property::CameraYaw)
+                .with_key(0.0, yaw)
+                .with_key(period, yaw + 360.0);
+            AnimationCommand::AddTrack(TURNTABLE_TRACK.to_owned(), track).exec(manager);
+            AnimationCommand::Play(true).exec(manager);
+        }
+        Statement::Turntable(None) => {
+            AnimationCommand::RemoveTrack(TURNTABLE_TRACK.to_owned()).exec(manager);
+        }
+        Statement::Play => {
+            TimeCommand::Play.exec(manager);
+        }
+        Statement::Pause => {
+            TimeCommand::Pause.exec(manager);
+        }
+        Statement::TimeScale(scale) => {
+            TimeCommand::SetTimeScale(scale).exec(manager);
+        }
+        Statement::PlayAnimation(looped) => {
+            AnimationCommand::Play(looped).exec(manager);
+        }
+        Statement::StopAnimation => {
+            AnimationCommand::Stop.exec(manager);
+        }
+        Statement::Render(size, path) => {
+            JobCommand::RenderToFile(size, path).exec(manager);
+        }
+    }
+    Ok(())
+}
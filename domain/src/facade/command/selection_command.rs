@@ -0,0 +1,71 @@
+use crate::facade::Command;
+use crate::managers::camera_manager::CameraManager;
+use crate::managers::scene_manager::SceneManager;
+use crate::managers::selection_manager::SelectionEvent;
+use crate::managers::selection_manager::SelectionManager;
+use crate::managers::ManagerSolution;
+
+/// Selection after the command
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct SelectionState {
+    pub active: Option<&'static str>,
+    pub selected: Vec<&'static str>,
+    /// Changes since the previous command
+    pub events: Vec<SelectionEvent>,
+}
+
+pub enum SelectionCommand {
+    /// Makes the object the only selected one
+    Select(&'static str),
+    /// Adds the object to the selection and makes it active
+    AddToSelection(&'static str),
+    Deselect(&'static str),
+    Clear,
+    /// Selects the nearest object under the pointer inside the canvas rect,
+    /// adding it to the selection when the flag is set. Clicking the empty
+    /// space clears the selection unless adding.
+    Pick(egui::Rect, egui::Pos2, bool),
+    Query,
+}
+
+impl Command for SelectionCommand {
+    type ReturnType = SelectionState;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        match self {
+            SelectionCommand::Select(id) => manager.get_mut::<SelectionManager>().select(id),
+            SelectionCommand::AddToSelection(id) => manager.get_mut::<SelectionManager>().add(id),
+            SelectionCommand::Deselect(id) => manager.get_mut::<SelectionManager>().deselect(id),
+            SelectionCommand::Clear => manager.get_mut::<SelectionManager>().clear(),
+            SelectionCommand::Pick(canvas, pointer, add) => {
+                let camera = manager.get::<CameraManager>().get_camera();
+                let (origin, dir) = camera.ray(canvas, pointer);
+                let hit = manager
+                    .get::<SceneManager>()
+                    .pick(origin, dir)
+                    .first()
+                    .map(|x| x.id);
+                let selection = manager.get_mut::<SelectionManager>();
+                match (hit, add) {
+                    (Some(id), true) => selection.add(id),
+                    (Some(id), false) => selection.select(id),
+                    (None, true) => {}
+                    (None, false) => selection.clear(),
+                }
+            }
+            SelectionCommand::Query => {}
+        }
+        let selection = manager.get_mut::<SelectionManager>();
+        let events = selection.take_events();
+        let state = SelectionState {
+            active: selection.active(),
+            selected: selection.selected().to_vec(),
+            events,
+        };
+        if !state.events.is_empty() {
+            manager
+                .get_mut::<SceneManager>()
+                .set_gizmo_target(state.active);
+        }
+        state
+    }
+}
@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+use crate::facade::Command;
+use crate::io::settings::Settings;
+use crate::managers::camera_manager::CameraManager;
+use crate::managers::diagnostics_manager::DiagnosticsManager;
+use crate::managers::settings_manager::SettingsManager;
+use crate::managers::ManagerSolution;
+
+pub enum SettingsCommandReturn {
+    Nothing,
+    Settings(Settings),
+    Error(String),
+}
+
+impl SettingsCommandReturn {
+    #[inline]
+    pub fn into_settings(self) -> Option<Settings> {
+        if let Self::Settings(settings) = self {
+            return Some(settings);
+        }
+        None
+    }
+}
+
+pub enum SettingsCommand {
+    /// Reads the settings from the file, or from the user config directory
+    /// when `None` is given, applies and returns them
+    Load(Option<PathBuf>),
+    Save,
+    Set(Settings),
+    Query,
+}
+
+impl Command for SettingsCommand {
+    type ReturnType = SettingsCommandReturn;
+    fn exec(self, manager: &mut ManagerSolution) -> Self::ReturnType {
+        let sm = manager.get_mut::<SettingsManager>();
+        match self {
+            SettingsCommand::Load(path) => {
+                if let Err(err) = sm.load(path) {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(None, format!("failed to load settings: {err}"));
+                    return SettingsCommandReturn::Error(err.to_string());
+                }
+                let settings = sm.settings().clone();
+                apply_camera(manager, &settings);
+                return SettingsCommandReturn::Settings(settings);
+            }
+            SettingsCommand::Save => {
+                if let Err(err) = sm.save() {
+                    manager
+                        .get_mut::<DiagnosticsManager>()
+                        .error(None, format!("failed to save settings: {err}"));
+                    return SettingsCommandReturn::Error(err.to_string());
+                }
+            }
+            SettingsCommand::Set(settings) => {
+                apply_camera(manager, &settings);
+                *manager.get_mut::<SettingsManager>().settings_mut() = settings;
+            }
+            SettingsCommand::Query => {
+                return SettingsCommandReturn::Settings(sm.settings().clone());
+            }
+        }
+        SettingsCommandReturn::Nothing
+    }
+}
+
+/// Hands the camera control of the settings to the camera
+fn apply_camera(manager: &mut ManagerSolution, settings: &Settings) {
+    manager.get_mut::<CameraManager>().get_mut_camera().control = settings.camera;
+}
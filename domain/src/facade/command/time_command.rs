@@ -0,0 +1,52 @@
+use crate::facade::command::animation_command::apply_animation;
+use crate::facade::command::script_command::run_due_scripts;
+use crate::facade::Command;
+use crate::managers::animation_manager::AnimationManager;
+use crate::managers::event_manager::{EventManager, ParamChanged};
+use crate::managers::scene_manager::SceneManager;
+use crate::managers::time_manager::FrameTime;
+use crate::managers::time_manager::TimeManager;
+use crate::managers::ManagerSolution;
+
+#[derive(Debug)]
+pub enum TimeCommand {
+    /// Starts a new frame that took the given seconds of wall time, plays
+    /// the animation, moves the animated objects of the scene and runs the
+    /// due scripts, see [`AnimationCommand`] and [`ScriptCommand`]
+    ///
+    /// [`AnimationCommand`]: crate::facade::AnimationCommand
+    /// [`ScriptCommand`]: crate::facade::ScriptCommand
+    Tick(f32),
+    Play,
+    Pause,
+    SetTimeScale(f32),
+    /// Returns the time of the current frame without advancing it
+    Query,
+}
+
+impl Command for TimeCommand {
+    type ReturnType = FrameTime;
+    fn exec(self, manager: &mut ManagerSolution) -> FrameTime {
+        let tm = manager.get_mut::<TimeManager>();
+        match self {
+            TimeCommand::Tick(real_dt) => {
+                tm.tick(real_dt);
+                let dt = tm.dt();
+                let values = manager.get_mut::<AnimationManager>().advance(dt);
+                apply_animation(manager, &values);
+                for id in manager.get_mut::<SceneManager>().update(dt) {
+                    manager
+                        .get_mut::<EventManager>()
+                        .publish(ParamChanged { id });
+                }
+                run_due_scripts(manager, real_dt);
+                return manager.get::<TimeManager>().frame_time();
+            }
+            TimeCommand::Play => tm.play(),
+            TimeCommand::Pause => tm.pause(),
+            TimeCommand::SetTimeScale(scale) => tm.set_time_scale(scale),
+            TimeCommand::Query => {}
+        }
+        tm.frame_time()
+    }
+}
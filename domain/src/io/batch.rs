@@ -0,0 +1,129 @@
+//! Batch rendering configs in TOML.
+//!
+//! Every `[[render]]` table renders a scene file, or the current scene when
+//! it has none, from a saved camera, or the current one, into an image:
+//!
+//! ```toml
+//! [[render]]
+//! scene = "scenes/sunset.ron"
+//! camera = "front"
+//! size = [1920, 1080]
+//! output = "figures/sunset.png"
+//! ```
+//!
+//! The paths are relative to the config. Outputs ending in `.exr` get the
+//! linear light of the clouds, the others a PNG.
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug)]
+pub enum BatchError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::Io(err) => write!(f, "{err}"),
+            BatchError::Parse(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for BatchError {}
+
+impl From<std::io::Error> for BatchError {
+    fn from(value: std::io::Error) -> Self {
+        BatchError::Io(value)
+    }
+}
+
+impl From<toml::de::Error> for BatchError {
+    fn from(value: toml::de::Error) -> Self {
+        BatchError::Parse(value)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchConfig {
+    #[serde(default, rename = "render")]
+    pub renders: Vec<BatchRender>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchRender {
+    /// Scene file, see [`crate::io::scene::load_scene`]
+    #[serde(default)]
+    pub scene: Option<PathBuf>,
+    /// Name of a saved camera
+    #[serde(default)]
+    pub camera: Option<String>,
+    pub size: [usize; 2],
+    pub output: PathBuf,
+}
+
+impl BatchRender {
+    #[inline]
+    pub fn is_hdr(&self) -> bool {
+        self.output.extension().is_some_and(|x| x == "exr")
+    }
+}
+
+/// Reads the config with its paths joined to the directory of the file
+pub fn load_batch(path: impl AsRef<Path>) -> Result<BatchConfig, BatchError> {
+    let path = path.as_ref();
+    let mut config: BatchConfig = toml::from_str(&fs::read_to_string(path)?)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for render in &mut config.renders {
+        render.output = dir.join(&render.output);
+        if let Some(scene) = &mut render.scene {
+            *scene = dir.join(&scene);
+        }
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_batch() {
+        let dir = std::env::temp_dir().join("batch_test");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("batch.toml");
+        let text = r#"
+            [[render]]
+            scene = "sunset.ron"
+            camera = "front"
+            size = [320, 240]
+            output = "figures/sunset.exr"
+
+            [[render]]
+            size = [64, 64]
+            output = "/tmp/current.png"
+        "#;
+        fs::write(&path, text).unwrap();
+        let config = load_batch(&path).unwrap();
+        assert_eq!(config.renders.len(), 2);
+        let [sunset, current] = &config.renders[..] else {
+            unreachable!()
+        };
+        assert_eq!(sunset.scene, Some(dir.join("sunset.ron")));
+        assert_eq!(sunset.camera.as_deref(), Some("front"));
+        assert_eq!(sunset.output, dir.join("figures/sunset.exr"));
+        assert!(sunset.is_hdr());
+        assert_eq!(current.scene, None);
+        assert_eq!(current.output, Path::new("/tmp/current.png"));
+        assert!(!current.is_hdr());
+
+        fs::write(&path, "[[render]]\nsize = [1, 1]\n").unwrap();
+        assert!(matches!(load_batch(&path), Err(BatchError::Parse(_))));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+// Density and lighting of the cloud for a single directional light. Mirrors
+// `Cloud::sample_density` and `Cloud::march_aligned` of cloud.rs, the
+// constants above are written by the exporter.
+
+// The noise volumes of the cloud, define the names before the snippet to
+// take other samplers, e.g. the channels of Shadertoy
+#ifndef CLOUD_SHAPE_NOISE
+uniform highp sampler3D cloudShapeNoise;
+#define CLOUD_SHAPE_NOISE cloudShapeNoise
+#endif
+#ifndef CLOUD_DETAIL_NOISE
+uniform highp sampler3D cloudDetailNoise;
+#define CLOUD_DETAIL_NOISE cloudDetailNoise
+#endif
+#ifndef CLOUD_WEATHER_MAP
+uniform highp sampler3D cloudWeatherMap;
+#define CLOUD_WEATHER_MAP cloudWeatherMap
+#endif
+
+const float CLOUD_PI = 3.14159265358979;
+
+// Nearest texel, wrapped around, as `Texture3D::sample_level`
+vec4 cloudSampleLevel(highp sampler3D t, vec3 uvw) {
+    ivec3 size = textureSize(t, 0);
+    ivec3 texel = ivec3(uvw * vec3(size));
+    ivec3 wrapped = texel - size * ivec3(floor(vec3(texel) / vec3(size)));
+    return texelFetch(t, wrapped, 0);
+}
+
+float cloudRemap(float v, float minOld, float maxOld, float minNew, float maxNew) {
+    return minNew + (v - minOld) * (maxNew - minNew) / (maxOld - minOld);
+}
+
+// Distance to the volume and the distance inside it along the ray
+vec2 cloudBoxDst(vec3 origin, vec3 dir) {
+    vec3 t0 = (CLOUD_BB_MIN - origin) / dir;
+    vec3 t1 = (CLOUD_BB_MAX - origin) / dir;
+    vec3 tmin = min(t0, t1);
+    vec3 tmax = max(t0, t1);
+    float dstA = max(max(tmin.x, tmin.y), tmin.z);
+    float dstB = min(min(tmax.x, tmax.y), tmax.z);
+    float dstToBox = max(0.0, dstA);
+    return vec2(dstToBox, max(0.0, dstB - dstToBox));
+}
+
+float cloudDensity(vec3 p) {
+    vec3 uvw = p * CLOUD_SCALE * (1.0 / 1000.0) + CLOUD_OFFSET * (1.0 / 100.0);
+    vec4 shape = abs(cloudSampleLevel(CLOUD_SHAPE_NOISE, uvw));
+
+    vec3 size = CLOUD_BB_MAX - CLOUD_BB_MIN;
+    vec3 center = 0.5 * (CLOUD_BB_MAX + CLOUD_BB_MIN);
+    float dstFromEdgeX = min(min(p.x - CLOUD_BB_MIN.x, CLOUD_BB_MAX.x - p.x), CLOUD_EDGE_DISTANCE);
+    float dstFromEdgeZ = min(min(p.z - CLOUD_BB_MIN.z, CLOUD_BB_MAX.z - p.z), CLOUD_EDGE_DISTANCE);
+    float edgeWeight = min(dstFromEdgeX, dstFromEdgeZ) / CLOUD_EDGE_DISTANCE;
+
+    vec2 weatherUv = (size.xz * 0.5 + (p.xz - center.xz)) / max(size.x, size.z);
+    float weather = cloudSampleLevel(CLOUD_WEATHER_MAP, vec3(weatherUv.x, 0.0, weatherUv.y)).x * 0.5;
+    float gMin = cloudRemap(weather, 0.0, 1.0, 0.1, 0.5);
+    float gMax = cloudRemap(weather, 0.0, 1.0, gMin, 0.9);
+
+    float heightPercent = (p.y - CLOUD_BB_MIN.y) / size.y;
+    float heightGradient = clamp(cloudRemap(heightPercent, 0.0, gMin, 0.0, 1.0), 0.0, 1.0)
+        * clamp(cloudRemap(heightPercent, 1.0, gMax, 0.0, 1.0), 0.0, 1.0)
+        * edgeWeight * CLOUD_HEIGHT_MAP_FACTOR;
+
+    float shapeFbm = dot(shape, CLOUD_SHAPE_WEIGHTS) * heightGradient;
+    float baseShapeDensity = shapeFbm + CLOUD_DENSITY_OFFSET * 0.1;
+    if (baseShapeDensity <= 0.0) {
+        return 0.0;
+    }
+
+    vec3 detailPos = uvw * CLOUD_DETAIL_NOISE_SCALE + CLOUD_DETAIL_OFFSET * (1.0 / 100.0)
+        + CLOUD_OFFSET * (1.0 / 100.0);
+    vec4 detail = abs(cloudSampleLevel(CLOUD_DETAIL_NOISE, detailPos));
+    float detailFbm = dot(detail, CLOUD_DETAIL_WEIGHTS);
+    float oneMinusShape = 1.0 - shapeFbm;
+    float detailErodeWeight = oneMinusShape * oneMinusShape * oneMinusShape;
+    float cloudDensity = baseShapeDensity
+        - (1.0 - detailFbm) * detailErodeWeight * CLOUD_DETAIL_NOISE_WEIGHT;
+    return cloudDensity * CLOUD_DENSITY_MULTIPLIER;
+}
+
+float cloudHg(float a, float g) {
+    float g2 = g * g;
+    return (1.0 - g2) / (4.0 * CLOUD_PI * pow(1.0 + g2 - 2.0 * g * a, 1.5));
+}
+
+float cloudPhase(float a) {
+    vec4 p = CLOUD_PHASE_PARAMS;
+    float hgBlend = cloudHg(a, p.x) * 0.5 + cloudHg(a, p.y) * 0.5;
+    return p.y + hgBlend * p.z;
+}
+
+// Share of the light reaching the point from the direction
+float cloudLightTransmittance(vec3 start, vec3 dir) {
+    float stepSize = cloudBoxDst(start, dir).y / float(CLOUD_NUM_STEPS_LIGHT);
+    vec3 p = start + dir * stepSize;
+    float totalDensity = 0.0;
+    for (int i = 0; i < CLOUD_NUM_STEPS_LIGHT; i++) {
+        totalDensity += max(cloudDensity(p), 0.0);
+        p += dir * stepSize;
+    }
+    float transmittance = exp(-(totalDensity * CLOUD_LIGHT_ABSORPTION_TOWARD_SUN * stepSize));
+    return mix(transmittance, 1.0, CLOUD_DARKNESS_THRESHOLD);
+}
+
+// Marches a view ray in world space lit by a directional light. Returns the
+// linear light leaving the cloud, weighted by the opacity, and the opacity.
+vec4 cloudMarch(vec3 rayOrigin, vec3 rayDir, vec3 dirToLight, vec3 light) {
+    vec3 origin = (CLOUD_VOLUME_MATRIX * vec4(rayOrigin, 1.0)).xyz;
+    vec3 dir = (CLOUD_VOLUME_MATRIX * vec4(rayDir, 0.0)).xyz;
+    vec3 toLight = (CLOUD_VOLUME_MATRIX * vec4(dirToLight, 0.0)).xyz;
+
+    vec2 rayBoxInfo = cloudBoxDst(origin, dir);
+    float dstLimit = rayBoxInfo.y;
+    if (dstLimit <= 0.0) {
+        return vec4(0.0);
+    }
+
+    int numSteps = CLOUD_NUM_STEPS;
+    if (CLOUD_AUTO_STEPS) {
+        float diagonal = max(length(CLOUD_BB_MAX - CLOUD_BB_MIN), 1.1920929e-7);
+        int steps = int(ceil(float(CLOUD_NUM_STEPS) * dstLimit / diagonal));
+        numSteps = clamp(steps, 1, CLOUD_NUM_STEPS);
+    }
+    float stepSize = dstLimit / float(numSteps);
+    vec3 entryPoint = origin + rayBoxInfo.x * dir;
+    float cosAngle = dot(dir, toLight);
+    float phase = cloudPhase(cosAngle);
+
+    float transmittance = 1.0;
+    vec3 lightEnergy = vec3(0.0);
+    for (int i = 0; i < numSteps; i++) {
+        vec3 rayPos = entryPoint + dir * (float(i) * stepSize);
+        float density = cloudDensity(rayPos);
+        if (density > 0.1) {
+            lightEnergy += density * stepSize * transmittance
+                * cloudLightTransmittance(rayPos, toLight) * phase * light;
+            transmittance *= exp(-(density * stepSize * CLOUD_LIGHT_ABSORPTION_THROUGH_CLOUD));
+            if (transmittance < 0.01) {
+                break;
+            }
+        }
+    }
+
+    // The light seen through the thin parts of the cloud
+    float focusedEyeCos = pow(clamp(cosAngle, 0.0, 1.0), CLOUD_GLOW_FOCUS);
+    float glow = clamp(cloudHg(focusedEyeCos, CLOUD_PHASE_PARAMS.w), -1.0, 1.0) * transmittance;
+    vec3 col = lightEnergy * CLOUD_TINT * (1.0 - clamp(glow, 0.0, 1.0)) + CLOUD_TINT * light * glow;
+    return vec4(col * (1.0 - transmittance), 1.0 - transmittance);
+}
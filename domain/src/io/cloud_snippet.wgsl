@@ -0,0 +1,142 @@
+// Density and lighting of the cloud for a single directional light. Mirrors
+// `Cloud::sample_density` and `Cloud::march_aligned` of cloud.rs, the
+// constants above are written by the exporter.
+
+// The noise volumes of the cloud, bind them to the layout of the pipeline
+@group(0) @binding(0) var cloud_shape_noise: texture_3d<f32>;
+@group(0) @binding(1) var cloud_detail_noise: texture_3d<f32>;
+@group(0) @binding(2) var cloud_weather_map: texture_3d<f32>;
+
+const CLOUD_PI: f32 = 3.14159265358979;
+
+// Nearest texel, wrapped around, as `Texture3D::sample_level`
+fn cloud_sample_level(t: texture_3d<f32>, uvw: vec3<f32>) -> vec4<f32> {
+    let size = vec3<i32>(textureDimensions(t));
+    let texel = vec3<i32>(uvw * vec3<f32>(size));
+    return textureLoad(t, (texel % size + size) % size, 0);
+}
+
+fn cloud_remap(v: f32, min_old: f32, max_old: f32, min_new: f32, max_new: f32) -> f32 {
+    return min_new + (v - min_old) * (max_new - min_new) / (max_old - min_old);
+}
+
+// Distance to the volume and the distance inside it along the ray
+fn cloud_box_dst(origin: vec3<f32>, dir: vec3<f32>) -> vec2<f32> {
+    let t0 = (CLOUD_BB_MIN - origin) / dir;
+    let t1 = (CLOUD_BB_MAX - origin) / dir;
+    let tmin = min(t0, t1);
+    let tmax = max(t0, t1);
+    let dst_a = max(max(tmin.x, tmin.y), tmin.z);
+    let dst_b = min(min(tmax.x, tmax.y), tmax.z);
+    let dst_to_box = max(0.0, dst_a);
+    return vec2(dst_to_box, max(0.0, dst_b - dst_to_box));
+}
+
+fn cloud_density(p: vec3<f32>) -> f32 {
+    let uvw = p * CLOUD_SCALE * (1.0 / 1000.0) + CLOUD_OFFSET * (1.0 / 100.0);
+    let shape = abs(cloud_sample_level(cloud_shape_noise, uvw));
+
+    let size = CLOUD_BB_MAX - CLOUD_BB_MIN;
+    let center = 0.5 * (CLOUD_BB_MAX + CLOUD_BB_MIN);
+    let dst_from_edge_x = min(min(p.x - CLOUD_BB_MIN.x, CLOUD_BB_MAX.x - p.x), CLOUD_EDGE_DISTANCE);
+    let dst_from_edge_z = min(min(p.z - CLOUD_BB_MIN.z, CLOUD_BB_MAX.z - p.z), CLOUD_EDGE_DISTANCE);
+    let edge_weight = min(dst_from_edge_x, dst_from_edge_z) / CLOUD_EDGE_DISTANCE;
+
+    let weather_uv = (size.xz * 0.5 + (p.xz - center.xz)) / max(size.x, size.z);
+    let weather = cloud_sample_level(cloud_weather_map, vec3(weather_uv.x, 0.0, weather_uv.y)).x * 0.5;
+    let g_min = cloud_remap(weather, 0.0, 1.0, 0.1, 0.5);
+    let g_max = cloud_remap(weather, 0.0, 1.0, g_min, 0.9);
+
+    let height_percent = (p.y - CLOUD_BB_MIN.y) / size.y;
+    let height_gradient = clamp(cloud_remap(height_percent, 0.0, g_min, 0.0, 1.0), 0.0, 1.0)
+        * clamp(cloud_remap(height_percent, 1.0, g_max, 0.0, 1.0), 0.0, 1.0)
+        * edge_weight * CLOUD_HEIGHT_MAP_FACTOR;
+
+    let shape_fbm = dot(shape, CLOUD_SHAPE_WEIGHTS) * height_gradient;
+    let base_shape_density = shape_fbm + CLOUD_DENSITY_OFFSET * 0.1;
+    if base_shape_density <= 0.0 {
+        return 0.0;
+    }
+
+    let detail_pos = uvw * CLOUD_DETAIL_NOISE_SCALE + CLOUD_DETAIL_OFFSET * (1.0 / 100.0)
+        + CLOUD_OFFSET * (1.0 / 100.0);
+    let detail = abs(cloud_sample_level(cloud_detail_noise, detail_pos));
+    let detail_fbm = dot(detail, CLOUD_DETAIL_WEIGHTS);
+    let one_minus_shape = 1.0 - shape_fbm;
+    let detail_erode_weight = one_minus_shape * one_minus_shape * one_minus_shape;
+    let cloud_density = base_shape_density
+        - (1.0 - detail_fbm) * detail_erode_weight * CLOUD_DETAIL_NOISE_WEIGHT;
+    return cloud_density * CLOUD_DENSITY_MULTIPLIER;
+}
+
+fn cloud_hg(a: f32, g: f32) -> f32 {
+    let g2 = g * g;
+    return (1.0 - g2) / (4.0 * CLOUD_PI * pow(1.0 + g2 - 2.0 * g * a, 1.5));
+}
+
+fn cloud_phase(a: f32) -> f32 {
+    let p = CLOUD_PHASE_PARAMS;
+    let hg_blend = cloud_hg(a, p.x) * 0.5 + cloud_hg(a, p.y) * 0.5;
+    return p.y + hg_blend * p.z;
+}
+
+// Share of the light reaching the point from the direction
+fn cloud_light_transmittance(start: vec3<f32>, dir: vec3<f32>) -> f32 {
+    let step_size = cloud_box_dst(start, dir).y / f32(CLOUD_NUM_STEPS_LIGHT);
+    var p = start + dir * step_size;
+    var total_density = 0.0;
+    for (var i = 0u; i < CLOUD_NUM_STEPS_LIGHT; i++) {
+        total_density += max(cloud_density(p), 0.0);
+        p += dir * step_size;
+    }
+    let transmittance = exp(-(total_density * CLOUD_LIGHT_ABSORPTION_TOWARD_SUN * step_size));
+    return mix(transmittance, 1.0, CLOUD_DARKNESS_THRESHOLD);
+}
+
+// Marches a view ray in world space lit by a directional light. Returns the
+// linear light leaving the cloud, weighted by the opacity, and the opacity.
+fn cloud_march(ray_origin: vec3<f32>, ray_dir: vec3<f32>, dir_to_light: vec3<f32>, light: vec3<f32>) -> vec4<f32> {
+    let origin = (CLOUD_VOLUME_MATRIX * vec4(ray_origin, 1.0)).xyz;
+    let dir = (CLOUD_VOLUME_MATRIX * vec4(ray_dir, 0.0)).xyz;
+    let to_light = (CLOUD_VOLUME_MATRIX * vec4(dir_to_light, 0.0)).xyz;
+
+    let ray_box_info = cloud_box_dst(origin, dir);
+    let dst_limit = ray_box_info.y;
+    if dst_limit <= 0.0 {
+        return vec4(0.0);
+    }
+
+    var num_steps = CLOUD_NUM_STEPS;
+    if CLOUD_AUTO_STEPS {
+        let diagonal = max(length(CLOUD_BB_MAX - CLOUD_BB_MIN), 1.1920929e-7);
+        let steps = u32(ceil(f32(CLOUD_NUM_STEPS) * dst_limit / diagonal));
+        num_steps = clamp(steps, 1u, CLOUD_NUM_STEPS);
+    }
+    let step_size = dst_limit / f32(num_steps);
+    let entry_point = origin + ray_box_info.x * dir;
+    let cos_angle = dot(dir, to_light);
+    let phase = cloud_phase(cos_angle);
+
+    var transmittance = 1.0;
+    var light_energy = vec3(0.0);
+    var dst_travelled = 0.0;
+    while dst_travelled < dst_limit {
+        let ray_pos = entry_point + dir * dst_travelled;
+        let density = cloud_density(ray_pos);
+        if density > 0.1 {
+            light_energy += density * step_size * transmittance
+                * cloud_light_transmittance(ray_pos, to_light) * phase * light;
+            transmittance *= exp(-(density * step_size * CLOUD_LIGHT_ABSORPTION_THROUGH_CLOUD));
+            if transmittance < 0.01 {
+                break;
+            }
+        }
+        dst_travelled += step_size;
+    }
+
+    // The light seen through the thin parts of the cloud
+    let focused_eye_cos = pow(clamp(cos_angle, 0.0, 1.0), CLOUD_GLOW_FOCUS);
+    let glow = clamp(cloud_hg(focused_eye_cos, CLOUD_PHASE_PARAMS.w), -1.0, 1.0) * transmittance;
+    let col = light_energy * CLOUD_TINT * (1.0 - clamp(glow, 0.0, 1.0)) + CLOUD_TINT * light * glow;
+    return vec4(col * (1.0 - transmittance), 1.0 - transmittance);
+}
@@ -0,0 +1,339 @@
+//! glTF 2.0 export of the scene layout.
+//!
+//! The document is written as a `.gltf` file with its geometry in a `.bin`
+//! file of the same name. The density of every cloud is sampled into a raw
+//! volume next to them, see [`DensityVolume`], and referenced from the
+//! `extras` of the cloud node.
+
+use std::fs;
+use std::path::Path;
+
+use egui::{Color32, Rgba};
+use glam::{Mat4, Vec3};
+use serde_json::{json, Value};
+
+use crate::io::volume::DensityVolume;
+use crate::managers::job_manager::JobContext;
+use crate::object::objects::density_field::DENSITY_FIELD_SIZE;
+use crate::object::objects::Cloud;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const LINES: u32 = 1;
+const TRIANGLES: u32 = 4;
+
+/// Nodes of the scene with their geometry, built by
+/// [`crate::visitor::gltf_visitor::GltfExportVisitor`]
+#[derive(Default)]
+pub struct GltfDocument {
+    nodes: Vec<Value>,
+    meshes: Vec<Value>,
+    materials: Vec<Value>,
+    accessors: Vec<Value>,
+    buffer_views: Vec<Value>,
+    cameras: Vec<Value>,
+    lights: Vec<Value>,
+    buffer: Vec<u8>,
+    /// Clouds sampled into volumes when saved, by their nodes
+    volumes: Vec<(usize, Cloud)>,
+}
+
+impl GltfDocument {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn nodes(&self) -> &[Value] {
+        &self.nodes
+    }
+
+    /// Node with a mesh of triangles, returns its index
+    pub fn add_triangles(
+        &mut self,
+        name: &str,
+        matrix: Mat4,
+        vertices: &[Vec3],
+        faces: &[[u32; 3]],
+        color: Color32,
+    ) -> usize {
+        let indices = faces.iter().flatten().copied().collect::<Vec<_>>();
+        self.add_primitive(name, matrix, vertices, &indices, TRIANGLES, color)
+    }
+
+    /// Node with a mesh of line segments, returns its index
+    pub fn add_lines(
+        &mut self,
+        name: &str,
+        matrix: Mat4,
+        segments: &[(Vec3, Vec3)],
+        color: Color32,
+    ) -> usize {
+        let vertices = segments
+            .iter()
+            .flat_map(|(a, b)| [*a, *b])
+            .collect::<Vec<_>>();
+        let indices = (0..vertices.len() as u32).collect::<Vec<_>>();
+        self.add_primitive(name, matrix, &vertices, &indices, LINES, color)
+    }
+
+    /// Perspective camera looking along -z of the node
+    pub fn add_camera(
+        &mut self,
+        name: &str,
+        matrix: Mat4,
+        yfov: f32,
+        aspect: f32,
+        near: f32,
+        far: f32,
+    ) -> usize {
+        self.cameras.push(json!({
+            "name": name,
+            "type": "perspective",
+            "perspective": {
+                "yfov": yfov,
+                "aspectRatio": aspect,
+                "znear": near,
+                "zfar": far,
+            },
+        }));
+        let camera = self.cameras.len() - 1;
+        self.add_node(json!({ "name": name, "matrix": matrix_array(matrix), "camera": camera }))
+    }
+
+    /// Directional light of `KHR_lights_punctual` shining along -z of the
+    /// node
+    pub fn add_directional_light(
+        &mut self,
+        name: &str,
+        matrix: Mat4,
+        color: Color32,
+        intensity: f32,
+    ) -> usize {
+        self.lights.push(json!({
+            "name": name,
+            "type": "directional",
+            "color": linear_rgb(color),
+            "intensity": intensity,
+        }));
+        let light = self.lights.len() - 1;
+        self.add_node(json!({
+            "name": name,
+            "matrix": matrix_array(matrix),
+            "extensions": { "KHR_lights_punctual": { "light": light } },
+        }))
+    }
+
+    /// Samples the density of the cloud into a volume next to the document
+    /// when it is saved. The voxels are in the space of the node.
+    pub fn attach_volume(&mut self, node: usize, cloud: Cloud) {
+        self.volumes.push((node, cloud));
+    }
+
+    /// Writes `path`, the `.bin` file next to it and the volumes of the
+    /// clouds. Nothing is written when the job is cancelled.
+    pub fn save(mut self, path: impl AsRef<Path>, context: &JobContext) -> Result<(), String> {
+        let path = path.as_ref();
+        let error = |path: &Path, err: std::io::Error| format!("{}: {err}", path.display());
+        let stem = path
+            .file_stem()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        // Every volume is sampled before the first write, so a cancelled
+        // job leaves no files behind
+        let mut sampled = Vec::with_capacity(self.volumes.len());
+        for (node, cloud) in std::mem::take(&mut self.volumes) {
+            let size = [DENSITY_FIELD_SIZE; 3];
+            let Some(volume) = DensityVolume::sample(&cloud, size, context) else {
+                return Ok(());
+            };
+            sampled.push((node, volume));
+        }
+        for (node, volume) in sampled {
+            let name = self.nodes[node]["name"].as_str().unwrap_or_default();
+            let raw = path.with_file_name(format!("{stem}_{name}.raw"));
+            volume.save_raw(&raw).map_err(|err| error(&raw, err))?;
+            let file_name =
+                |path: &Path| path.file_name().map(|x| x.to_string_lossy().into_owned());
+            self.nodes[node]["extras"] = json!({
+                "density": {
+                    "raw": file_name(&raw),
+                    "description": file_name(&raw.with_extension("json")),
+                },
+            });
+        }
+
+        let bin = path.with_extension("bin");
+        fs::write(&bin, &self.buffer).map_err(|err| error(&bin, err))?;
+        let uri = bin
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let json =
+            serde_json::to_string_pretty(&self.to_json(&uri)).map_err(|err| err.to_string())?;
+        fs::write(path, json).map_err(|err| error(path, err))
+    }
+
+    /// The document with its buffer at `uri`
+    pub fn to_json(&self, uri: &str) -> Value {
+        let mut document = json!({
+            "asset": { "version": "2.0", "generator": "coursework" },
+            "scene": 0,
+            "scenes": [{ "nodes": (0..self.nodes.len()).collect::<Vec<_>>() }],
+            "nodes": self.nodes,
+        });
+        let mut set = |key: &str, values: &[Value]| {
+            if !values.is_empty() {
+                document[key] = json!(values);
+            }
+        };
+        set("meshes", &self.meshes);
+        set("materials", &self.materials);
+        set("accessors", &self.accessors);
+        set("bufferViews", &self.buffer_views);
+        set("cameras", &self.cameras);
+        if !self.buffer.is_empty() {
+            document["buffers"] = json!([{ "uri": uri, "byteLength": self.buffer.len() }]);
+        }
+        if !self.lights.is_empty() {
+            document["extensionsUsed"] = json!(["KHR_lights_punctual"]);
+            document["extensions"] = json!({ "KHR_lights_punctual": { "lights": self.lights } });
+        }
+        document
+    }
+
+    fn add_node(&mut self, node: Value) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    fn add_primitive(
+        &mut self,
+        name: &str,
+        matrix: Mat4,
+        vertices: &[Vec3],
+        indices: &[u32],
+        mode: u32,
+        color: Color32,
+    ) -> usize {
+        let (min, max) = vertices.iter().fold(
+            (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+            |(min, max), x| (min.min(*x), max.max(*x)),
+        );
+        let positions = vertices.iter().flat_map(|x| x.to_array());
+        let view = self.add_buffer_view(positions.map(f32::to_le_bytes), ARRAY_BUFFER);
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": vertices.len(),
+            "type": "VEC3",
+            "min": min.to_array(),
+            "max": max.to_array(),
+        }));
+        let position = self.accessors.len() - 1;
+        let view = self.add_buffer_view(
+            indices.iter().map(|x| x.to_le_bytes()),
+            ELEMENT_ARRAY_BUFFER,
+        );
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }));
+        let index = self.accessors.len() - 1;
+
+        let [r, g, b] = linear_rgb(color);
+        self.materials.push(json!({
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [r, g, b, color.a() as f32 / 255.0],
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+            "doubleSided": true,
+        }));
+        let material = self.materials.len() - 1;
+        self.meshes.push(json!({
+            "name": name,
+            "primitives": [{
+                "attributes": { "POSITION": position },
+                "indices": index,
+                "material": material,
+                "mode": mode,
+            }],
+        }));
+        let mesh = self.meshes.len() - 1;
+        self.add_node(json!({ "name": name, "matrix": matrix_array(matrix), "mesh": mesh }))
+    }
+
+    /// Appends the words to the buffer, returns the index of their view
+    fn add_buffer_view(&mut self, words: impl Iterator<Item = [u8; 4]>, target: u32) -> usize {
+        let offset = self.buffer.len();
+        self.buffer.extend(words.flatten());
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": self.buffer.len() - offset,
+            "target": target,
+        }));
+        self.buffer_views.len() - 1
+    }
+}
+
+fn matrix_array(matrix: Mat4) -> [f32; 16] {
+    matrix.to_cols_array()
+}
+
+fn linear_rgb(color: Color32) -> [f32; 3] {
+    let color = Rgba::from(color.to_opaque());
+    [color.r(), color.g(), color.b()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::tests::dense_cloud;
+
+    #[test]
+    fn test_save_gltf() {
+        let mut document = GltfDocument::new();
+        let faces = [[0, 1, 2]];
+        let vertices = [Vec3::ZERO, Vec3::X, Vec3::Y];
+        document.add_triangles("mesh", Mat4::IDENTITY, &vertices, &faces, Color32::RED);
+        let segments = [(Vec3::ZERO, Vec3::Z)];
+        let cloud = document.add_lines("cloud", Mat4::IDENTITY, &segments, Color32::DARK_RED);
+        document.attach_volume(cloud, dense_cloud());
+        document.add_directional_light("sun", Mat4::IDENTITY, Color32::WHITE, 2.0);
+
+        let path = std::env::temp_dir().join("gltf_test.gltf");
+        document.save(&path, &JobContext::default()).unwrap();
+        let json: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json["nodes"].as_array().unwrap().len(), 3);
+        assert_eq!(json["meshes"][1]["primitives"][0]["mode"], LINES);
+        assert_eq!(json["accessors"][0]["max"], json!([1.0, 1.0, 0.0]));
+        assert_eq!(json["extensionsUsed"], json!(["KHR_lights_punctual"]));
+        // 3 + 2 positions and 3 + 2 indices
+        let bin = fs::read(path.with_extension("bin")).unwrap();
+        assert_eq!(bin.len(), (9 + 6 + 3 + 2) * 4);
+        assert_eq!(json["buffers"][0]["byteLength"], bin.len());
+        assert_eq!(bin[76..80], 1u32.to_le_bytes());
+
+        let raw = json["nodes"][1]["extras"]["density"]["raw"]
+            .as_str()
+            .unwrap();
+        assert_eq!(raw, "gltf_test_cloud.raw");
+        let raw = path.with_file_name(raw);
+        let size = DENSITY_FIELD_SIZE.pow(3) * 4;
+        assert_eq!(fs::metadata(&raw).unwrap().len(), size as u64);
+        for file in [
+            path.clone(),
+            path.with_extension("bin"),
+            raw.with_extension("json"),
+            raw,
+        ] {
+            fs::remove_file(file).unwrap();
+        }
+    }
+}
@@ -0,0 +1,13 @@
+//! Reading and writing scene data in external file formats
+
+pub mod batch;
+pub mod gltf;
+pub mod obj;
+pub mod preset;
+pub mod scene;
+pub mod script;
+pub mod settings;
+pub mod shader;
+pub mod stl;
+pub mod unity;
+pub mod volume;
@@ -0,0 +1,151 @@
+//! Wavefront OBJ import and export
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use glam::Vec3;
+
+use crate::object::objects::Mesh;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(std::io::Error),
+    /// Malformed statement at the given 1-based line
+    Parse {
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io(err) => write!(f, "{err}"),
+            ObjError::Parse { line, message } => write!(f, "line {line}: {message}"),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<std::io::Error> for ObjError {
+    fn from(value: std::io::Error) -> Self {
+        ObjError::Io(value)
+    }
+}
+
+/// Loads the geometry of an OBJ file, see [`read_obj`]
+pub fn load_obj(path: impl AsRef<Path>) -> Result<Mesh, ObjError> {
+    read_obj(BufReader::new(File::open(path)?))
+}
+
+/// Reads vertex positions and faces, polygons are split into triangle fans.
+///
+/// Texture coordinates, normals, groups and materials are ignored.
+pub fn read_obj(reader: impl BufRead) -> Result<Mesh, ObjError> {
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let error = |message: String| ObjError::Parse {
+            line: i + 1,
+            message,
+        };
+
+        let mut tokens = line.split_whitespace();
+        match tokens.next() {
+            Some("v") => {
+                let coords = tokens
+                    .take(3)
+                    .map(|x| x.parse::<f32>())
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|err| error(err.to_string()))?;
+                let [x, y, z] = coords[..] else {
+                    return Err(error("vertex needs three coordinates".to_owned()));
+                };
+                vertices.push(Vec3::new(x, y, z));
+            }
+            Some("f") => {
+                let polygon = tokens
+                    .map(|x| vertex_index(x, vertices.len()).map_err(&error))
+                    .collect::<Result<Vec<_>, _>>()?;
+                if polygon.len() < 3 {
+                    return Err(error("face needs at least three vertices".to_owned()));
+                }
+                for k in 1..polygon.len() - 1 {
+                    faces.push([polygon[0], polygon[k], polygon[k + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Mesh::new(vertices, faces))
+}
+
+/// Writes the mesh to an OBJ file, see [`write_obj`]
+pub fn save_obj(path: impl AsRef<Path>, mesh: &Mesh) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_obj(&mut out, mesh)?;
+    out.flush()
+}
+
+/// Writes the vertex positions and the triangles, nothing else
+pub fn write_obj(mut writer: impl Write, mesh: &Mesh) -> std::io::Result<()> {
+    for v in &mesh.vertices {
+        writeln!(writer, "v {} {} {}", v.x, v.y, v.z)?;
+    }
+    for [a, b, c] in &mesh.faces {
+        writeln!(writer, "f {} {} {}", a + 1, b + 1, c + 1)?;
+    }
+    Ok(())
+}
+
+/// Parses the position part of a face vertex (`v`, `v/vt`, `v//vn` or
+/// `v/vt/vn`), resolving negative indices relative to the end of the list
+fn vertex_index(token: &str, count: usize) -> Result<u32, String> {
+    let position = token.split('/').next().unwrap_or_default();
+    let index: i64 = position.parse().map_err(|_| format!("bad index {token}"))?;
+    let resolved = match index {
+        0 => None,
+        i if i > 0 => Some(i - 1),
+        i => Some(count as i64 + i),
+    };
+    resolved
+        .filter(|&i| (0..count as i64).contains(&i))
+        .map(|i| i as u32)
+        .ok_or_else(|| format!("vertex index {index} out of range"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_obj() {
+        let src = "\
+# unit quad
+v 0 0 0
+v 1 0 0
+v 1 0 1
+v 0 0 1
+vn 0 1 0
+f 1//1 2//1 3//1 4//1
+f -4 -3 -2
+";
+        let mesh = read_obj(src.as_bytes()).unwrap();
+        assert_eq!(mesh.vertices.len(), 4);
+        assert_eq!(mesh.faces, vec![[0, 1, 2], [0, 2, 3], [0, 1, 2]]);
+
+        let err = read_obj("v 0 0 0\nf 1 2 3\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, ObjError::Parse { line: 2, .. }));
+
+        let mut written = Vec::new();
+        write_obj(&mut written, &mesh).unwrap();
+        let read = read_obj(written.as_slice()).unwrap();
+        assert_eq!((read.vertices, read.faces), (mesh.vertices, mesh.faces));
+    }
+}
@@ -0,0 +1,86 @@
+//! Cloud parameter presets in TOML.
+//!
+//! A preset keeps every parameter of a cloud together with the builders of
+//! its noise, the volumes are generated again when it is applied.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::object::objects::cloud::CloudBuilder;
+
+#[derive(Debug)]
+pub enum PresetError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Write(toml::ser::Error),
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::Io(err) => write!(f, "{err}"),
+            PresetError::Parse(err) => write!(f, "{err}"),
+            PresetError::Write(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for PresetError {}
+
+impl From<std::io::Error> for PresetError {
+    fn from(value: std::io::Error) -> Self {
+        PresetError::Io(value)
+    }
+}
+
+impl From<toml::de::Error> for PresetError {
+    fn from(value: toml::de::Error) -> Self {
+        PresetError::Parse(value)
+    }
+}
+
+impl From<toml::ser::Error> for PresetError {
+    fn from(value: toml::ser::Error) -> Self {
+        PresetError::Write(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct CloudPreset {
+    cloud: CloudBuilder,
+}
+
+pub fn load_preset(path: impl AsRef<Path>) -> Result<CloudBuilder, PresetError> {
+    let preset: CloudPreset = toml::from_str(&fs::read_to_string(path)?)?;
+    Ok(preset.cloud)
+}
+
+pub fn save_preset(path: impl AsRef<Path>, cloud: &CloudBuilder) -> Result<(), PresetError> {
+    let preset = CloudPreset { cloud: *cloud };
+    fs::write(path, toml::to_string_pretty(&preset)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::tests::dense_cloud;
+    use crate::object::objects::texture3d::WorleyBuilder;
+
+    #[test]
+    fn test_preset_toml() {
+        let mut cloud = dense_cloud().cloud_params;
+        cloud.density_offset = -3.5;
+        cloud.detail_noise = WorleyBuilder::new().with_seed(7).with_resolution(16).into();
+        let path = std::env::temp_dir().join("preset_test.toml");
+        save_preset(&path, &cloud).unwrap();
+        assert_eq!(load_preset(&path).unwrap(), cloud);
+
+        fs::write(&path, "[cloud]\ncloud_scale = 1.0\n").unwrap();
+        assert!(matches!(load_preset(&path), Err(PresetError::Parse(_))));
+        fs::remove_file(path).unwrap();
+    }
+}
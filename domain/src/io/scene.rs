@@ -0,0 +1,273 @@
+//! Scene files in RON or JSON.
+//!
+//! Clouds and terrains are stored as their builders and regenerated on load,
+//! so the files stay small and independent of the noise resolution.
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::object::camera::Camera;
+use crate::object::objects::cloud::CloudBuilder;
+use crate::object::objects::terrain::TerrainBuilder;
+use crate::object::objects::{
+    Background, Fog, Grid, HeightMap, Light, Mesh, OrientationGizmo, Skybox, Sun, TransformGizmo,
+    Water,
+};
+use crate::object::Component;
+use crate::scene::scene_composite::{SceneObjects, DEBUG_LAYER, DEFAULT_LAYER};
+use crate::scene::ObjectTransform;
+use crate::visitor::serialize_visitor::SerializeVisitor;
+
+#[derive(Debug)]
+pub enum SceneError {
+    Io(std::io::Error),
+    Ron(String),
+    Json(serde_json::Error),
+    /// The file extension is neither `ron` nor `json`
+    UnknownFormat(String),
+    /// A parent refers to an object missing from the file
+    Hierarchy(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Io(err) => write!(f, "{err}"),
+            SceneError::Ron(err) => write!(f, "{err}"),
+            SceneError::Json(err) => write!(f, "{err}"),
+            SceneError::UnknownFormat(ext) => write!(f, "unknown scene format: {ext:?}"),
+            SceneError::Hierarchy(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+impl From<std::io::Error> for SceneError {
+    fn from(value: std::io::Error) -> Self {
+        SceneError::Io(value)
+    }
+}
+
+impl From<serde_json::Error> for SceneError {
+    fn from(value: serde_json::Error) -> Self {
+        SceneError::Json(value)
+    }
+}
+
+/// Serializable counterpart of [`SceneObjects`]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneFile {
+    pub objects: Vec<ObjectEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ObjectEntry {
+    pub name: String,
+    #[serde(default)]
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub transform: ObjectTransform,
+    #[serde(default = "visible_by_default")]
+    pub visible: bool,
+    #[serde(default = "default_layer")]
+    pub layer: String,
+    pub component: ComponentEntry,
+}
+
+/// Serializable counterpart of [`Component`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ComponentEntry {
+    Camera(Camera),
+    Composite(SceneFile),
+    Cloud(Box<CloudBuilder>),
+    Sun(Sun),
+    Grid(Grid),
+    Terrain {
+        builder: TerrainBuilder,
+        #[serde(default)]
+        height_map: Option<HeightMap>,
+    },
+    Gizmo(OrientationGizmo),
+    Background(Background),
+    Water(Water),
+    Skybox(Skybox),
+    Mesh(Mesh),
+    Light(Light),
+    TransformGizmo(TransformGizmo),
+    Fog(Fog),
+}
+
+impl From<&SceneObjects> for SceneFile {
+    fn from(value: &SceneObjects) -> Self {
+        SerializeVisitor::default().scene_file(value)
+    }
+}
+
+impl ComponentEntry {
+    /// Builds the component, regenerating the noise of clouds and terrains
+    pub fn build(self) -> Result<Component, SceneError> {
+        Ok(match self {
+            ComponentEntry::Camera(x) => x.into(),
+            ComponentEntry::Composite(x) => Component::Composite(x.build()?),
+            ComponentEntry::Cloud(x) => x.build().into(),
+            ComponentEntry::Sun(x) => x.into(),
+            ComponentEntry::Grid(x) => x.into(),
+            ComponentEntry::Terrain {
+                builder,
+                height_map,
+            } => {
+                let mut terrain = builder.build();
+                if height_map.is_some() {
+                    terrain.set_height_map(height_map);
+                    terrain.generate_grid();
+                }
+                terrain.into()
+            }
+            ComponentEntry::Gizmo(x) => x.into(),
+            ComponentEntry::Background(x) => x.into(),
+            ComponentEntry::Water(x) => x.into(),
+            ComponentEntry::Skybox(x) => x.into(),
+            ComponentEntry::Mesh(x) => x.into(),
+            ComponentEntry::Light(x) => x.into(),
+            ComponentEntry::TransformGizmo(x) => x.into(),
+            ComponentEntry::Fog(x) => x.into(),
+        })
+    }
+}
+
+impl SceneFile {
+    /// Builds the objects and restores their hierarchy.
+    ///
+    /// Object and custom layer names are leaked since the scene refers to
+    /// them as `'static`.
+    pub fn build(self) -> Result<SceneObjects, SceneError> {
+        let mut scene = SceneObjects::default();
+        let mut parents = Vec::new();
+        for entry in self.objects {
+            let name: &'static str = Box::leak(entry.name.into_boxed_str());
+            scene.add_object(name, entry.component.build()?);
+            if let Some(local) = scene.local_transform_mut(name) {
+                *local = entry.transform;
+            }
+            scene
+                .set_visible(name, entry.visible)
+                .map_err(SceneError::Hierarchy)?;
+            let layer = match entry.layer.as_str() {
+                DEFAULT_LAYER => DEFAULT_LAYER,
+                DEBUG_LAYER => DEBUG_LAYER,
+                _ => Box::leak(entry.layer.into_boxed_str()),
+            };
+            scene
+                .set_layer(name, layer)
+                .map_err(SceneError::Hierarchy)?;
+            if let Some(parent) = entry.parent {
+                parents.push((name, parent));
+            }
+        }
+
+        for (child, parent) in parents {
+            let parent = scene
+                .objects
+                .get_key_value(parent.as_str())
+                .map(|(&k, _)| k)
+                .ok_or_else(|| SceneError::Hierarchy(format!("no object named {parent}")))?;
+            scene
+                .set_parent(child, parent)
+                .map_err(SceneError::Hierarchy)?;
+        }
+        Ok(scene)
+    }
+
+    pub fn to_ron(&self) -> Result<String, SceneError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| SceneError::Ron(err.to_string()))
+    }
+
+    pub fn from_ron(s: &str) -> Result<Self, SceneError> {
+        ron::from_str(s).map_err(|err| SceneError::Ron(err.to_string()))
+    }
+
+    pub fn to_json(&self) -> Result<String, SceneError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, SceneError> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+/// Writes the scene as RON or JSON depending on the file extension. Returns
+/// the names of the objects left out, see [`SerializeVisitor`].
+pub fn save_scene(
+    path: impl AsRef<Path>,
+    scene: &SceneObjects,
+) -> Result<Vec<&'static str>, SceneError> {
+    let path = path.as_ref();
+    let mut visitor = SerializeVisitor::default();
+    let file = visitor.scene_file(scene);
+    let text = match extension(path) {
+        "ron" => file.to_ron()?,
+        "json" => file.to_json()?,
+        ext => return Err(SceneError::UnknownFormat(ext.to_owned())),
+    };
+    fs::write(path, text)?;
+    Ok(visitor.skipped().to_vec())
+}
+
+/// Reads a scene written by [`save_scene`]
+pub fn load_scene(path: impl AsRef<Path>) -> Result<SceneObjects, SceneError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)?;
+    let file = match extension(path) {
+        "ron" => SceneFile::from_ron(&text)?,
+        "json" => SceneFile::from_json(&text)?,
+        ext => return Err(SceneError::UnknownFormat(ext.to_owned())),
+    };
+    file.build()
+}
+
+fn visible_by_default() -> bool {
+    true
+}
+
+fn default_layer() -> String {
+    DEFAULT_LAYER.to_owned()
+}
+
+fn extension(path: &Path) -> &str {
+    path.extension().and_then(|x| x.to_str()).unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn test_scene_file_round_trip() {
+        let mut scene = SceneObjects::default();
+        scene.add_object("sun", Sun::new(10.0, -90.0, -90.0));
+        scene.add_object("box", Mesh::cuboid(Vec3::ONE));
+        scene.add_object("lamp", Light::point(Vec3::Y));
+        scene.set_parent("lamp", "box").unwrap();
+        *scene.local_transform_mut("box").unwrap() = ObjectTransform::from_translation(Vec3::X);
+
+        let file = SceneFile::from(&scene);
+        let ron = SceneFile::from_ron(&file.to_ron().unwrap()).unwrap();
+        let json = SceneFile::from_json(&file.to_json().unwrap()).unwrap();
+        assert_eq!(ron, file);
+        assert_eq!(json, file);
+
+        let loaded = ron.build().unwrap();
+        assert_eq!(loaded.parent("lamp"), Some("box"));
+        assert_eq!(
+            loaded.world_transform("lamp"),
+            scene.world_transform("lamp")
+        );
+        assert_eq!(SceneFile::from(&loaded), file);
+    }
+}
@@ -0,0 +1,225 @@
+//! Command scripts run at startup or on a timer, one command per line:
+//!
+//! ```text
+//! @every 60
+//! load_scene scenes/storm.ron
+//! quality low
+//! turntable 20
+//! ```
+//!
+//! Empty lines and lines starting with `#` are skipped. The optional `@`
+//! line chooses when the script runs, see [`Schedule`].
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::io::settings::QualitySettings;
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(std::io::Error),
+    /// Line the error was found at, counting from one
+    Parse(usize, String),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Io(err) => write!(f, "{err}"),
+            ScriptError::Parse(line, err) => write!(f, "line {line}: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl From<std::io::Error> for ScriptError {
+    fn from(value: std::io::Error) -> Self {
+        ScriptError::Io(value)
+    }
+}
+
+/// When the script runs
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Schedule {
+    /// Once, right after loading, `@startup`
+    #[default]
+    Startup,
+    /// Once, the seconds after loading, `@after 10`
+    After(f32),
+    /// Repeatedly with the period in seconds, `@every 60`
+    Every(f32),
+}
+
+/// Preset ray marching quality of the clouds
+pub fn quality_preset(name: &str) -> Option<QualitySettings> {
+    let (num_steps, num_steps_light, light_interval) = match name {
+        "low" => (64, 8, 4),
+        "medium" => (128, 12, 2),
+        "high" => (200, 20, 1),
+        _ => return None,
+    };
+    Some(QualitySettings {
+        num_steps,
+        num_steps_light,
+        light_interval,
+        ..Default::default()
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    /// `load_scene <path>`
+    LoadScene(PathBuf),
+    /// `save_scene <path>`
+    SaveScene(PathBuf),
+    /// `quality low|medium|high` or `quality <steps> <light steps>`
+    Quality(QualitySettings),
+    /// Turns the camera around the pivot once in the seconds,
+    /// `turntable <seconds>` or `turntable off`
+    Turntable(Option<f32>),
+    /// `play` and `pause` of the clock
+    Play,
+    Pause,
+    /// `time_scale <scale>`
+    TimeScale(f32),
+    /// `animation play [loop]`
+    PlayAnimation(bool),
+    /// `animation stop`
+    StopAnimation,
+    /// Renders the scene into a PNG file in the background,
+    /// `render <width> <height> <path>`
+    Render([usize; 2], PathBuf),
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Script {
+    pub schedule: Schedule,
+    pub statements: Vec<Statement>,
+}
+
+fn parse_number<T: std::str::FromStr>(word: Option<&str>) -> Result<T, String> {
+    let word = word.ok_or("missing number")?;
+    word.parse().map_err(|_| format!("{word} is not a number"))
+}
+
+fn parse_path(rest: &str) -> Result<PathBuf, String> {
+    if rest.is_empty() {
+        return Err("missing path".to_owned());
+    }
+    Ok(PathBuf::from(rest))
+}
+
+fn parse_schedule(line: &str) -> Result<Schedule, String> {
+    let mut words = line.split_whitespace();
+    let schedule = match words.next() {
+        Some("startup") => Schedule::Startup,
+        Some("after") => Schedule::After(parse_number(words.next())?),
+        Some("every") => {
+            let period: f32 = parse_number(words.next())?;
+            if period <= 0.0 {
+                return Err("the period must be positive".to_owned());
+            }
+            Schedule::Every(period)
+        }
+        Some(x) => return Err(format!("unknown schedule {x}")),
+        None => return Err("missing schedule".to_owned()),
+    };
+    Ok(schedule)
+}
+
+fn parse_statement(line: &str) -> Result<Statement, String> {
+    let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim();
+    let mut words = rest.split_whitespace();
+    let statement = match command {
+        "load_scene" => Statement::LoadScene(parse_path(rest)?),
+        "save_scene" => Statement::SaveScene(parse_path(rest)?),
+        "quality" => match quality_preset(rest) {
+            Some(quality) => Statement::Quality(quality),
+            None => Statement::Quality(QualitySettings {
+                num_steps: parse_number(words.next())?,
+                num_steps_light: parse_number(words.next())?,
+                ..Default::default()
+            }),
+        },
+        "turntable" if rest == "off" => Statement::Turntable(None),
+        "turntable" => Statement::Turntable(Some(parse_number(words.next())?)),
+        "play" => Statement::Play,
+        "pause" => Statement::Pause,
+        "time_scale" => Statement::TimeScale(parse_number(words.next())?),
+        "animation" => match (words.next(), words.next()) {
+            (Some("play"), None) => Statement::PlayAnimation(false),
+            (Some("play"), Some("loop")) => Statement::PlayAnimation(true),
+            (Some("stop"), None) => Statement::StopAnimation,
+            _ => return Err(format!("unknown animation command {rest}")),
+        },
+        "render" => {
+            let size = [parse_number(words.next())?, parse_number(words.next())?];
+            let path = words.collect::<Vec<_>>().join(" ");
+            Statement::Render(size, parse_path(&path)?)
+        }
+        _ => return Err(format!("unknown command {command}")),
+    };
+    Ok(statement)
+}
+
+pub fn parse_script(source: &str) -> Result<Script, ScriptError> {
+    let mut script = Script::default();
+    for (i, line) in source.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parsed = match line.strip_prefix('@') {
+            Some(schedule) => parse_schedule(schedule).map(|x| script.schedule = x),
+            None => parse_statement(line).map(|x| script.statements.push(x)),
+        };
+        parsed.map_err(|err| ScriptError::Parse(i + 1, err))?;
+    }
+    Ok(script)
+}
+
+pub fn load_script(path: impl AsRef<Path>) -> Result<Script, ScriptError> {
+    parse_script(&fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let source = "# nightly turntable\n\
+                      @every 60\n\
+                      load_scene scenes/storm.ron\n\
+                      \n\
+                      quality low\n\
+                      quality 100 10\n\
+                      turntable 20\n\
+                      animation play loop\n\
+                      render 640 480 out/frame.png\n";
+        let script = parse_script(source).unwrap();
+        assert_eq!(script.schedule, Schedule::Every(60.0));
+        assert_eq!(
+            script.statements,
+            [
+                Statement::LoadScene("scenes/storm.ron".into()),
+                Statement::Quality(quality_preset("low").unwrap()),
+                Statement::Quality(QualitySettings {
+                    num_steps: 100,
+                    num_steps_light: 10,
+                    ..Default::default()
+                }),
+                Statement::Turntable(Some(20.0)),
+                Statement::PlayAnimation(true),
+                Statement::Render([640, 480], "out/frame.png".into()),
+            ]
+        );
+
+        let err = parse_script("play\nquality ultra\n").unwrap_err();
+        assert!(matches!(err, ScriptError::Parse(2, _)), "{err}");
+        assert!(parse_script("@every 0").is_err());
+    }
+}
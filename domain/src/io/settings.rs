@@ -0,0 +1,140 @@
+//! Application settings kept in a TOML file in the user config directory
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::object::camera::ArcBallController;
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(std::io::Error),
+    Parse(toml::de::Error),
+    Write(toml::ser::Error),
+    /// The platform has no user config directory
+    NoConfigDir,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Io(err) => write!(f, "{err}"),
+            SettingsError::Parse(err) => write!(f, "{err}"),
+            SettingsError::Write(err) => write!(f, "{err}"),
+            SettingsError::NoConfigDir => write!(f, "no user config directory"),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl From<std::io::Error> for SettingsError {
+    fn from(value: std::io::Error) -> Self {
+        SettingsError::Io(value)
+    }
+}
+
+impl From<toml::de::Error> for SettingsError {
+    fn from(value: toml::de::Error) -> Self {
+        SettingsError::Parse(value)
+    }
+}
+
+impl From<toml::ser::Error> for SettingsError {
+    fn from(value: toml::ser::Error) -> Self {
+        SettingsError::Write(value)
+    }
+}
+
+/// Ray marching steps of the clouds and the frame rate held while the
+/// camera moves
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct QualitySettings {
+    pub num_steps: usize,
+    pub num_steps_light: usize,
+    /// View ray steps between the light marches of the clouds
+    pub light_interval: usize,
+    /// Whether the quality is lowered to hold the target frame rate
+    pub adaptive: bool,
+    pub target_fps: f32,
+    /// Whether the clouds read a coarse density while the camera moves
+    pub coarse_density: bool,
+}
+
+impl Default for QualitySettings {
+    fn default() -> Self {
+        Self {
+            num_steps: 200,
+            num_steps_light: 20,
+            light_interval: 1,
+            adaptive: false,
+            target_fps: 30.0,
+            coarse_density: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiSettings {
+    pub show_debug: bool,
+    pub dark_mode: bool,
+}
+
+/// Missing keys fall back to the defaults, so older files stay readable
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub quality: QualitySettings,
+    pub camera: ArcBallController,
+    pub ui: UiSettings,
+    pub last_scene: Option<PathBuf>,
+}
+
+/// `settings.toml` in the config directory of the application
+pub fn default_settings_path() -> Result<PathBuf, SettingsError> {
+    let dir = dirs::config_dir().ok_or(SettingsError::NoConfigDir)?;
+    Ok(dir.join("coursework").join("settings.toml"))
+}
+
+pub fn load_settings(path: impl AsRef<Path>) -> Result<Settings, SettingsError> {
+    Ok(toml::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Writes the settings, creating the directory when needed
+pub fn save_settings(path: impl AsRef<Path>, settings: &Settings) -> Result<(), SettingsError> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, toml::to_string_pretty(settings)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_toml() {
+        let settings = Settings {
+            quality: QualitySettings {
+                num_steps: 42,
+                num_steps_light: 4,
+                adaptive: true,
+                ..Default::default()
+            },
+            last_scene: Some(PathBuf::from("scene.ron")),
+            ..Default::default()
+        };
+        let s = toml::to_string_pretty(&settings).unwrap();
+        assert_eq!(toml::from_str::<Settings>(&s).unwrap(), settings);
+
+        let partial: Settings = toml::from_str("[ui]\ndark_mode = true\n").unwrap();
+        assert!(partial.ui.dark_mode && !partial.ui.show_debug);
+        assert_eq!(partial.quality, QualitySettings::default());
+    }
+}
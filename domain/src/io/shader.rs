@@ -0,0 +1,197 @@
+//! Shader snippets of a cloud in WGSL and GLSL.
+//!
+//! The snippet holds the density and the lighting of the cloud with its
+//! current parameters as constants. The noise volumes are left to be bound by
+//! the pipeline the snippet is pasted into.
+
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+
+use crate::object::objects::Cloud;
+use crate::visitor::raster::color32_to_vec4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShaderLanguage {
+    Wgsl,
+    Glsl,
+}
+
+impl ShaderLanguage {
+    fn source(self) -> &'static str {
+        match self {
+            ShaderLanguage::Wgsl => include_str!("cloud_snippet.wgsl"),
+            ShaderLanguage::Glsl => include_str!("cloud_snippet.glsl"),
+        }
+    }
+}
+
+/// Literal of the value, the literals have no NaN and the cloud would not be
+/// drawn with one anyway
+fn float(x: f32) -> String {
+    let x = if x.is_finite() { x } else { 0.0 };
+    format!("{x:?}")
+}
+
+/// Constant of the snippet
+enum Value {
+    Float(f32),
+    Uint(usize),
+    Bool(bool),
+    Vec3(Vec3),
+    Vec4(Vec4),
+    Mat4(Mat4),
+}
+
+impl Value {
+    fn declare(&self, language: ShaderLanguage, name: &str) -> String {
+        use ShaderLanguage::{Glsl, Wgsl};
+
+        let ty = match (self, language) {
+            (Value::Float(_), Wgsl) => "f32",
+            (Value::Float(_), Glsl) => "float",
+            (Value::Uint(_), Wgsl) => "u32",
+            (Value::Uint(_), Glsl) => "int",
+            (Value::Bool(_), _) => "bool",
+            (Value::Vec3(_), Wgsl) => "vec3<f32>",
+            (Value::Vec3(_), Glsl) => "vec3",
+            (Value::Vec4(_), Wgsl) => "vec4<f32>",
+            (Value::Vec4(_), Glsl) => "vec4",
+            (Value::Mat4(_), Wgsl) => "mat4x4<f32>",
+            (Value::Mat4(_), Glsl) => "mat4",
+        };
+        let components = |values: &[f32]| {
+            let values = values.iter().map(|x| float(*x)).collect::<Vec<_>>();
+            format!("{ty}({})", values.join(", "))
+        };
+        let value = match self {
+            Value::Float(x) => float(*x),
+            Value::Uint(x) if language == Wgsl => format!("{x}u"),
+            Value::Uint(x) => x.to_string(),
+            Value::Bool(x) => x.to_string(),
+            Value::Vec3(x) => components(&x.to_array()),
+            Value::Vec4(x) => components(&x.to_array()),
+            Value::Mat4(x) => components(&x.to_cols_array()),
+        };
+        match language {
+            Wgsl => format!("const {name}: {ty} = {value};"),
+            Glsl => format!("const {ty} {name} = {value};"),
+        }
+    }
+}
+
+/// Snippet of the cloud with its parameters written in
+pub fn export_shader(cloud: &Cloud, language: ShaderLanguage) -> String {
+    let params = &cloud.cloud_params;
+    let bb = params.bounding_box;
+    let normalized = |weights: Vec4| weights / weights.dot(Vec4::ONE);
+    let constants = [
+        ("CLOUD_VOLUME_MATRIX", Value::Mat4(cloud.volume_matrix())),
+        ("CLOUD_BB_MIN", Value::Vec3(bb.min)),
+        ("CLOUD_BB_MAX", Value::Vec3(bb.max)),
+        ("CLOUD_EDGE_DISTANCE", Value::Float(params.edge_distance)),
+        (
+            "CLOUD_HEIGHT_MAP_FACTOR",
+            Value::Float(params.height_map_factor),
+        ),
+        ("CLOUD_SCALE", Value::Float(params.cloud_scale)),
+        ("CLOUD_OFFSET", Value::Vec3(params.offset)),
+        ("CLOUD_DETAIL_OFFSET", Value::Vec3(params.detail_offset)),
+        (
+            "CLOUD_DETAIL_NOISE_SCALE",
+            Value::Float(params.detail_noise_scale),
+        ),
+        (
+            "CLOUD_DETAIL_NOISE_WEIGHT",
+            Value::Float(params.detail_noise_weight),
+        ),
+        (
+            "CLOUD_SHAPE_WEIGHTS",
+            Value::Vec4(normalized(params.shape_noise_weights)),
+        ),
+        (
+            "CLOUD_DETAIL_WEIGHTS",
+            Value::Vec4(normalized(params.detail_weights)),
+        ),
+        ("CLOUD_DENSITY_OFFSET", Value::Float(params.density_offset)),
+        (
+            "CLOUD_DENSITY_MULTIPLIER",
+            Value::Float(params.density_multiplier),
+        ),
+        ("CLOUD_PHASE_PARAMS", Value::Vec4(params.phase_params)),
+        ("CLOUD_GLOW_FOCUS", Value::Float(params.params.x)),
+        (
+            "CLOUD_LIGHT_ABSORPTION_TOWARD_SUN",
+            Value::Float(params.light_absorption_toward_sun),
+        ),
+        (
+            "CLOUD_LIGHT_ABSORPTION_THROUGH_CLOUD",
+            Value::Float(params.light_absorption_through_cloud),
+        ),
+        (
+            "CLOUD_DARKNESS_THRESHOLD",
+            Value::Float(params.darkness_threshold),
+        ),
+        (
+            "CLOUD_TINT",
+            Value::Vec3(color32_to_vec4(params.light_color).xyz()),
+        ),
+        ("CLOUD_NUM_STEPS", Value::Uint(params.num_steps.max(1))),
+        ("CLOUD_NUM_STEPS_LIGHT", Value::Uint(params.num_steps_light)),
+        ("CLOUD_AUTO_STEPS", Value::Bool(params.auto_steps)),
+    ];
+
+    let mut out = String::new();
+    let _ = writeln!(out, "// Shape noise: {:?}", params.noise);
+    let _ = writeln!(out, "// Detail noise: {:?}", params.detail_noise);
+    let _ = writeln!(out, "// Weather map: {:?}", params.weather_noise);
+    out.push('\n');
+    for (name, value) in constants {
+        let _ = writeln!(out, "{}", value.declare(language, name));
+    }
+    out.push('\n');
+    out.push_str(language.source());
+    out
+}
+
+pub fn save_shader(
+    path: impl AsRef<Path>,
+    cloud: &Cloud,
+    language: ShaderLanguage,
+) -> std::io::Result<()> {
+    fs::write(path, export_shader(cloud, language))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::tests::dense_cloud;
+
+    #[test]
+    fn test_export_wgsl() {
+        let mut cloud = dense_cloud();
+        cloud.density_offset = -3.5;
+        cloud.rotation = glam::Quat::from_rotation_y(0.5);
+        let source = export_shader(&cloud, ShaderLanguage::Wgsl);
+        assert!(source.contains("const CLOUD_DENSITY_OFFSET: f32 = -3.5;"));
+
+        let module = naga::front::wgsl::parse_str(&source).unwrap();
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .unwrap();
+    }
+
+    #[test]
+    fn test_export_glsl() {
+        let cloud = dense_cloud();
+        let source = export_shader(&cloud, ShaderLanguage::Glsl);
+        assert!(source.contains("const int CLOUD_NUM_STEPS_LIGHT = "));
+        assert!(source.contains("const mat4 CLOUD_VOLUME_MATRIX = mat4(1.0, 0.0, 0.0, 0.0,"));
+        assert!(source.contains("vec4 cloudMarch("));
+    }
+}
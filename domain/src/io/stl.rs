@@ -0,0 +1,50 @@
+//! Binary STL export
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::object::objects::Mesh;
+
+/// Writes the mesh to a binary STL file, see [`write_stl`]
+pub fn save_stl(path: impl AsRef<Path>, mesh: &Mesh) -> std::io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_stl(&mut out, mesh)?;
+    out.flush()
+}
+
+/// Writes every face with its normal, the header is left blank
+pub fn write_stl(mut writer: impl Write, mesh: &Mesh) -> std::io::Result<()> {
+    writer.write_all(&[0; 80])?;
+    writer.write_all(&(mesh.faces.len() as u32).to_le_bytes())?;
+    for face in &mesh.faces {
+        let normal = mesh.face_normal(*face);
+        for v in std::iter::once(normal).chain(mesh.triangle(*face)) {
+            for x in v.to_array() {
+                writer.write_all(&x.to_le_bytes())?;
+            }
+        }
+        writer.write_all(&[0; 2])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn test_write_stl() {
+        let mesh = Mesh::cuboid(Vec3::ONE);
+        let mut written = Vec::new();
+        write_stl(&mut written, &mesh).unwrap();
+        assert_eq!(written.len(), 84 + 50 * mesh.faces.len());
+        assert_eq!(written[80..84], (mesh.faces.len() as u32).to_le_bytes());
+
+        // Second corner of the first face
+        let corner = mesh.triangle(mesh.faces[0])[1];
+        let x = f32::from_le_bytes(written[84 + 24..84 + 28].try_into().unwrap());
+        assert_eq!(x, corner.x);
+    }
+}
@@ -0,0 +1,377 @@
+//! Cloud settings exported from the Unity clouds project.
+//!
+//! Reads the YAML of a `CloudMaster` component, a material of the cloud
+//! shader or the `WorleyNoiseSettings` assets and maps the values onto a
+//! [`CloudBuilder`]. Both projects sample the noise with the same base scale
+//! and offset speed, so most values carry over as they are.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use egui::Color32;
+use glam::{Vec3, Vec4};
+use serde_yaml::Value;
+
+use crate::object::objects::cloud::CloudBuilder;
+use crate::object::objects::texture3d::{NoiseBuilder, WorleyBuilder};
+
+/// Offset units per unit of the Unity time, see `offsetSpeed` of the shader
+const OFFSET_SPEED: f32 = 100.0;
+/// Direction the Unity shader moves the shape noise in over time
+const SHAPE_DRIFT: Vec3 = Vec3::new(1.0, 0.1, 0.2);
+
+#[derive(Debug)]
+pub enum UnityError {
+    Io(std::io::Error),
+    Yaml(serde_yaml::Error),
+    /// None of the documents has cloud or noise settings
+    NoSettings,
+}
+
+impl fmt::Display for UnityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnityError::Io(err) => write!(f, "{err}"),
+            UnityError::Yaml(err) => write!(f, "{err}"),
+            UnityError::NoSettings => write!(f, "no cloud settings in the file"),
+        }
+    }
+}
+
+impl std::error::Error for UnityError {}
+
+impl From<std::io::Error> for UnityError {
+    fn from(value: std::io::Error) -> Self {
+        UnityError::Io(value)
+    }
+}
+
+impl From<serde_yaml::Error> for UnityError {
+    fn from(value: serde_yaml::Error) -> Self {
+        UnityError::Yaml(value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnityImport {
+    pub cloud: CloudBuilder,
+    /// Settings found in the file that have no counterpart here
+    pub ignored: Vec<String>,
+}
+
+pub fn load_unity(path: impl AsRef<Path>, base: CloudBuilder) -> Result<UnityImport, UnityError> {
+    import_unity(&fs::read_to_string(path)?, base)
+}
+
+/// Applies the settings of every document of the file over `base`
+pub fn import_unity(yaml: &str, base: CloudBuilder) -> Result<UnityImport, UnityError> {
+    let mut import = UnityImport {
+        cloud: base,
+        ignored: Vec::new(),
+    };
+    let mut found = false;
+    for document in documents(yaml) {
+        let value: Value = serde_yaml::from_str(&document)?;
+        let Some((kind, object)) = value.as_mapping().and_then(|x| x.iter().next()) else {
+            continue;
+        };
+        let fields = match kind.as_str() {
+            Some("Material") => material_fields(object),
+            _ => fields(object),
+        };
+        if fields.contains_key("numDivisionsA") {
+            let name = fields.get("m_Name").and_then(Value::as_str).unwrap_or("");
+            let noise = match name.to_lowercase().contains("detail") {
+                true => &mut import.cloud.detail_noise,
+                false => &mut import.cloud.noise,
+            };
+            *noise = worley(&fields, *noise).into();
+            found = true;
+        } else if apply_cloud(&fields, &mut import) {
+            found = true;
+        }
+    }
+    if !found {
+        return Err(UnityError::NoSettings);
+    }
+    Ok(import)
+}
+
+/// Documents of the file as plain YAML. Unity tags every document with its
+/// class and marks some as `stripped`, which YAML parsers do not accept.
+fn documents(yaml: &str) -> Vec<String> {
+    let mut result = vec![String::new()];
+    for line in yaml.lines() {
+        if line.starts_with('%') {
+            continue;
+        }
+        if line.starts_with("---") {
+            result.push(String::new());
+            continue;
+        }
+        let document = result.last_mut().unwrap();
+        document.push_str(line);
+        document.push('\n');
+    }
+    result.retain(|x| !x.trim().is_empty());
+    result
+}
+
+fn fields(object: &Value) -> BTreeMap<String, Value> {
+    let Some(object) = object.as_mapping() else {
+        return BTreeMap::new();
+    };
+    object
+        .iter()
+        .filter_map(|(k, v)| Some((k.as_str()?.to_owned(), v.clone())))
+        .collect()
+}
+
+/// Properties of a material, saved as lists of one-key maps by their shader
+/// names with a leading underscore
+fn material_fields(object: &Value) -> BTreeMap<String, Value> {
+    let properties = &object["m_SavedProperties"];
+    let mut result = BTreeMap::new();
+    for list in ["m_Floats", "m_Colors", "m_Vectors"] {
+        for entry in properties[list].as_sequence().into_iter().flatten() {
+            for (k, v) in fields(entry) {
+                result.insert(k.trim_start_matches('_').to_owned(), v);
+            }
+        }
+    }
+    result
+}
+
+fn float(value: &Value) -> Option<f32> {
+    value.as_f64().map(|x| x as f32)
+}
+
+/// Unity vectors and colors are maps of their components
+fn vec4(value: &Value) -> Option<Vec4> {
+    let component = |keys: [&str; 2]| keys.iter().find_map(|k| float(&value[*k]));
+    Some(Vec4::new(
+        component(["x", "r"])?,
+        component(["y", "g"])?,
+        component(["z", "b"]).unwrap_or(0.0),
+        component(["w", "a"]).unwrap_or(0.0),
+    ))
+}
+
+fn color(value: &Value) -> Option<Color32> {
+    let [r, g, b, a] = vec4(value)?.clamp(Vec4::ZERO, Vec4::ONE).to_array();
+    let byte = |x: f32| (x * 255.0).round() as u8;
+    Some(Color32::from_rgba_unmultiplied(
+        byte(r),
+        byte(g),
+        byte(b),
+        byte(a),
+    ))
+}
+
+/// Sets the values of a `CloudMaster` or the cloud material, returns whether
+/// there were any
+fn apply_cloud(fields: &BTreeMap<String, Value>, import: &mut UnityImport) -> bool {
+    let get = |names: &[&str]| names.iter().find_map(|x| fields.get(*x));
+    let float_of = |names: &[&str]| get(names).and_then(float);
+    let vec_of = |names: &[&str]| get(names).and_then(vec4);
+    if float_of(&["densityMultiplier"]).is_none() {
+        return false;
+    }
+
+    let cloud = &mut import.cloud;
+    let set = |field: &mut f32, names: &[&str]| {
+        if let Some(x) = float_of(names) {
+            *field = x;
+        }
+    };
+    set(&mut cloud.cloud_scale, &["cloudScale", "scale"]);
+    set(&mut cloud.density_multiplier, &["densityMultiplier"]);
+    set(&mut cloud.density_offset, &["densityOffset"]);
+    set(&mut cloud.detail_noise_scale, &["detailNoiseScale"]);
+    set(&mut cloud.detail_noise_weight, &["detailNoiseWeight"]);
+    set(&mut cloud.ray_offset_strength, &["rayOffsetStrength"]);
+    set(
+        &mut cloud.light_absorption_through_cloud,
+        &["lightAbsorptionThroughCloud"],
+    );
+    set(
+        &mut cloud.light_absorption_toward_sun,
+        &["lightAbsorptionTowardSun"],
+    );
+    set(&mut cloud.darkness_threshold, &["darknessThreshold"]);
+    set(&mut cloud.edge_distance, &["containerEdgeFadeDst"]);
+    if let Some(x) = float_of(&["numStepsLight"]) {
+        cloud.num_steps_light = x.max(1.0) as usize;
+    }
+
+    if let Some(x) = vec_of(&["shapeNoiseWeights"]) {
+        cloud.shape_noise_weights = x;
+    }
+    // The detail weights have three components there
+    if let Some(x) = vec_of(&["detailNoiseWeights", "detailWeights"]) {
+        cloud.detail_weights = x.truncate().extend(0.0);
+    }
+    // The shape is sampled through the offset here
+    if let Some(x) = vec_of(&["shapeOffset"]) {
+        cloud.offset = x.truncate();
+    }
+    if let Some(x) = vec_of(&["detailOffset"]) {
+        cloud.detail_offset = x.truncate();
+    }
+    if let Some(x) = get(&["colA"]).and_then(color) {
+        cloud.col_a = x;
+    }
+    if let Some(x) = get(&["colB"]).and_then(color) {
+        cloud.col_b = x;
+    }
+
+    // The phase here has no separate base brightness, the back scattering
+    // takes its place
+    let phase = &mut cloud.phase_params;
+    match vec_of(&["phaseParams"]) {
+        Some(x) => *phase = Vec4::new(x.x, x.y, x.w, phase.w),
+        None => {
+            set(&mut phase.x, &["forwardScattering"]);
+            set(&mut phase.y, &["backScattering"]);
+            set(&mut phase.z, &["phaseFactor"]);
+        }
+    }
+
+    // The noise drifts by the base speed per unit of the scaled time
+    if let Some(speed) = float_of(&["baseSpeed"]) {
+        let time_scale = float_of(&["timeScale"]).unwrap_or(1.0);
+        cloud.wind = SHAPE_DRIFT * speed * time_scale * OFFSET_SPEED;
+    }
+
+    for name in ["baseBrightness", "heightOffset", "detailSpeed"] {
+        if fields.contains_key(name) {
+            import.ignored.push(name.to_owned());
+        }
+    }
+    true
+}
+
+fn worley(fields: &BTreeMap<String, Value>, base: NoiseBuilder) -> WorleyBuilder {
+    let mut builder = match base {
+        NoiseBuilder::WorleyBuilder(x) => x,
+        _ => WorleyBuilder::default(),
+    };
+    let count = |name: &str| fields.get(name).and_then(Value::as_u64).map(|x| x as usize);
+    builder.num_points_a = count("numDivisionsA").unwrap_or(builder.num_points_a);
+    builder.num_points_b = count("numDivisionsB").unwrap_or(builder.num_points_b);
+    builder.num_points_c = count("numDivisionsC").unwrap_or(builder.num_points_c);
+    if let Some(x) = fields.get("seed").and_then(Value::as_i64) {
+        builder.seed = x as u64;
+    }
+    if let Some(x) = fields.get("persistence").and_then(float) {
+        builder.persistence = x;
+    }
+    if let Some(x) = fields.get("tile").and_then(float) {
+        builder.tile = x;
+    }
+    // Unity keeps booleans as 0 and 1
+    if let Some(x) = fields.get("invert").and_then(Value::as_i64) {
+        builder.invert_noise = x != 0;
+    }
+    builder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLOUD_MASTER: &str = "%YAML 1.1
+%TAG !u! tag:unity3d.com,2011:
+--- !u!1 &100 stripped
+GameObject:
+  m_Name: Clouds
+--- !u!114 &11400000
+MonoBehaviour:
+  m_Name:
+  m_Script: {fileID: 11500000, guid: 0123456789abcdef, type: 3}
+  numStepsLight: 8
+  rayOffsetStrength: 10
+  cloudScale: 0.6
+  densityMultiplier: 0.74
+  densityOffset: -4.27
+  shapeOffset: {x: 104.5, y: 0, z: 0}
+  heightOffset: {x: 0, y: 0}
+  shapeNoiseWeights: {x: 1, y: 0.48, z: 0.15, w: 0}
+  detailNoiseScale: 3
+  detailNoiseWeight: 3.42
+  detailNoiseWeights: {x: 1, y: 0.5, z: 0.5}
+  lightAbsorptionThroughCloud: 0.75
+  darknessThreshold: 0.2
+  forwardScattering: 0.83
+  backScattering: 0.3
+  baseBrightness: 0.8
+  phaseFactor: 0.15
+  timeScale: 1
+  baseSpeed: 0.5
+  colA: {r: 1, g: 0.5, b: 0, a: 1}
+--- !u!114 &11400002
+MonoBehaviour:
+  m_Name: DetailSettings
+  seed: 7
+  numDivisionsA: 4
+  numDivisionsB: 9
+  numDivisionsC: 14
+  persistence: 0.6
+  invert: 1
+  tile: 1
+";
+
+    #[test]
+    fn test_import_cloud_master() {
+        let base = CloudBuilder::default()
+            .with_light_absorption_toward_sun(0.5)
+            .with_detail_noise(WorleyBuilder::new().with_resolution(32));
+        let import = import_unity(CLOUD_MASTER, base).unwrap();
+        let cloud = import.cloud;
+
+        assert_eq!(cloud.cloud_scale, 0.6);
+        assert_eq!(cloud.density_offset, -4.27);
+        assert_eq!(cloud.num_steps_light, 8);
+        assert_eq!(cloud.offset, Vec3::new(104.5, 0.0, 0.0));
+        assert_eq!(cloud.detail_weights, Vec4::new(1.0, 0.5, 0.5, 0.0));
+        assert_eq!(cloud.phase_params.truncate(), Vec3::new(0.83, 0.3, 0.15));
+        assert_eq!(cloud.wind, Vec3::new(50.0, 5.0, 10.0));
+        assert_eq!(cloud.col_a, Color32::from_rgb(255, 128, 0));
+        // Missing values keep the ones of the base
+        assert_eq!(cloud.light_absorption_toward_sun, 0.5);
+        assert_eq!(import.ignored, ["baseBrightness", "heightOffset"]);
+
+        let NoiseBuilder::WorleyBuilder(detail) = cloud.detail_noise else {
+            panic!("detail noise is not worley");
+        };
+        assert_eq!(detail.num_points_c, 14);
+        assert_eq!(detail.seed, 7);
+        assert!(detail.invert_noise);
+        assert_eq!(detail.resolution, 32);
+    }
+
+    #[test]
+    fn test_import_material() {
+        let material = "--- !u!21 &2100000
+Material:
+  m_Name: CloudMaterial
+  m_SavedProperties:
+    m_Floats:
+    - _densityMultiplier: 2
+    - _scale: 1.5
+    m_Colors:
+    - _phaseParams: {r: 0.8, g: 0.3, b: 0.9, a: 0.2}
+";
+        let cloud = import_unity(material, CloudBuilder::default())
+            .unwrap()
+            .cloud;
+        assert_eq!(cloud.density_multiplier, 2.0);
+        assert_eq!(cloud.cloud_scale, 1.5);
+        assert_eq!(cloud.phase_params.truncate(), Vec3::new(0.8, 0.3, 0.2));
+
+        let empty = import_unity("--- !u!1 &1\nGameObject: {}\n", CloudBuilder::default());
+        assert!(matches!(empty, Err(UnityError::NoSettings)));
+    }
+}
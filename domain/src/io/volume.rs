@@ -0,0 +1,166 @@
+//! Density of a cloud sampled into a voxel grid for other renderers.
+//!
+//! The voxels are written as raw little endian `f32` with a JSON file of the
+//! same name next to them describing the grid.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use glam::{Mat4, Vec3};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::managers::job_manager::JobContext;
+use crate::object::objects::isosurface::isosurface;
+use crate::object::objects::{BoundingBox, Cloud, Mesh};
+
+/// Density of the cloud at the centers of the voxels of a grid over its
+/// volume
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityVolume {
+    size: [usize; 3],
+    bounds: BoundingBox,
+    /// Takes the voxel centers from the grid to the world
+    index_to_world: Mat4,
+    density: Vec<f32>,
+}
+
+/// Description of the raw file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VolumeMetadata {
+    pub raw: String,
+    /// `float32`, little endian, x changes fastest then y then z
+    pub voxel_type: String,
+    pub size: [usize; 3],
+    /// Volume of the cloud before its rotation
+    pub bounds_min: [f32; 3],
+    pub bounds_max: [f32; 3],
+    pub voxel_size: [f32; 3],
+    /// Column major matrix taking the voxel indices to their centers in the
+    /// world
+    pub index_to_world: [f32; 16],
+}
+
+impl DensityVolume {
+    /// Samples [`Cloud::sample_density`] slice by slice along z, the
+    /// negative density is written as 0. Returns nothing when the job is
+    /// cancelled.
+    pub fn sample(cloud: &Cloud, size: [usize; 3], context: &JobContext) -> Option<Self> {
+        let size = size.map(|x| x.max(1));
+        let [nx, ny, nz] = size;
+        let bounds = *cloud.bounding_box();
+        let cell = bounds.size() / Vec3::new(nx as f32, ny as f32, nz as f32);
+        let index_to_world = cloud.volume_matrix().inverse()
+            * Mat4::from_translation(bounds.min + 0.5 * cell)
+            * Mat4::from_scale(cell);
+
+        let mut density = Vec::with_capacity(nx * ny * nz);
+        for z in 0..nz {
+            if context.is_cancelled() {
+                return None;
+            }
+            density.par_extend((0..nx * ny).into_par_iter().map(|i| {
+                let c = Vec3::new((i % nx) as f32, (i / nx) as f32, z as f32);
+                cloud.sample_density(bounds.min + (c + 0.5) * cell).max(0.0)
+            }));
+            context.set_progress((z + 1) as f32 / nz as f32);
+        }
+        Some(Self {
+            size,
+            bounds,
+            index_to_world,
+            density,
+        })
+    }
+
+    #[inline]
+    pub fn size(&self) -> [usize; 3] {
+        self.size
+    }
+
+    pub fn density(&self) -> &[f32] {
+        &self.density
+    }
+
+    pub fn metadata(&self, raw: impl Into<String>) -> VolumeMetadata {
+        let [nx, ny, nz] = self.size;
+        let voxel_size = self.bounds.size() / Vec3::new(nx as f32, ny as f32, nz as f32);
+        VolumeMetadata {
+            raw: raw.into(),
+            voxel_type: "float32".to_owned(),
+            size: self.size,
+            bounds_min: self.bounds.min.to_array(),
+            bounds_max: self.bounds.max.to_array(),
+            voxel_size: voxel_size.to_array(),
+            index_to_world: self.index_to_world.to_cols_array(),
+        }
+    }
+
+    /// Surface where the density crosses the threshold in the world, see
+    /// [`isosurface`]
+    pub fn isosurface(&self, threshold: f32) -> Mesh {
+        isosurface(self.size, &self.density, threshold, self.index_to_world)
+    }
+
+    /// Writes the voxels to `path` and the metadata to the `.json` file next
+    /// to it
+    pub fn save_raw(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut out = BufWriter::new(File::create(path)?);
+        for x in &self.density {
+            out.write_all(&x.to_le_bytes())?;
+        }
+        out.flush()?;
+
+        let raw = path
+            .file_name()
+            .map(|x| x.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let metadata = serde_json::to_string_pretty(&self.metadata(raw))?;
+        fs::write(path.with_extension("json"), metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::tests::dense_cloud;
+
+    #[test]
+    fn test_save_raw() {
+        let cloud = dense_cloud();
+        let volume = DensityVolume::sample(&cloud, [6, 4, 5], &JobContext::default()).unwrap();
+        assert_eq!(volume.density().len(), 6 * 4 * 5);
+        assert!(volume.density().iter().any(|x| *x > 0.0));
+
+        // Voxel (1, 2, 3) in the world and in the file
+        let bb = cloud.bounding_box();
+        let center = volume.metadata("").index_to_world;
+        let center = Mat4::from_cols_array(&center).transform_point3(Vec3::new(1.0, 2.0, 3.0));
+        let cell = bb.size() / Vec3::new(6.0, 4.0, 5.0);
+
+        // The surface closes at most half a voxel outside of the volume
+        let surface = volume.isosurface(0.1);
+        assert!(!surface.faces.is_empty());
+        assert!(surface
+            .vertices
+            .iter()
+            .all(|x| x.cmpge(bb.min - cell).all() && x.cmple(bb.max + cell).all()));
+        let expected = bb.min + Vec3::new(1.5, 2.5, 3.5) * cell;
+        assert!(center.distance(expected) < 1e-3);
+
+        let path = std::env::temp_dir().join("volume_test.raw");
+        volume.save_raw(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        let idx = (3 * 4 + 2) * 6 + 1;
+        let voxel = f32::from_le_bytes(bytes[idx * 4..idx * 4 + 4].try_into().unwrap());
+        assert_eq!(voxel, cloud.sample_density(expected).max(0.0));
+
+        let json = fs::read_to_string(path.with_extension("json")).unwrap();
+        let metadata: VolumeMetadata = serde_json::from_str(&json).unwrap();
+        assert_eq!(metadata, volume.metadata("volume_test.raw"));
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(path.with_extension("json")).unwrap();
+    }
+}
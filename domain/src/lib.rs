@@ -2,6 +2,7 @@ extern crate core;
 
 pub mod canvas;
 pub mod facade;
+pub mod io;
 pub mod managers;
 pub mod math;
 pub mod object;
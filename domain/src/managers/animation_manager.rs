@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+
+use crate::managers::Manager;
+
+/// Parameter a track changes, the camera ones ignore the track target
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Property {
+    CloudDensityMultiplier,
+    CloudDensityOffset,
+    CloudDensityThreshold,
+    CloudAbsorption,
+    /// Angle of the sun above the horizon, 90 at the zenith
+    SunElevation,
+    SunAzimuth,
+    SunIntensity,
+    /// Color of the sunlight in kelvins, see [`Sun::temperature_color`]
+    ///
+    /// [`Sun::temperature_color`]: crate::object::objects::Sun::temperature_color
+    SunTemperature,
+    CameraYaw,
+    CameraPitch,
+    CameraDistance,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Interpolation {
+    /// Holds the value of the previous key
+    Step,
+    #[default]
+    Linear,
+    /// Eases in and out of every key
+    Smooth,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Keyframe {
+    /// Seconds from the start of the animation
+    pub time: f32,
+    pub value: f32,
+}
+
+/// Values of a parameter over time, held before the first key and after
+/// the last one
+#[derive(Debug, PartialEq, Clone)]
+pub struct Track {
+    pub target: &'static str,
+    pub property: Property,
+    pub interpolation: Interpolation,
+    keys: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn new(target: &'static str, property: Property) -> Self {
+        Self {
+            target,
+            property,
+            interpolation: Interpolation::default(),
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    pub fn with_key(mut self, time: f32, value: f32) -> Self {
+        self.insert_key(time, value);
+        self
+    }
+
+    /// Adds the key, replacing the one at the same time
+    pub fn insert_key(&mut self, time: f32, value: f32) {
+        let key = Keyframe { time, value };
+        match self.keys.binary_search_by(|x| x.time.total_cmp(&time)) {
+            Ok(i) => self.keys[i] = key,
+            Err(i) => self.keys.insert(i, key),
+        }
+    }
+
+    pub fn keys(&self) -> &[Keyframe] {
+        &self.keys
+    }
+
+    pub fn duration(&self) -> f32 {
+        self.keys.last().map_or(0.0, |x| x.time)
+    }
+
+    pub fn sample(&self, time: f32) -> Option<f32> {
+        let next = self.keys.partition_point(|x| x.time <= time);
+        let (a, b) = match (next.checked_sub(1), self.keys.get(next)) {
+            (Some(i), Some(b)) => (self.keys[i], *b),
+            (Some(i), None) => return Some(self.keys[i].value),
+            (None, b) => return b.map(|x| x.value),
+        };
+        let t = (time - a.time) / (b.time - a.time);
+        let t = match self.interpolation {
+            Interpolation::Step => 0.0,
+            Interpolation::Linear => t,
+            Interpolation::Smooth => t * t * (3.0 - 2.0 * t),
+        };
+        Some(a.value + (b.value - a.value) * t)
+    }
+}
+
+/// Named tracks played together along one timeline
+#[derive(Debug, Default, Clone)]
+pub struct AnimationManager {
+    tracks: BTreeMap<String, Track>,
+    time: f32,
+    playing: bool,
+    looped: bool,
+}
+
+impl AnimationManager {
+    pub fn add_track(&mut self, name: impl Into<String>, track: Track) {
+        self.tracks.insert(name.into(), track);
+    }
+
+    pub fn remove_track(&mut self, name: &str) -> Option<Track> {
+        self.tracks.remove(name)
+    }
+
+    pub fn track_mut(&mut self, name: &str) -> Option<&mut Track> {
+        self.tracks.get_mut(name)
+    }
+
+    pub fn tracks(&self) -> impl Iterator<Item = (&str, &Track)> {
+        self.tracks.iter().map(|(name, x)| (name.as_str(), x))
+    }
+
+    pub fn clear(&mut self) {
+        self.tracks.clear();
+        self.time = 0.0;
+    }
+
+    /// End of the longest track
+    pub fn duration(&self) -> f32 {
+        self.tracks
+            .values()
+            .map(Track::duration)
+            .fold(0.0, f32::max)
+    }
+
+    pub fn time(&self) -> f32 {
+        self.time
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Plays from the current time, from the start when the end is reached
+    pub fn play(&mut self, looped: bool) {
+        if self.time >= self.duration() {
+            self.time = 0.0;
+        }
+        self.playing = true;
+        self.looped = looped;
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn seek(&mut self, time: f32) {
+        self.time = time.clamp(0.0, self.duration());
+    }
+
+    /// Moves the playing animation by `dt` seconds and returns the values of
+    /// the tracks, nothing when stopped
+    pub fn advance(&mut self, dt: f32) -> Vec<(&'static str, Property, f32)> {
+        if !self.playing {
+            return Vec::new();
+        }
+        let duration = self.duration();
+        self.time += dt;
+        if self.time >= duration {
+            if self.looped && duration > 0.0 {
+                self.time %= duration;
+            } else {
+                self.time = duration;
+                self.playing = false;
+            }
+        }
+        self.values()
+    }
+
+    /// Values of the tracks at the current time
+    pub fn values(&self) -> Vec<(&'static str, Property, f32)> {
+        self.tracks
+            .values()
+            .filter_map(|x| Some((x.target, x.property, x.sample(self.time)?)))
+            .collect()
+    }
+}
+
+impl Manager for AnimationManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_animation_tracks() {
+        let track = Track::new("sun", Property::SunElevation)
+            .with_key(2.0, 0.0)
+            .with_key(0.0, 40.0);
+        assert_eq!(track.sample(-1.0), Some(40.0));
+        assert_eq!(track.sample(1.0), Some(20.0));
+        assert_eq!(track.sample(5.0), Some(0.0));
+        let step = track.clone().with_interpolation(Interpolation::Step);
+        assert_eq!(step.sample(1.9), Some(40.0));
+
+        let mut manager = AnimationManager::default();
+        manager.add_track("sunset", track);
+        assert!(manager.advance(1.0).is_empty());
+        manager.play(false);
+        assert_eq!(
+            manager.advance(1.5),
+            [("sun", Property::SunElevation, 10.0)]
+        );
+        manager.advance(1.0);
+        assert_eq!(manager.time(), 2.0);
+        assert!(!manager.is_playing());
+    }
+}
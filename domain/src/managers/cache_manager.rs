@@ -0,0 +1,176 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+
+use crate::managers::event_manager::{ObjectAdded, ParamChanged, Subscription};
+use crate::managers::Manager;
+
+/// Hash of everything a cached value was derived from
+pub type CacheKey = u64;
+
+/// Key of the parameters, equal parameters give equal keys
+pub fn cache_key<T: Serialize + ?Sized>(params: &T) -> CacheKey {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_vec(params)
+        .expect("cached parameters are plain data")
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Key of several parameter sets, e.g. the camera and the cloud
+pub fn combine_keys(keys: &[CacheKey]) -> CacheKey {
+    let mut hasher = DefaultHasher::new();
+    keys.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct CacheStats {
+    pub entries: usize,
+    pub hits: usize,
+    pub misses: usize,
+}
+
+struct Entry {
+    key: CacheKey,
+    value: Arc<dyn Any + Send + Sync>,
+}
+
+/// Data derived from the objects, such as the rendered cloud image or the
+/// coarse density grid, one value of each type per object.
+///
+/// A value is returned only for the key it was stored with, and every value
+/// of an object is dropped when a [`ParamChanged`] or an [`ObjectAdded`]
+/// event names it, so all caches go stale the same way.
+pub struct CacheManager {
+    entries: Mutex<HashMap<(&'static str, TypeId), Entry>>,
+    changed: Subscription<ParamChanged>,
+    added: Subscription<ObjectAdded>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl CacheManager {
+    pub fn new(changed: Subscription<ParamChanged>, added: Subscription<ObjectAdded>) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            changed,
+            added,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    /// Subscriptions to the events invalidating the caches
+    pub fn subscriptions(&self) -> (Subscription<ParamChanged>, Subscription<ObjectAdded>) {
+        (self.changed, self.added)
+    }
+
+    /// Value of the type cached for the object with the key
+    pub fn get<T: Any + Send + Sync>(&self, id: &'static str, key: CacheKey) -> Option<Arc<T>> {
+        let entries = self.entries.lock().unwrap();
+        let value = entries
+            .get(&(id, TypeId::of::<T>()))
+            .filter(|x| x.key == key)
+            .and_then(|x| x.value.clone().downcast().ok());
+        let counter = if value.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    /// Replaces the value of the type cached for the object
+    pub fn insert<T: Any + Send + Sync>(
+        &self,
+        id: &'static str,
+        key: CacheKey,
+        value: T,
+    ) -> Arc<T> {
+        let value = Arc::new(value);
+        let entry = Entry {
+            key,
+            value: value.clone(),
+        };
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert((id, TypeId::of::<T>()), entry);
+        value
+    }
+
+    /// Cached value, derived with `f` when missing or stale
+    pub fn get_or_insert_with<T: Any + Send + Sync>(
+        &self,
+        id: &'static str,
+        key: CacheKey,
+        f: impl FnOnce() -> T,
+    ) -> Arc<T> {
+        match self.get(id, key) {
+            Some(value) => value,
+            None => self.insert(id, key, f()),
+        }
+    }
+
+    /// Drops every value cached for the object
+    pub fn invalidate(&self, id: &str) {
+        self.entries.lock().unwrap().retain(|(x, _), _| *x != id);
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            entries: self.entries.lock().unwrap().len(),
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl Manager for CacheManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::event_manager::EventManager;
+
+    #[test]
+    fn test_cache() {
+        let mut events = EventManager::default();
+        let cache = CacheManager::new(events.subscribe(), events.subscribe());
+        let key = cache_key(&[1.0f32, 2.0]);
+        assert_eq!(key, cache_key(&[1.0f32, 2.0]));
+        assert_ne!(key, cache_key(&[1.0f32, 3.0]));
+
+        let mut built = 0;
+        for _ in 0..2 {
+            let value = cache.get_or_insert_with("cloud", key, || {
+                built += 1;
+                vec![0u8; 4]
+            });
+            assert_eq!(value.len(), 4);
+        }
+        assert_eq!(built, 1);
+        assert!(cache.get::<Vec<u8>>("cloud", key + 1).is_none());
+        assert!(cache.get::<String>("cloud", key).is_none());
+
+        cache.insert("cloud", key, String::from("grid"));
+        cache.invalidate("cloud");
+        assert!(cache.get::<Vec<u8>>("cloud", key).is_none());
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                entries: 0,
+                hits: 1,
+                misses: 4
+            }
+        );
+    }
+}
@@ -1,9 +1,13 @@
+use std::collections::BTreeMap;
+
 use crate::managers::Manager;
 use crate::object::camera::Camera;
 
 #[derive(Default, Debug)]
 pub struct CameraManager {
     camera: Camera,
+    /// Saved cameras by name
+    presets: BTreeMap<String, Camera>,
 }
 
 impl CameraManager {
@@ -18,6 +22,24 @@ impl CameraManager {
     pub fn get_mut_camera(&mut self) -> &mut Camera {
         &mut self.camera
     }
+
+    /// Saves the current camera under the name, replacing the previous one
+    pub fn save_preset(&mut self, name: impl Into<String>) {
+        self.presets.insert(name.into(), self.camera);
+    }
+
+    pub fn preset(&self, name: &str) -> Option<&Camera> {
+        self.presets.get(name)
+    }
+
+    pub fn remove_preset(&mut self, name: &str) -> Option<Camera> {
+        self.presets.remove(name)
+    }
+
+    /// Names of the saved cameras in alphabetical order
+    pub fn presets(&self) -> Vec<String> {
+        self.presets.keys().cloned().collect()
+    }
 }
 
 impl Manager for CameraManager {}
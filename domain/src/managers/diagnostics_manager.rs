@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+
+use log::Level;
+
+use crate::managers::event_manager::{ParamChanged, Subscription};
+use crate::managers::Manager;
+use crate::object::camera::Camera;
+use crate::object::Component;
+use crate::scene::ObjectTransform;
+use crate::visitor::validate_visitor::{transform_problems, ValidateVisitor};
+use crate::visitor::Visitable;
+
+/// Oldest diagnostics are dropped past this number
+const MAX_DIAGNOSTICS: usize = 500;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    fn level(self) -> Level {
+        match self {
+            Severity::Info => Level::Info,
+            Severity::Warning => Level::Warn,
+            Severity::Error => Level::Error,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Object the problem was found in
+    pub source: Option<&'static str>,
+    pub message: String,
+    /// Times it was reported in a row
+    pub count: usize,
+}
+
+/// Problems with the parameters of the object that break its rendering, the
+/// objects of a composite are checked on their own, see [`ValidateVisitor`]
+pub fn validate(component: &Component, transform: &ObjectTransform) -> Vec<String> {
+    let mut problems = transform_problems(transform);
+    if !matches!(component, Component::Composite(_)) {
+        let found = component.accept(&mut ValidateVisitor::default());
+        problems.extend(found.into_iter().map(|(_, x)| x));
+    }
+    problems
+}
+
+/// Problems with the view of the camera
+pub fn validate_camera(camera: &Camera) -> Vec<String> {
+    let view = &camera.view;
+    let finite = [view.yaw, view.pitch, view.distance]
+        .iter()
+        .all(|x| x.is_finite());
+    if finite && view.pivot.is_finite() {
+        return Vec::new();
+    }
+    vec!["camera view is not a finite number".to_owned()]
+}
+
+/// Warnings and errors of the commands, the file I/O and the validation of
+/// the objects, kept for the diagnostics panel and logged as reported
+pub struct DiagnosticsManager {
+    diagnostics: VecDeque<Diagnostic>,
+    changed: Subscription<ParamChanged>,
+}
+
+impl DiagnosticsManager {
+    pub fn new(changed: Subscription<ParamChanged>) -> Self {
+        Self {
+            diagnostics: VecDeque::new(),
+            changed,
+        }
+    }
+
+    /// Subscription to the objects to validate
+    pub fn subscription(&self) -> Subscription<ParamChanged> {
+        self.changed
+    }
+
+    /// Logs the message and keeps it, counting the repeats of the last one
+    pub fn report(
+        &mut self,
+        severity: Severity,
+        source: Option<&'static str>,
+        message: impl Into<String>,
+    ) {
+        let message = message.into();
+        match source {
+            Some(source) => log::log!(severity.level(), "{source}: {message}"),
+            None => log::log!(severity.level(), "{message}"),
+        }
+        if let Some(last) = self.diagnostics.back_mut() {
+            if (last.severity, last.source, &last.message) == (severity, source, &message) {
+                last.count += 1;
+                return;
+            }
+        }
+        if self.diagnostics.len() == MAX_DIAGNOSTICS {
+            self.diagnostics.pop_front();
+        }
+        self.diagnostics.push_back(Diagnostic {
+            severity,
+            source,
+            message,
+            count: 1,
+        });
+    }
+
+    pub fn error(&mut self, source: Option<&'static str>, message: impl Into<String>) {
+        self.report(Severity::Error, source, message);
+    }
+
+    pub fn warning(&mut self, source: Option<&'static str>, message: impl Into<String>) {
+        self.report(Severity::Warning, source, message);
+    }
+
+    /// Diagnostics of the severity or a higher one, the oldest first
+    pub fn diagnostics(&self, min_severity: Severity) -> Vec<Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|x| x.severity >= min_severity)
+            .cloned()
+            .collect()
+    }
+
+    pub fn count(&self, severity: Severity) -> usize {
+        self.diagnostics
+            .iter()
+            .filter(|x| x.severity == severity)
+            .count()
+    }
+
+    pub fn clear(&mut self) {
+        self.diagnostics.clear();
+    }
+}
+
+impl Manager for DiagnosticsManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::event_manager::EventManager;
+    use crate::object::objects::cloud::CloudBuilder;
+    use crate::object::objects::Cloud;
+
+    #[test]
+    fn test_diagnostics() {
+        let mut diagnostics = DiagnosticsManager::new(EventManager::default().subscribe());
+        diagnostics.warning(Some("cloud"), "too dense");
+        diagnostics.warning(Some("cloud"), "too dense");
+        diagnostics.error(None, "failed to save scene.ron");
+        assert_eq!(diagnostics.count(Severity::Warning), 1);
+        let errors = diagnostics.diagnostics(Severity::Error);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(diagnostics.diagnostics(Severity::Info)[0].count, 2);
+
+        let cloud = CloudBuilder::default()
+            .with_bounding_box((glam::Vec3::ZERO, glam::Vec3::ONE))
+            .with_num_steps(10)
+            .with_density_multiplier(f32::NAN);
+        let cloud = Cloud::with_volumes(
+            cloud,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let problems = validate(&cloud.into(), &ObjectTransform::default());
+        assert_eq!(problems, ["density_multiplier is not a finite number"]);
+    }
+}
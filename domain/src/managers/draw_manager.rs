@@ -1,24 +1,77 @@
-use egui::{Color32, Stroke};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
 
-use crate::canvas::painter::Painter3D;
+use egui::{Color32, Stroke, Vec2};
+use glam::Mat4;
+
+use crate::canvas::hdr_target::HdrTarget;
+use crate::canvas::painter::{LineStyle, LineThickness, Painter3D};
+use crate::canvas::render_target::RenderTarget;
+use crate::canvas::render_thread::RenderThread;
+use crate::io::gltf::GltfDocument;
+use crate::managers::cache_manager::{cache_key, combine_keys, CacheManager};
+use crate::managers::profiling_manager::{Profiler, Stage};
+use crate::managers::render_manager::{PixelBuffer, RenderPass, RenderPlan};
 use crate::managers::Manager;
 use crate::object::camera::Camera;
+use crate::object::Component;
 use crate::scene::scene::Scene;
+use crate::visitor::cull_visitor::CullVisitor;
+use crate::visitor::depth_sort_visitor::DepthSortVisitor;
 use crate::visitor::draw_visitor::DrawVisitor;
-use crate::visitor::Visitable;
+use crate::visitor::gltf_visitor::GltfExportVisitor;
+use crate::visitor::hdr_visitor::HdrVisitor;
+use crate::visitor::lod_visitor::{Lod, LodVisitor};
+use crate::visitor::offscreen_visitor::OffscreenVisitor;
+use crate::visitor::shadow_visitor::{ShadowMap, ShadowVisitor};
+use crate::visitor::svg_visitor::SvgExportVisitor;
+use crate::visitor::{Visitable, Visitor};
+
+/// Texels across the shadow map of a frame
+const SHADOW_MAP_SIZE: usize = 128;
 
 #[derive(Default)]
 pub struct DrawManager {
     canvas: Option<Painter3D>,
     stroke: Stroke,
     color: Color32,
+    line_style: LineStyle,
+    hidden_layers: BTreeSet<&'static str>,
+    /// Objects farther away are not drawn, no limit when `None`
+    max_draw_distance: Option<f32>,
+    /// Whether small objects on screen are drawn at a lower quality
+    lod_enabled: bool,
+    /// Marches the clouds away from the frame when enabled
+    render_thread: Option<RenderThread>,
+    /// Whether the clouds are marched on the GPU
+    gpu_clouds: bool,
+    /// Whether a cloud is marched again only where new lights change it
+    partial_redraw: bool,
+    /// Timings of the stages of the frame, see [`ProfilingManager`]
+    ///
+    /// [`ProfilingManager`]: crate::managers::profiling_manager::ProfilingManager
+    profiler: Arc<Profiler>,
+    /// Owner of the copied images, opened on the first copy. On X11 the
+    /// image is served by its owner, so it lives as long as the app.
+    clipboard: Option<arboard::Clipboard>,
 }
 
 impl DrawManager {
+    pub fn with_profiler(mut self, profiler: Arc<Profiler>) -> Self {
+        self.profiler = profiler;
+        self
+    }
+
     pub fn set_canvas(&mut self, canvas: Painter3D) {
         self.canvas = Option::from(canvas);
     }
 
+    /// Size of the canvas in pixels, `None` before it is set
+    pub fn viewport_size(&self) -> Option<[usize; 2]> {
+        let size = self.canvas.as_ref()?.resp_rect().size().round();
+        Some([size.x as usize, size.y as usize])
+    }
+
     pub fn set_stroke(&mut self, stroke: Stroke) {
         self.stroke = stroke;
     }
@@ -27,13 +80,331 @@ impl DrawManager {
         self.color = color;
     }
 
+    pub fn set_line_thickness(&mut self, line_thickness: LineThickness) {
+        self.line_style.thickness = line_thickness;
+    }
+
+    pub fn set_line_anti_alias(&mut self, anti_alias: bool) {
+        self.line_style.anti_alias = anti_alias;
+    }
+
+    /// Shows or hides all objects of the render layer
+    pub fn set_layer_visible(&mut self, layer: &'static str, visible: bool) {
+        if visible {
+            self.hidden_layers.remove(layer);
+        } else {
+            self.hidden_layers.insert(layer);
+        }
+    }
+
+    pub fn is_layer_visible(&self, layer: &str) -> bool {
+        !self.hidden_layers.contains(layer)
+    }
+
+    pub fn set_max_draw_distance(&mut self, max_draw_distance: Option<f32>) {
+        self.max_draw_distance = max_draw_distance;
+    }
+
+    pub fn max_draw_distance(&self) -> Option<f32> {
+        self.max_draw_distance
+    }
+
+    pub fn set_lod_enabled(&mut self, enabled: bool) {
+        self.lod_enabled = enabled;
+    }
+
+    /// Marches the clouds on a thread of their own, the frame shows the
+    /// latest finished images
+    pub fn set_async_clouds(&mut self, enabled: bool) {
+        if enabled != self.render_thread.is_some() {
+            self.render_thread = enabled.then(RenderThread::spawn);
+        }
+    }
+
+    pub fn async_clouds(&self) -> bool {
+        self.render_thread.is_some()
+    }
+
+    /// Marches the clouds in a compute shader when there is a GPU and the
+    /// `gpu` feature is enabled, on the CPU otherwise
+    pub fn set_gpu_clouds(&mut self, enabled: bool) {
+        self.gpu_clouds = enabled;
+    }
+
+    pub fn gpu_clouds(&self) -> bool {
+        self.gpu_clouds
+    }
+
+    /// Marches again only the tiles over the occupied part of a cloud when
+    /// just the lights changed, e.g. the sun turned, and keeps the rest of
+    /// the image
+    pub fn set_partial_redraw(&mut self, enabled: bool) {
+        self.partial_redraw = enabled;
+    }
+
+    pub fn partial_redraw(&self) -> bool {
+        self.partial_redraw
+    }
+
+    /// Drops the images the render thread keeps for the removed object
+    pub fn forget_object(&self, id: &str) {
+        if let Some(thread) = &self.render_thread {
+            thread.forget(id);
+        }
+    }
+
+    /// Objects of the scene outside the view of the camera on the canvas or
+    /// beyond the draw distance
+    pub fn cull(&self, scene: &Scene, camera: &Camera) -> BTreeSet<&'static str> {
+        let Some(canvas) = &self.canvas else {
+            return BTreeSet::new();
+        };
+        let size = canvas.resp_rect().size() + Vec2::splat(16.0);
+        let mut visitor = CullVisitor::new(camera, size.x, size.y)
+            .with_max_distance(self.max_draw_distance.unwrap_or(f32::INFINITY));
+        scene.accept(&mut visitor)
+    }
+
+    /// Frees cached painter textures, e.g. of removed objects
+    pub fn release_textures(&self, names: &[&str]) {
+        if let Some(canvas) = &self.canvas {
+            for name in names {
+                canvas.forget_texture(name);
+            }
+        }
+    }
+
+    fn visitor<'a>(&'a self, canvas: &'a Painter3D, camera: &'a Camera) -> DrawVisitor<'a> {
+        DrawVisitor::new(camera, canvas)
+            .with_stroke(self.stroke)
+            .with_line_style(self.line_style)
+            .with_hidden_layers(self.hidden_layers.clone())
+            .with_render_thread(self.render_thread.as_ref())
+            .with_gpu_clouds(self.gpu_clouds)
+            .with_partial_redraw(self.partial_redraw)
+            .with_profiler(self.profiler.clone())
+    }
+
+    /// Translucent objects of the scene from the farthest one to the camera
+    pub fn depth_order(&self, scene: &Scene, camera: &Camera) -> Vec<&'static str> {
+        DepthSortVisitor::new(camera.pos())
+            .visit_composite(&scene.objects)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Quality levels of the objects smaller on the canvas, none when the
+    /// levels of detail are disabled
+    pub fn lod(&self, scene: &Scene, camera: &Camera) -> BTreeMap<&'static str, Lod> {
+        match &self.canvas {
+            Some(canvas) if self.lod_enabled => {
+                let height = canvas.resp_rect().height() + 16.0;
+                LodVisitor::new(camera, height).visit_composite(&scene.objects)
+            }
+            _ => BTreeMap::new(),
+        }
+    }
+
+    /// Culled objects, depth order and quality levels of the next frame.
+    /// The shadow map is kept in the cache when there is one.
+    pub fn plan(&self, scene: &Scene, camera: &Camera, cache: Option<&CacheManager>) -> RenderPlan {
+        RenderPlan {
+            culled: self.cull(scene, camera),
+            depth_order: self.depth_order(scene, camera),
+            lod: self.lod(scene, camera),
+            shadow: {
+                let _timer = self.profiler.scope(Stage::LightMarch);
+                shadow_map(scene, cache)
+            },
+            quality: None,
+            coarse_density: false,
+        }
+    }
+
+    /// Draws the whole scene in a single traversal
     pub fn draw_scene(&self, scene: &Scene, camera: &Camera) {
         if let Some(canvas) = &self.canvas {
-            let mut visitor = DrawVisitor::new(camera, canvas).with_stroke(self.stroke);
+            let mut visitor = self
+                .visitor(canvas, camera)
+                .with_plan(self.plan(scene, camera, None));
+            scene.accept(&mut visitor);
+        }
+    }
 
+    /// Draws the components of the render pass over the previous passes as
+    /// planned, the selected objects are outlined in the overlay. Clouds
+    /// unchanged since the previous frame are taken from the cache.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_pass(
+        &self,
+        scene: &Scene,
+        camera: &Camera,
+        pass: RenderPass,
+        selection: &[&'static str],
+        plan: &RenderPlan,
+        cache: &CacheManager,
+        pixels: &mut PixelBuffer,
+    ) {
+        if let Some(canvas) = &self.canvas {
+            let mut visitor = self
+                .visitor(canvas, camera)
+                .with_pass(pass)
+                .with_selection(selection)
+                .with_plan(plan.clone())
+                .with_cache(cache)
+                .with_pixels(pixels);
             scene.accept(&mut visitor);
         }
     }
+
+    /// Renders the scene into an owned buffer of the given resolution
+    pub fn render_offscreen(
+        &self,
+        scene: &Scene,
+        camera: &Camera,
+        width: usize,
+        height: usize,
+    ) -> RenderTarget {
+        render_offscreen(scene, camera, [width, height], self.hidden_layers.clone())
+    }
+
+    /// Outlines of the scene as an SVG document of the given size
+    pub fn export_svg(
+        &self,
+        scene: &Scene,
+        camera: &Camera,
+        [width, height]: [usize; 2],
+    ) -> String {
+        let mut visitor = SvgExportVisitor::new(camera, width as f32, height as f32)
+            .with_hidden_layers(self.hidden_layers.clone());
+        scene.accept(&mut visitor);
+        visitor.into_svg()
+    }
+
+    pub fn hidden_layers(&self) -> &BTreeSet<&'static str> {
+        &self.hidden_layers
+    }
+
+    /// Puts the image into the system clipboard
+    pub fn copy_to_clipboard(&mut self, target: &RenderTarget) -> Result<(), arboard::Error> {
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => self.clipboard.insert(arboard::Clipboard::new()?),
+        };
+        target.copy_to_clipboard(clipboard)
+    }
+}
+
+/// Renders the scene without the hidden layers, usable away from the UI
+/// thread
+pub fn render_offscreen(
+    scene: &Scene,
+    camera: &Camera,
+    [width, height]: [usize; 2],
+    hidden_layers: BTreeSet<&'static str>,
+) -> RenderTarget {
+    let shadow_caster = scene.objects.values().find_map(|x| match x {
+        Component::Cloud(cloud) => Some(cloud.as_ref()),
+        _ => None,
+    });
+    let shadow = shadow_map(scene, None);
+    let mut visitor = OffscreenVisitor::new(camera, width, height)
+        .with_shadow_caster(shadow_caster)
+        .with_shadow_map(shadow.as_deref())
+        .with_hidden_layers(hidden_layers);
+
+    scene.accept(&mut visitor);
+    visitor.into_target()
+}
+
+/// Linear light and transmittance of the clouds of the scene without the
+/// hidden layers, usable away from the UI thread
+pub fn render_hdr(
+    scene: &Scene,
+    camera: &Camera,
+    [width, height]: [usize; 2],
+    hidden_layers: BTreeSet<&'static str>,
+) -> HdrTarget {
+    let mut visitor = HdrVisitor::new(camera, width, height).with_hidden_layers(hidden_layers);
+    scene.accept(&mut visitor);
+    visitor.into_target()
+}
+
+/// Layout of the scene without the hidden layers as a glTF document, usable
+/// away from the UI thread
+pub fn export_gltf(
+    scene: &Scene,
+    camera: &Camera,
+    [width, height]: [usize; 2],
+    hidden_layers: BTreeSet<&'static str>,
+) -> GltfDocument {
+    let mut visitor = GltfExportVisitor::new(camera, width as f32, height as f32)
+        .with_hidden_layers(hidden_layers);
+    scene.accept(&mut visitor);
+    visitor.into_document()
+}
+
+/// Sunlight of the first visible sun over the scene bounds, `None` without
+/// a sun or objects to shade. With a cache the map is kept under the sun
+/// until the sun, the bounds or the objects casting the shadows change.
+pub fn shadow_map(scene: &Scene, cache: Option<&CacheManager>) -> Option<Arc<ShadowMap>> {
+    let objects = &scene.objects;
+    let (name, sun) = objects.visible().find_map(|(name, x)| match x {
+        Component::Sun(sun) => Some((name, sun)),
+        _ => None,
+    })?;
+    let sun = objects
+        .world_transform(name)
+        .transform_point3(sun.get_pos());
+    let bounds = objects.bounds(Mat4::IDENTITY)?;
+    let build = || {
+        let mut visitor = ShadowVisitor::new(sun - bounds.center(), &bounds, SHADOW_MAP_SIZE);
+        visitor.visit_composite(objects);
+        visitor.into_map()
+    };
+    let Some(cache) = cache else {
+        return Some(Arc::new(build()));
+    };
+    let mut keys = vec![cache_key(&sun), cache_key(&bounds)];
+    for (id, object) in objects.visible() {
+        keys.push(cache_key(&objects.world_transform(id).to_cols_array()));
+        if let Component::Cloud(cloud) = object {
+            keys.push(cache_key(&cloud.cloud_params));
+        }
+    }
+    Some(cache.get_or_insert_with(name, combine_keys(&keys), build))
 }
 
 impl Manager for DrawManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::event_manager::EventManager;
+    use crate::object::objects::{Mesh, Sun};
+    use crate::scene::ObjectTransform;
+    use glam::Vec3;
+
+    #[test]
+    fn test_cached_shadow_map() {
+        let mut scene = Scene::default();
+        scene.add_object("roof", Mesh::cuboid(Vec3::new(4.0, 0.5, 4.0)));
+        scene.add_object("sun", Sun::new(10.0, 0.0, 0.0));
+        let mut events = EventManager::default();
+        let cache = CacheManager::new(events.subscribe(), events.subscribe());
+
+        let map = shadow_map(&scene, Some(&cache)).unwrap();
+        assert!(Arc::ptr_eq(
+            &map,
+            &shadow_map(&scene, Some(&cache)).unwrap()
+        ));
+
+        // The sun is placed by its transform
+        *scene.objects.local_transform_mut("sun").unwrap() =
+            ObjectTransform::from_translation(Vec3::new(0.0, 50.0, 0.0));
+        let moved = shadow_map(&scene, Some(&cache)).unwrap();
+        assert_ne!(map, moved);
+        assert_eq!(moved, shadow_map(&scene, None).unwrap());
+    }
+}
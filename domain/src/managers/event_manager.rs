@@ -0,0 +1,201 @@
+use std::any::{Any, TypeId};
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::time::Duration;
+
+use crate::managers::job_manager::JobId;
+use crate::managers::Manager;
+
+/// Message published into the channel of its type
+pub trait Event: Any + Clone + Send + Sync {}
+
+/// Object put into the scene
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ObjectAdded {
+    pub id: &'static str,
+    pub kind: &'static str,
+}
+
+impl Event for ObjectAdded {}
+
+/// Parameters or the transform of the object were changed
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ParamChanged {
+    pub id: &'static str,
+}
+
+impl Event for ParamChanged {}
+
+/// Scene was drawn onto the canvas
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FrameRendered {
+    pub elapsed: Duration,
+}
+
+impl Event for FrameRendered {}
+
+/// Background job finished, see [`JobManager`]
+///
+/// [`JobManager`]: crate::managers::job_manager::JobManager
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct JobFinished {
+    pub job: JobId,
+    pub name: String,
+    /// Reason of the failure
+    pub error: Option<String>,
+}
+
+impl Event for JobFinished {}
+
+/// Handle reading the channel of `E` from the moment of subscription
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct Subscription<E> {
+    id: usize,
+    _event: PhantomData<fn() -> E>,
+}
+
+impl<E> Clone for Subscription<E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<E> Copy for Subscription<E> {}
+
+/// Events of one type kept until every subscriber has read them
+struct Channel<E> {
+    events: VecDeque<E>,
+    /// Number of the first kept event since the channel was created
+    first: u64,
+    /// Number of the next event to read by the subscriber
+    cursors: HashMap<usize, u64>,
+}
+
+impl<E: Event> Channel<E> {
+    fn new() -> Self {
+        Self {
+            events: VecDeque::new(),
+            first: 0,
+            cursors: HashMap::new(),
+        }
+    }
+
+    fn end(&self) -> u64 {
+        self.first + self.events.len() as u64
+    }
+
+    /// Drops the events read by every subscriber
+    fn trim(&mut self) {
+        let read = self.cursors.values().copied().min().unwrap_or(self.end());
+        while self.first < read {
+            self.events.pop_front();
+            self.first += 1;
+        }
+    }
+}
+
+/// Typed pub/sub channels, so commands publish what happened and the UI and
+/// the renderer react without knowing about each other.
+///
+/// Nothing is kept for a channel without subscribers.
+#[derive(Default)]
+pub struct EventManager {
+    channels: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+    next_id: usize,
+}
+
+impl EventManager {
+    fn channel<E: Event>(&mut self) -> &mut Channel<E> {
+        self.channels
+            .entry(TypeId::of::<E>())
+            .or_insert_with(|| Box::new(Channel::<E>::new()))
+            .downcast_mut()
+            .unwrap()
+    }
+
+    fn try_channel<E: Event>(&mut self) -> Option<&mut Channel<E>> {
+        self.channels.get_mut(&TypeId::of::<E>())?.downcast_mut()
+    }
+
+    pub fn subscribe<E: Event>(&mut self) -> Subscription<E> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let channel = self.channel::<E>();
+        let end = channel.end();
+        channel.cursors.insert(id, end);
+        Subscription {
+            id,
+            _event: PhantomData,
+        }
+    }
+
+    pub fn unsubscribe<E: Event>(&mut self, subscription: Subscription<E>) {
+        if let Some(channel) = self.try_channel::<E>() {
+            channel.cursors.remove(&subscription.id);
+            channel.trim();
+        }
+    }
+
+    pub fn publish<E: Event>(&mut self, event: E) {
+        if let Some(channel) = self.try_channel::<E>() {
+            if !channel.cursors.is_empty() {
+                channel.events.push_back(event);
+            }
+        }
+    }
+
+    /// Events published since the previous read of the subscriber
+    pub fn read<E: Event>(&mut self, subscription: Subscription<E>) -> Vec<E> {
+        let Some(channel) = self.try_channel::<E>() else {
+            return Vec::new();
+        };
+        let Some(cursor) = channel.cursors.get_mut(&subscription.id) else {
+            return Vec::new();
+        };
+        let start = (*cursor - channel.first) as usize;
+        *cursor = channel.first + channel.events.len() as u64;
+        let events = channel.events.range(start..).cloned().collect();
+        channel.trim();
+        events
+    }
+
+    /// Number of events kept for the slowest subscriber of `E`
+    pub fn pending<E: Event>(&mut self) -> usize {
+        self.try_channel::<E>().map_or(0, |x| x.events.len())
+    }
+}
+
+impl Manager for EventManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_events() {
+        let mut events = EventManager::default();
+        events.publish(ParamChanged { id: "lost" });
+
+        let ui = events.subscribe::<ParamChanged>();
+        let renderer = events.subscribe::<ParamChanged>();
+        let added = events.subscribe::<ObjectAdded>();
+        events.publish(ParamChanged { id: "cloud" });
+        assert_eq!(events.read(ui), [ParamChanged { id: "cloud" }]);
+        assert_eq!(events.pending::<ParamChanged>(), 1);
+
+        events.publish(ParamChanged { id: "sun" });
+        assert!(events.read(ui).iter().map(|x| x.id).eq(["sun"]));
+        assert!(events
+            .read(renderer)
+            .iter()
+            .map(|x| x.id)
+            .eq(["cloud", "sun"]));
+        assert_eq!(events.pending::<ParamChanged>(), 0);
+        assert!(events.read(added).is_empty());
+
+        events.unsubscribe(ui);
+        events.unsubscribe(renderer);
+        events.publish(ParamChanged { id: "water" });
+        assert_eq!(events.pending::<ParamChanged>(), 0);
+    }
+}
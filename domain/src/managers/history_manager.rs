@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::managers::Manager;
+use crate::object::objects::cloud::CloudBuilder;
+
+/// Named snapshot of the cloud parameters, such as "v2: storm"
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Version {
+    pub number: usize,
+    pub name: String,
+    pub params: CloudBuilder,
+}
+
+impl Version {
+    pub fn label(&self) -> String {
+        format!("v{}: {}", self.number, self.name)
+    }
+}
+
+/// Parameter that differs between two versions, the values are in JSON
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct ParamDiff {
+    /// Path of the field, e.g. `noise.Worley.seed`
+    pub field: String,
+    pub old: String,
+    pub new: String,
+}
+
+/// Adds the leaves of the value under their dotted paths
+fn flatten(prefix: &str, value: Value, fields: &mut BTreeMap<String, Value>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = match prefix {
+                    "" => key,
+                    _ => format!("{prefix}.{key}"),
+                };
+                flatten(&path, value, fields);
+            }
+        }
+        value => {
+            fields.insert(prefix.to_owned(), value);
+        }
+    }
+}
+
+fn fields(params: &CloudBuilder) -> BTreeMap<String, Value> {
+    let mut fields = BTreeMap::new();
+    let value = serde_json::to_value(params).expect("cloud parameters are plain data");
+    flatten("", value, &mut fields);
+    fields
+}
+
+/// Fields that changed from `old` to `new`, in the order of their paths
+pub fn diff(old: &CloudBuilder, new: &CloudBuilder) -> Vec<ParamDiff> {
+    let (old, mut new) = (fields(old), fields(new));
+    let mut diff = Vec::new();
+    for (field, old) in old {
+        let new = new.remove(&field).unwrap_or(Value::Null);
+        if old != new {
+            diff.push((field, old, new));
+        }
+    }
+    diff.extend(
+        new.into_iter()
+            .map(|(field, new)| (field, Value::Null, new)),
+    );
+    diff.sort_by(|a, b| a.0.cmp(&b.0));
+    diff.into_iter()
+        .map(|(field, old, new)| ParamDiff {
+            field,
+            old: old.to_string(),
+            new: new.to_string(),
+        })
+        .collect()
+}
+
+/// Named versions of the cloud parameters kept for comparison, apart from
+/// the undo of single edits
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HistoryManager {
+    versions: Vec<Version>,
+}
+
+impl HistoryManager {
+    /// Stores the parameters under the next version number
+    pub fn commit(&mut self, name: impl Into<String>, params: CloudBuilder) -> usize {
+        let number = self.versions.last().map_or(1, |x| x.number + 1);
+        self.versions.push(Version {
+            number,
+            name: name.into(),
+            params,
+        });
+        number
+    }
+
+    pub fn get(&self, number: usize) -> Option<&Version> {
+        self.versions.iter().find(|x| x.number == number)
+    }
+
+    pub fn remove(&mut self, number: usize) -> Option<Version> {
+        let i = self.versions.iter().position(|x| x.number == number)?;
+        Some(self.versions.remove(i))
+    }
+
+    pub fn versions(&self) -> &[Version] {
+        &self.versions
+    }
+
+    /// Changes from the version `old` to the version `new`
+    pub fn diff(&self, old: usize, new: usize) -> Option<Vec<ParamDiff>> {
+        Some(diff(&self.get(old)?.params, &self.get(new)?.params))
+    }
+
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    pub fn from_ron(s: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(s)
+    }
+}
+
+impl Manager for HistoryManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_versions() {
+        let wispy = CloudBuilder::default().with_density_multiplier(0.5);
+        let storm = wispy.with_density_multiplier(3.0).with_num_steps(300);
+
+        let mut history = HistoryManager::default();
+        assert_eq!(history.commit("wispy", wispy), 1);
+        assert_eq!(history.commit("storm", storm), 2);
+        assert_eq!(history.get(2).unwrap().label(), "v2: storm");
+
+        let diff = history.diff(1, 2).unwrap();
+        let fields: Vec<_> = diff.iter().map(|x| x.field.as_str()).collect();
+        assert_eq!(fields, ["density_multiplier", "num_steps"]);
+        assert_eq!((diff[0].old.as_str(), diff[0].new.as_str()), ("0.5", "3.0"));
+        assert!(history.diff(1, 3).is_none());
+
+        let restored = HistoryManager::from_ron(&history.to_ron().unwrap()).unwrap();
+        assert_eq!(restored, history);
+        history.remove(2);
+        assert_eq!(history.commit("calm", wispy), 2);
+    }
+}
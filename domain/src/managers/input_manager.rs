@@ -0,0 +1,229 @@
+use egui::{Key, Modifiers, PointerButton, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::managers::Manager;
+
+/// What a binding does, carried out by [`InputCommand::Handle`]
+///
+/// [`InputCommand::Handle`]: crate::facade::InputCommand::Handle
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum Action {
+    /// Turns the camera around its pivot
+    Orbit,
+    Pan,
+    Zoom,
+    /// Shows or hides the debug render layer
+    ToggleDebug,
+    Screenshot,
+    /// Copies the render of the current view for pasting elsewhere
+    CopyToClipboard,
+}
+
+/// Raw input triggering an action
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Binding {
+    /// Dragging with the button while exactly these modifiers are held
+    Drag {
+        button: PointerButton,
+        #[serde(default)]
+        modifiers: Modifiers,
+    },
+    /// Mouse wheel over the canvas
+    Scroll,
+    Key {
+        key: Key,
+        #[serde(default)]
+        modifiers: Modifiers,
+    },
+}
+
+/// Bindings checked in order, several of them may share an action
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct InputConfig {
+    pub bindings: Vec<(Binding, Action)>,
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        let drag = |button, modifiers| Binding::Drag { button, modifiers };
+        let key = |key| Binding::Key {
+            key,
+            modifiers: Modifiers::NONE,
+        };
+        Self {
+            bindings: vec![
+                (drag(PointerButton::Primary, Modifiers::NONE), Action::Orbit),
+                (drag(PointerButton::Primary, Modifiers::SHIFT), Action::Pan),
+                (drag(PointerButton::Secondary, Modifiers::NONE), Action::Pan),
+                (Binding::Scroll, Action::Zoom),
+                (key(Key::F3), Action::ToggleDebug),
+                (key(Key::F12), Action::Screenshot),
+                (key(Key::F10), Action::CopyToClipboard),
+            ],
+        }
+    }
+}
+
+impl InputConfig {
+    pub fn to_ron(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+    }
+
+    pub fn from_ron(s: &str) -> Result<Self, ron::error::SpannedError> {
+        ron::from_str(s)
+    }
+}
+
+/// Input of the canvas during one frame, detached from egui
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct InputFrame {
+    /// Buttons dragging over the canvas
+    pub dragged: Vec<PointerButton>,
+    pub drag_delta: Vec2,
+    /// Wheel movement while the pointer is over the canvas
+    pub scroll: f32,
+    /// Keys pressed this frame with the modifiers held at the time
+    pub keys: Vec<(Key, Modifiers)>,
+    pub modifiers: Modifiers,
+}
+
+impl InputFrame {
+    /// Gathers the input of the canvas response
+    pub fn from_response(resp: &egui::Response) -> Self {
+        let dragged = [
+            PointerButton::Primary,
+            PointerButton::Secondary,
+            PointerButton::Middle,
+        ]
+        .into_iter()
+        .filter(|&x| resp.dragged_by(x))
+        .collect();
+        resp.ctx.input(|i| Self {
+            dragged,
+            drag_delta: resp.drag_delta(),
+            scroll: if resp.hovered() {
+                i.raw_scroll_delta.y
+            } else {
+                0.0
+            },
+            keys: i
+                .events
+                .iter()
+                .filter_map(|x| match *x {
+                    egui::Event::Key {
+                        key,
+                        pressed: true,
+                        repeat: false,
+                        modifiers,
+                        ..
+                    } => Some((key, modifiers)),
+                    _ => None,
+                })
+                .collect(),
+            modifiers: i.modifiers,
+        })
+    }
+}
+
+/// Action triggered this frame with the amount of the input, the drag delta
+/// or the wheel movement along `y`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct InputEvent {
+    pub action: Action,
+    pub amount: Vec2,
+}
+
+#[derive(Debug, Default)]
+pub struct InputManager {
+    config: InputConfig,
+}
+
+impl InputManager {
+    pub fn set_config(&mut self, config: InputConfig) {
+        self.config = config;
+    }
+
+    pub fn config(&self) -> &InputConfig {
+        &self.config
+    }
+
+    /// Maps the input to actions. A drag or a key triggers only its first
+    /// matching binding.
+    pub fn events(&self, frame: &InputFrame) -> Vec<InputEvent> {
+        let mut events = Vec::new();
+        let mut push = |binding: &dyn Fn(&Binding) -> bool, amount| {
+            if let Some(&(_, action)) = self.config.bindings.iter().find(|(x, _)| binding(x)) {
+                events.push(InputEvent { action, amount });
+            }
+        };
+
+        for &button in &frame.dragged {
+            let drag = |x: &Binding| {
+                *x == Binding::Drag {
+                    button,
+                    modifiers: frame.modifiers,
+                }
+            };
+            push(&drag, frame.drag_delta);
+        }
+        if frame.scroll != 0.0 {
+            push(&|x| *x == Binding::Scroll, Vec2::new(0.0, frame.scroll));
+        }
+        for &(key, modifiers) in &frame.keys {
+            push(&|x| *x == Binding::Key { key, modifiers }, Vec2::ZERO);
+        }
+        events
+    }
+}
+
+impl Manager for InputManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_input_events() {
+        let mut manager = InputManager::default();
+        let frame = InputFrame {
+            dragged: vec![PointerButton::Primary],
+            drag_delta: Vec2::new(2.0, 1.0),
+            scroll: 3.0,
+            keys: vec![
+                (Key::F3, Modifiers::NONE),
+                (Key::A, Modifiers::NONE),
+                (Key::F10, Modifiers::NONE),
+            ],
+            modifiers: Modifiers::NONE,
+        };
+        let actions = |manager: &InputManager, frame| {
+            manager
+                .events(frame)
+                .iter()
+                .map(|x| x.action)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            actions(&manager, &frame),
+            [
+                Action::Orbit,
+                Action::Zoom,
+                Action::ToggleDebug,
+                Action::CopyToClipboard
+            ]
+        );
+        let shifted = InputFrame {
+            modifiers: Modifiers::SHIFT,
+            ..frame.clone()
+        };
+        assert_eq!(manager.events(&shifted)[0].action, Action::Pan);
+        assert_eq!(manager.events(&shifted)[0].amount, Vec2::new(2.0, 1.0));
+
+        let config = InputConfig {
+            bindings: vec![(Binding::Scroll, Action::Orbit)],
+        };
+        let config = InputConfig::from_ron(&config.to_ron().unwrap()).unwrap();
+        manager.set_config(config);
+        assert_eq!(actions(&manager, &frame), [Action::Orbit]);
+    }
+}
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::managers::Manager;
+use crate::object::objects::texture3d::{Noise, NoiseBuilder};
+use crate::object::objects::Mesh;
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct JobId(u64);
+
+/// Result of a finished job, applied by the facade
+#[derive(Debug)]
+pub enum JobOutput {
+    Nothing,
+    /// Shape noise of the cloud generated from the builder
+    Noise(&'static str, NoiseBuilder, Noise),
+    DetailNoise(&'static str, NoiseBuilder, Noise),
+    /// Mesh added under the given name as a child of the second object
+    Mesh(&'static str, &'static str, Mesh),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum JobStatus {
+    /// Share of the work done, from 0 to 1
+    Running(f32),
+    Done,
+    Cancelled,
+    Failed(String),
+}
+
+/// State of a job shared with the worker running it
+#[derive(Debug, Default)]
+pub struct JobContext {
+    /// Bits of the `f32` progress
+    progress: AtomicU32,
+    cancelled: AtomicBool,
+    result: Mutex<Option<Result<JobOutput, String>>>,
+}
+
+impl JobContext {
+    pub fn set_progress(&self, progress: f32) {
+        let progress = progress.clamp(0.0, 1.0);
+        self.progress.store(progress.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn progress(&self) -> f32 {
+        f32::from_bits(self.progress.load(Ordering::Relaxed))
+    }
+
+    /// Long jobs should check it now and then and give up early
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Description of a job for the UI
+#[derive(Debug, PartialEq, Clone)]
+pub struct JobInfo {
+    pub id: JobId,
+    pub name: String,
+    pub status: JobStatus,
+}
+
+#[derive(Debug)]
+struct Job {
+    name: String,
+    context: Arc<JobContext>,
+}
+
+impl Job {
+    fn status(&self) -> JobStatus {
+        if self.context.is_cancelled() {
+            return JobStatus::Cancelled;
+        }
+        match &*self.context.result.lock().unwrap() {
+            None => JobStatus::Running(self.context.progress()),
+            Some(Ok(_)) => JobStatus::Done,
+            Some(Err(err)) => JobStatus::Failed(err.clone()),
+        }
+    }
+}
+
+/// Runs long work on its own threads, so the frame never waits for it
+#[derive(Debug)]
+pub struct JobManager {
+    pool: ThreadPool,
+    jobs: BTreeMap<JobId, Job>,
+    next_id: u64,
+}
+
+impl Default for JobManager {
+    fn default() -> Self {
+        // Half of the cores are left to the renderer
+        let threads = std::thread::available_parallelism().map_or(1, |x| (x.get() / 2).max(1));
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .thread_name(|i| format!("job-{i}"))
+            .build()
+            .expect("failed to start the job threads");
+        Self {
+            pool,
+            jobs: BTreeMap::new(),
+            next_id: 0,
+        }
+    }
+}
+
+impl JobManager {
+    /// Queues the work, its result is returned by [`Self::take_finished`]
+    pub fn spawn<F>(&mut self, name: impl Into<String>, work: F) -> JobId
+    where
+        F: FnOnce(&JobContext) -> Result<JobOutput, String> + Send + 'static,
+    {
+        let id = JobId(self.next_id);
+        self.next_id += 1;
+        let context = Arc::new(JobContext::default());
+        let shared = context.clone();
+        self.pool.spawn(move || {
+            if shared.is_cancelled() {
+                return;
+            }
+            // Rayon aborts the process on a panic, the job fails instead
+            let result = catch_unwind(AssertUnwindSafe(|| work(&shared))).unwrap_or_else(|err| {
+                let message = err
+                    .downcast_ref::<&str>()
+                    .map(|x| x.to_string())
+                    .or_else(|| err.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "the job panicked".into());
+                Err(message)
+            });
+            shared.set_progress(1.0);
+            *shared.result.lock().unwrap() = Some(result);
+        });
+        self.jobs.insert(
+            id,
+            Job {
+                name: name.into(),
+                context,
+            },
+        );
+        id
+    }
+
+    /// Asks the job to stop, its result is dropped
+    pub fn cancel(&mut self, id: JobId) {
+        if let Some(job) = self.jobs.get(&id) {
+            job.context.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn status(&self, id: JobId) -> Option<JobStatus> {
+        self.jobs.get(&id).map(Job::status)
+    }
+
+    pub fn jobs(&self) -> Vec<JobInfo> {
+        self.jobs
+            .iter()
+            .map(|(&id, x)| JobInfo {
+                id,
+                name: x.name.clone(),
+                status: x.status(),
+            })
+            .collect()
+    }
+
+    /// Removes the finished and the cancelled jobs and returns the names and
+    /// the results of the finished ones
+    pub fn take_finished(&mut self) -> Vec<(JobId, String, Result<JobOutput, String>)> {
+        let mut finished = Vec::new();
+        self.jobs.retain(|&id, job| {
+            if job.context.is_cancelled() {
+                // A running job is dropped once its worker lets go of it
+                return Arc::strong_count(&job.context) > 1;
+            }
+            match job.context.result.lock().unwrap().take() {
+                Some(result) => {
+                    finished.push((id, std::mem::take(&mut job.name), result));
+                    false
+                }
+                None => true,
+            }
+        });
+        finished
+    }
+}
+
+impl Manager for JobManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_jobs() {
+        let mut manager = JobManager::default();
+        let done = manager.spawn("done", |_| Ok(JobOutput::Nothing));
+        let cancelled = manager.spawn("cancelled", |context| {
+            while !context.is_cancelled() {
+                std::thread::sleep(Duration::from_millis(1));
+            }
+            Ok(JobOutput::Nothing)
+        });
+        manager.cancel(cancelled);
+        assert_eq!(manager.status(cancelled), Some(JobStatus::Cancelled));
+
+        let mut finished = Vec::new();
+        while !manager.jobs.is_empty() {
+            finished.extend(manager.take_finished().into_iter().map(|(id, _, _)| id));
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(finished, [done]);
+    }
+
+    #[test]
+    fn test_panicked_job() {
+        let mut manager = JobManager::default();
+        let id = manager.spawn("panicked", |_| panic!("out of memory"));
+        while matches!(manager.status(id), Some(JobStatus::Running(_))) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(
+            manager.status(id),
+            Some(JobStatus::Failed("out of memory".into()))
+        );
+    }
+}
@@ -1,48 +1,212 @@
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+
+use crate::managers::animation_manager::AnimationManager;
+use crate::managers::cache_manager::CacheManager;
 use crate::managers::camera_manager::CameraManager;
+use crate::managers::diagnostics_manager::DiagnosticsManager;
 use crate::managers::draw_manager::DrawManager;
+use crate::managers::event_manager::EventManager;
+use crate::managers::history_manager::HistoryManager;
+use crate::managers::input_manager::InputManager;
+use crate::managers::job_manager::JobManager;
+use crate::managers::profiling_manager::ProfilingManager;
+use crate::managers::quality_manager::QualityManager;
+use crate::managers::render_manager::RenderManager;
+use crate::managers::resource_manager::ResourceManager;
 use crate::managers::scene_manager::SceneManager;
+use crate::managers::script_manager::ScriptManager;
+use crate::managers::selection_manager::SelectionManager;
+use crate::managers::settings_manager::SettingsManager;
+use crate::managers::time_manager::TimeManager;
 
+pub mod animation_manager;
+pub mod cache_manager;
 pub mod camera_manager;
+pub mod diagnostics_manager;
 pub mod draw_manager;
+pub mod event_manager;
+pub mod history_manager;
+pub mod input_manager;
+pub mod job_manager;
+pub mod profiling_manager;
+pub mod quality_manager;
+pub mod render_manager;
+pub mod resource_manager;
 pub mod scene_manager;
+pub mod script_manager;
+pub mod selection_manager;
+pub mod settings_manager;
+pub mod time_manager;
 
-pub trait Manager {}
+/// Part of the domain state owned by [`ManagerSolution`]
+pub trait Manager: Any + Send + Sync {}
 
-#[derive(Default)]
+/// Managers stored by their type, so a new manager only has to be
+/// registered
 pub struct ManagerSolution {
-    pub scene_manager: SceneManager,
-    pub camera_manager: CameraManager,
-    pub draw_manager: DrawManager,
+    managers: HashMap<TypeId, Box<dyn Manager>>,
+}
+
+impl Default for ManagerSolution {
+    fn default() -> Self {
+        let mut solution = Self {
+            managers: HashMap::new(),
+        };
+        let profiling = ProfilingManager::default();
+        solution.register(SceneManager::default());
+        solution.register(CameraManager::default());
+        solution.register(DrawManager::default().with_profiler(profiling.profiler()));
+        solution.register(RenderManager::default());
+        solution.register(QualityManager::default());
+        solution.register(TimeManager::default());
+        solution.register(InputManager::default());
+        solution.register(ResourceManager::default().with_profiler(profiling.profiler()));
+        solution.register(SettingsManager::default());
+        solution.register(profiling);
+        solution.register(SelectionManager::default());
+        solution.register(AnimationManager::default());
+        solution.register(JobManager::default());
+        let mut events = EventManager::default();
+        solution.register(CacheManager::new(events.subscribe(), events.subscribe()));
+        solution.register(DiagnosticsManager::new(events.subscribe()));
+        solution.register(events);
+        solution.register(ScriptManager::default());
+        solution.register(HistoryManager::default());
+        solution
+    }
 }
 
 impl ManagerSolution {
-    #[inline]
-    pub fn get_scene_manager(&self) -> &SceneManager {
-        &self.scene_manager
+    /// Adds the manager, replacing the one of the same type
+    pub fn register<M: Manager>(&mut self, manager: M) {
+        self.managers.insert(TypeId::of::<M>(), Box::new(manager));
     }
 
-    #[inline]
-    pub fn get_camera_manager(&self) -> &CameraManager {
-        &self.camera_manager
+    pub fn try_get<M: Manager>(&self) -> Option<&M> {
+        let manager: &dyn Any = self.managers.get(&TypeId::of::<M>())?.as_ref();
+        manager.downcast_ref()
     }
 
-    #[inline]
-    pub fn get_draw_manager(&self) -> &DrawManager {
-        &self.draw_manager
+    pub fn try_get_mut<M: Manager>(&mut self) -> Option<&mut M> {
+        let manager: &mut dyn Any = self.managers.get_mut(&TypeId::of::<M>())?.as_mut();
+        manager.downcast_mut()
     }
 
+    /// The registered manager of the type
+    ///
+    /// # Panics
+    ///
+    /// When no such manager is registered
     #[inline]
-    pub fn get_mut_scene_manager(&mut self) -> &mut SceneManager {
-        &mut self.scene_manager
+    pub fn get<M: Manager>(&self) -> &M {
+        self.try_get()
+            .unwrap_or_else(|| panic!("{} is not registered", type_name::<M>()))
     }
 
+    /// See [`Self::get`]
     #[inline]
-    pub fn get_mut_camera_manager(&mut self) -> &mut CameraManager {
-        &mut self.camera_manager
+    pub fn get_mut<M: Manager>(&mut self) -> &mut M {
+        self.try_get_mut()
+            .unwrap_or_else(|| panic!("{} is not registered", type_name::<M>()))
     }
 
-    #[inline]
-    pub fn get_mut_draw_manager(&mut self) -> &mut DrawManager {
-        &mut self.draw_manager
+    /// Takes the manager out for the duration of `f`, so it can be changed
+    /// while the other managers are read. It is put back even when `f`
+    /// panics.
+    pub fn lend<M: Manager, R>(&mut self, f: impl FnOnce(&mut M, &mut Self) -> R) -> R {
+        let id = TypeId::of::<M>();
+        let manager = self
+            .managers
+            .remove(&id)
+            .unwrap_or_else(|| panic!("{} is not registered", type_name::<M>()));
+        let lent = &mut Lent {
+            solution: self,
+            id,
+            manager: Some(manager),
+        };
+        let any: &mut dyn Any = lent.manager.as_mut().unwrap().as_mut();
+        f(any.downcast_mut().unwrap(), lent.solution)
+    }
+}
+
+/// Manager taken out by [`ManagerSolution::lend`], registered again when
+/// dropped
+struct Lent<'a> {
+    solution: &'a mut ManagerSolution,
+    id: TypeId,
+    manager: Option<Box<dyn Manager>>,
+}
+
+impl Drop for Lent<'_> {
+    fn drop(&mut self) {
+        if let Some(manager) = self.manager.take() {
+            self.solution.managers.insert(self.id, manager);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct CounterManager(usize);
+
+    impl Manager for CounterManager {}
+
+    #[test]
+    fn test_manager_registry() {
+        let mut solution = ManagerSolution::default();
+        assert!(solution.try_get::<CounterManager>().is_none());
+        solution.register(CounterManager(1));
+        solution.get_mut::<CounterManager>().0 += 1;
+        let paused = solution.lend(|counter: &mut CounterManager, solution| {
+            counter.0 += 1;
+            solution.get::<TimeManager>().is_paused()
+        });
+        assert!(!paused);
+        assert_eq!(solution.get::<CounterManager>().0, 3);
+
+        // A panic while lent leaves the manager registered
+        let lent = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            solution.lend(|_: &mut CounterManager, solution| solution.get::<Missing>().0)
+        }));
+        assert!(lent.is_err());
+        assert_eq!(solution.get::<CounterManager>().0, 3);
+    }
+
+    #[derive(Default)]
+    struct Missing(usize);
+
+    impl Manager for Missing {}
+
+    /// The commands take the managers without checking, so all of them
+    /// have to be there from the start
+    #[test]
+    fn test_default_managers() {
+        let solution = ManagerSolution::default();
+        let registered = [
+            solution.try_get::<AnimationManager>().is_some(),
+            solution.try_get::<CacheManager>().is_some(),
+            solution.try_get::<CameraManager>().is_some(),
+            solution.try_get::<DiagnosticsManager>().is_some(),
+            solution.try_get::<DrawManager>().is_some(),
+            solution.try_get::<EventManager>().is_some(),
+            solution.try_get::<HistoryManager>().is_some(),
+            solution.try_get::<InputManager>().is_some(),
+            solution.try_get::<JobManager>().is_some(),
+            solution.try_get::<ProfilingManager>().is_some(),
+            solution.try_get::<QualityManager>().is_some(),
+            solution.try_get::<RenderManager>().is_some(),
+            solution.try_get::<ResourceManager>().is_some(),
+            solution.try_get::<SceneManager>().is_some(),
+            solution.try_get::<ScriptManager>().is_some(),
+            solution.try_get::<SelectionManager>().is_some(),
+            solution.try_get::<SettingsManager>().is_some(),
+            solution.try_get::<TimeManager>().is_some(),
+        ];
+        assert!(registered.iter().all(|x| *x));
+        assert_eq!(solution.managers.len(), registered.len());
     }
 }
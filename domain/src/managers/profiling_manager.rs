@@ -0,0 +1,205 @@
+use std::collections::VecDeque;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::managers::Manager;
+
+/// Number of frames kept in the history
+pub const HISTORY_LEN: usize = 300;
+
+/// Measured part of a frame
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Stage {
+    NoiseGeneration,
+    /// Marching the view rays, the light march included
+    CloudMarch,
+    /// Marching the clouds towards the sun for the shadow map
+    LightMarch,
+    /// Sending the rendered images to egui
+    TextureUpload,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [
+        Stage::NoiseGeneration,
+        Stage::CloudMarch,
+        Stage::LightMarch,
+        Stage::TextureUpload,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::NoiseGeneration => "noise",
+            Stage::CloudMarch => "cloud march",
+            Stage::LightMarch => "light march",
+            Stage::TextureUpload => "texture upload",
+        }
+    }
+}
+
+/// Time spent in every stage since the last frame, shared with the threads
+/// doing the measured work
+#[derive(Debug, Default)]
+pub struct Profiler {
+    enabled: AtomicBool,
+    elapsed: [AtomicU64; Stage::ALL.len()],
+}
+
+impl Profiler {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Times the rest of the scope, see [`ScopeTimer`]
+    #[inline]
+    pub fn scope(&self, stage: Stage) -> ScopeTimer<'_> {
+        let start = self.is_enabled().then(Instant::now);
+        ScopeTimer {
+            profiler: self,
+            stage,
+            start,
+        }
+    }
+
+    pub fn record(&self, stage: Stage, elapsed: Duration) {
+        let nanos = elapsed.as_nanos() as u64;
+        self.elapsed[stage as usize].fetch_add(nanos, Ordering::Relaxed);
+    }
+
+    fn take(&self, stage: Stage) -> Duration {
+        Duration::from_nanos(self.elapsed[stage as usize].swap(0, Ordering::Relaxed))
+    }
+}
+
+/// Adds the time until it is dropped to the stage. Does nothing while the
+/// profiling is off.
+pub struct ScopeTimer<'a> {
+    profiler: &'a Profiler,
+    stage: Stage,
+    start: Option<Instant>,
+}
+
+impl Drop for ScopeTimer<'_> {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            self.profiler.record(self.stage, start.elapsed());
+        }
+    }
+}
+
+/// Time of every stage during one frame
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct FrameProfile {
+    pub stages: [Duration; Stage::ALL.len()],
+}
+
+impl FrameProfile {
+    pub fn get(&self, stage: Stage) -> Duration {
+        self.stages[stage as usize]
+    }
+}
+
+/// Collects the stage timings of the frames into a rolling history
+#[derive(Debug, Default)]
+pub struct ProfilingManager {
+    profiler: Arc<Profiler>,
+    history: VecDeque<FrameProfile>,
+}
+
+impl ProfilingManager {
+    /// Timings the measured work adds to, see [`Profiler::scope`]
+    pub fn profiler(&self) -> Arc<Profiler> {
+        self.profiler.clone()
+    }
+
+    /// Starts or stops the measurements, the history is kept
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.profiler.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.profiler.is_enabled()
+    }
+
+    /// Moves the time measured since the previous call into the history
+    pub fn end_frame(&mut self) -> FrameProfile {
+        let mut profile = FrameProfile::default();
+        for stage in Stage::ALL {
+            profile.stages[stage as usize] = self.profiler.take(stage);
+        }
+        if self.is_enabled() {
+            if self.history.len() == HISTORY_LEN {
+                self.history.pop_front();
+            }
+            self.history.push_back(profile);
+        }
+        profile
+    }
+
+    /// Frames from the oldest one
+    pub fn history(&self) -> &VecDeque<FrameProfile> {
+        &self.history
+    }
+
+    /// Mean time of the stage over the history
+    pub fn average(&self, stage: Stage) -> Duration {
+        let total: Duration = self.history.iter().map(|x| x.get(stage)).sum();
+        total / self.history.len().max(1) as u32
+    }
+
+    pub fn clear(&mut self) {
+        self.history.clear();
+    }
+
+    /// History as CSV in milliseconds, one frame per row
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("frame");
+        for stage in Stage::ALL {
+            let _ = write!(csv, ",{}", stage.name());
+        }
+        for (i, frame) in self.history.iter().enumerate() {
+            let _ = write!(csv, "\n{i}");
+            for time in frame.stages {
+                let _ = write!(csv, ",{:.3}", time.as_secs_f64() * 1e3);
+            }
+        }
+        csv.push('\n');
+        csv
+    }
+}
+
+impl Manager for ProfilingManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profiling_history() {
+        let mut manager = ProfilingManager::default();
+        manager.set_enabled(true);
+        {
+            let profiler = manager.profiler();
+            let _timer = profiler.scope(Stage::TextureUpload);
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        let profile = manager.end_frame();
+        assert!(profile.get(Stage::TextureUpload) >= Duration::from_millis(2));
+        assert_eq!(manager.history().len(), 1);
+        assert!(manager.to_csv().starts_with("frame,noise,"));
+
+        for _ in 0..HISTORY_LEN {
+            manager.end_frame();
+        }
+        assert_eq!(manager.history().len(), HISTORY_LEN);
+
+        // Every manager measures on its own
+        let other = ProfilingManager::default();
+        other
+            .profiler()
+            .record(Stage::CloudMarch, Duration::from_secs(1));
+        assert_eq!(manager.end_frame(), FrameProfile::default());
+    }
+}
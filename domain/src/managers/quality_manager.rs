@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::managers::Manager;
+use crate::object::camera::Camera;
+
+/// Frames measured before the quality is changed again
+const WINDOW: usize = 4;
+/// Lowest share of the full quality the controller goes down to
+const MIN_QUALITY: f32 = 0.25;
+/// Frame times within this share of the budget keep the quality
+const TOLERANCE: f32 = 0.15;
+
+/// Lowers the cloud resolution and steps while the camera moves so the
+/// frames fit the time budget, and brings the full quality back once the
+/// camera stops
+#[derive(Debug)]
+pub struct QualityManager {
+    /// Budget of a frame, no adaptation when `None`
+    budget: Option<Duration>,
+    /// Times of the frames drawn at the current quality
+    recent: VecDeque<Duration>,
+    quality: f32,
+    last_camera: Option<Camera>,
+    /// Whether the clouds read a coarse density field while the camera moves
+    coarse_density: bool,
+}
+
+impl Default for QualityManager {
+    fn default() -> Self {
+        Self {
+            budget: None,
+            recent: VecDeque::with_capacity(WINDOW),
+            quality: 1.0,
+            last_camera: None,
+            coarse_density: false,
+        }
+    }
+}
+
+impl QualityManager {
+    /// Frame rate held during interaction, the adaptation is off when `None`
+    pub fn set_target_fps(&mut self, fps: Option<f32>) {
+        self.budget = fps
+            .filter(|x| *x > 0.0)
+            .map(|x| Duration::from_secs_f32(1.0 / x));
+        self.restore();
+    }
+
+    pub fn target_fps(&self) -> Option<f32> {
+        self.budget.map(|x| 1.0 / x.as_secs_f32())
+    }
+
+    /// Share of the full cloud resolution and steps the next frame is drawn
+    /// at, `None` at the full quality
+    pub fn quality(&self) -> Option<f32> {
+        (self.quality < 1.0).then_some(self.quality)
+    }
+
+    pub fn set_coarse_density(&mut self, enabled: bool) {
+        self.coarse_density = enabled;
+    }
+
+    /// Whether the next frame, drawn from the camera, reads the coarse
+    /// density of the clouds. It does while the camera moves.
+    pub fn coarse_density(&self, camera: &Camera) -> bool {
+        self.coarse_density && self.last_camera.is_some_and(|x| x != *camera)
+    }
+
+    /// Takes the time of the frame drawn from the camera. The quality
+    /// follows the budget while the camera moves and is restored when it
+    /// stands still.
+    pub fn record(&mut self, elapsed: Duration, camera: &Camera) {
+        let moving = self.last_camera.is_some_and(|x| x != *camera);
+        self.last_camera = Some(*camera);
+        let Some(budget) = self.budget.filter(|_| moving) else {
+            self.restore();
+            return;
+        };
+        self.recent.push_back(elapsed);
+        if self.recent.len() < WINDOW {
+            return;
+        }
+        let average = self.recent.drain(..).sum::<Duration>() / WINDOW as u32;
+        let ratio = budget.as_secs_f32() / average.as_secs_f32().max(f32::EPSILON);
+        if (ratio - 1.0).abs() > TOLERANCE {
+            // The cost grows with the square of the resolution and with the
+            // steps
+            self.quality = (self.quality * ratio.cbrt()).clamp(MIN_QUALITY, 1.0);
+        }
+    }
+
+    fn restore(&mut self) {
+        self.recent.clear();
+        self.quality = 1.0;
+    }
+}
+
+impl Manager for QualityManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quality_follows_budget() {
+        let mut manager = QualityManager::default();
+        manager.set_target_fps(Some(30.0));
+        let mut camera = Camera::default();
+        let slow = Duration::from_millis(100);
+        manager.record(slow, &camera);
+        for _ in 0..WINDOW {
+            camera.view.yaw += 1.0;
+            manager.record(slow, &camera);
+        }
+        let quality = manager.quality().unwrap();
+        assert!((MIN_QUALITY..1.0).contains(&quality));
+
+        // A still camera gets the full quality back
+        manager.record(slow, &camera);
+        assert_eq!(manager.quality(), None);
+
+        manager.set_coarse_density(true);
+        assert!(!manager.coarse_density(&camera));
+        camera.view.yaw += 1.0;
+        assert!(manager.coarse_density(&camera));
+    }
+}
@@ -0,0 +1,229 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use egui::{Color32, ColorImage};
+
+use crate::managers::Manager;
+use crate::object::Component;
+use crate::visitor::lod_visitor::Lod;
+use crate::visitor::shadow_visitor::ShadowMap;
+
+/// What the passes of a frame draw and how, worked out once per frame
+/// before the passes
+///
+/// [`CullVisitor`]: crate::visitor::cull_visitor::CullVisitor
+/// [`DepthSortVisitor`]: crate::visitor::depth_sort_visitor::DepthSortVisitor
+/// [`LodVisitor`]: crate::visitor::lod_visitor::LodVisitor
+/// [`ShadowVisitor`]: crate::visitor::shadow_visitor::ShadowVisitor
+/// [`QualityManager`]: crate::managers::quality_manager::QualityManager
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RenderPlan {
+    /// Objects out of view that are skipped, see [`CullVisitor`]
+    pub culled: BTreeSet<&'static str>,
+    /// Translucent objects from the farthest one, see [`DepthSortVisitor`]
+    pub depth_order: Vec<&'static str>,
+    /// Objects drawn below the full quality, see [`LodVisitor`]
+    pub lod: BTreeMap<&'static str, Lod>,
+    /// Sunlight the ground is shaded with, see [`ShadowVisitor`]
+    pub shadow: Option<Arc<ShadowMap>>,
+    /// Share of the full cloud resolution and steps the frame fits its
+    /// budget with, see [`QualityManager`]
+    pub quality: Option<f32>,
+    /// Whether the clouds read their coarse density field instead of the
+    /// noise, see [`QualityManager`]
+    pub coarse_density: bool,
+}
+
+/// Stage of the frame drawing a group of components over the previous ones
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum RenderPass {
+    /// Background and skybox
+    Sky,
+    /// Terrain, water, meshes and the other solid objects
+    Opaque,
+    Clouds,
+    /// Gizmos drawn over everything else
+    Overlay,
+}
+
+impl RenderPass {
+    pub const ALL: [RenderPass; 4] = [
+        RenderPass::Sky,
+        RenderPass::Opaque,
+        RenderPass::Clouds,
+        RenderPass::Overlay,
+    ];
+
+    /// Whether the component is drawn during this pass. Composites and
+    /// plugins are entered by every pass.
+    pub fn draws(self, component: &Component) -> bool {
+        match component {
+            Component::Composite(_) | Component::Plugin(_) => true,
+            Component::Background(_) | Component::Skybox(_) => self == RenderPass::Sky,
+            Component::Cloud(_) => self == RenderPass::Clouds,
+            Component::Gizmo(_) | Component::TransformGizmo(_) => self == RenderPass::Overlay,
+            // The fog is applied by the objects it covers
+            Component::Camera(_) | Component::Fog(_) => false,
+            _ => self == RenderPass::Opaque,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RenderPass::Sky => "sky",
+            RenderPass::Opaque => "opaque",
+            RenderPass::Clouds => "clouds",
+            RenderPass::Overlay => "overlay",
+        }
+    }
+}
+
+/// Pass of the pipeline with its time during the last frame
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct PassState {
+    pub pass: RenderPass,
+    pub enabled: bool,
+    /// Zero when the pass was skipped
+    pub time: Duration,
+}
+
+/// Pixels of an image rendered on the CPU, kept between the frames so the
+/// allocation is reused and grows only with the image
+#[derive(Default)]
+pub struct PixelBuffer {
+    image: Arc<ColorImage>,
+}
+
+impl PixelBuffer {
+    /// Transparent image of the size. The pixels are copied out first only
+    /// while the upload of the previous image still holds them.
+    pub fn image(&mut self, size: [usize; 2]) -> &mut ColorImage {
+        let image = Arc::make_mut(&mut self.image);
+        image.size = size;
+        image.pixels.clear();
+        image.pixels.resize(size[0] * size[1], Color32::TRANSPARENT);
+        image
+    }
+
+    /// The last image, shared with the texture it is uploaded to
+    pub fn share(&self) -> Arc<ColorImage> {
+        self.image.clone()
+    }
+
+    /// Hands out the last image to be kept, the next one is drawn into the
+    /// pixels of `spare`
+    pub fn replace(&mut self, spare: Arc<ColorImage>) -> Arc<ColorImage> {
+        std::mem::replace(&mut self.image, spare)
+    }
+
+    /// Pixels the buffer holds without reallocating
+    pub fn capacity(&self) -> usize {
+        self.image.pixels.capacity()
+    }
+}
+
+/// Ordered list of the render passes run every frame
+pub struct RenderManager {
+    passes: Vec<PassState>,
+    /// Reused by the passes for the raymarched images
+    pixels: PixelBuffer,
+}
+
+impl Default for RenderManager {
+    fn default() -> Self {
+        Self {
+            passes: RenderPass::ALL
+                .into_iter()
+                .map(|pass| PassState {
+                    pass,
+                    enabled: true,
+                    time: Duration::ZERO,
+                })
+                .collect(),
+            pixels: PixelBuffer::default(),
+        }
+    }
+}
+
+impl RenderManager {
+    pub fn passes(&self) -> &[PassState] {
+        &self.passes
+    }
+
+    pub fn set_enabled(&mut self, pass: RenderPass, enabled: bool) {
+        if let Some(state) = self.passes.iter_mut().find(|x| x.pass == pass) {
+            state.enabled = enabled;
+        }
+    }
+
+    pub fn is_enabled(&self, pass: RenderPass) -> bool {
+        self.passes.iter().any(|x| x.pass == pass && x.enabled)
+    }
+
+    /// Reorders the pipeline. Passes missing from `order` keep running
+    /// after the listed ones.
+    pub fn set_order(&mut self, order: &[RenderPass]) {
+        let rank = |pass| order.iter().position(|&x| x == pass).unwrap_or(order.len());
+        self.passes.sort_by_key(|x| rank(x.pass));
+    }
+
+    /// Runs the enabled passes in order, timing each of them. The passes
+    /// share the pixel buffer of the manager.
+    pub fn render(&mut self, mut draw: impl FnMut(RenderPass, &mut PixelBuffer)) {
+        for state in &mut self.passes {
+            state.time = Duration::ZERO;
+            if state.enabled {
+                let start = Instant::now();
+                draw(state.pass, &mut self.pixels);
+                state.time = start.elapsed();
+            }
+        }
+    }
+}
+
+impl Manager for RenderManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_passes() {
+        let mut manager = RenderManager::default();
+        manager.set_enabled(RenderPass::Clouds, false);
+        manager.set_order(&[RenderPass::Overlay, RenderPass::Sky]);
+
+        let mut drawn = Vec::new();
+        manager.render(|pass, _| drawn.push(pass));
+        assert_eq!(
+            drawn,
+            [RenderPass::Overlay, RenderPass::Sky, RenderPass::Opaque]
+        );
+        assert!(!manager.is_enabled(RenderPass::Clouds));
+        assert_eq!(manager.passes()[3].time, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_pixel_buffer_reuse() {
+        let mut pixels = PixelBuffer::default();
+        pixels.image([64, 32]);
+        let capacity = pixels.capacity();
+        let address = pixels.image([16, 16]).pixels.as_ptr();
+        assert_eq!(pixels.capacity(), capacity);
+        assert_eq!(pixels.share().size, [16, 16]);
+
+        // A pending upload keeps the old pixels
+        let uploaded = pixels.share();
+        let image = pixels.image([8, 8]);
+        assert_ne!(image.pixels.as_ptr(), address);
+        assert_eq!(uploaded.size, [16, 16]);
+
+        // A kept image leaves the buffer with the spare pixels
+        let spare = Arc::new(ColorImage::new([32, 32], Color32::BLACK));
+        let address = spare.pixels.as_ptr();
+        let kept = pixels.replace(spare);
+        assert_eq!(kept.size, [8, 8]);
+        assert_eq!(pixels.image([16, 16]).pixels.as_ptr(), address);
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+
+use egui::ColorImage;
+
+use crate::io::obj::{load_obj, ObjError};
+use crate::managers::profiling_manager::Profiler;
+use crate::managers::Manager;
+use crate::object::objects::texture3d::{Noise, NoiseBuilder, VolumePool};
+use crate::object::objects::{Mesh, Skybox};
+
+/// Number of resources kept alive by the objects
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct ResourceStats {
+    pub volumes: usize,
+    pub textures: usize,
+    pub meshes: usize,
+}
+
+/// Shares loaded resources between objects. The objects own the handles, so
+/// a resource is freed together with the last object using it and is only
+/// generated or loaded again after that.
+#[derive(Debug, Default)]
+pub struct ResourceManager {
+    volumes: Vec<(NoiseBuilder, Weak<Noise>)>,
+    /// Storage of the freed volumes for the next ones
+    pool: VolumePool,
+    /// Replaced volumes waiting for their other users to drop them
+    retired: Vec<Arc<Noise>>,
+    textures: HashMap<PathBuf, Weak<ColorImage>>,
+    meshes: HashMap<PathBuf, Weak<Mesh>>,
+}
+
+impl ResourceManager {
+    /// Times the noise generation, see [`VolumePool::with_profiler`]
+    pub fn with_profiler(mut self, profiler: Arc<Profiler>) -> Self {
+        self.pool = self.pool.with_profiler(profiler);
+        self
+    }
+
+    /// 3D noise generated from the builder
+    pub fn volume(&mut self, builder: NoiseBuilder) -> Arc<Noise> {
+        match self.cached_volume(builder) {
+            Some(volume) => volume,
+            None => {
+                let noise = self.pool.build(builder);
+                self.insert_volume(builder, noise)
+            }
+        }
+    }
+
+    /// Pool the volumes are built in, shared with the volumes built by jobs
+    pub fn pool(&self) -> VolumePool {
+        self.pool.clone()
+    }
+
+    /// Takes a volume an object stopped using, its storage goes to the pool
+    /// once nothing else uses it
+    pub fn retire(&mut self, volume: Arc<Noise>) {
+        self.retired.push(volume);
+        self.recycle();
+    }
+
+    fn recycle(&mut self) {
+        let retired = std::mem::take(&mut self.retired);
+        self.retired = retired
+            .into_iter()
+            .filter_map(|x| self.pool.recycle(x))
+            .collect();
+    }
+
+    fn cached_volume(&self, builder: NoiseBuilder) -> Option<Arc<Noise>> {
+        self.volumes
+            .iter()
+            .find(|(x, _)| *x == builder)
+            .and_then(|(_, x)| x.upgrade())
+    }
+
+    /// Shares the noise generated elsewhere from the builder, a live volume
+    /// of the same builder wins
+    pub fn insert_volume(&mut self, builder: NoiseBuilder, noise: Noise) -> Arc<Noise> {
+        if let Some(volume) = self.cached_volume(builder) {
+            return volume;
+        }
+        let volume = Arc::new(noise);
+        self.volumes.retain(|(x, _)| *x != builder);
+        self.volumes.push((builder, Arc::downgrade(&volume)));
+        volume
+    }
+
+    /// Image loaded from the file
+    pub fn texture(&mut self, path: impl AsRef<Path>) -> image::ImageResult<Arc<ColorImage>> {
+        let path = path.as_ref();
+        if let Some(texture) = self.textures.get(path).and_then(Weak::upgrade) {
+            return Ok(texture);
+        }
+        let texture = Arc::new(Skybox::load_image(path)?);
+        self.textures
+            .insert(path.to_path_buf(), Arc::downgrade(&texture));
+        Ok(texture)
+    }
+
+    /// Mesh loaded from the OBJ file
+    pub fn mesh(&mut self, path: impl AsRef<Path>) -> Result<Arc<Mesh>, ObjError> {
+        let path = path.as_ref();
+        if let Some(mesh) = self.meshes.get(path).and_then(Weak::upgrade) {
+            return Ok(mesh);
+        }
+        let mesh = Arc::new(load_obj(path)?);
+        self.meshes
+            .insert(path.to_path_buf(), Arc::downgrade(&mesh));
+        Ok(mesh)
+    }
+
+    /// Forgets the freed resources
+    pub fn collect(&mut self) {
+        self.recycle();
+        self.volumes.retain(|(_, x)| x.strong_count() > 0);
+        self.textures.retain(|_, x| x.strong_count() > 0);
+        self.meshes.retain(|_, x| x.strong_count() > 0);
+    }
+
+    pub fn stats(&self) -> ResourceStats {
+        ResourceStats {
+            volumes: self
+                .volumes
+                .iter()
+                .filter(|(_, x)| x.strong_count() > 0)
+                .count(),
+            textures: self
+                .textures
+                .values()
+                .filter(|x| x.strong_count() > 0)
+                .count(),
+            meshes: self
+                .meshes
+                .values()
+                .filter(|x| x.strong_count() > 0)
+                .count(),
+        }
+    }
+}
+
+impl Manager for ResourceManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::tests::worley;
+    use crate::object::objects::texture3d::{INoise, INoiseBuilder};
+    use glam::Vec3;
+
+    #[test]
+    fn test_shared_volume() {
+        let mut resources = ResourceManager::default();
+        let builder = NoiseBuilder::WorleyBuilder(worley(0, 4));
+        let a = resources.volume(builder);
+        let b = resources.volume(builder);
+        assert!(Arc::ptr_eq(&a, &b));
+        assert_eq!(resources.stats().volumes, 1);
+
+        drop((a, b));
+        assert_eq!(resources.stats().volumes, 0);
+        resources.collect();
+        assert!(resources.volumes.is_empty());
+    }
+
+    #[test]
+    fn test_volume_pool() {
+        let mut resources = ResourceManager::default();
+        let builder = |seed| NoiseBuilder::WorleyBuilder(worley(seed, 4));
+        let a = resources.volume(builder(0));
+        let shared = a.clone();
+        resources.retire(a);
+        // Still used elsewhere
+        assert_eq!(resources.pool.pooled_bytes(), 0);
+
+        drop(shared);
+        resources.collect();
+        assert!(resources.retired.is_empty());
+        assert_eq!(resources.pool.pooled_bytes(), 4 * 4 * 4 * 16);
+
+        let b = resources.volume(builder(1));
+        assert_eq!(resources.pool.pooled_bytes(), 0);
+        // Built over the old texels, on the GPU when there is one
+        let fresh = builder(1).build();
+        for p in [Vec3::ZERO, Vec3::splat(0.3), Vec3::new(0.9, 0.1, 0.5)] {
+            assert!(b.sample_level(p).abs_diff_eq(fresh.sample_level(p), 1e-5));
+        }
+    }
+}
@@ -1,6 +1,39 @@
+use std::path::Path;
+
+use glam::{Mat4, Vec3};
+
+use crate::io::scene::{load_scene, save_scene, SceneError};
 use crate::managers::Manager;
+use crate::object::objects::cloud::CloudBuilder;
+use crate::object::objects::{Background, BoundingBox, Cloud, Fog, Grid, Light, Sun};
 use crate::object::Component;
+use crate::scene::pick::HitRecord;
 use crate::scene::scene::Scene;
+use crate::scene::ObjectTransform;
+use crate::visitor::update_visitor::UpdateVisitor;
+use crate::visitor::VisitableMut;
+
+/// Short description of a scene object
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectInfo {
+    pub name: &'static str,
+    pub kind: &'static str,
+    pub parent: Option<&'static str>,
+    pub visible: bool,
+    pub layer: &'static str,
+}
+
+/// Plain copy of the parameters of an object, detached from the scene so
+/// the UI can be filled with the actual values
+#[derive(Debug, Clone, PartialEq)]
+pub enum ComponentSnapshot {
+    Cloud(Box<CloudBuilder>),
+    Sun(Sun),
+    Grid(Grid),
+    Light(Light),
+    Fog(Fog),
+    Background(Background),
+}
 
 #[derive(Default)]
 pub struct SceneManager {
@@ -12,17 +45,257 @@ impl SceneManager {
         self.scene.add_object(name, object);
     }
 
-    pub fn get_object(&mut self, name: &'static str) {
-        self.scene.get_object(name);
+    pub fn get_object(&self, name: &'static str) -> Option<&Component> {
+        self.scene.get_object(name)
+    }
+
+    /// Copies the parameters of the object. Objects without editable
+    /// parameters, such as meshes, have no snapshot.
+    pub fn get_object_snapshot(&self, name: &str) -> Option<ComponentSnapshot> {
+        let (_, object) = self.find_by_name(name)?;
+        Some(match object {
+            Component::Cloud(x) => ComponentSnapshot::Cloud(Box::new(x.cloud_params)),
+            Component::Sun(x) => ComponentSnapshot::Sun(*x),
+            Component::Grid(x) => ComponentSnapshot::Grid(x.clone()),
+            Component::Light(x) => ComponentSnapshot::Light(*x),
+            Component::Fog(x) => ComponentSnapshot::Fog(*x),
+            Component::Background(x) => ComponentSnapshot::Background(*x),
+            _ => return None,
+        })
     }
 
     pub fn get_mut_object(&mut self, name: &'static str) -> Option<&mut Component> {
         self.scene.get_mut_object(name)
     }
 
+    /// Removes the object with its descendants, dropping their noise
+    /// textures. The removed objects are returned.
+    pub fn remove_object(&mut self, name: &str) -> Vec<(&'static str, Component)> {
+        self.scene.remove_object(name)
+    }
+
+    /// Removes all objects of the given [`Component::kind`]
+    pub fn remove_kind(&mut self, kind: &str) -> Vec<(&'static str, Component)> {
+        self.scene.remove_kind(kind)
+    }
+
+    /// Removes all objects and returns them
+    pub fn clear_scene(&mut self) -> Vec<(&'static str, Component)> {
+        self.scene.clear()
+    }
+
+    /// Painter textures of the removed components that no remaining object
+    /// is rendered into
+    pub fn unused_textures<'a>(
+        &self,
+        removed: impl IntoIterator<Item = &'a Component>,
+    ) -> Vec<&'static str> {
+        let mut names: Vec<_> = removed
+            .into_iter()
+            .filter_map(Component::texture_name)
+            .filter(|&name| {
+                !self
+                    .scene
+                    .objects
+                    .values()
+                    .any(|x| x.texture_name() == Some(name))
+            })
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Attaches `child` to `parent`, failing on missing objects and cycles
+    pub fn set_parent(&mut self, child: &str, parent: &'static str) -> Result<(), String> {
+        self.scene.set_parent(child, parent)
+    }
+
+    /// Makes the object a root keeping its world transform
+    pub fn detach_object(&mut self, name: &str) -> Result<(), String> {
+        self.scene.detach_object(name)
+    }
+
+    /// Shows or hides the object together with its descendants
+    pub fn set_visible(&mut self, name: &str, visible: bool) -> Result<(), String> {
+        self.scene.set_visible(name, visible)
+    }
+
+    /// Moves the object to the render layer
+    pub fn set_layer(&mut self, name: &str, layer: &'static str) -> Result<(), String> {
+        self.scene.set_layer(name, layer)
+    }
+
+    pub fn local_transform_mut(&mut self, name: &str) -> Option<&mut ObjectTransform> {
+        self.scene.local_transform_mut(name)
+    }
+
+    pub fn world_transform(&self, name: &str) -> Mat4 {
+        self.scene.world_transform(name)
+    }
+
+    pub fn local_transform(&self, name: &str) -> Option<ObjectTransform> {
+        self.scene.objects.node(name).map(|x| x.local)
+    }
+
+    /// World position of the center of the object
+    pub fn world_pos(&self, name: &str) -> Option<Vec3> {
+        let (_, object) = self.find_by_name(name)?;
+        Some(self.world_transform(name).transform_point3(object.pos()))
+    }
+
+    /// Moves the transform gizmos to the objects they are attached to and
+    /// detaches the ones whose object is gone
+    pub fn update_gizmos(&mut self) {
+        let pivots: Vec<_> = self
+            .scene
+            .objects
+            .iter()
+            .filter_map(|(&name, x)| match x {
+                Component::TransformGizmo(gizmo) => {
+                    Some((name, gizmo.target.and_then(|x| self.world_pos(x))))
+                }
+                _ => None,
+            })
+            .collect();
+        for (name, pivot) in pivots {
+            if let Some(Component::TransformGizmo(gizmo)) = self.get_mut_object(name) {
+                match pivot {
+                    Some(pivot) => gizmo.pivot = pivot,
+                    None => gizmo.target = None,
+                }
+            }
+        }
+    }
+
+    /// Attaches every transform gizmo to the object, or detaches them
+    pub fn set_gizmo_target(&mut self, target: Option<&'static str>) {
+        for object in self.scene.objects.values_mut() {
+            if let Component::TransformGizmo(gizmo) = object {
+                gizmo.target = target;
+            }
+        }
+    }
+
+    /// Writes the scene to a RON or JSON file, chosen by the extension.
+    /// Returns the names of the objects left out of the file.
+    pub fn save_scene(&self, path: impl AsRef<Path>) -> Result<Vec<&'static str>, SceneError> {
+        save_scene(path, &self.scene.objects)
+    }
+
+    /// Replaces the scene with the one read from the file
+    pub fn load_scene(&mut self, path: impl AsRef<Path>) -> Result<(), SceneError> {
+        self.scene.objects = load_scene(path)?;
+        Ok(())
+    }
+
+    /// Describes every top-level object, sorted by name
+    pub fn list_objects(&self) -> Vec<ObjectInfo> {
+        let objects = &self.scene.objects;
+        objects
+            .iter()
+            .map(|(&name, x)| ObjectInfo {
+                name,
+                kind: x.kind(),
+                parent: objects.parent(name),
+                visible: objects.node(name).is_none_or(|x| x.visible),
+                layer: objects.layer(name),
+            })
+            .collect()
+    }
+
+    /// Moves the animated objects `dt` seconds forward, returns the changed
+    /// ones
+    pub fn update(&mut self, dt: f32) -> Vec<&'static str> {
+        if dt == 0.0 {
+            return Vec::new();
+        }
+        let mut visitor = UpdateVisitor::new(dt);
+        self.scene.accept_mut(&mut visitor);
+        visitor.into_updated()
+    }
+
+    /// World space box around the visible objects
+    pub fn bounds(&self) -> Option<BoundingBox> {
+        self.scene.objects.bounds(Mat4::IDENTITY)
+    }
+
+    /// Objects hit by the world space ray, nearest first
+    pub fn pick(&self, origin: Vec3, dir: Vec3) -> Vec<HitRecord> {
+        self.scene.objects.pick(Mat4::IDENTITY, origin, dir)
+    }
+
+    /// Looks up an object by a name that need not be `'static`
+    pub fn find_by_name(&self, name: &str) -> Option<(&'static str, &Component)> {
+        self.scene.objects.get_key_value(name).map(|(&k, v)| (k, v))
+    }
+
+    pub fn iter_clouds(&self) -> impl Iterator<Item = (&'static str, &Cloud)> {
+        self.scene.objects.iter().filter_map(|(&name, x)| match x {
+            Component::Cloud(cloud) => Some((name, cloud.as_ref())),
+            _ => None,
+        })
+    }
+
+    pub fn iter_lights(&self) -> impl Iterator<Item = (&'static str, &Light)> {
+        self.scene.objects.iter().filter_map(|(&name, x)| match x {
+            Component::Light(light) => Some((name, light)),
+            _ => None,
+        })
+    }
+
     pub fn get_scene(&self) -> &Scene {
         &self.scene
     }
+
+    pub fn get_mut_scene(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
 }
 
 impl Manager for SceneManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_objects() {
+        let mut manager = SceneManager::default();
+        manager.add_object("grid", Grid::new(10, 1.0));
+        manager.add_object("sun", Sun::new(1.0, 0.0, 0.0));
+        manager.add_object("lamp", Light::point(glam::Vec3::Y));
+        manager.set_parent("lamp", "sun").unwrap();
+
+        let names = manager
+            .list_objects()
+            .iter()
+            .map(|x| x.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["grid", "lamp", "sun"]);
+        assert_eq!(
+            manager.list_objects()[1],
+            ObjectInfo {
+                name: "lamp",
+                kind: "light",
+                parent: Some("sun"),
+                visible: true,
+                layer: "default",
+            }
+        );
+
+        let name = String::from("grid");
+        assert!(matches!(
+            manager.find_by_name(&name),
+            Some(("grid", Component::Grid(_)))
+        ));
+        assert_eq!(manager.iter_lights().count(), 1);
+        assert_eq!(manager.iter_clouds().count(), 0);
+
+        assert_eq!(
+            manager.get_object_snapshot("grid"),
+            Some(ComponentSnapshot::Grid(Grid::new(10, 1.0)))
+        );
+        assert_eq!(manager.get_object_snapshot("missing"), None);
+    }
+}
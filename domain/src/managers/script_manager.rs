@@ -0,0 +1,170 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::io::script::{load_script, Schedule, Script, ScriptError, Statement};
+use crate::managers::Manager;
+
+/// Extension of the script files found by [`ScriptManager::discover`]
+pub const SCRIPT_EXTENSION: &str = "script";
+
+/// Description of a loaded script for the UI
+#[derive(Debug, PartialEq, Clone)]
+pub struct ScriptInfo {
+    pub path: PathBuf,
+    pub schedule: Schedule,
+    pub runs: usize,
+    /// Failure of the last load or run
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+struct Entry {
+    path: PathBuf,
+    script: Script,
+    runs: usize,
+    error: Option<String>,
+    /// Seconds until the next run, `None` when the script is not due anymore
+    next: Option<f32>,
+}
+
+/// Scripts waiting for their time, see [`crate::io::script`]
+#[derive(Debug, Default)]
+pub struct ScriptManager {
+    scripts: Vec<Entry>,
+}
+
+impl ScriptManager {
+    /// Loads the script, replacing the one from the same file. A script
+    /// that fails to parse is kept with the error so the UI can show it.
+    pub fn load(&mut self, path: impl Into<PathBuf>) -> Result<(), ScriptError> {
+        let path = path.into();
+        let (script, error) = match load_script(&path) {
+            Ok(script) => (script, None),
+            Err(err) => (Script::default(), Some(err)),
+        };
+        let next = match script.schedule {
+            _ if error.is_some() => None,
+            Schedule::Startup => Some(0.0),
+            Schedule::After(delay) => Some(delay),
+            Schedule::Every(period) => Some(period),
+        };
+        let entry = Entry {
+            path,
+            script,
+            runs: 0,
+            error: error.as_ref().map(ToString::to_string),
+            next,
+        };
+        match self.scripts.iter_mut().find(|x| x.path == entry.path) {
+            Some(x) => *x = entry,
+            None => self.scripts.push(entry),
+        }
+        error.map_or(Ok(()), Err)
+    }
+
+    /// Loads every script in the directory in the order of the file names,
+    /// returning the errors of the files that failed
+    pub fn discover(&mut self, dir: impl AsRef<Path>) -> Result<Vec<ScriptError>, ScriptError> {
+        let mut paths = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|x| x == SCRIPT_EXTENSION) {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(paths
+            .into_iter()
+            .filter_map(|x| self.load(x).err())
+            .collect())
+    }
+
+    pub fn remove(&mut self, path: &Path) {
+        self.scripts.retain(|x| x.path != path);
+    }
+
+    pub fn statements(&self, path: &Path) -> Option<&[Statement]> {
+        let entry = self.scripts.iter().find(|x| x.path == path)?;
+        Some(&entry.script.statements)
+    }
+
+    /// Moves the timers by `dt` seconds of wall time and returns the scripts
+    /// due to run
+    pub fn advance(&mut self, dt: f32) -> Vec<PathBuf> {
+        let mut due = Vec::new();
+        for entry in &mut self.scripts {
+            let Some(next) = &mut entry.next else {
+                continue;
+            };
+            *next -= dt;
+            if *next > 0.0 {
+                continue;
+            }
+            due.push(entry.path.clone());
+            entry.next = match entry.script.schedule {
+                // A long frame runs the script once, not once per missed period
+                Schedule::Every(period) => match next.rem_euclid(period) {
+                    rest if rest > 0.0 => Some(rest),
+                    _ => Some(period),
+                },
+                _ => None,
+            };
+        }
+        due
+    }
+
+    /// Records the outcome of a run
+    pub fn finish(&mut self, path: &Path, result: Result<(), String>) {
+        if let Some(entry) = self.scripts.iter_mut().find(|x| x.path == path) {
+            entry.runs += 1;
+            entry.error = result.err();
+        }
+    }
+
+    pub fn scripts(&self) -> Vec<ScriptInfo> {
+        self.scripts
+            .iter()
+            .map(|x| ScriptInfo {
+                path: x.path.clone(),
+                schedule: x.script.schedule,
+                runs: x.runs,
+                error: x.error.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Manager for ScriptManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule() {
+        let dir = std::env::temp_dir().join(format!("scripts-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.script"), "pause\n").unwrap();
+        fs::write(dir.join("b.script"), "@every 2\nplay\n").unwrap();
+        fs::write(dir.join("c.script"), "fly\n").unwrap();
+        fs::write(dir.join("notes.txt"), "fly\n").unwrap();
+
+        let mut manager = ScriptManager::default();
+        let errors = manager.discover(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(errors.len(), 1);
+        let scripts = manager.scripts();
+        assert_eq!(scripts.len(), 3);
+        assert!(scripts[2].error.is_some());
+
+        let name = |paths: Vec<PathBuf>| -> Vec<String> {
+            let name = |x: PathBuf| x.file_stem().unwrap().to_string_lossy().into_owned();
+            paths.into_iter().map(name).collect()
+        };
+        assert_eq!(name(manager.advance(0.5)), ["a"]);
+        assert!(manager.advance(1.0).is_empty());
+        assert_eq!(name(manager.advance(5.0)), ["b"]);
+        assert!(manager.advance(1.0).is_empty());
+        assert_eq!(name(manager.advance(0.6)), ["b"]);
+    }
+}
@@ -0,0 +1,106 @@
+use crate::managers::Manager;
+
+/// Change of the selection, reported once by [`SelectionManager::take_events`]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SelectionEvent {
+    Selected(&'static str),
+    Deselected(&'static str),
+}
+
+/// Selected objects in the order they were selected, the last one is active
+#[derive(Debug, Default)]
+pub struct SelectionManager {
+    selected: Vec<&'static str>,
+    events: Vec<SelectionEvent>,
+}
+
+impl SelectionManager {
+    /// Object the gizmo and the UI work with
+    pub fn active(&self) -> Option<&'static str> {
+        self.selected.last().copied()
+    }
+
+    pub fn selected(&self) -> &[&'static str] {
+        &self.selected
+    }
+
+    pub fn is_selected(&self, id: &str) -> bool {
+        self.selected.contains(&id)
+    }
+
+    /// Makes the object the only selected one
+    pub fn select(&mut self, id: &'static str) {
+        let others: Vec<_> = self.selected.iter().copied().filter(|&x| x != id).collect();
+        for x in others {
+            self.deselect(x);
+        }
+        self.add(id);
+    }
+
+    /// Adds the object to the selection and makes it active
+    pub fn add(&mut self, id: &'static str) {
+        if self.active() == Some(id) {
+            return;
+        }
+        if let Some(i) = self.selected.iter().position(|&x| x == id) {
+            self.selected.remove(i);
+        } else {
+            self.events.push(SelectionEvent::Selected(id));
+        }
+        self.selected.push(id);
+    }
+
+    pub fn deselect(&mut self, id: &str) {
+        if let Some(i) = self.selected.iter().position(|&x| x == id) {
+            let id = self.selected.remove(i);
+            self.events.push(SelectionEvent::Deselected(id));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        while let Some(&id) = self.selected.first() {
+            self.deselect(id);
+        }
+    }
+
+    /// Changes since the previous call
+    pub fn take_events(&mut self) -> Vec<SelectionEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+impl Manager for SelectionManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection() {
+        let mut selection = SelectionManager::default();
+        selection.select("cloud");
+        selection.add("sun");
+        selection.add("cloud");
+        assert_eq!(selection.selected(), ["sun", "cloud"]);
+        assert_eq!(selection.active(), Some("cloud"));
+        assert_eq!(
+            selection.take_events(),
+            [
+                SelectionEvent::Selected("cloud"),
+                SelectionEvent::Selected("sun")
+            ]
+        );
+
+        selection.select("sun");
+        assert_eq!(selection.selected(), ["sun"]);
+        selection.clear();
+        assert_eq!(selection.active(), None);
+        assert_eq!(
+            selection.take_events(),
+            [
+                SelectionEvent::Deselected("cloud"),
+                SelectionEvent::Deselected("sun")
+            ]
+        );
+    }
+}
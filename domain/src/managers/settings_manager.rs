@@ -0,0 +1,51 @@
+use std::path::{Path, PathBuf};
+
+use crate::io::settings::{default_settings_path, load_settings, save_settings};
+use crate::io::settings::{Settings, SettingsError};
+use crate::managers::Manager;
+
+/// Settings shared by the UI and the domain, persisted between runs
+#[derive(Debug, Default)]
+pub struct SettingsManager {
+    settings: Settings,
+    /// File the settings were loaded from, the default location when `None`
+    path: Option<PathBuf>,
+}
+
+impl SettingsManager {
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn settings_mut(&mut self) -> &mut Settings {
+        &mut self.settings
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Reads the settings from the file or from the default location. A
+    /// missing file leaves the current settings, it is created on save.
+    pub fn load(&mut self, path: Option<PathBuf>) -> Result<&Settings, SettingsError> {
+        let path = match path {
+            Some(path) => path,
+            None => default_settings_path()?,
+        };
+        if path.exists() {
+            self.settings = load_settings(&path)?;
+        }
+        self.path = Some(path);
+        Ok(&self.settings)
+    }
+
+    pub fn save(&self) -> Result<(), SettingsError> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => default_settings_path()?,
+        };
+        save_settings(path, &self.settings)
+    }
+}
+
+impl Manager for SettingsManager {}
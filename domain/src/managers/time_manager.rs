@@ -0,0 +1,118 @@
+use crate::managers::Manager;
+
+/// Animation clock advanced once per frame
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeManager {
+    /// Scaled time since the previous frame, zero while paused
+    dt: f32,
+    /// Scaled time since the start
+    total: f32,
+    paused: bool,
+    time_scale: f32,
+}
+
+impl Default for TimeManager {
+    fn default() -> Self {
+        Self {
+            dt: 0.0,
+            total: 0.0,
+            paused: false,
+            time_scale: 1.0,
+        }
+    }
+}
+
+/// Time of the current frame shared by everything animated
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FrameTime {
+    pub dt: f32,
+    pub total: f32,
+}
+
+impl TimeManager {
+    /// Starts a new frame that took `real_dt` seconds of wall time
+    pub fn tick(&mut self, real_dt: f32) {
+        self.dt = if self.paused {
+            0.0
+        } else {
+            real_dt.max(0.0) * self.time_scale
+        };
+        self.total += self.dt;
+    }
+
+    pub fn frame_time(&self) -> FrameTime {
+        FrameTime {
+            dt: self.dt,
+            total: self.total,
+        }
+    }
+
+    #[inline]
+    pub fn dt(&self) -> f32 {
+        self.dt
+    }
+
+    #[inline]
+    pub fn total(&self) -> f32 {
+        self.total
+    }
+
+    pub fn play(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Speeds the animation up or slows it down, negative scales are clamped
+    /// to zero
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.max(0.0);
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+}
+
+impl Manager for TimeManager {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_manager() {
+        let mut time = TimeManager::default();
+        time.tick(0.5);
+        time.set_time_scale(2.0);
+        time.tick(0.25);
+        assert_eq!(
+            time.frame_time(),
+            FrameTime {
+                dt: 0.5,
+                total: 1.0
+            }
+        );
+
+        time.pause();
+        time.tick(1.0);
+        assert_eq!(
+            time.frame_time(),
+            FrameTime {
+                dt: 0.0,
+                total: 1.0
+            }
+        );
+
+        time.play();
+        time.set_time_scale(-1.0);
+        time.tick(1.0);
+        assert_eq!(time.dt(), 0.0);
+    }
+}
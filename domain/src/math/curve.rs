@@ -0,0 +1,70 @@
+use glam::Vec3;
+
+/// Parametric 3D curve, evaluated for `t` in `0.0..=1.0`
+#[derive(Clone, Debug, PartialEq)]
+pub enum Curve {
+    /// Cubic Bezier segment given by its four control points
+    CubicBezier([Vec3; 4]),
+    /// Uniform Catmull-Rom spline passing through every point
+    CatmullRom(Vec<Vec3>),
+}
+
+impl Curve {
+    /// Number of polynomial pieces the curve is made of
+    pub fn pieces(&self) -> usize {
+        match self {
+            Curve::CubicBezier(_) => 1,
+            Curve::CatmullRom(points) => points.len().saturating_sub(1),
+        }
+    }
+
+    pub fn eval(&self, t: f32) -> Vec3 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Curve::CubicBezier([p0, p1, p2, p3]) => {
+                let s = 1.0 - t;
+                s * s * s * *p0 + 3.0 * s * s * t * *p1 + 3.0 * s * t * t * *p2 + t * t * t * *p3
+            }
+            Curve::CatmullRom(points) => match points.len() {
+                0 => Vec3::ZERO,
+                1 => points[0],
+                n => {
+                    let x = t * (n - 1) as f32;
+                    let i = (x.floor() as usize).min(n - 2);
+                    let u = x - i as f32;
+
+                    // End points are repeated so the spline reaches them
+                    let p0 = points[i.saturating_sub(1)];
+                    let (p1, p2) = (points[i], points[i + 1]);
+                    let p3 = points[(i + 2).min(n - 1)];
+
+                    let (u2, u3) = (u * u, u * u * u);
+                    0.5 * (2.0 * p1
+                        + (p2 - p0) * u
+                        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * u2
+                        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * u3)
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curve_end_points() {
+        let bezier = Curve::CubicBezier([Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::ONE]);
+        assert_eq!(bezier.eval(0.0), Vec3::ZERO);
+        assert_eq!(bezier.eval(1.0), Vec3::ONE);
+
+        let points = vec![Vec3::ZERO, Vec3::X, Vec3::new(1.0, 1.0, 0.0), Vec3::Y];
+        let spline = Curve::CatmullRom(points.clone());
+        assert_eq!(spline.pieces(), 3);
+        for (i, pt) in points.iter().enumerate() {
+            let t = i as f32 / 3.0;
+            assert!(spline.eval(t).distance(*pt) < 1e-5);
+        }
+    }
+}
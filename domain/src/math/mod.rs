@@ -1,2 +1,4 @@
+pub mod curve;
 pub mod transform;
+pub use curve::Curve;
 pub use transform::Transform;
@@ -17,6 +17,12 @@ impl Transform {
         }
     }
 
+    /// Returns the transform applied to points in the local space of an
+    /// object with the given model matrix
+    pub fn with_model(&self, model: Mat4) -> Self {
+        Self::new(self.mat * model, self.rect)
+    }
+
     /// Returns egui coordinates and z value for the given point
     pub fn world_to_egui(&self, world: glam::Vec3) -> (egui::Vec2, f32) {
         let pre: glam::Vec4 = self.mat * world.extend(1.);
@@ -33,6 +39,11 @@ impl Transform {
         (sc + self.rect.min.to_vec2(), dc.z)
     }
 
+    /// Returns the clip-space `w` of the given point, i.e. its depth along the view direction
+    pub fn clip_w(&self, world: glam::Vec3) -> f32 {
+        (self.mat * world.extend(1.)).w
+    }
+
     pub fn egui_to_world(&self, screen: egui::Vec2, depth: f32) -> glam::Vec3 {
         let sc = screen - self.rect.min.to_vec2();
         let sc =
@@ -43,7 +54,7 @@ impl Transform {
 
         let ndc = glam::Vec3::new(dc.x, dc.y, depth).extend(1.0);
 
-        let world = self.mat.inverse() * ndc;
+        let world = self.inverse * ndc;
 
         world.xyz() / world.w
     }
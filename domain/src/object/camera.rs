@@ -2,12 +2,21 @@ use std::f32::consts::FRAC_PI_2;
 
 use egui::{Pos2, Rect, Vec2};
 use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
 
 use crate::math::Transform;
+use crate::object::objects::BoundingBox;
 use crate::visitor::{Visitable, Visitor};
 
+/// Share of the scene size left behind the far clip plane fitted to it
+const CLIP_MARGIN: f32 = 1.1;
+/// Ratio of the fitted far clip distance to the near one
+const CLIP_RANGE: f32 = 1000.0;
+/// Fitted far clip distance for empty or tiny scenes
+const MIN_CLIP_FAR: f32 = 10.0;
+
 /// Camera controller and parameters
-#[derive(Default, Copy, Clone, Debug, PartialEq)]
+#[derive(Default, Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Camera {
     pub proj: Perspective,
     pub view: ArcBall,
@@ -15,8 +24,8 @@ pub struct Camera {
 }
 
 impl Visitable for Camera {
-    fn accept(&self, _visitor: &mut impl Visitor) {
-        todo!()
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_camera(self)
     }
 }
 
@@ -54,6 +63,50 @@ impl Camera {
         self.control.zoom(&mut self.view, delta)
     }
 
+    /// Moves the pivot to the center of the box and backs off until the
+    /// whole box is in view, keeping the direction of the view
+    pub fn frame(&mut self, bounds: &BoundingBox) {
+        let radius = (bounds.size().length() / 2.0).max(f32::EPSILON);
+        self.view.pivot = bounds.center();
+        self.view.distance = (radius / (self.proj.fov / 2.0).sin()).max(self.control.closest_zoom);
+    }
+
+    /// Camera looking at the whole box from the given direction, e.g. from
+    /// above for a map of the scene
+    pub fn looking_from(dir: Vec3, bounds: &BoundingBox) -> Self {
+        let mut camera = Self::default();
+        camera.view.look_from(dir);
+        camera.frame(bounds);
+        camera.fit_clip_planes(bounds);
+        camera
+    }
+
+    /// Moves the far clip plane just behind the box and the near one as far
+    /// as the depth precision allows
+    pub fn fit_clip_planes(&mut self, bounds: &BoundingBox) {
+        let radius = bounds.size().length() / 2.0;
+        let far = self.pos().distance(bounds.center()) + radius;
+        self.proj.clip_far = (far * CLIP_MARGIN).max(MIN_CLIP_FAR);
+        self.proj.clip_near = self.proj.clip_far / CLIP_RANGE;
+    }
+
+    /// Moves the camera around the pivot so that it looks against `dir`
+    pub fn look_from(&mut self, dir: Vec3) {
+        self.view.look_from(dir)
+    }
+
+    /// Ray from the eye through the pointer inside the canvas rect, the
+    /// direction is normalized
+    pub fn ray(&self, canvas: Rect, pointer: Pos2) -> (Vec3, Vec3) {
+        let t = Transform::new(
+            self.projection(canvas.width(), canvas.height()) * self.view(),
+            canvas,
+        );
+        let far = t.egui_to_world(pointer.to_vec2(), 1.0);
+        let eye = self.pos();
+        (eye, (far - eye).normalize())
+    }
+
     pub fn egui_to_world(&self, i: usize, j: usize, width: usize, height: usize) -> Vec3 {
         let t = Transform::new(
             self.projection(width as f32, height as f32) * self.view(),
@@ -62,10 +115,45 @@ impl Camera {
 
         t.egui_to_world(Vec2::new(j as f32, i as f32), -1.)
     }
+
+    /// Directions of the rays from the eye through every pixel of the
+    /// viewport, see [`Self::egui_to_world`]
+    pub fn view_rays(&self, width: usize, height: usize) -> ViewRays {
+        use rayon::prelude::*;
+
+        let t = Transform::new(
+            self.projection(width as f32, height as f32) * self.view(),
+            Rect::from_min_size(Pos2::ZERO, (width as f32, height as f32).into()),
+        );
+        let eye = self.pos();
+        let dirs = (0..width * height)
+            .into_par_iter()
+            .map(|idx| {
+                let (i, j) = (idx / width, idx % width);
+                (t.egui_to_world(Vec2::new(j as f32, i as f32), -1.) - eye).normalize()
+            })
+            .collect();
+        ViewRays { width, dirs }
+    }
+}
+
+/// Normalized world space directions of the view rays through the pixels,
+/// worked out once per camera or viewport change instead of per pixel
+#[derive(Debug, Clone, PartialEq)]
+pub struct ViewRays {
+    width: usize,
+    dirs: Vec<Vec3>,
+}
+
+impl ViewRays {
+    /// Direction through the pixel in row `i` and column `j`
+    pub fn dir(&self, i: usize, j: usize) -> Vec3 {
+        self.dirs[i * self.width + j]
+    }
 }
 
 /// Perspective projection parameters
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Perspective {
     pub fov: f32,
     pub clip_near: f32,
@@ -73,7 +161,7 @@ pub struct Perspective {
 }
 
 /// Arcball camera parameters
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ArcBall {
     pub pivot: Vec3,
     pub distance: f32,
@@ -82,7 +170,7 @@ pub struct ArcBall {
 }
 
 /// Arcball camera controller parameters
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ArcBallController {
     pub pan_sensitivity: f32,
     pub swivel_sensitivity: f32,
@@ -106,6 +194,19 @@ impl ArcBall {
         Mat4::look_at_rh(eye, self.pivot, Vec3::new(0.0, 1.0, 0.0))
     }
 
+    /// Sets yaw and pitch so that the eye lies in the given direction from the pivot
+    pub fn look_from(&mut self, dir: Vec3) {
+        let Some(dir) = dir.try_normalize() else {
+            return;
+        };
+        // Looking straight up or down degenerates the look-at matrix
+        let max_pitch = FRAC_PI_2 - 1e-3;
+        self.pitch = dir.y.asin().clamp(-max_pitch, max_pitch);
+        if dir.x != 0.0 || dir.z != 0.0 {
+            self.yaw = dir.z.atan2(dir.x);
+        }
+    }
+
     pub fn eye(&self) -> Vec3 {
         Vec3::new(
             self.yaw.cos() * self.pitch.cos(),
@@ -182,6 +283,26 @@ impl Default for ArcBallController {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_view_rays() {
+        let camera = Camera::default();
+        let rays = camera.view_rays(64, 48);
+        for (i, j) in [(0, 0), (20, 31), (47, 63)] {
+            let dir = (camera.egui_to_world(i, j, 64, 48) - camera.pos()).normalize();
+            assert!(rays.dir(i, j).abs_diff_eq(dir, 1e-5));
+        }
+    }
+
+    #[test]
+    fn test_camera_looking_from() {
+        let bounds = BoundingBox::from_two_pos(Vec3::splat(-2.0), Vec3::splat(2.0));
+        let camera = Camera::looking_from(Vec3::Y, &bounds);
+        assert!(camera.dir().abs_diff_eq(Vec3::NEG_Y, 1e-2));
+        assert!(camera.pos().y > bounds.max.y);
+        assert!(camera.proj.clip_far > camera.pos().distance(bounds.min));
+    }
+
+    #[allow(clippy::module_inception)]
     #[cfg(test)]
     mod tests {
         use super::*;
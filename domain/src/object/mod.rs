@@ -1,15 +1,22 @@
-use glam::Vec3;
+use std::sync::Arc;
+
+use glam::{Mat4, Vec3};
 use objects::cloud::Cloud;
 
 use crate::object::camera::Camera;
-use crate::object::objects::{Grid, Sun, Terrain};
-use crate::scene::scene_composite::SceneObjects;
+use crate::object::objects::{
+    Background, Fog, Grid, Light, LightKind, Mesh, Obb, OrientationGizmo, Skybox, Sun, Terrain,
+    TransformGizmo, Water,
+};
+use crate::object::plugin::PluginComponent;
+use crate::scene::scene_composite::{SceneObjects, DEBUG_LAYER, DEFAULT_LAYER};
 use crate::visitor::{Visitable, Visitor};
 
 pub mod camera;
 pub mod objects;
+pub mod plugin;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum Component {
     Camera(Box<Camera>),
     Composite(SceneObjects),
@@ -17,6 +24,17 @@ pub enum Component {
     Sun(Sun),
     Grid(Grid),
     Terrain(Box<Terrain>),
+    Gizmo(OrientationGizmo),
+    Background(Background),
+    Water(Box<Water>),
+    Skybox(Skybox),
+    /// Shared with the other objects of the same model
+    Mesh(Arc<Mesh>),
+    Light(Light),
+    TransformGizmo(TransformGizmo),
+    Fog(Fog),
+    /// Component of a type unknown to the crate, see [`PluginComponent`]
+    Plugin(Arc<dyn PluginComponent>),
 }
 
 impl Component {
@@ -30,6 +48,10 @@ impl Component {
         Component::Composite(so)
     }
 
+    pub fn plugin(plugin: impl PluginComponent + 'static) -> Self {
+        Component::Plugin(Arc::new(plugin))
+    }
+
     pub fn pos(&self) -> glam::Vec3 {
         match self {
             Component::Camera(x) => x.pos(),
@@ -38,6 +60,89 @@ impl Component {
             Component::Sun(x) => x.get_pos(),
             Component::Grid(_) => Vec3::ZERO,
             Component::Terrain(x) => x.bounding_box.center(),
+            Component::Gizmo(_) => Vec3::ZERO,
+            Component::Background(_) => Vec3::ZERO,
+            Component::Water(x) => x.bounding_box.center(),
+            Component::Skybox(_) => Vec3::ZERO,
+            Component::Mesh(x) => x.bounding_box().center(),
+            Component::Light(x) => match x.kind {
+                LightKind::Directional(_) => Vec3::ZERO,
+                LightKind::Point(position) => position,
+            },
+            Component::TransformGizmo(x) => x.pivot,
+            Component::Fog(_) => Vec3::ZERO,
+            Component::Plugin(x) => x
+                .parts()
+                .bounds(Mat4::IDENTITY)
+                .map_or(Vec3::ZERO, |x| x.center()),
+        }
+    }
+
+    /// Name of the component type
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Component::Camera(_) => "camera",
+            Component::Composite(_) => "composite",
+            Component::Cloud(_) => "cloud",
+            Component::Sun(_) => "sun",
+            Component::Grid(_) => "grid",
+            Component::Terrain(_) => "terrain",
+            Component::Gizmo(_) => "gizmo",
+            Component::Background(_) => "background",
+            Component::Water(_) => "water",
+            Component::Skybox(_) => "skybox",
+            Component::Mesh(_) => "mesh",
+            Component::Light(_) => "light",
+            Component::TransformGizmo(_) => "transform_gizmo",
+            Component::Fog(_) => "fog",
+            Component::Plugin(x) => x.kind(),
+        }
+    }
+
+    /// Objects of a lower layer are drawn before all objects of a higher one,
+    /// regardless of their distance to the camera
+    pub fn layer(&self) -> u8 {
+        match self {
+            Component::Skybox(_) => 0,
+            // Water is the floor everything else is drawn over
+            Component::Water(_) => 1,
+            // Handles stay on top of the objects they move
+            Component::TransformGizmo(_) => 3,
+            _ => 2,
+        }
+    }
+
+    /// Render layer the component is put on when added to the scene
+    pub fn default_layer(&self) -> &'static str {
+        match self {
+            Component::Gizmo(_) | Component::TransformGizmo(_) => DEBUG_LAYER,
+            _ => DEFAULT_LAYER,
+        }
+    }
+
+    /// Name of the painter texture the component is rendered into, shared
+    /// by all components of the same kind
+    pub fn texture_name(&self) -> Option<&'static str> {
+        match self {
+            Component::Cloud(_) => Some("cloud"),
+            Component::Terrain(_) => Some("terrain"),
+            Component::Skybox(_) => Some("skybox"),
+            Component::Water(_) => Some("water"),
+            Component::Background(Background::Sky) => Some("sky"),
+            _ => None,
+        }
+    }
+
+    /// Box around the component in its local space, `None` for the ones
+    /// without a volume
+    pub fn bounds(&self) -> Option<Obb> {
+        match self {
+            Component::Cloud(x) => Some(x.obb()),
+            Component::Terrain(x) => Some(x.bounding_box.into()),
+            Component::Water(x) => Some(x.bounding_box.into()),
+            Component::Mesh(x) => Some(x.bounding_box().into()),
+            Component::Plugin(x) => x.parts().bounds(Mat4::IDENTITY).map(Obb::from),
+            _ => None,
         }
     }
 }
@@ -72,8 +177,62 @@ impl From<Terrain> for Component {
     }
 }
 
+impl From<OrientationGizmo> for Component {
+    fn from(value: OrientationGizmo) -> Self {
+        Component::Gizmo(value)
+    }
+}
+
+impl From<Background> for Component {
+    fn from(value: Background) -> Self {
+        Component::Background(value)
+    }
+}
+
+impl From<Water> for Component {
+    fn from(value: Water) -> Self {
+        Component::Water(Box::new(value))
+    }
+}
+
+impl From<Skybox> for Component {
+    fn from(value: Skybox) -> Self {
+        Component::Skybox(value)
+    }
+}
+
+impl From<Mesh> for Component {
+    fn from(value: Mesh) -> Self {
+        Component::Mesh(Arc::new(value))
+    }
+}
+
+impl From<Arc<Mesh>> for Component {
+    fn from(value: Arc<Mesh>) -> Self {
+        Component::Mesh(value)
+    }
+}
+
+impl From<Light> for Component {
+    fn from(value: Light) -> Self {
+        Component::Light(value)
+    }
+}
+
+impl From<TransformGizmo> for Component {
+    fn from(value: TransformGizmo) -> Self {
+        Component::TransformGizmo(value)
+    }
+}
+
+impl From<Fog> for Component {
+    fn from(value: Fog) -> Self {
+        Component::Fog(value)
+    }
+}
+
 impl Visitable for Component {
-    fn accept(&self, visitor: &mut impl Visitor) {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
         match self {
             Component::Camera(camera) => camera.accept(visitor),
             Component::Composite(composite) => composite.accept(visitor),
@@ -81,6 +240,15 @@ impl Visitable for Component {
             Component::Grid(grid) => grid.accept(visitor),
             Component::Sun(sun) => sun.accept(visitor),
             Component::Terrain(ter) => ter.accept(visitor),
+            Component::Gizmo(gizmo) => gizmo.accept(visitor),
+            Component::Background(bg) => bg.accept(visitor),
+            Component::Water(water) => water.accept(visitor),
+            Component::Skybox(skybox) => skybox.accept(visitor),
+            Component::Mesh(mesh) => mesh.accept(visitor),
+            Component::Light(light) => light.accept(visitor),
+            Component::TransformGizmo(gizmo) => gizmo.accept(visitor),
+            Component::Fog(fog) => fog.accept(visitor),
+            Component::Plugin(plugin) => visitor.visit_plugin(plugin.as_ref()),
         }
     }
 }
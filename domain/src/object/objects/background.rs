@@ -0,0 +1,24 @@
+use egui::Color32;
+use serde::{Deserialize, Serialize};
+
+use crate::visitor::{Visitable, Visitor};
+
+/// Fill of the viewport behind all other objects
+#[derive(Debug, Default, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Background {
+    Solid(Color32),
+    /// Vertical gradient from the top to the bottom edge of the viewport
+    Gradient {
+        top: Color32,
+        bottom: Color32,
+    },
+    /// Sky colored after the sun position
+    #[default]
+    Sky,
+}
+
+impl Visitable for Background {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_background(self)
+    }
+}
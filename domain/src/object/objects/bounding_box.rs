@@ -1,7 +1,8 @@
 use crate::visitor::{Visitable, Visitor};
 use glam::Vec3;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct BoundingBox {
     /// One of the corners of the rectangle, usually the left top one.
     pub min: Vec3,
@@ -82,6 +83,25 @@ impl BoundingBox {
         ]
     }
 
+    /// Smallest box enclosing both boxes
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// Axis aligned box enclosing this box moved by the given matrix
+    pub fn transformed(&self, matrix: glam::Mat4) -> Self {
+        let corners = self.corners().map(|x| matrix.transform_point3(x));
+        let (min, max) = corners[1..]
+            .iter()
+            .fold((corners[0], corners[0]), |(min, max), &x| {
+                (min.min(x), max.max(x))
+            });
+        Self { min, max }
+    }
+
     pub const fn edges(&self) -> [(usize, usize); 12] {
         [
             (0, 1),
@@ -102,7 +122,7 @@ impl BoundingBox {
 
 impl Visitable for BoundingBox {
     #[inline]
-    fn accept(&self, visitor: &mut impl Visitor) {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
         visitor.visit_bounding_box(self)
     }
 }
@@ -0,0 +1,72 @@
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::object::objects::BoundingBox;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    #[inline]
+    pub fn contains(&self, position: Vec3) -> bool {
+        position.distance_squared(self.center) <= self.radius * self.radius
+    }
+
+    /// Distance along the ray to the sphere and the distance travelled
+    /// inside it, like [`BoundingBox::dst`]. `ray_dir` has to be normalized.
+    pub fn dst(&self, ray_origin: Vec3, ray_dir: Vec3) -> Vec2 {
+        let to_center = self.center - ray_origin;
+        let t = to_center.dot(ray_dir);
+        let d2 = to_center.length_squared() - t * t;
+        let r2 = self.radius * self.radius;
+        if d2 > r2 {
+            return Vec2::ZERO;
+        }
+
+        let half_chord = (r2 - d2).sqrt();
+        let dst_to_sphere = (t - half_chord).max(0.0);
+        let dst_inside_sphere = (t + half_chord - dst_to_sphere).max(0.0);
+        Vec2::new(dst_to_sphere, dst_inside_sphere)
+    }
+}
+
+impl From<BoundingBox> for BoundingSphere {
+    /// Smallest sphere around the box
+    fn from(value: BoundingBox) -> Self {
+        Self::new(value.center(), 0.5 * value.size().length())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bounding_sphere() {
+        let sphere = BoundingSphere::new(Vec3::ZERO, 1.0);
+        assert_eq!(
+            sphere.dst(Vec3::new(5.0, 0.0, 0.0), Vec3::NEG_X),
+            Vec2::new(4.0, 2.0)
+        );
+        assert_eq!(sphere.dst(Vec3::ZERO, Vec3::X), Vec2::new(0.0, 1.0));
+        assert_eq!(
+            sphere.dst(Vec3::new(5.0, 2.0, 0.0), Vec3::NEG_X),
+            Vec2::ZERO
+        );
+        assert_eq!(sphere.dst(Vec3::new(5.0, 0.0, 0.0), Vec3::X), Vec2::ZERO);
+
+        assert!(sphere.contains(Vec3::new(0.0, 0.5, 0.5)));
+        assert!(!sphere.contains(Vec3::ONE));
+
+        let bb = BoundingBox::from_two_pos(Vec3::splat(-1.0), Vec3::ONE);
+        let sphere = BoundingSphere::from(bb);
+        assert!(bb.corners().iter().all(|&x| sphere.contains(x * 0.999)));
+    }
+}
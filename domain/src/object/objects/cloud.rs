@@ -1,13 +1,19 @@
 use std::fmt::{Debug, Formatter};
 use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
 
 use crate::object::objects::texture3d::{INoise, INoiseBuilder, Noise, NoiseBuilder};
+use crate::object::objects::{Light, LightKind};
+use crate::visitor::raster::color32_to_vec4;
 use crate::visitor::{Visitable, Visitor};
 use egui::Color32;
-use glam::{FloatExt, IVec3, Vec3, Vec3Swizzles, Vec4};
+use glam::{FloatExt, IVec3, Mat4, Quat, Vec3, Vec3Swizzles, Vec4, Vec4Swizzles};
 use log::info;
+use serde::{Deserialize, Serialize};
 
-use super::BoundingBox;
+use super::density_field::DensityField;
+use super::occupancy_grid::OccupancyGrid;
+use super::{BoundingBox, Obb};
 
 #[inline]
 pub fn remap(v: f32, min_old: f32, max_old: f32, min_new: f32, max_new: f32) -> f32 {
@@ -32,10 +38,81 @@ pub fn beer(d: f32) -> f32 {
     (-d).exp()
 }
 
-#[derive(Default, Debug, Copy,Clone)]
+/// Lights whose phase and light march are kept along a ray, the others are
+/// evaluated at every step
+const CACHED_LIGHTS: usize = 8;
+
+/// Weights of the linear RGB channels in the brightness of a light
+const LUMINANCE: Vec3 = Vec3::new(0.2126, 0.7152, 0.0722);
+/// Noise texture repeats per cloud space unit at a scale of 1
+const BASE_SCALE: f32 = 1.0 / 1000.0;
+/// Noise texture repeats the offset moves the noise by
+const OFFSET_SPEED: f32 = 1.0 / 100.0;
+
+/// Light march of a view ray taken every few steps, see
+/// [`CloudBuilder::light_interval`]
+#[derive(Debug, Default, Clone, Copy)]
+struct LightSamples {
+    /// Interval of steps the samples are taken at the ends of
+    segment: Option<usize>,
+    start: f32,
+    end: f32,
+}
+
+/// Values of a [`Cloud`] march shared by all the rays of a frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloudUniforms {
+    /// Volume matrix of a rotated cloud
+    matrix: Option<Mat4>,
+    /// Lights in the space of the volume
+    lights: Vec<Light>,
+    /// Light color of the cloud as a normalized color
+    tint: Vec3,
+    occupancy: Option<Arc<OccupancyGrid>>,
+    /// Density read instead of the noise
+    density: Option<Arc<DensityField>>,
+}
+
+impl CloudUniforms {
+    /// Steps over the empty bricks of the grid, which must be built from
+    /// the cloud the rays are marched through, up to its offset
+    pub fn with_occupancy(mut self, occupancy: Option<Arc<OccupancyGrid>>) -> Self {
+        self.occupancy = occupancy;
+        self
+    }
+
+    /// Reads the density from the coarse field built from the cloud instead
+    /// of sampling the noise, for quick previews
+    pub fn with_density_field(mut self, density: Option<Arc<DensityField>>) -> Self {
+        self.density = density;
+        self
+    }
+
+    /// Matrix taking the rays into the space of the volume of a rotated
+    /// cloud
+    pub fn matrix(&self) -> Option<Mat4> {
+        self.matrix
+    }
+
+    pub fn lights(&self) -> &[Light] {
+        &self.lights
+    }
+
+    pub fn tint(&self) -> Vec3 {
+        self.tint
+    }
+}
+
+#[derive(Default, Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CloudBuilder {
     pub bounding_box: BoundingBox,
+    /// Rotation of the volume around the center of the bounding box
+    #[serde(default)]
+    pub rotation: Quat,
     pub offset: Vec3,
+    /// Change of [`Self::offset`] per second, the noise drifts with the wind
+    #[serde(default)]
+    pub wind: Vec3,
     pub cloud_scale: f32,
     pub density_threshold: f32,
     pub density_offset: f32,
@@ -43,6 +120,15 @@ pub struct CloudBuilder {
 
     pub num_steps_light: usize,
     pub num_steps: usize,
+    /// Spreads the steps over the rays by their path through the volume, so
+    /// the step length is the same across it, see [`Cloud::ray_steps`]
+    #[serde(default)]
+    pub auto_steps: bool,
+    /// View ray steps between the light marches, the light is interpolated
+    /// between them. It changes much slower along the ray than the density.
+    /// 0 and 1 march the light at every step.
+    #[serde(default)]
+    pub light_interval: usize,
     pub ray_offset_strength: f32,
 
     pub alpha_threshold: u8,
@@ -83,6 +169,11 @@ impl CloudBuilder {
         self
     }
 
+    pub fn with_rotation(mut self, rotation: Quat) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
     pub fn with_noise(mut self, builder: impl Into<NoiseBuilder>) -> Self {
         self.noise = builder.into();
         self
@@ -103,6 +194,11 @@ impl CloudBuilder {
         self
     }
 
+    pub fn with_wind(mut self, wind: Vec3) -> Self {
+        self.wind = wind;
+        self
+    }
+
     pub fn with_cloud_scale(mut self, cloud_scale: f32) -> Self {
         self.cloud_scale = cloud_scale;
         self
@@ -138,6 +234,16 @@ impl CloudBuilder {
         self
     }
 
+    pub fn with_auto_steps(mut self, auto_steps: bool) -> Self {
+        self.auto_steps = auto_steps;
+        self
+    }
+
+    pub fn with_light_interval(mut self, light_interval: usize) -> Self {
+        self.light_interval = light_interval;
+        self
+    }
+
     pub fn with_alpha_threshold(mut self, alpha_threshold: u8) -> Self {
         self.alpha_threshold = alpha_threshold;
         self
@@ -239,9 +345,9 @@ impl CloudBuilder {
 
 #[derive(Clone, Default)]
 pub struct Cloud {
-    noise: Noise,
-    detail_noise: Noise,
-    weather_map: Noise,
+    noise: Arc<Noise>,
+    detail_noise: Arc<Noise>,
+    weather_map: Arc<Noise>,
     pub cloud_params: CloudBuilder,
 }
 
@@ -267,9 +373,22 @@ impl DerefMut for Cloud {
 impl Cloud {
     pub fn build(cloud_params: CloudBuilder) -> Self {
         info!("Cloud created at {:?}", cloud_params.bounding_box);
-        let noise = cloud_params.noise.build();
-        let detail_noise = cloud_params.detail_noise.build();
-        let weather_map = cloud_params.weather_noise.build();
+        let noise = Arc::new(cloud_params.noise.build());
+        let detail_noise = Arc::new(cloud_params.detail_noise.build());
+        let weather_map = Arc::new(cloud_params.weather_noise.build());
+        Self::with_volumes(cloud_params, noise, detail_noise, weather_map)
+    }
+
+    /// Cloud sampling already generated noise, which may be shared with other
+    /// clouds, see [`ResourceManager::volume`]
+    ///
+    /// [`ResourceManager::volume`]: crate::managers::resource_manager::ResourceManager::volume
+    pub fn with_volumes(
+        cloud_params: CloudBuilder,
+        noise: Arc<Noise>,
+        detail_noise: Arc<Noise>,
+        weather_map: Arc<Noise>,
+    ) -> Self {
         Self {
             cloud_params,
             noise,
@@ -279,22 +398,74 @@ impl Cloud {
     }
 
     pub fn regenerate_noise(&mut self, builder: impl Into<NoiseBuilder>) {
-        self.noise = builder.into().build();
+        self.noise = Arc::new(builder.into().build());
     }
 
     pub fn regenerate_detail_noise(&mut self, builder: impl Into<NoiseBuilder>) {
-        self.detail_noise = builder.into().build();
+        self.detail_noise = Arc::new(builder.into().build());
+    }
+
+    /// Samples the shape from the given noise generated by `builder` and
+    /// returns the replaced noise, see [`ResourceManager::retire`]
+    ///
+    /// [`ResourceManager::retire`]: crate::managers::resource_manager::ResourceManager::retire
+    pub fn set_noise(&mut self, builder: NoiseBuilder, noise: Arc<Noise>) -> Arc<Noise> {
+        self.cloud_params.noise = builder;
+        std::mem::replace(&mut self.noise, noise)
+    }
+
+    pub fn set_detail_noise(&mut self, builder: NoiseBuilder, noise: Arc<Noise>) -> Arc<Noise> {
+        self.cloud_params.detail_noise = builder;
+        std::mem::replace(&mut self.detail_noise, noise)
     }
 
     pub fn bounding_box(&self) -> &BoundingBox {
         &self.bounding_box
     }
 
-    pub fn sample_density(&self, ray_pos: Vec3) -> f32 {
-        const BASE_SCALE: f32 = 1.0 / 1000.0;
-        const OFFSET_SPEED: f32 = 1.0 / 100.0;
+    /// Shape noise, detail noise and weather map the density is sampled from
+    pub fn volumes(&self) -> [&Arc<Noise>; 3] {
+        [&self.noise, &self.detail_noise, &self.weather_map]
+    }
+
+    /// Volume of the cloud with its rotation
+    pub fn obb(&self) -> Obb {
+        Obb::from_box(&self.bounding_box, self.rotation)
+    }
+
+    #[inline]
+    pub fn is_rotated(&self) -> bool {
+        self.rotation != Quat::IDENTITY
+    }
+
+    /// Matrix taking points of the cloud space into the space where the
+    /// volume is the axis aligned bounding box
+    pub fn volume_matrix(&self) -> Mat4 {
+        let center = self.bounding_box.center();
+        Mat4::from_translation(center)
+            * Mat4::from_quat(self.rotation.inverse())
+            * Mat4::from_translation(-center)
+    }
+
+    /// Distance the shape noise has moved through the volume since the
+    /// offset was `from`. The noise at a point is the one a point this far
+    /// ahead had then, while the height gradient and the edges of the volume stay
+    /// in place.
+    pub fn noise_shift(&self, from: Vec3) -> Vec3 {
+        let scale = self.cloud_scale * BASE_SCALE;
+        if scale == 0.0 {
+            return Vec3::ZERO;
+        }
+        (self.offset - from) * OFFSET_SPEED / scale
+    }
+
+    /// Coordinates of the point in the shape noise
+    fn shape_uvw(&self, p: Vec3) -> Vec3 {
+        p * self.cloud_scale * BASE_SCALE + self.offset * OFFSET_SPEED
+    }
 
-        let uvw = ray_pos * self.cloud_scale * BASE_SCALE + self.offset * OFFSET_SPEED;
+    pub fn sample_density(&self, ray_pos: Vec3) -> f32 {
+        let uvw = self.shape_uvw(ray_pos);
         let shape = self.noise.sample_level(uvw).abs();
 
         let bb = self.bounding_box();
@@ -352,8 +523,36 @@ impl Cloud {
         0.0
     }
 
-    pub fn light_march(&self, mut p: Vec3, world_space_light_pos0: Vec3) -> f32 {
-        let dir_to_light = world_space_light_pos0;
+    /// Returns the share of light reaching the point from the given
+    /// direction through the cloud
+    pub fn light_march(&self, p: Vec3, dir_to_light: Vec3) -> f32 {
+        let (p, dir_to_light) = if self.is_rotated() {
+            let matrix = self.volume_matrix();
+            (
+                matrix.transform_point3(p),
+                matrix.transform_vector3(dir_to_light),
+            )
+        } else {
+            (p, dir_to_light)
+        };
+        self.light_transmittance(p, dir_to_light, self.light_absorption_toward_sun, None)
+    }
+
+    /// Density at the point from the field when there is one
+    fn density(&self, field: Option<&DensityField>, p: Vec3) -> f32 {
+        match field {
+            Some(field) => field.sample(p + field.shift(self)),
+            None => self.sample_density(p),
+        }
+    }
+
+    fn light_transmittance(
+        &self,
+        mut p: Vec3,
+        dir_to_light: Vec3,
+        absorption: f32,
+        field: Option<&DensityField>,
+    ) -> f32 {
         let dst_inside_box = self.bounding_box().dst(p, dir_to_light).y;
         let step_size = dst_inside_box / self.num_steps_light as f32;
         p += dir_to_light * step_size;
@@ -362,18 +561,380 @@ impl Cloud {
         let step_size_f32 = step_size;
 
         for _ in 0..self.num_steps_light {
-            let density = self.sample_density(p);
+            let density = self.density(field, p);
             total_density += density.max(0.0);
             p += dir_to_light * step_size_f32;
         }
 
-        let transmittance = beer(total_density * self.light_absorption_toward_sun * step_size_f32);
+        let transmittance = beer(total_density * absorption * step_size_f32);
         transmittance.lerp(1.0, self.darkness_threshold)
     }
+
+    /// Returns the share of light passing through the whole cloud along the
+    /// ray, e.g. from the eye towards the sun
+    pub fn transmittance(&self, ray_origin: Vec3, ray_dir: Vec3) -> f32 {
+        let (ray_origin, ray_dir) = if self.is_rotated() {
+            let matrix = self.volume_matrix();
+            (
+                matrix.transform_point3(ray_origin),
+                matrix.transform_vector3(ray_dir),
+            )
+        } else {
+            (ray_origin, ray_dir)
+        };
+        let (dst_to_box, dst_inside_box) = self.bounding_box().dst(ray_origin, ray_dir).into();
+        if dst_inside_box <= 0.0 {
+            return 1.0;
+        }
+
+        let step_size = dst_inside_box / self.num_steps_light.max(1) as f32;
+        let mut p = ray_origin + ray_dir * (dst_to_box + 0.5 * step_size);
+        let mut total_density = 0.0;
+        for _ in 0..self.num_steps_light.max(1) {
+            total_density += self.sample_density(p).max(0.0);
+            p += ray_dir * step_size;
+        }
+        beer(total_density * step_size * self.light_absorption_through_cloud)
+    }
+
+    /// Steps of a view ray running `chord` through the volume. With
+    /// [`CloudBuilder::auto_steps`] the diagonal of the volume gets
+    /// [`CloudBuilder::num_steps`] and shorter paths get fewer, otherwise
+    /// every ray gets them all.
+    pub fn ray_steps(&self, chord: f32) -> usize {
+        if !self.auto_steps {
+            return self.num_steps.max(1);
+        }
+        let diagonal = self.bounding_box.size().length().max(f32::EPSILON);
+        let steps = (self.num_steps as f32 * chord / diagonal).ceil() as usize;
+        steps.clamp(1, self.num_steps.max(1))
+    }
+
+    /// Values shared by every ray of a frame, converted once before the
+    /// rays are marched. Dark lights are left out, marching towards them
+    /// would not add any light.
+    pub fn uniforms(&self, lights: &[Light]) -> CloudUniforms {
+        let matrix = self.is_rotated().then(|| self.volume_matrix());
+        let lights = lights.iter().filter(|x| !x.is_dark());
+        let lights = match matrix {
+            Some(matrix) => lights.map(|x| x.transformed(matrix)).collect(),
+            None => lights.copied().collect(),
+        };
+        CloudUniforms {
+            matrix,
+            lights,
+            tint: color32_to_vec4(self.light_color).xyz(),
+            occupancy: None,
+            density: None,
+        }
+    }
+
+    /// Marches a single view ray through the cloud and returns its color.
+    /// The light scattered towards the viewer is summed over all lights.
+    ///
+    /// Rays missing the bounding box are transparent. A rotated cloud is
+    /// marched in the space of its volume. Rays of the same frame should
+    /// share the [`CloudUniforms`] through [`Self::march_with`].
+    pub fn march(&self, ray_origin: Vec3, ray_dir: Vec3, lights: &[Light]) -> Color32 {
+        self.march_with(&self.uniforms(lights), ray_origin, ray_dir)
+    }
+
+    pub fn march_with(&self, uniforms: &CloudUniforms, ray_origin: Vec3, ray_dir: Vec3) -> Color32 {
+        self.march_radiance(uniforms, ray_origin, ray_dir)
+            .to_color()
+    }
+
+    /// Marches the ray like [`Self::march_with`], keeping the light linear
+    /// and unclamped
+    pub fn march_radiance(
+        &self,
+        uniforms: &CloudUniforms,
+        ray_origin: Vec3,
+        ray_dir: Vec3,
+    ) -> CloudRadiance {
+        match uniforms.matrix {
+            Some(matrix) => self.march_aligned(
+                uniforms,
+                matrix.transform_point3(ray_origin),
+                matrix.transform_vector3(ray_dir),
+            ),
+            None => self.march_aligned(uniforms, ray_origin, ray_dir),
+        }
+    }
+
+    fn march_aligned(
+        &self,
+        uniforms: &CloudUniforms,
+        ray_origin: Vec3,
+        ray_dir: Vec3,
+    ) -> CloudRadiance {
+        let ray_box_info = self.bounding_box().dst(ray_origin, ray_dir);
+        let dst_to_box = ray_box_info.x;
+        let dst_inside_box = ray_box_info.y;
+
+        if dst_inside_box <= 0.0 {
+            return CloudRadiance::EMPTY;
+        }
+
+        let mut dst_travelled = 0.0;
+        let dst_limit = dst_inside_box;
+        let step_size = dst_inside_box / self.ray_steps(dst_inside_box) as f32;
+        let mut transmittance = 1.0;
+        let mut light_energy = Vec3::ZERO;
+
+        let entry_point = ray_origin + dst_to_box * ray_dir;
+        let (lights, tint) = (&uniforms.lights, uniforms.tint);
+        // The angle to a directional light stays the same along the ray
+        let mut phases = [None; CACHED_LIGHTS];
+        for (cached, light) in phases.iter_mut().zip(lights) {
+            if let LightKind::Directional(_) = light.kind {
+                let cos_angle = ray_dir.dot(light.dir_to_light(entry_point));
+                *cached = Some(phase(cos_angle, self.phase_params));
+            }
+        }
+
+        let field = uniforms.density.as_deref();
+        let interval = self.light_interval.max(1);
+        let mut samples = [LightSamples::default(); CACHED_LIGHTS];
+        // Light reaching the point of the ray `step` steps into the volume
+        let light_at = |light: &Light, step: usize| {
+            let p = entry_point + ray_dir * (step as f32 * step_size).min(dst_limit);
+            let absorption = self.light_absorption_toward_sun * light.absorption;
+            self.light_transmittance(p, light.dir_to_light(p), absorption, field)
+        };
+
+        let occupancy = uniforms.occupancy.as_deref().map(|x| (x, x.shift(self)));
+        let mut step = 0;
+        while dst_travelled < dst_limit {
+            let ray_pos = entry_point + ray_dir * dst_travelled;
+            if let Some((grid, shift)) = occupancy.filter(|(x, shift)| x.is_empty(ray_pos + *shift))
+            {
+                // Whole steps over the empty brick
+                let skip = (grid.brick_exit(ray_pos + shift, ray_dir) / step_size)
+                    .ceil()
+                    .max(1.0);
+                dst_travelled += skip * step_size;
+                step += skip as usize;
+                continue;
+            }
+            let density = self.density(field, ray_pos);
+            if density > 0.1 {
+                for (k, light) in lights.iter().enumerate() {
+                    let dir_to_light = light.dir_to_light(ray_pos);
+                    let phase = phases
+                        .get(k)
+                        .copied()
+                        .flatten()
+                        .unwrap_or_else(|| phase(ray_dir.dot(dir_to_light), self.phase_params));
+                    let light_transmittance = match samples.get_mut(k) {
+                        Some(samples) if interval > 1 => {
+                            let segment = step / interval;
+                            if samples.segment != Some(segment) {
+                                samples.start = match samples.segment {
+                                    Some(x) if x + 1 == segment => samples.end,
+                                    _ => light_at(light, segment * interval),
+                                };
+                                samples.end = light_at(light, (segment + 1) * interval);
+                                samples.segment = Some(segment);
+                            }
+                            let t = (step % interval) as f32 / interval as f32;
+                            samples.start.lerp(samples.end, t)
+                        }
+                        _ => self.light_transmittance(
+                            ray_pos,
+                            dir_to_light,
+                            self.light_absorption_toward_sun * light.absorption,
+                            field,
+                        ),
+                    };
+                    light_energy += density
+                        * step_size
+                        * transmittance
+                        * light_transmittance
+                        * phase
+                        * light.radiance(ray_pos);
+                }
+                transmittance *= beer(density * step_size * self.light_absorption_through_cloud);
+                if transmittance < 0.01 {
+                    break;
+                }
+            }
+            dst_travelled += step_size;
+            step += 1;
+        }
+
+        // Lights seen through the thin parts of the cloud, by their brightness
+        let (mut glow, mut glow_color) = (0.0, Vec3::ZERO);
+        for light in lights {
+            let radiance = light.radiance(entry_point);
+            let cos_angle = ray_dir.dot(light.dir_to_light(entry_point));
+            let focused_eye_cos = cos_angle.clamp(-1.0, 1.0).powf(self.params.x);
+            let sun = hg(focused_eye_cos, self.phase_params.w).clamp(-1.0, 1.0) * transmittance;
+            glow += sun * radiance.dot(LUMINANCE);
+            glow_color += tint * radiance * sun;
+        }
+
+        CloudRadiance {
+            scattered: light_energy * tint,
+            glow,
+            glow_color,
+            transmittance,
+        }
+    }
+}
+
+/// Light a view ray gathers in the cloud before it becomes a color
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CloudRadiance {
+    /// Light scattered towards the eye
+    pub scattered: Vec3,
+    /// Share and color of the lights seen through the cloud
+    pub glow: f32,
+    pub glow_color: Vec3,
+    /// Share of the light behind the cloud passing through it
+    pub transmittance: f32,
+}
+
+impl CloudRadiance {
+    /// Ray missing the cloud
+    pub const EMPTY: Self = Self {
+        scattered: Vec3::ZERO,
+        glow: 0.0,
+        glow_color: Vec3::ZERO,
+        transmittance: 1.0,
+    };
+
+    /// Light leaving the cloud towards the eye, weighted by the opacity like
+    /// the alpha of [`Self::to_color`]
+    pub fn radiance(&self) -> Vec3 {
+        let col = self.scattered * (1.0 - self.glow.clamp(0.0, 1.0)) + self.glow_color;
+        col * (1.0 - self.transmittance)
+    }
+
+    pub fn to_color(&self) -> Color32 {
+        let col = self.scattered.clamp(Vec3::ZERO, Vec3::ONE) * (1.0 - self.glow.clamp(0.0, 1.0))
+            + self.glow_color;
+        let (r, g, b) = col.clamp(Vec3::ZERO, Vec3::ONE).into();
+        Color32::from_rgba_unmultiplied(
+            (r * 255.0) as u8,
+            (g * 255.0) as u8,
+            (b * 255.0) as u8,
+            (255.0 * (1.0 - self.transmittance)) as u8,
+        )
+    }
 }
 
 impl Visitable for Cloud {
-    fn accept(&self, visitor: &mut impl Visitor) {
-        visitor.visit_cloud(self);
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_cloud(self)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::object::objects::texture3d::WorleyBuilder;
+
+    /// Worley noise of the test volumes, coarse enough to build at once
+    pub(crate) fn worley(seed: u64, resolution: usize) -> WorleyBuilder {
+        WorleyBuilder::new()
+            .with_seed(seed)
+            .with_num_points_a(2)
+            .with_num_points_b(3)
+            .with_num_points_c(4)
+            .with_tile(1.0)
+            .with_resolution(resolution)
+            .with_color_mask(Vec4::ONE)
+            .with_persistence(0.5)
+    }
+
+    /// Small cloud with dense and empty parts in the box from -1 to 1
+    pub(crate) fn dense_cloud() -> Cloud {
+        let noise = worley(0, 8);
+        CloudBuilder::default()
+            .with_bounding_box((Vec3::splat(-1.0), Vec3::splat(1.0)))
+            .with_noise(noise)
+            .with_detail_noise(noise)
+            .with_weather_noise(noise)
+            .with_cloud_scale(100.0)
+            .with_density_multiplier(5.0)
+            .with_shape_noise_weights(Vec4::ONE)
+            .with_detail_weights(Vec4::ONE)
+            .with_edge_distance(0.5)
+            .with_height_map_factor(2.0)
+            .with_light_color(Color32::WHITE)
+            .with_light_absorption_through_cloud(0.6)
+            .with_light_absorption_toward_sun(0.6)
+            .with_phase_params(Vec4::new(0.0, 0.48, 0.37, 0.34))
+            .with_num_steps(64)
+            .with_num_steps_light(8)
+            .build()
+    }
+
+    #[test]
+    fn test_noise_shift() {
+        let cloud = dense_cloud();
+        let mut moved = cloud.clone();
+        moved.offset += Vec3::new(3.0, -1.0, 0.5);
+        let shift = moved.noise_shift(cloud.offset);
+        assert_ne!(shift, Vec3::ZERO);
+        for p in [Vec3::ZERO, Vec3::new(0.3, -0.7, 0.9)] {
+            let (a, b) = (moved.shape_uvw(p), cloud.shape_uvw(p + shift));
+            assert!(a.abs_diff_eq(b, 1e-5), "{a} {b}");
+        }
+        assert_eq!(cloud.noise_shift(cloud.offset), Vec3::ZERO);
+    }
+
+    #[test]
+    fn test_ray_steps() {
+        let params = CloudBuilder::default()
+            .with_bounding_box((Vec3::ZERO, Vec3::new(3.0, 0.0, 4.0)))
+            .with_num_steps(100);
+        let volumes = || Arc::new(Noise::default());
+        let mut cloud = Cloud::with_volumes(params, volumes(), volumes(), volumes());
+        assert_eq!(cloud.ray_steps(1.0), 100);
+
+        cloud.auto_steps = true;
+        assert_eq!(cloud.ray_steps(5.0), 100);
+        assert_eq!(cloud.ray_steps(2.5), 50);
+        // Grazing rays still take a step
+        assert_eq!(cloud.ray_steps(0.0), 1);
+    }
+
+    #[test]
+    fn test_light_interval() {
+        let mut cloud = dense_cloud();
+        let lights = [Light::directional(Vec3::Y)];
+        let march = |cloud: &Cloud| cloud.march(Vec3::new(0.0, 0.0, 3.0), -Vec3::Z, &lights);
+
+        let every_step = march(&cloud);
+        assert!(every_step.a() > 0);
+        cloud.light_interval = 1;
+        assert_eq!(march(&cloud), every_step);
+        cloud.light_interval = 4;
+        let interpolated = march(&cloud);
+        for (a, b) in interpolated
+            .to_array()
+            .into_iter()
+            .zip(every_step.to_array())
+        {
+            assert!(a.abs_diff(b) <= 16, "{interpolated:?} {every_step:?}");
+        }
+    }
+
+    #[test]
+    fn test_dark_light_glow() {
+        let cloud = dense_cloud();
+        let sun = Light::directional(Vec3::Y);
+        // Shining at the eye through the cloud
+        let fill = Light::directional(Vec3::Z).with_intensity(0.0);
+        let march = |lights: &[Light]| {
+            let uniforms = cloud.uniforms(lights);
+            cloud.march_radiance(&uniforms, Vec3::new(0.5, 0.5, 3.0), -Vec3::Z)
+        };
+        let (lit, filled) = (march(&[sun]), march(&[sun, fill]));
+        assert_eq!(filled, lit);
+        // Nothing is marched towards the dark light
+        assert_eq!(cloud.uniforms(&[sun, fill]).lights(), [sun]);
     }
 }
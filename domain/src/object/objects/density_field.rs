@@ -0,0 +1,124 @@
+//! Density of a cloud sampled once into a coarse 3D grid
+
+use glam::{UVec3, Vec3};
+use rayon::prelude::*;
+
+use super::{BoundingBox, Cloud};
+
+/// Samples along each side of the field the clouds are marched with while
+/// the camera moves
+pub const DENSITY_FIELD_SIZE: usize = 64;
+
+/// Density of the cloud at the centers of the cells of a grid over its
+/// volume, read back with trilinear filtering. Much cheaper than the shape
+/// and detail noise, at the cost of the fine detail.
+///
+/// Like the [`OccupancyGrid`], the field is read shifted by the drift of the
+/// noise since it was sampled, until the drift reaches a cell.
+///
+/// [`OccupancyGrid`]: super::occupancy_grid::OccupancyGrid
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityField {
+    /// Volume of the cloud, in the space the cloud is marched in
+    bounds: BoundingBox,
+    /// Offset of the cloud when sampled
+    offset: Vec3,
+    size: usize,
+    density: Vec<f32>,
+}
+
+impl DensityField {
+    /// Samples the density of the cloud in `size` cells along each side
+    pub fn new(cloud: &Cloud, size: usize) -> Self {
+        let size = size.max(1);
+        let bounds = *cloud.bounding_box();
+        let cell = bounds.size() / size as f32;
+        let density = (0..size * size * size)
+            .into_par_iter()
+            .map(|i| {
+                let c = UVec3::new(
+                    (i % size) as u32,
+                    (i / size % size) as u32,
+                    (i / (size * size)) as u32,
+                );
+                cloud.sample_density(bounds.min + (c.as_vec3() + 0.5) * cell)
+            })
+            .collect();
+        Self {
+            bounds,
+            offset: cloud.offset,
+            size,
+            density,
+        }
+    }
+
+    /// Shift of the points of the cloud to the point of the field with the
+    /// same density, see [`Cloud::noise_shift`]
+    pub fn shift(&self, cloud: &Cloud) -> Vec3 {
+        cloud.noise_shift(self.offset)
+    }
+
+    /// Whether the noise of the cloud has drifted less than a cell since
+    /// the field was sampled
+    pub fn is_current(&self, cloud: &Cloud) -> bool {
+        let cell = self.bounds.size() / self.size as f32;
+        self.shift(cloud).abs().cmplt(cell).all()
+    }
+
+    fn at(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.density[(z * self.size + y) * self.size + x]
+    }
+
+    /// Density at the point, the points outside of the volume take the one
+    /// of its nearest side
+    pub fn sample(&self, p: Vec3) -> f32 {
+        let last = (self.size - 1) as f32;
+        let t = ((p - self.bounds.min) / self.bounds.size() * self.size as f32 - 0.5)
+            .clamp(Vec3::ZERO, Vec3::splat(last));
+        if t.is_nan() {
+            return 0.0;
+        }
+        let i = t.floor().as_uvec3().min(UVec3::splat(self.size as u32 - 1));
+        let j = (i + 1).min(UVec3::splat(self.size as u32 - 1));
+        let f = t - i.as_vec3();
+        let (i, j) = (
+            i.to_array().map(|x| x as usize),
+            j.to_array().map(|x| x as usize),
+        );
+
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        let plane = |z| {
+            let bottom = lerp(self.at(i[0], i[1], z), self.at(j[0], i[1], z), f.x);
+            let top = lerp(self.at(i[0], j[1], z), self.at(j[0], j[1], z), f.x);
+            lerp(bottom, top, f.y)
+        };
+        lerp(plane(i[2]), plane(j[2]), f.z)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::tests::dense_cloud;
+
+    #[test]
+    fn test_density_field() {
+        let cloud = dense_cloud();
+        let field = DensityField::new(&cloud, 16);
+        let cell = cloud.bounding_box().size() / 16.0;
+        let center = cloud.bounding_box().min + cell * Vec3::new(3.5, 5.5, 7.5);
+        assert_eq!(field.sample(center), cloud.sample_density(center));
+
+        // Between two cells
+        let next = center + Vec3::X * cell.x;
+        let between = field.sample(center + Vec3::X * cell.x * 0.5);
+        let (a, b) = (field.sample(center), field.sample(next));
+        assert!((between - (a + b) / 2.0).abs() < 1e-4);
+
+        let mut moved = cloud.clone();
+        moved.offset += Vec3::new(0.5, 0.0, 0.0);
+        assert!(field.is_current(&moved));
+        moved.offset.x += 1.0;
+        assert!(!field.is_current(&moved));
+    }
+}
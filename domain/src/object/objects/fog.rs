@@ -0,0 +1,119 @@
+use egui::Color32;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::visitor::{Visitable, Visitor};
+
+/// Exponential height fog, thinning out above its base height
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct Fog {
+    /// Density at the base height
+    pub density: f32,
+    /// How fast the density decays with the height
+    pub falloff: f32,
+    /// Height at which the density is [`Self::density`]
+    pub height: f32,
+    pub color: Color32,
+}
+
+impl Default for Fog {
+    fn default() -> Self {
+        Self {
+            density: 0.05,
+            falloff: 0.5,
+            height: 0.0,
+            color: Color32::from_rgb(200, 210, 225),
+        }
+    }
+}
+
+impl Fog {
+    pub fn new(density: f32, falloff: f32, color: Color32) -> Self {
+        Self {
+            density,
+            falloff,
+            color,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_height(mut self, height: f32) -> Self {
+        self.height = height;
+        self
+    }
+
+    /// Fog moved by the matrix. Only the translation is taken into account,
+    /// the fog stays horizontal.
+    pub fn transformed(&self, matrix: Mat4) -> Self {
+        Self {
+            height: self.height + matrix.w_axis.y,
+            ..*self
+        }
+    }
+
+    /// Share of the light scattered away on the way from the point at the
+    /// given distance along the ray to its origin. The distance may be
+    /// infinite for the rays escaping to the sky, `dir` has to be normalized.
+    pub fn amount(&self, origin: Vec3, dir: Vec3, distance: f32) -> f32 {
+        if self.density <= 0.0 {
+            return 0.0;
+        }
+        let base = self.density * (-self.falloff * (origin.y - self.height)).exp();
+        let rise = self.falloff * dir.y;
+        let depth = if distance.is_infinite() {
+            if rise <= f32::EPSILON {
+                return 1.0;
+            }
+            base / rise
+        } else if (rise * distance).abs() > 1e-4 {
+            base * (1.0 - (-rise * distance).exp()) / rise
+        } else {
+            base * distance
+        };
+        1.0 - (-depth.max(0.0)).exp()
+    }
+
+    /// Blends the premultiplied color with the fog, keeping its opacity
+    pub fn apply(&self, color: Color32, origin: Vec3, dir: Vec3, distance: f32) -> Color32 {
+        let amount = self.amount(origin, dir, distance);
+        let alpha = color.a() as f32 / 255.0;
+        let mix = |c: u8, f: u8| (c as f32 * (1.0 - amount) + f as f32 * alpha * amount) as u8;
+        Color32::from_rgba_premultiplied(
+            mix(color.r(), self.color.r()),
+            mix(color.g(), self.color.g()),
+            mix(color.b(), self.color.b()),
+            color.a(),
+        )
+    }
+}
+
+impl Visitable for Fog {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_fog(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fog_amount() {
+        let fog = Fog::new(0.1, 1.0, Color32::WHITE);
+        assert_eq!(fog.amount(Vec3::ZERO, Vec3::X, 0.0), 0.0);
+        let near = fog.amount(Vec3::ZERO, Vec3::X, 10.0);
+        let far = fog.amount(Vec3::ZERO, Vec3::X, 100.0);
+        assert!((near - (1.0 - (-1.0_f32).exp())).abs() < 1e-5);
+        assert!(near < far && far < 1.0);
+
+        // Thinner above the base and fully opaque along the horizon
+        assert!(fog.amount(Vec3::Y * 5.0, Vec3::X, 10.0) < near);
+        assert!(fog.amount(Vec3::ZERO, Vec3::Y, f32::INFINITY) < 0.1);
+        assert_eq!(fog.amount(Vec3::ZERO, Vec3::X, f32::INFINITY), 1.0);
+
+        let color = fog.apply(Color32::BLACK, Vec3::ZERO, Vec3::X, f32::INFINITY);
+        assert_eq!(color, Color32::WHITE);
+        let clear = Fog::new(0.0, 1.0, Color32::WHITE);
+        assert_eq!(clear.amount(Vec3::ZERO, Vec3::X, f32::INFINITY), 0.0);
+    }
+}
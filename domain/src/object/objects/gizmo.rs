@@ -0,0 +1,138 @@
+use egui::{Align2, Color32, Pos2, Rect, Vec2};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::object::camera::Camera;
+use crate::visitor::{Visitable, Visitor};
+
+/// Axis triad drawn in a corner of the canvas that follows the camera
+/// rotation. Its axis handles can be clicked to look along that axis.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct OrientationGizmo {
+    /// Corner of the canvas the gizmo is attached to
+    pub corner: Align2,
+    /// Length of the axes in screen points
+    pub size: f32,
+    /// Distance from the canvas edges to the gizmo bounds
+    pub margin: f32,
+    /// Radius of the clickable handle at the end of each axis
+    pub handle_radius: f32,
+}
+
+/// Projected end of one gizmo axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GizmoHandle {
+    /// World direction the handle stands for
+    pub axis: Vec3,
+    /// Position on screen
+    pub pos: Pos2,
+    /// View-space depth, larger values are closer to the viewer
+    pub depth: f32,
+    pub color: Color32,
+    pub label: &'static str,
+}
+
+impl Default for OrientationGizmo {
+    fn default() -> Self {
+        Self {
+            corner: Align2::RIGHT_TOP,
+            size: 40.0,
+            margin: 16.0,
+            handle_radius: 8.0,
+        }
+    }
+}
+
+impl OrientationGizmo {
+    pub fn new(corner: Align2, size: f32) -> Self {
+        Self {
+            corner,
+            size,
+            ..Default::default()
+        }
+    }
+
+    /// Center of the gizmo inside the given canvas rect
+    pub fn center(&self, canvas: Rect) -> Pos2 {
+        let extent = self.size + self.handle_radius + self.margin;
+        let inner = canvas.shrink(extent);
+        let [x, y] = self.corner.0;
+        Pos2::new(
+            x.to_factor() * inner.width() + inner.min.x,
+            y.to_factor() * inner.height() + inner.min.y,
+        )
+    }
+
+    /// Returns the six axis handles sorted back to front
+    pub fn handles(&self, camera: &Camera, canvas: Rect) -> [GizmoHandle; 6] {
+        let center = self.center(canvas);
+        let view = camera.view();
+        let axes = [
+            (Vec3::X, Color32::RED, "X"),
+            (Vec3::Y, Color32::DARK_GREEN, "Y"),
+            (Vec3::Z, Color32::BLUE, "Z"),
+            (Vec3::NEG_X, Color32::RED, ""),
+            (Vec3::NEG_Y, Color32::DARK_GREEN, ""),
+            (Vec3::NEG_Z, Color32::BLUE, ""),
+        ];
+
+        let mut handles = axes.map(|(axis, color, label)| {
+            let v = view.transform_vector3(axis);
+            GizmoHandle {
+                axis,
+                pos: center + Vec2::new(v.x, -v.y) * self.size,
+                depth: v.z,
+                color,
+                label,
+            }
+        });
+        handles.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+        handles
+    }
+
+    /// Returns the axis whose handle is under the pointer, preferring the
+    /// one closest to the viewer
+    pub fn hit(&self, camera: &Camera, canvas: Rect, pointer: Pos2) -> Option<Vec3> {
+        self.handles(camera, canvas)
+            .iter()
+            .rev()
+            .find(|h| h.pos.distance(pointer) <= self.handle_radius)
+            .map(|h| h.axis)
+    }
+}
+
+impl Visitable for OrientationGizmo {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_gizmo(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::camera::ArcBall;
+
+    #[test]
+    fn test_gizmo_hit() {
+        let camera = Camera {
+            view: ArcBall {
+                pivot: Vec3::ZERO,
+                distance: 10.0,
+                yaw: 0.0,
+                pitch: 0.0,
+            },
+            ..Default::default()
+        };
+        let gizmo = OrientationGizmo::default();
+        let canvas = Rect::from_min_size(Pos2::ZERO, Vec2::new(800.0, 600.0));
+        let center = gizmo.center(canvas);
+
+        assert_eq!(
+            gizmo.hit(&camera, canvas, center - Vec2::new(0.0, gizmo.size)),
+            Some(Vec3::Y)
+        );
+        // The X axis points at the viewer and hides the -X handle behind it
+        assert_eq!(gizmo.hit(&camera, canvas, center), Some(Vec3::X));
+        assert_eq!(gizmo.hit(&camera, canvas, Pos2::ZERO), None);
+    }
+}
@@ -1,20 +1,318 @@
+use crate::object::camera::Camera;
 use crate::visitor::{Visitable, Visitor};
+use egui::Color32;
 pub use glam::Vec3;
+use glam::{Vec2, Vec3Swizzles};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, Clone)]
+/// Max number of major lines across the visible area before the spacing
+/// is increased tenfold
+const MAX_LINES: f32 = 40.0;
+
+/// Coordinate plane the grid lies in
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum GridPlane {
+    /// The ground plane
+    #[default]
+    XZ,
+    XY,
+    YZ,
+}
+
+impl GridPlane {
+    /// Index of the world axis perpendicular to the plane
+    pub fn normal_axis(self) -> usize {
+        match self {
+            GridPlane::XZ => 1,
+            GridPlane::XY => 2,
+            GridPlane::YZ => 0,
+        }
+    }
+
+    /// Coordinates of the point inside the plane
+    pub fn project(self, p: Vec3) -> Vec2 {
+        match self {
+            GridPlane::XZ => p.xz(),
+            GridPlane::XY => p.xy(),
+            GridPlane::YZ => p.yz(),
+        }
+    }
+
+    /// World point of the plane with the given coordinates
+    pub fn unproject(self, p: Vec2) -> Vec3 {
+        match self {
+            GridPlane::XZ => Vec3::new(p.x, 0.0, p.y),
+            GridPlane::XY => Vec3::new(p.x, p.y, 0.0),
+            GridPlane::YZ => Vec3::new(0.0, p.x, p.y),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Grid {
+    /// Number of base grid cells per `scale`
     pub k: i32,
     pub scale: f32,
+    /// Radius at which the grid fully fades out, relative to the camera distance
+    pub fade: f32,
+    #[serde(default)]
+    pub plane: GridPlane,
+    #[serde(default = "default_line_color")]
+    pub major_color: Color32,
+    #[serde(default = "default_line_color")]
+    pub minor_color: Color32,
+}
+
+fn default_line_color() -> Color32 {
+    Color32::BLACK
+}
+
+/// Grid line segment on the ground plane ready to be drawn
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct GridLine {
+    pub a: Vec3,
+    pub b: Vec3,
+    /// Opacity in `0.0..=1.0`
+    pub alpha: f32,
+    /// Whether the line belongs to the main grid rather than the fine one
+    pub major: bool,
 }
 
 impl Grid {
     pub fn new(k: i32, scale: f32) -> Self {
-        Self { k, scale }
+        Self {
+            k,
+            scale,
+            fade: 1.0,
+            plane: GridPlane::default(),
+            major_color: default_line_color(),
+            minor_color: default_line_color(),
+        }
+    }
+
+    pub fn with_fade(mut self, fade: f32) -> Self {
+        self.fade = fade;
+        self
+    }
+
+    pub fn with_plane(mut self, plane: GridPlane) -> Self {
+        self.plane = plane;
+        self
+    }
+
+    pub fn with_colors(mut self, major: Color32, minor: Color32) -> Self {
+        self.major_color = major;
+        self.minor_color = minor;
+        self
+    }
+
+    /// Distance between neighbouring lines of the base grid
+    pub fn spacing(&self) -> f32 {
+        self.scale / self.k.max(1) as f32
+    }
+
+    /// Changes the scale so that the base lines are `spacing` apart
+    pub fn set_spacing(&mut self, spacing: f32) {
+        self.scale = spacing * self.k.max(1) as f32;
+    }
+
+    /// Color of the line with the given opacity
+    pub fn line_color(&self, line: &GridLine) -> Color32 {
+        let color = if line.major {
+            self.major_color
+        } else {
+            self.minor_color
+        };
+        color.gamma_multiply(line.alpha)
+    }
+
+    /// Returns the grid segments covering the part of the grid plane seen
+    /// by the camera.
+    ///
+    /// Lines fade out towards the edge of the fade radius around the camera
+    /// pivot. The spacing grows tenfold when too many lines would be visible,
+    /// and a ten times finer grid fades in when zoomed in close.
+    pub fn visible_lines(&self, camera: &Camera, width: f32, height: f32) -> Vec<GridLine> {
+        let radius = self.fade * camera.view.distance.max(self.scale);
+        let center = self.plane.project(camera.view.pivot());
+        let (mut min, mut max) = (center - radius, center + radius);
+        if let Some((lo, hi)) = footprint(camera, self.plane, width, height, radius) {
+            min = min.max(lo);
+            max = max.min(hi);
+        }
+        if min.x >= max.x || min.y >= max.y {
+            return Vec::new();
+        }
+
+        let mut spacing = self.spacing();
+        if !(spacing > 0.0 && spacing.is_finite()) {
+            return Vec::new();
+        }
+        while 2.0 * radius / spacing > MAX_LINES {
+            spacing *= 10.0;
+        }
+        let fine_alpha = (MAX_LINES - 2.0 * radius / spacing) / (0.9 * MAX_LINES);
+
+        let mut lines = Vec::new();
+        let plane = self.plane;
+        push_lines(
+            &mut lines,
+            plane,
+            spacing,
+            [min, max],
+            center,
+            radius,
+            1.0,
+            true,
+        );
+        if fine_alpha > 0.05 {
+            let r = 0.5 * radius;
+            let bounds = [min.max(center - r), max.min(center + r)];
+            push_lines(
+                &mut lines,
+                plane,
+                0.1 * spacing,
+                bounds,
+                center,
+                r,
+                fine_alpha.min(1.0),
+                false,
+            );
+        }
+        lines
+    }
+}
+
+/// Bounds of the grid plane area covered by the view frustum. Rays that
+/// do not reach the plane are extended along it to `radius`.
+fn footprint(
+    camera: &Camera,
+    plane: GridPlane,
+    width: f32,
+    height: f32,
+    radius: f32,
+) -> Option<(Vec2, Vec2)> {
+    let n = plane.normal_axis();
+    let inverse = (camera.projection(width, height) * camera.view()).inverse();
+    let mut points = Vec::with_capacity(4);
+    for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+        let near = inverse.project_point3(Vec3::new(x, y, 0.0));
+        let far = inverse.project_point3(Vec3::new(x, y, 1.0));
+        let dir = far - near;
+
+        let t = -near[n] / dir[n];
+        if dir[n].abs() > f32::EPSILON && (0.0..=1.0).contains(&t) {
+            points.push(plane.project(near + dir * t));
+        } else if let Some(plane_dir) = plane.project(dir).try_normalize() {
+            points.push(plane.project(near) + plane_dir * 2.0 * radius);
+        }
+    }
+
+    let first = *points.first()?;
+    Some(
+        points
+            .into_iter()
+            .fold((first, first), |(lo, hi), p| (lo.min(p), hi.max(p))),
+    )
+}
+
+/// Pushes lines parallel to both axes with the given spacing, split into
+/// pieces so that each piece gets its own fade
+#[allow(clippy::too_many_arguments)]
+fn push_lines(
+    lines: &mut Vec<GridLine>,
+    plane: GridPlane,
+    spacing: f32,
+    [min, max]: [Vec2; 2],
+    center: Vec2,
+    radius: f32,
+    alpha: f32,
+    major: bool,
+) {
+    let fade = |p: Vec2| {
+        let t = ((p.distance(center) / radius - 0.5) / 0.5).clamp(0.0, 1.0);
+        1.0 - t * t * (3.0 - 2.0 * t)
+    };
+    let piece = radius / 8.0;
+
+    for axis in [0, 1] {
+        // `u` runs across the lines, `v` along them
+        let (u_min, u_max) = (min[axis], max[axis]);
+        let (v_min, v_max) = (min[1 - axis], max[1 - axis]);
+        let pieces = ((v_max - v_min) / piece).ceil().clamp(1.0, 32.0) as usize;
+        let point = |u: f32, v: f32| {
+            let mut p = Vec2::ZERO;
+            p[axis] = u;
+            p[1 - axis] = v;
+            p
+        };
+
+        let first = (u_min / spacing).ceil() as i64;
+        let last = (u_max / spacing).floor() as i64;
+        for i in first..=last {
+            // Fine lines that coincide with the major ones are skipped
+            if !major && i % 10 == 0 {
+                continue;
+            }
+            let u = i as f32 * spacing;
+            for j in 0..pieces {
+                let v0 = v_min + (v_max - v_min) * j as f32 / pieces as f32;
+                let v1 = v_min + (v_max - v_min) * (j + 1) as f32 / pieces as f32;
+                let (a, b) = (point(u, v0), point(u, v1));
+                let alpha = alpha * fade(0.5 * (a + b));
+                if alpha > 0.01 {
+                    lines.push(GridLine {
+                        a: plane.unproject(a),
+                        b: plane.unproject(b),
+                        alpha,
+                        major,
+                    });
+                }
+            }
+        }
     }
 }
 
 impl Visitable for Grid {
-    fn accept(&self, visitor: &mut impl Visitor) {
-        visitor.visit_grid(self);
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_grid(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grid_visible_lines() {
+        let grid = Grid::new(10, 1.0);
+        let camera = Camera::default();
+        let lines = grid.visible_lines(&camera, 800.0, 600.0);
+
+        assert!(lines.iter().any(|x| x.major));
+        assert!(lines.iter().all(|x| x.alpha > 0.0 && x.alpha <= 1.0));
+        assert!(lines.iter().all(|x| x.a.y == 0.0 && x.b.y == 0.0));
+
+        let radius = grid.fade * camera.view.distance;
+        let pivot = camera.view.pivot().xz();
+        assert!(lines
+            .iter()
+            .all(|x| x.a.xz().distance(pivot) <= 1.5 * radius));
+
+        let grid = grid.with_plane(GridPlane::XY);
+        let lines = grid.visible_lines(&camera, 800.0, 600.0);
+        assert!(!lines.is_empty());
+        assert!(lines.iter().all(|x| x.a.z == 0.0 && x.b.z == 0.0));
+    }
+
+    #[test]
+    fn test_grid_zero_scale() {
+        let camera = Camera::default();
+        assert!(Grid::new(10, 0.0)
+            .visible_lines(&camera, 800.0, 600.0)
+            .is_empty());
+        assert!(Grid::new(10, f32::NAN)
+            .visible_lines(&camera, 800.0, 600.0)
+            .is_empty());
     }
 }
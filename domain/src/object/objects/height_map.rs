@@ -0,0 +1,72 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+/// Grid of heights in `0.0..=1.0` used to shape a terrain
+#[derive(Debug, Default, PartialEq, Clone, Serialize, Deserialize)]
+pub struct HeightMap {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl HeightMap {
+    /// Creates a height map from row-major samples.
+    ///
+    /// Panics if the number of samples does not match the size.
+    pub fn new(width: usize, height: usize, data: Vec<f32>) -> Self {
+        assert_eq!(width * height, data.len(), "height map size mismatch");
+        Self {
+            width,
+            height,
+            data,
+        }
+    }
+
+    /// Loads a height map from the luminance of an image file
+    pub fn from_file(path: impl AsRef<Path>) -> image::ImageResult<Self> {
+        let img = image::open(path)?.into_luma16();
+        let (width, height) = img.dimensions();
+        let data = img
+            .into_raw()
+            .into_iter()
+            .map(|x| x as f32 / u16::MAX as f32)
+            .collect();
+        Ok(Self::new(width as usize, height as usize, data))
+    }
+
+    pub fn size(&self) -> [usize; 2] {
+        [self.width, self.height]
+    }
+
+    /// Bilinearly samples the map at normalized coordinates, clamping to the edges
+    pub fn sample(&self, u: f32, v: f32) -> f32 {
+        if self.data.is_empty() {
+            return 0.0;
+        }
+        let x = u.clamp(0.0, 1.0) * (self.width - 1) as f32;
+        let y = v.clamp(0.0, 1.0) * (self.height - 1) as f32;
+        let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+        let at = |x: usize, y: usize| self.data[y * self.width + x];
+        let top = at(x0, y0) + (at(x1, y0) - at(x0, y0)) * tx;
+        let bottom = at(x0, y1) + (at(x1, y1) - at(x0, y1)) * tx;
+        top + (bottom - top) * ty
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_height_map_sample() {
+        let map = HeightMap::new(2, 2, vec![0.0, 1.0, 0.0, 1.0]);
+        assert_eq!(map.sample(0.0, 0.0), 0.0);
+        assert_eq!(map.sample(1.0, 1.0), 1.0);
+        assert_eq!(map.sample(0.5, 0.3), 0.5);
+        assert_eq!(map.sample(2.0, -1.0), 1.0);
+    }
+}
@@ -0,0 +1,149 @@
+//! Isosurface of a scalar grid, e.g. to see where a density threshold cuts
+//! the cloud
+
+use std::collections::HashMap;
+
+use glam::{Mat4, Vec3};
+
+use super::Mesh;
+
+/// Corners of the cube along x, y and z by the bits of their index
+const CORNERS: [[i64; 3]; 8] = [
+    [0, 0, 0],
+    [1, 0, 0],
+    [0, 1, 0],
+    [1, 1, 0],
+    [0, 0, 1],
+    [1, 0, 1],
+    [0, 1, 1],
+    [1, 1, 1],
+];
+
+/// Tetrahedra of a cube around its main diagonal, the neighbouring cubes
+/// split their shared faces the same way
+const TETRAHEDRA: [[usize; 4]; 6] = [
+    [0, 1, 3, 7],
+    [0, 3, 2, 7],
+    [0, 2, 6, 7],
+    [0, 6, 4, 7],
+    [0, 4, 5, 7],
+    [0, 5, 1, 7],
+];
+
+/// Surface where the values cross `threshold` by marching cubes, every cube
+/// is split into tetrahedra so that no case table is needed.
+///
+/// `values` go along x, then y, then z and the grid is surrounded by zero,
+/// so a positive threshold gives a closed surface. The faces wind counter
+/// clockwise seen from the lower values. `index_to_world` takes the grid
+/// indices to the positions of the vertices.
+pub fn isosurface(size: [usize; 3], values: &[f32], threshold: f32, index_to_world: Mat4) -> Mesh {
+    let [nx, ny, nz] = size.map(|x| x as i64);
+    assert_eq!(values.len() as i64, nx * ny * nz);
+    let value = |[x, y, z]: [i64; 3]| {
+        if (0..nx).contains(&x) && (0..ny).contains(&y) && (0..nz).contains(&z) {
+            values[((z * ny + y) * nx + x) as usize]
+        } else {
+            0.0
+        }
+    };
+    let point = |[x, y, z]: [i64; 3]| Vec3::new(x as f32, y as f32, z as f32);
+
+    let mut vertices = Vec::new();
+    let mut faces = Vec::new();
+    // Vertices by the grid points of their edges, shared by the faces
+    let mut edges = HashMap::new();
+    let mut vertex = |a: [i64; 3], b: [i64; 3]| {
+        let (a, b) = if a < b { (a, b) } else { (b, a) };
+        *edges.entry((a, b)).or_insert_with(|| {
+            let (va, vb) = (value(a), value(b));
+            let t = (threshold - va) / (vb - va);
+            let p = point(a).lerp(point(b), t);
+            vertices.push(index_to_world.transform_point3(p));
+            vertices.len() as u32 - 1
+        })
+    };
+
+    for z in -1..nz {
+        for y in -1..ny {
+            for x in -1..nx {
+                let corners = CORNERS.map(|[dx, dy, dz]| [x + dx, y + dy, z + dz]);
+                for tetrahedron in TETRAHEDRA {
+                    let corners = tetrahedron.map(|i| corners[i]);
+                    let (inside, outside): (Vec<_>, Vec<_>) =
+                        corners.into_iter().partition(|x| value(*x) > threshold);
+                    let triangles = match (inside.len(), outside.len()) {
+                        (1, 3) | (3, 1) => {
+                            let (apex, base) = if inside.len() == 1 {
+                                (inside[0], &outside)
+                            } else {
+                                (outside[0], &inside)
+                            };
+                            vec![[0, 1, 2].map(|i| (apex, base[i]))]
+                        }
+                        (2, 2) => {
+                            let quad = [
+                                (inside[0], outside[0]),
+                                (inside[0], outside[1]),
+                                (inside[1], outside[1]),
+                                (inside[1], outside[0]),
+                            ];
+                            vec![[quad[0], quad[1], quad[2]], [quad[0], quad[2], quad[3]]]
+                        }
+                        _ => continue,
+                    };
+
+                    let center =
+                        |x: &[[i64; 3]]| x.iter().map(|x| point(*x)).sum::<Vec3>() / x.len() as f32;
+                    let outward = center(&outside) - center(&inside);
+                    for triangle in triangles {
+                        let [a, b, c] = triangle.map(|(a, b)| point(a).lerp(point(b), 0.5));
+                        let face = triangle.map(|(a, b)| vertex(a, b));
+                        if (b - a).cross(c - a).dot(outward) < 0.0 {
+                            faces.push([face[0], face[2], face[1]]);
+                        } else {
+                            faces.push(face);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Mesh::new(vertices, faces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sphere() {
+        let n = 16;
+        let center = Vec3::splat(7.5);
+        let values = (0..n * n * n)
+            .map(|i| {
+                let p = Vec3::new((i % n) as f32, (i / n % n) as f32, (i / (n * n)) as f32);
+                5.0 - p.distance(center)
+            })
+            .collect::<Vec<_>>();
+        let mesh = isosurface([n; 3], &values, 0.0, Mat4::IDENTITY);
+        assert!(!mesh.faces.is_empty());
+        for v in &mesh.vertices {
+            assert!((v.distance(center) - 5.0).abs() < 0.3);
+        }
+
+        // Closed, with every face towards the lower values
+        let mut edges = HashMap::new();
+        for face in &mesh.faces {
+            for k in 0..3 {
+                let (a, b) = (face[k], face[(k + 1) % 3]);
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+            let [a, b, c] = mesh.triangle(*face);
+            let normal = mesh.face_normal(*face);
+            assert!(normal.dot((a + b + c) / 3.0 - center) > 0.0);
+        }
+        assert!(edges.values().all(|x| *x == 2));
+    }
+}
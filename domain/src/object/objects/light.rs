@@ -0,0 +1,136 @@
+use egui::Color32;
+use glam::{Mat4, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::object::objects::Sun;
+use crate::visitor::raster::color32_to_vec4;
+use crate::visitor::{Visitable, Visitor};
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum LightKind {
+    /// Infinitely far light shining along the given direction
+    Directional(Vec3),
+    /// Light at the given position, fading with the squared distance
+    Point(Vec3),
+}
+
+/// Light source shading the clouds and meshes
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct Light {
+    pub kind: LightKind,
+    pub color: Color32,
+    pub intensity: f32,
+    /// Multiplier of the cloud absorption along the rays of this light
+    pub absorption: f32,
+}
+
+impl Default for Light {
+    fn default() -> Self {
+        Self::directional(Vec3::NEG_Y)
+    }
+}
+
+impl Light {
+    pub fn directional(direction: Vec3) -> Self {
+        Self {
+            kind: LightKind::Directional(direction.normalize_or_zero()),
+            color: Color32::WHITE,
+            intensity: 1.0,
+            absorption: 1.0,
+        }
+    }
+
+    pub fn point(position: Vec3) -> Self {
+        Self {
+            kind: LightKind::Point(position),
+            color: Color32::WHITE,
+            intensity: 1.0,
+            absorption: 1.0,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color32) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    pub fn with_absorption(mut self, absorption: f32) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    /// Returns the light moved by the given matrix
+    pub fn transformed(&self, matrix: Mat4) -> Self {
+        let kind = match self.kind {
+            LightKind::Directional(direction) => {
+                LightKind::Directional(matrix.transform_vector3(direction).normalize_or_zero())
+            }
+            LightKind::Point(position) => LightKind::Point(matrix.transform_point3(position)),
+        };
+        Self { kind, ..*self }
+    }
+
+    /// Normalized direction from the given point towards the light
+    #[inline]
+    pub fn dir_to_light(&self, p: Vec3) -> Vec3 {
+        match self.kind {
+            LightKind::Directional(direction) => -direction,
+            LightKind::Point(position) => (position - p).normalize_or_zero(),
+        }
+    }
+
+    /// Color and intensity of the light reaching the given point
+    #[inline]
+    pub fn radiance(&self, p: Vec3) -> Vec3 {
+        let falloff = match self.kind {
+            LightKind::Directional(_) => 1.0,
+            LightKind::Point(position) => 1.0 / (1.0 + position.distance_squared(p)),
+        };
+        color32_to_vec4(self.color).truncate() * self.intensity * falloff
+    }
+
+    /// Whether the light gives no radiance anywhere
+    pub fn is_dark(&self) -> bool {
+        self.intensity <= 0.0 || color32_to_vec4(self.color).truncate() == Vec3::ZERO
+    }
+}
+
+impl From<&Sun> for Light {
+    /// Directional light of the sun color shining towards the origin
+    fn from(sun: &Sun) -> Self {
+        Light::directional(-sun.get_pos())
+            .with_color(sun.color)
+            .with_intensity(sun.intensity)
+    }
+}
+
+impl Visitable for Light {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_light(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light_radiance() {
+        let sun = Light::directional(Vec3::new(0.0, -2.0, 0.0)).with_intensity(2.0);
+        assert_eq!(sun.dir_to_light(Vec3::ZERO), Vec3::Y);
+        assert_eq!(sun.radiance(Vec3::splat(100.0)), Vec3::splat(2.0));
+
+        let lamp = Light::point(Vec3::Y).with_color(Color32::RED);
+        assert_eq!(lamp.dir_to_light(Vec3::ZERO), Vec3::Y);
+        assert_eq!(lamp.radiance(Vec3::ZERO), Vec3::new(0.5, 0.0, 0.0));
+        assert!(lamp.radiance(Vec3::splat(10.0)).x < 0.01);
+        assert!(!lamp.is_dark());
+        assert!(lamp.with_intensity(0.0).is_dark());
+        assert!(lamp.with_color(Color32::BLACK).is_dark());
+    }
+}
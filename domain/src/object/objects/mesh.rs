@@ -0,0 +1,245 @@
+use std::f32::consts::{PI, TAU};
+
+use egui::Color32;
+use glam::{Vec3, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
+
+use crate::object::objects::{BoundingBox, Light};
+use crate::visitor::raster::color32_to_vec4;
+use crate::visitor::{Visitable, Visitor};
+
+/// Triangle mesh drawn with flat shading
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Mesh {
+    pub vertices: Vec<Vec3>,
+    /// Counter-clockwise triangles as indices into [`Self::vertices`]
+    pub faces: Vec<[u32; 3]>,
+    pub color: Color32,
+}
+
+impl Default for Mesh {
+    fn default() -> Self {
+        Self {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+            color: Color32::LIGHT_GRAY,
+        }
+    }
+}
+
+impl Mesh {
+    pub fn new(vertices: Vec<Vec3>, faces: Vec<[u32; 3]>) -> Self {
+        Self {
+            vertices,
+            faces,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_color(mut self, color: Color32) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Moves the mesh by the given offset
+    pub fn with_offset(mut self, offset: Vec3) -> Self {
+        self.translate(offset);
+        self
+    }
+
+    /// UV sphere centered at the origin
+    pub fn sphere(radius: f32, segments: usize) -> Self {
+        let (segments, rings) = (segments.max(3), (segments / 2).max(2));
+        let mut vertices = Vec::with_capacity((rings + 1) * segments);
+        for i in 0..=rings {
+            let theta = PI * i as f32 / rings as f32;
+            for j in 0..segments {
+                let phi = TAU * j as f32 / segments as f32;
+                let (sin_t, cos_t) = theta.sin_cos();
+                let (sin_p, cos_p) = phi.sin_cos();
+                vertices.push(radius * Vec3::new(sin_t * cos_p, cos_t, sin_t * sin_p));
+            }
+        }
+
+        let idx = |i: usize, j: usize| (i * segments + j % segments) as u32;
+        let mut faces = Vec::with_capacity(2 * rings * segments);
+        for i in 0..rings {
+            for j in 0..segments {
+                let (a, b) = (idx(i, j), idx(i, j + 1));
+                let (c, d) = (idx(i + 1, j), idx(i + 1, j + 1));
+                if i != 0 {
+                    faces.push([a, b, c]);
+                }
+                if i + 1 != rings {
+                    faces.push([b, d, c]);
+                }
+            }
+        }
+        Self::new(vertices, faces)
+    }
+
+    /// Box with the given edge lengths centered at the origin
+    pub fn cuboid(size: Vec3) -> Self {
+        let h = size / 2.0;
+        let vertices = (0..8)
+            .map(|i| {
+                let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+                Vec3::new(sign(1), sign(2), sign(4)) * h
+            })
+            .collect();
+        let faces = vec![
+            [0, 4, 6],
+            [0, 6, 2],
+            [1, 3, 7],
+            [1, 7, 5],
+            [0, 1, 5],
+            [0, 5, 4],
+            [2, 6, 7],
+            [2, 7, 3],
+            [0, 2, 3],
+            [0, 3, 1],
+            [4, 5, 7],
+            [4, 7, 6],
+        ];
+        Self::new(vertices, faces)
+    }
+
+    /// Cylinder standing on the XZ plane with its axis along Y, the top
+    /// radius is scaled by `taper`
+    fn frustum(radius: f32, height: f32, taper: f32, segments: usize) -> Self {
+        let segments = segments.max(3);
+        let ring = |y: f32, r: f32| {
+            (0..segments).map(move |j| {
+                let (sin, cos) = (TAU * j as f32 / segments as f32).sin_cos();
+                Vec3::new(r * cos, y, r * sin)
+            })
+        };
+        let apex = taper == 0.0;
+        let mut vertices = ring(0.0, radius).collect::<Vec<_>>();
+        if apex {
+            vertices.push(Vec3::new(0.0, height, 0.0));
+        } else {
+            vertices.extend(ring(height, radius * taper));
+        }
+        let (bottom, top) = (vertices.len() as u32, vertices.len() as u32 + 1);
+        vertices.push(Vec3::ZERO);
+        vertices.push(Vec3::new(0.0, height, 0.0));
+
+        let n = segments as u32;
+        let mut faces = Vec::new();
+        for j in 0..n {
+            let k = (j + 1) % n;
+            faces.push([bottom, j, k]);
+            if apex {
+                faces.push([j, n, k]);
+            } else {
+                faces.push([j, n + j, k]);
+                faces.push([k, n + j, n + k]);
+                faces.push([top, n + k, n + j]);
+            }
+        }
+        Self::new(vertices, faces)
+    }
+
+    /// Cone standing on the XZ plane with its apex on the Y axis
+    pub fn cone(radius: f32, height: f32, segments: usize) -> Self {
+        Self::frustum(radius, height, 0.0, segments)
+    }
+
+    /// Cylinder standing on the XZ plane with its axis along Y
+    pub fn cylinder(radius: f32, height: f32, segments: usize) -> Self {
+        Self::frustum(radius, height, 1.0, segments)
+    }
+
+    /// Corners of the given face
+    #[inline]
+    pub fn triangle(&self, face: [u32; 3]) -> [Vec3; 3] {
+        face.map(|i| self.vertices[i as usize])
+    }
+
+    pub fn face_normal(&self, face: [u32; 3]) -> Vec3 {
+        let [a, b, c] = self.triangle(face);
+        (b - a).cross(c - a).normalize_or_zero()
+    }
+
+    /// Returns every face with a flat diffuse shade under the given lights.
+    /// Faces are lit from both sides, since the winding of imported models
+    /// can't be relied on.
+    pub fn shaded_triangles(&self, lights: &[Light]) -> Vec<([Vec3; 3], Color32)> {
+        self.faces
+            .iter()
+            .map(|&face| {
+                let triangle = self.triangle(face);
+                let center = (triangle[0] + triangle[1] + triangle[2]) / 3.0;
+                let normal = self.face_normal(face);
+                let diffuse = lights
+                    .iter()
+                    .map(|x| normal.dot(x.dir_to_light(center)).abs() * x.radiance(center))
+                    .sum::<Vec3>();
+                let albedo = color32_to_vec4(self.color).xyz();
+                let shade = albedo * (0.35 + 0.65 * diffuse);
+                let (r, g, b) = (shade.clamp(Vec3::ZERO, Vec3::ONE) * 255.0).into();
+                (triangle, Color32::from_rgb(r as u8, g as u8, b as u8))
+            })
+            .collect()
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        let Some(&first) = self.vertices.first() else {
+            return BoundingBox::default();
+        };
+        let (min, max) = self
+            .vertices
+            .iter()
+            .fold((first, first), |(min, max), &v| (min.min(v), max.max(v)));
+        BoundingBox::from_two_pos(min, max)
+    }
+
+    pub fn translate(&mut self, offset: Vec3) {
+        self.vertices.iter_mut().for_each(|v| *v += offset);
+    }
+
+    /// Scales the mesh relative to the origin
+    pub fn scale(&mut self, factor: Vec3) {
+        self.vertices.iter_mut().for_each(|v| *v *= factor);
+    }
+}
+
+impl Visitable for Mesh {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_mesh(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mesh_primitives() {
+        let cube = Mesh::cuboid(Vec3::new(2.0, 4.0, 6.0));
+        assert_eq!(cube.faces.len(), 12);
+        assert_eq!(cube.bounding_box().size(), Vec3::new(2.0, 4.0, 6.0));
+        assert_eq!(cube.bounding_box().center(), Vec3::ZERO);
+
+        let sphere = Mesh::sphere(2.0, 16);
+        assert!(sphere
+            .vertices
+            .iter()
+            .all(|v| (v.length() - 2.0).abs() < 1e-5));
+        assert!(sphere
+            .faces
+            .iter()
+            .all(|&f| sphere.face_normal(f) != Vec3::ZERO));
+
+        for mesh in [Mesh::cone(1.0, 2.0, 8), Mesh::cylinder(1.0, 2.0, 8)] {
+            let bb = mesh.bounding_box();
+            assert!((bb.min.y, bb.max.y) == (0.0, 2.0));
+            assert!(mesh
+                .faces
+                .iter()
+                .flatten()
+                .all(|&i| (i as usize) < mesh.vertices.len()));
+        }
+    }
+}
@@ -1,13 +1,40 @@
+pub use background::Background;
 pub use bounding_box::BoundingBox;
+pub use bounding_sphere::BoundingSphere;
 pub use cloud::Cloud;
-pub use grid::Grid;
+pub use density_field::DensityField;
+pub use fog::Fog;
+pub use gizmo::OrientationGizmo;
+pub use grid::{Grid, GridPlane};
+pub use height_map::HeightMap;
+pub use light::{Light, LightKind};
+pub use mesh::Mesh;
+pub use obb::Obb;
+pub use occupancy_grid::OccupancyGrid;
+pub use skybox::Skybox;
 pub use sun::Sun;
 pub use terrain::Terrain;
+pub use transform_gizmo::{GizmoDrag, GizmoMode, TransformGizmo};
 pub use textures::texture3d;
+pub use water::Water;
 
+pub mod background;
 pub mod bounding_box;
+pub mod bounding_sphere;
 pub mod cloud;
+pub mod density_field;
+pub mod fog;
+pub mod gizmo;
 pub mod grid;
+pub mod height_map;
+pub mod isosurface;
+pub mod light;
+pub mod mesh;
+pub mod obb;
+pub mod occupancy_grid;
+pub mod skybox;
 pub mod sun;
 pub mod terrain;
 pub mod textures;
+pub mod transform_gizmo;
+pub mod water;
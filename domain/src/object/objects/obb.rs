@@ -0,0 +1,95 @@
+use glam::{Mat4, Quat, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::object::objects::BoundingBox;
+
+/// Oriented bounding box, a box turned around its center
+#[derive(Debug, Default, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Obb {
+    pub center: Vec3,
+    /// Half of the edge lengths along the box axes
+    pub half_size: Vec3,
+    pub rotation: Quat,
+}
+
+impl Obb {
+    pub fn new(center: Vec3, half_size: Vec3, rotation: Quat) -> Self {
+        Self {
+            center,
+            half_size,
+            rotation,
+        }
+    }
+
+    /// Turns the axis aligned box around its center
+    pub fn from_box(bb: &BoundingBox, rotation: Quat) -> Self {
+        Self::new(bb.center(), 0.5 * bb.size(), rotation)
+    }
+
+    /// Box in its own space, centered at the origin
+    pub fn local_box(&self) -> BoundingBox {
+        BoundingBox::from_two_pos(-self.half_size, self.half_size)
+    }
+
+    /// Matrix from the box space to the world
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_rotation_translation(self.rotation, self.center)
+    }
+
+    #[inline]
+    pub fn to_local(&self, position: Vec3) -> Vec3 {
+        self.rotation.inverse() * (position - self.center)
+    }
+
+    #[inline]
+    pub fn contains(&self, position: Vec3) -> bool {
+        self.local_box().contains(self.to_local(position))
+    }
+
+    /// Distance along the ray to the box and the distance travelled inside
+    /// it, like [`BoundingBox::dst`]
+    pub fn dst(&self, ray_origin: Vec3, ray_dir: Vec3) -> Vec2 {
+        let dir = self.rotation.inverse() * ray_dir;
+        self.local_box().dst(self.to_local(ray_origin), dir)
+    }
+
+    pub fn corners(&self) -> [Vec3; 8] {
+        self.local_box()
+            .corners()
+            .map(|x| self.center + self.rotation * x)
+    }
+
+    /// Axis aligned box enclosing this one
+    pub fn aabb(&self) -> BoundingBox {
+        self.local_box().transformed(self.matrix())
+    }
+}
+
+impl From<BoundingBox> for Obb {
+    fn from(value: BoundingBox) -> Self {
+        Self::from_box(&value, Quat::IDENTITY)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_obb() {
+        let bb = BoundingBox::from_two_pos(Vec3::new(-2.0, -0.5, -0.5), Vec3::new(2.0, 0.5, 0.5));
+        let obb = Obb::from_box(&bb, Quat::from_rotation_z(std::f32::consts::FRAC_PI_2));
+
+        assert!(obb.contains(Vec3::new(0.0, 1.5, 0.0)));
+        assert!(!obb.contains(Vec3::new(1.5, 0.0, 0.0)));
+
+        let dst = obb.dst(Vec3::new(0.0, 5.0, 0.0), Vec3::NEG_Y);
+        assert!(dst.abs_diff_eq(Vec2::new(3.0, 4.0), 1e-5));
+        let dst = obb.dst(Vec3::new(5.0, 0.0, 0.0), Vec3::NEG_X);
+        assert!(dst.abs_diff_eq(Vec2::new(4.5, 1.0), 1e-5));
+
+        let aabb = obb.aabb();
+        assert!(aabb.size().abs_diff_eq(Vec3::new(1.0, 4.0, 1.0), 1e-5));
+        assert!(obb.corners().iter().all(|&x| aabb.contains(x * 0.999)));
+    }
+}
@@ -0,0 +1,219 @@
+//! Coarse map of where a cloud has any density
+
+use glam::{UVec3, Vec3};
+use rayon::prelude::*;
+
+use super::{BoundingBox, Cloud};
+
+/// Bricks along each side of the grid a cloud is marched with
+pub const OCCUPANCY_GRID_SIZE: usize = 16;
+/// Density samples along each side of a brick
+const BRICK_SAMPLES: usize = 3;
+/// Density the march starts to collect light at, see [`Cloud::march`]
+const EMPTY_DENSITY: f32 = 0.1;
+
+/// Largest density of each brick of the cloud volume, so the march can step
+/// over the empty bricks at once.
+///
+/// The grid is not strictly conservative. The largest density is taken over
+/// a few samples per brick and spread over the neighbours, so a wisp thinner
+/// than a brick falling between the samples may be stepped over. A true
+/// bound would need the noise bounded per brick, which it is not, and the
+/// misses are rare enough to go unseen, see the tests.
+///
+/// The grid keeps the offset of the cloud it was sampled at and is read
+/// shifted by the drift of the noise since, so the wind does not rebuild it
+/// every frame, see [`Self::is_current`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct OccupancyGrid {
+    /// Volume of the cloud, in the space the cloud is marched in
+    bounds: BoundingBox,
+    /// Offset of the cloud when sampled
+    offset: Vec3,
+    size: usize,
+    max_density: Vec<f32>,
+}
+
+impl OccupancyGrid {
+    /// Samples the density of the cloud in `size` bricks along each side
+    pub fn new(cloud: &Cloud, size: usize) -> Self {
+        let size = size.max(1);
+        let bounds = *cloud.bounding_box();
+        let brick = bounds.size() / size as f32;
+        let sampled = (0..size * size * size)
+            .into_par_iter()
+            .map(|i| {
+                let min = bounds.min + Self::coords(i, size).as_vec3() * brick;
+                let mut max = f32::NEG_INFINITY;
+                for s in 0..BRICK_SAMPLES.pow(3) {
+                    let t = Self::coords(s, BRICK_SAMPLES).as_vec3() / (BRICK_SAMPLES - 1) as f32;
+                    max = max.max(cloud.sample_density(min + t * brick));
+                }
+                max
+            })
+            .collect::<Vec<_>>();
+
+        // Spreads every brick over its neighbours
+        let max_density = (0..sampled.len())
+            .map(|i| {
+                let c = Self::coords(i, size).as_ivec3();
+                let mut max = f32::NEG_INFINITY;
+                for n in 0..27 {
+                    let n = c + Self::coords(n, 3).as_ivec3() - 1;
+                    if n.cmpge(glam::IVec3::ZERO).all()
+                        && n.cmplt(glam::IVec3::splat(size as i32)).all()
+                    {
+                        let n = n.as_uvec3();
+                        max = max.max(sampled[Self::index(n, size)]);
+                    }
+                }
+                max
+            })
+            .collect();
+        Self {
+            bounds,
+            offset: cloud.offset,
+            size,
+            max_density,
+        }
+    }
+
+    /// Shift of the points of the cloud to the point of the grid with the
+    /// same density, see [`Cloud::noise_shift`]
+    pub fn shift(&self, cloud: &Cloud) -> Vec3 {
+        cloud.noise_shift(self.offset)
+    }
+
+    /// Whether the noise of the cloud has drifted less than a brick since
+    /// the grid was sampled. Further on the fixed height gradient and edges
+    /// of the volume no longer match the shifted grid.
+    pub fn is_current(&self, cloud: &Cloud) -> bool {
+        let brick = self.bounds.size() / self.size as f32;
+        self.shift(cloud).abs().cmplt(brick).all()
+    }
+
+    fn coords(index: usize, size: usize) -> UVec3 {
+        UVec3::new(
+            (index % size) as u32,
+            (index / size % size) as u32,
+            (index / (size * size)) as u32,
+        )
+    }
+
+    fn index(c: UVec3, size: usize) -> usize {
+        (c.z as usize * size + c.y as usize) * size + c.x as usize
+    }
+
+    /// Brick of the point inside the volume
+    fn brick(&self, p: Vec3) -> Option<UVec3> {
+        let t = (p - self.bounds.min) / self.bounds.size() * self.size as f32;
+        if t.cmplt(Vec3::ZERO).any() || t.is_nan() {
+            return None;
+        }
+        let c = t.as_uvec3().min(UVec3::splat(self.size as u32 - 1));
+        Some(c)
+    }
+
+    /// Whether the brick of the point holds no density worth marching. The
+    /// point is in the grid, shifted by [`Self::shift`].
+    pub fn is_empty(&self, p: Vec3) -> bool {
+        self.brick(p)
+            .is_some_and(|c| self.max_density[Self::index(c, self.size)] <= EMPTY_DENSITY)
+    }
+
+    /// Box of the occupied bricks in the grid, `None` when every brick is
+    /// empty. The rays missing it collect no light.
+    pub fn occupied_bounds(&self) -> Option<BoundingBox> {
+        let (min, max) = (0..self.max_density.len())
+            .filter(|&i| self.max_density[i] > EMPTY_DENSITY)
+            .map(|i| Self::coords(i, self.size))
+            .fold(None, |bounds, c| match bounds {
+                Some((min, max)) => Some((c.min(min), c.max(max))),
+                None => Some((c, c)),
+            })?;
+        let brick = self.bounds.size() / self.size as f32;
+        Some(BoundingBox::from_two_pos(
+            self.bounds.min + min.as_vec3() * brick,
+            self.bounds.min + (max + 1).as_vec3() * brick,
+        ))
+    }
+
+    /// Distance along the ray from the point to where it leaves the brick of
+    /// the point
+    pub fn brick_exit(&self, p: Vec3, dir: Vec3) -> f32 {
+        let Some(c) = self.brick(p) else {
+            return 0.0;
+        };
+        let brick = self.bounds.size() / self.size as f32;
+        let min = self.bounds.min + c.as_vec3() * brick;
+        BoundingBox::from_two_pos(min, min + brick).dst(p, dir).y
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::tests::dense_cloud;
+    use crate::object::objects::Light;
+
+    #[test]
+    fn test_empty_space_skipping() {
+        let mut cloud = dense_cloud();
+        cloud.density_offset = -10.0;
+        let grid = OccupancyGrid::new(&cloud, 8);
+        assert!(grid.max_density.iter().any(|x| *x > EMPTY_DENSITY));
+        assert!(grid.max_density.iter().any(|x| *x <= EMPTY_DENSITY));
+        let occupied = grid.occupied_bounds().unwrap();
+        assert!(cloud.bounding_box().contains(occupied.min));
+        assert!(cloud.bounding_box().contains(occupied.max));
+
+        let lights = [Light::directional(Vec3::Y)];
+        let uniforms = cloud.uniforms(&lights);
+        let skipping = uniforms.clone().with_occupancy(Some(grid.into()));
+        let mut visible = 0;
+        for x in [-0.8, -0.3, 0.2, 0.7] {
+            let (origin, dir) = (Vec3::new(x, -0.5, 3.0), Vec3::new(0.05, 0.2, -1.0));
+            let full = cloud.march_with(&uniforms, origin, dir);
+            visible += usize::from(full.a() > 0);
+            let skipped = cloud.march_with(&skipping, origin, dir);
+            for (a, b) in full.to_array().into_iter().zip(skipped.to_array()) {
+                assert!(a.abs_diff(b) <= 4, "{full:?} {skipped:?}");
+            }
+        }
+        assert!(visible > 0);
+    }
+
+    /// Share of the dense points of the cloud in bricks taken as empty
+    fn missed(grid: &OccupancyGrid, cloud: &Cloud) -> f32 {
+        let shift = grid.shift(cloud);
+        let (mut dense, mut missed) = (0, 0);
+        let n = 40;
+        for i in 0..n * n * n {
+            let t = (UVec3::new(i % n, i / n % n, i / (n * n)).as_vec3() + 0.5) / n as f32;
+            let p = cloud.bounding_box().min + t * cloud.bounding_box().size();
+            if cloud.sample_density(p) > EMPTY_DENSITY {
+                dense += 1;
+                missed += usize::from(grid.is_empty(p + shift));
+            }
+        }
+        assert!(dense > 0);
+        missed as f32 / dense as f32
+    }
+
+    #[test]
+    fn test_sampled_bound() {
+        let mut cloud = dense_cloud();
+        cloud.density_offset = -10.0;
+        let grid = OccupancyGrid::new(&cloud, 8);
+        assert!(missed(&grid, &cloud) < 0.01);
+
+        // The wind moves the noise by less than a brick
+        cloud.offset += Vec3::new(2.0, 0.0, 1.0);
+        assert_ne!(grid.shift(&cloud), Vec3::ZERO);
+        assert!(grid.is_current(&cloud));
+        assert!(missed(&grid, &cloud) < 0.01);
+
+        cloud.offset += Vec3::X * 100.0;
+        assert!(!grid.is_current(&cloud));
+    }
+}
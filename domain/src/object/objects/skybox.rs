@@ -0,0 +1,132 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use egui::{Color32, ColorImage};
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::object::camera::Camera;
+use crate::visitor::{Visitable, Visitor};
+
+/// Environment drawn behind every other object, looked up by view direction
+/// so that it turns together with the camera
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub enum Skybox {
+    /// Gradient over the view elevation
+    Dome {
+        zenith: Color32,
+        horizon: Color32,
+        ground: Color32,
+    },
+    /// Equirectangular panorama
+    Panorama(Arc<ColorImage>),
+}
+
+impl Default for Skybox {
+    fn default() -> Self {
+        Skybox::Dome {
+            zenith: Color32::from_rgb(51, 102, 204),
+            horizon: Color32::from_rgb(200, 220, 240),
+            ground: Color32::from_rgb(90, 90, 100),
+        }
+    }
+}
+
+impl Skybox {
+    /// Loads an equirectangular panorama from an image file
+    pub fn from_file(path: impl AsRef<Path>) -> image::ImageResult<Self> {
+        Ok(Skybox::Panorama(Arc::new(Self::load_image(path)?)))
+    }
+
+    /// Reads the image of a panorama
+    pub fn load_image(path: impl AsRef<Path>) -> image::ImageResult<ColorImage> {
+        let img = image::open(path)?.into_rgba8();
+        let size = [img.width() as usize, img.height() as usize];
+        Ok(ColorImage::from_rgba_unmultiplied(size, img.as_raw()))
+    }
+
+    /// Color seen in the given normalized direction
+    pub fn sample(&self, dir: Vec3) -> Color32 {
+        match self {
+            Skybox::Dome {
+                zenith,
+                horizon,
+                ground,
+            } => {
+                if dir.y >= 0.0 {
+                    horizon.lerp_to_gamma(*zenith, dir.y.sqrt())
+                } else {
+                    horizon.lerp_to_gamma(*ground, (-dir.y).sqrt())
+                }
+            }
+            Skybox::Panorama(img) => {
+                let [w, h] = img.size;
+                if w == 0 || h == 0 {
+                    return Color32::TRANSPARENT;
+                }
+                let u = dir.z.atan2(dir.x) / std::f32::consts::TAU + 0.5;
+                let v = dir.y.clamp(-1.0, 1.0).acos() / std::f32::consts::PI;
+                let x = ((u * w as f32) as usize).min(w - 1);
+                let y = ((v * h as f32) as usize).min(h - 1);
+                img[(x, y)]
+            }
+        }
+    }
+
+    /// Renders the skybox as seen by the camera into an image of the given size
+    pub fn render(&self, camera: &Camera, [width, height]: [usize; 2]) -> ColorImage {
+        use rayon::prelude::*;
+
+        let mut img = ColorImage::new([width, height], Color32::TRANSPARENT);
+        if width == 0 || height == 0 {
+            return img;
+        }
+
+        // Points of constant depth unproject onto a plane, so the ray
+        // directions can be interpolated between the corners
+        let eye = camera.pos();
+        let (last_i, last_j) = (height - 1, width - 1);
+        let corner = |i, j| camera.egui_to_world(i, j, width, height) - eye;
+        let (top_left, top_right) = (corner(0, 0), corner(0, last_j));
+        let (bottom_left, bottom_right) = (corner(last_i, 0), corner(last_i, last_j));
+
+        img.pixels
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(idx, pixel)| {
+                let u = (idx % width) as f32 / last_j.max(1) as f32;
+                let v = (idx / width) as f32 / last_i.max(1) as f32;
+                let top = top_left.lerp(top_right, u);
+                let bottom = bottom_left.lerp(bottom_right, u);
+                *pixel = self.sample(top.lerp(bottom, v).normalize());
+            });
+        img
+    }
+}
+
+impl Visitable for Skybox {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_skybox(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_skybox_dome() {
+        let Skybox::Dome {
+            zenith,
+            horizon,
+            ground,
+        } = Skybox::default()
+        else {
+            unreachable!()
+        };
+        let sky = Skybox::default();
+        assert_eq!(sky.sample(Vec3::Y), zenith);
+        assert_eq!(sky.sample(Vec3::X), horizon);
+        assert_eq!(sky.sample(Vec3::NEG_Y), ground);
+    }
+}
@@ -1,19 +1,117 @@
-use glam::{Vec3, Vec4, Vec4Swizzles};
+use egui::Color32;
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
 
 use crate::visitor::{Visitable, Visitor};
 
-#[derive(Debug, Default, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Sun {
     pos: Vec4,
     pub a: f32,
     pub z: f32,
     pub d: f32,
+    /// Color of the sunlight, see [`Sun::with_temperature`]
+    #[serde(default = "default_color")]
+    pub color: Color32,
+    #[serde(default = "default_intensity")]
+    pub intensity: f32,
+    /// Seconds of a full turn along the day cycle, the sun stands still at
+    /// zero
+    #[serde(default)]
+    pub day_length: f32,
 }
 
+impl Default for Sun {
+    fn default() -> Self {
+        Self {
+            pos: Vec4::ZERO,
+            a: 0.0,
+            z: 0.0,
+            d: 0.0,
+            color: default_color(),
+            intensity: default_intensity(),
+            day_length: 0.0,
+        }
+    }
+}
+
+fn default_color() -> Color32 {
+    Color32::WHITE
+}
+
+fn default_intensity() -> f32 {
+    1.0
+}
+
+/// Radii of the halo rings around the sun disc relative to the disc radius
+/// and their opacities, from the outermost ring to the disc
+const GLOW: [(f32, f32); 4] = [(4.0, 0.08), (2.5, 0.15), (1.6, 0.35), (1.0, 1.0)];
+
 impl Sun {
     pub fn new(d: f32, a: f32, z: f32) -> Self {
         let pos = Vec4::new(-1.0, 0.0, 0.0, 0.0);
-        Self { pos, a, z, d }
+        Self {
+            pos,
+            a,
+            z,
+            d,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_color(mut self, color: Color32) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Sets the color of a black body of the given temperature in kelvins
+    pub fn with_temperature(mut self, kelvin: f32) -> Self {
+        self.color = Self::temperature_color(kelvin);
+        self
+    }
+
+    pub fn with_intensity(mut self, intensity: f32) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Approximate color of a black body of the given temperature in
+    /// kelvins, fitted for `1000..=40000`
+    pub fn temperature_color(kelvin: f32) -> Color32 {
+        let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+        let r = if t <= 66.0 {
+            255.0
+        } else {
+            329.699 * (t - 60.0).powf(-0.133_204_76)
+        };
+        let g = if t <= 66.0 {
+            99.470_8 * t.ln() - 161.119_57
+        } else {
+            288.122_16 * (t - 60.0).powf(-0.075_514_85)
+        };
+        let b = if t >= 66.0 {
+            255.0
+        } else if t <= 19.0 {
+            0.0
+        } else {
+            138.517_73 * (t - 10.0).ln() - 305.044_8
+        };
+        let channel = |x: f32| x.clamp(0.0, 255.0) as u8;
+        Color32::from_rgb(channel(r), channel(g), channel(b))
+    }
+
+    pub fn with_day_length(mut self, day_length: f32) -> Self {
+        self.day_length = day_length;
+        self
+    }
+
+    /// Turns the sun along its day cycle, the elevation is kept within
+    /// `-180..180` degrees
+    pub fn advance(&mut self, dt: f32) {
+        if self.day_length > 0.0 {
+            let a = self.a - 360.0 * dt / self.day_length;
+            self.a = (a + 180.0).rem_euclid(360.0) - 180.0;
+        }
     }
 
     #[inline]
@@ -23,6 +121,19 @@ impl Sun {
         (mat * self.pos).xyz()
     }
 
+    /// Sun standing at its position moved by the matrix, e.g. the world
+    /// matrix of the sun object
+    pub fn transformed(&self, matrix: Mat4) -> Self {
+        let pos = matrix.transform_point3(self.get_pos());
+        Self {
+            pos: pos.normalize_or_zero().extend(0.0),
+            a: 0.0,
+            z: 0.0,
+            d: pos.length(),
+            ..*self
+        }
+    }
+
     pub fn prepend_angle(&mut self, a: glam::Vec2) {
         self.a = a.x;
         self.z = a.y;
@@ -32,10 +143,46 @@ impl Sun {
     pub fn set_d(&mut self, d: f32) {
         self.d = d
     }
+
+    /// Circles the glowing sun is painted with, as radii relative to the
+    /// disc radius and colors, from the outermost one. `visibility` is the
+    /// share of the sunlight that reaches the eye.
+    pub fn glow(color: Color32, visibility: f32) -> impl Iterator<Item = (f32, Color32)> {
+        GLOW.into_iter()
+            .map(move |(radius, alpha)| (radius, color.gamma_multiply(alpha * visibility)))
+    }
 }
 
 impl Visitable for Sun {
-    fn accept(&self, visitor: &mut impl Visitor) {
-        visitor.visit_sun(self);
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_sun(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_temperature_color() {
+        let warm = Sun::temperature_color(2000.0);
+        let daylight = Sun::temperature_color(6600.0);
+        let cold = Sun::temperature_color(15000.0);
+        assert_eq!(warm.r(), 255);
+        assert!(warm.b() < warm.g() && warm.g() < warm.r());
+        assert!(daylight.r() > 250 && daylight.g() > 240 && daylight.b() > 240);
+        assert!(cold.b() == 255 && cold.r() < cold.b());
+    }
+
+    #[test]
+    fn test_transformed() {
+        let sun = Sun::new(10.0, -45.0, 30.0);
+        let matrix = Mat4::from_rotation_x(0.5) * Mat4::from_translation(Vec3::Y);
+        let moved = sun.transformed(matrix);
+        assert!(moved
+            .get_pos()
+            .abs_diff_eq(matrix.transform_point3(sun.get_pos()), 1e-4));
+        assert_eq!(moved.color, sun.color);
+        assert_eq!(sun.transformed(Mat4::IDENTITY).get_pos(), sun.get_pos());
     }
 }
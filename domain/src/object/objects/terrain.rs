@@ -3,14 +3,16 @@
 use egui::Color32;
 use glam::Vec3;
 use rayon::iter::IntoParallelIterator;
+use serde::{Deserialize, Serialize};
 use std::ops::{Deref, DerefMut};
+use std::sync::Arc;
 
 use crate::object::objects::texture3d::{INoise, INoiseBuilder, Noise, NoiseBuilder, Perlin, PerlinBuilder};
 use crate::object::objects::textures::texture3d::{Worley, WorleyBuilder};
-use crate::object::objects::BoundingBox;
+use crate::object::objects::{BoundingBox, HeightMap};
 use crate::visitor::{Visitable, Visitor};
 
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct TerrainBuilder {
     pub bounding_box: BoundingBox,
     pub scale: usize,
@@ -129,6 +131,8 @@ pub struct Terrain {
     pub terrain_builder: TerrainBuilder,
     pub triangles: Vec<(TriangleMesh, (Vec3, Vec3, Vec3))>,
     perlin: Noise,
+    /// Replaces the noise as the source of heights when set
+    height_map: Option<Arc<HeightMap>>,
 }
 
 impl Deref for Terrain {
@@ -157,6 +161,7 @@ impl Terrain {
             terrain_builder,
             triangles: vec![],
             perlin,
+            height_map: None,
         };
 
         res.generate_grid();
@@ -167,6 +172,31 @@ impl Terrain {
         self.perlin = worley_builder.build();
     }
 
+    pub fn height_map(&self) -> Option<&HeightMap> {
+        self.height_map.as_deref()
+    }
+
+    /// Uses the height map instead of the noise, or goes back to the noise
+    /// when `None` is given. The grid has to be regenerated afterwards.
+    pub fn set_height_map(&mut self, height_map: Option<HeightMap>) {
+        self.height_map = height_map.map(Arc::new);
+    }
+
+    /// Resizes the terrain footprint around its center
+    pub fn set_size(&mut self, size: glam::Vec2) {
+        let bb = &mut self.terrain_builder.bounding_box;
+        let center = bb.center();
+        let half = Vec3::new(size.x, 0.0, size.y) * 0.5;
+        bb.min = Vec3::new(center.x - half.x, bb.min.y, center.z - half.z);
+        bb.max = Vec3::new(center.x + half.x, bb.max.y, center.z + half.z);
+    }
+
+    /// Sets the height of the highest possible point above the terrain base
+    pub fn set_height(&mut self, height: f32) {
+        let bb = &mut self.terrain_builder.bounding_box;
+        bb.max.y = bb.min.y + height.max(0.0);
+    }
+
     pub fn sample_height(&self, x: f32, z: f32) -> Vec3 {
         let bb = self.bounding_box;
         let sample_pos = bb.min + Vec3::new(x, self.noise_weight.x, z) / bb.size();
@@ -190,6 +220,19 @@ impl Terrain {
         let sample_y = self.noise_weight.x;
         let sample_z = self.noise_weight.y;
         let noise = &self.perlin;
+        let height_map = self.height_map.as_deref();
+        let sample = |base_x: f32, base_z: f32| match height_map {
+            Some(map) => map.sample(
+                (base_x - min.x) / (max.x - min.x),
+                (base_z - min.z) / (max.z - min.z),
+            ),
+            None => {
+                let sample_pos = bb.min
+                    + Vec3::new(base_x, sample_y, base_z)
+                        / Vec3::new(bb.size().x, 1.0, bb.size().z);
+                noise.sample_level(sample_pos).x
+            }
+        };
 
         let vec: Vec<_> = (0..self.scale)
             .into_par_iter()
@@ -205,18 +248,10 @@ impl Terrain {
                         let base_z = min.z + (max.z - min.z) * z_frac;
                         let next_z = min.z + (max.z - min.z) * next_z_frac;
 
-                        let sample_pos = bb.min
-                            + Vec3::new(base_x, sample_y, base_z)
-                                / Vec3::new(bb.size().x, 1.0, bb.size().z);
-                        let sample_pos2 = bb.min
-                            + Vec3::new(base_x, sample_y, next_z)
-                                / Vec3::new(bb.size().x, 1.0, bb.size().z);
-                        
                         let worley_height =
-                            bb.min.y + noise.sample_level(sample_pos).x * (bb.max.y - bb.min.y);
-                        
+                            bb.min.y + sample(base_x, base_z) * (bb.max.y - bb.min.y);
                         let worley_height2 =
-                            bb.min.y + noise.sample_level(sample_pos2).x * (bb.max.y - bb.min.y);
+                            bb.min.y + sample(base_x, next_z) * (bb.max.y - bb.min.y);
 
                         let vec = Vec3::new(base_x, worley_height, base_z);
                         let vec2 = Vec3::new(base_x, worley_height2, next_z);
@@ -238,8 +273,9 @@ impl Terrain {
                 if i == self.scale * 2 - 1
                     || i == self.scale * 2 - 2
                     || i >= 2 * self.scale
-                        && ((i - 2 * (i / (2 * self.scale) - 1)) % (self.scale * 2) != 0
-                            && ((i - 1) - 2 * (i / (2 * self.scale) - 1)) % (self.scale * 2) != 0)
+                        && (!(i - 2 * (i / (2 * self.scale) - 1)).is_multiple_of(self.scale * 2)
+                            && !((i - 1) - 2 * (i / (2 * self.scale) - 1))
+                                .is_multiple_of(self.scale * 2))
                 {
                     Some(TriangleMesh::new(
                         if i % 2 == 0 { v2 } else { v0 },
@@ -280,7 +316,7 @@ impl Terrain {
 }
 
 impl Visitable for Terrain {
-    fn accept(&self, visitor: &mut impl Visitor) {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
         visitor.visit_terrain(self)
     }
 }
@@ -0,0 +1,198 @@
+//! Noise generation in a wgpu compute shader
+
+use std::sync::OnceLock;
+
+use glam::Vec3;
+
+use super::texture3d::{PerlinBuilder, WorleyBuilder};
+use crate::canvas::gpu::{words_to_bytes, Gpu};
+
+/// Side of the workgroups of `noise.wgsl`
+const WORKGROUP_SIZE: u32 = 4;
+
+/// Pipelines generating the 3D noise on the [`Gpu`]. The shader computes the
+/// octave sum of every texel and the CPU composes the texels from them, so a
+/// volume of 128 texels a side takes milliseconds instead of seconds.
+#[derive(Debug)]
+pub struct GpuNoise {
+    gpu: &'static Gpu,
+    worley: wgpu::ComputePipeline,
+    perlin: wgpu::ComputePipeline,
+}
+
+impl GpuNoise {
+    pub fn new(gpu: &'static Gpu) -> Self {
+        let module = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("noise"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("noise.wgsl").into()),
+            });
+        Self {
+            gpu,
+            worley: gpu.pipeline(&module, None, "worley_main"),
+            perlin: gpu.pipeline(&module, None, "perlin_main"),
+        }
+    }
+
+    /// Pipelines on the shared device, `None` when there is no adapter
+    pub fn shared() -> Option<&'static Self> {
+        static SHARED: OnceLock<Option<GpuNoise>> = OnceLock::new();
+        SHARED.get_or_init(|| Gpu::shared().map(Self::new)).as_ref()
+    }
+
+    /// Octave sums of the Worley noise from the points of its three octaves
+    pub fn worley(&self, builder: &WorleyBuilder, points: &[Vec<Vec3>; 3]) -> Option<Vec<f32>> {
+        let points = points
+            .iter()
+            .flatten()
+            .flat_map(|p| [p.x, p.y, p.z, 0.0])
+            .collect::<Vec<_>>();
+        let params = [
+            builder.resolution as u32,
+            builder.num_points_a as u32,
+            builder.num_points_b as u32,
+            builder.num_points_c as u32,
+            builder.tile.to_bits(),
+            builder.persistence.to_bits(),
+            builder.invert_noise as u32,
+            0,
+        ];
+        self.run(&self.worley, params, Some(&points))
+    }
+
+    /// Octave sums of the Perlin noise
+    pub fn perlin(&self, builder: &PerlinBuilder) -> Option<Vec<f32>> {
+        let params = [
+            builder.resolution as u32,
+            builder.num_points_a as u32,
+            builder.num_points_b as u32,
+            builder.num_points_c as u32,
+            builder.tile.to_bits(),
+            builder.persistence.to_bits(),
+            builder.invert_noise as u32,
+            0,
+        ];
+        self.run(&self.perlin, params, None)
+    }
+
+    /// Runs the pipeline over the volume and reads the sums back
+    fn run(
+        &self,
+        pipeline: &wgpu::ComputePipeline,
+        params: [u32; 8],
+        points: Option<&[f32]>,
+    ) -> Option<Vec<f32>> {
+        let resolution = params[0];
+        let size = (resolution as u64).pow(3) * std::mem::size_of::<f32>() as u64;
+        if size == 0 {
+            return Some(Vec::new());
+        }
+        let gpu = self.gpu;
+        let readback = gpu.submit(|encoder| {
+            let params = gpu.buffer(
+                "noise params",
+                &words_to_bytes(&params),
+                wgpu::BufferUsages::UNIFORM,
+            );
+            let sums = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("noise sums"),
+                size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            let points = points.map(|points| {
+                // A binding can't be empty
+                let mut data = points.iter().map(|x| x.to_bits()).collect::<Vec<_>>();
+                data.resize(data.len().max(4), 0);
+                gpu.buffer(
+                    "noise points",
+                    &words_to_bytes(&data),
+                    wgpu::BufferUsages::STORAGE,
+                )
+            });
+
+            let mut entries = vec![
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: sums.as_entire_binding(),
+                },
+            ];
+            if let Some(points) = &points {
+                entries.push(wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: points.as_entire_binding(),
+                });
+            }
+            let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("noise"),
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &entries,
+            });
+
+            {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                pass.set_pipeline(pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                let groups = resolution.div_ceil(WORKGROUP_SIZE);
+                pass.dispatch_workgroups(groups, groups, groups);
+            }
+            let readback = gpu.readback_buffer(size);
+            encoder.copy_buffer_to_buffer(&sums, 0, &readback, 0, size);
+            readback
+        })?;
+        let sums = gpu.read_words(&readback)?;
+        Some(sums.into_iter().map(f32::from_bits).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec4;
+
+    use super::*;
+    use crate::object::objects::cloud::tests::worley;
+    use crate::object::objects::texture3d::{INoise, INoiseBuilder, NoiseBuilder};
+
+    fn assert_close(gpu: &impl INoise, cpu: &impl INoise, resolution: usize) {
+        let mut max_error = 0.0f32;
+        for i in 0..resolution.pow(3) {
+            let p = Vec3::new(
+                (i % resolution) as f32,
+                (i / resolution % resolution) as f32,
+                (i / (resolution * resolution)) as f32,
+            ) / resolution as f32;
+            let error = (gpu.sample_level(p) - cpu.sample_level(p))
+                .abs()
+                .max_element();
+            max_error = max_error.max(error);
+        }
+        assert!(max_error < 1e-3, "{max_error}");
+    }
+
+    #[test]
+    fn test_gpu_noise() {
+        let Some(gpu) = GpuNoise::shared() else {
+            // Nothing to compare on a machine without an adapter
+            return;
+        };
+        let worley = worley(7, 16);
+        let on_gpu = NoiseBuilder::from(worley).build_on(gpu, Vec::new());
+        assert_close(&on_gpu, &worley.build(), 16);
+
+        let perlin = PerlinBuilder::new()
+            .with_num_points_a(2)
+            .with_num_points_b(4)
+            .with_num_points_c(8)
+            .with_persistence(0.5)
+            .with_resolution(16)
+            .with_tile(1.0)
+            .with_color_mask(Vec4::ONE);
+        let on_gpu = NoiseBuilder::from(perlin).build_on(gpu, Vec::new());
+        assert_close(&on_gpu, &perlin.build(), 16);
+    }
+}
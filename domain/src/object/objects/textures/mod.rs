@@ -1,3 +1,5 @@
+#[cfg(feature = "gpu")]
+pub mod gpu_noise;
 pub mod texture2d;
 pub mod texture3d;
 
@@ -0,0 +1,134 @@
+// Octave sums of the 3D noise, one per texel. Mirrors `Worley::worley` and
+// `Perlin::perlin` of texture3d.rs, the texels are composed on the CPU.
+
+struct Params {
+    resolution: u32,
+    num_a: u32,
+    num_b: u32,
+    num_c: u32,
+    tile: f32,
+    persistence: f32,
+    invert: u32,
+    _padding: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+// Worley points of the three octaves one after another
+@group(0) @binding(1) var<storage, read> points: array<vec4<f32>>;
+@group(0) @binding(2) var<storage, read_write> sums: array<f32>;
+
+fn worley(first: u32, num: u32, sample_pos: vec3<f32>) -> f32 {
+    let pos = (sample_pos * params.tile) % vec3(1.0);
+    let n = i32(num);
+    let cell_id = vec3<i32>(floor(pos * f32(num)));
+    var min_sqrt_dist = 1.0;
+    for (var z = -1; z <= 1; z++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                let adj_id = cell_id + vec3(x, y, z);
+                let low = min(adj_id.x, min(adj_id.y, adj_id.z));
+                let high = max(adj_id.x, max(adj_id.y, adj_id.z));
+                if low == -1 || high == n {
+                    let wrapped_id = (adj_id + n) % n;
+                    let index = wrapped_id.x + n * (wrapped_id.y + wrapped_id.z * n);
+                    let point = points[first + u32(index)].xyz;
+                    for (var k = 0; k < 27; k++) {
+                        let shift = vec3(k % 3, k / 3 % 3, k / 9) - 1;
+                        let offset = pos - (point + vec3<f32>(shift));
+                        min_sqrt_dist = min(min_sqrt_dist, dot(offset, offset));
+                    }
+                } else {
+                    let index = adj_id.x + n * (adj_id.y + adj_id.z * n);
+                    let offset = pos - points[first + u32(index)].xyz;
+                    min_sqrt_dist = min(min_sqrt_dist, dot(offset, offset));
+                }
+            }
+        }
+    }
+    return sqrt(min_sqrt_dist);
+}
+
+fn mod289_3(x: vec3<f32>) -> vec3<f32> {
+    return x - floor(x / 289.0) * 289.0;
+}
+
+fn mod289_2(x: vec2<f32>) -> vec2<f32> {
+    return x - floor(x / 289.0) * 289.0;
+}
+
+fn permute(x: vec3<f32>) -> vec3<f32> {
+    return mod289_3((x * 34.0 + 1.0) * x);
+}
+
+fn taylor_inv_sqrt(r: vec3<f32>) -> vec3<f32> {
+    return vec3(1.79284291400159) - r * 0.85373472095314;
+}
+
+fn snoise(v: vec2<f32>) -> f32 {
+    let c = vec4(0.211324865405187, 0.366025403784439, -0.577350269189626, 0.024390243902439);
+
+    var i = floor(v + dot(v, c.yy));
+    let x0 = v - i + dot(i, c.xx);
+    let i1 = select(vec2(0.0, 1.0), vec2(1.0, 0.0), x0.x > x0.y);
+    let x1 = x0 - i1 + c.xx;
+    let x2 = x0 + c.zz;
+
+    i = mod289_2(i);
+    let p = permute(
+        permute(vec3(i.y, i.y + i1.y, i.y + 1.0)) + vec3(i.x, i.x + i1.x, i.x + 1.0)
+    );
+
+    var m = max(vec3(0.5) - vec3(dot(x0, x0), dot(x1, x1), dot(x2, x2)), vec3(0.0));
+    m = m * m * m * m;
+
+    let x = 2.0 * fract(p * c.www) - 1.0;
+    let h = abs(x) - 0.5;
+    let ox = floor(x + 0.5);
+    let a0 = x - ox;
+    m = m * taylor_inv_sqrt(a0 * a0 + h * h);
+
+    let g = vec3(a0.x * x0.x + h.x * x0.y, a0.y * x1.x + h.y * x1.y, a0.z * x2.x + h.z * x2.y);
+    return (130.0 * dot(m, g)) * 0.5 + 0.5;
+}
+
+fn perlin(num: u32, pos: vec3<f32>) -> f32 {
+    return snoise(vec2(pos.z * params.tile * f32(num), pos.x * params.tile * f32(num)));
+}
+
+// Sum of the octaves scaled to 0..1, inverted when asked
+fn store(id: vec3<u32>, a: f32, b: f32, c: f32) {
+    let p = params.persistence;
+    var sum = (a + b * p + c * p * p) / (1.0 + p + p * p);
+    if params.invert != 0u {
+        sum = 1.0 - sum;
+    }
+    let r = params.resolution;
+    sums[(id.z * r + id.y) * r + id.x] = sum;
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn worley_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let r = params.resolution;
+    if any(id >= vec3(r)) {
+        return;
+    }
+    let pos = vec3<f32>(id) / f32(r);
+    let first_b = params.num_a * params.num_a * params.num_a;
+    let first_c = first_b + params.num_b * params.num_b * params.num_b;
+    store(
+        id,
+        worley(0u, params.num_a, pos),
+        worley(first_b, params.num_b, pos),
+        worley(first_c, params.num_c, pos),
+    );
+}
+
+@compute @workgroup_size(4, 4, 4)
+fn perlin_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let r = params.resolution;
+    if any(id >= vec3(r)) {
+        return;
+    }
+    let pos = vec3<f32>(id) / f32(r);
+    store(id, perlin(params.num_a, pos), perlin(params.num_b, pos), perlin(params.num_c, pos));
+}
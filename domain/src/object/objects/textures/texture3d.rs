@@ -1,8 +1,14 @@
 use std::ops::{Deref, DerefMut, Index, IndexMut};
+use std::sync::{Arc, Mutex};
 
 use glam::{IVec3, UVec3, Vec2, Vec3, Vec4, Vec4Swizzles};
 use rand::prelude::StdRng;
 use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::managers::profiling_manager::{Profiler, Stage};
+#[cfg(feature = "gpu")]
+use crate::object::objects::textures::gpu_noise::GpuNoise;
 
 const OFFSETS: [IVec3; 27] = [
     // centre
@@ -46,6 +52,21 @@ struct Texture3D<T> {
     z: usize,
 }
 
+impl<T: Copy> Texture3D<T> {
+    /// Cube of `resolution` texels filled with `value`, kept in `data`
+    /// whose allocation is reused when it is large enough
+    fn cube_in(mut data: Vec<T>, resolution: usize, value: T) -> Self {
+        data.clear();
+        data.resize(resolution * resolution * resolution, value);
+        Self {
+            data,
+            x: resolution,
+            y: resolution,
+            z: resolution,
+        }
+    }
+}
+
 impl<T> Index<UVec3> for Texture3D<T> {
     type Output = T;
 
@@ -75,7 +96,7 @@ impl<T> IndexMut<UVec3> for Texture3D<T> {
     }
 }
 
-#[derive(Default, Debug, PartialEq, Copy, Clone)]
+#[derive(Default, Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct WorleyBuilder {
     pub seed: u64,
     pub num_points_a: usize,
@@ -182,30 +203,125 @@ impl Worley {
         points
     }
 
-    fn worley(points: &[Vec3], num_cells: usize, sample_pos: Vec3, tile: f32) -> f32 {
-        let sample_pos = (sample_pos * tile) % 1.;
-        let cell_id = (sample_pos * num_cells as f32).floor().as_ivec3();
-        let mut min_sqrt_dist: f32 = 1.0;
+    /// Points of the three octaves, in the order the seed gives them
+    fn points(builder: &WorleyBuilder) -> [Vec<Vec3>; 3] {
+        let mut rng = StdRng::seed_from_u64(builder.seed);
+        [
+            builder.num_points_a,
+            builder.num_points_b,
+            builder.num_points_c,
+        ]
+        .map(|x| Self::create_worley_points_buffer(&mut rng, x))
+    }
 
-        let num_cells = num_cells as i32;
-        for i in OFFSETS {
-            let adj_id = cell_id + i;
-            if adj_id.min_element() == -1 || adj_id.max_element() == num_cells {
-                let wrapped_id = (adj_id + num_cells) % num_cells;
-                let adj_cell_index =
-                    wrapped_id.x + num_cells * (wrapped_id.y + wrapped_id.z * num_cells);
-                let wrapped_point = points[adj_cell_index as usize];
-                for j in OFFSETS {
-                    let sample_offset = sample_pos - (wrapped_point + j.as_vec3());
-                    min_sqrt_dist = min_sqrt_dist.min(sample_offset.dot(sample_offset));
+    /// Fills the texels from the octave sums and normalizes them, the way
+    /// [`INoise::generate_noise`] does
+    #[cfg(feature = "gpu")]
+    fn compose(&mut self, sums: &[f32]) {
+        use rayon::prelude::*;
+
+        let params = &self.builder;
+        let (min, max) = sums
+            .par_iter()
+            .fold(
+                || (i32::MAX, i32::MIN),
+                |(min, max), x| {
+                    let scaled = (x * 10_000_000.0) as i32;
+                    (min.min(scaled), max.max(scaled))
+                },
+            )
+            .reduce(|| (i32::MAX, i32::MIN), |a, b| (a.0.min(b.0), a.1.max(b.1)));
+        let (min_val, max_val) = (min as f32 / 10_000_000.0, max as f32 / 10_000_000.0);
+        self.texture3d
+            .data
+            .par_iter_mut()
+            .zip(sums)
+            .for_each(|(val, noise_sum)| {
+                *val = *val * (1.0 - params.color_mask) + noise_sum * params.color_mask;
+                let normalized_val = (*val - min_val) / (max_val - min_val);
+                *val = *val * (1.0 - params.color_mask) + normalized_val * params.color_mask;
+            });
+    }
+}
+
+/// Feature points of an octave gathered for every cell of the volume: the
+/// points of the 27 neighbouring cells, with the wrapped ones repeated at
+/// every tile offset, less the ones too far to be the nearest to any texel
+/// of the cell. The texels of a cell then search one short contiguous run.
+#[derive(Debug)]
+struct PointGrid {
+    num_cells: i32,
+    /// First candidate of every cell, one past the last candidate at the end
+    starts: Vec<u32>,
+    candidates: Vec<Vec3>,
+}
+
+impl PointGrid {
+    fn new(points: &[Vec3], num_cells: usize) -> Self {
+        let n = num_cells as i32;
+        let cell_size = 1.0 / num_cells as f32;
+        let mut starts = Vec::with_capacity(num_cells.pow(3) + 1);
+        let mut candidates = Vec::new();
+        let mut neighbours = Vec::new();
+        for z in 0..n {
+            for y in 0..n {
+                for x in 0..n {
+                    let cell_id = IVec3::new(x, y, z);
+                    neighbours.clear();
+                    for i in OFFSETS {
+                        let adj_id = cell_id + i;
+                        if adj_id.min_element() == -1 || adj_id.max_element() == n {
+                            let wrapped_id = (adj_id + n) % n;
+                            let index = wrapped_id.x + n * (wrapped_id.y + wrapped_id.z * n);
+                            let point = points[index as usize];
+                            neighbours.extend(OFFSETS.map(|j| point + j.as_vec3()));
+                        } else {
+                            let index = adj_id.x + n * (adj_id.y + adj_id.z * n);
+                            neighbours.push(points[index as usize]);
+                        }
+                    }
+
+                    // The own point is at most `reach` away from any texel of
+                    // the cell, so a point farther than that from the whole
+                    // cell never wins. The margin absorbs the rounding.
+                    let margin = Vec3::splat(1e-4);
+                    let low = cell_id.as_vec3() * cell_size - margin;
+                    let high = (cell_id + 1).as_vec3() * cell_size + margin;
+                    let own = points[(x + n * (y + z * n)) as usize];
+                    let reach = (own - low).abs().max((high - own).abs()).length_squared();
+                    starts.push(candidates.len() as u32);
+                    candidates.extend(neighbours.iter().filter(|&&point| {
+                        let outside = (low - point).max(point - high).max(Vec3::ZERO);
+                        outside.length_squared() <= reach * 1.001
+                    }));
                 }
-            } else {
-                let adj_cell_index = adj_id.x + num_cells * (adj_id.y + adj_id.z * num_cells);
-                let sample_offset = sample_pos - points[adj_cell_index as usize];
-                min_sqrt_dist = min_sqrt_dist.min(sample_offset.dot(sample_offset));
             }
         }
+        starts.push(candidates.len() as u32);
+        Self {
+            num_cells: n,
+            starts,
+            candidates,
+        }
+    }
 
+    /// Distance to the nearest feature point
+    fn worley(&self, sample_pos: Vec3, tile: f32) -> f32 {
+        let sample_pos = (sample_pos * tile) % 1.;
+        let n = self.num_cells;
+        let cell_id = (sample_pos * n as f32)
+            .floor()
+            .as_ivec3()
+            .clamp(IVec3::ZERO, IVec3::splat(n - 1));
+        let cell = (cell_id.x + n * (cell_id.y + cell_id.z * n)) as usize;
+        let candidates =
+            &self.candidates[self.starts[cell] as usize..self.starts[cell + 1] as usize];
+
+        let mut min_sqrt_dist: f32 = 1.0;
+        for &point in candidates {
+            let sample_offset = sample_pos - point;
+            min_sqrt_dist = min_sqrt_dist.min(sample_offset.dot(sample_offset));
+        }
         min_sqrt_dist.sqrt()
     }
 }
@@ -213,7 +329,7 @@ impl Worley {
 // pub type Perlin = Worley;
 // pub type PerlinBuilder = WorleyBuilder;
 
-#[derive(Default, Debug, PartialEq, Copy, Clone)]
+#[derive(Default, Debug, PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub struct PerlinBuilder {
     pub seed: u64,
     pub num_points_a: usize,
@@ -369,12 +485,48 @@ impl Perlin {
     }
 }
 
+#[cfg(feature = "gpu")]
+impl Perlin {
+    /// Fills the texels from the octave sums, the way
+    /// [`INoise::generate_noise`] does
+    fn compose(&mut self, sums: &[f32]) {
+        use rayon::prelude::*;
+
+        let mask = self.builder.color_mask;
+        self.texture3d
+            .data
+            .par_iter_mut()
+            .zip(sums)
+            .for_each(|(val, noise_sum)| *val = *val * (1.0 - mask) + noise_sum * mask);
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Noise {
     Worley(Worley),
     Perlin(Perlin),
 }
 
+impl Noise {
+    /// Texels in rows along x, then y, then z
+    pub fn texels(&self) -> &[Vec4] {
+        &self.texture().data
+    }
+
+    /// Texels along x, y and z
+    pub fn size(&self) -> [usize; 3] {
+        let texture = self.texture();
+        [texture.x, texture.y, texture.z]
+    }
+
+    fn texture(&self) -> &Texture3D<Vec4> {
+        match self {
+            Noise::Worley(x) => &x.texture3d,
+            Noise::Perlin(x) => &x.texture3d,
+        }
+    }
+}
+
 impl Default for Noise {
     fn default() -> Self {
         Self::Worley(Worley::default())
@@ -399,9 +551,20 @@ impl INoise for Noise {
     fn build(noise_builder: Self::NoiseBuilder) -> Self {
         noise_builder.build()
     }
+
+    fn build_in(noise_builder: Self::NoiseBuilder, storage: Vec<Vec4>) -> Self {
+        noise_builder.build_in(storage)
+    }
+
+    fn into_storage(self) -> Vec<Vec4> {
+        match self {
+            Noise::Worley(x) => x.into_storage(),
+            Noise::Perlin(x) => x.into_storage(),
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum NoiseBuilder {
     WorleyBuilder(WorleyBuilder),
     PerlinBuilder(PerlinBuilder),
@@ -410,9 +573,63 @@ pub enum NoiseBuilder {
 impl INoiseBuilder for NoiseBuilder {
     type Noise = Noise;
     fn build(self) -> Noise {
+        self.build_in(Vec::new())
+    }
+
+    fn build_in(self, storage: Vec<Vec4>) -> Noise {
+        match self {
+            NoiseBuilder::WorleyBuilder(x) => Noise::Worley(x.build_in(storage)),
+            NoiseBuilder::PerlinBuilder(x) => Noise::Perlin(x.build_in(storage)),
+        }
+    }
+}
+
+impl NoiseBuilder {
+    /// Texels of the noise built from this builder
+    pub fn len(&self) -> usize {
+        let resolution = match self {
+            NoiseBuilder::WorleyBuilder(x) => x.resolution,
+            NoiseBuilder::PerlinBuilder(x) => x.resolution,
+        };
+        resolution * resolution * resolution
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Builds the noise with the compute shader of the device, or on the
+    /// CPU when the device fails
+    #[cfg(feature = "gpu")]
+    pub fn build_on(self, gpu: &GpuNoise, storage: Vec<Vec4>) -> Noise {
         match self {
-            NoiseBuilder::WorleyBuilder(x) => Noise::Worley(x.build()),
-            NoiseBuilder::PerlinBuilder(x) => Noise::Perlin(x.build()),
+            NoiseBuilder::WorleyBuilder(builder) => {
+                let points = Worley::points(&builder);
+                let Some(sums) = gpu.worley(&builder, &points) else {
+                    return Noise::Worley(builder.build_in(storage));
+                };
+                let [points_a, points_b, points_c] = points;
+                let mut w = Worley {
+                    points_a,
+                    points_b,
+                    points_c,
+                    texture3d: Texture3D::cube_in(storage, builder.resolution, Vec4::ZERO),
+                    builder,
+                };
+                w.compose(&sums);
+                Noise::Worley(w)
+            }
+            NoiseBuilder::PerlinBuilder(builder) => {
+                let Some(sums) = gpu.perlin(&builder) else {
+                    return Noise::Perlin(builder.build_in(storage));
+                };
+                let mut p = Perlin {
+                    texture3d: Texture3D::cube_in(storage, builder.resolution, Vec4::ZERO),
+                    builder,
+                };
+                p.compose(&sums);
+                Noise::Perlin(p)
+            }
         }
     }
 }
@@ -441,23 +658,108 @@ pub trait INoise: Sized {
     fn generate_noise(&mut self);
 
     fn build(noise_builder: Self::NoiseBuilder) -> Self;
+    /// Builds the noise into the storage of a freed volume, see
+    /// [`VolumePool`]
+    fn build_in(noise_builder: Self::NoiseBuilder, storage: Vec<Vec4>) -> Self;
+    /// Gives the storage of the texels back
+    fn into_storage(self) -> Vec<Vec4>;
 }
 
 pub trait INoiseBuilder {
     type Noise;
     fn build(self) -> Self::Noise;
+    fn build_in(self, storage: Vec<Vec4>) -> Self::Noise;
 }
 impl INoiseBuilder for PerlinBuilder {
     type Noise = Perlin;
     fn build(self) -> Perlin {
         Perlin::build(self)
     }
+    fn build_in(self, storage: Vec<Vec4>) -> Perlin {
+        Perlin::build_in(self, storage)
+    }
 }
 impl INoiseBuilder for WorleyBuilder {
     type Noise = Worley;
     fn build(self) -> Worley {
         Worley::build(self)
     }
+    fn build_in(self, storage: Vec<Vec4>) -> Worley {
+        Worley::build_in(self, storage)
+    }
+}
+
+/// Most of the freed storages a [`VolumePool`] keeps
+const POOLED_VOLUMES: usize = 4;
+
+/// Storage of the freed noise volumes, reused by the next volumes instead of
+/// allocating them again. A volume of 128 texels a side takes 32 MB, so
+/// tweaking the noise would otherwise allocate that much on every change.
+/// Clones share the pool, so the volumes built on other threads use it too.
+#[derive(Debug, Clone, Default)]
+pub struct VolumePool {
+    free: Arc<Mutex<Vec<Vec<Vec4>>>>,
+    /// Timings the generation of the noise adds to
+    profiler: Option<Arc<Profiler>>,
+}
+
+impl VolumePool {
+    pub fn with_profiler(mut self, profiler: Arc<Profiler>) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    /// Builds the noise, in a freed storage when there is one. With the
+    /// `gpu` feature the noise is generated on the GPU when there is one.
+    pub fn build(&self, builder: NoiseBuilder) -> Noise {
+        let _timer = self
+            .profiler
+            .as_ref()
+            .map(|x| x.scope(Stage::NoiseGeneration));
+        let storage = self.take(builder.len());
+        #[cfg(feature = "gpu")]
+        if let Some(gpu) = GpuNoise::shared() {
+            return builder.build_on(gpu, storage);
+        }
+        builder.build_in(storage)
+    }
+
+    /// Freed storage for `len` texels: one of the same length if there is,
+    /// then the smallest one holding them
+    fn take(&self, len: usize) -> Vec<Vec4> {
+        let mut free = self.free.lock().unwrap();
+        let index = free.iter().position(|x| x.len() == len).or_else(|| {
+            (0..free.len())
+                .filter(|&i| free[i].capacity() >= len)
+                .min_by_key(|&i| free[i].capacity())
+        });
+        index.map(|i| free.swap_remove(i)).unwrap_or_default()
+    }
+
+    /// Keeps the storage of the noise, or gives the noise back while
+    /// something else still uses it
+    pub fn recycle(&self, noise: Arc<Noise>) -> Option<Arc<Noise>> {
+        let noise = match Arc::try_unwrap(noise) {
+            Ok(noise) => noise,
+            Err(shared) => return Some(shared),
+        };
+        let mut free = self.free.lock().unwrap();
+        free.push(noise.into_storage());
+        if free.len() > POOLED_VOLUMES {
+            // The smallest storage is the cheapest to allocate again
+            let smallest = (0..free.len()).min_by_key(|&i| free[i].capacity());
+            free.swap_remove(smallest.unwrap());
+        }
+        None
+    }
+
+    /// Bytes kept for reuse
+    pub fn pooled_bytes(&self) -> usize {
+        let free = self.free.lock().unwrap();
+        free.iter()
+            .map(|x| x.capacity() * std::mem::size_of::<Vec4>())
+            .sum()
+    }
 }
 
 impl INoise for Perlin {
@@ -499,24 +801,24 @@ impl INoise for Perlin {
             });
     }
     fn build(noise_builder: Self::NoiseBuilder) -> Self {
+        Self::build_in(noise_builder, Vec::new())
+    }
+
+    fn build_in(noise_builder: Self::NoiseBuilder, storage: Vec<Vec4>) -> Self {
         let resolution = noise_builder.resolution;
         // let mut rng = StdRng::seed_from_u64(perlin_noise_builder.seed);
         let mut w = Self {
-            texture3d: Texture3D {
-                data: {
-                    let len = resolution * resolution * resolution;
-                    vec![Vec4::ZERO; len]
-                },
-                x: resolution,
-                y: resolution,
-                z: resolution,
-            },
+            texture3d: Texture3D::cube_in(storage, resolution, Vec4::ZERO),
             builder: noise_builder,
         };
 
         w.generate_noise();
         w
     }
+
+    fn into_storage(self) -> Vec<Vec4> {
+        self.texture3d.data
+    }
 }
 
 impl INoise for Worley {
@@ -526,81 +828,134 @@ impl INoise for Worley {
     }
     fn generate_noise(&mut self) {
         use rayon::prelude::*;
-        use std::sync::{Arc, Mutex};
-        let min_max_lock = Arc::new(Mutex::new([i32::MAX, i32::MIN]));
 
         let params = &self.builder;
-        self.texture3d
+        let grids = [
+            PointGrid::new(&self.points_a, params.num_points_a),
+            PointGrid::new(&self.points_b, params.num_points_b),
+            PointGrid::new(&self.points_c, params.num_points_c),
+        ];
+        let resolution = params.resolution;
+        let slice = (resolution * resolution).max(1);
+        // Every slice keeps its own extremes, merged once it is done
+        let (min, max) = self
+            .texture3d
             .data
-            .par_iter_mut()
+            .par_chunks_mut(slice)
             .enumerate()
-            .for_each(|(index, val)| {
-                let id = UVec3::new(
-                    (index % params.resolution) as u32,
-                    ((index / params.resolution) % params.resolution) as u32,
-                    (index / (params.resolution * params.resolution)) as u32,
-                );
-                let pos = id.as_vec3() / params.resolution as f32;
-
-                let noise_sum =
-                    Worley::worley(&self.points_a, params.num_points_a, pos, params.tile)
-                        + Worley::worley(&self.points_b, params.num_points_b, pos, params.tile)
-                            * params.persistence
-                        + Worley::worley(&self.points_c, params.num_points_c, pos, params.tile)
+            .map(|(z, texels)| {
+                let (mut min, mut max) = (i32::MAX, i32::MIN);
+                for (index, val) in texels.iter_mut().enumerate() {
+                    let id = UVec3::new(
+                        (index % resolution) as u32,
+                        (index / resolution) as u32,
+                        z as u32,
+                    );
+                    let pos = id.as_vec3() / resolution as f32;
+
+                    let noise_sum = grids[0].worley(pos, params.tile)
+                        + grids[1].worley(pos, params.tile) * params.persistence
+                        + grids[2].worley(pos, params.tile)
                             * params.persistence
                             * params.persistence;
 
-                let max_val =
-                    1.0 + (params.persistence) + (params.persistence * params.persistence);
-
-                let noise_sum = noise_sum / max_val;
-                let noise_sum = if params.invert_noise {
-                    1.0 - noise_sum
-                } else {
-                    noise_sum
-                };
-                let scaled_val = (noise_sum * 10_000_000.0) as i32;
-                let mut min_max_lock = min_max_lock.lock().unwrap();
-                min_max_lock[0] = min_max_lock[0].min(scaled_val);
-                min_max_lock[1] = min_max_lock[1].max(scaled_val);
-                drop(min_max_lock);
-
-                *val = *val * (1.0 - params.color_mask) + noise_sum * params.color_mask;
-                assert!(val.x >= 0., "{:?} {:?}", val, noise_sum);
-            });
+                    let max_val =
+                        1.0 + (params.persistence) + (params.persistence * params.persistence);
+
+                    let noise_sum = noise_sum / max_val;
+                    let noise_sum = if params.invert_noise {
+                        1.0 - noise_sum
+                    } else {
+                        noise_sum
+                    };
+                    let scaled_val = (noise_sum * 10_000_000.0) as i32;
+                    min = min.min(scaled_val);
+                    max = max.max(scaled_val);
+
+                    *val = *val * (1.0 - params.color_mask) + noise_sum * params.color_mask;
+                    assert!(val.x >= 0., "{:?} {:?}", val, noise_sum);
+                }
+                (min, max)
+            })
+            .reduce(|| (i32::MAX, i32::MIN), |a, b| (a.0.min(b.0), a.1.max(b.1)));
 
-        let min_max = std::sync::Arc::into_inner(min_max_lock)
-            .expect("one strong reference")
-            .into_inner()
-            .expect("No one holding the mutex");
+        let min_val = min as f32 / 10_000_000.0;
+        let max_val = max as f32 / 10_000_000.0;
         self.texture3d.data.par_iter_mut().for_each(|val| {
-            let min_val = min_max[0] as f32 / 10_000_000.0;
-            let max_val = min_max[1] as f32 / 10_000_000.0;
             let normalized_val = (*val - min_val) / (max_val - min_val);
             *val = *val * (1.0 - params.color_mask) + normalized_val * params.color_mask;
         });
     }
 
     fn build(worley_builder: Self::NoiseBuilder) -> Self {
+        Self::build_in(worley_builder, Vec::new())
+    }
+
+    fn build_in(worley_builder: Self::NoiseBuilder, storage: Vec<Vec4>) -> Self {
         let resolution = worley_builder.resolution;
-        let mut rng = StdRng::seed_from_u64(worley_builder.seed);
+        let [points_a, points_b, points_c] = Self::points(&worley_builder);
         let mut w = Self {
-            points_a: Self::create_worley_points_buffer(&mut rng, worley_builder.num_points_a),
-            points_b: Self::create_worley_points_buffer(&mut rng, worley_builder.num_points_b),
-            points_c: Self::create_worley_points_buffer(&mut rng, worley_builder.num_points_c),
-            texture3d: Texture3D {
-                data: {
-                    let len = resolution * resolution * resolution;
-                    vec![Vec4::ZERO; len]
-                },
-                x: resolution,
-                y: resolution,
-                z: resolution,
-            },
+            points_a,
+            points_b,
+            points_c,
+            texture3d: Texture3D::cube_in(storage, resolution, Vec4::ZERO),
             builder: worley_builder,
         };
 
         w.generate_noise();
         w
     }
+
+    fn into_storage(self) -> Vec<Vec4> {
+        self.texture3d.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Nearest point search wrapping the neighbouring cells on the fly
+    fn wrapped_worley(points: &[Vec3], num_cells: usize, sample_pos: Vec3, tile: f32) -> f32 {
+        let sample_pos = (sample_pos * tile) % 1.;
+        let cell_id = (sample_pos * num_cells as f32).floor().as_ivec3();
+        let mut min_sqrt_dist: f32 = 1.0;
+
+        let num_cells = num_cells as i32;
+        for i in OFFSETS {
+            let adj_id = cell_id + i;
+            if adj_id.min_element() == -1 || adj_id.max_element() == num_cells {
+                let wrapped_id = (adj_id + num_cells) % num_cells;
+                let index = wrapped_id.x + num_cells * (wrapped_id.y + wrapped_id.z * num_cells);
+                for j in OFFSETS {
+                    let sample_offset = sample_pos - (points[index as usize] + j.as_vec3());
+                    min_sqrt_dist = min_sqrt_dist.min(sample_offset.dot(sample_offset));
+                }
+            } else {
+                let index = adj_id.x + num_cells * (adj_id.y + adj_id.z * num_cells);
+                let sample_offset = sample_pos - points[index as usize];
+                min_sqrt_dist = min_sqrt_dist.min(sample_offset.dot(sample_offset));
+            }
+        }
+
+        min_sqrt_dist.sqrt()
+    }
+
+    #[test]
+    fn test_point_grid() {
+        let mut rng = StdRng::seed_from_u64(5);
+        for num_cells in [1, 2, 5] {
+            let points = Worley::create_worley_points_buffer(&mut rng, num_cells);
+            let grid = PointGrid::new(&points, num_cells);
+            for _ in 0..500 {
+                let pos = Vec3::new(rng.gen(), rng.gen(), rng.gen());
+                for tile in [1.0, 2.5] {
+                    assert_eq!(
+                        grid.worley(pos, tile),
+                        wrapped_worley(&points, num_cells, pos, tile)
+                    );
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,222 @@
+use egui::{Color32, Pos2, Rect, Vec2};
+use glam::{Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::math::Transform;
+use crate::object::camera::Camera;
+use crate::visitor::{Visitable, Visitor};
+
+/// Number of segments the rotation rings are drawn and hit-tested with
+pub const RING_SEGMENTS: usize = 48;
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum GizmoMode {
+    /// Arrows along the world axes
+    #[default]
+    Translate,
+    /// Rings around the world axes
+    Rotate,
+}
+
+/// Change of the target requested by dragging a handle, in world space
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum GizmoDrag {
+    Translate(Vec3),
+    /// Rotation around the pivot
+    Rotate(Quat),
+}
+
+/// Handles drawn around the selected object that move or turn it when
+/// dragged
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct TransformGizmo {
+    /// Object manipulated by the gizmo
+    #[serde(skip)]
+    pub target: Option<&'static str>,
+    pub mode: GizmoMode,
+    /// World position of the target, kept up to date by the scene manager
+    #[serde(skip)]
+    pub pivot: Vec3,
+    /// Length of the handles in screen points
+    pub size: f32,
+    /// Distance in screen points within which a handle is grabbed
+    pub handle_radius: f32,
+    /// Axis of the handle being dragged
+    #[serde(skip)]
+    pub active: Option<Vec3>,
+}
+
+impl Default for TransformGizmo {
+    fn default() -> Self {
+        Self {
+            target: None,
+            mode: GizmoMode::default(),
+            pivot: Vec3::ZERO,
+            size: 80.0,
+            handle_radius: 6.0,
+            active: None,
+        }
+    }
+}
+
+impl TransformGizmo {
+    pub fn new(mode: GizmoMode) -> Self {
+        Self {
+            mode,
+            ..Default::default()
+        }
+    }
+
+    pub fn axes() -> [(Vec3, Color32); 3] {
+        [
+            (Vec3::X, Color32::RED),
+            (Vec3::Y, Color32::DARK_GREEN),
+            (Vec3::Z, Color32::BLUE),
+        ]
+    }
+
+    fn project(camera: &Camera, canvas: Rect) -> Transform {
+        let proj = camera.projection(canvas.width(), canvas.height());
+        Transform::new(proj * camera.view(), canvas)
+    }
+
+    /// World length of the handles that keeps them `size` points long
+    pub fn world_size(&self, camera: &Camera, canvas: Rect) -> f32 {
+        let t = Self::project(camera, canvas);
+        let right = camera
+            .dir()
+            .cross(Vec3::Y)
+            .try_normalize()
+            .unwrap_or(Vec3::X);
+        let (a, _) = t.world_to_egui(self.pivot);
+        let (b, _) = t.world_to_egui(self.pivot + right);
+        self.size / (b - a).length().max(f32::EPSILON)
+    }
+
+    /// Screen polyline of the handle of the given axis
+    pub fn handle(&self, camera: &Camera, canvas: Rect, axis: Vec3) -> Vec<Pos2> {
+        let t = Self::project(camera, canvas);
+        let len = self.world_size(camera, canvas);
+        let screen = |p: Vec3| t.world_to_egui(p).0.to_pos2();
+        match self.mode {
+            GizmoMode::Translate => vec![screen(self.pivot), screen(self.pivot + axis * len)],
+            GizmoMode::Rotate => {
+                let u = axis.any_orthonormal_vector();
+                let v = axis.cross(u);
+                (0..=RING_SEGMENTS)
+                    .map(|i| {
+                        let a = i as f32 / RING_SEGMENTS as f32 * std::f32::consts::TAU;
+                        screen(self.pivot + (u * a.cos() + v * a.sin()) * len)
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Returns the axis of the handle under the pointer
+    pub fn hit(&self, camera: &Camera, canvas: Rect, pointer: Pos2) -> Option<Vec3> {
+        Self::axes()
+            .into_iter()
+            .map(|(axis, _)| {
+                let handle = self.handle(camera, canvas, axis);
+                let distance = handle
+                    .windows(2)
+                    .map(|x| segment_distance(pointer, x[0], x[1]))
+                    .fold(f32::INFINITY, f32::min);
+                (axis, distance)
+            })
+            .filter(|&(_, distance)| distance <= self.handle_radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(axis, _)| axis)
+    }
+
+    /// Change of the target after dragging the handle of `axis` from one
+    /// pointer position to another
+    pub fn drag(
+        &self,
+        camera: &Camera,
+        canvas: Rect,
+        axis: Vec3,
+        from: Pos2,
+        to: Pos2,
+    ) -> GizmoDrag {
+        let t = Self::project(camera, canvas);
+        let center = t.world_to_egui(self.pivot).0.to_pos2();
+        match self.mode {
+            GizmoMode::Translate => {
+                let len = self.world_size(camera, canvas);
+                let end = t.world_to_egui(self.pivot + axis * len).0.to_pos2();
+                let along = end - center;
+                let amount = (to - from).dot(along) / along.length_sq().max(f32::EPSILON);
+                GizmoDrag::Translate(axis * amount * len)
+            }
+            GizmoMode::Rotate => {
+                let (a, b) = (from - center, to - center);
+                // Screen y points down, so a positive angle turns clockwise
+                let angle = cross(a, b).atan2(a.dot(b));
+                let facing = axis.dot(camera.pos() - self.pivot) > 0.0;
+                let angle = if facing { -angle } else { angle };
+                GizmoDrag::Rotate(Quat::from_axis_angle(axis, angle))
+            }
+        }
+    }
+}
+
+fn cross(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+fn segment_distance(p: Pos2, a: Pos2, b: Pos2) -> f32 {
+    let ab = b - a;
+    let t = ((p - a).dot(ab) / ab.length_sq().max(f32::EPSILON)).clamp(0.0, 1.0);
+    p.distance(a + ab * t)
+}
+
+impl Visitable for TransformGizmo {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_transform_gizmo(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::camera::ArcBall;
+
+    #[test]
+    fn test_transform_gizmo_drag() {
+        let camera = Camera {
+            view: ArcBall {
+                pivot: Vec3::ZERO,
+                distance: 10.0,
+                yaw: 0.0,
+                pitch: 0.0,
+            },
+            ..Default::default()
+        };
+        let canvas = Rect::from_min_size(Pos2::ZERO, Vec2::new(800.0, 600.0));
+        let gizmo = TransformGizmo::default();
+
+        let handle = gizmo.handle(&camera, canvas, Vec3::Y);
+        let tip = handle[1];
+        assert_eq!(gizmo.hit(&camera, canvas, tip), Some(Vec3::Y));
+        assert_eq!(gizmo.hit(&camera, canvas, Pos2::ZERO), None);
+
+        let drag = gizmo.drag(&camera, canvas, Vec3::Y, tip, tip + (tip - handle[0]));
+        let GizmoDrag::Translate(offset) = drag else {
+            unreachable!()
+        };
+        let len = gizmo.world_size(&camera, canvas);
+        assert!(offset.abs_diff_eq(Vec3::Y * len, 1e-3 * len));
+
+        let gizmo = TransformGizmo::new(GizmoMode::Rotate);
+        let ring = gizmo.handle(&camera, canvas, Vec3::X);
+        let (from, to) = (ring[0], ring[RING_SEGMENTS / 4]);
+        let GizmoDrag::Rotate(rotation) = gizmo.drag(&camera, canvas, Vec3::X, from, to) else {
+            unreachable!()
+        };
+        let (axis, angle) = rotation.to_axis_angle();
+        assert!((angle - std::f32::consts::FRAC_PI_2).abs() < 1e-2);
+        assert!(axis.abs_diff_eq(Vec3::X, 1e-3));
+    }
+}
@@ -0,0 +1,163 @@
+use egui::Color32;
+use glam::{Vec3, Vec4Swizzles};
+use serde::{Deserialize, Serialize};
+
+use crate::object::objects::{BoundingBox, Sun};
+use crate::visitor::raster::{color32_to_vec4, sky_color};
+use crate::visitor::{Visitable, Visitor};
+
+/// Directions and relative frequencies of the summed waves
+const WAVES: [(f32, f32, f32); 3] = [(1.0, 0.3, 1.0), (-0.4, 1.0, 1.7), (0.7, -0.8, 2.9)];
+
+/// Flat water surface that reflects the sky and the sun
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Water {
+    /// Horizontal extent of the surface; it lies at the top of the box
+    pub bounding_box: BoundingBox,
+    pub color: Color32,
+    /// How much of the reflected sky is mixed into the water color
+    pub reflectivity: f32,
+    /// Specular exponent of the sun highlight
+    pub shininess: f32,
+    /// Wave slope, zero gives a perfect mirror
+    pub wave_amplitude: f32,
+    /// Number of waves per unit of length
+    pub wave_scale: f32,
+    pub wave_speed: f32,
+    /// Animation time in seconds
+    pub time: f32,
+}
+
+impl Default for Water {
+    fn default() -> Self {
+        Self {
+            bounding_box: BoundingBox::default(),
+            color: Color32::from_rgb(20, 60, 90),
+            reflectivity: 0.8,
+            shininess: 200.0,
+            wave_amplitude: 0.05,
+            wave_scale: 4.0,
+            wave_speed: 1.0,
+            time: 0.0,
+        }
+    }
+}
+
+impl Water {
+    pub fn new(bounding_box: impl Into<BoundingBox>) -> Self {
+        Self {
+            bounding_box: bounding_box.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_color(mut self, color: Color32) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_reflectivity(mut self, reflectivity: f32) -> Self {
+        self.reflectivity = reflectivity;
+        self
+    }
+
+    pub fn with_waves(mut self, amplitude: f32, scale: f32, speed: f32) -> Self {
+        self.wave_amplitude = amplitude;
+        self.wave_scale = scale;
+        self.wave_speed = speed;
+        self
+    }
+
+    /// Height of the surface
+    #[inline]
+    pub fn level(&self) -> f32 {
+        self.bounding_box.max.y
+    }
+
+    /// Moves the surface to the given height
+    pub fn set_level(&mut self, level: f32) {
+        let depth = self.bounding_box.size().y;
+        self.bounding_box.max.y = level;
+        self.bounding_box.min.y = level - depth;
+    }
+
+    /// Advances the wave animation
+    pub fn advance(&mut self, dt: f32) {
+        self.time += dt;
+    }
+
+    /// Surface normal at the given point of the plane
+    pub fn normal(&self, x: f32, z: f32) -> Vec3 {
+        let t = self.time * self.wave_speed;
+        let (mut dx, mut dz) = (0.0, 0.0);
+        for (kx, kz, w) in WAVES {
+            let k = self.wave_scale * w;
+            let phase = (kx * x + kz * z) * k + t * w;
+            let slope = self.wave_amplitude * phase.cos() / w;
+            dx += slope * kx;
+            dz += slope * kz;
+        }
+        Vec3::new(-dx, 1.0, -dz).normalize()
+    }
+
+    /// Returns the color of the surface seen along the ray, or transparent
+    /// if the ray misses it
+    pub fn shade(&self, ray_origin: Vec3, ray_dir: Vec3, sun: &Sun) -> Color32 {
+        let bb = self.bounding_box;
+        if ray_dir.y.abs() <= f32::EPSILON {
+            return Color32::TRANSPARENT;
+        }
+        let t = (self.level() - ray_origin.y) / ray_dir.y;
+        let p = ray_origin + ray_dir * t;
+        if t <= 0.0
+            || !(bb.min.x..=bb.max.x).contains(&p.x)
+            || !(bb.min.z..=bb.max.z).contains(&p.z)
+        {
+            return Color32::TRANSPARENT;
+        }
+
+        let mut normal = self.normal(p.x, p.z);
+        if ray_dir.y > 0.0 {
+            normal = -normal;
+        }
+        let mut reflected = ray_dir - 2.0 * ray_dir.dot(normal) * normal;
+        reflected.y = reflected.y.abs();
+
+        let cos = (-ray_dir.dot(normal)).clamp(0.0, 1.0);
+        let fresnel = 0.02 + 0.98 * (1.0 - cos).powi(5);
+        let sky = color32_to_vec4(sky_color(reflected.y.clamp(0.0, 1.0), sun)).xyz();
+        let base = color32_to_vec4(self.color).xyz();
+        let col = base.lerp(
+            sky,
+            (self.reflectivity * (0.3 + 0.7 * fresnel)).clamp(0.0, 1.0),
+        );
+
+        let sun_dir = (sun.get_pos() - p).normalize();
+        let specular = reflected.dot(sun_dir).max(0.0).powf(self.shininess);
+        let (r, g, b) = ((col + Vec3::splat(specular)) * 255.0).into();
+        Color32::from_rgb(r as u8, g as u8, b as u8)
+    }
+}
+
+impl Visitable for Water {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_water(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_water_shade() {
+        let water = Water::new((Vec3::new(-1.0, -0.1, -1.0), Vec3::new(1.0, 0.0, 1.0)));
+        let sun = Sun::new(10.0, -90.0, -90.0);
+        let eye = Vec3::new(0.0, 1.0, 0.0);
+
+        assert_ne!(water.shade(eye, Vec3::NEG_Y, &sun), Color32::TRANSPARENT);
+        assert_eq!(water.shade(eye, Vec3::Y, &sun), Color32::TRANSPARENT);
+        let outside = (Vec3::new(5.0, 0.0, 0.0) - eye).normalize();
+        assert_eq!(water.shade(eye, outside, &sun), Color32::TRANSPARENT);
+    }
+}
@@ -0,0 +1,86 @@
+//! Components defined outside of the crate
+
+use std::any::Any;
+use std::fmt::Debug;
+
+use crate::scene::scene_composite::SceneObjects;
+
+/// Object-safe component kept in the scene as [`Component::Plugin`].
+///
+/// Visitors that do not know the plugin see the built-in components it is
+/// made of, the ones that do can downcast it in
+/// [`Visitor::visit_plugin`].
+///
+/// [`Component::Plugin`]: crate::object::Component::Plugin
+/// [`Visitor::visit_plugin`]: crate::visitor::Visitor::visit_plugin
+pub trait PluginComponent: Debug + Send + Sync {
+    /// Name of the component type, see [`crate::object::Component::kind`]
+    fn kind(&self) -> &'static str;
+
+    /// Built-in components standing for the plugin in its local space
+    fn parts(&self) -> &SceneObjects;
+
+    fn as_any(&self) -> &dyn Any;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::scene::SceneFile;
+    use crate::object::objects::Mesh;
+    use crate::object::Component;
+    use crate::scene::ObjectTransform;
+    use crate::visitor::serialize_visitor::SerializeVisitor;
+    use glam::{Mat4, Vec3};
+
+    #[derive(Debug)]
+    struct Tree {
+        parts: SceneObjects,
+    }
+
+    impl Tree {
+        fn new() -> Self {
+            let mut parts = SceneObjects::default();
+            parts.add_object("trunk", Mesh::cuboid(Vec3::new(0.2, 2.0, 0.2)));
+            Self { parts }
+        }
+    }
+
+    impl PluginComponent for Tree {
+        fn kind(&self) -> &'static str {
+            "tree"
+        }
+
+        fn parts(&self) -> &SceneObjects {
+            &self.parts
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn test_plugin_component() {
+        let mut objects = SceneObjects::default();
+        objects.add_object("tree", Component::plugin(Tree::new()));
+        *objects.local_transform_mut("tree").unwrap() =
+            ObjectTransform::from_translation(Vec3::new(3.0, 0.0, 0.0));
+        let tree = objects.get_object("tree").unwrap();
+        assert_eq!(tree.kind(), "tree");
+        let Component::Plugin(plugin) = tree else {
+            panic!("the tree is not a plugin");
+        };
+        assert!(plugin.as_any().downcast_ref::<Tree>().is_some());
+
+        let bounds = objects.bounds(Mat4::IDENTITY).unwrap();
+        assert!(bounds.center().abs_diff_eq(Vec3::new(3.0, 0.0, 0.0), 1e-5));
+        let hits = objects.pick(Mat4::IDENTITY, Vec3::new(3.0, 0.0, 5.0), Vec3::NEG_Z);
+        assert_eq!(hits.len(), 1);
+        assert!(SceneFile::from(&objects).objects.is_empty());
+
+        let mut visitor = SerializeVisitor::default();
+        visitor.scene_file(&objects);
+        assert_eq!(visitor.skipped(), ["tree"]);
+    }
+}
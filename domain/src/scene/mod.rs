@@ -1,3 +1,7 @@
 #[allow(clippy::module_inception)]
 pub mod scene;
+pub mod pick;
 pub mod scene_composite;
+pub mod transform;
+
+pub use transform::ObjectTransform;
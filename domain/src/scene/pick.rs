@@ -0,0 +1,59 @@
+//! Picking objects with a ray cast from the camera
+
+use glam::{Mat4, Vec3};
+
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::intersect_visitor::{IntersectVisitor, Ray};
+use crate::visitor::Visitor;
+
+/// Object hit by a picking ray
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HitRecord {
+    pub id: &'static str,
+    /// Distance from the ray origin to the hit point
+    pub distance: f32,
+    /// Hit point in world space
+    pub point: Vec3,
+}
+
+impl SceneObjects {
+    /// Returns the visible objects hit by the ray sorted from the nearest one.
+    ///
+    /// `parent` is the world matrix of this composite and `dir` has to be
+    /// normalized. Objects inside nested composites are reported by their
+    /// own names.
+    pub fn pick(&self, parent: Mat4, origin: Vec3, dir: Vec3) -> Vec<HitRecord> {
+        IntersectVisitor::new(Ray::new(origin, dir))
+            .with_model(parent)
+            .visit_composite(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::{Light, Mesh};
+    use crate::scene::ObjectTransform;
+
+    #[test]
+    fn test_pick() {
+        let mut objects = SceneObjects::default();
+        objects.add_object("near", Mesh::cuboid(Vec3::ONE));
+        objects.add_object("far", Mesh::cuboid(Vec3::ONE));
+        objects.add_object("lamp", Light::point(Vec3::new(0.0, 5.0, 0.0)));
+        *objects.local_transform_mut("far").unwrap() =
+            ObjectTransform::from_translation(Vec3::new(0.0, 0.0, -5.0));
+
+        let origin = Vec3::new(0.0, 0.0, 5.0);
+        let hits = objects.pick(Mat4::IDENTITY, origin, Vec3::NEG_Z);
+        let ids = hits.iter().map(|x| x.id).collect::<Vec<_>>();
+        assert_eq!(ids, ["near", "far"]);
+        assert!((hits[0].distance - 4.5).abs() < 1e-5);
+        assert!(hits[1].point.abs_diff_eq(Vec3::new(0.0, 0.0, -4.5), 1e-5));
+
+        let dir = (Vec3::new(0.0, 5.0, 0.0) - origin).normalize();
+        let hits = objects.pick(Mat4::IDENTITY, origin, dir);
+        assert_eq!(hits.first().map(|x| x.id), Some("lamp"));
+        assert!(objects.pick(Mat4::IDENTITY, origin, Vec3::Z).is_empty());
+    }
+}
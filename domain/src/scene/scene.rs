@@ -1,9 +1,11 @@
 use crate::object::Component;
 use crate::scene::scene_composite::SceneObjects;
-use crate::visitor::{Visitable, Visitor};
+use crate::scene::ObjectTransform;
+use crate::visitor::{Visitable, VisitableMut, Visitor, VisitorMut};
+use glam::Mat4;
 use log::debug;
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct Scene {
     pub objects: SceneObjects,
 }
@@ -14,8 +16,16 @@ impl Scene {
         self.objects.add_object(name, object)
     }
 
-    pub fn remove_object(&mut self, _index: usize) {
-        unimplemented!()
+    pub fn remove_object(&mut self, name: &str) -> Vec<(&'static str, Component)> {
+        self.objects.remove_object(name)
+    }
+
+    pub fn remove_kind(&mut self, kind: &str) -> Vec<(&'static str, Component)> {
+        self.objects.remove_kind(kind)
+    }
+
+    pub fn clear(&mut self) -> Vec<(&'static str, Component)> {
+        self.objects.clear()
     }
 
     pub fn get_object(&self, name: &'static str) -> Option<&Component> {
@@ -25,10 +35,40 @@ impl Scene {
     pub fn get_mut_object(&mut self, name: &'static str) -> Option<&mut Component> {
         self.objects.get_mut_object(name)
     }
+
+    pub fn set_parent(&mut self, child: &str, parent: &'static str) -> Result<(), String> {
+        self.objects.set_parent(child, parent)
+    }
+
+    pub fn detach_object(&mut self, name: &str) -> Result<(), String> {
+        self.objects.detach(name)
+    }
+
+    pub fn set_visible(&mut self, name: &str, visible: bool) -> Result<(), String> {
+        self.objects.set_visible(name, visible)
+    }
+
+    pub fn set_layer(&mut self, name: &str, layer: &'static str) -> Result<(), String> {
+        self.objects.set_layer(name, layer)
+    }
+
+    pub fn local_transform_mut(&mut self, name: &str) -> Option<&mut ObjectTransform> {
+        self.objects.local_transform_mut(name)
+    }
+
+    pub fn world_transform(&self, name: &str) -> Mat4 {
+        self.objects.world_transform(name)
+    }
 }
 
 impl Visitable for Scene {
-    fn accept(&self, visitor: &mut impl Visitor) {
-        visitor.visit_composite(&self.objects);
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_composite(&self.objects)
+    }
+}
+
+impl VisitableMut for Scene {
+    fn accept_mut(&mut self, visitor: &mut impl VisitorMut) {
+        visitor.visit_composite_mut(&mut self.objects);
     }
 }
@@ -1,21 +1,206 @@
 use std::collections::BTreeMap as Map;
 use std::ops::{Deref, DerefMut};
 
+use glam::Mat4;
+
+use crate::object::objects::{BoundingBox, Light};
 use crate::object::Component;
+use crate::scene::ObjectTransform;
+use crate::visitor::bounds_visitor::BoundsVisitor;
 use crate::visitor::{Visitable, Visitor};
 
-#[derive(Default, Debug)]
+/// Layer objects belong to unless told otherwise
+pub const DEFAULT_LAYER: &str = "default";
+/// Layer of the helpers that are not part of the picture, such as gizmos
+/// and bounding boxes
+pub const DEBUG_LAYER: &str = "debug";
+
+/// Place of an object in the scene hierarchy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SceneNode {
+    pub parent: Option<&'static str>,
+    /// Transform relative to the parent, or to the world for root objects
+    pub local: ObjectTransform,
+    /// Hidden objects and their descendants are skipped when drawing
+    pub visible: bool,
+    /// Render layer, whole layers can be hidden by the draw manager
+    pub layer: &'static str,
+}
+
+impl Default for SceneNode {
+    fn default() -> Self {
+        Self {
+            parent: None,
+            local: ObjectTransform::IDENTITY,
+            visible: true,
+            layer: DEFAULT_LAYER,
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone)]
 pub struct SceneObjects {
     pub objects: Map<&'static str, Component>,
+    /// Hierarchy of the objects, kept in sync with `objects`
+    pub nodes: Map<&'static str, SceneNode>,
 }
 
-impl<'a> SceneObjects {
+impl SceneObjects {
+    /// Adds the object as a root, or replaces the component of an existing
+    /// object keeping its place in the hierarchy
     pub fn add_object(&mut self, name: &'static str, object: impl Into<Component>) {
-        self.objects.insert(name, object.into());
+        let object = object.into();
+        let layer = object.default_layer();
+        self.objects.insert(name, object);
+        self.nodes.entry(name).or_insert_with(|| SceneNode {
+            layer,
+            ..Default::default()
+        });
+    }
+
+    pub fn node(&self, name: &str) -> Option<&SceneNode> {
+        self.nodes.get(name)
+    }
+
+    pub fn parent(&self, name: &str) -> Option<&'static str> {
+        self.nodes.get(name).and_then(|x| x.parent)
+    }
+
+    /// Direct children of the object
+    pub fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'static str> + 'a {
+        self.nodes
+            .iter()
+            .filter(move |(_, node)| node.parent == Some(name))
+            .map(|(&child, _)| child)
+    }
+
+    /// Makes `parent` the parent of `child`, the local transform of the child
+    /// is kept. Fails if either object is missing or the parent is a
+    /// descendant of the child.
+    pub fn set_parent(&mut self, child: &str, parent: &'static str) -> Result<(), String> {
+        if !self.nodes.contains_key(parent) {
+            return Err(format!("no object named {parent}"));
+        }
+        let mut ancestor = Some(parent);
+        while let Some(name) = ancestor {
+            if name == child {
+                return Err(format!("{parent} is a descendant of {child}"));
+            }
+            ancestor = self.parent(name);
+        }
+
+        let node = self
+            .nodes
+            .get_mut(child)
+            .ok_or_else(|| format!("no object named {child}"))?;
+        node.parent = Some(parent);
+        Ok(())
+    }
+
+    /// Turns the object into a root without moving it in the world
+    pub fn detach(&mut self, name: &str) -> Result<(), String> {
+        let world = self.world_transform(name);
+        let node = self
+            .nodes
+            .get_mut(name)
+            .ok_or_else(|| format!("no object named {name}"))?;
+        node.parent = None;
+        node.local = ObjectTransform::from_matrix(world);
+        Ok(())
+    }
+
+    pub fn set_visible(&mut self, name: &str, visible: bool) -> Result<(), String> {
+        let node = self
+            .nodes
+            .get_mut(name)
+            .ok_or_else(|| format!("no object named {name}"))?;
+        node.visible = visible;
+        Ok(())
+    }
+
+    pub fn set_layer(&mut self, name: &str, layer: &'static str) -> Result<(), String> {
+        let node = self
+            .nodes
+            .get_mut(name)
+            .ok_or_else(|| format!("no object named {name}"))?;
+        node.layer = layer;
+        Ok(())
+    }
+
+    pub fn layer(&self, name: &str) -> &'static str {
+        self.nodes.get(name).map_or(DEFAULT_LAYER, |x| x.layer)
+    }
+
+    /// Whether the object and all its ancestors are visible
+    pub fn is_visible(&self, name: &str) -> bool {
+        let mut current = self.nodes.get(name);
+        while let Some(node) = current {
+            if !node.visible {
+                return false;
+            }
+            current = node.parent.and_then(|x| self.nodes.get(x));
+        }
+        true
+    }
+
+    /// Objects that are drawn, see [`Self::is_visible`]
+    pub fn visible(&self) -> impl Iterator<Item = (&'static str, &Component)> {
+        self.objects
+            .iter()
+            .filter(|(name, _)| self.is_visible(name))
+            .map(|(&name, x)| (name, x))
+    }
+
+    pub fn local_transform_mut(&mut self, name: &str) -> Option<&mut ObjectTransform> {
+        self.nodes.get_mut(name).map(|x| &mut x.local)
+    }
+
+    /// Local transforms of the object and its ancestors composed from the
+    /// root down
+    pub fn world_transform(&self, name: &str) -> Mat4 {
+        let mut world = Mat4::IDENTITY;
+        let mut current = self.nodes.get(name);
+        while let Some(node) = current {
+            world = node.local.matrix() * world;
+            current = node.parent.and_then(|x| self.nodes.get(x));
+        }
+        world
+    }
+
+    /// Removes the object together with its descendants and returns them
+    pub fn remove_object(&mut self, name: &str) -> Vec<(&'static str, Component)> {
+        let mut removed = Vec::new();
+        let mut pending = vec![name];
+        while let Some(name) = pending.pop() {
+            let Some((name, component)) = self.objects.remove_entry(name) else {
+                continue;
+            };
+            self.nodes.remove(name);
+            pending.extend(self.children(name).collect::<Vec<_>>());
+            removed.push((name, component));
+        }
+        removed
+    }
+
+    /// Removes every object of the given [`Component::kind`] with their
+    /// descendants
+    pub fn remove_kind(&mut self, kind: &str) -> Vec<(&'static str, Component)> {
+        let names: Vec<_> = self
+            .objects
+            .iter()
+            .filter(|(_, x)| x.kind() == kind)
+            .map(|(&name, _)| name)
+            .collect();
+        names
+            .into_iter()
+            .flat_map(|name| self.remove_object(name))
+            .collect()
     }
 
-    pub fn remove_object(&mut self, _index: usize) {
-        unimplemented!()
+    /// Removes all objects and returns them
+    pub fn clear(&mut self) -> Vec<(&'static str, Component)> {
+        self.nodes.clear();
+        std::mem::take(&mut self.objects).into_iter().collect()
     }
 
     pub fn get_object(&self, name: &'static str) -> Option<&Component> {
@@ -27,11 +212,36 @@ impl<'a> SceneObjects {
     }
 }
 
+impl SceneObjects {
+    /// Visible light components and the sun moved to world space, `parent`
+    /// is the world matrix of this composite
+    pub fn lights(&self, parent: Mat4) -> impl Iterator<Item = Light> + '_ {
+        self.visible().filter_map(move |(name, x)| {
+            let light = match x {
+                Component::Light(light) => *light,
+                Component::Sun(sun) => Light::from(sun),
+                _ => return None,
+            };
+            Some(light.transformed(parent * self.world_transform(name)))
+        })
+    }
+
+    /// World space box around the visible objects, see [`BoundsVisitor`]
+    pub fn bounds(&self, parent: Mat4) -> Option<BoundingBox> {
+        BoundsVisitor::default()
+            .with_model(parent)
+            .visit_composite(self)
+    }
+}
+
 impl Visitable for SceneObjects {
-    fn accept(&self, visitor: &mut impl Visitor) {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        let mut output = V::Output::default();
         for i in self.objects.values() {
-            i.accept(visitor);
+            let next = i.accept(visitor);
+            visitor.join(&mut output, next);
         }
+        output
     }
 }
 
@@ -49,3 +259,89 @@ impl DerefMut for SceneObjects {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::{Grid, OrientationGizmo};
+    use glam::Vec3;
+
+    #[test]
+    fn test_scene_hierarchy() {
+        let mut objects = SceneObjects::default();
+        for name in ["pivot", "sun", "moon"] {
+            objects.add_object(name, Grid::new(1, 1.0));
+        }
+        *objects.local_transform_mut("pivot").unwrap() = ObjectTransform::from_translation(Vec3::X);
+        *objects.local_transform_mut("sun").unwrap() = ObjectTransform::from_translation(Vec3::Y);
+
+        objects.set_parent("sun", "pivot").unwrap();
+        objects.set_parent("moon", "sun").unwrap();
+        assert!(objects.set_parent("pivot", "moon").is_err());
+        assert!(objects.set_parent("sun", "missing").is_err());
+        assert_eq!(objects.children("pivot").collect::<Vec<_>>(), ["sun"]);
+
+        let moon = objects.world_transform("moon").transform_point3(Vec3::ZERO);
+        assert_eq!(moon, Vec3::new(1.0, 1.0, 0.0));
+
+        objects.detach("sun").unwrap();
+        assert_eq!(objects.parent("sun"), None);
+        let moon = objects.world_transform("moon").transform_point3(Vec3::ZERO);
+        assert_eq!(moon, Vec3::new(1.0, 1.0, 0.0));
+
+        objects
+            .local_transform_mut("pivot")
+            .unwrap()
+            .rotate(glam::Quat::from_rotation_z(std::f32::consts::FRAC_PI_2));
+        objects.set_parent("sun", "pivot").unwrap();
+        let sun = objects.world_transform("sun").transform_point3(Vec3::ZERO);
+        assert!(sun.abs_diff_eq(Vec3::Y, 1e-5));
+    }
+
+    #[test]
+    fn test_visibility() {
+        let mut objects = SceneObjects::default();
+        for name in ["pivot", "sun", "grid"] {
+            objects.add_object(name, Grid::new(1, 1.0));
+        }
+        objects.set_parent("sun", "pivot").unwrap();
+        objects.set_visible("pivot", false).unwrap();
+        assert!(objects.set_visible("missing", false).is_err());
+
+        assert!(!objects.is_visible("sun"));
+        let names = objects.visible().map(|(x, _)| x).collect::<Vec<_>>();
+        assert_eq!(names, ["grid"]);
+
+        objects.set_visible("pivot", true).unwrap();
+        assert!(objects.is_visible("sun"));
+
+        objects.add_object("gizmo", OrientationGizmo::default());
+        assert_eq!(objects.layer("gizmo"), DEBUG_LAYER);
+        assert_eq!(objects.layer("sun"), DEFAULT_LAYER);
+        objects.set_layer("sun", DEBUG_LAYER).unwrap();
+        assert_eq!(objects.layer("sun"), DEBUG_LAYER);
+    }
+
+    #[test]
+    fn test_remove_object() {
+        let mut objects = SceneObjects::default();
+        for name in ["pivot", "sun", "moon", "grid"] {
+            objects.add_object(name, Grid::new(1, 1.0));
+        }
+        objects.set_parent("sun", "pivot").unwrap();
+        objects.set_parent("moon", "sun").unwrap();
+
+        let mut removed = objects
+            .remove_object("pivot")
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        removed.sort_unstable();
+        assert_eq!(removed, ["moon", "pivot", "sun"]);
+        assert_eq!(objects.keys().copied().collect::<Vec<_>>(), ["grid"]);
+        assert_eq!(objects.nodes.len(), 1);
+        assert!(objects.remove_object("pivot").is_empty());
+
+        assert_eq!(objects.remove_kind("grid").len(), 1);
+        assert!(objects.clear().is_empty());
+    }
+}
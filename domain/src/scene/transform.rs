@@ -0,0 +1,61 @@
+use glam::{Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// Translation, rotation and scale of a scene object, applied in the order
+/// scale, rotation, translation. The projection onto the screen is the
+/// [`Transform`](crate::math::Transform).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ObjectTransform {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for ObjectTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl ObjectTransform {
+    pub const IDENTITY: Self = Self {
+        translation: Vec3::ZERO,
+        rotation: Quat::IDENTITY,
+        scale: Vec3::ONE,
+    };
+
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            translation,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Decomposes an affine matrix, shear is lost
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let (scale, rotation, translation) = matrix.to_scale_rotation_translation();
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    #[inline]
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_scale_rotation_translation(self.scale, self.rotation, self.translation)
+    }
+
+    pub fn translate(&mut self, offset: Vec3) {
+        self.translation += offset;
+    }
+
+    /// Rotates around the object origin
+    pub fn rotate(&mut self, rotation: Quat) {
+        self.rotation = (rotation * self.rotation).normalize();
+    }
+
+    pub fn scale(&mut self, factor: Vec3) {
+        self.scale *= factor;
+    }
+}
@@ -0,0 +1,96 @@
+//! World space box around the scene, used to frame it with the camera and to
+//! fit the clip planes
+
+use glam::{Mat4, Vec2};
+
+use crate::object::objects::{BoundingBox, Cloud, Grid, Mesh, Terrain, Water};
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::{visit_visible, Visitor, WorldVisitor};
+
+/// Union of the world space boxes of the visible objects that have a size.
+/// Suns, lights and the helpers drawn on screen are skipped.
+#[derive(Default)]
+pub struct BoundsVisitor {
+    /// World matrix of the object being visited
+    model: Mat4,
+}
+
+impl BoundsVisitor {
+    /// World matrix of the visited composite
+    pub fn with_model(mut self, model: Mat4) -> Self {
+        self.model = model;
+        self
+    }
+
+    fn world(&self, local: &BoundingBox) -> Option<BoundingBox> {
+        Some(local.transformed(self.model))
+    }
+}
+
+impl WorldVisitor for BoundsVisitor {
+    fn model_mut(&mut self) -> &mut Mat4 {
+        &mut self.model
+    }
+}
+
+impl Visitor for BoundsVisitor {
+    /// Box around the visited objects, `None` if none of them has a size
+    type Output = Option<BoundingBox>;
+
+    fn join(&mut self, output: &mut Option<BoundingBox>, next: Option<BoundingBox>) {
+        *output = match (*output, next) {
+            (Some(a), Some(b)) => Some(a.union(&b)),
+            (a, b) => a.or(b),
+        };
+    }
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) -> Option<BoundingBox> {
+        visit_visible(self, scene_objects)
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) -> Option<BoundingBox> {
+        self.world(&cloud.obb().aabb())
+    }
+
+    /// The grid is endless, its scale is taken as the extent
+    fn visit_grid(&mut self, grid: &Grid) -> Option<BoundingBox> {
+        let extent = Vec2::splat(grid.scale);
+        let (min, max) = (grid.plane.unproject(-extent), grid.plane.unproject(extent));
+        self.world(&BoundingBox::from_two_pos(min, max))
+    }
+
+    fn visit_terrain(&mut self, terrain: &Terrain) -> Option<BoundingBox> {
+        self.world(&terrain.bounding_box)
+    }
+
+    fn visit_water(&mut self, water: &Water) -> Option<BoundingBox> {
+        self.world(&water.bounding_box)
+    }
+
+    fn visit_mesh(&mut self, mesh: &Mesh) -> Option<BoundingBox> {
+        self.world(&mesh.bounding_box())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::ObjectTransform;
+    use glam::Vec3;
+
+    #[test]
+    fn test_scene_bounds() {
+        let mut objects = SceneObjects::default();
+        assert!(objects.bounds(Mat4::IDENTITY).is_none());
+        objects.add_object("box", Mesh::cuboid(Vec3::ONE));
+        objects.add_object("far", Mesh::cuboid(Vec3::ONE));
+        *objects.local_transform_mut("far").unwrap() =
+            ObjectTransform::from_translation(Vec3::new(4.0, 0.0, 0.0));
+        objects.add_object("hidden", Mesh::cuboid(Vec3::splat(100.0)));
+        objects.set_visible("hidden", false).unwrap();
+
+        let bounds = objects.bounds(Mat4::IDENTITY).unwrap();
+        assert!(bounds.min.abs_diff_eq(Vec3::splat(-0.5), 1e-5));
+        assert!(bounds.max.abs_diff_eq(Vec3::new(4.5, 0.5, 0.5), 1e-5));
+    }
+}
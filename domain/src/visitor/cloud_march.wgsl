@@ -0,0 +1,297 @@
+// Cloud march of one view ray per pixel. Mirrors `Cloud::march_aligned` of
+// cloud.rs, which stays the reference; the rays come in the space of the
+// axis aligned volume.
+
+struct Params {
+    // xyz: volume, w: edge distance
+    bb_min: vec4<f32>,
+    // xyz: volume, w: height map factor
+    bb_max: vec4<f32>,
+    // xyz: offset, w: cloud scale
+    offset: vec4<f32>,
+    // xyz: detail offset, w: detail noise scale
+    detail_offset: vec4<f32>,
+    shape_weights: vec4<f32>,
+    detail_weights: vec4<f32>,
+    phase_params: vec4<f32>,
+    // density offset, density multiplier, detail noise weight, glow focus
+    density: vec4<f32>,
+    // toward the sun, through the cloud, darkness threshold
+    absorption: vec4<f32>,
+    origin: vec4<f32>,
+    tint: vec4<f32>,
+    // steps, light steps, auto steps, light interval
+    steps: vec4<u32>,
+    // width, height, lights
+    size: vec4<u32>,
+}
+
+struct Light {
+    // xyz: position of a point light (w = 1) or direction of a directional one
+    position: vec4<f32>,
+    // xyz: color times intensity, w: absorption
+    radiance: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> lights: array<Light>;
+@group(0) @binding(2) var<storage, read> dirs: array<vec4<f32>>;
+@group(0) @binding(3) var<storage, read_write> pixels: array<u32>;
+@group(0) @binding(4) var shape_noise: texture_3d<f32>;
+@group(0) @binding(5) var detail_noise: texture_3d<f32>;
+@group(0) @binding(6) var weather_map: texture_3d<f32>;
+
+const PI: f32 = 3.14159265358979;
+// Lights whose phase and light samples are kept along a ray
+const CACHED_LIGHTS: u32 = 8u;
+
+// Nearest texel, wrapped around, as `Texture3D::sample_level`
+fn sample_level(t: texture_3d<f32>, uvw: vec3<f32>) -> vec4<f32> {
+    let size = vec3<i32>(textureDimensions(t));
+    let texel = vec3<i32>(uvw * vec3<f32>(size));
+    return textureLoad(t, (texel % size + size) % size, 0);
+}
+
+fn remap(v: f32, min_old: f32, max_old: f32, min_new: f32, max_new: f32) -> f32 {
+    return min_new + (max_new - min_new) * ((v - min_old) / (max_old - min_old));
+}
+
+fn box_dst(origin: vec3<f32>, dir: vec3<f32>) -> vec2<f32> {
+    let t0 = (params.bb_min.xyz - origin) / dir;
+    let t1 = (params.bb_max.xyz - origin) / dir;
+    let tmin = min(t0, t1);
+    let tmax = max(t0, t1);
+    let dst_a = max(max(tmin.x, tmin.y), tmin.z);
+    let dst_b = min(min(tmax.x, tmax.y), tmax.z);
+    let dst_to_box = max(0.0, dst_a);
+    return vec2(dst_to_box, max(0.0, dst_b - dst_to_box));
+}
+
+fn sample_density(p: vec3<f32>) -> f32 {
+    let uvw = p * params.offset.w * (1.0 / 1000.0) + params.offset.xyz * (1.0 / 100.0);
+    let shape = abs(sample_level(shape_noise, uvw));
+
+    let bb_min = params.bb_min.xyz;
+    let bb_max = params.bb_max.xyz;
+    let size = bb_max - bb_min;
+    let center = 0.5 * (bb_max + bb_min);
+    let edge = params.bb_min.w;
+    let dst_from_edge_x = min(min(p.x - bb_min.x, bb_max.x - p.x), edge);
+    let dst_from_edge_z = min(min(p.z - bb_min.z, bb_max.z - p.z), edge);
+    let edge_weight = min(dst_from_edge_x, dst_from_edge_z) / edge;
+
+    let weather_uv = (size.xz * 0.5 + (p.xz - center.xz)) / max(size.x, size.z);
+    let weather = sample_level(weather_map, vec3(weather_uv.x, 0.0, weather_uv.y)).x * 0.5;
+    let g_min = remap(weather, 0.0, 1.0, 0.1, 0.5);
+    let g_max = remap(weather, 0.0, 1.0, g_min, 0.9);
+
+    let height_percent = (p.y - bb_min.y) / size.y;
+    let height_gradient = clamp(remap(height_percent, 0.0, g_min, 0.0, 1.0), 0.0, 1.0)
+        * clamp(remap(height_percent, 1.0, g_max, 0.0, 1.0), 0.0, 1.0)
+        * edge_weight * params.bb_max.w;
+
+    let shape_weights = params.shape_weights / dot(params.shape_weights, vec4(1.0));
+    let shape_fbm = dot(shape, shape_weights) * height_gradient;
+    let base_shape_density = shape_fbm + params.density.x * 0.1;
+    if base_shape_density <= 0.0 {
+        return 0.0;
+    }
+
+    let detail_pos = uvw * params.detail_offset.w + params.detail_offset.xyz * (1.0 / 100.0)
+        + params.offset.xyz * (1.0 / 100.0);
+    let detail = abs(sample_level(detail_noise, detail_pos));
+    let detail_weights = params.detail_weights / dot(params.detail_weights, vec4(1.0));
+    let detail_fbm = dot(detail, detail_weights);
+    let one_minus_shape = 1.0 - shape_fbm;
+    let detail_erode_weight = one_minus_shape * one_minus_shape * one_minus_shape;
+    let cloud_density = base_shape_density
+        - (1.0 - detail_fbm) * detail_erode_weight * params.density.z;
+    return cloud_density * params.density.y;
+}
+
+fn dir_to_light(light: Light, p: vec3<f32>) -> vec3<f32> {
+    if light.position.w == 0.0 {
+        return -light.position.xyz;
+    }
+    let to_light = light.position.xyz - p;
+    let distance = length(to_light);
+    return select(vec3(0.0), to_light / distance, distance > 0.0);
+}
+
+fn radiance(light: Light, p: vec3<f32>) -> vec3<f32> {
+    var falloff = 1.0;
+    if light.position.w != 0.0 {
+        let offset = light.position.xyz - p;
+        falloff = 1.0 / (1.0 + dot(offset, offset));
+    }
+    return light.radiance.xyz * falloff;
+}
+
+fn hg(a: f32, g: f32) -> f32 {
+    let g2 = g * g;
+    return (1.0 - g2) / (4.0 * PI * pow(1.0 + g2 - 2.0 * g * a, 1.5));
+}
+
+fn phase(a: f32) -> f32 {
+    let p = params.phase_params;
+    let hg_blend = hg(a, p.x) * 0.5 + hg(a, p.y) * 0.5;
+    return p.y + hg_blend * p.z;
+}
+
+fn light_transmittance(start: vec3<f32>, dir: vec3<f32>, absorption: f32) -> f32 {
+    let steps = params.steps.y;
+    let step_size = box_dst(start, dir).y / f32(steps);
+    var p = start + dir * step_size;
+    var total_density = 0.0;
+    for (var i = 0u; i < steps; i++) {
+        total_density += max(sample_density(p), 0.0);
+        p += dir * step_size;
+    }
+    let transmittance = exp(-(total_density * absorption * step_size));
+    return transmittance + (1.0 - transmittance) * params.absorption.z;
+}
+
+fn ray_steps(chord: f32) -> u32 {
+    let num_steps = max(params.steps.x, 1u);
+    if params.steps.z == 0u {
+        return num_steps;
+    }
+    let size = params.bb_max.xyz - params.bb_min.xyz;
+    let diagonal = max(length(size), 1.1920929e-7);
+    let steps = u32(ceil(f32(params.steps.x) * chord / diagonal));
+    return clamp(steps, 1u, num_steps);
+}
+
+fn march(origin: vec3<f32>, dir: vec3<f32>) -> u32 {
+    let ray_box_info = box_dst(origin, dir);
+    let dst_to_box = ray_box_info.x;
+    let dst_limit = ray_box_info.y;
+    if dst_limit <= 0.0 {
+        return 0u;
+    }
+
+    let step_size = dst_limit / f32(ray_steps(dst_limit));
+    var transmittance = 1.0;
+    var light_energy = vec3(0.0);
+    let entry_point = origin + dst_to_box * dir;
+    let num_lights = params.size.z;
+    let toward_sun = params.absorption.x;
+
+    // The angle to a directional light stays the same along the ray
+    var phases: array<f32, CACHED_LIGHTS>;
+    var cached: array<bool, CACHED_LIGHTS>;
+    for (var k = 0u; k < min(num_lights, CACHED_LIGHTS); k++) {
+        let light = lights[k];
+        if light.position.w == 0.0 {
+            phases[k] = phase(dot(dir, dir_to_light(light, entry_point)));
+            cached[k] = true;
+        }
+    }
+
+    // Light samples at the ends of the intervals of steps
+    let interval = max(params.steps.w, 1u);
+    var segments: array<i32, CACHED_LIGHTS>;
+    var starts: array<f32, CACHED_LIGHTS>;
+    var ends: array<f32, CACHED_LIGHTS>;
+    for (var k = 0u; k < CACHED_LIGHTS; k++) {
+        segments[k] = -1;
+    }
+
+    var dst_travelled = 0.0;
+    var step = 0u;
+    while dst_travelled < dst_limit {
+        let ray_pos = entry_point + dir * dst_travelled;
+        let density = sample_density(ray_pos);
+        if density > 0.1 {
+            for (var k = 0u; k < num_lights; k++) {
+                let light = lights[k];
+                let to_light = dir_to_light(light, ray_pos);
+                let absorption = toward_sun * light.radiance.w;
+                var light_phase: f32;
+                if k < CACHED_LIGHTS && cached[k] {
+                    light_phase = phases[k];
+                } else {
+                    light_phase = phase(dot(dir, to_light));
+                }
+
+                var to_light_transmittance: f32;
+                if k < CACHED_LIGHTS && interval > 1u {
+                    let segment = step / interval;
+                    if segments[k] != i32(segment) {
+                        if segments[k] >= 0 && u32(segments[k]) + 1u == segment {
+                            starts[k] = ends[k];
+                        } else {
+                            let p = entry_point
+                                + dir * min(f32(segment * interval) * step_size, dst_limit);
+                            starts[k] = light_transmittance(p, dir_to_light(light, p), absorption);
+                        }
+                        let p = entry_point
+                            + dir * min(f32((segment + 1u) * interval) * step_size, dst_limit);
+                        ends[k] = light_transmittance(p, dir_to_light(light, p), absorption);
+                        segments[k] = i32(segment);
+                    }
+                    let t = f32(step % interval) / f32(interval);
+                    to_light_transmittance = starts[k] + (ends[k] - starts[k]) * t;
+                } else {
+                    to_light_transmittance = light_transmittance(ray_pos, to_light, absorption);
+                }
+
+                light_energy += density * step_size * transmittance * to_light_transmittance
+                    * light_phase * radiance(light, ray_pos);
+            }
+            transmittance *= exp(-(density * step_size * params.absorption.y));
+            if transmittance < 0.01 {
+                break;
+            }
+        }
+        dst_travelled += step_size;
+        step += 1u;
+    }
+
+    // Lights seen through the thin parts of the cloud
+    let focus = params.density.w;
+    let tint = params.tint.xyz;
+    var glow = 0.0;
+    var glow_color = vec3(0.0);
+    // A negative base with a fractional exponent has no power, the CPU
+    // gets NaN and a black pixel
+    var undefined = false;
+    for (var k = 0u; k < num_lights; k++) {
+        let light = lights[k];
+        let cos_angle = clamp(dot(dir, dir_to_light(light, entry_point)), -1.0, 1.0);
+        var focused_eye_cos = 1.0;
+        if focus != 0.0 {
+            if cos_angle >= 0.0 {
+                focused_eye_cos = pow(cos_angle, focus);
+            } else if fract(focus) == 0.0 {
+                let power = pow(-cos_angle, focus);
+                focused_eye_cos = select(power, -power, (i32(focus) & 1) != 0);
+            } else {
+                undefined = true;
+            }
+        }
+        let sun = clamp(hg(focused_eye_cos, params.phase_params.w), -1.0, 1.0) * transmittance;
+        glow += sun;
+        glow_color += tint * radiance(light, entry_point) * sun;
+    }
+
+    let cloud_col = light_energy * tint;
+    var col = clamp(cloud_col, vec3(0.0), vec3(1.0)) * (1.0 - clamp(glow, 0.0, 1.0)) + glow_color;
+    col = clamp(col, vec3(0.0), vec3(1.0));
+    if undefined {
+        col = vec3(0.0);
+    }
+    let rgb = vec3<u32>(col * 255.0);
+    let alpha = u32(255.0 * (1.0 - transmittance));
+    return rgb.r | (rgb.g << 8u) | (rgb.b << 16u) | (alpha << 24u);
+}
+
+@compute @workgroup_size(8, 8)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let width = params.size.x;
+    if id.x >= width || id.y >= params.size.y {
+        return;
+    }
+    let index = id.y * width + id.x;
+    pixels[index] = march(params.origin.xyz, dirs[index].xyz);
+}
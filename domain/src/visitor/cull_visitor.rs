@@ -0,0 +1,139 @@
+//! Objects that are not drawn since they are off screen or too far away
+
+use std::collections::BTreeSet;
+
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::object::camera::Camera;
+use crate::object::objects::{BoundingBox, Cloud, Mesh, Terrain, Water};
+use crate::object::Component;
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::{visit_visible, Visitor, WorldVisitor};
+
+/// Names the visible objects whose volume lies outside the camera frustum or
+/// farther than the draw distance. Objects without a volume, such as the
+/// grid or the sun, are never culled.
+pub struct CullVisitor {
+    view_projection: Mat4,
+    eye: Vec3,
+    max_distance: f32,
+    /// World matrix of the object being visited
+    model: Mat4,
+    id: &'static str,
+}
+
+impl CullVisitor {
+    pub fn new(camera: &Camera, width: f32, height: f32) -> Self {
+        Self {
+            view_projection: camera.projection(width, height) * camera.view(),
+            eye: camera.pos(),
+            max_distance: f32::INFINITY,
+            model: Mat4::IDENTITY,
+            id: "",
+        }
+    }
+
+    /// Objects farther than the distance from the eye are culled
+    pub fn with_max_distance(mut self, max_distance: f32) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    fn is_culled(&self, local: &BoundingBox) -> bool {
+        let world = local.transformed(self.model);
+        let nearest = self.eye.clamp(world.min, world.max);
+        if nearest.distance(self.eye) > self.max_distance {
+            return true;
+        }
+        // The box is outside when all its corners are beyond the same plane
+        // of the frustum
+        let clip = world
+            .corners()
+            .map(|x| self.view_projection * x.extend(1.0));
+        let outside = |plane: fn(Vec4) -> bool| clip.iter().all(|&x| plane(x));
+        outside(|x| x.x < -x.w)
+            || outside(|x| x.x > x.w)
+            || outside(|x| x.y < -x.w)
+            || outside(|x| x.y > x.w)
+            || outside(|x| x.z < 0.0)
+            || outside(|x| x.z > x.w)
+    }
+
+    fn cull(&self, local: &BoundingBox) -> BTreeSet<&'static str> {
+        let mut culled = BTreeSet::new();
+        if self.is_culled(local) {
+            culled.insert(self.id);
+        }
+        culled
+    }
+}
+
+impl WorldVisitor for CullVisitor {
+    fn model_mut(&mut self) -> &mut Mat4 {
+        &mut self.model
+    }
+
+    fn enter(
+        &mut self,
+        _scene_objects: &SceneObjects,
+        id: &'static str,
+        _object: &Component,
+    ) -> bool {
+        self.id = id;
+        true
+    }
+}
+
+impl Visitor for CullVisitor {
+    type Output = BTreeSet<&'static str>;
+
+    fn join(&mut self, output: &mut BTreeSet<&'static str>, next: BTreeSet<&'static str>) {
+        output.extend(next);
+    }
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) -> BTreeSet<&'static str> {
+        visit_visible(self, scene_objects)
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) -> BTreeSet<&'static str> {
+        self.cull(&cloud.obb().aabb())
+    }
+
+    fn visit_terrain(&mut self, terrain: &Terrain) -> BTreeSet<&'static str> {
+        self.cull(&terrain.bounding_box)
+    }
+
+    fn visit_water(&mut self, water: &Water) -> BTreeSet<&'static str> {
+        self.cull(&water.bounding_box)
+    }
+
+    fn visit_mesh(&mut self, mesh: &Mesh) -> BTreeSet<&'static str> {
+        self.cull(&mesh.bounding_box())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::Grid;
+    use crate::scene::ObjectTransform;
+
+    #[test]
+    fn test_culling() {
+        let camera = Camera::default();
+        let mut objects = SceneObjects::default();
+        objects.add_object("grid", Grid::new(10, 1.0));
+        objects.add_object("seen", Mesh::cuboid(Vec3::ONE));
+        objects.add_object("behind", Mesh::cuboid(Vec3::ONE));
+        *objects.local_transform_mut("behind").unwrap() =
+            ObjectTransform::from_translation(camera.pos() * 2.0);
+
+        let culled = CullVisitor::new(&camera, 640.0, 480.0).visit_composite(&objects);
+        assert_eq!(culled.into_iter().collect::<Vec<_>>(), ["behind"]);
+
+        let culled = CullVisitor::new(&camera, 640.0, 480.0)
+            .with_max_distance(1.0)
+            .visit_composite(&objects);
+        assert_eq!(culled.len(), 2);
+    }
+}
@@ -0,0 +1,122 @@
+//! Order of the translucent objects for painting them back to front
+
+use std::cmp::Ordering;
+
+use glam::{Mat4, Vec3};
+
+use crate::object::objects::{BoundingBox, Cloud, Fog, Water};
+use crate::object::Component;
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::{visit_visible, Visitor, WorldVisitor};
+
+/// Translucent objects of the scene, clouds, fog and water, with their
+/// distances to the eye, from the farthest one. Unlike the draw order of a
+/// single composite, the objects of all nested composites are sorted
+/// together.
+pub struct DepthSortVisitor {
+    eye: Vec3,
+    /// World matrix of the object being visited
+    model: Mat4,
+    id: &'static str,
+}
+
+impl DepthSortVisitor {
+    pub fn new(eye: Vec3) -> Self {
+        Self {
+            eye,
+            model: Mat4::IDENTITY,
+            id: "",
+        }
+    }
+
+    /// World matrix of the visited composite
+    pub fn with_model(mut self, model: Mat4) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Distance from the eye to the center of the box in world space
+    fn distance(&self, local: &BoundingBox) -> Vec<(&'static str, f32)> {
+        let center = local.transformed(self.model).center();
+        vec![(self.id, center.distance(self.eye))]
+    }
+}
+
+impl WorldVisitor for DepthSortVisitor {
+    fn model_mut(&mut self) -> &mut Mat4 {
+        &mut self.model
+    }
+
+    fn enter(
+        &mut self,
+        _scene_objects: &SceneObjects,
+        id: &'static str,
+        _object: &Component,
+    ) -> bool {
+        self.id = id;
+        true
+    }
+}
+
+impl Visitor for DepthSortVisitor {
+    type Output = Vec<(&'static str, f32)>;
+
+    fn join(&mut self, output: &mut Self::Output, next: Self::Output) {
+        output.extend(next);
+    }
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) -> Self::Output {
+        let mut objects = visit_visible(self, scene_objects);
+        objects.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(Ordering::Equal));
+        objects
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) -> Self::Output {
+        self.distance(&cloud.obb().aabb())
+    }
+
+    fn visit_water(&mut self, water: &Water) -> Self::Output {
+        self.distance(&water.bounding_box)
+    }
+
+    /// The fog is a horizontal layer, so it is as far as its base height
+    fn visit_fog(&mut self, fog: &Fog) -> Self::Output {
+        let fog = fog.transformed(self.model);
+        vec![(self.id, (self.eye.y - fog.height).abs())]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::CloudBuilder;
+    use crate::object::objects::Grid;
+    use crate::scene::ObjectTransform;
+
+    fn cloud() -> Cloud {
+        let cloud = CloudBuilder::default().with_bounding_box((Vec3::ZERO, Vec3::ONE));
+        Cloud::with_volumes(
+            cloud,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        )
+    }
+
+    #[test]
+    fn test_back_to_front() {
+        let mut group = SceneObjects::default();
+        group.add_object("far", cloud());
+        let mut objects = SceneObjects::default();
+        objects.add_object("grid", Grid::new(10, 1.0));
+        objects.add_object("near", cloud());
+        objects.add_object("group", Component::Composite(group));
+        *objects.local_transform_mut("group").unwrap() =
+            ObjectTransform::from_translation(Vec3::new(-10.0, 0.0, 0.0));
+
+        let order = DepthSortVisitor::new(Vec3::new(5.0, 0.5, 0.5)).visit_composite(&objects);
+        let names: Vec<_> = order.iter().map(|(id, _)| *id).collect();
+        assert_eq!(names, ["far", "near"]);
+        assert!((order[0].1 - 14.5).abs() < 1e-4);
+    }
+}
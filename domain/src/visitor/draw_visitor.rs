@@ -1,297 +1,668 @@
-use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::any::Any;
+use std::collections::BTreeSet;
 use std::ops::Sub;
-use std::sync::{Arc, Mutex};
+use std::sync::Arc;
 
-use egui::{Color32, Pos2, Stroke, TextureId};
-use glam::{Vec3, Vec4, Vec4Swizzles};
+use egui::{Color32, ColorImage, Pos2, Stroke, TextureId};
+use glam::{Mat4, Vec3};
 use log::debug;
 
-use crate::canvas::painter::Painter3D;
+use crate::canvas::painter::{LineStyle, Occlusion, Painter3D};
+use crate::canvas::render_thread::RenderThread;
+use crate::managers::cache_manager::{cache_key, combine_keys, CacheKey, CacheManager};
+use crate::managers::profiling_manager::{Profiler, ScopeTimer, Stage};
 use crate::math::Transform;
-use crate::object::camera::Camera;
-use crate::object::objects::{BoundingBox, Cloud, Grid, Sun, Terrain};
-use crate::object::objects::cloud::{beer, hg, phase};
-use crate::scene::scene_composite::SceneObjects;
-use crate::visitor::{Visitable, Visitor};
+use crate::object::camera::{Camera, ViewRays};
+use crate::object::objects::cloud::CloudBuilder;
+use crate::object::objects::density_field::DENSITY_FIELD_SIZE;
+use crate::object::objects::occupancy_grid::OCCUPANCY_GRID_SIZE;
+use crate::object::objects::{
+    Background, BoundingBox, Cloud, DensityField, Fog, GizmoMode, Grid, Light, LightKind, Mesh,
+    OccupancyGrid, OrientationGizmo, Skybox, Sun, Terrain, TransformGizmo, Water,
+};
+use crate::managers::render_manager::{PixelBuffer, RenderPass, RenderPlan};
+use crate::object::plugin::PluginComponent;
+use crate::object::Component;
+use crate::scene::scene_composite::{SceneObjects, DEBUG_LAYER};
+use crate::visitor::lod_visitor::Lod;
+use crate::visitor::raster::{
+    cloud_shadow, convex_hull, rasterize_terrain, sky_color, view_ray_dir, CloudMarch,
+};
+use crate::visitor::{draw_order, find_visible, sun_visibility, world_sun, Visitable, Visitor};
 
 pub struct DrawVisitor<'a> {
     canvas: &'a Painter3D,
     camera: &'a Camera,
     stroke: Stroke,
+    line_style: LineStyle,
+    occluders: Vec<BoundingBox>,
+    lights: Vec<Light>,
+    background_filled: bool,
+    /// World matrix of the object being visited
+    model: Mat4,
+    view_projection: Transform,
     mvp: Transform,
+    /// Render layers that are not drawn
+    hidden_layers: BTreeSet<&'static str>,
+    /// Share of the sunlight passing through the clouds of the composite
+    sun_visibility: f32,
+    /// First sun of the scene in world space, the terrain, water and sky are
+    /// lit by it
+    sun: Option<Sun>,
+    fog: Option<Fog>,
+    /// Pass whose components are drawn, all of them when `None`
+    pass: Option<RenderPass>,
+    /// Objects outlined in the overlay pass
+    selection: Vec<&'static str>,
+    /// Culled objects, depth order and quality levels of the frame. The
+    /// clouds of the depth order are painted after the rest of the scene;
+    /// water stays the floor and fog is applied per pixel.
+    plan: RenderPlan,
+    /// Name of the object being visited
+    id: &'static str,
+    /// Composites entered, the root one is the first
+    nesting: usize,
+    /// Keeps the cloud images of the previous frames
+    cache: Option<&'a CacheManager>,
+    /// Reused for the raymarched images instead of allocating them per frame
+    pixels: Option<&'a mut PixelBuffer>,
+    /// Largest side of a cloud image, larger clouds on screen are marched at
+    /// a lower resolution and stretched
+    cloud_resolution: usize,
+    /// Marches the clouds away from the frame when set
+    render_thread: Option<&'a RenderThread>,
+    /// Marches the clouds on the GPU, see [`CloudMarch::gpu`]
+    gpu_clouds: bool,
+    /// Marches only the changed part of a cloud when the lights change, see
+    /// [`CloudMarch::base`]
+    partial_redraw: bool,
+    /// Timings of the stages of the frame
+    profiler: Option<Arc<Profiler>>,
 }
 
+/// Default largest side of a cloud image, whatever its size on screen
+pub const MAX_CLOUD_RESOLUTION: usize = 512;
+
+/// Uploaded image of a cloud, reused until the cloud, its lighting or the
+/// camera change
+struct CloudTexture(egui::TextureHandle);
+
+/// Last image of a cloud, kept for [`CloudMarch::base`] under the key of
+/// everything but the lights. It is taken out of the [`PixelBuffer`], so the
+/// buffer is not copied to draw the next image.
+struct CloudImage(Arc<ColorImage>);
+
 impl<'a> DrawVisitor<'a> {
     pub fn new(camera: &'a Camera, canvas: &'a Painter3D) -> Self {
         let resp_rect = canvas.resp_rect().sub((-8.0).into());
         let proj = camera.projection(resp_rect.width(), resp_rect.height());
-        let camera_tf = proj * camera.view();
+        let mvp = Transform::new(proj * camera.view(), resp_rect);
 
-        let res = Self {
+        Self {
             canvas,
             camera,
             stroke: Stroke::new(1.0, Color32::GRAY),
-            mvp: Transform::new(camera_tf, resp_rect),
-        };
-        res.visit_sky();
-        res
+            line_style: LineStyle::default(),
+            occluders: Vec::new(),
+            lights: Vec::new(),
+            background_filled: false,
+            model: Mat4::IDENTITY,
+            view_projection: mvp,
+            mvp,
+            hidden_layers: BTreeSet::new(),
+            sun_visibility: 1.0,
+            sun: None,
+            fog: None,
+            pass: None,
+            selection: Vec::new(),
+            plan: RenderPlan::default(),
+            id: "",
+            nesting: 0,
+            cache: None,
+            pixels: None,
+            cloud_resolution: MAX_CLOUD_RESOLUTION,
+            render_thread: None,
+            gpu_clouds: false,
+            partial_redraw: false,
+            profiler: None,
+        }
     }
 
     pub fn with_stroke(mut self, stroke: Stroke) -> Self {
         self.stroke = stroke;
         self
     }
-}
 
-impl<'a> Visitor for DrawVisitor<'a> {
-    fn visit_composite(&mut self, scene_objects: &SceneObjects) {
-        let mut objs = scene_objects.values().collect::<Vec<_>>();
-        objs.sort_by(|x, y| {
-            (y.pos() - self.camera.pos())
-                .length()
-                .partial_cmp(&(x.pos() - self.camera.pos()).length())
-                .unwrap_or(Ordering::Greater)
-        });
-        
-        for i in objs {
-            i.accept(self);
+    pub fn with_line_style(mut self, line_style: LineStyle) -> Self {
+        self.line_style = line_style;
+        self
+    }
+
+    pub fn with_hidden_layers(mut self, hidden_layers: BTreeSet<&'static str>) -> Self {
+        self.hidden_layers = hidden_layers;
+        self
+    }
+
+    /// Draws only the components of the pass
+    pub fn with_pass(mut self, pass: RenderPass) -> Self {
+        self.pass = Some(pass);
+        self
+    }
+
+    pub fn with_selection(mut self, selection: &[&'static str]) -> Self {
+        self.selection = selection.to_vec();
+        self
+    }
+
+    /// Skips the culled objects, paints the clouds back to front and lowers
+    /// the quality of the objects as planned
+    pub fn with_plan(mut self, plan: RenderPlan) -> Self {
+        self.plan = plan;
+        self
+    }
+
+    /// Reuses the cloud images while nothing they depend on changes
+    pub fn with_cache(mut self, cache: &'a CacheManager) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn with_pixels(mut self, pixels: &'a mut PixelBuffer) -> Self {
+        self.pixels = Some(pixels);
+        self
+    }
+
+    /// Shows the latest clouds finished by the thread instead of marching
+    /// them during the frame
+    pub fn with_render_thread(mut self, render_thread: Option<&'a RenderThread>) -> Self {
+        self.render_thread = render_thread;
+        self
+    }
+
+    pub fn with_gpu_clouds(mut self, gpu_clouds: bool) -> Self {
+        self.gpu_clouds = gpu_clouds;
+        self
+    }
+
+    pub fn with_partial_redraw(mut self, partial_redraw: bool) -> Self {
+        self.partial_redraw = partial_redraw;
+        self
+    }
+
+    pub fn with_profiler(mut self, profiler: Arc<Profiler>) -> Self {
+        self.profiler = Some(profiler);
+        self
+    }
+
+    /// Uploads the image into the named texture of the canvas, timed as
+    /// [`Stage::TextureUpload`]
+    fn load_texture(
+        &self,
+        name: &str,
+        image: impl Into<egui::ImageData>,
+        options: egui::TextureOptions,
+    ) -> TextureId {
+        let _timer = self.timer(Stage::TextureUpload);
+        self.canvas.load_texture(name, image, options)
+    }
+
+    fn timer(&self, stage: Stage) -> Option<ScopeTimer<'_>> {
+        self.profiler.as_ref().map(|x| x.scope(stage))
+    }
+
+    /// Resolution the clouds are marched at, see [`MAX_CLOUD_RESOLUTION`]
+    pub fn with_cloud_resolution(mut self, resolution: usize) -> Self {
+        self.cloud_resolution = resolution.max(1);
+        self
+    }
+
+    /// Size of the canvas in pixels
+    fn viewport(&self) -> [usize; 2] {
+        let size = self.canvas.resp_rect().size().round();
+        [size.x as usize, size.y as usize]
+    }
+
+    /// View rays of the canvas, kept in the cache until the camera moves or
+    /// the canvas is resized
+    fn view_rays(&self) -> Option<Arc<ViewRays>> {
+        let cache = self.cache?;
+        let [width, height] = self.viewport();
+        let key = combine_keys(&[cache_key(self.camera), cache_key(&[width, height])]);
+        Some(cache.get_or_insert_with("camera", key, || self.camera.view_rays(width, height)))
+    }
+
+    /// Resolution the cloud covering the screen rect is marched at, the
+    /// rect scaled down to fit the internal resolution at the planned
+    /// quality
+    fn cloud_image_size(&self, rect: egui::Rect) -> [usize; 2] {
+        let resolution = self.cloud_resolution as f32 * self.plan.quality.unwrap_or(1.0);
+        let scale = (resolution / rect.width().max(rect.height())).min(1.0);
+        let size = (rect.size() * scale).round();
+        [size.x as usize, size.y as usize]
+    }
+
+    /// Everything the march of the cloud over the screen rect needs, so it
+    /// can run away from the visitor
+    fn cloud_march(&self, cloud: &Cloud, lights: Vec<Light>, rect: egui::Rect) -> CloudMarch {
+        let inverse = self.model.inverse();
+        // Without a hull, e.g. with the camera inside the volume, every tile
+        // is marched
+        let hull = cloud
+            .obb()
+            .corners()
+            .into_iter()
+            .map(|x| self.canvas.transform(x, self.mvp))
+            .collect::<Option<Vec<_>>>()
+            .map(|x| convex_hull(&x));
+        CloudMarch {
+            cloud: cloud.clone(),
+            camera: *self.camera,
+            viewport: self.viewport(),
+            inverse,
+            lights,
+            fog: self.fog.map(|x| x.transformed(inverse)),
+            rect,
+            hull,
+            rays: self.view_rays(),
+            occupancy: Some(self.density_cached(
+                cloud,
+                |x: &OccupancyGrid| x.is_current(cloud),
+                || OccupancyGrid::new(cloud, OCCUPANCY_GRID_SIZE),
+            )),
+            density: self.plan.coarse_density.then(|| {
+                self.density_cached(
+                    cloud,
+                    |x: &DensityField| x.is_current(cloud),
+                    || DensityField::new(cloud, DENSITY_FIELD_SIZE),
+                )
+            }),
+            gpu: self.gpu_clouds,
+            base: None,
+            profiler: self.profiler.clone(),
         }
     }
 
-    fn visit_camera(&mut self, _camera: &Camera) {
-        debug!("Visit camera {:?}", self.camera);
+    /// Value built from the density of the cloud, kept in the cache until
+    /// the density changes. The offset moved by the wind is left out of the
+    /// key, the value is rebuilt once it is no longer `current`.
+    fn density_cached<T: Any + Send + Sync>(
+        &self,
+        cloud: &Cloud,
+        current: impl FnOnce(&T) -> bool,
+        build: impl FnOnce() -> T,
+    ) -> Arc<T> {
+        let Some(cache) = self.cache else {
+            return Arc::new(build());
+        };
+        // The steps of the march leave the density as it is
+        let density = CloudBuilder {
+            num_steps: 0,
+            num_steps_light: 0,
+            auto_steps: false,
+            light_interval: 0,
+            offset: Vec3::ZERO,
+            ..cloud.cloud_params
+        };
+        let key = cache_key(&density);
+        match cache.get(self.id, key).filter(|x| current(x)) {
+            Some(value) => value,
+            None => cache.insert(self.id, key, build()),
+        }
     }
 
-    fn visit_cloud(&mut self, cloud: &Cloud) {
-        self.canvas
-            .ctx()
-            .data_mut(|x| x.insert_temp("cloud".into(), cloud.clone()));
-        use rayon::prelude::*;
+    /// Texture of the cloud marched over the screen rect, taken from the
+    /// cache while the key stays the same. `unlit` is the key of everything
+    /// but the lights.
+    fn cloud_texture(
+        &mut self,
+        cloud: &Cloud,
+        lights: Vec<Light>,
+        rect: egui::Rect,
+        [unlit, key]: [CacheKey; 2],
+    ) -> TextureId {
+        if let Some(cached) = self
+            .cache
+            .and_then(|cache| cache.get::<CloudTexture>(self.id, key))
+        {
+            return cached.0.id();
+        }
+        let mut march = self.cloud_march(cloud, lights, rect);
+        let cache = self.cache.filter(|_| self.partial_redraw);
+        march.base = cache
+            .and_then(|cache| cache.get::<CloudImage>(self.id, unlit))
+            .map(|x| x.0.clone());
+        let mut fresh = PixelBuffer::default();
+        let mut reused = self.pixels.take();
+        let pixels = reused.as_deref_mut().unwrap_or(&mut fresh);
+        march.run(pixels.image(self.cloud_image_size(rect)));
+        let img = match cache {
+            Some(cache) => {
+                // The old base is dropped by the cache, the buffer draws the
+                // next image into its pixels
+                let img = pixels.replace(march.base.take().unwrap_or_default());
+                cache.insert(self.id, unlit, CloudImage(img.clone()));
+                img
+            }
+            None => pixels.share(),
+        };
+        self.pixels = reused;
+        match self.cache {
+            Some(cache) => {
+                let name = format!("cloud {}", self.id);
+                let _timer = self.timer(Stage::TextureUpload);
+                let texture = self
+                    .canvas
+                    .ctx()
+                    .load_texture(name, img, Default::default());
+                cache.insert(self.id, key, CloudTexture(texture)).0.id()
+            }
+            None => self.load_texture("cloud", img, Default::default()),
+        }
+    }
 
-        let bb = cloud.bounding_box();
-        let corners = bb.corners();
+    /// Whether the object is left for [`Self::draw_sorted`]
+    fn is_deferred(&self, name: &str, component: &Component) -> bool {
+        matches!(component, Component::Cloud(_)) && self.plan.depth_order.contains(&name)
+    }
 
-        let (width, height) = (1056.0, 900.0);
+    /// Paints the deferred clouds of the root composite back to front
+    fn draw_sorted(&mut self, scene_objects: &SceneObjects, parent: Mat4) {
+        for name in self.plan.depth_order.clone() {
+            let Some((model, cloud)) =
+                find_visible(scene_objects, parent, name, &self.hidden_layers)
+            else {
+                continue;
+            };
+            if !self.is_deferred(name, cloud)
+                || !self.in_pass(cloud)
+                || self.plan.culled.contains(name)
+            {
+                continue;
+            }
+            self.id = name;
+            self.model = model;
+            self.mvp = self.view_projection.with_model(model);
+            cloud.accept(self);
+        }
+    }
 
-        let (min_tuple, max_tuple) = corners
-            .iter()
-            .enumerate()
-            .map(|(i, &corner)| {
-                self.canvas.transform(corner, self.mvp).unwrap_or_else(|| {
-                    if i < 4 {
-                        Pos2::new(width, height)
-                    } else {
-                        Pos2::ZERO
-                    }
-                })
-            })
-            .fold(
-                (Pos2::new(width, height), Pos2::new(0.0, 0.0)),
-                |(mut min, mut max), p| {
-                    min.x = min.x.min(p.x).clamp(0.0, width);
-                    min.y = min.y.min(p.y).clamp(0.0, height);
-                    max.x = max.x.max(p.x).clamp(0.0, width);
-                    max.y = max.y.max(p.y).clamp(0.0, height);
-                    (min, max)
-                },
-            );
+    fn in_pass(&self, component: &Component) -> bool {
+        self.pass.is_none_or(|x| x.draws(component))
+    }
 
-        let wh = max_tuple - min_tuple;
-        let (w, h) = (wh.x as usize, wh.y as usize);
+    /// Whether bounding boxes are drawn
+    fn shows_debug(&self) -> bool {
+        !self.hidden_layers.contains(DEBUG_LAYER)
+    }
 
-        let mut img = egui::ColorImage::new([w, h], Color32::TRANSPARENT);
-        let light_color: Vec4 = cloud
-            .light_color
-            .to_array()
-            .map(|x| x as f32 / 255.0)
-            .into();
+    /// Draws the boxes around the selected objects of the composite
+    fn outline_selection(&self, scene_objects: &SceneObjects, parent: Mat4) {
+        let stroke = Stroke::new(2.0, Color32::from_rgb(255, 160, 0));
+        for (name, object) in scene_objects.visible() {
+            let Some(obb) = object.bounds().filter(|_| self.selection.contains(&name)) else {
+                continue;
+            };
+            let model = parent * scene_objects.world_transform(name) * obb.matrix();
+            let mvp = self.view_projection.with_model(model);
+            for (a, b) in box_edges(&obb.local_box()) {
+                self.canvas.line(a, b, stroke, mvp);
+            }
+        }
+    }
 
-        let sun = self
-            .canvas
-            .ctx()
-            .data_mut(|x| x.get_persisted::<Sun>("sun".into()));
-        let sun = sun.unwrap_or_default();
-        let sun_pos = sun.get_pos();
-        let ray_origin = self.camera.pos();
-        img.pixels
-            .par_iter_mut()
-            .enumerate()
-            .for_each(|(idx, pixel)| {
-                let i = idx / w + min_tuple.y as usize;
-                let j = idx % w + min_tuple.x as usize;
+    /// Lights of the scene together with the sun, moved to the local space
+    /// of the object being visited
+    fn local_lights(&self) -> Vec<Light> {
+        let inverse = self.model.inverse();
+        self.lights.iter().map(|x| x.transformed(inverse)).collect()
+    }
+}
 
-                let ray_dir = (self.camera.egui_to_world(i, j, 1056, 900) - ray_origin).normalize();
-
-                let ray_box_info = bb.dst(ray_origin, ray_dir);
-                let dst_to_box = ray_box_info.x;
-                let dst_inside_box = ray_box_info.y;
-
-                if dst_inside_box <= 0.0 {
-                    *pixel = Color32::TRANSPARENT
-                } else {
-                    let mut dst_travelled = 0.0;
-                    let dst_limit = dst_inside_box;
-                    let step_size = dst_inside_box / cloud.num_steps as f32;
-                    let mut transmittance = 1.0;
-                    let mut light_energy = 0.0;
-
-                    let entry_point = ray_origin + dst_to_box * ray_dir;
-                    let cos_angle = ray_dir.dot((sun_pos).normalize());
-                    let phase = phase(cos_angle, cloud.phase_params);
-
-                    while dst_travelled < dst_limit {
-                        let ray_pos = entry_point + ray_dir * dst_travelled;
-                        let density = cloud.sample_density(ray_pos);
-                        if density > 0.1 {
-                            let light_transmittance = cloud.light_march(ray_pos, sun_pos);
-                            light_energy +=
-                                density * step_size * transmittance * light_transmittance * phase;
-                            transmittance *=
-                                beer(density * step_size * cloud.light_absorption_through_cloud);
-                            if transmittance < 0.01 {
-                                break;
-                            }
-                        }
-                        dst_travelled += step_size;
-                    }
+impl<'a> Visitor for DrawVisitor<'a> {
+    type Output = ();
 
-                    let focused_eye_cos = cos_angle.clamp(-1.0, 1.0).powf(cloud.params.x);
-                    let sun =
-                        hg(focused_eye_cos, cloud.phase_params.w).clamp(-1.0, 1.0) * transmittance;
-
-                    let cloud_col = light_energy * light_color.xyz();
-                    let col = cloud_col.clamp(Vec3::ZERO, Vec3::ONE) * (1.0 - sun)
-                        + light_color.xyz() * sun;
-                    let (r, g, b) = col.into();
-                    *pixel = Color32::from_rgba_unmultiplied(
-                        (r * 255.0) as u8,
-                        (g * 255.0) as u8,
-                        (b * 255.0) as u8,
-                        (255.0 * (1.0 - transmittance)) as u8,
-                    );
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) {
+        if self.sun.is_none() {
+            self.sun = world_sun(scene_objects, self.model);
+        }
+        if self.fog.is_none() {
+            self.fog = scene_objects.visible().find_map(|(name, x)| match x {
+                Component::Fog(fog) => {
+                    Some(fog.transformed(self.model * scene_objects.world_transform(name)))
                 }
+                _ => None,
             });
+        }
+        if !self.background_filled && self.pass.is_none_or(|x| x == RenderPass::Sky) {
+            let background = scene_objects
+                .visible()
+                .find_map(|(_, x)| match x {
+                    Component::Background(bg) => Some(*bg),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            self.visit_background(&background);
+        }
 
-        let handle = self
-            .canvas
+        let parent = self.model;
+        self.occluders
+            .extend(scene_objects.visible().filter_map(|(name, x)| match x {
+                Component::Cloud(cloud) => {
+                    let model = parent * scene_objects.world_transform(name);
+                    Some(cloud.obb().aabb().transformed(model))
+                }
+                _ => None,
+            }));
+        self.lights.extend(scene_objects.lights(parent));
+
+        // Marching towards the sun is only worth it when the sun is drawn
+        let sun_visibility = if self.pass.is_none_or(|x| x == RenderPass::Opaque) {
+            sun_visibility(scene_objects, parent, self.camera.pos())
+        } else {
+            1.0
+        };
+        for (name, model, i) in draw_order(
+            scene_objects,
+            parent,
+            self.camera.pos(),
+            &self.hidden_layers,
+        ) {
+            if !self.in_pass(i) || self.plan.culled.contains(name) || self.is_deferred(name, i) {
+                continue;
+            }
+            self.id = name;
+            self.sun_visibility = sun_visibility;
+            self.model = model;
+            self.mvp = self.view_projection.with_model(model);
+            match i {
+                Component::Composite(objects) => {
+                    self.nesting += 1;
+                    self.visit_composite(objects);
+                    self.nesting -= 1;
+                }
+                i => i.accept(self),
+            }
+        }
+        if self.nesting == 0 {
+            self.draw_sorted(scene_objects, parent);
+        }
+        if self.pass.is_none_or(|x| x == RenderPass::Overlay) {
+            self.outline_selection(scene_objects, parent);
+        }
+        self.model = parent;
+        self.mvp = self.view_projection.with_model(parent);
+    }
+
+    fn visit_plugin(&mut self, plugin: &dyn PluginComponent) {
+        self.nesting += 1;
+        self.visit_composite(plugin.parts());
+        self.nesting -= 1;
+    }
+
+    fn visit_camera(&mut self, _camera: &Camera) {
+        debug!("Visit camera {:?}", self.camera);
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) {
+        let lod = match self.plan.lod.get(self.id) {
+            Some(&Lod::Cloud {
+                num_steps,
+                num_steps_light,
+            }) => Some((num_steps, num_steps_light)),
+            _ => None,
+        };
+        let reduced;
+        let cloud = if lod.is_some() || self.plan.quality.is_some() {
+            let (num_steps, num_steps_light) =
+                lod.unwrap_or((cloud.num_steps, cloud.num_steps_light));
+            let quality = self.plan.quality.unwrap_or(1.0);
+            let steps = |x: usize| ((x as f32 * quality).ceil() as usize).max(1);
+            let mut cloud = cloud.clone();
+            cloud.num_steps = steps(num_steps);
+            cloud.num_steps_light = steps(num_steps_light);
+            reduced = cloud;
+            &reduced
+        } else {
+            cloud
+        };
+        self.canvas
             .ctx()
-            .load_texture("cloud", img, Default::default());
-        let textureid = TextureId::from(&handle);
-        self.canvas.image(
-            textureid,
-            egui::Rect::from_two_pos(min_tuple, max_tuple),
-            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
-            Color32::WHITE,
-        );
-        // self.visit_bounding_box(bb);
+            .data_mut(|x| x.insert_temp("cloud".into(), cloud.clone()));
+        let (min_tuple, max_tuple) = self.screen_rect(&cloud.obb().aabb());
+        let rect = egui::Rect::from_two_pos(min_tuple, max_tuple);
+        let lights = self.local_lights();
+        let size = self.cloud_image_size(rect);
+        let unlit = combine_keys(&[
+            cache_key(self.camera),
+            cache_key(&cloud.cloud_params),
+            cache_key(&self.model),
+            cache_key(&self.fog),
+            cache_key(&[rect.min.x, rect.min.y, rect.max.x, rect.max.y]),
+            cache_key(&size),
+            cache_key(&self.plan.coarse_density),
+            cache_key(&self.gpu_clouds),
+        ]);
+        let key = combine_keys(&[unlit, cache_key(&lights)]);
+        // The render thread shows its latest image, which may lag behind
+        let shown = match self.render_thread {
+            // Nothing of the volume is on screen
+            _ if rect.width() < 1.0 || rect.height() < 1.0 => None,
+            Some(thread) => {
+                thread.submit(self.id, key, size, || self.cloud_march(cloud, lights, rect));
+                if thread.is_pending(self.id) {
+                    self.canvas.ctx().request_repaint();
+                }
+                thread.present(self.canvas.ctx(), self.id)
+            }
+            None => Some((self.cloud_texture(cloud, lights, rect, [unlit, key]), rect)),
+        };
+        if let Some((textureid, rect)) = shown {
+            self.canvas.image(
+                textureid,
+                rect,
+                egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                Color32::WHITE,
+            );
+        }
+        if self.shows_debug() {
+            // The box is drawn in the space of the rotated volume
+            let mvp = self.mvp;
+            self.mvp = self
+                .view_projection
+                .with_model(self.model * cloud.volume_matrix().inverse());
+            self.visit_bounding_box(cloud.bounding_box());
+            self.mvp = mvp;
+        }
     }
 
     fn visit_grid(&mut self, grid: &Grid) {
-        let k = grid.k;
+        let reduced;
+        let grid = match self.plan.lod.get(self.id) {
+            Some(&Lod::Grid { k }) => {
+                reduced = Grid { k, ..grid.clone() };
+                &reduced
+            }
+            _ => grid,
+        };
         let scale = grid.scale;
-        let f = k as f32;
-        for i in -k..=k {
-            self.canvas.line(
-                Vec3::new(-1., 0., i as f32 / f) * scale,
-                Vec3::new(1., 0., i as f32 / f) * scale,
-                Stroke::new(1.0, Color32::BLACK),
-                self.mvp,
-            );
-
-            self.canvas.line(
-                Vec3::new(i as f32 / f, 0., -1.) * scale,
-                Vec3::new(i as f32 / f, 0., 1.) * scale,
-                Stroke::new(1.0, Color32::BLACK),
+        let size = self.canvas.resp_rect().size();
+        let mut batch = self.canvas.batch();
+        for line in grid.visible_lines(self.camera, size.x, size.y) {
+            let width = if line.major { 1.0 } else { 0.5 };
+            batch.styled_line(
+                line.a,
+                line.b,
+                Stroke::new(width, grid.line_color(&line)),
+                self.line_style,
                 self.mvp,
             );
         }
 
-        self.canvas.line(
+        batch.styled_line(
             Vec3::new(0., 0., 0.) * scale,
             Vec3::new(0., 1.4, 0.) * scale,
             Stroke::new(2.0, Color32::DARK_GREEN),
+            self.line_style,
             self.mvp,
         );
 
-        self.canvas.line(
+        batch.styled_line(
             Vec3::new(0., 0., 0.) * scale,
             Vec3::new(0., 0., 1.4) * scale,
             Stroke::new(2.0, Color32::BLUE),
+            self.line_style,
             self.mvp,
         );
 
-        self.canvas.line(
+        batch.styled_line(
             Vec3::new(0., 0., 0.) * scale,
             Vec3::new(1.4, 0., 0.) * scale,
             Stroke::new(2.0, Color32::RED),
+            self.line_style,
             self.mvp,
         );
+        batch.submit();
 
-        self.canvas.text(
+        self.canvas.text_occluded(
             Vec3::new(1.5, 0., 0.),
             egui::Align2::CENTER_BOTTOM,
             "x",
             egui::FontId::monospace(14.0),
             Color32::RED,
+            Occlusion::new(self.camera.pos(), &self.occluders),
             self.mvp,
         );
 
-        self.canvas.text(
+        self.canvas.text_occluded(
             Vec3::new(0., 1.5, 0.),
             egui::Align2::CENTER_BOTTOM,
             "y",
             egui::FontId::monospace(14.0),
             Color32::DARK_GREEN,
+            Occlusion::new(self.camera.pos(), &self.occluders),
             self.mvp,
         );
 
-        self.canvas.text(
+        self.canvas.text_occluded(
             Vec3::new(0., 0., 1.5),
             egui::Align2::CENTER_BOTTOM,
             "z",
             egui::FontId::monospace(14.0),
             Color32::BLUE,
+            Occlusion::new(self.camera.pos(), &self.occluders),
             self.mvp,
         );
     }
 
     fn visit_bounding_box(&mut self, bb: &BoundingBox) {
-        let (x1, y1, z1) = bb.min.into();
-        let (x2, y2, z2) = bb.max.into();
-        let corners = [
-            (x1, y1, z1),
-            (x2, y1, z1),
-            (x1, y2, z1),
-            (x1, y1, z2),
-            (x2, y2, z1),
-            (x2, y1, z2),
-            (x1, y2, z2),
-            (x2, y2, z2),
-        ];
-        let edges = [
-            (0, 1),
-            (0, 2),
-            (0, 3),
-            (1, 4),
-            (2, 4),
-            (1, 5),
-            (3, 5),
-            (2, 6),
-            (3, 6),
-            (4, 7),
-            (6, 7),
-            (5, 7),
-        ];
-        for &(i1, i2) in &edges {
-            let (x1, y1, z1) = corners[i1];
-            let (x2, y2, z2) = corners[i2];
-            self.canvas.dashed_line(
-                Vec3::new(x1, y1, z1),
-                Vec3::new(x2, y2, z2),
+        for (a, b) in box_edges(bb) {
+            self.canvas.styled_dashed_line(
+                a,
+                b,
                 1.0,
                 0.5,
                 Stroke::new(1.0, Color32::DARK_RED),
+                self.line_style,
                 self.mvp,
             );
         }
@@ -299,231 +670,157 @@ impl<'a> Visitor for DrawVisitor<'a> {
 
     fn visit_sun(&mut self, sun: &Sun) {
         let sun_pos = sun.get_pos();
-        self.canvas
-            .ctx()
-            .data_mut(|x| x.insert_persisted("sun".into(), *sun));
-        self.canvas.circle_filled(
-            sun_pos,
-            sun.get_pos() + Vec3::new(0.1, 0.0, 0.0),
-            Color32::LIGHT_YELLOW,
-            self.mvp,
-        );
+        // The disc is dimmed by the clouds in front of it
+        for (radius, color) in Sun::glow(sun.color, self.sun_visibility) {
+            self.canvas.circle_filled(
+                sun_pos,
+                sun_pos + Vec3::new(0.1 * radius, 0.0, 0.0),
+                color,
+                self.mvp,
+            );
+        }
     }
 
     fn visit_terrain(&mut self, terrain: &Terrain) {
-        use rayon::prelude::*;
-
-        let sun = self
-            .canvas
-            .ctx()
-            .data_mut(|x| x.get_persisted::<Sun>("sun".into()));
-
-        if sun.is_none() {
+        let Some(sun) = self.sun else {
             return;
-        }
-        let sun_pos = sun.unwrap().get_pos();
+        };
+        let sun_pos = sun.get_pos();
+        let inverse = self.model.inverse();
         let cloud = self
             .canvas
             .ctx()
             .data_mut(|x| x.get_temp::<Cloud>("cloud".into()))
             .unwrap_or_default();
 
-        let (width, height) = (1056.0, 900.0);
+        let [w, h] = self.viewport();
+        let (width, height) = (w as f32, h as f32);
         let (min_tuple, max_tuple) = (Pos2::ZERO, Pos2::new(width, height));
-        let wh = max_tuple - min_tuple;
-        let (w, h) = (wh.x as usize, wh.y as usize);
-        let bb = terrain.bounding_box;
 
-        let img = Arc::new(Mutex::new(egui::ColorImage::new(
+        let fog = self.fog.map(|x| x.transformed(inverse));
+        let sun_pos = inverse.transform_point3(sun_pos);
+        let model = self.model;
+        let shadow_map = self.plan.shadow.as_deref();
+        let img = rasterize_terrain(
+            terrain,
+            |p| match shadow_map {
+                Some(map) => map
+                    .transmittance(model.transform_point3(p))
+                    .max(terrain.shadow_threshold),
+                None => cloud_shadow(&cloud, terrain, sun_pos, p),
+            },
+            fog.as_ref(),
+            sun_pos,
+            inverse.transform_point3(self.camera.pos()),
             [w, h],
-            Color32::TRANSPARENT,
-        )));
-        let z_buffer = Arc::new(Mutex::new(HashMap::new()));
-
-        terrain.triangles.par_iter().for_each(|(v, (n0, n1, n2))| {
-            let img = img.clone();
-            let z_buffer = z_buffer.clone();
-            let get_shadow_factor = |probe: Vec3| -> f32 {
-                let sun_dir = (sun_pos - probe).normalize();
-                let cloud_bb = cloud.bounding_box().dst(probe, sun_dir);
-                let (dir_to_box, dst_inside_box) = cloud_bb.into();
-                if dst_inside_box != 0.0 {
-                    let mut p = probe;
-                    let num_steps = terrain.num_shadows_steps;
-                    let step_size = dst_inside_box / num_steps as f32;
-                    p += dir_to_box * sun_dir;
-
-                    let mut total_density = 0.0;
-
-                    for _ in 0..num_steps {
-                        let density = cloud.sample_density(p);
-                        total_density += density.max(0.0) * step_size;
-                        p += sun_dir * step_size;
-                    }
-                    beer(total_density / terrain.density_scale).clamp(terrain.shadow_threshold, 1.0)
-                } else {
-                    1.0
-                }
-            };
+            |v| self.canvas.transform(v, self.mvp),
+        );
+        let textureid = self.load_texture("terrain", img, egui::TextureOptions::NEAREST);
+        self.canvas.image(
+            textureid,
+            egui::Rect::from_two_pos(min_tuple, max_tuple),
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+            Color32::WHITE,
+        );
 
-            let (v0, v1, v2) = v.to_tuple();
-            let (p0, p1, p2) = (v0, v1, v2);
-
-            let v0 = self.canvas.transform(v0, self.mvp);
-            let v1 = self.canvas.transform(v1, self.mvp);
-            let v2 = self.canvas.transform(v2, self.mvp);
-            if let (Some(v0), Some(v1), Some(v2)) = (v0, v1, v2) {
-                let min_x = v0.x.min(v1.x).min(v2.x) as usize;
-                let max_x = v0.x.max(v1.x).max(v2.x) as usize;
-                let min_y = v0.y.min(v1.y).min(v2.y) as usize;
-                let max_y = v0.y.max(v1.y).max(v2.y) as usize;
-
-                for y in min_y..=max_y {
-                    for x in min_x..=max_x {
-                        if inside_triangle(Pos2::new(x as f32, y as f32), v0, v1, v2)
-                            && x < width as usize
-                            && y < height as usize
-                        {
-                            let a1 = Vec3::new(p1.x - p0.x, p1.y - p0.y, p1.z - p0.z);
-                            let a2 = Vec3::new(p2.x - p0.x, p2.y - p0.y, p2.z - p0.z);
-                            let normal = a1.cross(a2);
-                            let d = -(normal.x * p0.x + normal.y * p0.y + normal.z * p0.z);
-                            let (a, b, c, d) = (normal.x, normal.y, normal.z, d);
-
-                            let new_u = p1.x + (p0.x - p1.x) * ((x as f32 - v1.x) / (v0.x - v1.x));
-                            let new_v = p1.y + (p0.y - p1.y) * ((y as f32 - v1.y) / (v0.y - v1.y));
-                            let z_pixel = if c != 0.0 {
-                                -(a * new_u + b * new_v + d) / c
-                            } else {
-                                0.0
-                            };
-
-                            let probe = Vec3::new(new_u, new_v, z_pixel);
-
-                            let depth = self.camera.pos().distance_squared(probe);
-
-                            let x1 = get_shadow_factor(p0);
-                            let x2 = get_shadow_factor(p1);
-                            let x3 = get_shadow_factor(p2);
-
-                            let alpha1 = ((sun_pos - p0).normalize()).dot(n0.normalize());
-                            let alpha2 = ((sun_pos - p1).normalize()).dot(n1.normalize());
-                            let alpha3 = ((sun_pos - p2).normalize()).dot(n2.normalize());
-
-                            let beta = interpolate(
-                                Pos2::new(x as f32, y as f32),
-                                v0,
-                                v1,
-                                v2,
-                                x1 * alpha1,
-                                x2 * alpha2,
-                                x3 * alpha3,
-                            );
-
-                            let dif = 0.55 * beta;
-                            let bottom = color32_to_vec4(terrain.bottom_color).xyz();
-                            let top = color32_to_vec4(terrain.top_color).xyz();
-                            let p0_col = bottom.lerp(top, (p0.y - bb.min.y).abs() / bb.size().y);
-                            let p1_col = bottom.lerp(top, (p1.y - bb.min.y).abs() / bb.size().y);
-                            let p2_col = bottom.lerp(top, (p2.y - bb.min.y).abs() / bb.size().y);
-
-                            let col = interpolate(
-                                Pos2::new(x as f32, y as f32),
-                                v0,
-                                v1,
-                                v2,
-                                p0_col,
-                                p1_col,
-                                p2_col,
-                            );
-                            let col = col * dif;
-                            let (r, g, b) = (col.x * 255.0, col.y * 255.0, col.z * 255.0);
-                            let color = Color32::from_rgb(r as u8, g as u8, b as u8);
-                            let mut z_buffer = z_buffer.lock().unwrap();
-                            if let Some(existing_depth) = z_buffer.get(&(x, y)) {
-                                if depth < *existing_depth {
-                                    img.lock().unwrap()[(x, y)] = color;
-                                    z_buffer.insert((x, y), depth);
-                                }
-                            } else {
-                                img.lock().unwrap()[(x, y)] = color;
-                                z_buffer.insert((x, y), depth);
-                            }
-                        }
-                    }
-                }
+        if self.shows_debug() {
+            self.visit_bounding_box(&terrain.bounding_box);
+        }
+    }
+
+    fn visit_background(&mut self, background: &Background) {
+        // Filled once before everything else, see `visit_composite`
+        if self.background_filled {
+            return;
+        }
+        self.background_filled = true;
+
+        let rect = self.canvas.resp_rect();
+        match *background {
+            Background::Solid(color) => {
+                self.canvas.rect_filled(rect, 0.0, color);
+            }
+            Background::Gradient { top, bottom } => {
+                let mut mesh = egui::Mesh::default();
+                mesh.colored_vertex(rect.left_top(), top);
+                mesh.colored_vertex(rect.right_top(), top);
+                mesh.colored_vertex(rect.right_bottom(), bottom);
+                mesh.colored_vertex(rect.left_bottom(), bottom);
+                mesh.add_triangle(0, 1, 2);
+                mesh.add_triangle(0, 2, 3);
+                self.canvas.add(mesh);
             }
-        });
+            Background::Sky => self.visit_sky(),
+        }
+        if let Some(fog) = self.fog {
+            self.visit_sky_fog(&fog);
+        }
+    }
 
-        let img = Arc::try_unwrap(img)
-            .expect("one strong reference")
-            .into_inner()
-            .expect("No one holding the mutex");
-        let handle = self
-            .canvas
-            .ctx()
-            .load_texture("terrain", img, egui::TextureOptions::NEAREST);
-        let textureid = TextureId::from(&handle);
+    fn visit_mesh(&mut self, mesh: &Mesh) {
+        let lights = self.local_lights();
+        if let Some(Lod::Mesh { proxy: true }) = self.plan.lod.get(self.id) {
+            let bounds = mesh.bounding_box();
+            let proxy = Mesh::cuboid(bounds.size())
+                .with_offset(bounds.center())
+                .with_color(mesh.color);
+            self.canvas.mesh(&proxy.shaded_triangles(&lights), self.mvp);
+            return;
+        }
+        self.canvas.mesh(&mesh.shaded_triangles(&lights), self.mvp);
+    }
+
+    fn visit_light(&mut self, light: &Light) {
+        if let LightKind::Point(position) = light.kind {
+            self.canvas.points(&[position], 6.0, light.color, self.mvp);
+        }
+    }
+
+    fn visit_skybox(&mut self, skybox: &Skybox) {
+        // Rendered at half resolution, the texture filtering hides it
+        let [width, height] = self.viewport();
+        let img = skybox.render(self.camera, [width / 2, height / 2]);
+        let textureid = self.load_texture("skybox", img, Default::default());
         self.canvas.image(
             textureid,
-            egui::Rect::from_two_pos(min_tuple, max_tuple),
+            egui::Rect::from_min_max(Pos2::ZERO, Pos2::new(width as f32, height as f32)),
             egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
             Color32::WHITE,
         );
-
-        // self.visit_bounding_box(&terrain.bounding_box);
     }
-}
 
-impl<'a> DrawVisitor<'a> {
-    fn visit_sky(&self) {
+    fn visit_water(&mut self, water: &Water) {
         use rayon::prelude::*;
-        let (width, height) = (1066.0, 950.0);
-        let (min_tuple, max_tuple) = (Pos2::ZERO, Pos2::new(width, height));
+
+        let Some(sun) = self.sun else {
+            return;
+        };
+
+        let (min_tuple, max_tuple) = self.screen_rect(&water.bounding_box);
         let wh = max_tuple - min_tuple;
         let (w, h) = (wh.x as usize, wh.y as usize);
-
-        let sun = self
-            .canvas
-            .ctx()
-            .data_mut(|x| x.get_persisted::<Sun>("sun".into()));
-        if sun.is_none() {
+        if w == 0 || h == 0 {
             return;
         }
-        let sun = sun.unwrap();
 
-        let mut img = egui::ColorImage::new([w, h], Color32::BLACK);
+        let mut img = egui::ColorImage::new([w, h], Color32::TRANSPARENT);
+        let inverse = self.model.inverse();
+        let sun = sun.transformed(inverse);
+        let ray_origin = inverse.transform_point3(self.camera.pos());
+        let rays = self.view_rays();
+        let viewport = self.viewport();
         img.pixels
             .par_iter_mut()
             .enumerate()
             .for_each(|(idx, pixel)| {
                 let i = idx / w + min_tuple.y as usize;
                 let j = idx % w + min_tuple.x as usize;
-                let uv = glam::Vec2::new(j as f32, 900.0 - i as f32) / 900.0;
-
-                let atmosphere = (1.0_f32 - uv.y).sqrt();
-                let sky_col = Vec3::new(0.2, 0.4, 0.8);
-
-                let scatter = sun.get_pos().y / sun.d;
-                let scatter = scatter.powf(1.0 / 10.0);
-                let scatter = 1.0 - scatter.clamp(0.2, 0.9);
-
-                let col = Vec3::splat(1.0).lerp(Vec3::new(0.8, 0.3, 0.0) * 1.1, scatter);
-                let col = sky_col.lerp(col, atmosphere / 1.1);
-                let (r, g, b) = col.into();
-                *pixel = Color32::from_rgba_unmultiplied(
-                    (r * 255.0) as u8,
-                    (g * 255.0) as u8,
-                    (b * 255.0) as u8,
-                    255,
-                );
+
+                let ray_dir = view_ray_dir(self.camera, rays.as_deref(), viewport, inverse, [i, j]);
+                *pixel = water.shade(ray_origin, ray_dir, &sun);
             });
 
-        let handle = self
-            .canvas
-            .ctx()
-            .load_texture("terrain", img, Default::default());
-        let textureid = TextureId::from(&handle);
+        let textureid = self.load_texture("water", img, Default::default());
         self.canvas.image(
             textureid,
             egui::Rect::from_two_pos(min_tuple, max_tuple),
@@ -531,37 +828,164 @@ impl<'a> DrawVisitor<'a> {
             Color32::WHITE,
         );
     }
-}
 
-fn interpolate<T>(pos: Pos2, v0: Pos2, v1: Pos2, v2: Pos2, n0: T, n1: T, n2: T) -> T
-where
-    T: std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
-{
-    let area_total = (v1.x - v0.x) * (v2.y - v0.y) - (v2.x - v0.x) * (v1.y - v0.y);
-    let alpha = ((v1.x - pos.x) * (v2.y - pos.y) - (v2.x - pos.x) * (v1.y - pos.y)) / area_total;
-    let beta = ((v2.x - pos.x) * (v0.y - pos.y) - (v0.x - pos.x) * (v2.y - pos.y)) / area_total;
-    let gamma = 1.0 - alpha - beta;
+    fn visit_gizmo(&mut self, gizmo: &OrientationGizmo) {
+        let hud = self.canvas.hud();
+        let canvas = egui::Rect::from_min_size(Pos2::ZERO, hud.rect().size());
+        let center = gizmo.center(canvas);
 
-    n0 * alpha + n1 * beta + n2 * gamma
-}
+        hud.circle_filled(
+            center,
+            gizmo.size + gizmo.handle_radius,
+            Color32::from_white_alpha(96),
+        );
+        for handle in gizmo.handles(self.camera, canvas) {
+            if handle.label.is_empty() {
+                hud.circle_stroke(
+                    handle.pos,
+                    0.75 * gizmo.handle_radius,
+                    Stroke::new(1.5, handle.color),
+                );
+                continue;
+            }
+            hud.line(center, handle.pos, Stroke::new(2.0, handle.color));
+            hud.circle_filled(handle.pos, gizmo.handle_radius, handle.color);
+            hud.text(
+                handle.pos,
+                egui::Align2::CENTER_CENTER,
+                handle.label,
+                egui::FontId::monospace(10.0),
+                Color32::WHITE,
+            );
+        }
+    }
 
-#[inline]
-fn sign(p1: Pos2, p2: Pos2, p3: Pos2) -> f32 {
-    (p1.x - p3.x) * (p2.y - p3.y) - (p2.x - p3.x) * (p1.y - p3.y)
+    fn visit_transform_gizmo(&mut self, gizmo: &TransformGizmo) {
+        if gizmo.target.is_none() {
+            return;
+        }
+        let canvas = self.canvas.resp_rect();
+        let painter = self.canvas.egui();
+        for (axis, color) in TransformGizmo::axes() {
+            let color = if gizmo.active == Some(axis) {
+                Color32::YELLOW
+            } else {
+                color
+            };
+            let handle = gizmo.handle(self.camera, canvas, axis);
+            let stroke = Stroke::new(2.0, color);
+            match gizmo.mode {
+                GizmoMode::Translate => {
+                    painter.line_segment([handle[0], handle[1]], stroke);
+                    painter.circle_filled(handle[1], gizmo.handle_radius, color);
+                }
+                GizmoMode::Rotate => {
+                    painter.add(egui::Shape::line(handle, stroke));
+                }
+            }
+        }
+    }
 }
 
-fn inside_triangle(p: Pos2, v1: Pos2, v2: Pos2, v3: Pos2) -> bool {
-    let d1 = sign(p, v1, v2);
-    let d2 = sign(p, v2, v3);
-    let d3 = sign(p, v3, v1);
+impl<'a> DrawVisitor<'a> {
+    /// Screen rect covered by the projected box, clamped to the canvas
+    fn screen_rect(&self, bb: &BoundingBox) -> (Pos2, Pos2) {
+        let [width, height] = self.viewport().map(|x| x as f32);
 
-    let has_neg = (d1 < 0.) || (d2 < 0.) || (d3 < 0.);
-    let has_pos = (d1 > 0.) || (d2 > 0.) || (d3 > 0.);
+        bb.corners()
+            .iter()
+            .enumerate()
+            .map(|(i, &corner)| {
+                self.canvas.transform(corner, self.mvp).unwrap_or_else(|| {
+                    if i < 4 {
+                        Pos2::new(width, height)
+                    } else {
+                        Pos2::ZERO
+                    }
+                })
+            })
+            .fold(
+                (Pos2::new(width, height), Pos2::new(0.0, 0.0)),
+                |(mut min, mut max), p| {
+                    min.x = min.x.min(p.x).clamp(0.0, width);
+                    min.y = min.y.min(p.y).clamp(0.0, height);
+                    max.x = max.x.max(p.x).clamp(0.0, width);
+                    max.y = max.y.max(p.y).clamp(0.0, height);
+                    (min, max)
+                },
+            )
+    }
+
+    /// Covers the background with the fog seen along the view rays, which
+    /// hides the horizon
+    fn visit_sky_fog(&self, fog: &Fog) {
+        const ROWS: usize = 64;
+        let [width, height] = self.viewport();
+        let rect = self.canvas.resp_rect();
+        let eye = self.camera.pos();
+        let mut mesh = egui::Mesh::default();
+        for row in 0..=ROWS {
+            let i = row * height.saturating_sub(1) / ROWS;
+            let dir = (self.camera.egui_to_world(i, width / 2, width, height) - eye).normalize();
+            let color = fog
+                .color
+                .gamma_multiply(fog.amount(eye, dir, f32::INFINITY));
+            let y = rect.top() + rect.height() * row as f32 / ROWS as f32;
+            mesh.colored_vertex(Pos2::new(rect.left(), y), color);
+            mesh.colored_vertex(Pos2::new(rect.right(), y), color);
+            if row > 0 {
+                let k = 2 * row as u32;
+                mesh.add_triangle(k - 2, k - 1, k + 1);
+                mesh.add_triangle(k - 2, k + 1, k);
+            }
+        }
+        self.canvas.add(mesh);
+    }
+
+    fn visit_sky(&self) {
+        use rayon::prelude::*;
+        let [w, h] = self.viewport();
+        let (min_tuple, max_tuple) = (Pos2::ZERO, Pos2::new(w as f32, h as f32));
+
+        let Some(sun) = self.sun else {
+            self.canvas
+                .rect_filled(self.canvas.resp_rect(), 0.0, self.canvas.color);
+            return;
+        };
+
+        let mut img = egui::ColorImage::new([w, h], Color32::BLACK);
+        img.pixels
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(idx, pixel)| {
+                let i = idx / w + min_tuple.y as usize;
+                *pixel = sky_color((h - i) as f32 / h as f32, &sun);
+            });
 
-    !(has_neg && has_pos)
+        let textureid = self.load_texture("sky", img, Default::default());
+        self.canvas.image(
+            textureid,
+            egui::Rect::from_two_pos(min_tuple, max_tuple),
+            egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+            Color32::WHITE,
+        );
+    }
 }
 
-#[inline]
-fn color32_to_vec4(color32: Color32) -> Vec4 {
-    color32.to_array().map(|x| x as f32 / 255.0).into()
+/// The twelve edges of the box
+pub(crate) fn box_edges(bb: &BoundingBox) -> [(Vec3, Vec3); 12] {
+    let corner = |i: usize| {
+        Vec3::new(
+            if i & 1 == 0 { bb.min.x } else { bb.max.x },
+            if i & 2 == 0 { bb.min.y } else { bb.max.y },
+            if i & 4 == 0 { bb.min.z } else { bb.max.z },
+        )
+    };
+    // Corners differing in a single bit share an edge
+    let mut edges = [(Vec3::ZERO, Vec3::ZERO); 12];
+    let pairs = (0..8).flat_map(|i| [1, 2, 4].map(|bit| (i, i | bit)));
+    for (edge, (i, j)) in edges.iter_mut().zip(pairs.filter(|(i, j)| i != j)) {
+        *edge = (corner(i), corner(j));
+    }
+    edges
 }
@@ -0,0 +1,210 @@
+//! Layout of the scene as a glTF document: the grid, the terrain and the
+//! meshes, the sun, the cameras and the boxes of the clouds
+
+use std::collections::BTreeSet;
+
+use egui::Color32;
+use glam::{Mat4, Quat, Vec3};
+
+use crate::io::gltf::GltfDocument;
+use crate::object::camera::Camera;
+use crate::object::objects::{BoundingBox, Cloud, Grid, Mesh, Sun, Terrain};
+use crate::object::Component;
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::draw_visitor::box_edges;
+use crate::visitor::{visit_visible, Visitor, WorldVisitor};
+
+/// Adds the visible objects to a [`GltfDocument`] with their world
+/// matrices, the hierarchy of the scene is flattened
+pub struct GltfExportVisitor<'a> {
+    camera: &'a Camera,
+    size: [f32; 2],
+    /// Name and world matrix of the object being visited
+    name: &'static str,
+    model: Mat4,
+    hidden_layers: BTreeSet<&'static str>,
+    document: GltfDocument,
+}
+
+impl<'a> GltfExportVisitor<'a> {
+    /// The camera is added as the `view` node, the size gives its aspect
+    /// ratio and the extent of the grid
+    pub fn new(camera: &'a Camera, width: f32, height: f32) -> Self {
+        let mut visitor = Self {
+            camera,
+            size: [width, height],
+            name: "view",
+            model: Mat4::IDENTITY,
+            hidden_layers: BTreeSet::new(),
+            document: GltfDocument::new(),
+        };
+        visitor.add_camera(camera);
+        visitor
+    }
+
+    pub fn with_hidden_layers(mut self, hidden_layers: BTreeSet<&'static str>) -> Self {
+        self.hidden_layers = hidden_layers;
+        self
+    }
+
+    pub fn into_document(self) -> GltfDocument {
+        self.document
+    }
+
+    fn add_camera(&mut self, camera: &Camera) {
+        let [width, height] = self.size;
+        let proj = camera.proj;
+        self.document.add_camera(
+            self.name,
+            self.model * camera.view().inverse(),
+            proj.fov,
+            width / height,
+            proj.clip_near,
+            proj.clip_far,
+        );
+    }
+
+    fn add_box(&mut self, bb: &BoundingBox, matrix: Mat4, color: Color32) -> usize {
+        let edges =
+            box_edges(bb).map(|(a, b)| (matrix.transform_point3(a), matrix.transform_point3(b)));
+        self.document
+            .add_lines(self.name, self.model, &edges, color)
+    }
+}
+
+impl WorldVisitor for GltfExportVisitor<'_> {
+    fn model_mut(&mut self) -> &mut Mat4 {
+        &mut self.model
+    }
+
+    fn enter(
+        &mut self,
+        scene_objects: &SceneObjects,
+        id: &'static str,
+        _object: &Component,
+    ) -> bool {
+        if self.hidden_layers.contains(scene_objects.layer(id)) {
+            return false;
+        }
+        self.name = id;
+        true
+    }
+}
+
+impl Visitor for GltfExportVisitor<'_> {
+    type Output = ();
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) {
+        visit_visible(self, scene_objects)
+    }
+
+    fn visit_camera(&mut self, camera: &Camera) {
+        self.add_camera(camera);
+    }
+
+    /// Box of the cloud with its density volume attached
+    fn visit_cloud(&mut self, cloud: &Cloud) {
+        let obb = cloud.obb();
+        let node = self.add_box(&obb.local_box(), obb.matrix(), Color32::DARK_RED);
+        self.document.attach_volume(node, cloud.clone());
+    }
+
+    /// Major lines of the part of the grid seen by the camera
+    fn visit_grid(&mut self, grid: &Grid) {
+        let [width, height] = self.size;
+        let lines = grid
+            .visible_lines(self.camera, width, height)
+            .into_iter()
+            .filter(|x| x.major)
+            .map(|x| (x.a, x.b))
+            .collect::<Vec<_>>();
+        if !lines.is_empty() {
+            self.document
+                .add_lines(self.name, self.model, &lines, grid.major_color);
+        }
+    }
+
+    fn visit_bounding_box(&mut self, bb: &BoundingBox) {
+        self.add_box(bb, Mat4::IDENTITY, Color32::DARK_RED);
+    }
+
+    /// Light at the sun shining towards the origin of its composite
+    fn visit_sun(&mut self, sun: &Sun) {
+        let pos = sun.get_pos();
+        let rotation = Quat::from_rotation_arc(Vec3::NEG_Z, (-pos).normalize_or(Vec3::NEG_Y));
+        let matrix = self.model * Mat4::from_rotation_translation(rotation, pos);
+        self.document
+            .add_directional_light(self.name, matrix, sun.color, sun.intensity);
+    }
+
+    fn visit_terrain(&mut self, terrain: &Terrain) {
+        let vertices = terrain
+            .triangles
+            .iter()
+            .flat_map(|(x, _)| x.to_array())
+            .collect::<Vec<_>>();
+        if vertices.is_empty() {
+            return;
+        }
+        let faces = (0..vertices.len() as u32 / 3)
+            .map(|i| [3 * i, 3 * i + 1, 3 * i + 2])
+            .collect::<Vec<_>>();
+        self.document
+            .add_triangles(self.name, self.model, &vertices, &faces, terrain.top_color);
+    }
+
+    fn visit_mesh(&mut self, mesh: &Mesh) {
+        if mesh.faces.is_empty() {
+            return;
+        }
+        self.document.add_triangles(
+            self.name,
+            self.model,
+            &mesh.vertices,
+            &mesh.faces,
+            mesh.color,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::tests::dense_cloud;
+
+    #[test]
+    fn test_gltf_export() {
+        let mut objects = SceneObjects::default();
+        objects.add_object("box", Mesh::cuboid(Vec3::ONE));
+        objects.add_object("sun", Sun::new(10.0, -45.0, -45.0));
+        objects.add_object("cloud", dense_cloud());
+        objects.add_object("grid", Grid::new(10, 100.0));
+        // Hidden with its parent
+        objects.add_object(
+            "props",
+            Component::composite_from([("crate", Mesh::cuboid(Vec3::ONE))]),
+        );
+        objects.set_layer("props", "props").unwrap();
+
+        let camera = Camera::default();
+        let mut visitor = GltfExportVisitor::new(&camera, 320.0, 240.0)
+            .with_hidden_layers(BTreeSet::from(["props"]));
+        visitor.visit_composite(&objects);
+        let document = visitor.into_document();
+        let names = document
+            .nodes()
+            .iter()
+            .map(|x| x["name"].as_str().unwrap())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(
+            names,
+            BTreeSet::from(["box", "cloud", "grid", "sun", "view"])
+        );
+
+        // The view camera sits at the eye
+        let view = &document.nodes()[0]["matrix"];
+        let eye = camera.pos();
+        assert!((view[12].as_f64().unwrap() as f32 - eye.x).abs() < 1e-3);
+        assert!((view[14].as_f64().unwrap() as f32 - eye.z).abs() < 1e-3);
+    }
+}
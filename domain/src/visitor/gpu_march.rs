@@ -0,0 +1,353 @@
+//! Cloud march in a wgpu compute shader, an alternative to the CPU march of
+//! [`CloudMarch::run`], which stays the reference
+
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+use egui::{Color32, ColorImage};
+use glam::{Vec3, Vec4};
+
+use crate::canvas::gpu::{words_to_bytes, Gpu};
+use crate::object::objects::texture3d::Noise;
+use crate::object::objects::LightKind;
+use crate::visitor::raster::{color32_to_vec4, CloudMarch};
+
+/// Side of the workgroups of `cloud_march.wgsl`
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Pipeline marching the clouds on the [`Gpu`]. The view rays are computed
+/// on the CPU the same way as for the CPU march and the pixels are read
+/// back into the image, fog included. The occupancy grid and the coarse
+/// density field are left to the CPU march.
+#[derive(Debug)]
+pub struct GpuMarch {
+    gpu: &'static Gpu,
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::ComputePipeline,
+    /// Noise volumes uploaded as textures, kept while the clouds use them
+    volumes: Mutex<Vec<(Weak<Noise>, Arc<wgpu::TextureView>)>>,
+}
+
+impl GpuMarch {
+    pub fn new(gpu: &'static Gpu) -> Self {
+        let module = gpu
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("cloud march"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("cloud_march.wgsl").into()),
+            });
+        let entry = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty,
+            count: None,
+        };
+        let storage = |read_only| wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage { read_only },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        // The volumes are 32-bit float textures, which are only loaded
+        let volume = wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            view_dimension: wgpu::TextureViewDimension::D3,
+            multisampled: false,
+        };
+        let uniform = wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        };
+        let layout = gpu
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("cloud march"),
+                entries: &[
+                    entry(0, uniform),
+                    entry(1, storage(true)),
+                    entry(2, storage(true)),
+                    entry(3, storage(false)),
+                    entry(4, volume),
+                    entry(5, volume),
+                    entry(6, volume),
+                ],
+            });
+        let pipeline_layout = gpu
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("cloud march"),
+                bind_group_layouts: &[&layout],
+                push_constant_ranges: &[],
+            });
+        Self {
+            gpu,
+            pipeline: gpu.pipeline(&module, Some(&pipeline_layout), "main"),
+            layout,
+            volumes: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Pipeline on the shared device, `None` when there is no adapter
+    pub fn shared() -> Option<&'static Self> {
+        static SHARED: OnceLock<Option<GpuMarch>> = OnceLock::new();
+        SHARED.get_or_init(|| Gpu::shared().map(Self::new)).as_ref()
+    }
+
+    /// Texture of the noise, uploaded the first time it is marched
+    fn volume(&self, noise: &Arc<Noise>) -> Arc<wgpu::TextureView> {
+        let mut volumes = self.volumes.lock().unwrap();
+        volumes.retain(|(x, _)| x.strong_count() > 0);
+        if let Some((_, view)) = volumes
+            .iter()
+            .find(|(x, _)| std::ptr::eq(x.as_ptr(), Arc::as_ptr(noise)))
+        {
+            return view.clone();
+        }
+
+        let [x, y, z] = noise.size().map(|x| x as u32);
+        let size = wgpu::Extent3d {
+            width: x,
+            height: y,
+            depth_or_array_layers: z,
+        };
+        let texture = self.gpu.device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("cloud volume"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D3,
+            format: wgpu::TextureFormat::Rgba32Float,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let texels = noise
+            .texels()
+            .iter()
+            .flat_map(|x| x.to_array())
+            .flat_map(f32::to_le_bytes)
+            .collect::<Vec<_>>();
+        self.gpu.queue.write_texture(
+            texture.as_image_copy(),
+            &texels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(x * std::mem::size_of::<Vec4>() as u32),
+                rows_per_image: Some(y),
+            },
+            size,
+        );
+        let view = Arc::new(texture.create_view(&Default::default()));
+        volumes.push((Arc::downgrade(noise), view.clone()));
+        view
+    }
+
+    /// Marches the image of the march, `false` when the device fails and
+    /// the image is left as it was
+    pub fn run(&self, march: &CloudMarch, img: &mut ColorImage) -> bool {
+        use rayon::prelude::*;
+
+        let [w, h] = img.size;
+        let cloud = &march.cloud;
+        let volumes = cloud.volumes();
+        if volumes.iter().any(|x| x.texels().is_empty()) {
+            return false;
+        }
+        let uniforms = cloud.uniforms(&march.lights);
+        let origin = march.inverse.transform_point3(march.camera.pos());
+        let dirs = (0..w * h)
+            .into_par_iter()
+            .map(|i| march.pixel_dir([w, h], i % w, i / w))
+            .collect::<Vec<_>>();
+        // The shader marches in the space of the axis aligned volume
+        let (aligned_origin, aligned) = match uniforms.matrix() {
+            Some(matrix) => (
+                matrix.transform_point3(origin),
+                dirs.iter().map(|&x| matrix.transform_vector3(x)).collect(),
+            ),
+            None => (origin, dirs.clone()),
+        };
+
+        let bb = cloud.bounding_box();
+        let f = |x: Vec3, w: f32| [x.x, x.y, x.z, w].map(f32::to_bits);
+        let v = |x: Vec4| x.to_array().map(f32::to_bits);
+        let u = |x: [usize; 4]| x.map(|x| x as u32);
+        let params = [
+            f(bb.min, cloud.edge_distance),
+            f(bb.max, cloud.height_map_factor),
+            f(cloud.offset, cloud.cloud_scale),
+            f(cloud.detail_offset, cloud.detail_noise_scale),
+            v(cloud.shape_noise_weights),
+            v(cloud.detail_weights),
+            v(cloud.phase_params),
+            v(Vec4::new(
+                cloud.density_offset,
+                cloud.density_multiplier,
+                cloud.detail_noise_weight,
+                cloud.params.x,
+            )),
+            v(Vec4::new(
+                cloud.light_absorption_toward_sun,
+                cloud.light_absorption_through_cloud,
+                cloud.darkness_threshold,
+                0.0,
+            )),
+            f(aligned_origin, 1.0),
+            f(uniforms.tint(), 0.0),
+            u([
+                cloud.num_steps,
+                cloud.num_steps_light,
+                cloud.auto_steps as usize,
+                cloud.light_interval,
+            ]),
+            u([w, h, uniforms.lights().len(), 0]),
+        ]
+        .concat();
+        let mut lights = uniforms
+            .lights()
+            .iter()
+            .flat_map(|light| {
+                let position = match light.kind {
+                    LightKind::Directional(direction) => f(direction, 0.0),
+                    LightKind::Point(position) => f(position, 1.0),
+                };
+                let radiance = color32_to_vec4(light.color).truncate() * light.intensity;
+                [position, f(radiance, light.absorption)]
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        // A binding can't be empty
+        lights.resize(lights.len().max(8), 0);
+        let aligned = aligned.iter().flat_map(|&x| f(x, 0.0)).collect::<Vec<_>>();
+
+        let gpu = self.gpu;
+        let size = (w * h * std::mem::size_of::<u32>()) as u64;
+        let readback = gpu.submit(|encoder| {
+            let [shape, detail, weather] = volumes.map(|x| self.volume(x));
+            let params = gpu.buffer(
+                "cloud params",
+                &words_to_bytes(&params),
+                wgpu::BufferUsages::UNIFORM,
+            );
+            let lights = gpu.buffer(
+                "cloud lights",
+                &words_to_bytes(&lights),
+                wgpu::BufferUsages::STORAGE,
+            );
+            let dirs = gpu.buffer(
+                "cloud rays",
+                &words_to_bytes(&aligned),
+                wgpu::BufferUsages::STORAGE,
+            );
+            let pixels = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("cloud pixels"),
+                size,
+                usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            let buffers = [&params, &lights, &dirs, &pixels];
+            let views = [&shape, &detail, &weather];
+            let entries = buffers
+                .iter()
+                .map(|x| x.as_entire_binding())
+                .chain(
+                    views
+                        .iter()
+                        .map(|x| wgpu::BindingResource::TextureView(x.as_ref())),
+                )
+                .enumerate()
+                .map(|(binding, resource)| wgpu::BindGroupEntry {
+                    binding: binding as u32,
+                    resource,
+                })
+                .collect::<Vec<_>>();
+            let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("cloud march"),
+                layout: &self.layout,
+                entries: &entries,
+            });
+
+            {
+                let mut pass = encoder.begin_compute_pass(&Default::default());
+                pass.set_pipeline(&self.pipeline);
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.dispatch_workgroups(
+                    (w as u32).div_ceil(WORKGROUP_SIZE),
+                    (h as u32).div_ceil(WORKGROUP_SIZE),
+                    1,
+                );
+            }
+            let readback = gpu.readback_buffer(size);
+            encoder.copy_buffer_to_buffer(&pixels, 0, &readback, 0, size);
+            readback
+        });
+        let Some(words) = readback.and_then(|x| gpu.read_words(&x)) else {
+            return false;
+        };
+
+        let obb = cloud.obb();
+        img.pixels
+            .par_iter_mut()
+            .zip(words)
+            .zip(dirs)
+            .for_each(|((pixel, word), dir)| {
+                let [r, g, b, a] = word.to_le_bytes();
+                *pixel = Color32::from_rgba_unmultiplied(r, g, b, a);
+                if let Some(fog) = march.fog.filter(|_| a > 0) {
+                    let distance = obb.dst(origin, dir).x;
+                    *pixel = fog.apply(*pixel, origin, dir, distance);
+                }
+            });
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::camera::Camera;
+    use crate::object::objects::cloud::tests::dense_cloud;
+    use crate::object::objects::Light;
+    use glam::Mat4;
+
+    #[test]
+    fn test_gpu_march() {
+        let Some(gpu) = GpuMarch::shared() else {
+            // Nothing to compare on a machine without an adapter
+            return;
+        };
+        let cloud = dense_cloud();
+        let march = CloudMarch {
+            cloud,
+            camera: Camera::default(),
+            viewport: [64, 64],
+            inverse: Mat4::IDENTITY,
+            lights: vec![Light::directional(Vec3::NEG_Y), Light::point(Vec3::Y)],
+            fog: None,
+            rect: egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(64.0, 64.0)),
+            hull: None,
+            rays: None,
+            occupancy: None,
+            density: None,
+            gpu: false,
+            base: None,
+            profiler: None,
+        };
+        let mut cpu = ColorImage::new([32, 32], Color32::TRANSPARENT);
+        march.run(&mut cpu);
+        let mut on_gpu = ColorImage::new([32, 32], Color32::TRANSPARENT);
+        assert!(gpu.run(&march, &mut on_gpu));
+        let differences = cpu
+            .pixels
+            .iter()
+            .zip(&on_gpu.pixels)
+            .filter(|(a, b)| {
+                a.to_array()
+                    .iter()
+                    .zip(b.to_array())
+                    .any(|(a, b)| a.abs_diff(b) > 2)
+            })
+            .count();
+        assert!(cpu.pixels.iter().any(|x| x.a() > 0));
+        // Rounding may tip a few rays over a threshold
+        assert!(differences <= cpu.pixels.len() / 50, "{differences}");
+    }
+}
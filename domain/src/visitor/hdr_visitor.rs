@@ -0,0 +1,118 @@
+use std::collections::BTreeSet;
+
+use glam::Mat4;
+
+use crate::canvas::hdr_target::HdrTarget;
+use crate::object::camera::Camera;
+use crate::object::objects::occupancy_grid::OCCUPANCY_GRID_SIZE;
+use crate::object::objects::{Cloud, Light, OccupancyGrid};
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::{draw_order, Visitable, Visitor};
+
+/// Accumulates the linear light of the clouds into an [`HdrTarget`], from
+/// the nearest to the farthest. The other objects are left out.
+pub struct HdrVisitor<'a> {
+    camera: &'a Camera,
+    target: HdrTarget,
+    lights: Vec<Light>,
+    /// World matrix of the object being visited
+    model: Mat4,
+    hidden_layers: BTreeSet<&'static str>,
+}
+
+impl<'a> HdrVisitor<'a> {
+    pub fn new(camera: &'a Camera, width: usize, height: usize) -> Self {
+        Self {
+            camera,
+            target: HdrTarget::new(width, height),
+            lights: Vec::new(),
+            model: Mat4::IDENTITY,
+            hidden_layers: BTreeSet::new(),
+        }
+    }
+
+    pub fn with_hidden_layers(mut self, hidden_layers: BTreeSet<&'static str>) -> Self {
+        self.hidden_layers = hidden_layers;
+        self
+    }
+
+    pub fn into_target(self) -> HdrTarget {
+        self.target
+    }
+}
+
+impl<'a> Visitor for HdrVisitor<'a> {
+    type Output = ();
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) {
+        let parent = self.model;
+        self.lights.extend(scene_objects.lights(parent));
+        let objects = draw_order(
+            scene_objects,
+            parent,
+            self.camera.pos(),
+            &self.hidden_layers,
+        );
+        for (_, model, x) in objects.into_iter().rev() {
+            self.model = model;
+            x.accept(self);
+        }
+        self.model = parent;
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) {
+        let inverse = self.model.inverse();
+        let lights = self
+            .lights
+            .iter()
+            .map(|x| x.transformed(inverse))
+            .collect::<Vec<_>>();
+        let occupancy = OccupancyGrid::new(cloud, OCCUPANCY_GRID_SIZE);
+        let uniforms = cloud
+            .uniforms(&lights)
+            .with_occupancy(Some(occupancy.into()));
+        let [width, height] = self.target.size();
+        let camera = self.camera;
+        let origin = inverse.transform_point3(camera.pos());
+        self.target.add_behind(|idx| {
+            let (i, j) = (idx / width, idx % width);
+            let target = inverse.transform_point3(camera.egui_to_world(i, j, width, height));
+            let sample = cloud.march_radiance(&uniforms, origin, (target - origin).normalize());
+            (sample.radiance(), sample.transmittance)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::tests::dense_cloud;
+    use crate::object::objects::Sun;
+    use crate::scene::scene::Scene;
+    use crate::scene::scene_composite::DEFAULT_LAYER;
+
+    #[test]
+    fn test_hdr_visitor() {
+        let mut cloud = dense_cloud();
+        cloud.density_offset = -10.0;
+        let mut scene = Scene::default();
+        scene.add_object("sun", Sun::new(10.0, 170.0, 0.0));
+        scene.add_object("cloud", cloud);
+        let camera = Camera::default();
+        let render = |hidden_layers| {
+            let mut visitor = HdrVisitor::new(&camera, 32, 32).with_hidden_layers(hidden_layers);
+            scene.accept(&mut visitor);
+            visitor.into_target()
+        };
+
+        let target = render(BTreeSet::new());
+        let covered = target.transmittance().iter().filter(|x| **x < 1.0).count();
+        assert!(covered > 0);
+        for (radiance, t) in target.radiance().iter().zip(target.transmittance()) {
+            assert!((0.0..=1.0).contains(t));
+            assert_eq!(*t == 1.0, *radiance == glam::Vec3::ZERO);
+        }
+        let empty = render(BTreeSet::from([DEFAULT_LAYER]));
+        assert_eq!(empty, HdrTarget::new(32, 32));
+    }
+}
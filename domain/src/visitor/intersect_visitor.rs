@@ -0,0 +1,228 @@
+//! Intersection of a ray with the objects, the core of picking and of the
+//! tools measuring the scene
+
+use glam::{Mat4, Vec3};
+
+use crate::object::objects::{BoundingBox, Cloud, Light, LightKind, Mesh, Sun, Terrain, Water};
+use crate::object::Component;
+use crate::scene::pick::HitRecord;
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::{visit_visible, Visitor, WorldVisitor};
+
+/// Radius of the sphere that stands for point-like objects such as the sun,
+/// relative to their distance from the ray origin so that they keep the same
+/// size on screen
+const POINT_PICK_RADIUS: f32 = 0.02;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Vec3,
+    /// Normalized direction
+    pub dir: Vec3,
+}
+
+impl Ray {
+    pub fn new(origin: Vec3, dir: Vec3) -> Self {
+        Self {
+            origin,
+            dir: dir.normalize(),
+        }
+    }
+
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.dir * distance
+    }
+}
+
+/// Collects the visible objects hit by the ray. Objects inside nested
+/// composites are reported by their own names.
+pub struct IntersectVisitor {
+    ray: Ray,
+    /// World matrix of the object being visited
+    model: Mat4,
+    id: &'static str,
+}
+
+impl IntersectVisitor {
+    pub fn new(ray: Ray) -> Self {
+        Self {
+            ray,
+            model: Mat4::IDENTITY,
+            id: "",
+        }
+    }
+
+    /// World matrix of the visited composite
+    pub fn with_model(mut self, model: Mat4) -> Self {
+        self.model = model;
+        self
+    }
+
+    /// Ray moved to the local space of the visited object. The matrix is
+    /// affine, so the ray parameter in local space is the world distance
+    /// along the ray.
+    fn local_ray(&self) -> (Vec3, Vec3) {
+        let inverse = self.model.inverse();
+        (
+            inverse.transform_point3(self.ray.origin),
+            inverse.transform_vector3(self.ray.dir),
+        )
+    }
+
+    fn hit(&self, distance: Option<f32>) -> Vec<HitRecord> {
+        distance
+            .map(|distance| HitRecord {
+                id: self.id,
+                distance,
+                point: self.ray.at(distance),
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+impl WorldVisitor for IntersectVisitor {
+    fn model_mut(&mut self) -> &mut Mat4 {
+        &mut self.model
+    }
+
+    fn enter(
+        &mut self,
+        _scene_objects: &SceneObjects,
+        id: &'static str,
+        _object: &Component,
+    ) -> bool {
+        self.id = id;
+        true
+    }
+}
+
+impl Visitor for IntersectVisitor {
+    /// Hits sorted from the nearest one
+    type Output = Vec<HitRecord>;
+
+    fn join(&mut self, output: &mut Vec<HitRecord>, next: Vec<HitRecord>) {
+        output.extend(next);
+    }
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) -> Vec<HitRecord> {
+        let mut hits = visit_visible(self, scene_objects);
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) -> Vec<HitRecord> {
+        let (origin, dir) = self.local_ray();
+        let volume = cloud.volume_matrix();
+        self.hit(intersect_box(
+            cloud.bounding_box(),
+            volume.transform_point3(origin),
+            volume.transform_vector3(dir),
+        ))
+    }
+
+    fn visit_sun(&mut self, sun: &Sun) -> Vec<HitRecord> {
+        let (origin, dir) = self.local_ray();
+        self.hit(intersect_point(sun.get_pos(), origin, dir))
+    }
+
+    fn visit_terrain(&mut self, terrain: &Terrain) -> Vec<HitRecord> {
+        let (origin, dir) = self.local_ray();
+        if intersect_box(&terrain.bounding_box, origin, dir).is_none() {
+            return Vec::new();
+        }
+        self.hit(nearest(terrain.triangles.iter().filter_map(|(tri, _)| {
+            intersect_triangle(tri.to_array(), origin, dir)
+        })))
+    }
+
+    fn visit_water(&mut self, water: &Water) -> Vec<HitRecord> {
+        let (origin, dir) = self.local_ray();
+        self.hit(intersect_box(&water.bounding_box, origin, dir))
+    }
+
+    fn visit_mesh(&mut self, mesh: &Mesh) -> Vec<HitRecord> {
+        let (origin, dir) = self.local_ray();
+        if intersect_box(&mesh.bounding_box(), origin, dir).is_none() {
+            return Vec::new();
+        }
+        let vertex = |i: u32| mesh.vertices[i as usize];
+        self.hit(nearest(mesh.faces.iter().filter_map(|&[a, b, c]| {
+            intersect_triangle([vertex(a), vertex(b), vertex(c)], origin, dir)
+        })))
+    }
+
+    fn visit_light(&mut self, light: &Light) -> Vec<HitRecord> {
+        let LightKind::Point(position) = light.kind else {
+            return Vec::new();
+        };
+        let (origin, dir) = self.local_ray();
+        self.hit(intersect_point(position, origin, dir))
+    }
+}
+
+fn nearest(distances: impl Iterator<Item = f32>) -> Option<f32> {
+    distances.min_by(f32::total_cmp)
+}
+
+fn intersect_box(bounding_box: &BoundingBox, origin: Vec3, dir: Vec3) -> Option<f32> {
+    let t0 = (bounding_box.min - origin) / dir;
+    let t1 = (bounding_box.max - origin) / dir;
+    let near = t0.min(t1).max_element().max(0.0);
+    let far = t0.max(t1).min_element();
+    (near <= far).then_some(near)
+}
+
+/// Möller–Trumbore ray-triangle intersection, both sides are hit
+fn intersect_triangle([a, b, c]: [Vec3; 3], origin: Vec3, dir: Vec3) -> Option<f32> {
+    let (ab, ac) = (b - a, c - a);
+    let p = dir.cross(ac);
+    let det = ab.dot(p);
+    if det.abs() <= f32::EPSILON {
+        return None;
+    }
+
+    let s = origin - a;
+    let u = s.dot(p) / det;
+    let q = s.cross(ab);
+    let v = dir.dot(q) / det;
+    if u < 0.0 || v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(q) / det;
+    (t >= 0.0).then_some(t)
+}
+
+fn intersect_point(center: Vec3, origin: Vec3, dir: Vec3) -> Option<f32> {
+    let to_center = center - origin;
+    let radius = POINT_PICK_RADIUS * to_center.length();
+    let t = to_center.dot(dir) / dir.length_squared();
+    let closest = origin + dir * t;
+    (t >= 0.0 && closest.distance(center) <= radius).then_some(t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::ObjectTransform;
+
+    #[test]
+    fn test_nested_composite() {
+        let box_mesh = Mesh::cuboid(Vec3::ONE);
+        let mut objects = SceneObjects::default();
+        objects.add_object("group", Component::composite_from([("box", box_mesh)]));
+        *objects.local_transform_mut("group").unwrap() =
+            ObjectTransform::from_translation(Vec3::new(3.0, 0.0, 0.0));
+
+        let ray = Ray::new(Vec3::new(3.0, 0.0, 5.0), Vec3::NEG_Z);
+        let hits = IntersectVisitor::new(ray).visit_composite(&objects);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].id, "box");
+        assert!(hits[0].point.abs_diff_eq(Vec3::new(3.0, 0.0, 0.5), 1e-5));
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::NEG_Z);
+        assert!(IntersectVisitor::new(ray)
+            .visit_composite(&objects)
+            .is_empty());
+    }
+}
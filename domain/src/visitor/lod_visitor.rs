@@ -0,0 +1,178 @@
+//! Quality levels of the objects chosen by their size on screen
+
+use std::collections::BTreeMap;
+
+use glam::{Mat4, Vec3};
+
+use crate::object::camera::Camera;
+use crate::object::objects::{BoundingBox, Cloud, Grid, Mesh};
+use crate::object::Component;
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::{visit_visible, Visitor, WorldVisitor};
+
+/// Clouds at least this many pixels across are marched with all the steps
+const FULL_QUALITY_PIXELS: f32 = 400.0;
+/// Smallest share of the steps a distant cloud is marched with
+const MIN_QUALITY: f32 = 0.25;
+/// Fewest steps of a reduced cloud
+const MIN_STEPS: usize = 8;
+/// Meshes smaller on screen are drawn as their bounding box
+const MESH_PROXY_PIXELS: f32 = 12.0;
+
+/// Quality an object is drawn with in the current frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lod {
+    Cloud {
+        num_steps: usize,
+        num_steps_light: usize,
+    },
+    /// Number of base grid cells per scale
+    Grid { k: i32 },
+    /// The mesh is replaced with its bounding box when it covers a few
+    /// pixels
+    Mesh { proxy: bool },
+}
+
+/// Picks the quality of the visible clouds, grids and meshes for the
+/// camera: the fewer pixels an object covers, the cheaper it is drawn.
+/// Objects drawn at full quality are left out.
+pub struct LodVisitor {
+    eye: Vec3,
+    camera_distance: f32,
+    /// Pixels covered by a unit sized object at a unit distance
+    pixels_per_unit: f32,
+    /// World matrix of the object being visited
+    model: Mat4,
+    id: &'static str,
+}
+
+impl LodVisitor {
+    pub fn new(camera: &Camera, height: f32) -> Self {
+        Self {
+            eye: camera.pos(),
+            camera_distance: camera.view.distance,
+            pixels_per_unit: height / (2.0 * (camera.proj.fov / 2.0).tan()),
+            model: Mat4::IDENTITY,
+            id: "",
+        }
+    }
+
+    /// Approximate number of pixels across the box on screen
+    fn screen_size(&self, local: &BoundingBox) -> f32 {
+        let world = local.transformed(self.model);
+        let distance = world.center().distance(self.eye).max(f32::EPSILON);
+        world.size().length() / distance * self.pixels_per_unit
+    }
+
+    fn level(&self, lod: Lod) -> BTreeMap<&'static str, Lod> {
+        BTreeMap::from([(self.id, lod)])
+    }
+}
+
+/// Steps of the reduced march, never more than `steps`
+fn reduced(steps: usize, quality: f32) -> usize {
+    ((steps as f32 * quality).ceil() as usize)
+        .max(MIN_STEPS)
+        .min(steps)
+}
+
+impl WorldVisitor for LodVisitor {
+    fn model_mut(&mut self) -> &mut Mat4 {
+        &mut self.model
+    }
+
+    fn enter(
+        &mut self,
+        _scene_objects: &SceneObjects,
+        id: &'static str,
+        _object: &Component,
+    ) -> bool {
+        self.id = id;
+        true
+    }
+}
+
+impl Visitor for LodVisitor {
+    type Output = BTreeMap<&'static str, Lod>;
+
+    fn join(&mut self, output: &mut Self::Output, next: Self::Output) {
+        output.extend(next);
+    }
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) -> Self::Output {
+        visit_visible(self, scene_objects)
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) -> Self::Output {
+        let quality = self.screen_size(&cloud.obb().aabb()) / FULL_QUALITY_PIXELS;
+        if quality >= 1.0 {
+            return BTreeMap::new();
+        }
+        let quality = quality.max(MIN_QUALITY);
+        self.level(Lod::Cloud {
+            num_steps: reduced(cloud.num_steps, quality),
+            num_steps_light: reduced(cloud.num_steps_light, quality),
+        })
+    }
+
+    /// The fine cells are dropped as the camera backs off from the grid
+    fn visit_grid(&mut self, grid: &Grid) -> Self::Output {
+        let cells = (grid.k as f32 * grid.scale / self.camera_distance).ceil() as i32;
+        if cells >= grid.k {
+            return BTreeMap::new();
+        }
+        self.level(Lod::Grid { k: cells.max(1) })
+    }
+
+    fn visit_mesh(&mut self, mesh: &Mesh) -> Self::Output {
+        if self.screen_size(&mesh.bounding_box()) >= MESH_PROXY_PIXELS {
+            return BTreeMap::new();
+        }
+        self.level(Lod::Mesh { proxy: true })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::CloudBuilder;
+    use crate::scene::ObjectTransform;
+
+    #[test]
+    fn test_lod() {
+        let cloud = CloudBuilder::default()
+            .with_bounding_box((Vec3::ZERO, Vec3::ONE))
+            .with_num_steps(100)
+            .with_num_steps_light(10);
+        let cloud = Cloud::with_volumes(
+            cloud,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let mut objects = SceneObjects::default();
+        objects.add_object("cloud", cloud);
+        objects.add_object("near", Mesh::cuboid(Vec3::ONE));
+        objects.add_object("far", Mesh::cuboid(Vec3::ONE));
+        objects.add_object("grid", Grid::new(10, 1.0));
+        let camera = Camera::default();
+        *objects.local_transform_mut("near").unwrap() =
+            ObjectTransform::from_translation(camera.pos() * 0.8);
+        *objects.local_transform_mut("far").unwrap() =
+            ObjectTransform::from_translation(camera.pos() * -20.0);
+
+        let levels = LodVisitor::new(&camera, 900.0).visit_composite(&objects);
+        let Some(&Lod::Cloud {
+            num_steps,
+            num_steps_light,
+        }) = levels.get("cloud")
+        else {
+            panic!("the cloud is drawn at full quality");
+        };
+        assert!((25..100).contains(&num_steps));
+        assert_eq!(num_steps_light, MIN_STEPS);
+        assert_eq!(levels.get("far"), Some(&Lod::Mesh { proxy: true }));
+        assert_eq!(levels.get("near"), None);
+        assert_eq!(levels.get("grid"), Some(&Lod::Grid { k: 1 }));
+    }
+}
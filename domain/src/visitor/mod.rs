@@ -1,27 +1,343 @@
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+use glam::{Mat4, Vec3};
+
 use crate::object::camera::Camera;
 use crate::object::objects::cloud::Cloud;
-use crate::object::objects::{BoundingBox, Grid, Sun, Terrain};
+use crate::object::objects::{
+    Background, BoundingBox, Fog, Grid, Light, Mesh, OrientationGizmo, Skybox, Sun, Terrain,
+    TransformGizmo, Water,
+};
+use crate::object::plugin::PluginComponent;
+use crate::object::Component;
 use crate::scene::scene_composite::SceneObjects;
 
+pub mod bounds_visitor;
+pub mod cull_visitor;
+pub mod depth_sort_visitor;
 pub mod draw_visitor;
+pub mod gltf_visitor;
+#[cfg(feature = "gpu")]
+pub mod gpu_march;
+pub mod hdr_visitor;
+pub mod intersect_visitor;
+pub mod lod_visitor;
+pub mod offscreen_visitor;
+pub mod raster;
+pub mod serialize_visitor;
+pub mod shadow_visitor;
+pub mod stats_visitor;
+pub mod svg_visitor;
+pub mod update_visitor;
+pub mod validate_visitor;
+
+/// Visible objects of the composite outside the hidden render layers with
+/// their names and world matrices, in the order they are painted: by draw layer, then
+/// from the farthest to the nearest to the eye
+pub(crate) fn draw_order<'a>(
+    scene_objects: &'a SceneObjects,
+    parent: Mat4,
+    eye: Vec3,
+    hidden_layers: &BTreeSet<&'static str>,
+) -> Vec<(&'static str, Mat4, &'a Component)> {
+    let mut objs = scene_objects
+        .visible()
+        .filter(|(name, _)| !hidden_layers.contains(scene_objects.layer(name)))
+        .map(|(name, x)| (name, parent * scene_objects.world_transform(name), x))
+        .collect::<Vec<_>>();
+    objs.sort_by(|(_, mx, x), (_, my, y)| {
+        let dx = mx.transform_point3(x.pos()).distance(eye);
+        let dy = my.transform_point3(y.pos()).distance(eye);
+        x.layer()
+            .cmp(&y.layer())
+            .then_with(|| dy.partial_cmp(&dx).unwrap_or(Ordering::Greater))
+    });
+    objs
+}
+
+/// Visible object of the composite, its nested composites or plugins with
+/// its world matrix. Objects in the hidden layers, or inside a composite in
+/// one, are not found.
+pub(crate) fn find_visible<'a>(
+    scene_objects: &'a SceneObjects,
+    parent: Mat4,
+    name: &str,
+    hidden_layers: &BTreeSet<&'static str>,
+) -> Option<(Mat4, &'a Component)> {
+    scene_objects
+        .visible()
+        .filter(|(id, _)| !hidden_layers.contains(scene_objects.layer(id)))
+        .find_map(|(id, x)| {
+            let model = parent * scene_objects.world_transform(id);
+            match x {
+                _ if id == name => Some((model, x)),
+                Component::Composite(objects) => find_visible(objects, model, name, hidden_layers),
+                Component::Plugin(plugin) => {
+                    find_visible(plugin.parts(), model, name, hidden_layers)
+                }
+                _ => None,
+            }
+        })
+}
+
+/// First visible sun of the composite moved by its world matrix, `parent`
+/// is the world matrix of the composite
+pub(crate) fn world_sun(scene_objects: &SceneObjects, parent: Mat4) -> Option<Sun> {
+    scene_objects.visible().find_map(|(name, x)| match x {
+        Component::Sun(sun) => Some(sun.transformed(parent * scene_objects.world_transform(name))),
+        _ => None,
+    })
+}
+
+/// Visitor that keeps the world matrix of the object being visited, its
+/// composites are walked by [`visit_visible`]
+pub(crate) trait WorldVisitor: Visitor {
+    /// World matrix of the object being visited
+    fn model_mut(&mut self) -> &mut Mat4;
+
+    /// Called before the object is visited, it is skipped when `false` is
+    /// returned
+    fn enter(
+        &mut self,
+        _scene_objects: &SceneObjects,
+        _id: &'static str,
+        _object: &Component,
+    ) -> bool {
+        true
+    }
+}
+
+/// Visits the visible objects of the composite, nested composites included,
+/// with the world matrix of each set on the visitor and joins their outputs
+pub(crate) fn visit_visible<V: WorldVisitor>(
+    visitor: &mut V,
+    scene_objects: &SceneObjects,
+) -> V::Output {
+    let parent = *visitor.model_mut();
+    let mut output = V::Output::default();
+    for (id, object) in scene_objects.visible() {
+        if !visitor.enter(scene_objects, id, object) {
+            continue;
+        }
+        *visitor.model_mut() = parent * scene_objects.world_transform(id);
+        let next = match object {
+            Component::Composite(objects) => visitor.visit_composite(objects),
+            object => object.accept(visitor),
+        };
+        visitor.join(&mut output, next);
+    }
+    *visitor.model_mut() = parent;
+    output
+}
+
+/// Share of the light of the first visible sun of the composite that passes
+/// through its clouds to the eye
+pub(crate) fn sun_visibility(scene_objects: &SceneObjects, parent: Mat4, eye: Vec3) -> f32 {
+    let Some(sun) = world_sun(scene_objects, parent).map(|x| x.get_pos()) else {
+        return 1.0;
+    };
+    scene_objects
+        .visible()
+        .filter_map(|(name, x)| match x {
+            Component::Cloud(cloud) => {
+                let inverse = (parent * scene_objects.world_transform(name)).inverse();
+                let origin = inverse.transform_point3(eye);
+                let dir = (inverse.transform_point3(sun) - origin).normalize_or_zero();
+                Some(cloud.transmittance(origin, dir))
+            }
+            _ => None,
+        })
+        .product()
+}
 
 pub trait Visitable {
-    fn accept(&self, visitor: &mut impl Visitor);
+    /// Dispatches to the method of the visitor for this object and returns
+    /// what it produced
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output;
 }
 
 pub trait Visitor: Sized + Send + Sync {
-    fn visit_composite(&mut self, scene_objects: &SceneObjects) {
+    /// Result of a visit, e.g. the hits of a ray or the box around the
+    /// object. Objects the visitor skips give the default value.
+    type Output: Default;
+
+    /// Adds the output of the next object of a composite to the output of
+    /// the composite, the outputs of the objects are dropped by default
+    fn join(&mut self, _output: &mut Self::Output, _next: Self::Output) {}
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) -> Self::Output {
         // use rayon::prelude::*;
+        let mut output = Self::Output::default();
         for (_, i) in scene_objects.iter() {
-            i.accept(self)
+            let next = i.accept(self);
+            self.join(&mut output, next);
         }
+        output
     }
 
-    fn visit_camera(&mut self, _camera: &Camera) {}
-    fn visit_cloud(&mut self, _cloud: &Cloud) {}
-    fn visit_grid(&mut self, _grid: &Grid) {}
-    fn visit_bounding_box(&mut self, _bb: &BoundingBox) {}
-    fn visit_sun(&mut self, _bb: &Sun) {}
+    fn visit_camera(&mut self, _camera: &Camera) -> Self::Output {
+        Default::default()
+    }
+    fn visit_cloud(&mut self, _cloud: &Cloud) -> Self::Output {
+        Default::default()
+    }
+    fn visit_grid(&mut self, _grid: &Grid) -> Self::Output {
+        Default::default()
+    }
+    fn visit_bounding_box(&mut self, _bb: &BoundingBox) -> Self::Output {
+        Default::default()
+    }
+    fn visit_sun(&mut self, _bb: &Sun) -> Self::Output {
+        Default::default()
+    }
 
-    fn visit_terrain(&mut self, _terrain: &Terrain) {}
+    fn visit_terrain(&mut self, _terrain: &Terrain) -> Self::Output {
+        Default::default()
+    }
+    fn visit_gizmo(&mut self, _gizmo: &OrientationGizmo) -> Self::Output {
+        Default::default()
+    }
+    fn visit_background(&mut self, _background: &Background) -> Self::Output {
+        Default::default()
+    }
+    fn visit_water(&mut self, _water: &Water) -> Self::Output {
+        Default::default()
+    }
+    fn visit_skybox(&mut self, _skybox: &Skybox) -> Self::Output {
+        Default::default()
+    }
+    fn visit_mesh(&mut self, _mesh: &Mesh) -> Self::Output {
+        Default::default()
+    }
+    fn visit_light(&mut self, _light: &Light) -> Self::Output {
+        Default::default()
+    }
+    fn visit_transform_gizmo(&mut self, _gizmo: &TransformGizmo) -> Self::Output {
+        Default::default()
+    }
+    fn visit_fog(&mut self, _fog: &Fog) -> Self::Output {
+        Default::default()
+    }
+    /// Visits the parts of the plugin as a nested composite
+    fn visit_plugin(&mut self, plugin: &dyn PluginComponent) -> Self::Output {
+        self.visit_composite(plugin.parts())
+    }
+}
+
+/// Objects that let a [`VisitorMut`] change them, such as the per-frame
+/// updates of the wind or the sun
+pub trait VisitableMut {
+    fn accept_mut(&mut self, visitor: &mut impl VisitorMut);
+}
+
+/// Mutable counterpart of [`Visitor`]
+pub trait VisitorMut: Sized + Send + Sync {
+    fn visit_composite_mut(&mut self, scene_objects: &mut SceneObjects) {
+        for i in scene_objects.values_mut() {
+            i.accept_mut(self)
+        }
+    }
+
+    fn visit_camera_mut(&mut self, _camera: &mut Camera) {}
+    fn visit_cloud_mut(&mut self, _cloud: &mut Cloud) {}
+    fn visit_grid_mut(&mut self, _grid: &mut Grid) {}
+    fn visit_sun_mut(&mut self, _sun: &mut Sun) {}
+    fn visit_terrain_mut(&mut self, _terrain: &mut Terrain) {}
+    fn visit_gizmo_mut(&mut self, _gizmo: &mut OrientationGizmo) {}
+    fn visit_background_mut(&mut self, _background: &mut Background) {}
+    fn visit_water_mut(&mut self, _water: &mut Water) {}
+    fn visit_skybox_mut(&mut self, _skybox: &mut Skybox) {}
+    /// The model is shared with the other objects of the same mesh, use
+    /// [`std::sync::Arc::make_mut`] to change only this one
+    fn visit_mesh_mut(&mut self, _mesh: &mut std::sync::Arc<Mesh>) {}
+    fn visit_light_mut(&mut self, _light: &mut Light) {}
+    fn visit_transform_gizmo_mut(&mut self, _gizmo: &mut TransformGizmo) {}
+    fn visit_fog_mut(&mut self, _fog: &mut Fog) {}
+    /// Plugins are shared and can only be replaced as a whole
+    fn visit_plugin_mut(&mut self, _plugin: &mut std::sync::Arc<dyn PluginComponent>) {}
+}
+
+impl VisitableMut for Component {
+    fn accept_mut(&mut self, visitor: &mut impl VisitorMut) {
+        match self {
+            Component::Camera(camera) => visitor.visit_camera_mut(camera),
+            Component::Composite(composite) => composite.accept_mut(visitor),
+            Component::Cloud(cloud) => visitor.visit_cloud_mut(cloud),
+            Component::Grid(grid) => visitor.visit_grid_mut(grid),
+            Component::Sun(sun) => visitor.visit_sun_mut(sun),
+            Component::Terrain(ter) => visitor.visit_terrain_mut(ter),
+            Component::Gizmo(gizmo) => visitor.visit_gizmo_mut(gizmo),
+            Component::Background(bg) => visitor.visit_background_mut(bg),
+            Component::Water(water) => visitor.visit_water_mut(water),
+            Component::Skybox(skybox) => visitor.visit_skybox_mut(skybox),
+            Component::Mesh(mesh) => visitor.visit_mesh_mut(mesh),
+            Component::Light(light) => visitor.visit_light_mut(light),
+            Component::TransformGizmo(gizmo) => visitor.visit_transform_gizmo_mut(gizmo),
+            Component::Fog(fog) => visitor.visit_fog_mut(fog),
+            Component::Plugin(plugin) => visitor.visit_plugin_mut(plugin),
+        }
+    }
+}
+
+impl VisitableMut for SceneObjects {
+    fn accept_mut(&mut self, visitor: &mut impl VisitorMut) {
+        visitor.visit_composite_mut(self);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::Water;
+
+    /// Moves every sun and water of the scene, nested ones included
+    struct Advance(f32);
+
+    impl VisitorMut for Advance {
+        fn visit_sun_mut(&mut self, sun: &mut Sun) {
+            sun.a += self.0;
+        }
+
+        fn visit_water_mut(&mut self, water: &mut Water) {
+            water.advance(self.0);
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut() {
+        let water = Water::new((Vec3::splat(-1.0), Vec3::ONE));
+        let mut objects = SceneObjects::default();
+        objects.add_object("sun", Sun::new(10.0, 0.0, 0.0));
+        objects.add_object("lake", Component::composite_from([("water", water)]));
+        objects.add_object("grid", Grid::new(10, 1.0));
+
+        objects.accept_mut(&mut Advance(0.5));
+        let Some(Component::Sun(sun)) = objects.get_object("sun") else {
+            panic!("no sun");
+        };
+        assert_eq!(sun.a, 0.5);
+        let Some(Component::Composite(lake)) = objects.get_object("lake") else {
+            panic!("no lake");
+        };
+        let Some(Component::Water(water)) = lake.get_object("water") else {
+            panic!("no water");
+        };
+        assert_eq!(water.time, 0.5);
+    }
+
+    #[test]
+    fn test_find_visible_in_hidden_parent() {
+        let mut objects = SceneObjects::default();
+        objects.add_object(
+            "sky",
+            Component::composite_from([("sun", Sun::new(10.0, 0.0, 0.0))]),
+        );
+        let mut hidden = BTreeSet::new();
+        assert!(find_visible(&objects, Mat4::IDENTITY, "sun", &hidden).is_some());
+
+        objects.set_layer("sky", "background").unwrap();
+        hidden.insert("background");
+        assert!(find_visible(&objects, Mat4::IDENTITY, "sun", &hidden).is_none());
+    }
 }
@@ -0,0 +1,419 @@
+use std::collections::BTreeSet;
+
+use egui::{Color32, Pos2, Rect};
+use glam::{Mat4, Vec3};
+
+use crate::canvas::render_target::RenderTarget;
+use crate::math::Transform;
+use crate::object::camera::Camera;
+use crate::object::objects::occupancy_grid::OCCUPANCY_GRID_SIZE;
+use crate::object::objects::{
+    Background, BoundingBox, Cloud, Fog, Grid, Light, LightKind, Mesh, OccupancyGrid, Skybox, Sun,
+    Terrain, Water,
+};
+use crate::object::Component;
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::raster::{cloud_shadow, rasterize_terrain, sky_color};
+use crate::visitor::shadow_visitor::ShadowMap;
+use crate::visitor::{draw_order, sun_visibility, world_sun, Visitable, Visitor};
+
+/// Renders the scene into an owned pixel buffer instead of the egui painter,
+/// so the output resolution does not depend on the window size
+pub struct OffscreenVisitor<'a> {
+    camera: &'a Camera,
+    shadow_caster: Option<&'a Cloud>,
+    shadow_map: Option<&'a ShadowMap>,
+    target: RenderTarget,
+    sun: Option<Sun>,
+    lights: Vec<Light>,
+    background_filled: bool,
+    /// World matrix of the object being visited
+    model: Mat4,
+    view_projection: Transform,
+    mvp: Transform,
+    /// Render layers that are not drawn
+    hidden_layers: BTreeSet<&'static str>,
+    /// Share of the sunlight passing through the clouds of the composite
+    sun_visibility: f32,
+    fog: Option<Fog>,
+}
+
+impl<'a> OffscreenVisitor<'a> {
+    pub fn new(camera: &'a Camera, width: usize, height: usize) -> Self {
+        let (w, h) = (width as f32, height as f32);
+        let mvp = Transform::new(
+            camera.projection(w, h) * camera.view(),
+            Rect::from_min_size(Pos2::ZERO, (w, h).into()),
+        );
+        Self {
+            camera,
+            shadow_caster: None,
+            shadow_map: None,
+            target: RenderTarget::new(width, height),
+            sun: None,
+            lights: Vec::new(),
+            background_filled: false,
+            model: Mat4::IDENTITY,
+            view_projection: mvp,
+            mvp,
+            hidden_layers: BTreeSet::new(),
+            sun_visibility: 1.0,
+            fog: None,
+        }
+    }
+
+    /// Sets the cloud that shades the terrain with its shadow
+    pub fn with_shadow_caster(mut self, cloud: Option<&'a Cloud>) -> Self {
+        self.shadow_caster = cloud;
+        self
+    }
+
+    /// Sets the sunlight the terrain is shaded with instead of marching the
+    /// shadow caster
+    pub fn with_shadow_map(mut self, shadow_map: Option<&'a ShadowMap>) -> Self {
+        self.shadow_map = shadow_map;
+        self
+    }
+
+    pub fn target(&self) -> &RenderTarget {
+        &self.target
+    }
+
+    pub fn into_target(self) -> RenderTarget {
+        self.target
+    }
+
+    pub fn with_hidden_layers(mut self, hidden_layers: BTreeSet<&'static str>) -> Self {
+        self.hidden_layers = hidden_layers;
+        self
+    }
+
+    /// Lights of the scene together with the sun, moved to the local space
+    /// of the object being visited
+    fn local_lights(&self) -> Vec<Light> {
+        let inverse = self.model.inverse();
+        self.lights.iter().map(|x| x.transformed(inverse)).collect()
+    }
+
+    /// Ray from the eye through the center of the given pixel in the local
+    /// space of the object being visited
+    fn local_ray(&self, inverse: Mat4, i: usize, j: usize) -> (Vec3, Vec3) {
+        let [width, height] = self.target.size();
+        let origin = inverse.transform_point3(self.camera.pos());
+        let target = inverse.transform_point3(self.camera.egui_to_world(i, j, width, height));
+        (origin, (target - origin).normalize())
+    }
+
+    fn project(&self, pt: Vec3) -> Option<Pos2> {
+        let (sc, z) = self.mvp.world_to_egui(pt);
+        (0.0..=1.0).contains(&z).then(|| sc.to_pos2())
+    }
+
+    fn line(&mut self, a: Vec3, b: Vec3, color: Color32) {
+        if let (Some(a), Some(b)) = (self.project(a), self.project(b)) {
+            self.target.line(a, b, color);
+        }
+    }
+
+    /// Top left corner and size of the pixel rect covered by the projected box
+    fn pixel_rect(&self, bb: &BoundingBox) -> Option<([usize; 2], [usize; 2])> {
+        let [width, height] = self.target.size();
+        let projected = bb.corners().map(|corner| self.project(corner));
+
+        // Only the pixels covered by the projected box are traced, unless part
+        // of it is behind the camera and the projection can't be trusted
+        let (min, max) = if projected.iter().all(Option::is_some) {
+            projected.iter().flatten().fold(
+                (Pos2::new(width as f32, height as f32), Pos2::ZERO),
+                |(min, max), p| (min.min(*p), max.max(*p)),
+            )
+        } else {
+            (Pos2::ZERO, Pos2::new(width as f32, height as f32))
+        };
+        let (min_x, min_y) = (
+            (min.x.max(0.0) as usize).min(width),
+            (min.y.max(0.0) as usize).min(height),
+        );
+        let (max_x, max_y) = (
+            (max.x.ceil().max(0.0) as usize).min(width),
+            (max.y.ceil().max(0.0) as usize).min(height),
+        );
+        (min_x < max_x && min_y < max_y).then_some(([min_x, min_y], [max_x - min_x, max_y - min_y]))
+    }
+
+    fn fill_sky(&mut self, sun: &Sun) {
+        use rayon::prelude::*;
+
+        let [w, h] = self.target.size();
+        self.target
+            .pixels_mut()
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(idx, pixel)| {
+                let i = idx / w;
+                *pixel = sky_color((h - i) as f32 / h as f32, sun);
+            });
+    }
+}
+
+impl<'a> Visitor for OffscreenVisitor<'a> {
+    type Output = ();
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) {
+        if self.sun.is_none() {
+            self.sun = world_sun(scene_objects, self.model);
+        }
+        let parent = self.model;
+        if self.fog.is_none() {
+            self.fog = scene_objects.visible().find_map(|(name, x)| match x {
+                Component::Fog(fog) => {
+                    Some(fog.transformed(parent * scene_objects.world_transform(name)))
+                }
+                _ => None,
+            });
+        }
+        self.lights.extend(scene_objects.lights(parent));
+        if !self.background_filled {
+            let background = scene_objects
+                .visible()
+                .find_map(|(_, x)| match x {
+                    Component::Background(bg) => Some(*bg),
+                    _ => None,
+                })
+                .unwrap_or_default();
+            self.visit_background(&background);
+        }
+
+        let sun_visibility = sun_visibility(scene_objects, parent, self.camera.pos());
+        for (_, model, i) in draw_order(
+            scene_objects,
+            parent,
+            self.camera.pos(),
+            &self.hidden_layers,
+        ) {
+            self.sun_visibility = sun_visibility;
+            self.model = model;
+            self.mvp = self.view_projection.with_model(model);
+            i.accept(self);
+        }
+        self.model = parent;
+        self.mvp = self.view_projection.with_model(parent);
+    }
+
+    fn visit_background(&mut self, background: &Background) {
+        if self.background_filled {
+            return;
+        }
+        self.background_filled = true;
+
+        match *background {
+            Background::Solid(color) => self.target.fill(color),
+            Background::Gradient { top, bottom } => {
+                let [w, h] = self.target.size();
+                for (idx, pixel) in self.target.pixels_mut().iter_mut().enumerate() {
+                    let t = (idx / w) as f32 / (h.max(2) - 1) as f32;
+                    *pixel = top.lerp_to_gamma(bottom, t);
+                }
+            }
+            Background::Sky => {
+                if let Some(sun) = self.sun {
+                    self.fill_sky(&sun);
+                }
+            }
+        }
+        if let Some(fog) = self.fog {
+            let [w, h] = self.target.size();
+            let (eye, _) = self.local_ray(Mat4::IDENTITY, 0, 0);
+            for i in 0..h {
+                let (_, dir) = self.local_ray(Mat4::IDENTITY, i, w / 2);
+                let color = fog
+                    .color
+                    .gamma_multiply(fog.amount(eye, dir, f32::INFINITY));
+                for j in 0..w {
+                    self.target.blend(j, i, color);
+                }
+            }
+        }
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) {
+        use rayon::prelude::*;
+
+        let Some(([min_x, min_y], [w, h])) = self.pixel_rect(&cloud.obb().aabb()) else {
+            return;
+        };
+        let mut img = egui::ColorImage::new([w, h], Color32::TRANSPARENT);
+        let occupancy = OccupancyGrid::new(cloud, OCCUPANCY_GRID_SIZE);
+        let uniforms = cloud
+            .uniforms(&self.local_lights())
+            .with_occupancy(Some(occupancy.into()));
+        let inverse = self.model.inverse();
+        let fog = self.fog.map(|x| x.transformed(inverse));
+        let obb = cloud.obb();
+        img.pixels
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(idx, pixel)| {
+                let i = idx / w + min_y;
+                let j = idx % w + min_x;
+
+                let (ray_origin, ray_dir) = self.local_ray(inverse, i, j);
+                *pixel = cloud.march_with(&uniforms, ray_origin, ray_dir);
+                if let Some(fog) = fog.filter(|_| pixel.a() > 0) {
+                    let distance = obb.dst(ray_origin, ray_dir).x;
+                    *pixel = fog.apply(*pixel, ray_origin, ray_dir, distance);
+                }
+            });
+
+        self.target.draw_image([min_x, min_y], &img);
+    }
+
+    fn visit_skybox(&mut self, skybox: &Skybox) {
+        let img = skybox.render(self.camera, self.target.size());
+        self.target.draw_image([0, 0], &img);
+    }
+
+    fn visit_water(&mut self, water: &Water) {
+        use rayon::prelude::*;
+
+        let Some(sun) = self.sun else {
+            return;
+        };
+        let Some(([min_x, min_y], [w, h])) = self.pixel_rect(&water.bounding_box) else {
+            return;
+        };
+
+        let mut img = egui::ColorImage::new([w, h], Color32::TRANSPARENT);
+        let inverse = self.model.inverse();
+        let sun = sun.transformed(inverse);
+        img.pixels
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(idx, pixel)| {
+                let i = idx / w + min_y;
+                let j = idx % w + min_x;
+
+                let (ray_origin, ray_dir) = self.local_ray(inverse, i, j);
+                *pixel = water.shade(ray_origin, ray_dir, &sun);
+            });
+
+        self.target.draw_image([min_x, min_y], &img);
+    }
+
+    fn visit_grid(&mut self, grid: &Grid) {
+        let scale = grid.scale;
+        let [width, height] = self.target.size();
+        for line in grid.visible_lines(self.camera, width as f32, height as f32) {
+            self.line(line.a, line.b, grid.line_color(&line));
+        }
+
+        self.line(
+            Vec3::ZERO,
+            Vec3::new(0., 1.4, 0.) * scale,
+            Color32::DARK_GREEN,
+        );
+        self.line(Vec3::ZERO, Vec3::new(0., 0., 1.4) * scale, Color32::BLUE);
+        self.line(Vec3::ZERO, Vec3::new(1.4, 0., 0.) * scale, Color32::RED);
+    }
+
+    fn visit_sun(&mut self, sun: &Sun) {
+        let sun_pos = sun.get_pos();
+        let (Some(center), Some(edge)) = (
+            self.project(sun_pos),
+            self.project(sun_pos + Vec3::new(0.1, 0.0, 0.0)),
+        ) else {
+            return;
+        };
+        let disc = (edge - center).length();
+        for (radius, color) in Sun::glow(sun.color, self.sun_visibility) {
+            self.target.circle_filled(center, disc * radius, color);
+        }
+    }
+
+    fn visit_mesh(&mut self, mesh: &Mesh) {
+        let lights = self.local_lights();
+        let mut triangles = mesh
+            .shaded_triangles(&lights)
+            .into_iter()
+            .filter_map(|(corners, color)| {
+                let [a, b, c] = corners.map(|x| self.project(x));
+                let depth = corners.iter().map(|&x| self.mvp.clip_w(x)).sum::<f32>();
+                Some(([a?, b?, c?], depth, color))
+            })
+            .collect::<Vec<_>>();
+        triangles.sort_by(|x, y| y.1.total_cmp(&x.1));
+
+        for (corners, _, color) in triangles {
+            self.target.triangle(corners, color);
+        }
+    }
+
+    fn visit_light(&mut self, light: &Light) {
+        if let LightKind::Point(position) = light.kind {
+            if let Some(center) = self.project(position) {
+                self.target.circle_filled(center, 3.0, light.color);
+            }
+        }
+    }
+
+    fn visit_terrain(&mut self, terrain: &Terrain) {
+        let Some(sun) = self.sun else {
+            return;
+        };
+        let inverse = self.model.inverse();
+        let fog = self.fog.map(|x| x.transformed(inverse));
+        let sun_pos = inverse.transform_point3(sun.get_pos());
+        let model = self.model;
+        let img = rasterize_terrain(
+            terrain,
+            |p| match (self.shadow_map, self.shadow_caster) {
+                (Some(map), _) => map
+                    .transmittance(model.transform_point3(p))
+                    .max(terrain.shadow_threshold),
+                (None, Some(cloud)) => cloud_shadow(cloud, terrain, sun_pos, p),
+                (None, None) => 1.0,
+            },
+            fog.as_ref(),
+            sun_pos,
+            inverse.transform_point3(self.camera.pos()),
+            self.target.size(),
+            |v| self.project(v),
+        );
+        self.target.draw_image([0, 0], &img);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::scene::Scene;
+    use crate::scene::ObjectTransform;
+
+    #[test]
+    fn test_offscreen_grid() {
+        let mut scene = Scene::default();
+        scene.add_object("grid", Grid::new(10, 1.0));
+        let camera = Camera::default();
+
+        let mut visitor = OffscreenVisitor::new(&camera, 320, 200);
+        scene.accept(&mut visitor);
+        let target = visitor.into_target();
+
+        assert_eq!(target.size(), [320, 200]);
+        assert!(target.image().pixels.contains(&Color32::BLACK));
+    }
+
+    #[test]
+    fn test_offscreen_sun_transform() {
+        let mut scene = Scene::default();
+        scene.add_object("sun", Sun::new(10.0, 0.0, 0.0));
+        *scene.objects.local_transform_mut("sun").unwrap() =
+            ObjectTransform::from_translation(Vec3::new(0.0, 10.0, 0.0));
+        let camera = Camera::default();
+
+        let mut visitor = OffscreenVisitor::new(&camera, 32, 20);
+        scene.accept(&mut visitor);
+        let sun = visitor.sun.unwrap().get_pos();
+        assert!(sun.abs_diff_eq(Vec3::new(-10.0, 10.0, 0.0), 1e-4));
+    }
+}
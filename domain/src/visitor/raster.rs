@@ -0,0 +1,558 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use egui::{Color32, ColorImage, Pos2, Rect};
+use glam::{Mat4, Vec3, Vec4, Vec4Swizzles};
+
+use crate::managers::profiling_manager::{Profiler, Stage};
+use crate::object::camera::{Camera, ViewRays};
+use crate::object::objects::cloud::beer;
+use crate::object::objects::{Cloud, DensityField, Fog, Light, Obb, OccupancyGrid, Sun, Terrain};
+
+/// Returns the sky gradient color at the given vertical screen position
+/// (0.0 at the bottom, 1.0 at the top)
+pub fn sky_color(height: f32, sun: &Sun) -> Color32 {
+    let atmosphere = (1.0_f32 - height).sqrt();
+    let sky_col = Vec3::new(0.2, 0.4, 0.8);
+
+    let scatter = sun.get_pos().y / sun.d;
+    let scatter = scatter.powf(1.0 / 10.0);
+    let scatter = 1.0 - scatter.clamp(0.2, 0.9);
+
+    let col = Vec3::splat(1.0).lerp(Vec3::new(0.8, 0.3, 0.0) * 1.1, scatter);
+    let col = sky_col.lerp(col, atmosphere / 1.1);
+    let (r, g, b) = col.into();
+    Color32::from_rgba_unmultiplied((r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8, 255)
+}
+
+/// Rasterizes the terrain mesh into an image of the given size.
+///
+/// `project` maps world positions to pixel coordinates of the image, and
+/// `shadow` gives the share of the sunlight reaching a point of the ground,
+/// e.g. from a [`ShadowMap`] or [`cloud_shadow`]. Distant ground fades into
+/// the fog.
+///
+/// [`ShadowMap`]: crate::visitor::shadow_visitor::ShadowMap
+pub fn rasterize_terrain(
+    terrain: &Terrain,
+    shadow: impl Fn(Vec3) -> f32 + Sync,
+    fog: Option<&Fog>,
+    sun_pos: Vec3,
+    eye: Vec3,
+    [w, h]: [usize; 2],
+    project: impl Fn(Vec3) -> Option<Pos2> + Sync,
+) -> egui::ColorImage {
+    use rayon::prelude::*;
+
+    let bb = terrain.bounding_box;
+    let img = Arc::new(Mutex::new(egui::ColorImage::new(
+        [w, h],
+        Color32::TRANSPARENT,
+    )));
+    let z_buffer = Arc::new(Mutex::new(HashMap::new()));
+
+    terrain.triangles.par_iter().for_each(|(v, (n0, n1, n2))| {
+        let img = img.clone();
+        let z_buffer = z_buffer.clone();
+        let get_shadow_factor = &shadow;
+
+        let (v0, v1, v2) = v.to_tuple();
+        let (p0, p1, p2) = (v0, v1, v2);
+
+        let v0 = project(v0);
+        let v1 = project(v1);
+        let v2 = project(v2);
+        if let (Some(v0), Some(v1), Some(v2)) = (v0, v1, v2) {
+            let min_x = v0.x.min(v1.x).min(v2.x) as usize;
+            let max_x = v0.x.max(v1.x).max(v2.x) as usize;
+            let min_y = v0.y.min(v1.y).min(v2.y) as usize;
+            let max_y = v0.y.max(v1.y).max(v2.y) as usize;
+
+            for y in min_y..=max_y {
+                for x in min_x..=max_x {
+                    if inside_triangle(Pos2::new(x as f32, y as f32), v0, v1, v2) && x < w && y < h
+                    {
+                        let a1 = Vec3::new(p1.x - p0.x, p1.y - p0.y, p1.z - p0.z);
+                        let a2 = Vec3::new(p2.x - p0.x, p2.y - p0.y, p2.z - p0.z);
+                        let normal = a1.cross(a2);
+                        let d = -(normal.x * p0.x + normal.y * p0.y + normal.z * p0.z);
+                        let (a, b, c, d) = (normal.x, normal.y, normal.z, d);
+
+                        let new_u = p1.x + (p0.x - p1.x) * ((x as f32 - v1.x) / (v0.x - v1.x));
+                        let new_v = p1.y + (p0.y - p1.y) * ((y as f32 - v1.y) / (v0.y - v1.y));
+                        let z_pixel = if c != 0.0 {
+                            -(a * new_u + b * new_v + d) / c
+                        } else {
+                            0.0
+                        };
+
+                        let probe = Vec3::new(new_u, new_v, z_pixel);
+
+                        let depth = eye.distance_squared(probe);
+
+                        let x1 = get_shadow_factor(p0);
+                        let x2 = get_shadow_factor(p1);
+                        let x3 = get_shadow_factor(p2);
+
+                        let alpha1 = ((sun_pos - p0).normalize()).dot(n0.normalize());
+                        let alpha2 = ((sun_pos - p1).normalize()).dot(n1.normalize());
+                        let alpha3 = ((sun_pos - p2).normalize()).dot(n2.normalize());
+
+                        let beta = interpolate(
+                            Pos2::new(x as f32, y as f32),
+                            v0,
+                            v1,
+                            v2,
+                            x1 * alpha1,
+                            x2 * alpha2,
+                            x3 * alpha3,
+                        );
+
+                        let dif = 0.55 * beta;
+                        let bottom = color32_to_vec4(terrain.bottom_color).xyz();
+                        let top = color32_to_vec4(terrain.top_color).xyz();
+                        let p0_col = bottom.lerp(top, (p0.y - bb.min.y).abs() / bb.size().y);
+                        let p1_col = bottom.lerp(top, (p1.y - bb.min.y).abs() / bb.size().y);
+                        let p2_col = bottom.lerp(top, (p2.y - bb.min.y).abs() / bb.size().y);
+
+                        let col = interpolate(
+                            Pos2::new(x as f32, y as f32),
+                            v0,
+                            v1,
+                            v2,
+                            p0_col,
+                            p1_col,
+                            p2_col,
+                        );
+                        let col = col * dif;
+                        let (r, g, b) = (col.x * 255.0, col.y * 255.0, col.z * 255.0);
+                        let mut color = Color32::from_rgb(r as u8, g as u8, b as u8);
+                        if let Some(fog) = fog {
+                            let pos = Pos2::new(x as f32, y as f32);
+                            let point = interpolate(pos, v0, v1, v2, p0, p1, p2);
+                            let distance = point.distance(eye);
+                            let dir = (point - eye) / distance.max(f32::EPSILON);
+                            color = fog.apply(color, eye, dir, distance);
+                        }
+                        let mut z_buffer = z_buffer.lock().unwrap();
+                        if let Some(existing_depth) = z_buffer.get(&(x, y)) {
+                            if depth < *existing_depth {
+                                img.lock().unwrap()[(x, y)] = color;
+                                z_buffer.insert((x, y), depth);
+                            }
+                        } else {
+                            img.lock().unwrap()[(x, y)] = color;
+                            z_buffer.insert((x, y), depth);
+                        }
+                    }
+                }
+            }
+        }
+    });
+
+    Arc::try_unwrap(img)
+        .expect("one strong reference")
+        .into_inner()
+        .expect("No one holding the mutex")
+}
+
+/// Share of the sunlight reaching the ground point through the cloud,
+/// marched towards the sun with the terrain shadow settings
+pub fn cloud_shadow(cloud: &Cloud, terrain: &Terrain, sun_pos: Vec3, probe: Vec3) -> f32 {
+    let sun_dir = (sun_pos - probe).normalize();
+    let (probe, sun_dir) = if cloud.is_rotated() {
+        let matrix = cloud.volume_matrix();
+        (
+            matrix.transform_point3(probe),
+            matrix.transform_vector3(sun_dir),
+        )
+    } else {
+        (probe, sun_dir)
+    };
+    let cloud_bb = cloud.bounding_box().dst(probe, sun_dir);
+    let (dir_to_box, dst_inside_box) = cloud_bb.into();
+    if dst_inside_box != 0.0 {
+        let mut p = probe;
+        let num_steps = terrain.num_shadows_steps;
+        let step_size = dst_inside_box / num_steps as f32;
+        p += dir_to_box * sun_dir;
+
+        let mut total_density = 0.0;
+
+        for _ in 0..num_steps {
+            let density = cloud.sample_density(p);
+            total_density += density.max(0.0) * step_size;
+            p += sun_dir * step_size;
+        }
+        beer(total_density / terrain.density_scale).clamp(terrain.shadow_threshold, 1.0)
+    } else {
+        1.0
+    }
+}
+
+/// Side of the square tiles the cloud images are marched in, in pixels
+const CLOUD_TILE: usize = 16;
+
+/// Direction of the view ray through the pixel of the viewport in the local
+/// space of the object with the `inverse` world matrix, taken from the
+/// precomputed rays when there are any
+pub fn view_ray_dir(
+    camera: &Camera,
+    rays: Option<&ViewRays>,
+    [width, height]: [usize; 2],
+    inverse: Mat4,
+    [i, j]: [usize; 2],
+) -> Vec3 {
+    match rays {
+        Some(rays) => inverse.transform_vector3(rays.dir(i, j)).normalize(),
+        None => {
+            let origin = inverse.transform_point3(camera.pos());
+            let target = inverse.transform_point3(camera.egui_to_world(i, j, width, height));
+            (target - origin).normalize()
+        }
+    }
+}
+
+/// Raymarch of a cloud into an image stretched over its screen rect. It
+/// owns everything it reads, so it can run on another thread.
+#[derive(Clone)]
+pub struct CloudMarch {
+    pub cloud: Cloud,
+    pub camera: Camera,
+    /// Size of the canvas the screen rect is on
+    pub viewport: [usize; 2],
+    /// Inverse world matrix of the cloud, the march runs in its local space
+    pub inverse: Mat4,
+    /// Lights in the local space of the cloud
+    pub lights: Vec<Light>,
+    /// Fog in the local space of the cloud
+    pub fog: Option<Fog>,
+    pub rect: Rect,
+    /// Projected volume, tiles outside of it are skipped. Every tile is
+    /// marched without it.
+    pub hull: Option<Vec<Pos2>>,
+    pub rays: Option<Arc<ViewRays>>,
+    /// Empty bricks of the cloud the rays step over
+    pub occupancy: Option<Arc<OccupancyGrid>>,
+    /// Coarse density read instead of the noise
+    pub density: Option<Arc<DensityField>>,
+    /// Marches on the GPU when there is one, with the `gpu` feature
+    pub gpu: bool,
+    /// Earlier image of the same view with other lights. The tiles whose
+    /// rays miss every occupied brick keep its pixels, the lights change
+    /// nothing there.
+    pub base: Option<Arc<ColorImage>>,
+    /// Timings the march adds to
+    pub profiler: Option<Arc<Profiler>>,
+}
+
+impl CloudMarch {
+    /// Direction of the view ray through the pixel of an image of the given
+    /// size, in the local space of the cloud
+    pub fn pixel_dir(&self, [w, h]: [usize; 2], x: usize, y: usize) -> Vec3 {
+        let [max_j, max_i] = self.viewport.map(|x| x.saturating_sub(1));
+        // Screen pixels per image pixel
+        let scale = self.rect.size() / egui::vec2(w as f32, h as f32);
+        let offset = egui::vec2(x as f32 + 0.5, y as f32 + 0.5);
+        let screen = self.rect.min + offset * scale;
+        let pixel = [
+            (screen.y as usize).min(max_i),
+            (screen.x as usize).min(max_j),
+        ];
+        view_ray_dir(
+            &self.camera,
+            self.rays.as_deref(),
+            self.viewport,
+            self.inverse,
+            pixel,
+        )
+    }
+
+    /// Volume of the occupied bricks, `None` when the cloud is empty
+    fn occupied_obb(&self, occupancy: &OccupancyGrid) -> Option<Obb> {
+        let obb = self.cloud.obb();
+        let occupied = occupancy.occupied_bounds()?;
+        let occupied_center = occupied.center() - occupancy.shift(&self.cloud);
+        // The bricks are turned around the center of the whole volume
+        let center = obb.center + obb.rotation * (occupied_center - obb.center);
+        Some(Obb::new(center, 0.5 * occupied.size(), obb.rotation))
+    }
+
+    /// Marches the image. It is split into square tiles marched as separate
+    /// tasks, tiles outside the projected volume stay transparent. With
+    /// [`Self::gpu`] and the `gpu` feature the image is marched by the
+    /// compute shader instead, unless the device fails.
+    pub fn run(&self, img: &mut ColorImage) {
+        use rayon::prelude::*;
+
+        let [w, h] = img.size;
+        if w == 0 || h == 0 {
+            return;
+        }
+        #[cfg(feature = "gpu")]
+        if self.gpu {
+            let _timer = self.profiler.as_ref().map(|x| x.scope(Stage::CloudMarch));
+            let gpu = crate::visitor::gpu_march::GpuMarch::shared();
+            if gpu.is_some_and(|x| x.run(self, img)) {
+                return;
+            }
+        }
+        // Screen pixels per image pixel
+        let scale = self.rect.size() / egui::vec2(w as f32, h as f32);
+        let (cloud, inverse) = (&self.cloud, self.inverse);
+        let obb = cloud.obb();
+        let ray_origin = inverse.transform_point3(self.camera.pos());
+        let uniforms = cloud
+            .uniforms(&self.lights)
+            .with_occupancy(self.occupancy.clone())
+            .with_density_field(self.density.clone());
+        let _timer = self.profiler.as_ref().map(|x| x.scope(Stage::CloudMarch));
+        // Only the tiles over the occupied bricks are marched again
+        let changed = match (&self.base, &self.occupancy) {
+            (Some(base), Some(occupancy)) if base.size == img.size => {
+                img.pixels.copy_from_slice(&base.pixels);
+                Some(self.occupied_obb(occupancy))
+            }
+            _ => None,
+        };
+
+        let mut tiles = Vec::new();
+        for (ty, band) in img.pixels.chunks_mut(w * CLOUD_TILE).enumerate() {
+            let mut rows: Vec<Vec<&mut [Color32]>> = (0..w.div_ceil(CLOUD_TILE))
+                .map(|_| Vec::with_capacity(CLOUD_TILE))
+                .collect();
+            for row in band.chunks_mut(w) {
+                for (tx, part) in row.chunks_mut(CLOUD_TILE).enumerate() {
+                    rows[tx].push(part);
+                }
+            }
+            tiles.extend(
+                rows.into_iter()
+                    .enumerate()
+                    .map(|(tx, rows)| (tx * CLOUD_TILE, ty * CLOUD_TILE, rows)),
+            );
+        }
+
+        let dir = |x, y| self.pixel_dir([w, h], x, y);
+
+        tiles.into_par_iter().for_each(|(x0, y0, rows)| {
+            let (x1, y1) = (x0 + rows[0].len() - 1, y0 + rows.len() - 1);
+            let tile = Rect::from_min_size(
+                self.rect.min + egui::vec2(x0 as f32, y0 as f32) * scale,
+                egui::vec2(rows[0].len() as f32, rows.len() as f32) * scale,
+            );
+            if self
+                .hull
+                .as_ref()
+                .is_some_and(|x| !rect_overlaps_hull(tile, x))
+            {
+                return;
+            }
+            let corners = [(x0, y0), (x1, y0), (x1, y1), (x0, y1)].map(|(x, y)| dir(x, y));
+            if tile_misses_box(ray_origin, corners, &obb) {
+                return;
+            }
+            if changed.as_ref().is_some_and(|x| {
+                x.as_ref()
+                    .is_none_or(|x| tile_misses_box(ray_origin, corners, x))
+            }) {
+                return;
+            }
+            for (dy, row) in rows.into_iter().enumerate() {
+                for (dx, pixel) in row.iter_mut().enumerate() {
+                    let ray_dir = dir(x0 + dx, y0 + dy);
+                    *pixel = cloud.march_with(&uniforms, ray_origin, ray_dir);
+                    if let Some(fog) = self.fog.filter(|_| pixel.a() > 0) {
+                        let distance = obb.dst(ray_origin, ray_dir).x;
+                        *pixel = fog.apply(*pixel, ray_origin, ray_dir, distance);
+                    }
+                }
+            }
+        });
+    }
+}
+
+pub fn interpolate<T>(pos: Pos2, v0: Pos2, v1: Pos2, v2: Pos2, n0: T, n1: T, n2: T) -> T
+where
+    T: std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+{
+    let area_total = (v1.x - v0.x) * (v2.y - v0.y) - (v2.x - v0.x) * (v1.y - v0.y);
+    let alpha = ((v1.x - pos.x) * (v2.y - pos.y) - (v2.x - pos.x) * (v1.y - pos.y)) / area_total;
+    let beta = ((v2.x - pos.x) * (v0.y - pos.y) - (v0.x - pos.x) * (v2.y - pos.y)) / area_total;
+    let gamma = 1.0 - alpha - beta;
+
+    n0 * alpha + n1 * beta + n2 * gamma
+}
+
+#[inline]
+fn sign(p1: Pos2, p2: Pos2, p3: Pos2) -> f32 {
+    (p1.x - p3.x) * (p2.y - p3.y) - (p2.x - p3.x) * (p1.y - p3.y)
+}
+
+pub fn inside_triangle(p: Pos2, v1: Pos2, v2: Pos2, v3: Pos2) -> bool {
+    let d1 = sign(p, v1, v2);
+    let d2 = sign(p, v2, v3);
+    let d3 = sign(p, v3, v1);
+
+    let has_neg = (d1 < 0.) || (d2 < 0.) || (d3 < 0.);
+    let has_pos = (d1 > 0.) || (d2 > 0.) || (d3 > 0.);
+
+    !(has_neg && has_pos)
+}
+
+/// Convex hull of the points, its vertices in order around it
+pub fn convex_hull(points: &[Pos2]) -> Vec<Pos2> {
+    let mut points = points.to_vec();
+    points.sort_by(|a, b| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+    if points.len() < 3 {
+        return points;
+    }
+    let cross = |o: Pos2, a: Pos2, b: Pos2| (a - o).x * (b - o).y - (a - o).y * (b - o).x;
+    let mut hull: Vec<Pos2> = Vec::with_capacity(points.len() * 2);
+    for pass in [
+        &points[..],
+        &points.iter().rev().copied().collect::<Vec<_>>()[..],
+    ] {
+        let start = hull.len();
+        for &p in pass {
+            while hull.len() >= start + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        hull.pop();
+    }
+    hull
+}
+
+/// Whether none of the rays from the origin between the corner rays of a
+/// tile, given in order around it, can reach the box. The tile is kept when
+/// a corner ray hits the box, and dropped when the whole box lies behind one
+/// side of the pyramid spanned by the corner rays.
+pub fn tile_misses_box(origin: Vec3, corners: [Vec3; 4], obb: &Obb) -> bool {
+    if corners.iter().any(|&dir| obb.dst(origin, dir).y > 0.0) {
+        return false;
+    }
+    let center = corners.iter().sum::<Vec3>();
+    let points = obb.corners().map(|x| x - origin);
+    if points.iter().all(|x| x.dot(center) <= 0.0) {
+        return true;
+    }
+    (0..4).any(|k| {
+        let normal = corners[k].cross(corners[(k + 1) % 4]);
+        let inside = normal.dot(center).signum();
+        inside != 0.0 && points.iter().all(|x| x.dot(normal) * inside < 0.0)
+    })
+}
+
+/// Whether the rectangle and the convex polygon overlap, tested on the
+/// axes of the rectangle and of the polygon edges
+pub fn rect_overlaps_hull(rect: Rect, hull: &[Pos2]) -> bool {
+    if hull.is_empty() {
+        return false;
+    }
+    let corners = [
+        rect.left_top(),
+        rect.right_top(),
+        rect.right_bottom(),
+        rect.left_bottom(),
+    ];
+    let edges = hull
+        .iter()
+        .zip(hull.iter().cycle().skip(1))
+        .map(|(&a, &b)| (b - a).rot90());
+    [egui::Vec2::X, egui::Vec2::Y]
+        .into_iter()
+        .chain(edges)
+        .all(|axis| {
+            let range = |points: &mut dyn Iterator<Item = Pos2>| {
+                points.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), p| {
+                    let d = p.to_vec2().dot(axis);
+                    (min.min(d), max.max(d))
+                })
+            };
+            let (a_min, a_max) = range(&mut corners.iter().copied());
+            let (b_min, b_max) = range(&mut hull.iter().copied());
+            a_min <= b_max && b_min <= a_max
+        })
+}
+
+#[inline]
+pub fn color32_to_vec4(color32: Color32) -> Vec4 {
+    color32.to_array().map(|x| x as f32 / 255.0).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rect_overlaps_hull() {
+        let points = [(0.0, 0.0), (10.0, 0.0), (0.0, 10.0), (3.0, 3.0)].map(Pos2::from);
+        let hull = convex_hull(&points);
+        assert_eq!(hull.len(), 3);
+
+        let rect = |x: f32, y: f32| egui::Rect::from_min_size(Pos2::new(x, y), (2.0, 2.0).into());
+        assert!(rect_overlaps_hull(rect(1.0, 1.0), &hull));
+        assert!(rect_overlaps_hull(rect(-1.0, -1.0), &hull));
+        // Inside the bounding box of the triangle, beyond its diagonal
+        assert!(!rect_overlaps_hull(rect(7.0, 7.0), &hull));
+    }
+
+    #[test]
+    fn test_partial_redraw() {
+        let mut cloud = crate::object::objects::cloud::tests::dense_cloud();
+        cloud.density_offset = -10.0;
+        let march = |lights, base| CloudMarch {
+            occupancy: Some(Arc::new(OccupancyGrid::new(&cloud, 8))),
+            cloud: cloud.clone(),
+            camera: Camera::default(),
+            viewport: [64, 64],
+            inverse: Mat4::IDENTITY,
+            lights,
+            fog: None,
+            rect: Rect::from_min_size(Pos2::ZERO, egui::vec2(64.0, 64.0)),
+            hull: None,
+            rays: None,
+            density: None,
+            gpu: false,
+            base,
+            profiler: None,
+        };
+        let image = |march: CloudMarch| {
+            let mut img = ColorImage::new([64, 64], Color32::TRANSPARENT);
+            march.run(&mut img);
+            img
+        };
+        let base = image(march(vec![Light::directional(Vec3::NEG_Y)], None));
+        let lights = vec![Light::directional(Vec3::new(1.0, -1.0, 0.0))];
+        let full = image(march(lights.clone(), None));
+        assert!(full.pixels.iter().any(|x| x.a() > 0));
+        assert_ne!(full.pixels, base.pixels);
+        let partial = image(march(lights, Some(Arc::new(base))));
+        assert!(full.pixels == partial.pixels);
+    }
+
+    #[test]
+    fn test_tile_misses_box() {
+        let obb = Obb::from(crate::object::objects::BoundingBox::from_two_pos(
+            Vec3::splat(-1.0),
+            Vec3::splat(1.0),
+        ));
+        let origin = Vec3::new(0.0, 0.0, 10.0);
+        let tile = |x: f32, y: f32, size: f32| {
+            [(0.0, 0.0), (size, 0.0), (size, size), (0.0, size)]
+                .map(|(dx, dy)| Vec3::new(x + dx, y + dy, -10.0).normalize())
+        };
+        // A corner ray through the box
+        assert!(!tile_misses_box(origin, tile(0.0, 0.0, 1.0), &obb));
+        // The box between the corner rays of a wide tile
+        assert!(!tile_misses_box(origin, tile(-5.0, -5.0, 10.0), &obb));
+        assert!(tile_misses_box(origin, tile(3.0, 3.0, 1.0), &obb));
+        // Looking away from the box
+        let away = tile(-5.0, -5.0, 10.0).map(|x| -x);
+        assert!(tile_misses_box(origin, away, &obb));
+    }
+}
@@ -0,0 +1,149 @@
+//! Description of the scene for saving, see [`crate::io::scene`]
+
+use crate::io::scene::{ComponentEntry, ObjectEntry, SceneFile};
+use crate::object::camera::Camera;
+use crate::object::objects::{
+    Background, Cloud, Fog, Grid, Light, Mesh, OrientationGizmo, Skybox, Sun, Terrain,
+    TransformGizmo, Water,
+};
+use crate::object::plugin::PluginComponent;
+use crate::object::Component;
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::{Visitable, Visitor};
+
+/// Gives the serializable entries of the visited objects, composites with
+/// the place of their objects in the hierarchy. Plugin components have no
+/// entry, their objects are left out and listed in [`Self::skipped`].
+#[derive(Default)]
+pub struct SerializeVisitor {
+    skipped: Vec<&'static str>,
+}
+
+impl SerializeVisitor {
+    /// Names of the objects left out of the files, nested ones included
+    pub fn skipped(&self) -> &[&'static str] {
+        &self.skipped
+    }
+
+    pub fn scene_file(&mut self, scene_objects: &SceneObjects) -> SceneFile {
+        let mut file = SceneFile::default();
+        for (&name, component) in scene_objects.iter() {
+            let component = match component {
+                Component::Composite(objects) => self.visit_composite(objects),
+                component => component.accept(self),
+            };
+            let Some(component) = component else {
+                self.skipped.push(name);
+                continue;
+            };
+            let node = scene_objects.node(name).copied().unwrap_or_default();
+            file.objects.push(ObjectEntry {
+                name: name.to_owned(),
+                parent: node.parent.map(str::to_owned),
+                transform: node.local,
+                visible: node.visible,
+                layer: node.layer.to_owned(),
+                component,
+            });
+        }
+        file
+    }
+}
+
+impl Visitor for SerializeVisitor {
+    type Output = Option<ComponentEntry>;
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Composite(self.scene_file(scene_objects)))
+    }
+
+    fn visit_camera(&mut self, camera: &Camera) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Camera(*camera))
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Cloud(Box::new(cloud.cloud_params)))
+    }
+
+    fn visit_grid(&mut self, grid: &Grid) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Grid(grid.clone()))
+    }
+
+    fn visit_sun(&mut self, sun: &Sun) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Sun(*sun))
+    }
+
+    fn visit_terrain(&mut self, terrain: &Terrain) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Terrain {
+            builder: terrain.terrain_builder,
+            height_map: terrain.height_map().cloned(),
+        })
+    }
+
+    fn visit_gizmo(&mut self, gizmo: &OrientationGizmo) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Gizmo(gizmo.clone()))
+    }
+
+    fn visit_background(&mut self, background: &Background) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Background(*background))
+    }
+
+    fn visit_water(&mut self, water: &Water) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Water(water.clone()))
+    }
+
+    fn visit_skybox(&mut self, skybox: &Skybox) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Skybox(skybox.clone()))
+    }
+
+    fn visit_mesh(&mut self, mesh: &Mesh) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Mesh(mesh.clone()))
+    }
+
+    fn visit_light(&mut self, light: &Light) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Light(*light))
+    }
+
+    fn visit_transform_gizmo(&mut self, gizmo: &TransformGizmo) -> Option<ComponentEntry> {
+        Some(ComponentEntry::TransformGizmo(gizmo.clone()))
+    }
+
+    fn visit_fog(&mut self, fog: &Fog) -> Option<ComponentEntry> {
+        Some(ComponentEntry::Fog(*fog))
+    }
+
+    fn visit_plugin(&mut self, _plugin: &dyn PluginComponent) -> Option<ComponentEntry> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::Vec3;
+
+    #[test]
+    fn test_serialize_composite() {
+        let mut scene = SceneObjects::default();
+        scene.add_object("camera", Camera::default());
+        scene.add_object(
+            "group",
+            Component::composite_from([("lamp", Light::point(Vec3::Y))]),
+        );
+
+        let file = SerializeVisitor::default().scene_file(&scene);
+        assert_eq!(file.objects.len(), 2);
+        assert_eq!(
+            file.objects[0].component,
+            ComponentEntry::Camera(Camera::default())
+        );
+        let ComponentEntry::Composite(group) = &file.objects[1].component else {
+            panic!("the group is not a composite");
+        };
+        assert_eq!(group.objects[0].name, "lamp");
+        assert_eq!(
+            group.objects[0].component,
+            ComponentEntry::Light(Light::point(Vec3::Y))
+        );
+    }
+}
@@ -0,0 +1,220 @@
+//! Shadow pass rendering the scene from the sun
+
+use glam::{Mat4, Vec3};
+use rayon::prelude::*;
+
+use crate::object::objects::{BoundingBox, Cloud, Mesh};
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::raster::inside_triangle;
+use crate::visitor::{visit_visible, Visitor, WorldVisitor};
+
+/// Depth offset keeping the occluders from shadowing themselves
+const DEPTH_BIAS: f32 = 1e-3;
+
+/// Sunlight reaching the scene, seen from the sun with an orthographic
+/// projection over the scene bounds
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowMap {
+    /// World to light space, x and y in -1..1 across the map and z from 0
+    /// at the sun side of the bounds
+    light: Mat4,
+    size: usize,
+    /// Share of the sunlight passing all the occluders of the texel
+    transmittance: Vec<f32>,
+    /// Light space depth of the nearest occluder of the texel
+    depth: Vec<f32>,
+}
+
+impl ShadowMap {
+    /// Fully lit map of `size` by `size` texels covering the bounds
+    pub fn new(sun_dir: Vec3, bounds: &BoundingBox, size: usize) -> Self {
+        let radius = (bounds.size().length() / 2.0).max(f32::EPSILON);
+        let center = bounds.center();
+        let sun_dir = sun_dir.try_normalize().unwrap_or(Vec3::Y);
+        let up = if sun_dir.y.abs() > 0.99 {
+            Vec3::Z
+        } else {
+            Vec3::Y
+        };
+        let view = Mat4::look_at_rh(center + sun_dir * 2.0 * radius, center, up);
+        let projection = Mat4::orthographic_rh(-radius, radius, -radius, radius, 0.0, 4.0 * radius);
+        let size = size.max(1);
+        Self {
+            light: projection * view,
+            size,
+            transmittance: vec![1.0; size * size],
+            depth: vec![f32::INFINITY; size * size],
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Texel index and light space depth of the world position
+    fn texel(&self, world: Vec3) -> Option<(usize, f32)> {
+        let p = self.light.project_point3(world);
+        let (x, y) = self.to_map(p.x, p.y);
+        if !(0.0..self.size as f32).contains(&x) || !(0.0..self.size as f32).contains(&y) {
+            return None;
+        }
+        Some((y as usize * self.size + x as usize, p.z))
+    }
+
+    fn to_map(&self, x: f32, y: f32) -> (f32, f32) {
+        let size = self.size as f32;
+        ((x + 1.0) / 2.0 * size, (1.0 - y) / 2.0 * size)
+    }
+
+    /// World space ray entering the bounds from the sun side through the
+    /// center of the texel
+    fn ray(&self, index: usize) -> (Vec3, Vec3) {
+        let size = self.size as f32;
+        let x = (index % self.size) as f32 + 0.5;
+        let y = (index / self.size) as f32 + 0.5;
+        let (x, y) = (x / size * 2.0 - 1.0, 1.0 - y / size * 2.0);
+        let inverse = self.light.inverse();
+        let origin = inverse.project_point3(Vec3::new(x, y, 0.0));
+        let end = inverse.project_point3(Vec3::new(x, y, 1.0));
+        (origin, (end - origin).normalize())
+    }
+
+    /// Share of the sunlight reaching the world position, 1.0 outside the
+    /// map and in front of the occluders
+    pub fn transmittance(&self, world: Vec3) -> f32 {
+        match self.texel(world) {
+            Some((i, depth)) if depth > self.depth[i] + DEPTH_BIAS => self.transmittance[i],
+            _ => 1.0,
+        }
+    }
+
+    /// Covers the texel with an occluder letting `transmittance` of the
+    /// light through
+    fn occlude(&mut self, index: usize, depth: f32, transmittance: f32) {
+        self.transmittance[index] *= transmittance;
+        self.depth[index] = self.depth[index].min(depth);
+    }
+}
+
+/// Renders the occluders into a [`ShadowMap`]: clouds let a part of the
+/// sunlight through, meshes block it. Components without a visit of their
+/// own cast no shadow.
+pub struct ShadowVisitor {
+    map: ShadowMap,
+    /// World matrix of the object being visited
+    model: Mat4,
+}
+
+impl ShadowVisitor {
+    /// `sun_dir` points from the scene towards the sun
+    pub fn new(sun_dir: Vec3, bounds: &BoundingBox, size: usize) -> Self {
+        Self {
+            map: ShadowMap::new(sun_dir, bounds, size),
+            model: Mat4::IDENTITY,
+        }
+    }
+
+    pub fn into_map(self) -> ShadowMap {
+        self.map
+    }
+
+    /// Light space depth of the side of the box facing the sun
+    fn near_depth(&self, local: &BoundingBox) -> f32 {
+        let world = local.transformed(self.model);
+        let (min, max) = (world.min, world.max);
+        (0..8)
+            .map(|i| {
+                let corner = Vec3::new(
+                    if i & 1 == 0 { min.x } else { max.x },
+                    if i & 2 == 0 { min.y } else { max.y },
+                    if i & 4 == 0 { min.z } else { max.z },
+                );
+                self.map.light.project_point3(corner).z
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+impl WorldVisitor for ShadowVisitor {
+    fn model_mut(&mut self) -> &mut Mat4 {
+        &mut self.model
+    }
+}
+
+impl Visitor for ShadowVisitor {
+    type Output = ();
+
+    fn join(&mut self, _output: &mut Self::Output, _next: Self::Output) {}
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) -> Self::Output {
+        visit_visible(self, scene_objects)
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) {
+        let inverse = self.model.inverse();
+        let depth = self.near_depth(&cloud.obb().aabb());
+        let transmittance = (0..self.map.transmittance.len())
+            .into_par_iter()
+            .map(|i| {
+                let (origin, dir) = self.map.ray(i);
+                let dir = inverse.transform_vector3(dir).normalize();
+                cloud.transmittance(inverse.transform_point3(origin), dir)
+            })
+            .collect::<Vec<_>>();
+        for (i, t) in transmittance.into_iter().enumerate() {
+            if t < 1.0 {
+                self.map.occlude(i, depth, t);
+            }
+        }
+    }
+
+    fn visit_mesh(&mut self, mesh: &Mesh) {
+        let size = self.map.size as f32;
+        for &face in &mesh.faces {
+            let [a, b, c] = mesh.triangle(face).map(|v| {
+                self.map
+                    .light
+                    .project_point3(self.model.transform_point3(v))
+            });
+            let depth = a.z.min(b.z).min(c.z);
+            let [a, b, c] = [a, b, c].map(|p| {
+                let (x, y) = self.map.to_map(p.x, p.y);
+                egui::Pos2::new(x, y)
+            });
+            // Faces seen edge-on cover no texels
+            if ((b - a).x * (c - a).y - (c - a).x * (b - a).y).abs() < f32::EPSILON {
+                continue;
+            }
+            let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as usize;
+            let max_x = a.x.max(b.x).max(c.x).ceil().min(size) as usize;
+            let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as usize;
+            let max_y = a.y.max(b.y).max(c.y).ceil().min(size) as usize;
+            for y in min_y..max_y {
+                for x in min_x..max_x {
+                    let p = egui::Pos2::new(x as f32 + 0.5, y as f32 + 0.5);
+                    if inside_triangle(p, a, b, c) {
+                        self.map.occlude(y * self.map.size + x, depth, 0.0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mesh_shadow() {
+        let bounds = BoundingBox::from_two_pos(Vec3::splat(-10.0), Vec3::splat(10.0));
+        let mut visitor = ShadowVisitor::new(Vec3::Y, &bounds, 64);
+        let roof = Mesh::cuboid(Vec3::new(4.0, 0.5, 4.0)).with_offset(Vec3::new(0.0, 5.0, 0.0));
+        visitor.visit_mesh(&roof);
+        let map = visitor.into_map();
+
+        assert_eq!(map.transmittance(Vec3::ZERO), 0.0);
+        assert_eq!(map.transmittance(Vec3::new(0.0, 8.0, 0.0)), 1.0);
+        assert_eq!(map.transmittance(Vec3::new(8.0, 0.0, 0.0)), 1.0);
+    }
+}
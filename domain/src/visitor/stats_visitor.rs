@@ -0,0 +1,220 @@
+//! Size and cost of the scene objects for the statistics panel
+
+use std::mem::size_of;
+
+use glam::{Mat4, Vec2, Vec3, Vec4};
+
+use crate::object::camera::Camera;
+use crate::object::objects::texture3d::NoiseBuilder;
+use crate::object::objects::{BoundingBox, Cloud, Grid, Light, Mesh, Skybox, Sun, Terrain, Water};
+use crate::object::Component;
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::{visit_visible, Visitor, WorldVisitor};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ObjectStats {
+    pub id: &'static str,
+    pub kind: &'static str,
+    /// Triangles, lines and points drawn for the object
+    pub primitives: usize,
+    /// Density samples of a frame, the screen area of the volume in pixels
+    /// times the steps along each ray
+    pub march_cost: f32,
+    /// Estimated bytes of the geometry and the textures. Shared data is
+    /// counted for every object using it.
+    pub memory: usize,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SceneStats {
+    pub objects: Vec<ObjectStats>,
+}
+
+impl SceneStats {
+    pub fn primitives(&self) -> usize {
+        self.objects.iter().map(|x| x.primitives).sum()
+    }
+
+    pub fn march_cost(&self) -> f32 {
+        self.objects.iter().map(|x| x.march_cost).sum()
+    }
+
+    pub fn memory(&self) -> usize {
+        self.objects.iter().map(|x| x.memory).sum()
+    }
+}
+
+fn noise_memory(builder: NoiseBuilder) -> usize {
+    let resolution = match builder {
+        NoiseBuilder::WorleyBuilder(x) => x.resolution,
+        NoiseBuilder::PerlinBuilder(x) => x.resolution,
+    };
+    resolution.pow(3) * size_of::<Vec4>()
+}
+
+/// Gives the [`ObjectStats`] of the visible objects seen by the camera on a
+/// canvas of the given size
+pub struct StatsVisitor<'a> {
+    camera: &'a Camera,
+    size: Vec2,
+    view_projection: Mat4,
+    /// World matrix of the object being visited
+    model: Mat4,
+    id: &'static str,
+    kind: &'static str,
+}
+
+impl<'a> StatsVisitor<'a> {
+    pub fn new(camera: &'a Camera, width: f32, height: f32) -> Self {
+        Self {
+            camera,
+            size: Vec2::new(width, height),
+            view_projection: camera.projection(width, height) * camera.view(),
+            model: Mat4::IDENTITY,
+            id: "",
+            kind: "",
+        }
+    }
+
+    /// Pixels covered by the screen rectangle around the box, the whole
+    /// canvas when the box reaches behind the eye
+    fn screen_area(&self, local: &BoundingBox) -> f32 {
+        let mvp = self.view_projection * self.model;
+        let mut min = Vec2::splat(1.0);
+        let mut max = Vec2::splat(-1.0);
+        for corner in local.corners() {
+            let clip = mvp * corner.extend(1.0);
+            if clip.w <= 0.0 {
+                return self.size.x * self.size.y;
+            }
+            let ndc = Vec2::new(clip.x, clip.y) / clip.w;
+            min = min.min(ndc);
+            max = max.max(ndc);
+        }
+        let extent = (max.min(Vec2::ONE) - min.max(Vec2::NEG_ONE)).max(Vec2::ZERO);
+        let pixels = extent / 2.0 * self.size;
+        pixels.x * pixels.y
+    }
+
+    /// Statistics of the object being visited
+    fn object(&self, primitives: usize, march_cost: f32, memory: usize) -> Vec<ObjectStats> {
+        vec![ObjectStats {
+            id: self.id,
+            kind: self.kind,
+            primitives,
+            march_cost,
+            memory,
+        }]
+    }
+}
+
+impl WorldVisitor for StatsVisitor<'_> {
+    fn model_mut(&mut self) -> &mut Mat4 {
+        &mut self.model
+    }
+
+    fn enter(
+        &mut self,
+        _scene_objects: &SceneObjects,
+        id: &'static str,
+        object: &Component,
+    ) -> bool {
+        (self.id, self.kind) = (id, object.kind());
+        true
+    }
+}
+
+impl Visitor for StatsVisitor<'_> {
+    /// Objects the visitor knows about, the others are skipped
+    type Output = Vec<ObjectStats>;
+
+    fn join(&mut self, output: &mut Vec<ObjectStats>, next: Vec<ObjectStats>) {
+        output.extend(next);
+    }
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) -> Vec<ObjectStats> {
+        visit_visible(self, scene_objects)
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) -> Vec<ObjectStats> {
+        let area = self.screen_area(&cloud.obb().aabb());
+        let memory = [cloud.noise, cloud.detail_noise, cloud.weather_noise]
+            .into_iter()
+            .map(noise_memory)
+            .sum();
+        self.object(0, area * cloud.num_steps as f32, memory)
+    }
+
+    fn visit_grid(&mut self, grid: &Grid) -> Vec<ObjectStats> {
+        let lines = grid.visible_lines(self.camera, self.size.x, self.size.y);
+        self.object(lines.len(), 0.0, 0)
+    }
+
+    fn visit_sun(&mut self, _sun: &Sun) -> Vec<ObjectStats> {
+        self.object(1, 0.0, 0)
+    }
+
+    fn visit_terrain(&mut self, terrain: &Terrain) -> Vec<ObjectStats> {
+        let triangles = terrain.triangles.len();
+        self.object(triangles, 0.0, triangles * size_of::<[Vec3; 6]>())
+    }
+
+    fn visit_water(&mut self, water: &Water) -> Vec<ObjectStats> {
+        // Every pixel of the surface is shaded once
+        let area = self.screen_area(&water.bounding_box);
+        self.object(2, area, 0)
+    }
+
+    fn visit_skybox(&mut self, skybox: &Skybox) -> Vec<ObjectStats> {
+        let memory = match skybox {
+            Skybox::Panorama(image) => image.pixels.len() * size_of::<egui::Color32>(),
+            Skybox::Dome { .. } => 0,
+        };
+        self.object(0, 0.0, memory)
+    }
+
+    fn visit_mesh(&mut self, mesh: &Mesh) -> Vec<ObjectStats> {
+        let memory =
+            mesh.vertices.len() * size_of::<Vec3>() + mesh.faces.len() * size_of::<[u32; 3]>();
+        self.object(mesh.faces.len(), 0.0, memory)
+    }
+
+    fn visit_light(&mut self, _light: &Light) -> Vec<ObjectStats> {
+        self.object(1, 0.0, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::CloudBuilder;
+    use crate::object::objects::texture3d::WorleyBuilder;
+
+    #[test]
+    fn test_scene_stats() {
+        let mut objects = SceneObjects::default();
+        objects.add_object("box", Mesh::cuboid(Vec3::ONE));
+        let cloud = CloudBuilder::default()
+            .with_noise(WorleyBuilder::new().with_resolution(4))
+            .with_bounding_box((Vec3::splat(-1.0), Vec3::ONE))
+            .with_num_steps(10);
+        let cloud = Cloud::with_volumes(
+            cloud,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        objects.add_object("cloud", Component::composite_from([("cloud", cloud)]));
+
+        let camera = Camera::default();
+        let stats = SceneStats {
+            objects: StatsVisitor::new(&camera, 200.0, 100.0).visit_composite(&objects),
+        };
+        assert_eq!(stats.objects.len(), 2);
+        assert_eq!(stats.primitives(), 12);
+        let cloud = stats.objects[1];
+        assert_eq!((cloud.id, cloud.kind), ("cloud", "cloud"));
+        assert!(cloud.march_cost > 0.0 && cloud.march_cost <= 200.0 * 100.0 * 10.0);
+        assert!(cloud.memory > 0);
+    }
+}
@@ -0,0 +1,248 @@
+//! Vector figures of the scene: the grid, the boxes of the objects, camera
+//! frustums and the direction of the sunlight
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use egui::{Color32, Pos2, Rect};
+use glam::{Mat4, Vec3};
+
+use crate::math::Transform;
+use crate::object::camera::Camera;
+use crate::object::objects::{BoundingBox, Cloud, Grid, Mesh, Sun, Terrain, Water};
+use crate::object::Component;
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::draw_visitor::box_edges;
+use crate::visitor::{visit_visible, Visitor, WorldVisitor};
+
+/// Share of the distance from the sun to the scene center covered by its
+/// arrow
+const SUN_ARROW_LENGTH: f32 = 0.25;
+/// Length of the arrowhead strokes in pixels
+const ARROWHEAD: f32 = 10.0;
+
+fn svg_color(color: Color32) -> String {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    format!(
+        "stroke=\"#{r:02x}{g:02x}{b:02x}\" stroke-opacity=\"{:.3}\"",
+        a as f32 / 255.0
+    )
+}
+
+/// Writes the outlines of the visible objects as SVG lines, projected the
+/// same way [`super::draw_visitor::DrawVisitor`] draws them
+pub struct SvgExportVisitor<'a> {
+    camera: &'a Camera,
+    rect: Rect,
+    view_projection: Transform,
+    /// World matrix of the object being visited
+    model: Mat4,
+    hidden_layers: BTreeSet<&'static str>,
+    elements: Vec<String>,
+}
+
+impl<'a> SvgExportVisitor<'a> {
+    pub fn new(camera: &'a Camera, width: f32, height: f32) -> Self {
+        let rect = Rect::from_min_size(Pos2::ZERO, egui::vec2(width, height));
+        let proj = camera.projection(width, height);
+        Self {
+            camera,
+            rect,
+            view_projection: Transform::new(proj * camera.view(), rect),
+            model: Mat4::IDENTITY,
+            hidden_layers: BTreeSet::new(),
+            elements: Vec::new(),
+        }
+    }
+
+    pub fn with_hidden_layers(mut self, hidden_layers: BTreeSet<&'static str>) -> Self {
+        self.hidden_layers = hidden_layers;
+        self
+    }
+
+    /// Adds the frustum of the camera up to its pivot, e.g. of the camera a
+    /// picture was rendered from
+    pub fn with_frustum(mut self, camera: &Camera) -> Self {
+        self.frustum(camera);
+        self
+    }
+
+    pub fn into_svg(self) -> String {
+        let (width, height) = (self.rect.width(), self.rect.height());
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+             viewBox=\"0 0 {width} {height}\">\n"
+        );
+        svg.push_str("<rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n");
+        for element in self.elements {
+            svg.push_str(&element);
+            svg.push('\n');
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    fn project(&self, point: Vec3) -> Option<Pos2> {
+        let mvp = self.view_projection.with_model(self.model);
+        if mvp.clip_w(point) <= self.camera.proj.clip_near {
+            return None;
+        }
+        Some(mvp.world_to_egui(point).0.to_pos2())
+    }
+
+    /// Segment between points of the local space of the visited object,
+    /// skipped when it reaches behind the eye
+    fn line(&mut self, a: Vec3, b: Vec3, width: f32, color: Color32) {
+        let (Some(a), Some(b)) = (self.project(a), self.project(b)) else {
+            return;
+        };
+        self.screen_line(a, b, width, color);
+    }
+
+    fn screen_line(&mut self, a: Pos2, b: Pos2, width: f32, color: Color32) {
+        let mut element = String::new();
+        let _ = write!(
+            element,
+            "<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke-width=\"{width}\" {}/>",
+            a.x,
+            a.y,
+            b.x,
+            b.y,
+            svg_color(color)
+        );
+        self.elements.push(element);
+    }
+
+    fn bounding_box(&mut self, bb: &BoundingBox, color: Color32) {
+        for (a, b) in box_edges(bb) {
+            self.line(a, b, 1.0, color);
+        }
+    }
+
+    fn frustum(&mut self, camera: &Camera) {
+        let model = std::mem::replace(&mut self.model, Mat4::IDENTITY);
+        let eye = camera.pos();
+        let forward = camera.dir();
+        let right = forward.cross(Vec3::Y).normalize_or(Vec3::X);
+        let up = right.cross(forward);
+        let aspect = self.rect.aspect_ratio();
+        let corners = |distance: f32| {
+            let half_height = (camera.proj.fov / 2.0).tan() * distance;
+            let (up, right) = (up * half_height, right * half_height * aspect);
+            let center = eye + forward * distance;
+            [
+                center - right - up,
+                center + right - up,
+                center + right + up,
+                center - right + up,
+            ]
+        };
+        let near = corners(camera.proj.clip_near);
+        let far = corners(camera.view.distance);
+        let color = Color32::from_rgb(40, 40, 160);
+        for i in 0..4 {
+            let j = (i + 1) % 4;
+            self.line(near[i], near[j], 1.0, color);
+            self.line(far[i], far[j], 1.0, color);
+            self.line(near[i], far[i], 0.5, color);
+        }
+        self.model = model;
+    }
+}
+
+impl WorldVisitor for SvgExportVisitor<'_> {
+    fn model_mut(&mut self) -> &mut Mat4 {
+        &mut self.model
+    }
+
+    fn enter(
+        &mut self,
+        scene_objects: &SceneObjects,
+        id: &'static str,
+        _object: &Component,
+    ) -> bool {
+        !self.hidden_layers.contains(scene_objects.layer(id))
+    }
+}
+
+impl Visitor for SvgExportVisitor<'_> {
+    type Output = ();
+
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) {
+        visit_visible(self, scene_objects)
+    }
+
+    fn visit_camera(&mut self, camera: &Camera) {
+        self.frustum(camera);
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) {
+        let obb = cloud.obb();
+        let model = self.model;
+        self.model = model * obb.matrix();
+        self.bounding_box(&obb.local_box(), Color32::DARK_RED);
+        self.model = model;
+    }
+
+    fn visit_grid(&mut self, grid: &Grid) {
+        let lines = grid.visible_lines(self.camera, self.rect.width(), self.rect.height());
+        for line in lines {
+            let width = if line.major { 1.0 } else { 0.5 };
+            self.line(line.a, line.b, width, grid.line_color(&line));
+        }
+    }
+
+    fn visit_bounding_box(&mut self, bb: &BoundingBox) {
+        self.bounding_box(bb, Color32::DARK_RED);
+    }
+
+    /// Arrow from the sun towards the origin of its composite
+    fn visit_sun(&mut self, sun: &Sun) {
+        let from = sun.get_pos();
+        let to = from - from * SUN_ARROW_LENGTH;
+        let (Some(a), Some(b)) = (self.project(from), self.project(to)) else {
+            return;
+        };
+        let color = Color32::from_rgb(230, 150, 0);
+        self.screen_line(a, b, 2.0, color);
+        let back = (a - b).normalized() * ARROWHEAD;
+        for angle in [-0.4f32, 0.4] {
+            let (sin, cos) = angle.sin_cos();
+            let side = egui::vec2(back.x * cos - back.y * sin, back.x * sin + back.y * cos);
+            self.screen_line(b, b + side, 2.0, color);
+        }
+    }
+
+    fn visit_terrain(&mut self, terrain: &Terrain) {
+        self.bounding_box(&terrain.bounding_box, Color32::DARK_GREEN);
+    }
+
+    fn visit_water(&mut self, water: &Water) {
+        self.bounding_box(&water.bounding_box, Color32::BLUE);
+    }
+
+    fn visit_mesh(&mut self, mesh: &Mesh) {
+        self.bounding_box(&mesh.bounding_box(), Color32::DARK_GRAY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_svg_export() {
+        let mut objects = SceneObjects::default();
+        objects.add_object("box", Mesh::cuboid(Vec3::ONE));
+        objects.add_object("sun", Sun::new(10.0, -45.0, -45.0));
+
+        let camera = Camera::default();
+        let mut visitor = SvgExportVisitor::new(&camera, 320.0, 240.0);
+        visitor.visit_composite(&objects);
+        let svg = visitor.into_svg();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        // Twelve edges of the box, the sun arrow and its head
+        assert_eq!(svg.matches("<line").count(), 15);
+    }
+}
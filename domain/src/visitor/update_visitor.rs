@@ -0,0 +1,101 @@
+//! Per-frame changes of the time-dependent objects
+
+use crate::object::objects::{Cloud, Sun, Water};
+use crate::scene::scene_composite::SceneObjects;
+use crate::visitor::{VisitableMut, VisitorMut};
+
+/// Moves the animated objects `dt` seconds forward: the clouds drift with
+/// the wind, the sun follows its day cycle and the waves roll
+pub struct UpdateVisitor {
+    pub dt: f32,
+    id: &'static str,
+    /// Objects changed by the update
+    updated: Vec<&'static str>,
+}
+
+impl UpdateVisitor {
+    pub fn new(dt: f32) -> Self {
+        Self {
+            dt,
+            id: "",
+            updated: Vec::new(),
+        }
+    }
+
+    /// Names of the objects changed by the update
+    pub fn into_updated(self) -> Vec<&'static str> {
+        self.updated
+    }
+}
+
+impl VisitorMut for UpdateVisitor {
+    fn visit_composite_mut(&mut self, scene_objects: &mut SceneObjects) {
+        for (&id, object) in scene_objects.objects.iter_mut() {
+            self.id = id;
+            object.accept_mut(self);
+        }
+    }
+
+    fn visit_cloud_mut(&mut self, cloud: &mut Cloud) {
+        let wind = cloud.wind;
+        if wind != glam::Vec3::ZERO {
+            cloud.offset += wind * self.dt;
+            self.updated.push(self.id);
+        }
+    }
+
+    fn visit_sun_mut(&mut self, sun: &mut Sun) {
+        if sun.day_length > 0.0 {
+            sun.advance(self.dt);
+            self.updated.push(self.id);
+        }
+    }
+
+    fn visit_water_mut(&mut self, water: &mut Water) {
+        water.advance(self.dt);
+        self.updated.push(self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::CloudBuilder;
+    use crate::object::Component;
+    use glam::Vec3;
+
+    #[test]
+    fn test_update() {
+        let cloud = CloudBuilder::default().with_wind(Vec3::X);
+        let cloud = Cloud::with_volumes(
+            cloud,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let mut nested = SceneObjects::default();
+        nested.add_object("sun", Sun::new(10.0, 170.0, 0.0).with_day_length(36.0));
+        let mut objects = SceneObjects::default();
+        objects.add_object("cloud", cloud);
+        objects.add_object("still", Sun::new(10.0, 0.0, 0.0));
+        objects.add_object("sky", Component::Composite(nested));
+
+        let mut visitor = UpdateVisitor::new(2.0);
+        objects.accept_mut(&mut visitor);
+        let mut updated = visitor.into_updated();
+        updated.sort();
+        assert_eq!(updated, ["cloud", "sun"]);
+
+        let Some(Component::Cloud(cloud)) = objects.get_object("cloud") else {
+            panic!("no cloud");
+        };
+        assert_eq!(cloud.offset, Vec3::new(2.0, 0.0, 0.0));
+        let Some(Component::Composite(nested)) = objects.get_object("sky") else {
+            panic!("no composite");
+        };
+        let Some(Component::Sun(sun)) = nested.get_object("sun") else {
+            panic!("no sun");
+        };
+        assert!((sun.a - 150.0).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,180 @@
+//! Parameters of the objects that break their rendering
+
+use crate::object::objects::{Cloud, Grid, Light, Sun};
+use crate::object::Component;
+use crate::scene::scene_composite::SceneObjects;
+use crate::scene::ObjectTransform;
+use crate::visitor::{Visitable, Visitor};
+
+/// Problems with the local transform of an object
+pub(crate) fn transform_problems(transform: &ObjectTransform) -> Vec<String> {
+    [
+        ("translation", transform.translation.is_finite()),
+        ("rotation", transform.rotation.is_finite()),
+        ("scale", transform.scale.is_finite()),
+    ]
+    .into_iter()
+    .filter(|(_, finite)| !finite)
+    .map(|(name, _)| format!("{name} is not a finite number"))
+    .collect()
+}
+
+/// Finds the objects of the composite in an invalid state, such as an empty
+/// cloud volume or a sun at zero distance, with the problems named
+#[derive(Default)]
+pub struct ValidateVisitor {
+    /// Name of the object being visited
+    id: &'static str,
+}
+
+impl ValidateVisitor {
+    /// Problem for each named value that is not a finite number
+    fn finite<'a>(
+        &self,
+        values: impl IntoIterator<Item = (&'a str, bool)>,
+    ) -> Vec<(&'static str, String)> {
+        values
+            .into_iter()
+            .filter(|(_, finite)| !finite)
+            .map(|(name, _)| self.problem(format!("{name} is not a finite number")))
+            .collect()
+    }
+
+    fn problem(&self, message: impl Into<String>) -> (&'static str, String) {
+        (self.id, message.into())
+    }
+}
+
+impl Visitor for ValidateVisitor {
+    type Output = Vec<(&'static str, String)>;
+
+    /// Checks the hidden objects as well, they may be shown later
+    fn visit_composite(&mut self, scene_objects: &SceneObjects) -> Self::Output {
+        let mut problems = Vec::new();
+        for (&id, object) in scene_objects.iter() {
+            self.id = id;
+            if let Some(node) = scene_objects.node(id) {
+                problems.extend(
+                    transform_problems(&node.local)
+                        .into_iter()
+                        .map(|x| self.problem(x)),
+                );
+            }
+            problems.extend(match object {
+                Component::Composite(objects) => self.visit_composite(objects),
+                object => object.accept(self),
+            });
+        }
+        problems
+    }
+
+    fn visit_cloud(&mut self, cloud: &Cloud) -> Self::Output {
+        let bounds = cloud.bounding_box;
+        let mut problems = self.finite([
+            ("cloud_scale", cloud.cloud_scale.is_finite()),
+            ("density_threshold", cloud.density_threshold.is_finite()),
+            ("density_offset", cloud.density_offset.is_finite()),
+            ("density_multiplier", cloud.density_multiplier.is_finite()),
+            ("ray_offset_strength", cloud.ray_offset_strength.is_finite()),
+            ("detail_noise_scale", cloud.detail_noise_scale.is_finite()),
+            ("detail_noise_weight", cloud.detail_noise_weight.is_finite()),
+            (
+                "light_absorption_toward_sun",
+                cloud.light_absorption_toward_sun.is_finite(),
+            ),
+            (
+                "light_absorption_through_cloud",
+                cloud.light_absorption_through_cloud.is_finite(),
+            ),
+            ("darkness_threshold", cloud.darkness_threshold.is_finite()),
+            ("height_map_factor", cloud.height_map_factor.is_finite()),
+            ("volume_offset", cloud.volume_offset.is_finite()),
+            ("edge_distance", cloud.edge_distance.is_finite()),
+            ("offset", cloud.offset.is_finite()),
+            ("wind", cloud.wind.is_finite()),
+            ("shape_offset", cloud.shape_offset.is_finite()),
+            ("detail_offset", cloud.detail_offset.is_finite()),
+            ("detail_weights", cloud.detail_weights.is_finite()),
+            ("shape_noise_weights", cloud.shape_noise_weights.is_finite()),
+            ("phase_params", cloud.phase_params.is_finite()),
+            (
+                "bounding_box",
+                bounds.min.is_finite() && bounds.max.is_finite(),
+            ),
+        ]);
+        if bounds.min.cmpge(bounds.max).any() {
+            problems.push(self.problem("bounding_box is empty"));
+        }
+        if cloud.num_steps == 0 {
+            problems.push(self.problem("num_steps is zero"));
+        }
+        problems
+    }
+
+    fn visit_sun(&mut self, sun: &Sun) -> Self::Output {
+        let mut problems = self.finite([
+            (
+                "sun angles",
+                [sun.d, sun.a, sun.z].iter().all(|x| x.is_finite()),
+            ),
+            ("intensity", sun.intensity.is_finite()),
+            ("day_length", sun.day_length.is_finite()),
+        ]);
+        if sun.d <= 0.0 {
+            problems.push(self.problem("sun distance is not positive"));
+        }
+        problems
+    }
+
+    fn visit_grid(&mut self, grid: &Grid) -> Self::Output {
+        let mut problems = self.finite([("grid scale", grid.scale.is_finite())]);
+        if grid.k <= 0 || grid.scale <= 0.0 {
+            problems.push(self.problem("grid has no cells"));
+        }
+        problems
+    }
+
+    fn visit_light(&mut self, light: &Light) -> Self::Output {
+        self.finite([("intensity", light.intensity.is_finite())])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::objects::cloud::CloudBuilder;
+    use glam::Vec3;
+
+    #[test]
+    fn test_validate_scene() {
+        let cloud = CloudBuilder::default()
+            .with_bounding_box((Vec3::ZERO, Vec3::new(1.0, 0.0, 1.0)))
+            .with_clouds_offset(Vec3::NAN);
+        let cloud = Cloud::with_volumes(
+            cloud,
+            Default::default(),
+            Default::default(),
+            Default::default(),
+        );
+        let mut nested = SceneObjects::default();
+        nested.add_object("sun", Sun::new(0.0, 0.0, 0.0));
+        nested.set_visible("sun", false).unwrap();
+        let mut objects = SceneObjects::default();
+        objects.add_object("cloud", cloud);
+        objects.add_object("grid", Grid::new(10, 1.0));
+        objects.add_object("sky", Component::Composite(nested));
+
+        let mut problems = ValidateVisitor::default().visit_composite(&objects);
+        problems.sort();
+        let problems: Vec<_> = problems.iter().map(|(id, x)| (*id, x.as_str())).collect();
+        assert_eq!(
+            problems,
+            [
+                ("cloud", "bounding_box is empty"),
+                ("cloud", "num_steps is zero"),
+                ("cloud", "offset is not a finite number"),
+                ("sun", "sun distance is not positive"),
+            ]
+        );
+    }
+}
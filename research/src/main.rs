@@ -5,7 +5,7 @@ use domain::object::camera::Camera;
 use domain::object::objects::{Cloud, Sun};
 use domain::object::objects::cloud::{beer, hg, phase, CloudBuilder};
 use domain::object::objects::texture3d::{PerlinBuilder, WorleyBuilder};
-use domain::visitor::{Visitable, Visitor};
+use domain::visitor::Visitor;
 
 pub struct DrawVisitorTest<'a> {
     camera: &'a Camera,
@@ -22,6 +22,7 @@ impl<'a> DrawVisitorTest<'a> {
 }
 
 impl<'a> Visitor for DrawVisitorTest<'a> {
+    type Output = ();
 
     fn visit_cloud(&mut self, cloud: &Cloud) {
         use rayon::prelude::*;
@@ -121,9 +122,9 @@ impl<'a> DrawVisitorTest2<'a> {
 }
 
 impl<'a> Visitor for DrawVisitorTest2<'a> {
+    type Output = ();
 
     fn visit_cloud(&mut self, cloud: &Cloud) {
-        use rayon::prelude::*;
 
         let bb = cloud.bounding_box();
         let (w, h) = (1056, 900);
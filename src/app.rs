@@ -1,28 +1,154 @@
 use eframe::egui;
 use eframe::egui::Color32;
 
+use domain::canvas::clip::ClipFormat;
+use domain::canvas::frame_sequence::SequenceSettings;
 use domain::canvas::painter::Painter3D;
-use domain::facade::{CameraCommand, DrawCommand, SceneCommand};
+use domain::facade::{
+    AnimationCommand, CacheCommand, CameraCommand, DiagnosticsCommand, DrawCommand, EventCommand,
+    HistoryCommand, InputCommand, IsosurfaceTarget, JobCommand, ProfilingCommand, SceneCommand,
+    ScriptCommand, SelectionCommand, SelectionState, SettingsCommand, TimeCommand, View,
+};
 use domain::facade::{Executor, Facade};
+use domain::io::shader::ShaderLanguage;
+use domain::managers::animation_manager::{Interpolation, Property, Track};
+use domain::managers::diagnostics_manager::Severity;
+use domain::managers::event_manager::{JobFinished, Subscription};
+use domain::managers::history_manager::ParamDiff;
+use domain::managers::input_manager::{Action, InputFrame};
+use domain::managers::job_manager::JobStatus;
+use domain::managers::profiling_manager::Stage;
+use domain::managers::scene_manager::{ComponentSnapshot, ObjectInfo};
 use domain::math::transform::glam;
 use domain::math::transform::glam::{Vec3, Vec4};
-use domain::object::camera::Camera;
-use domain::object::objects::{Grid, Sun};
+use domain::object::camera::{ArcBallController, Camera};
+use domain::object::objects::{
+    Background, Fog, GizmoMode, Grid, GridPlane, HeightMap, Light, LightKind, Mesh, OrientationGizmo,
+    Skybox, Sun, TransformGizmo, Water,
+};
 use domain::object::objects::cloud::CloudBuilder;
 use domain::object::objects::terrain::TerrainBuilder;
 use domain::object::objects::texture3d::{NoiseBuilder, PerlinBuilder, WorleyBuilder};
 
+/// Controls overriding the default ones, see `InputConfig`
+const BINDINGS_PATH: &str = "bindings.ron";
+/// Image rendered in the background by the "Рендер в файл" button
+const RENDER_PATH: &str = "render.png";
+const RENDER_SIZE: [usize; 2] = [1920, 1080];
+/// Linear light of the clouds written by the "HDR в EXR" button
+const HDR_PATH: &str = "render.exr";
+/// Renders for the report listed for the "Пакетный рендер" button, see
+/// `domain::io::batch`
+const BATCH_PATH: &str = "batch.toml";
+/// Layout of the scene written by the "glTF" button
+const GLTF_PATH: &str = "scene.gltf";
+/// Density of the cloud written by the "Воксели" button, with its `.json`
+/// description next to it
+const VOLUME_PATH: &str = "cloud.raw";
+const VOLUME_SIZE: [usize; 3] = [128, 64, 128];
+/// Isosurface of the cloud density shown in the scene or written by the
+/// "OBJ" and "STL" buttons
+const ISOSURFACE_NAME: &str = "isosurface";
+const ISOSURFACE_SIZE: [usize; 3] = [64, 32, 64];
+const ISOSURFACE_OBJ_PATH: &str = "isosurface.obj";
+const ISOSURFACE_STL_PATH: &str = "isosurface.stl";
+/// Directory the "Рендер кадров" button writes the animation frames to
+const SEQUENCE_DIR: &str = "frames";
+const SEQUENCE_SIZE: [usize; 2] = [960, 540];
+const SEQUENCE_FPS: f32 = 24.0;
+/// Clips of the animation written by the "GIF" and "MP4" buttons
+const GIF_PATH: &str = "animation.gif";
+#[cfg(feature = "mp4")]
+const MP4_PATH: &str = "animation.mp4";
+/// Size of the thumbnails of the saved cameras and the auxiliary views
+const THUMBNAIL_SIZE: [usize; 2] = [160, 120];
+/// Stage timings written for the performance graphs
+const PROFILE_PATH: &str = "profile.csv";
+/// Named versions of the cloud parameters
+const VERSIONS_PATH: &str = "versions.ron";
+/// Parameters of the cloud with its noise written by the preset buttons
+const PRESET_PATH: &str = "cloud.toml";
+/// Shader snippets of the cloud written by the export buttons
+const WGSL_PATH: &str = "cloud.wgsl";
+const GLSL_PATH: &str = "cloud.glsl";
+/// Cloud settings exported from the Unity project of Sebastian Lague
+const UNITY_PATH: &str = "clouds.asset";
+/// Directory with the `.script` files run at startup or on a timer
+const SCRIPTS_DIR: &str = "scripts";
+/// Vector figure of the scene written by the export button
+const SVG_PATH: &str = "scene.svg";
+/// Size of the canvas the scene is drawn on
+const CANVAS_SIZE: [f32; 2] = [1056.0, 900.0];
+/// Seconds a status message stays on the canvas
+const STATUS_TIME: f64 = 4.0;
+
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        ctx.set_visuals(egui::Visuals::light());
+        ctx.set_visuals(if self.dark_mode {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        });
         ctx.request_repaint();
-        self.cloud.offset += self.offset_speed;
         self.executor
-            .exec(SceneCommand::SetOffset("cloud", self.cloud.offset));
+            .exec(TimeCommand::Tick(ctx.input(|i| i.stable_dt)));
+        // The cloud drifts with the wind during the tick
+        if let Some(ComponentSnapshot::Cloud(cloud)) = self
+            .executor
+            .exec(SceneCommand::GetObjectSnapshot("cloud"))
+            .as_snapshot()
+        {
+            self.cloud.offset = cloud.offset;
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             self.ui(ui);
         });
         self.executor.exec(DrawCommand::Draw);
+        self.executor.exec(ProfilingCommand::EndFrame);
+        self.executor.exec(JobCommand::Poll);
+        let finished = self.executor.exec(EventCommand::Read(self.job_events));
+        for job in finished.into_events() {
+            let text = match job.error {
+                None => format!("Готово: {}", job.name),
+                Some(err) => format!("Ошибка: {}: {err}", job.name),
+            };
+            self.status = Some((text, ctx.input(|i| i.time) + STATUS_TIME));
+        }
+    }
+}
+
+/// New file for a screenshot, named by the time it is taken
+fn screenshot_path() -> std::path::PathBuf {
+    let time = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    format!("screenshot-{}.png", time.as_millis()).into()
+}
+
+/// Lists the children of `parent`, nesting the objects that have children.
+/// The checkboxes show and hide the objects.
+fn object_tree(
+    ui: &mut egui::Ui,
+    executor: &mut impl Executor,
+    objects: &[ObjectInfo],
+    parent: Option<&'static str>,
+) {
+    for object in objects.iter().filter(|x| x.parent == parent) {
+        let label = format!("{} ({})", object.name, object.kind);
+        let mut visible = object.visible;
+        let toggled = |visible| SceneCommand::SetVisible(object.name, visible);
+        if objects.iter().any(|x| x.parent == Some(object.name)) {
+            ui.horizontal(|ui| {
+                if ui.checkbox(&mut visible, "").changed() {
+                    executor.exec(toggled(visible));
+                }
+                ui.collapsing(label, |ui| {
+                    object_tree(ui, executor, objects, Some(object.name))
+                });
+            });
+        } else if ui.checkbox(&mut visible, label).changed() {
+            executor.exec(toggled(visible));
+        }
     }
 }
 
@@ -42,7 +168,7 @@ impl App {
 
     fn painter(&mut self, ui: &mut egui::Ui) -> (egui::Response, Painter3D) {
         let (response, painter) =
-            ui.allocate_painter([1056.0, 900.0].into(), egui::Sense::click_and_drag());
+            ui.allocate_painter(CANVAS_SIZE.into(), egui::Sense::click_and_drag());
 
         let bc = self.background_color;
         painter.rect(
@@ -52,33 +178,688 @@ impl App {
             egui::Stroke::new(0.5, egui::Color32::BLACK),
         );
         let rect = response.rect;
-        (response, Painter3D::new(painter, rect, bc))
+        let painter = Painter3D::new(painter, rect, bc);
+
+        let frame_time = ui.input(|i| i.unstable_dt) * 1000.0;
+        painter.hud().text(
+            egui::Pos2::new(8.0, 8.0),
+            egui::Align2::LEFT_TOP,
+            format!("{frame_time:.1} мс"),
+            egui::FontId::monospace(12.0),
+            Color32::BLACK,
+        );
+        let now = ui.input(|i| i.time);
+        if let Some((text, _)) = self.status.as_ref().filter(|(_, until)| *until > now) {
+            painter.hud().text(
+                egui::Pos2::new(8.0, rect.height() - 8.0),
+                egui::Align2::LEFT_BOTTOM,
+                text,
+                egui::FontId::proportional(14.0),
+                Color32::BLACK,
+            );
+        }
+        (response, painter)
+    }
+
+    /// Fills the panels with the parameters of the newly active object
+    fn select(&mut self, state: SelectionState) {
+        if state.events.is_empty() {
+            return;
+        }
+        self.selected = state.selected;
+        let Some(id) = state.active else {
+            return;
+        };
+        match self
+            .executor
+            .exec(SceneCommand::GetObjectSnapshot(id))
+            .as_snapshot()
+        {
+            Some(ComponentSnapshot::Cloud(cloud)) => self.cloud = **cloud,
+            Some(ComponentSnapshot::Sun(sun)) => {
+                self.sun = (sun.d, sun.a.abs(), sun.z.abs());
+                self.sun_intensity = sun.intensity;
+            }
+            Some(ComponentSnapshot::Grid(grid)) => self.grid = grid.clone(),
+            Some(ComponentSnapshot::Fog(fog)) => self.fog = *fog,
+            _ => {}
+        }
+    }
+
+    /// Shot where the sun sets while the cloud thickens
+    fn author_sunset(&mut self) {
+        const DURATION: f32 = 20.0;
+        let smooth = |target, property| {
+            Track::new(target, property).with_interpolation(Interpolation::Smooth)
+        };
+        let tracks = [
+            (
+                "sun_elevation",
+                smooth("sun", Property::SunElevation)
+                    .with_key(0.0, 60.0)
+                    .with_key(DURATION, 2.0),
+            ),
+            (
+                "sun_temperature",
+                smooth("sun", Property::SunTemperature)
+                    .with_key(0.0, self.sun_temperature)
+                    .with_key(DURATION, 2000.0),
+            ),
+            (
+                "sun_intensity",
+                smooth("sun", Property::SunIntensity)
+                    .with_key(0.0, self.sun_intensity)
+                    .with_key(DURATION, 0.4 * self.sun_intensity),
+            ),
+            (
+                "cloud_density",
+                smooth("cloud", Property::CloudDensityMultiplier)
+                    .with_key(0.0, self.cloud.density_multiplier)
+                    .with_key(DURATION, 1.5 * self.cloud.density_multiplier),
+            ),
+        ];
+        self.executor.exec(AnimationCommand::Clear);
+        for (name, track) in tracks {
+            self.executor
+                .exec(AnimationCommand::AddTrack(name.to_owned(), track));
+        }
+        self.executor
+            .exec(AnimationCommand::Play(self.animation_looped));
+    }
+
+    /// Renders the view of the scene into a texture of the thumbnail size
+    fn render_view(&mut self, ctx: &egui::Context, view: View) -> Option<egui::TextureHandle> {
+        let image = self
+            .executor
+            .exec(DrawCommand::RenderView(view, THUMBNAIL_SIZE))
+            .into_image()?;
+        Some(ctx.load_texture("view", image.into_image(), Default::default()))
+    }
+
+    /// Passes the settings changed in the UI to the domain
+    fn store_settings(&mut self) {
+        let Some(mut settings) = self.executor.exec(SettingsCommand::Query).into_settings() else {
+            return;
+        };
+        settings.quality.num_steps = self.cloud.num_steps;
+        settings.quality.num_steps_light = self.cloud.num_steps_light;
+        settings.quality.light_interval = self.cloud.light_interval;
+        settings.ui.show_debug = self.show_debug;
+        settings.ui.dark_mode = self.dark_mode;
+        settings.quality.adaptive = self.adaptive_quality;
+        settings.quality.target_fps = self.target_fps;
+        settings.quality.coarse_density = self.coarse_density;
+        settings.camera = self.camera_control;
+        self.executor.exec(SettingsCommand::Set(settings));
     }
 
     fn handle_camera(&mut self, resp: &egui::Response, ui: &mut egui::Ui) {
-        if resp.dragged_by(egui::PointerButton::Primary) {
-            if ui.input(|i| i.raw.modifiers.shift_only()) {
-                let pan = CameraCommand::Pan(resp.drag_delta().x, resp.drag_delta().y);
-                self.executor.exec(pan);
-            } else {
-                let pivot = CameraCommand::Pivot(resp.drag_delta().x, resp.drag_delta().y);
-                self.executor.exec(pivot);
+        if let Some(pos) = resp.interact_pointer_pos().filter(|_| resp.clicked()) {
+            self.executor
+                .exec(CameraCommand::ClickGizmo("gizmo", resp.rect, pos));
+            let add = ui.input(|i| i.modifiers.shift);
+            let state = self
+                .executor
+                .exec(SelectionCommand::Pick(resp.rect, pos, add));
+            self.select(state);
+        }
+
+        if resp.drag_started_by(egui::PointerButton::Primary) {
+            if let Some(pos) = ui.input(|i| i.pointer.press_origin()) {
+                let grab = SceneCommand::GrabGizmo("transform_gizmo", resp.rect, pos);
+                self.gizmo_grabbed = self.executor.exec(grab).is_grabbed();
             }
         }
+        if resp.drag_stopped() && self.gizmo_grabbed {
+            self.gizmo_grabbed = false;
+            self.executor
+                .exec(SceneCommand::ReleaseGizmo("transform_gizmo"));
+        }
 
-        if resp.dragged_by(egui::PointerButton::Secondary) {
-            let pan = CameraCommand::Pan(resp.drag_delta().x, resp.drag_delta().y);
-            self.executor.exec(pan);
+        if resp.dragged_by(egui::PointerButton::Primary) && self.gizmo_grabbed {
+            if let Some(pos) = resp.interact_pointer_pos() {
+                let from = pos - resp.drag_delta();
+                let drag = SceneCommand::DragGizmo("transform_gizmo", resp.rect, from, pos);
+                self.executor.exec(drag);
+            }
         }
 
-        if resp.hovered() {
-            let zoom = CameraCommand::Zoom(ui.input(|i| -i.raw_scroll_delta.y));
-            self.executor.exec(zoom);
+        let mut frame = InputFrame::from_response(resp);
+        if self.gizmo_grabbed {
+            frame.dragged.retain(|&x| x != egui::PointerButton::Primary);
+        }
+        let handled = self.executor.exec(InputCommand::Handle(frame));
+        for event in handled.as_events().unwrap_or_default() {
+            match event.action {
+                Action::ToggleDebug => self.show_debug = !self.show_debug,
+                Action::Screenshot => {
+                    self.executor
+                        .exec(DrawCommand::Screenshot(screenshot_path(), None));
+                }
+                _ => {}
+            }
         }
     }
 
     fn control(&mut self, ui: &mut egui::Ui) {
         ui.vertical(|ui| {
+            ui.collapsing("Время", |ui| {
+                ui.horizontal(|ui| {
+                    let label = if self.paused {
+                        "Пуск"
+                    } else {
+                        "Пауза"
+                    };
+                    if ui.button(label).clicked() {
+                        self.paused = !self.paused;
+                        let command = if self.paused {
+                            TimeCommand::Pause
+                        } else {
+                            TimeCommand::Play
+                        };
+                        self.executor.exec(command);
+                    }
+                    let resp = ui.add(egui::widgets::Slider::new(&mut self.time_scale, 0.0..=4.0));
+                    ui.label("Скорость");
+                    if resp.changed() {
+                        self.executor
+                            .exec(TimeCommand::SetTimeScale(self.time_scale));
+                    }
+                });
+                let time = self.executor.exec(TimeCommand::Query);
+                ui.label(format!("{:.1} с", time.total));
+            });
+            ui.collapsing("Версии облака", |ui| {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.version_name);
+                    if ui.button("Сохранить версию").clicked() {
+                        let name = std::mem::take(&mut self.version_name);
+                        self.executor.exec(HistoryCommand::Commit("cloud", name));
+                    }
+                });
+                let versions = self.executor.exec(HistoryCommand::Query);
+                for version in versions.as_versions().unwrap_or_default() {
+                    ui.horizontal(|ui| {
+                        ui.label(version.label());
+                        if ui.button("Восстановить").clicked() {
+                            self.executor
+                                .exec(HistoryCommand::Restore("cloud", version.number));
+                            self.cloud = version.params;
+                        }
+                        if ui.button("Сравнить").clicked() {
+                            let diff = HistoryCommand::DiffCurrent(version.number, "cloud");
+                            let diff = self.executor.exec(diff);
+                            self.version_diff = diff.as_diff().unwrap_or_default().to_vec();
+                        }
+                        if ui.button("Удалить").clicked() {
+                            self.executor.exec(HistoryCommand::Remove(version.number));
+                        }
+                    });
+                }
+                egui::Grid::new("version_diff")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for change in &self.version_diff {
+                            ui.label(&change.field);
+                            ui.label(&change.old);
+                            ui.label(&change.new);
+                            ui.end_row();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if ui.button("Сохранить в файл").clicked() {
+                        self.executor
+                            .exec(HistoryCommand::Save(VERSIONS_PATH.into()));
+                    }
+                    if ui.button("Загрузить из файла").clicked() {
+                        self.executor
+                            .exec(HistoryCommand::Load(VERSIONS_PATH.into()));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    if ui.button("Сохранить пресет").clicked() {
+                        self.executor
+                            .exec(SceneCommand::SavePreset("cloud", PRESET_PATH.into()));
+                    }
+                    if ui.button("Загрузить пресет").clicked() {
+                        self.executor
+                            .exec(SceneCommand::LoadPreset("cloud", PRESET_PATH.into()));
+                        if let Some(ComponentSnapshot::Cloud(cloud)) = self
+                            .executor
+                            .exec(SceneCommand::GetObjectSnapshot("cloud"))
+                            .as_snapshot()
+                        {
+                            self.cloud = **cloud;
+                        }
+                    }
+                    if ui.button("WGSL").clicked() {
+                        self.executor.exec(SceneCommand::ExportShader(
+                            "cloud",
+                            ShaderLanguage::Wgsl,
+                            WGSL_PATH.into(),
+                        ));
+                    }
+                    if ui.button("GLSL").clicked() {
+                        self.executor.exec(SceneCommand::ExportShader(
+                            "cloud",
+                            ShaderLanguage::Glsl,
+                            GLSL_PATH.into(),
+                        ));
+                    }
+                    if ui.button("Импорт из Unity").clicked() {
+                        self.executor
+                            .exec(SceneCommand::ImportUnity("cloud", UNITY_PATH.into()));
+                        if let Some(ComponentSnapshot::Cloud(cloud)) = self
+                            .executor
+                            .exec(SceneCommand::GetObjectSnapshot("cloud"))
+                            .as_snapshot()
+                        {
+                            self.cloud = **cloud;
+                        }
+                    }
+                });
+            });
+            ui.collapsing("Анимация", |ui| {
+                let state = self.executor.exec(AnimationCommand::Query);
+                ui.horizontal(|ui| {
+                    if ui.button("Закат").clicked() {
+                        self.author_sunset();
+                    }
+                    if state.playing {
+                        if ui.button("Стоп").clicked() {
+                            self.executor.exec(AnimationCommand::Stop);
+                        }
+                    } else if ui.button("Воспроизвести").clicked() {
+                        self.executor
+                            .exec(AnimationCommand::Play(self.animation_looped));
+                    }
+                    ui.checkbox(&mut self.animation_looped, "Повтор");
+                });
+                let mut time = state.time;
+                let slider = egui::Slider::new(&mut time, 0.0..=state.duration).suffix(" с");
+                if ui.add(slider).changed() {
+                    self.executor.exec(AnimationCommand::Seek(time));
+                }
+                ui.label(format!("Дорожки: {}", state.tracks.join(", ")));
+                let settings = SequenceSettings {
+                    start: 0.0,
+                    end: state.duration,
+                    fps: SEQUENCE_FPS,
+                    size: SEQUENCE_SIZE,
+                    quality: 1.0,
+                };
+                ui.horizontal(|ui| {
+                    if ui.button("Рендер кадров").clicked() {
+                        let command = JobCommand::RenderSequence(settings, SEQUENCE_DIR.into());
+                        self.executor.exec(command);
+                    }
+                    if ui.button("GIF").clicked() {
+                        let command =
+                            JobCommand::RenderClip(settings, ClipFormat::Gif, GIF_PATH.into());
+                        self.executor.exec(command);
+                    }
+                    #[cfg(feature = "mp4")]
+                    if ui.button("MP4").clicked() {
+                        let command =
+                            JobCommand::RenderClip(settings, ClipFormat::Mp4, MP4_PATH.into());
+                        self.executor.exec(command);
+                    }
+                });
+            });
+            ui.collapsing("Задачи", |ui| {
+                if ui.button("Рендер в файл").clicked() {
+                    let command = JobCommand::RenderToFile(RENDER_SIZE, RENDER_PATH.into());
+                    self.executor.exec(command);
+                }
+                if ui.button("HDR в EXR").clicked() {
+                    let command = JobCommand::RenderHdr(RENDER_SIZE, HDR_PATH.into());
+                    self.executor.exec(command);
+                }
+                if ui.button("Пакетный рендер").clicked() {
+                    self.executor
+                        .exec(JobCommand::RenderBatch(BATCH_PATH.into()));
+                }
+                if ui.button("glTF").clicked() {
+                    let command = JobCommand::ExportGltf(RENDER_SIZE, GLTF_PATH.into());
+                    self.executor.exec(command);
+                }
+                if ui.button("Воксели").clicked() {
+                    let command =
+                        JobCommand::ExportVolume("cloud", VOLUME_SIZE, VOLUME_PATH.into());
+                    self.executor.exec(command);
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Порог плотности");
+                    ui.add(egui::Slider::new(&mut self.iso_threshold, 0.0..=5.0));
+                });
+                ui.horizontal(|ui| {
+                    let scene = ui.button("Изоповерхность").clicked();
+                    let obj = ui.button("OBJ").clicked();
+                    let stl = ui.button("STL").clicked();
+                    let target = if scene {
+                        Some(IsosurfaceTarget::Scene(ISOSURFACE_NAME))
+                    } else if obj {
+                        Some(IsosurfaceTarget::Obj(ISOSURFACE_OBJ_PATH.into()))
+                    } else if stl {
+                        Some(IsosurfaceTarget::Stl(ISOSURFACE_STL_PATH.into()))
+                    } else {
+                        None
+                    };
+                    if let Some(target) = target {
+                        self.executor.exec(JobCommand::ExtractIsosurface(
+                            "cloud",
+                            ISOSURFACE_SIZE,
+                            self.iso_threshold,
+                            target,
+                        ));
+                    }
+                    if ui.button("Убрать").clicked() {
+                        self.executor
+                            .exec(SceneCommand::RemoveObject(ISOSURFACE_NAME));
+                    }
+                });
+                let jobs = self.executor.exec(JobCommand::Query);
+                for job in jobs.as_jobs().unwrap_or_default() {
+                    ui.horizontal(|ui| {
+                        match &job.status {
+                            JobStatus::Running(progress) => {
+                                ui.add(egui::ProgressBar::new(*progress).desired_width(80.0));
+                            }
+                            status => {
+                                ui.label(format!("{status:?}"));
+                            }
+                        }
+                        ui.label(&job.name);
+                        if ui.button("Отмена").clicked() {
+                            self.executor.exec(JobCommand::Cancel(job.id));
+                        }
+                    });
+                }
+            });
+            ui.collapsing("Диагностика", |ui| {
+                let diagnostics = self
+                    .executor
+                    .exec(DiagnosticsCommand::Query(Severity::Info));
+                for diagnostic in diagnostics.iter().rev() {
+                    let color = match diagnostic.severity {
+                        Severity::Info => ui.visuals().text_color(),
+                        Severity::Warning => Color32::from_rgb(230, 150, 0),
+                        Severity::Error => Color32::RED,
+                    };
+                    let mut text = diagnostic.message.clone();
+                    if let Some(source) = diagnostic.source {
+                        text = format!("{source}: {text}");
+                    }
+                    if diagnostic.count > 1 {
+                        text = format!("{text} (×{})", diagnostic.count);
+                    }
+                    ui.colored_label(color, text);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Проверить сцену").clicked() {
+                        self.executor.exec(DiagnosticsCommand::ValidateScene);
+                    }
+                    if ui.button("Очистить").clicked() {
+                        self.executor.exec(DiagnosticsCommand::Clear);
+                    }
+                });
+            });
+            ui.collapsing("Скрипты", |ui| {
+                let scripts = self.executor.exec(ScriptCommand::Query);
+                for script in scripts.as_scripts().unwrap_or_default() {
+                    ui.horizontal(|ui| {
+                        if ui.button("Запустить").clicked() {
+                            self.executor.exec(ScriptCommand::Run(script.path.clone()));
+                        }
+                        ui.label(format!("{} ({:?})", script.path.display(), script.schedule));
+                        ui.label(format!("запусков: {}", script.runs));
+                    });
+                    if let Some(err) = &script.error {
+                        ui.colored_label(Color32::RED, err);
+                    }
+                }
+                if ui.button("Обновить").clicked() {
+                    self.executor
+                        .exec(ScriptCommand::Discover(SCRIPTS_DIR.into()));
+                }
+            });
+            ui.collapsing("Проходы рендеринга", |ui| {
+                let passes = self.executor.exec(DrawCommand::QueryPasses);
+                for state in passes.as_passes().unwrap_or_default() {
+                    let mut enabled = state.enabled;
+                    let time = state.time.as_secs_f32() * 1000.0;
+                    let label = format!("{} ({time:.1} мс)", state.pass.name());
+                    if ui.checkbox(&mut enabled, label).changed() {
+                        self.executor
+                            .exec(DrawCommand::SetPassEnabled(state.pass, enabled));
+                    }
+                }
+            });
+            ui.collapsing("Профилирование", |ui| {
+                if ui.checkbox(&mut self.profiling, "Замер этапов").changed() {
+                    self.executor
+                        .exec(ProfilingCommand::SetEnabled(self.profiling));
+                }
+                let averages = self.executor.exec(ProfilingCommand::QueryAverages);
+                if let Some(profile) = averages.as_profile() {
+                    for stage in Stage::ALL {
+                        let time = profile.get(stage).as_secs_f32() * 1000.0;
+                        ui.label(format!("{}: {time:.2} мс", stage.name()));
+                    }
+                }
+                let cache = self.executor.exec(CacheCommand::Query);
+                ui.label(format!(
+                    "Кэш: {} записей, попаданий {}, промахов {}",
+                    cache.entries, cache.hits, cache.misses
+                ));
+                let stats = self
+                    .executor
+                    .exec(ProfilingCommand::QuerySceneStats(CANVAS_SIZE));
+                if let Some(stats) = stats.as_scene_stats() {
+                    ui.label(format!(
+                        "Примитивов: {}, выборок плотности: {:.1} млн, память: {:.1} МБ",
+                        stats.primitives(),
+                        stats.march_cost() / 1e6,
+                        stats.memory() as f32 / (1024.0 * 1024.0)
+                    ));
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Сбросить").clicked() {
+                        self.executor.exec(ProfilingCommand::Clear);
+                        self.executor.exec(CacheCommand::Clear);
+                    }
+                    if ui.button("Сохранить CSV").clicked() {
+                        self.executor
+                            .exec(ProfilingCommand::SaveCsv(PROFILE_PATH.into()));
+                    }
+                });
+            });
+            ui.collapsing("Объекты", |ui| {
+                let scene = self.executor.exec(SceneCommand::QueryScene);
+                if let Some(objects) = scene.as_scene() {
+                    object_tree(ui, &mut self.executor, objects, None);
+                }
+                let selected = if self.selected.is_empty() {
+                    "-".to_owned()
+                } else {
+                    self.selected.join(", ")
+                };
+                ui.label(format!("Выбрано: {selected}"));
+                if ui
+                    .checkbox(&mut self.show_debug, "Отладочный слой")
+                    .changed()
+                {
+                    self.executor
+                        .exec(DrawCommand::SetLayerVisible("debug", self.show_debug));
+                }
+                ui.horizontal(|ui| {
+                    let mut mode = self.gizmo_mode;
+                    ui.radio_value(&mut mode, GizmoMode::Translate, "Перемещение");
+                    ui.radio_value(&mut mode, GizmoMode::Rotate, "Вращение");
+                    if mode != self.gizmo_mode {
+                        self.gizmo_mode = mode;
+                        self.executor
+                            .exec(SceneCommand::SetGizmoMode("transform_gizmo", mode));
+                    }
+                });
+            });
+            ui.collapsing("Настройки", |ui| {
+                ui.checkbox(&mut self.dark_mode, "Тёмная тема");
+                if ui
+                    .checkbox(&mut self.lod_enabled, "Упрощать дальние объекты")
+                    .changed()
+                {
+                    self.executor
+                        .exec(DrawCommand::SetLodEnabled(self.lod_enabled));
+                }
+                if ui
+                    .checkbox(&mut self.async_clouds, "Облака в фоновом потоке")
+                    .changed()
+                {
+                    self.executor
+                        .exec(DrawCommand::SetAsyncClouds(self.async_clouds));
+                }
+                #[cfg(feature = "gpu")]
+                if ui
+                    .checkbox(&mut self.gpu_clouds, "Облака на видеокарте")
+                    .changed()
+                {
+                    self.executor
+                        .exec(DrawCommand::SetGpuClouds(self.gpu_clouds));
+                }
+                if ui
+                    .checkbox(&mut self.partial_redraw, "Перерисовывать только освещение")
+                    .changed()
+                {
+                    self.executor
+                        .exec(DrawCommand::SetPartialRedraw(self.partial_redraw));
+                }
+                ui.horizontal(|ui| {
+                    let adaptive = ui
+                        .checkbox(&mut self.adaptive_quality, "Держать частоту кадров")
+                        .changed();
+                    let fps = ui
+                        .add_enabled(
+                            self.adaptive_quality,
+                            egui::DragValue::new(&mut self.target_fps)
+                                .range(10.0..=120.0)
+                                .suffix(" кадр/с"),
+                        )
+                        .changed();
+                    if adaptive || fps {
+                        let target = self.adaptive_quality.then_some(self.target_fps);
+                        self.executor.exec(DrawCommand::SetTargetFps(target));
+                    }
+                });
+                if ui
+                    .checkbox(&mut self.coarse_density, "Грубая плотность при движении")
+                    .changed()
+                {
+                    self.executor
+                        .exec(DrawCommand::SetCoarseDensity(self.coarse_density));
+                }
+                let control = &mut self.camera_control;
+                let mut changed = false;
+                for (value, label) in [
+                    (&mut control.swivel_sensitivity, "Вращение камеры"),
+                    (&mut control.pan_sensitivity, "Сдвиг камеры"),
+                    (&mut control.zoom_sensitivity, "Приближение камеры"),
+                ] {
+                    changed |= ui
+                        .add(
+                            egui::DragValue::new(value)
+                                .speed(1e-4)
+                                .prefix(label)
+                                .range(0.0..=1.0),
+                        )
+                        .changed();
+                }
+                if changed {
+                    self.store_settings();
+                }
+                if ui.button("Сохранить настройки").clicked() {
+                    self.store_settings();
+                    // The error is logged by the command
+                    self.executor.exec(SettingsCommand::Save);
+                }
+            });
+            ui.collapsing("Сцена", |ui| {
+                ui.text_edit_singleline(&mut self.scene_path);
+                ui.horizontal(|ui| {
+                    let path = || self.scene_path.clone().into();
+                    if ui.button("Сохранить").clicked() {
+                        self.executor.exec(SceneCommand::SaveScene(path()));
+                    }
+                    if ui.button("Загрузить").clicked() {
+                        self.executor.exec(SceneCommand::LoadScene(path()));
+                    }
+                    if ui.button("Очистить").clicked() {
+                        self.executor.exec(SceneCommand::ClearScene);
+                    }
+                    if ui.button("Показать всё").clicked() {
+                        self.executor.exec(CameraCommand::FrameAll);
+                    }
+                    if ui.button("Экспорт SVG").clicked() {
+                        let size = CANVAS_SIZE.map(|x| x as usize);
+                        self.executor
+                            .exec(DrawCommand::ExportSvg(SVG_PATH.into(), size));
+                    }
+                    if ui.button("Снимок PNG").clicked() {
+                        self.executor
+                            .exec(DrawCommand::Screenshot(screenshot_path(), None));
+                    }
+                    if ui.button("В буфер обмена").clicked() {
+                        self.executor.exec(DrawCommand::CopyToClipboard(None));
+                    }
+                });
+            });
+            ui.collapsing("Виды камеры", |ui| {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.view_name);
+                    if ui.button("Сохранить вид").clicked() && !self.view_name.is_empty()
+                    {
+                        let name = std::mem::take(&mut self.view_name);
+                        self.executor.exec(CameraCommand::SavePreset(name.clone()));
+                        self.view_thumbnails.retain(|(x, _)| *x != name);
+                        if let Some(texture) =
+                            self.render_view(ui.ctx(), View::Preset(name.clone()))
+                        {
+                            self.view_thumbnails.push((name, texture));
+                        }
+                    }
+                });
+                let mut removed = None;
+                for (name, texture) in &self.view_thumbnails {
+                    ui.horizontal(|ui| {
+                        ui.image((texture.id(), texture.size_vec2()));
+                        ui.vertical(|ui| {
+                            ui.label(name);
+                            if ui.button("Применить").clicked() {
+                                self.executor.exec(CameraCommand::ApplyPreset(name.clone()));
+                            }
+                            if ui.button("Удалить").clicked() {
+                                removed = Some(name.clone());
+                            }
+                        });
+                    });
+                }
+                if let Some(name) = removed {
+                    self.executor
+                        .exec(CameraCommand::RemovePreset(name.clone()));
+                    self.view_thumbnails.retain(|(x, _)| *x != name);
+                }
+                ui.horizontal(|ui| {
+                    if ui.button("Вид сверху").clicked() {
+                        self.aux_view = self.render_view(ui.ctx(), View::TopDown);
+                    }
+                    if ui.button("Вид от солнца").clicked() {
+                        self.aux_view = self.render_view(ui.ctx(), View::Sun);
+                    }
+                });
+                if let Some(texture) = &self.aux_view {
+                    ui.image((texture.id(), texture.size_vec2()));
+                }
+            });
             ui.collapsing("Параметры облаков", |ui| {
                 ui.vertical(|ui| {
                     ui.vertical(|ui| {
@@ -103,28 +884,59 @@ impl App {
                                     .exec(SceneCommand::MoveBoundingBox("cloud", self.move_vector));
                             }
                         });
+                        let bounds = &mut self.cloud.bounding_box;
+                        let mut changed = false;
+                        for (corner, label) in [
+                            (&mut bounds.min, "Нижний угол облака"),
+                            (&mut bounds.max, "Верхний угол облака"),
+                        ] {
+                            ui.horizontal(|ui| {
+                                for v in [&mut corner.x, &mut corner.y, &mut corner.z] {
+                                    changed |=
+                                        ui.add(egui::DragValue::new(v).speed(0.05)).changed();
+                                }
+                                ui.label(label);
+                            });
+                        }
+                        if changed {
+                            self.executor.exec(SceneCommand::SetCloudBounds(
+                                "cloud", bounds.min, bounds.max,
+                            ));
+                        }
                         ui.separator();
 
                         ui.horizontal(|ui| {
-                            ui.add(egui::widgets::Slider::new(
-                                &mut self.offset_speed.x,
-                                -1.0..=1.0,
+                            let resp = ui.add(egui::widgets::Slider::new(
+                                &mut self.cloud.wind.x,
+                                -60.0..=60.0,
                             ));
                             ui.label("Ветер x");
+                            if resp.changed() {
+                                self.executor
+                                    .exec(SceneCommand::SetWind("cloud", self.cloud.wind));
+                            }
                         });
                         ui.horizontal(|ui| {
-                            ui.add(egui::widgets::Slider::new(
-                                &mut self.offset_speed.y,
-                                -1.0..=1.0,
+                            let resp = ui.add(egui::widgets::Slider::new(
+                                &mut self.cloud.wind.y,
+                                -60.0..=60.0,
                             ));
                             ui.label("Ветер y");
+                            if resp.changed() {
+                                self.executor
+                                    .exec(SceneCommand::SetWind("cloud", self.cloud.wind));
+                            }
                         });
                         ui.horizontal(|ui| {
-                            ui.add(egui::widgets::Slider::new(
-                                &mut self.offset_speed.z,
-                                -1.0..=1.0,
+                            let resp = ui.add(egui::widgets::Slider::new(
+                                &mut self.cloud.wind.z,
+                                -60.0..=60.0,
                             ));
                             ui.label("Ветер z");
+                            if resp.changed() {
+                                self.executor
+                                    .exec(SceneCommand::SetWind("cloud", self.cloud.wind));
+                            }
                         });
                         ui.separator();
                         ui.horizontal(|ui| {
@@ -154,6 +966,24 @@ impl App {
                                 ));
                             }
                         });
+                        ui.horizontal(|ui| {
+                            let resp = ui.add(egui::widgets::Slider::new(
+                                &mut self.cloud.light_interval,
+                                1..=8,
+                            ));
+                            ui.label("Шагов между выборками света");
+                            if resp.changed() {
+                                self.executor.exec(SceneCommand::SetLightInterval(
+                                    "cloud",
+                                    self.cloud.light_interval,
+                                ));
+                            }
+                        });
+                        let resp = ui.checkbox(&mut self.cloud.auto_steps, "Шаги по длине луча");
+                        if resp.changed() {
+                            self.executor
+                                .exec(SceneCommand::SetAutoSteps("cloud", self.cloud.auto_steps));
+                        }
                         ui.separator();
                         ui.horizontal(|ui| {
                             let resp = ui.add(
@@ -461,8 +1291,8 @@ impl App {
 
                         if ui.button("Сгенерировать").clicked() {
                             let command = match self.noise_mode {
-                                NoiseMode::Shape => SceneCommand::SetNoise,
-                                NoiseMode::Detail => SceneCommand::SetDetailNoise,
+                                NoiseMode::Shape => JobCommand::RegenerateNoise,
+                                NoiseMode::Detail => JobCommand::RegenerateDetailNoise,
                             };
                             self.executor
                                 .exec(command("cloud", (*worley_builder).into()));
@@ -495,9 +1325,339 @@ impl App {
                                 ));
                             }
                         });
+                        ui.horizontal(|ui| {
+                            let resp = ui.add(egui::widgets::Slider::new(
+                                &mut self.day_length,
+                                0.0..=600.0,
+                            ));
+                            ui.label("Длина суток, с");
+                            if resp.changed() {
+                                self.executor
+                                    .exec(SceneCommand::SetDayLength("sun", self.day_length));
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let resp = ui.add(egui::widgets::Slider::new(
+                                &mut self.sun_temperature,
+                                1500.0..=15000.0,
+                            ));
+                            ui.label("Цветовая температура, К");
+                            if resp.changed() {
+                                self.executor.exec(SceneCommand::SetSunColor(
+                                    "sun",
+                                    Sun::temperature_color(self.sun_temperature),
+                                ));
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let resp = ui.add(egui::widgets::Slider::new(
+                                &mut self.sun_intensity,
+                                0.0..=3.0,
+                            ));
+                            ui.label("Интенсивность");
+                            if resp.changed() {
+                                self.executor
+                                    .exec(SceneCommand::SetSunIntensity("sun", self.sun_intensity));
+                            }
+                        });
                     })
                 });
             });
+            ui.collapsing("Фон", |ui| {
+                let before = self.background;
+                ui.horizontal(|ui| {
+                    let background = &mut self.background;
+                    if ui.radio(*background == Background::Sky, "Небо").clicked() {
+                        *background = Background::Sky;
+                    }
+                    let solid = matches!(background, Background::Solid(_));
+                    if ui.radio(solid, "Цвет").clicked() && !solid {
+                        *background = Background::Solid(self.background_color);
+                    }
+                    let gradient = matches!(background, Background::Gradient { .. });
+                    if ui.radio(gradient, "Градиент").clicked() && !gradient {
+                        *background = Background::Gradient {
+                            top: self.background_color,
+                            bottom: Color32::WHITE,
+                        };
+                    }
+                });
+                match &mut self.background {
+                    Background::Solid(color) => {
+                        ui.color_edit_button_srgba(color);
+                    }
+                    Background::Gradient { top, bottom } => {
+                        ui.horizontal(|ui| {
+                            ui.color_edit_button_srgba(top);
+                            ui.label("Верх");
+                        });
+                        ui.horizontal(|ui| {
+                            ui.color_edit_button_srgba(bottom);
+                            ui.label("Низ");
+                        });
+                    }
+                    Background::Sky => {}
+                }
+                if self.background != before {
+                    self.executor
+                        .exec(SceneCommand::SetBackground("background", self.background));
+                }
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Купол").clicked() {
+                        self.executor
+                            .exec(SceneCommand::AddObject("skybox", Skybox::default().into()));
+                    }
+                    ui.text_edit_singleline(&mut self.skybox_path);
+                    if ui.button("Панорама").clicked() {
+                        let path = self.skybox_path.clone().into();
+                        self.executor
+                            .exec(SceneCommand::AddSkyboxFromFile("skybox", path));
+                    }
+                });
+            });
+            ui.collapsing("Модель", |ui| {
+                ui.horizontal(|ui| {
+                    ui.text_edit_singleline(&mut self.model_path);
+                    if ui.button("Загрузить OBJ").clicked() {
+                        self.executor.exec(SceneCommand::AddObjectFromFile(
+                            "model",
+                            self.model_path.clone().into(),
+                        ));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let mut primitive = None;
+                    if ui.button("Сфера").clicked() {
+                        primitive = Some(Mesh::sphere(0.3, 24));
+                    }
+                    if ui.button("Куб").clicked() {
+                        primitive = Some(Mesh::cuboid(Vec3::splat(0.5)));
+                    }
+                    if ui.button("Конус").clicked() {
+                        primitive = Some(Mesh::cone(0.3, 0.6, 24));
+                    }
+                    if ui.button("Цилиндр").clicked() {
+                        primitive = Some(Mesh::cylinder(0.3, 0.6, 24));
+                    }
+                    if let Some(mesh) = primitive {
+                        let scene = self.executor.exec(SceneCommand::QueryScene);
+                        let taken = |name: &str| {
+                            scene
+                                .as_scene()
+                                .is_some_and(|x| x.iter().any(|x| x.name == name))
+                        };
+                        let name = loop {
+                            self.primitive_count += 1;
+                            let name = format!("model {}", self.primitive_count);
+                            if !taken(&name) {
+                                break name;
+                            }
+                        };
+                        // Object ids are static, every primitive leaks its own
+                        let id: &'static str = Box::leak(name.into_boxed_str());
+                        self.executor.exec(SceneCommand::AddObject(id, mesh.into()));
+                        self.primitives.push(id);
+                    }
+                    let remove = egui::Button::new("Удалить");
+                    if ui
+                        .add_enabled(!self.primitives.is_empty(), remove)
+                        .clicked()
+                    {
+                        if let Some(id) = self.primitives.pop() {
+                            self.executor.exec(SceneCommand::RemoveObject(id));
+                        }
+                    }
+                });
+            });
+            ui.collapsing("Дополнительный свет", |ui| {
+                let mut changed = false;
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .color_edit_button_srgba(&mut self.fill_light.color)
+                        .changed();
+                    ui.label("Цвет");
+                });
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .add(egui::widgets::Slider::new(
+                            &mut self.fill_light.intensity,
+                            0.0..=10.0,
+                        ))
+                        .changed();
+                    ui.label("Интенсивность");
+                });
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .add(egui::widgets::Slider::new(
+                            &mut self.fill_light.absorption,
+                            0.0..=2.0,
+                        ))
+                        .changed();
+                    ui.label("Поглощение");
+                });
+                if let LightKind::Point(position) = &mut self.fill_light.kind {
+                    ui.horizontal(|ui| {
+                        for v in [&mut position.x, &mut position.y, &mut position.z] {
+                            changed |= ui.add(egui::DragValue::new(v).speed(0.05)).changed();
+                        }
+                        ui.label("Положение");
+                    });
+                }
+                if changed {
+                    self.executor
+                        .exec(SceneCommand::SetLight("fill_light", self.fill_light));
+                }
+            });
+            ui.collapsing("Сетка", |ui| {
+                ui.horizontal(|ui| {
+                    let resp = ui.add(egui::widgets::Slider::new(&mut self.grid.k, 1..=20));
+                    ui.label("Делений");
+                    if resp.changed() {
+                        self.executor
+                            .exec(SceneCommand::SetGridK("grid", self.grid.k));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let resp = ui.add(egui::widgets::Slider::new(&mut self.grid.scale, 0.1..=10.0));
+                    ui.label("Масштаб");
+                    if resp.changed() {
+                        self.executor
+                            .exec(SceneCommand::SetGridScale("grid", self.grid.scale));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let mut plane = self.grid.plane;
+                    ui.radio_value(&mut plane, GridPlane::XZ, "XZ");
+                    ui.radio_value(&mut plane, GridPlane::XY, "XY");
+                    ui.radio_value(&mut plane, GridPlane::YZ, "YZ");
+                    ui.label("Плоскость");
+                    if plane != self.grid.plane {
+                        self.grid.plane = plane;
+                        self.executor
+                            .exec(SceneCommand::SetGridPlane("grid", plane));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let mut changed = ui
+                        .color_edit_button_srgba(&mut self.grid.major_color)
+                        .changed();
+                    changed |= ui
+                        .color_edit_button_srgba(&mut self.grid.minor_color)
+                        .changed();
+                    ui.label("Цвет линий");
+                    if changed {
+                        self.executor.exec(SceneCommand::SetGridColors(
+                            "grid",
+                            self.grid.major_color,
+                            self.grid.minor_color,
+                        ));
+                    }
+                });
+            });
+            ui.collapsing("Туман", |ui| {
+                let mut changed = false;
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .add(egui::widgets::Slider::new(&mut self.fog.density, 0.0..=0.5))
+                        .changed();
+                    ui.label("Плотность");
+                });
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .add(egui::widgets::Slider::new(
+                            &mut self.fog.falloff,
+                            0.01..=5.0,
+                        ))
+                        .changed();
+                    ui.label("Спад с высотой");
+                });
+                ui.horizontal(|ui| {
+                    changed |= ui
+                        .add(egui::widgets::Slider::new(&mut self.fog.height, -2.0..=5.0))
+                        .changed();
+                    ui.label("Высота");
+                });
+                ui.horizontal(|ui| {
+                    changed |= ui.color_edit_button_srgba(&mut self.fog.color).changed();
+                    ui.label("Цвет тумана");
+                });
+                if changed {
+                    self.executor.exec(SceneCommand::SetFog("fog", self.fog));
+                }
+            });
+            ui.collapsing("Параметры воды", |ui| {
+                ui.horizontal(|ui| {
+                    let resp = ui.color_edit_button_srgba(&mut self.water.color);
+                    ui.label("Цвет воды");
+                    if resp.changed() {
+                        self.executor
+                            .exec(SceneCommand::SetWaterColor("water", self.water.color));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let mut level = self.water.level();
+                    let resp = ui.add(egui::widgets::Slider::new(&mut level, -1.0..=1.0));
+                    ui.label("Уровень");
+                    if resp.changed() {
+                        self.water.set_level(level);
+                        self.executor
+                            .exec(SceneCommand::SetWaterLevel("water", level));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let resp = ui.add(egui::widgets::Slider::new(
+                        &mut self.water.reflectivity,
+                        0.0..=1.0,
+                    ));
+                    ui.label("Отражение");
+                    if resp.changed() {
+                        self.executor.exec(SceneCommand::SetWaterReflectivity(
+                            "water",
+                            self.water.reflectivity,
+                        ));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let resp = ui.add(egui::widgets::Slider::new(
+                        &mut self.water.wave_amplitude,
+                        0.0..=0.3,
+                    ));
+                    ui.label("Высота волн");
+                    if resp.changed() {
+                        self.executor.exec(SceneCommand::SetWaterWaveAmplitude(
+                            "water",
+                            self.water.wave_amplitude,
+                        ));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let resp = ui.add(egui::widgets::Slider::new(
+                        &mut self.water.wave_scale,
+                        0.5..=20.0,
+                    ));
+                    ui.label("Частота волн");
+                    if resp.changed() {
+                        self.executor.exec(SceneCommand::SetWaterWaveScale(
+                            "water",
+                            self.water.wave_scale,
+                        ));
+                    }
+                });
+                ui.horizontal(|ui| {
+                    let resp = ui.add(egui::widgets::Slider::new(
+                        &mut self.water.wave_speed,
+                        0.0..=5.0,
+                    ));
+                    ui.label("Скорость волн");
+                    if resp.changed() {
+                        self.executor.exec(SceneCommand::SetWaterWaveSpeed(
+                            "water",
+                            self.water.wave_speed,
+                        ));
+                    }
+                });
+            });
             ui.collapsing("Параметры ландшафта", |ui| {
                 ui.vertical(|ui| {
                     ui.vertical(|ui| {
@@ -512,6 +1672,35 @@ impl App {
                                 ));
                             }
                         });
+                        ui.horizontal(|ui| {
+                            let bb = &mut self.terrain.bounding_box;
+                            let mut height = bb.max.y - bb.min.y;
+                            let resp = ui.add(egui::widgets::Slider::new(&mut height, 0.0..=2.0));
+                            ui.label("Высота");
+                            if resp.changed() {
+                                bb.max.y = bb.min.y + height;
+                                self.executor
+                                    .exec(SceneCommand::SetTerrainHeight("terrain", height));
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut self.height_map_path);
+                            if ui.button("Карта высот").clicked() {
+                                match HeightMap::from_file(&self.height_map_path) {
+                                    Ok(map) => {
+                                        self.executor.exec(SceneCommand::SetTerrainHeightMap(
+                                            "terrain",
+                                            Some(map),
+                                        ));
+                                    }
+                                    Err(err) => log::error!("{}: {err}", self.height_map_path),
+                                }
+                            }
+                            if ui.button("Шум").clicked() {
+                                self.executor
+                                    .exec(SceneCommand::SetTerrainHeightMap("terrain", None));
+                            }
+                        });
                         ui.separator();
                         ui.horizontal(|ui| {
                             let resp = ui.add(egui::widgets::Slider::new(
@@ -691,9 +1880,55 @@ struct App {
     cloud: CloudBuilder,
     terrain: TerrainBuilder,
     sun: (f32, f32, f32),
+    sun_temperature: f32,
+    sun_intensity: f32,
     background_color: Color32,
-    offset_speed: Vec3,
+    background: Background,
+    height_map_path: String,
+    skybox_path: String,
+    model_path: String,
+    scene_path: String,
+    selected: Vec<&'static str>,
+    gizmo_mode: GizmoMode,
+    gizmo_grabbed: bool,
+    show_debug: bool,
+    dark_mode: bool,
+    lod_enabled: bool,
+    async_clouds: bool,
+    #[cfg(feature = "gpu")]
+    gpu_clouds: bool,
+    partial_redraw: bool,
+    adaptive_quality: bool,
+    target_fps: f32,
+    coarse_density: bool,
+    camera_control: ArcBallController,
+    fill_light: Light,
+    water: Water,
+    grid: Grid,
+    fog: Fog,
+    day_length: f32,
+    paused: bool,
+    profiling: bool,
+    animation_looped: bool,
+    time_scale: f32,
     move_vector: Vec3,
+    version_name: String,
+    /// Changes from the compared version to the current cloud
+    version_diff: Vec<ParamDiff>,
+    job_events: Subscription<JobFinished>,
+    view_name: String,
+    /// Thumbnails of the saved cameras by name
+    view_thumbnails: Vec<(String, egui::TextureHandle)>,
+    /// Last rendered top-down or sun view
+    aux_view: Option<egui::TextureHandle>,
+    /// Message shown on the canvas until the time
+    status: Option<(String, f64)>,
+    /// Density the isosurface of the cloud is extracted at
+    iso_threshold: f32,
+    /// Ids of the added primitives, the last one is removed first
+    primitives: Vec<&'static str>,
+    /// Last number given to a primitive id
+    primitive_count: usize,
 }
 
 impl App {
@@ -750,6 +1985,7 @@ impl App {
             .with_volume_offset(0.0)
             .with_height_map_factor(2.0)
             .with_clouds_offset(Vec3::new(0.0, 0.0, 0.0))
+            .with_wind(Vec3::new(60.0, 0.0, 60.0))
             .with_weather_noise(
                 PerlinBuilder::new()
                     .with_num_points_a(1)
@@ -763,8 +1999,17 @@ impl App {
             );
 
         let mut executor = Facade::default();
+        let settings = executor
+            .exec(SettingsCommand::Load(None))
+            .into_settings()
+            .unwrap_or_default();
+        let cloud_params = cloud_params
+            .with_num_steps(settings.quality.num_steps)
+            .with_num_steps_light(settings.quality.num_steps_light)
+            .with_light_interval(settings.quality.light_interval);
 
-        let sun = Sun::new(10.0, -90.0, -90.0);
+        let sun_temperature = 6500.0;
+        let sun = Sun::new(10.0, -90.0, -90.0).with_temperature(sun_temperature);
 
         let terrain_params = TerrainBuilder::default()
             .with_bounding_box((Vec3::new(-2.5, 0.0, -2.5), Vec3::new(2.5, 0.5, 2.5)))
@@ -787,17 +2032,54 @@ impl App {
                     .with_persistence(1.3)
                     .with_invert_noise(true),
             );
-        executor.exec(SceneCommand::AddObject("grid", Grid::new(10, 1.0).into()));
+        let fog = Fog::new(0.0, 1.0, Color32::from_rgb(200, 210, 225));
+        executor.exec(SceneCommand::AddObject("fog", fog.into()));
+        let grid = Grid::new(10, 1.0);
+        executor.exec(SceneCommand::AddObject("grid", grid.clone().into()));
         executor.exec(CameraCommand::SetCamera(Camera::default()));
         executor.exec(SceneCommand::AddObject("sun", sun.into()));
-        executor.exec(SceneCommand::AddObject(
-            "cloud",
-            cloud_params.build().into(),
-        ));
+        let fill_light = Light::point(Vec3::new(0.0, 1.0, 0.0))
+            .with_color(Color32::from_rgb(255, 170, 90))
+            .with_intensity(0.0);
+        executor.exec(SceneCommand::AddObject("fill_light", fill_light.into()));
+        executor.exec(SceneCommand::AddCloud("cloud", Box::new(cloud_params)));
         executor.exec(SceneCommand::AddObject(
             "terrain",
             terrain_params.build().into(),
         ));
+        let water = Water::new((Vec3::new(-6.0, -0.1, -6.0), Vec3::new(6.0, 0.1, 6.0)));
+        executor.exec(SceneCommand::AddObject("water", water.clone().into()));
+        executor.exec(SceneCommand::AddObject(
+            "background",
+            Background::default().into(),
+        ));
+        executor.exec(SceneCommand::AddObject(
+            "gizmo",
+            OrientationGizmo::default().into(),
+        ));
+        executor.exec(SceneCommand::AddObject(
+            "transform_gizmo",
+            TransformGizmo::default().into(),
+        ));
+        executor.exec(DrawCommand::SetLayerVisible(
+            "debug",
+            settings.ui.show_debug,
+        ));
+        let quality = settings.quality;
+        executor.exec(DrawCommand::SetTargetFps(
+            quality.adaptive.then_some(quality.target_fps),
+        ));
+        executor.exec(DrawCommand::SetCoarseDensity(quality.coarse_density));
+        if std::path::Path::new(BINDINGS_PATH).exists() {
+            executor.exec(InputCommand::LoadConfig(BINDINGS_PATH.into()));
+        }
+        if std::path::Path::new(SCRIPTS_DIR).is_dir() {
+            executor.exec(ScriptCommand::Discover(SCRIPTS_DIR.into()));
+        }
+        let job_events = executor
+            .exec(EventCommand::Subscribe)
+            .as_subscription()
+            .unwrap();
 
         Self {
             executor,
@@ -805,9 +2087,50 @@ impl App {
             cloud: cloud_params,
             terrain: terrain_params,
             background_color: Color32::LIGHT_BLUE,
-            offset_speed: Vec3::new(1.0, 0.0, 1.0),
+            background: Background::default(),
+            height_map_path: String::new(),
+            skybox_path: String::new(),
+            model_path: String::new(),
+            scene_path: settings
+                .last_scene
+                .map_or("scene.ron".to_owned(), |x| x.display().to_string()),
+            selected: Vec::new(),
+            gizmo_mode: GizmoMode::default(),
+            gizmo_grabbed: false,
+            show_debug: settings.ui.show_debug,
+            dark_mode: settings.ui.dark_mode,
+            lod_enabled: false,
+            async_clouds: false,
+            #[cfg(feature = "gpu")]
+            gpu_clouds: false,
+            partial_redraw: false,
+            adaptive_quality: settings.quality.adaptive,
+            target_fps: settings.quality.target_fps,
+            coarse_density: settings.quality.coarse_density,
+            camera_control: settings.camera,
+            fill_light,
+            water,
+            grid,
+            fog,
+            day_length: sun.day_length,
+            paused: false,
+            profiling: false,
+            animation_looped: false,
+            time_scale: 1.0,
             sun: (sun.d, sun.a.abs(), sun.z.abs()),
+            sun_temperature,
+            sun_intensity: sun.intensity,
             move_vector: Vec3::ZERO,
+            version_name: String::new(),
+            version_diff: Vec::new(),
+            job_events,
+            view_name: String::new(),
+            view_thumbnails: Vec::new(),
+            aux_view: None,
+            status: None,
+            iso_threshold: 0.5,
+            primitives: vec![],
+            primitive_count: 0,
         }
     }
 }
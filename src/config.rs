@@ -2,7 +2,6 @@ use log::debug;
 use rayon::{ThreadPoolBuilder, ThreadPoolBuildError};
 
 #[allow(clippy::pedantic)]
-
 pub fn init_logger() -> Result<(), log::SetLoggerError> {
     if std::env::var_os("RUST_LOG").is_none() {
         env_logger::builder()